@@ -0,0 +1,72 @@
+use std::{hint::black_box, str::FromStr};
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use pngme::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    fixtures::{make_large, make_many_chunks},
+    observer::NoopObserver,
+    png::{ParseOptions, Png},
+};
+
+const LARGE_SIZE: usize = 100 << 20;
+const MANY_CHUNKS: usize = 100_000;
+const CRC_SIZE: usize = 16 << 20;
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+
+    let large = make_large(LARGE_SIZE, 4).as_bytes();
+    group.throughput(Throughput::Bytes(large.len() as u64));
+    group.bench_function("100 MB in 4 IDATs", |b| {
+        b.iter(|| Png::parse(black_box(&large), &ParseOptions::default(), &NoopObserver).unwrap())
+    });
+
+    let many = make_many_chunks(MANY_CHUNKS).as_bytes();
+    group.throughput(Throughput::Elements(MANY_CHUNKS as u64));
+    group.bench_function("100k chunks", |b| {
+        b.iter(|| Png::parse(black_box(&many), &ParseOptions::default(), &NoopObserver).unwrap())
+    });
+
+    group.finish();
+}
+
+fn serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+    group.sample_size(10);
+
+    let png = make_large(LARGE_SIZE, 4);
+    group.throughput(Throughput::Bytes(LARGE_SIZE as u64));
+    group.bench_function("as_bytes", |b| b.iter(|| black_box(&png).as_bytes()));
+    let mut buffer = Vec::with_capacity(LARGE_SIZE + 1024);
+    group.bench_function("write_to", |b| {
+        b.iter(|| {
+            buffer.clear();
+            black_box(&png).write_to(&mut buffer).unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+fn crc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc");
+    group.sample_size(10);
+
+    let chunk_type = ChunkType::from_str("IDAT").unwrap();
+    let data = vec![0xA5; CRC_SIZE];
+    group.throughput(Throughput::Bytes(CRC_SIZE as u64));
+    group.bench_function("Chunk::new 16 MB", |b| {
+        b.iter_batched(
+            || data.clone(),
+            |data| Chunk::new(chunk_type, data),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, parse, serialize, crc);
+criterion_main!(benches);
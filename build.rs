@@ -0,0 +1,49 @@
+//! Records how pngme was built, for `pngme version` and `capabilities`.
+//!
+//! Each value is passed to the compiler as a `PNGME_*` environment variable
+//! read with `env!` in `src/build_info.rs`. The git commit is empty when the
+//! sources aren't a git checkout, e.g. a crate downloaded from a registry.
+
+use std::{env, path::PathBuf, process::Command};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let target = env::var("TARGET").unwrap_or_default();
+    let profile = env::var("PROFILE").unwrap_or_default();
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = output(Command::new(rustc).arg("--version")).unwrap_or_default();
+    let commit = output(Command::new("git").args(["rev-parse", "HEAD"])).unwrap_or_default();
+
+    println!("cargo:rustc-env=PNGME_TARGET={target}");
+    println!("cargo:rustc-env=PNGME_PROFILE={profile}");
+    println!("cargo:rustc-env=PNGME_RUSTC_VERSION={rustc_version}");
+    println!("cargo:rustc-env=PNGME_GIT_COMMIT={commit}");
+
+    // A new commit or checkout changes the commit recorded
+    if let Some(git_dir) = output(Command::new("git").args(["rev-parse", "--git-dir"])) {
+        let git_dir = PathBuf::from(git_dir);
+        let head = git_dir.join("HEAD");
+        if let Ok(content) = std::fs::read_to_string(&head) {
+            println!("cargo:rerun-if-changed={}", head.display());
+            if let Some(reference) = content.trim().strip_prefix("ref: ") {
+                for path in [git_dir.join(reference), git_dir.join("packed-refs")] {
+                    if path.exists() {
+                        println!("cargo:rerun-if-changed={}", path.display());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The trimmed standard output of `command`, `None` when it fails
+fn output(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8(output.stdout).ok()?;
+
+    Some(output.trim().to_string())
+}
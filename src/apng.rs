@@ -0,0 +1,200 @@
+//! Sequence numbers of animated PNGs.
+//!
+//! Every fcTL and fdAT chunk starts with a sequence number, counting up from
+//! 0 in file order across both types. Adding or removing one of them leaves
+//! a gap, and browsers then show the first frame only. Commands changing an
+//! animated image call [`renumber`] to close it, and `verify` reports the
+//! first chunk out of sequence with [`check`].
+//!
+//! Chunks of other types don't take part in the numbering: `encode` puts
+//! its chunks before IEND or after IHDR, outside the frames, unless given an
+//! index between them.
+
+use std::ops::RangeInclusive;
+
+use crate::{
+    chunk::Chunk,
+    chunk_ref::ChunkRef,
+    chunk_type::ChunkType,
+    consts::DATA_OFFSET,
+    png::{ParseWarning, Png},
+};
+
+/// Types of the chunks holding a sequence number
+const SEQUENCE_CHUNKS: [&[u8; 4]; 2] = [b"fcTL", b"fdAT"];
+
+fn is_sequence_chunk(chunk_type: &ChunkType) -> bool {
+    SEQUENCE_CHUNKS.contains(&&chunk_type.bytes())
+}
+
+/// Whether `png` has an animation control chunk
+pub fn is_animated(png: &Png) -> bool {
+    png.chunk_by_type("acTL").is_some()
+}
+
+/// Indices of the first and last fcTL or fdAT chunk of an animated image
+pub fn animation_range(png: &Png) -> Option<RangeInclusive<usize>> {
+    if !is_animated(png) {
+        return None;
+    }
+    let mut indices = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| is_sequence_chunk(chunk.chunk_type()))
+        .map(|(index, _)| index);
+    let first = indices.next()?;
+
+    Some(first..=indices.next_back().unwrap_or(first))
+}
+
+/// Whether a chunk inserted at `index` lands between two frame chunks
+pub fn is_inside_animation(png: &Png, index: usize) -> bool {
+    animation_range(png).is_some_and(|range| index > *range.start() && index <= *range.end())
+}
+
+/// Rewrites the sequence numbers of an animated image to count up from 0,
+/// the CRCs being computed again. Returns how many chunks changed, none for
+/// a still image. Chunks too short to hold a number are left alone.
+pub fn renumber(png: &mut Png) -> usize {
+    let Some(range) = animation_range(png) else {
+        return 0;
+    };
+
+    let mut next = 0_u32;
+    let mut changed = 0;
+    for index in range {
+        let chunk = &png.chunks()[index];
+        if !is_sequence_chunk(chunk.chunk_type()) {
+            continue;
+        }
+        let Some((number, rest)) = chunk.data().split_first_chunk::<4>() else {
+            continue;
+        };
+
+        if u32::from_be_bytes(*number) != next {
+            let data = [&next.to_be_bytes()[..], rest].concat();
+            let renumbered = Chunk::new(*chunk.chunk_type(), data);
+            png.replace_chunk_at(index, renumbered)
+                .expect("the index comes from the chunk list");
+            changed += 1;
+        }
+        next += 1;
+    }
+
+    changed
+}
+
+/// The first fcTL or fdAT chunk of `chunks` whose sequence number is not
+/// the next one, `range` covering the number
+pub fn check(chunks: &[ChunkRef]) -> Option<ParseWarning> {
+    if !chunks
+        .iter()
+        .any(|chunk| chunk.chunk_type.bytes() == *b"acTL")
+    {
+        return None;
+    }
+
+    chunks
+        .iter()
+        .filter(|chunk| is_sequence_chunk(&chunk.chunk_type))
+        .filter_map(|chunk| Some((chunk, chunk.data.first_chunk::<4>()?)))
+        .zip(0_u32..)
+        .find(|((_, number), expected)| u32::from_be_bytes(**number) != *expected)
+        .map(|((chunk, number), expected)| {
+            let start = chunk.offset + DATA_OFFSET as u64;
+            ParseWarning::AnimationSequence {
+                range: start..start + 4,
+                chunk_type: chunk.chunk_type.to_string(),
+                expected,
+                found: u32::from_be_bytes(*number),
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        chunk_ref::chunk_refs,
+        fixtures::{FixtureKind, make_fixture},
+        png::ParseOptions,
+    };
+
+    fn apng() -> Png {
+        Png::try_from(make_fixture(FixtureKind::Apng).as_slice()).unwrap()
+    }
+
+    fn sequence_warning(png: &Png) -> Option<ParseWarning> {
+        let bytes = png.as_bytes();
+        let chunks: Vec<_> = chunk_refs(&bytes, false, ParseOptions::DEFAULT_MAX_CHUNKS)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        check(&chunks)
+    }
+
+    #[test]
+    fn test_fixture_is_in_sequence() {
+        let mut png = apng();
+
+        assert_eq!(animation_range(&png), Some(2..=5));
+        assert_eq!(sequence_warning(&png), None);
+        assert_eq!(renumber(&mut png), 0);
+        assert_eq!(png, apng());
+    }
+
+    #[test]
+    fn test_inserted_chunk_is_inside_animation() {
+        let mut png = apng();
+        assert!(is_inside_animation(&png, 4));
+        assert!(!is_inside_animation(&png, 2));
+        assert!(!is_inside_animation(
+            &png,
+            png.index_of(Default::default()).unwrap()
+        ));
+
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hi".to_vec());
+        png.insert_chunk(4, chunk).unwrap();
+        assert_eq!(renumber(&mut png), 0);
+        assert_eq!(sequence_warning(&png), None);
+    }
+
+    #[test]
+    fn test_removed_frame_chunk_is_renumbered() {
+        let mut png = apng();
+        // The second fcTL, leaving fdAT with number 2
+        png.remove_chunk_at(4).unwrap();
+
+        assert_eq!(
+            sequence_warning(&png),
+            Some(ParseWarning::AnimationSequence {
+                range: 124..128,
+                chunk_type: "fdAT".to_string(),
+                expected: 1,
+                found: 2,
+            })
+        );
+        assert_eq!(renumber(&mut png), 1);
+        assert_eq!(sequence_warning(&png), None);
+
+        let fdat = png.chunk_by_type("fdAT").unwrap();
+        assert_eq!(fdat.data()[..4], 1_u32.to_be_bytes());
+        assert_eq!(
+            fdat.crc(),
+            Chunk::compute_crc(fdat.chunk_type(), fdat.data())
+        );
+    }
+
+    #[test]
+    fn test_still_images_are_not_checked() {
+        let mut png = Png::new_minimal();
+        let fctl = Chunk::new(ChunkType::from_str("fcTL").unwrap(), vec![0, 0, 0, 9]);
+        png.insert_chunk(1, fctl).unwrap();
+
+        assert_eq!(renumber(&mut png), 0);
+        assert_eq!(sequence_warning(&png), None);
+    }
+}
@@ -0,0 +1,285 @@
+//! Images read from inside zip and tar archives, without extracting them.
+//!
+//! A member is given as `bundle.zip!sprites/hero.png` or with
+//! `--archive-member`, and resolves to [`crate::input::InputSource::Archive`].
+//! Archives are only ever read: commands writing an image need `--output`.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use thiserror::Error;
+use zip::{ZipArchive, result::ZipError};
+
+use crate::codes::Code;
+
+/// Near matches listed at most when a member is missing
+const MAX_NEAR_MATCHES: usize = 5;
+
+/// Largest edit distance of a near match
+const MAX_DISTANCE: usize = 3;
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("Could not read the archive {}: {source}", archive.display())]
+    Io { archive: PathBuf, source: io::Error },
+
+    #[error("{} is not a valid zip archive: {source}", archive.display())]
+    Zip { archive: PathBuf, source: ZipError },
+
+    #[error("{member} is not in {}{}", archive.display(), format_near_matches(near_matches))]
+    MemberNotFound {
+        archive: PathBuf,
+        member: String,
+        near_matches: Vec<String>,
+    },
+
+    #[error("{member} is encrypted in {}, pngme can't read encrypted archives", archive.display())]
+    Encrypted { archive: PathBuf, member: String },
+}
+
+impl ArchiveError {
+    pub fn code(&self) -> Code {
+        match self {
+            ArchiveError::Io { .. } | ArchiveError::Zip { .. } => Code::ArchiveReadFailed,
+            ArchiveError::MemberNotFound { .. } => Code::ArchiveMemberNotFound,
+            ArchiveError::Encrypted { .. } => Code::ArchiveEncrypted,
+        }
+    }
+}
+
+fn format_near_matches(near_matches: &[String]) -> String {
+    match near_matches {
+        [] => String::new(),
+        _ => format!(" (did you mean {}?)", near_matches.join(", ")),
+    }
+}
+
+/// Kinds of archives, told apart by their extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    /// A gzip-compressed tar, `.tar.gz` or `.tgz`
+    TarGz,
+}
+
+impl ArchiveKind {
+    pub fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+
+        if name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Splits `bundle.zip!sprites/hero.png` into the archive and the member,
+/// when the part before the first `!` is an archive
+pub fn split_member(arg: &str) -> Option<(PathBuf, String)> {
+    let (archive, member) = arg.split_once('!')?;
+    let archive = PathBuf::from(archive);
+
+    (ArchiveKind::of(&archive).is_some() && !member.is_empty())
+        .then(|| (archive, member.to_string()))
+}
+
+/// Reads `member` out of `archive`, stopping one byte past `max_size` so
+/// the caller can tell the member is too large
+pub fn read_member(
+    archive: &Path,
+    member: &str,
+    max_size: Option<u64>,
+) -> Result<Vec<u8>, ArchiveError> {
+    let file = BufReader::new(File::open(archive).map_err(|source| ArchiveError::Io {
+        archive: archive.to_path_buf(),
+        source,
+    })?);
+
+    match ArchiveKind::of(archive) {
+        Some(ArchiveKind::Tar) => read_tar(file, archive, member, max_size),
+        Some(ArchiveKind::TarGz) => read_tar(GzDecoder::new(file), archive, member, max_size),
+        Some(ArchiveKind::Zip) | None => read_zip(file, archive, member, max_size),
+    }
+}
+
+fn read_zip(
+    file: BufReader<File>,
+    archive: &Path,
+    member: &str,
+    max_size: Option<u64>,
+) -> Result<Vec<u8>, ArchiveError> {
+    let zip_error = |source| ArchiveError::Zip {
+        archive: archive.to_path_buf(),
+        source,
+    };
+    let mut zip = ZipArchive::new(file).map_err(zip_error)?;
+    let names: Vec<String> = zip.file_names().map(str::to_string).collect();
+
+    match zip.by_name(member) {
+        Ok(entry) => read_entry(entry, archive, max_size),
+        Err(ZipError::FileNotFound) => Err(not_found(archive, member, &names)),
+        Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)) => {
+            Err(ArchiveError::Encrypted {
+                archive: archive.to_path_buf(),
+                member: member.to_string(),
+            })
+        }
+        Err(err) => Err(zip_error(err)),
+    }
+}
+
+/// Tar archives have no index, the entries are read in order up to `member`
+fn read_tar(
+    reader: impl Read,
+    archive: &Path,
+    member: &str,
+    max_size: Option<u64>,
+) -> Result<Vec<u8>, ArchiveError> {
+    let io_error = |source| ArchiveError::Io {
+        archive: archive.to_path_buf(),
+        source,
+    };
+    let mut names = Vec::new();
+
+    for entry in tar::Archive::new(reader).entries().map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        let path = entry
+            .path()
+            .map_err(io_error)?
+            .to_string_lossy()
+            .into_owned();
+
+        if path == member {
+            return read_entry(entry, archive, max_size);
+        }
+        names.push(path);
+    }
+
+    Err(not_found(archive, member, &names))
+}
+
+fn read_entry(
+    entry: impl Read,
+    archive: &Path,
+    max_size: Option<u64>,
+) -> Result<Vec<u8>, ArchiveError> {
+    let mut bytes = Vec::new();
+    entry
+        .take(max_size.map_or(u64::MAX, |limit| limit + 1))
+        .read_to_end(&mut bytes)
+        .map_err(|source| ArchiveError::Io {
+            archive: archive.to_path_buf(),
+            source,
+        })?;
+
+    Ok(bytes)
+}
+
+fn not_found(archive: &Path, member: &str, names: &[String]) -> ArchiveError {
+    ArchiveError::MemberNotFound {
+        archive: archive.to_path_buf(),
+        member: member.to_string(),
+        near_matches: near_matches(member, names),
+    }
+}
+
+/// Members of `names` close to `member`: the same name in another case or
+/// directory, then names a few edits away, closest first
+pub fn near_matches(member: &str, names: &[String]) -> Vec<String> {
+    let file_name = |name: &str| name.rsplit('/').next().unwrap_or(name).to_lowercase();
+    let wanted = member.to_lowercase();
+
+    let mut matches: Vec<(usize, &String)> = names
+        .iter()
+        .filter(|name| !name.ends_with('/'))
+        .filter_map(|name| {
+            let distance = edit_distance(&wanted, &name.to_lowercase());
+            let same_file = file_name(name) == file_name(member);
+            (same_file || distance <= MAX_DISTANCE).then_some((distance, name))
+        })
+        .collect();
+    matches.sort();
+
+    matches
+        .into_iter()
+        .take(MAX_NEAR_MATCHES)
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+/// Levenshtein distance between `a` and `b`, in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_member() {
+        assert_eq!(
+            split_member("assets/bundle.ZIP!sprites/hero.png"),
+            Some((
+                PathBuf::from("assets/bundle.ZIP"),
+                "sprites/hero.png".to_string()
+            ))
+        );
+        assert_eq!(
+            split_member("bundle.tgz!hero.png").map(|(archive, _)| archive),
+            Some(PathBuf::from("bundle.tgz"))
+        );
+        assert_eq!(split_member("wow!.png"), None);
+        assert_eq!(split_member("bundle.zip!"), None);
+        assert_eq!(split_member("image.png"), None);
+    }
+
+    #[test]
+    fn test_near_matches() {
+        let names: Vec<String> = [
+            "sprites/",
+            "sprites/hero.png",
+            "sprites/heroes.png",
+            "backup/hero.png",
+            "music/theme.ogg",
+        ]
+        .map(str::to_string)
+        .to_vec();
+
+        assert_eq!(
+            near_matches("sprites/Hero.png", &names),
+            ["sprites/hero.png", "sprites/heroes.png", "backup/hero.png"]
+        );
+        assert_eq!(near_matches("readme.txt", &names), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("hero", "hero"), 0);
+        assert_eq!(edit_distance("hero", "heroes"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -1,6 +1,25 @@
-use std::path::PathBuf;
+use std::{env, ffi::OsString, num::NonZeroUsize, path::PathBuf, str::FromStr, time::Duration};
 
-use clap::{Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum, error::ErrorKind};
+
+use crate::{
+    clock::parse_timestamp,
+    commands::Context,
+    fixtures::FixtureKind,
+    format::{Encoding, PayloadFormat, TextOptions, decode_hex},
+    input::InputSource,
+    meta::OnConflict,
+    png::{ParseOptions, Position},
+    profiles::{Profile, parse_profile},
+    scan::ScanOptions,
+    secret::{PASSPHRASE_VARIABLE, SecretSource},
+    template::parse_var,
+    undo::UndoStore,
+    walk::{Links, PngFiles, png_files},
+};
+
+#[cfg(feature = "server")]
+use crate::server::ServerOptions;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -8,41 +27,1374 @@ use clap::{Parser, Subcommand};
 pub struct Arguments {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Maximum number of chunks accepted when parsing a file
+    #[arg(long, global = true, default_value_t = ParseOptions::DEFAULT_MAX_CHUNKS)]
+    pub max_chunks: usize,
+
+    /// Maximum size of an input file, download or stream, in bytes or with
+    /// a K, M or G suffix
+    #[arg(long, global = true)]
+    pub max_input_size: Option<String>,
+
+    /// Maximum size compressed chunk data and messages may inflate to, in
+    /// bytes or with a K, M or G suffix [default: 16M]
+    #[arg(long, global = true)]
+    pub max_decompressed_size: Option<String>,
+
+    /// Maximum speed of downloads in bytes per second, or with a K, M or G
+    /// suffix, e.g. 200K
+    #[arg(long, global = true)]
+    pub limit_rate: Option<String>,
+
+    /// Keep downloaded images and download them again only when the server
+    /// says they changed
+    #[arg(long, global = true)]
+    pub cache: bool,
+
+    /// Download without the cache, even with --cache, e.g. from an alias
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+
+    /// Directory of the download cache [default: pngme/downloads in the
+    /// cache directory of the platform]
+    #[arg(long, global = true, value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Cached images not checked for this long are downloaded again without
+    /// asking the server, e.g. 2h or 7days
+    #[arg(long, global = true, value_name = "DURATION", default_value = "1day",
+        value_parser = humantime::parse_duration)]
+    pub cache_max_age: Duration,
+
+    /// Answer yes to confirmations, e.g. about unusual chunk names
+    #[arg(short = 'y', long, global = true)]
+    pub assume_yes: bool,
+
+    /// Seconds to wait for another process editing the same file in place
+    #[arg(long, global = true, default_value_t = Context::DEFAULT_LOCK_TIMEOUT.as_secs())]
+    pub lock_timeout: u64,
+
+    /// Save the original of files edited in place so `pngme undo` can
+    /// restore it
+    #[arg(long, global = true)]
+    pub undoable: bool,
+
+    /// Copy files edited in place to their path plus SUFFIX (.bak by
+    /// default) before reading them
+    #[arg(
+        long,
+        global = true,
+        value_name = "SUFFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ".bak",
+        value_parser = clap::builder::NonEmptyStringValueParser::new()
+    )]
+    pub backup: Option<String>,
+
+    /// Overwrite the copy made by --backup when it already exists
+    #[arg(long, global = true, requires = "backup")]
+    pub force: bool,
+
+    /// Write the chunks encode, remove, strip, inject and import-meta
+    /// change to PATH, for `pngme apply-patch`
+    #[arg(long, global = true, value_name = "PATH")]
+    pub emit_patch: Option<PathBuf>,
+
+    /// Write every file of a command writing several, e.g. `import-meta`,
+    /// or none of them when one fails
+    #[arg(long, global = true)]
+    pub transactional: bool,
+
+    /// Let `encode` and `remove` write files holding a critical chunk that
+    /// decoders don't know
+    #[arg(long, global = true)]
+    pub allow_unknown_critical: bool,
+
+    /// Fail instead of warning when an image is written to a path not
+    /// ending in .png or .apng
+    #[arg(long, global = true)]
+    pub strict_extension: bool,
+
+    /// Write images to output paths without an extension as given, instead
+    /// of adding .png
+    #[arg(long, global = true)]
+    pub no_ext_fixup: bool,
+
+    /// Number of undo states kept per file
+    #[arg(long, global = true, default_value_t = UndoStore::DEFAULT_KEEP, requires = "undoable",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub keep_backups: usize,
+
+    /// Print where the time went once the command is done: reading, parsing,
+    /// embedding, serializing and writing
+    #[arg(long, global = true)]
+    pub stats: bool,
+
+    /// Leave the temporary files of interrupted or failed writes in place to
+    /// inspect them
+    #[arg(long, global = true)]
+    pub keep_temp: bool,
+
+    /// Don't run `pngme-<name>` programs from the PATH for commands that
+    /// aren't built in
+    #[arg(long, global = true)]
+    pub no_external: bool,
+
+    /// Read the image from this member of the zip or tar archive given as
+    /// FILE, like FILE!MEMBER
+    #[cfg(feature = "archives")]
+    #[arg(long, global = true)]
+    pub archive_member: Option<String>,
 }
 
+#[cfg(feature = "archives")]
+impl Arguments {
+    /// Turns the input of the command into the `--archive-member` of it
+    pub fn resolve_archive_member(&mut self) -> Result<(), crate::validate::Problem> {
+        let Some(member) = self.archive_member.clone() else {
+            return Ok(());
+        };
+
+        if let Some(file) = self.command.file_mut()
+            && let InputSource::Path(archive) = file
+        {
+            let archive = std::mem::take(archive);
+            *file = InputSource::Archive { archive, member };
+            return Ok(());
+        }
+
+        Err(crate::validate::Problem::Conflict {
+            first: "--archive-member",
+            second: "this input",
+            reason: "only a single local archive holds members, or use FILE!MEMBER",
+        })
+    }
+}
+
+// Parsed once per run, the size of the largest command doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Clone)]
 pub enum Commands {
     /// Encode a message into an image
-    Encode {
-        /// Path or Url to the png file 
-        file: PathBuf,
-        /// Name of the chunk embedding the message
-        chunk_name: String,
-        /// The message to encode
-        message: String,
-        /// Output file. Default to "output.png"
-        output: Option<PathBuf>
-    },
+    #[command(visible_alias = "write")]
+    Encode(EncodeArgs),
 
     /// Decode a message embedded into an image
+    #[command(visible_alias = "read")]
     Decode {
-        /// Path to the png file
-        file: PathBuf,
-        /// Name of the chunk embedding the message
-        chunk_name: String
+        /// Paths, URLs, data URIs or `-` for stdin, followed by the chunk name
+        /// unless `--chunk` is given. Only the chunk name with --recursive
+        #[arg(required_unless_present = "batch", num_args = 1..)]
+        files: Vec<String>,
+        /// Name of the chunk, instead of the last positional argument
+        #[arg(long)]
+        chunk: Option<String>,
+        /// Only print the message
+        #[arg(short, long)]
+        quiet: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+        /// Check that every file carries the same payload
+        #[arg(long, conflicts_with_all = ["quiet", "format"])]
+        compare: bool,
+        /// Write the payload bytes exactly as stored, without decoding or
+        /// escaping them
+        #[arg(long, conflicts_with_all = ["quiet", "format", "compare"])]
+        raw: bool,
+        /// Show messages even if they expired
+        #[arg(long)]
+        ignore_expiry: bool,
+        /// Open the envelopes nested in the message, left by encoding a
+        /// payload that already was one, and show the innermost message
+        #[arg(long, conflicts_with_all = ["raw", "compare"])]
+        unwrap_all: bool,
+        /// How the message is printed, e.g. base45 for QR tooling
+        #[arg(long, value_enum, default_value_t = Encoding::Text, conflicts_with_all = ["raw", "compare"])]
+        output_encoding: Encoding,
+        /// Break an encoded message into lines of this many characters, 76
+        /// like MIME when no number is given. Text isn't wrapped
+        #[arg(
+            long,
+            value_name = "COLUMNS",
+            num_args = 0..=1,
+            default_missing_value = "76",
+            requires = "output_encoding"
+        )]
+        wrap: Option<NonZeroUsize>,
+        /// Print the message laid out as a document of this format, failing
+        /// when it isn't one
+        #[arg(long, value_enum, conflicts_with_all = ["raw", "compare", "output", "output_encoding"])]
+        payload_format: Option<PayloadFormat>,
+        /// Clean-ups of a text message before it is shown, left out for
+        /// other encodings and raw output
+        #[command(flatten)]
+        text: TextOptions,
+        /// Write the message bytes to this file instead of printing them,
+        /// `-` for stdout. A directory, or a path ending with a dot like
+        /// `payload.`, gets a file named with the extension of the format of
+        /// the message: png, jpg, pdf, zip, gz, txt or bin when unknown
+        #[arg(long, conflicts_with_all = ["quiet", "format", "compare", "raw", "output_encoding"])]
+        output: Option<PathBuf>,
+        /// Write to the --output path as it is, without guessing the format
+        /// of the message
+        #[arg(long, requires = "output")]
+        no_sniff: bool,
+        /// Decrypt messages written with `encode --encrypt`, with the same
+        /// passphrase sources
+        #[arg(long, conflicts_with_all = ["compare", "raw"])]
+        decrypt: bool,
+        #[command(flatten)]
+        password: PasswordArgs,
+        /// Fail unless the message carries an HMAC matching the key of
+        /// --hmac-key or --hmac-key-file
+        #[arg(long, requires = "hmac_key_source", conflicts_with = "compare")]
+        verify_hmac: bool,
+        #[command(flatten)]
+        hmac_key: HmacKeyArgs,
+        #[command(flatten)]
+        recursive: RecursiveArgs,
     },
 
     /// Remove a message embedded into an iamge
+    #[command(visible_alias = "rm")]
     Remove {
-        /// Path to the png file
-        file: PathBuf,
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
         /// Name of the chunk embedding the message
-        chunk_name: String
+        #[arg(required_unless_present_any = ["at", "chunk"], conflicts_with = "chunk")]
+        chunk_name: Option<String>,
+        /// Name of the chunk, instead of the positional argument
+        #[arg(long)]
+        chunk: Option<String>,
+        /// Absolute zero-based index of the chunk to remove, as shown by `print`
+        #[arg(long, conflicts_with_all = ["chunk_name", "chunk"])]
+        at: Option<usize>,
+        /// Output file. Default to the input file
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Print the chunks and size the image would have, without writing
+        /// anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show a summary of an image
+    Info {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+    },
+
+    /// Extract data embedded into an image
+    Extract {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+        /// Write the decompressed ICC color profile to this file
+        #[arg(long, required_unless_present = "index", conflicts_with = "index")]
+        icc: Option<PathBuf>,
+        /// Absolute zero-based index of the chunk whose data to extract, as
+        /// shown by `print`
+        #[arg(long, requires = "output")]
+        index: Option<usize>,
+        /// Write the data of the chunk at --index to this file, `-` for
+        /// stdout
+        #[arg(long, requires = "index")]
+        output: Option<PathBuf>,
+    },
+
+    /// Inject data into an image
+    Inject {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+        /// ICC color profile to embed as an iCCP chunk
+        #[arg(long, requires = "name", required_unless_present = "chunk_spec")]
+        icc: Option<PathBuf>,
+        /// Name of the color profile
+        #[arg(long, requires = "icc")]
+        name: Option<String>,
+        /// Chunk to add before IEND, as TYPE:hex or TYPE:"text", e.g.
+        /// 'noTe:"hello"'. Can be repeated
+        #[arg(long, value_name = "SPEC", conflicts_with = "icc")]
+        chunk_spec: Vec<String>,
+        /// Replace an existing iCCP or sRGB chunk, or with --chunk-spec the
+        /// first chunk of the same type
+        #[arg(long)]
+        replace: bool,
+        /// Output file. Default to the input file
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(hide = true)]
+    Debug {
+        #[command(subcommand)]
+        command: DebugCommands,
     },
 
     /// Prints the path of an image
+    #[command(visible_aliases = ["list", "ls"])]
     Print {
-        /// Path to the png file
+        /// Path, URL, data URI or `-` for stdin
+        #[arg(required_unless_present = "batch", conflicts_with = "batch")]
+        file: Option<InputSource>,
+        /// Collapse runs of consecutive chunks of the same type (e.g. IDAT)
+        #[arg(long)]
+        collapse: bool,
+        #[command(flatten)]
+        recursive: RecursiveArgs,
+    },
+
+    /// Rate how likely a chunk is to survive when the image is re-encoded
+    Survivability {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+        /// Type of the chunk to rate
+        chunk_type: String,
+        /// Rename and move the chunk as suggested
+        #[arg(long)]
+        apply_suggestions: bool,
+        /// Also check the constraints of this pipeline profile, see
+        /// `encode --evade`
+        #[arg(long, value_name = "PROFILE", value_parser = parse_profile)]
+        profile: Option<&'static Profile>,
+        /// Output file of --apply-suggestions. Default to the input file
+        #[arg(long, requires = "apply_suggestions")]
+        output: Option<PathBuf>,
+    },
+
+    /// Remove ancillary chunks from an image
+    Strip {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+        /// Only remove chunks holding an expired message
+        #[arg(long)]
+        expired_only: bool,
+        /// Also remove the chunks affecting colors and transparency (gAMA,
+        /// sRGB, cHRM, iCCP, sBIT, bKGD, tRNS), kept by default
+        #[arg(long)]
+        strip_color: bool,
+        /// Only remove the chunks of this selection, color ones included:
+        /// comma-separated categories (all, private, public, text, color,
+        /// time) and chunk types, read left to right, `!` removing them
+        #[arg(long, value_name = "SELECTION", conflicts_with = "strip_color")]
+        select: Option<String>,
+        /// Output file. Default to the input file
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Save every ancillary chunk of an image to a sidecar file
+    ExportMeta {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+        /// Sidecar file to write, `-` for stdout
+        sidecar: PathBuf,
+        /// Only the chunks of this selection: comma-separated categories
+        /// (all, private, public, text, color, time) and chunk types, read
+        /// left to right, `!` removing them, e.g. `private,text,!zTXt`
+        #[arg(long, value_name = "SELECTION")]
+        select: Option<String>,
+    },
+
+    /// Restore the chunks saved by export-meta into images
+    ImportMeta {
+        /// Paths, URLs, data URIs or `-` for stdin
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<InputSource>,
+        /// Sidecar file written by export-meta
+        sidecar: PathBuf,
+        /// What to do with chunks whose type the image already has
+        #[arg(long, value_enum, default_value_t = OnConflict::Fail)]
+        on_conflict: OnConflict,
+        /// Only the chunks of this selection: comma-separated categories
+        /// (all, private, public, text, color, time) and chunk types, read
+        /// left to right, `!` removing them, e.g. `private,text,!zTXt`
+        #[arg(long, value_name = "SELECTION")]
+        select: Option<String>,
+        /// Output file. Default to the input file
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Apply a patch written by --emit-patch to an image, or undo it
+    ApplyPatch {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+        /// Patch file written by --emit-patch
+        patch: PathBuf,
+        /// Undo the patch on the image it made
+        #[arg(long)]
+        reverse: bool,
+        /// Output file. Default to the input file
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Serve encode and decode over a local HTTP API
+    #[cfg(feature = "server")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: String,
+        /// Shared secret every request must send in the X-Pngme-Token header
+        #[arg(long)]
+        token: Option<String>,
+        /// Maximum size of a request body, in bytes or with a K, M or G suffix
+        #[arg(long, default_value_t = ServerOptions::DEFAULT_MAX_BODY_SIZE.to_string())]
+        max_body_size: String,
+        /// Seconds allowed to receive a request body
+        #[arg(long, default_value_t = ServerOptions::DEFAULT_TIMEOUT.as_secs())]
+        timeout: u64,
+        /// Requests handled at the same time, the others wait their turn
+        #[arg(long, default_value_t = ServerOptions::DEFAULT_WORKERS)]
+        workers: NonZeroUsize,
+    },
+
+    /// Check the structure of an image, reporting every problem with its byte range
+    Verify {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+        /// Check the signature of the chunk of this type instead of the
+        /// structure of the image
+        #[arg(requires = "pubkey", conflicts_with = "deep")]
+        chunk: Option<String>,
+        /// Ed25519 public key, as a PEM file, the chunk must be signed with
+        #[arg(long, value_name = "PUB.pem", requires = "chunk")]
+        pubkey: Option<PathBuf>,
+        /// Also inflate the image data and check its size against the IHDR
+        /// dimensions (interlaced images are skipped)
+        #[arg(long)]
+        deep: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+
+    /// Repair the problems reported by verify: bad CRCs, chunks after IEND,
+    /// missing IEND and trailing bytes. Reserved bits are only reported,
+    /// unless `--fix-reserved` is given
+    Fix {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+        /// Turn a signature-only file into a minimal valid 1x1 image
+        #[arg(long)]
+        bootstrap: bool,
+        /// Re-encode an interlaced (Adam7) image without interlacing,
+        /// keeping its other chunks
+        #[arg(long)]
+        deinterlace: bool,
+        /// Clear the reserved bit of chunk types (uppercase their third
+        /// letter), unless a chunk of the corrected type already exists
+        #[arg(long)]
+        fix_reserved: bool,
+        /// Output file. Default to the input file
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Rewrite an image in a canonical form, identical for files holding the
+    /// same chunks: a single IDAT, the chunks in a fixed order (the order the
+    /// specification requires, then by type and data, so textual chunks by
+    /// keyword) and nothing after IEND. The pixels and the data of the other
+    /// chunks are untouched
+    Canonicalize {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+        /// Output file. Default to the input file
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Restore a file edited in place with `--undoable`
+    Undo {
+        /// Path to the file
         file: PathBuf,
+        /// List the available states instead of restoring the latest
+        #[arg(long)]
+        list: bool,
+    },
+
+    /// Manage the download cache of --cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// Manage the passphrases stored in the OS keychain
+    #[cfg(feature = "keyring")]
+    Key {
+        #[command(subcommand)]
+        command: KeyCommands,
+    },
+
+    /// Count the chunk types of many files: chunks, files holding them and
+    /// sizes
+    Types {
+        /// Files, or directories whose PNG files are read
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Also read the PNG files of subdirectories
+        #[arg(short, long)]
+        recursive: bool,
+        /// Only list the N most frequent types
+        #[arg(long, value_name = "N")]
+        top: Option<usize>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+
+    /// Report the size of an image and what embedding a payload takes: the
+    /// chunks it needs, whether it must be split and the size of the output
+    Capacity {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+        /// Size of the payload as stored, after any compression or
+        /// encryption, in bytes or with a K, M or G suffix
+        #[arg(long, value_name = "SIZE")]
+        payload_size: Option<String>,
+        /// Fail when the image with the payload would be larger than this
+        #[arg(long, value_name = "SIZE")]
+        max_size: Option<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+
+    /// Look for chunks whose size hints at hidden data
+    Scan {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+        /// Flag private chunks larger than this size, in bytes or with a K, M
+        /// or G suffix
+        #[arg(long, default_value_t = ScanOptions::DEFAULT_MAX_PRIVATE_SIZE.to_string())]
+        max_private_size: String,
+        /// Flag ancillary chunks larger than this fraction of the image data
+        #[arg(long, default_value_t = ScanOptions::DEFAULT_MAX_IDAT_RATIO)]
+        max_idat_ratio: f64,
+        /// List the chunks written by pngme and their annotations instead
+        #[arg(long)]
+        provenance: bool,
+    },
+
+    /// Describe this build: version, enabled features, inputs and the
+    /// commands with their flags
+    Capabilities {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+
+    /// Describe an error or warning code, e.g. `pngme explain W0203`, or
+    /// list every code without one
+    Explain {
+        /// Code or name of the error or warning
+        code: Option<String>,
+    },
+
+    /// Print the version with the features, target, compiler, profile and
+    /// commit of this build, like `--version --verbose`
+    Version {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+
+    /// Any other command runs the `pngme-<name>` program from the PATH
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
+}
+
+/// The download cache, in --cache-dir or the default directory
+#[derive(Subcommand, Clone)]
+pub enum CacheCommands {
+    /// Remove every cached download
+    Clear,
+}
+
+/// Passphrases in the OS keychain, used with `--password-keychain`
+#[cfg(feature = "keyring")]
+#[derive(Subcommand, Clone)]
+pub enum KeyCommands {
+    /// Store a passphrase, prompted for unless given
+    Store {
+        /// Name of the passphrase
+        name: String,
+        #[command(flatten)]
+        password: PasswordArgs,
+    },
+
+    /// Delete a stored passphrase
+    Delete {
+        /// Name of the passphrase
+        name: String,
     },
-}
\ No newline at end of file
+}
+
+impl Commands {
+    /// The input of the commands reading a single image
+    pub fn file_mut(&mut self) -> Option<&mut InputSource> {
+        match self {
+            Commands::Encode(EncodeArgs { file: Some(file), .. })
+            | Commands::Remove { file, .. }
+            | Commands::Info { file }
+            | Commands::Extract { file, .. }
+            | Commands::Inject { file, .. }
+            | Commands::Print { file: Some(file), .. }
+            | Commands::Survivability { file, .. }
+            | Commands::Strip { file, .. }
+            | Commands::ExportMeta { file, .. }
+            | Commands::ApplyPatch { file, .. }
+            | Commands::Verify { file, .. }
+            | Commands::Fix { file, .. }
+            | Commands::Canonicalize { file, .. }
+            | Commands::Capacity { file, .. }
+            | Commands::Scan { file, .. } => Some(file),
+            Commands::ImportMeta { files, .. } if files.len() == 1 => files.first_mut(),
+            _ => None,
+        }
+    }
+
+    /// Output format of the commands that have one
+    pub fn format(&self) -> OutputFormat {
+        match self {
+            Commands::Encode(EncodeArgs { format, .. })
+            | Commands::Decode { format, .. }
+            | Commands::Verify { format, .. }
+            | Commands::Types { format, .. }
+            | Commands::Capacity { format, .. }
+            | Commands::Capabilities { format }
+            | Commands::Version { format } => *format,
+            _ => OutputFormat::Human,
+        }
+    }
+}
+
+/// Arguments of `encode`, kept apart from [`Commands`] for
+/// [`encode_command`](crate::commands::encode_command)
+#[derive(Args, Clone, Debug)]
+pub struct EncodeArgs {
+    /// Path, URL, data URI or `-` for stdin
+    #[arg(required_unless_present = "batch", conflicts_with = "batch")]
+    pub file: Option<InputSource>,
+    /// Encode every file matching these patterns instead of FILE, e.g.
+    /// 'assets/**/*.png' quoted for the shell to leave it alone, each in
+    /// place. The chunk and the message then come from flags
+    #[arg(
+        long = "glob",
+        id = "globs",
+        value_name = "PATTERN",
+        num_args = 1..,
+        group = "batch",
+        conflicts_with_all = ["output", "output_flag"]
+    )]
+    pub globs: Vec<String>,
+    #[command(flatten)]
+    pub recursive: RecursiveArgs,
+    /// Stop a --glob or --recursive batch at the first file that fails,
+    /// instead of encoding the others and listing the failures at the
+    /// end
+    #[arg(long, requires = "batch")]
+    pub fail_fast: bool,
+    /// Record each file of a --glob or --recursive batch in this JSON Lines
+    /// journal as it is encoded, for --resume to go on after a crash
+    #[arg(long, value_name = "PATH", requires = "batch", conflicts_with_all = ["resume", "dry_run"])]
+    pub journal: Option<PathBuf>,
+    /// Go on with the batch recorded in this journal: the files it marks
+    /// done are skipped, and the others are encoded and recorded in it
+    #[arg(
+        long,
+        value_name = "JOURNAL",
+        requires = "batch",
+        conflicts_with_all = ["dry_run", "random_type"]
+    )]
+    pub resume: Option<PathBuf>,
+    /// Name of the chunk embedding the message
+    #[arg(required_unless_present_any = ["chunk", "pairs", "random_type"], conflicts_with = "chunk")]
+    pub chunk_name: Option<String>,
+    /// The message to encode
+    #[arg(
+        required_unless_present_any = ["message_flag", "message_template", "message_file", "pairs"],
+        conflicts_with_all = ["message_flag", "message_template", "message_file"]
+    )]
+    pub message: Option<String>,
+    /// Output file. Default to the input file
+    #[arg(conflicts_with = "output_flag")]
+    pub output: Option<PathBuf>,
+    /// Name of the chunk, instead of the positional argument
+    #[arg(long)]
+    pub chunk: Option<String>,
+    /// Embed the message in a chunk of a random ancillary, private and
+    /// safe-to-copy type, like `xxXx`, printed on stderr. The message
+    /// then comes from --message, --message-file or --message-template
+    #[arg(long, conflicts_with_all = ["chunk_name", "chunk", "pairs", "text_keyword", "itxt", "ztxt"])]
+    pub random_type: bool,
+    /// The message, instead of the positional argument
+    #[arg(
+        long = "message",
+        id = "message_flag",
+        conflicts_with_all = ["message_template", "message_file"]
+    )]
+    pub message_flag: Option<String>,
+    /// A chunk name and its message, as NAME=MESSAGE. Repeat it to embed
+    /// several messages with one read and write of the image, in the
+    /// order given
+    #[arg(
+        long = "pair",
+        id = "pairs",
+        value_name = "NAME=MESSAGE",
+        value_parser = parse_var,
+        conflicts_with_all = ["chunk_name", "message", "chunk", "message_flag", "message_template", "message_file"]
+    )]
+    pub pairs: Vec<(String, String)>,
+    /// Read the message from a file, embedded byte for byte even if it
+    /// isn't UTF-8, e.g. an image or an archive
+    #[arg(
+        long,
+        visible_alias = "input-file",
+        conflicts_with_all = ["message_template", "template", "strip_bom", "normalize_newlines"]
+    )]
+    pub message_file: Option<PathBuf>,
+    /// Read the message from a template file with `{{var}}` placeholders
+    #[arg(long)]
+    pub message_template: Option<PathBuf>,
+    /// Fill the `{{var}}` placeholders of the inline message
+    #[arg(long, conflicts_with = "message_template")]
+    pub template: bool,
+    /// Value of a template placeholder, as NAME=VALUE
+    #[arg(long = "var", value_parser = parse_var)]
+    pub vars: Vec<(String, String)>,
+    /// Reject the {{date}} and {{hostname}} placeholders unless set with
+    /// --var, and leave the time out of annotations unless set with
+    /// --annotation-date
+    #[arg(long)]
+    pub deterministic: bool,
+    /// Output file, instead of the positional argument
+    #[arg(long = "output", id = "output_flag")]
+    pub output_flag: Option<PathBuf>,
+    /// Embed the message even if it is empty
+    #[arg(long)]
+    pub allow_empty: bool,
+    /// When the message already is a pngme envelope, e.g. the raw
+    /// payload of another image, embed its message instead
+    #[arg(long)]
+    pub unwrap: bool,
+    /// Embed a message that already is a pngme envelope without warning
+    #[arg(long, conflicts_with = "unwrap")]
+    pub wrap_anyway: bool,
+    /// How the message is written, e.g. base45 for text transcribed from a QR code
+    #[arg(long, value_enum, default_value_t = Encoding::Text)]
+    pub input_encoding: Encoding,
+    /// Refuse a message that is not a valid document of this format,
+    /// before the image is read
+    #[arg(long, value_enum)]
+    pub payload_format: Option<PayloadFormat>,
+    /// Expiry of the message as an RFC 3339 UTC timestamp, e.g.
+    /// 2025-01-01T00:00:00Z. Expired messages are hidden by `decode`
+    #[arg(long, value_parser = parse_timestamp)]
+    pub expires: Option<u64>,
+    /// Record the pngme version and the current time with the message
+    #[arg(long)]
+    pub annotate: bool,
+    /// Note recorded by --annotate, e.g. who wrote the chunk and why
+    #[arg(long, requires = "annotate")]
+    pub annotation: Option<String>,
+    /// Time recorded by --annotate instead of the current one, as an
+    /// RFC 3339 UTC timestamp
+    #[arg(long, requires = "annotate", value_parser = parse_timestamp)]
+    pub annotation_date: Option<u64>,
+    /// Compress the message (zlib), `decode` decompresses it
+    #[arg(long)]
+    pub compress: bool,
+    /// Encrypt the message with a passphrase, read from the
+    /// `--password*` flags, PNGME_PASSPHRASE or a prompt
+    #[arg(long)]
+    pub encrypt: bool,
+    #[command(flatten)]
+    pub password: PasswordArgs,
+    /// Store an HMAC-SHA256 of the message, computed with the key of
+    /// --hmac-key or --hmac-key-file, for `decode --verify-hmac`
+    #[arg(long, requires = "hmac_key_source", conflicts_with_all = ["text_keyword", "itxt", "ztxt"])]
+    pub hmac: bool,
+    #[command(flatten)]
+    pub hmac_key: HmacKeyArgs,
+    /// Sign the message with the Ed25519 private key of this PKCS#8 PEM
+    /// file, in a siGn chunk `verify --pubkey` checks
+    #[arg(long, value_name = "KEY.pem")]
+    pub sign_key: Option<PathBuf>,
+    /// Store the author, the current time, the pngme version and the
+    /// image name in a meTa chunk next to the message, shown by `decode`
+    /// and `print`. --deterministic leaves the time out
+    #[arg(long)]
+    pub meta: bool,
+    /// Author recorded by --meta
+    #[arg(long, value_name = "NAME", requires = "meta")]
+    pub meta_author: Option<String>,
+    /// Store no meTa chunk even with --meta, e.g. set in an alias
+    #[arg(long)]
+    pub no_meta: bool,
+    /// Split the message across chunks of the same type holding at most
+    /// this many bytes each, with a K, M or G suffix, e.g. 64K
+    #[arg(long, value_name = "SIZE")]
+    pub split_size: Option<String>,
+    /// Overwrite the first chunk of this type in place, instead of adding
+    /// another chunk after it
+    #[arg(long)]
+    pub replace: bool,
+    /// Where the chunk is added: before-iend, after-ihdr or an absolute
+    /// zero-based index as shown by `print`
+    #[arg(long, default_value = "before-iend")]
+    pub position: Position,
+    /// Pick the chunk name casing and position known to survive this
+    /// pipeline: oxipng-default, pngcrush-default or wordpress
+    #[arg(
+        long,
+        value_name = "PROFILE",
+        value_parser = parse_profile,
+        conflicts_with_all = ["position", "text_keyword", "itxt", "ztxt"]
+    )]
+    pub evade: Option<&'static Profile>,
+    /// Write the message as the text of a standard tEXt chunk with this
+    /// keyword, shown by other tools. The chunk name must be tEXt
+    #[arg(
+        long,
+        value_name = "KEYWORD",
+        group = "latin1_text",
+        conflicts_with_all = ["pairs", "expires", "annotate", "compress", "encrypt", "split_size"]
+    )]
+    pub text_keyword: Option<String>,
+    /// Write the message as the UTF-8 text of a standard iTXt chunk with
+    /// this keyword. The chunk name must be iTXt
+    #[arg(
+        long,
+        value_name = "KEYWORD",
+        conflicts_with_all = ["pairs", "expires", "annotate", "compress", "encrypt", "split_size", "text_keyword"]
+    )]
+    pub itxt: Option<String>,
+    /// Language tag of the iTXt text, e.g. `fr` or `en-GB`
+    #[arg(long, value_name = "TAG", requires = "itxt")]
+    pub language: Option<String>,
+    /// The iTXt keyword translated into the language of the text
+    #[arg(long, value_name = "KEYWORD", requires = "itxt")]
+    pub translated_keyword: Option<String>,
+    /// Write the message as the compressed Latin-1 text of a standard
+    /// zTXt chunk with this keyword. The chunk name must be zTXt
+    #[arg(
+        long,
+        value_name = "KEYWORD",
+        group = "latin1_text",
+        conflicts_with_all = ["pairs", "expires", "annotate", "compress", "encrypt", "split_size", "text_keyword", "itxt"]
+    )]
+    pub ztxt: Option<String>,
+    /// Replace the characters of a --text-keyword or --ztxt message
+    /// outside Latin-1 with '?' rather than fail, e.g. emoji
+    #[arg(long, requires = "latin1_text")]
+    pub force_latin1_lossy: bool,
+    /// Write a critical chunk type or one with the reserved bit set,
+    /// which most decoders reject
+    #[arg(long)]
+    pub allow_unsafe_type: bool,
+    /// Clean-ups of a text message, left out for other encodings and
+    /// refused with --message-file
+    #[command(flatten)]
+    pub text: TextOptions,
+    /// Print the chunks and size the image would have, without writing
+    /// anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Read the output back before it replaces the destination and fail
+    /// unless every message decodes to what was asked
+    #[arg(long, conflicts_with = "dry_run")]
+    pub verify_after: bool,
+    /// Write the output even when --verify-after fails, to inspect it
+    #[arg(long, requires = "verify_after")]
+    pub keep_on_failure: bool,
+    /// Warn when the output is larger than this, instead of the common
+    /// attachment limits (8, 10 and 25 MiB). Repeat it for several
+    /// thresholds
+    #[arg(long, value_name = "SIZE")]
+    pub size_warn: Vec<String>,
+    /// Don't compare the output with any size
+    #[arg(long, conflicts_with = "size_warn")]
+    pub no_size_warn: bool,
+    /// Output format of the report, JSON listing the size of the output
+    /// and the limits it exceeds
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human, conflicts_with = "dry_run")]
+    pub format: OutputFormat,
+}
+
+/// Where to read a passphrase from: the first of these flags, else the
+/// PNGME_PASSPHRASE variable, else a prompt
+#[derive(Args, Clone, Debug, Default)]
+pub struct PasswordArgs {
+    /// Passphrase. It shows in the shell history and the process list, prefer
+    /// the other sources
+    #[arg(long, group = "passphrase")]
+    pub password: Option<String>,
+
+    /// Read the passphrase from a file, less one trailing newline
+    #[arg(long, group = "passphrase")]
+    pub password_file: Option<PathBuf>,
+
+    /// Read the passphrase from an inherited file descriptor, e.g.
+    /// `--password-fd 3 3<secret.txt`
+    #[cfg(unix)]
+    #[arg(long, group = "passphrase")]
+    pub password_fd: Option<i32>,
+
+    /// Name of a passphrase stored with `pngme key store`
+    #[cfg(feature = "keyring")]
+    #[arg(long, group = "passphrase")]
+    pub password_keychain: Option<String>,
+}
+
+impl PasswordArgs {
+    pub fn source(&self) -> SecretSource {
+        #[cfg(feature = "keyring")]
+        if let Some(name) = &self.password_keychain {
+            return SecretSource::Keychain(name.clone());
+        }
+
+        #[cfg(unix)]
+        if let Some(fd) = self.password_fd {
+            return SecretSource::Fd(fd);
+        }
+
+        match (&self.password, &self.password_file) {
+            (Some(password), _) => SecretSource::Flag(password.clone()),
+            (None, Some(path)) => SecretSource::File(path.clone()),
+            (None, None) if env::var_os(PASSPHRASE_VARIABLE).is_some() => {
+                SecretSource::Variable(PASSPHRASE_VARIABLE.to_string())
+            }
+            (None, None) => SecretSource::Prompt,
+        }
+    }
+}
+
+/// Where to read the key of `--hmac` and `--verify-hmac` from
+#[derive(Args, Clone, Debug, Default)]
+pub struct HmacKeyArgs {
+    /// Key of the HMAC. It shows in the shell history and the process list,
+    /// prefer --hmac-key-file
+    #[arg(long, group = "hmac_key_source")]
+    pub hmac_key: Option<String>,
+
+    /// Read the key of the HMAC from a file, less one trailing newline
+    #[arg(long, group = "hmac_key_source")]
+    pub hmac_key_file: Option<PathBuf>,
+}
+
+impl HmacKeyArgs {
+    /// Source of the key, `None` when no key is given
+    pub fn source(&self) -> Option<SecretSource> {
+        match (&self.hmac_key, &self.hmac_key_file) {
+            (Some(key), _) => Some(SecretSource::Flag(key.clone())),
+            (None, Some(path)) => Some(SecretSource::File(path.clone())),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Walking a directory tree instead of reading FILE, for `encode`, `decode`
+/// and `print`
+#[derive(Args, Clone, Debug, Default)]
+pub struct RecursiveArgs {
+    /// Work on every .png file under this directory and its
+    /// subdirectories, in path order. Files without the PNG signature are
+    /// skipped with a warning
+    #[arg(long, value_name = "DIR", group = "batch")]
+    pub recursive: Option<PathBuf>,
+
+    /// Enter symbolic links to directories and read linked files, which
+    /// --recursive otherwise leaves out
+    #[arg(long, requires = "recursive")]
+    pub follow_symlinks: bool,
+
+    /// Skip the files and directories matching this pattern, e.g. 'vendor'
+    /// at any depth or 'icons/*.png' under DIR
+    #[arg(long, value_name = "PATTERN", requires = "recursive")]
+    pub exclude: Vec<String>,
+}
+
+impl RecursiveArgs {
+    /// The walk of `--recursive`, `None` without it
+    pub fn walk(&self) -> Option<PngFiles> {
+        let dir = self.recursive.as_ref()?;
+        let links = match self.follow_symlinks {
+            true => Links::Follow,
+            false => Links::Skip,
+        };
+        let exclude = self
+            .exclude
+            .iter()
+            .map(|pattern| pattern.parse().expect("patterns are validated"))
+            .collect();
+
+        Some(png_files(std::slice::from_ref(dir), true).links(links).exclude(exclude))
+    }
+}
+
+/// Developer tools, hidden from the help
+#[derive(Subcommand, Clone)]
+pub enum DebugCommands {
+    /// Time each stage of reading, parsing and writing an image
+    BenchParse {
+        /// Path, URL, data URI or `-` for stdin
+        file: InputSource,
+    },
+
+    /// Write a generated test fixture
+    MakeFixture {
+        /// Kind of fixture to generate
+        #[arg(value_enum)]
+        kind: FixtureKind,
+        /// Output file
+        output: PathBuf,
+    },
+
+    /// Print the CRC pngme computes for a chunk
+    Crc {
+        /// Chunk type
+        chunk_type: String,
+        /// Read the chunk data from this file
+        #[arg(long, conflicts_with = "data_hex")]
+        data_file: Option<PathBuf>,
+        /// Chunk data as hexadecimal digits, e.g. `48656c6c6f`
+        #[arg(long)]
+        data_hex: Option<HexBytes>,
+    },
+}
+
+/// Bytes given as hexadecimal digits on the command line, whitespace is ignored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexBytes(pub Vec<u8>);
+
+impl FromStr for HexBytes {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        decode_hex(value)
+            .map(HexBytes)
+            .map_err(|err| err.to_string())
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+/// Whether `args`, less the program name, ask for `--version --verbose`, in
+/// either order. clap prints the short version on its own for `--version`,
+/// so this is checked before parsing.
+pub fn is_verbose_version(args: &[OsString]) -> bool {
+    let args: Vec<_> = args.iter().skip(1).map(|arg| arg.to_str()).collect();
+
+    matches!(
+        args.as_slice(),
+        [Some("--version" | "-V"), Some("--verbose")] | [Some("--verbose"), Some("--version" | "-V")]
+    )
+}
+
+/// Splits the positionals of `decode` into inputs and chunk name.
+///
+/// Without `--chunk` the last positional is the chunk name. With it every
+/// positional is an input, but a last positional equal to the flag is
+/// rejected as the chunk name given twice.
+pub fn decode_inputs(
+    positionals: &[String],
+    chunk: &Option<String>,
+    recursive: bool,
+) -> Result<(Vec<InputSource>, String), clap::Error> {
+    let mut cmd = Arguments::command();
+
+    // The files come from the walk
+    if recursive {
+        return match (chunk, positionals) {
+            (Some(chunk), []) | (None, [chunk]) => Ok((Vec::new(), chunk.clone())),
+            _ => Err(cmd.error(
+                ErrorKind::ArgumentConflict,
+                "with --recursive, decode takes the chunk name only (positional or --chunk)",
+            )),
+        };
+    }
+
+    let (files, chunk_name) = match (chunk, positionals) {
+        (Some(chunk), [.., last]) if last == chunk => {
+            return Err(cmd.error(
+                ErrorKind::ArgumentConflict,
+                format!("the chunk name '{chunk}' is given both as a positional argument and with --chunk"),
+            ));
+        }
+        (Some(chunk), files) => (files, chunk.clone()),
+        (None, [files @ .., last]) if !files.is_empty() => (files, last.clone()),
+        (None, _) => {
+            return Err(cmd.error(
+                ErrorKind::MissingRequiredArgument,
+                "decode needs at least one file and a chunk name (positional or --chunk)",
+            ));
+        }
+    };
+
+    let files = files
+        .iter()
+        .map(|file| {
+            InputSource::parse(file).map_err(|err| {
+                cmd.error(
+                    ErrorKind::ValueValidation,
+                    format!("invalid input '{file}': {err}"),
+                )
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((files, chunk_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Commands, clap::Error> {
+        Arguments::try_parse_from(["pngme"].iter().chain(args)).map(|args| args.command)
+    }
+
+    #[derive(Parser)]
+    struct PasswordCli {
+        #[command(flatten)]
+        password: PasswordArgs,
+    }
+
+    fn password_source(args: &[&str]) -> Result<SecretSource, clap::Error> {
+        PasswordCli::try_parse_from(["pngme"].iter().chain(args)).map(|cli| cli.password.source())
+    }
+
+    fn decode(args: &[&str]) -> Result<(Vec<InputSource>, String), clap::Error> {
+        match parse(args)? {
+            Commands::Decode {
+                files,
+                chunk,
+                recursive,
+                ..
+            } => decode_inputs(&files, &chunk, recursive.recursive.is_some()),
+            _ => panic!("expected decode"),
+        }
+    }
+
+    #[test]
+    fn test_encode_aliases_and_styles() {
+        for name in ["encode", "write"] {
+            let positional = parse(&[name, "img.png", "ruSt", "hi", "out.png"]).unwrap();
+            let named = parse(&[
+                name,
+                "--chunk",
+                "ruSt",
+                "--message",
+                "hi",
+                "--output",
+                "out.png",
+                "img.png",
+            ])
+            .unwrap();
+
+            let Commands::Encode(EncodeArgs {
+                chunk_name,
+                message,
+                output,
+                ..
+            }) = positional
+            else {
+                panic!("expected encode");
+            };
+            assert_eq!(chunk_name.as_deref(), Some("ruSt"));
+            assert_eq!(message.as_deref(), Some("hi"));
+            assert_eq!(output, Some(PathBuf::from("out.png")));
+
+            let Commands::Encode(EncodeArgs {
+                chunk,
+                message_flag,
+                output_flag,
+                ..
+            }) = named
+            else {
+                panic!("expected encode");
+            };
+            assert_eq!(chunk.as_deref(), Some("ruSt"));
+            assert_eq!(message_flag.as_deref(), Some("hi"));
+            assert_eq!(output_flag, Some(PathBuf::from("out.png")));
+        }
+    }
+
+    #[test]
+    fn test_encode_ambiguous_chunk() {
+        let err = parse(&["write", "img.png", "ruSt", "hi", "--chunk", "ruSt"])
+            .err()
+            .unwrap();
+
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_encode_ambiguous_output() {
+        let err = parse(&[
+            "write", "img.png", "ruSt", "hi", "a.png", "--output", "b.png",
+        ])
+        .err()
+        .unwrap();
+
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_encode_missing_message() {
+        let err = parse(&["write", "img.png", "--chunk", "ruSt"])
+            .err()
+            .unwrap();
+
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn test_decode_aliases_and_styles() {
+        for name in ["decode", "read"] {
+            let (files, chunk) = decode(&[name, "a.png", "b.png", "ruSt"]).unwrap();
+            assert_eq!(files.len(), 2);
+            assert_eq!(chunk, "ruSt");
+
+            let (files, chunk) = decode(&[name, "--chunk", "ruSt", "a.png", "b.png"]).unwrap();
+            assert_eq!(files.len(), 2);
+            assert_eq!(chunk, "ruSt");
+        }
+    }
+
+    #[test]
+    fn test_decode_ambiguous_chunk() {
+        let err = decode(&["read", "a.png", "ruSt", "--chunk", "ruSt"])
+            .err()
+            .unwrap();
+
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_decode_missing_chunk() {
+        let err = decode(&["read", "a.png"]).err().unwrap();
+
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn test_decode_recursive_takes_the_chunk_name_only() {
+        for args in [
+            ["read", "--recursive", "assets", "ruSt"].as_slice(),
+            &["read", "--recursive", "assets", "--chunk", "ruSt"],
+        ] {
+            assert_eq!(decode(args).unwrap(), (Vec::new(), "ruSt".to_string()));
+        }
+
+        let err = decode(&["read", "--recursive", "assets", "a.png", "ruSt"])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        assert!(parse(&["read", "--exclude", "vendor", "a.png", "ruSt"]).is_err());
+    }
+
+    #[test]
+    fn test_remove_aliases_and_styles() {
+        for name in ["remove", "rm"] {
+            for args in [
+                [name, "img.png", "ruSt"].as_slice(),
+                &[name, "img.png", "--chunk", "ruSt"],
+            ] {
+                let Commands::Remove {
+                    chunk_name, chunk, ..
+                } = parse(args).unwrap()
+                else {
+                    panic!("expected remove");
+                };
+                assert_eq!(chunk_name.or(chunk).as_deref(), Some("ruSt"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_remove_ambiguous_chunk() {
+        let err = parse(&["rm", "img.png", "ruSt", "--chunk", "ruSt"])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+
+        let err = parse(&["rm", "img.png", "--chunk", "ruSt", "--at", "2"])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_parse_hex_bytes() {
+        assert_eq!("48656c 6C6f".parse(), Ok(HexBytes(b"Hello".to_vec())));
+        assert_eq!("".parse(), Ok(HexBytes(Vec::new())));
+        assert!("486".parse::<HexBytes>().is_err());
+        assert!("zz".parse::<HexBytes>().is_err());
+    }
+
+    #[test]
+    fn test_print_aliases() {
+        for name in ["print", "list", "ls"] {
+            assert!(matches!(
+                parse(&[name, "img.png"]).unwrap(),
+                Commands::Print { .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn test_password_sources() {
+        assert_eq!(password_source(&[]).unwrap(), SecretSource::Prompt);
+        assert_eq!(
+            password_source(&["--password", "secret"]).unwrap(),
+            SecretSource::Flag("secret".to_string())
+        );
+        assert_eq!(
+            password_source(&["--password-file", "secret.txt"]).unwrap(),
+            SecretSource::File(PathBuf::from("secret.txt"))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            password_source(&["--password-fd", "3"]).unwrap(),
+            SecretSource::Fd(3)
+        );
+    }
+
+    #[test]
+    fn test_password_sources_are_exclusive() {
+        let conflicts: &[&[&str]] = &[
+            &["--password", "secret", "--password-file", "secret.txt"],
+            #[cfg(unix)]
+            &["--password", "secret", "--password-fd", "3"],
+            #[cfg(unix)]
+            &["--password-file", "secret.txt", "--password-fd", "3"],
+        ];
+
+        for args in conflicts {
+            let err = password_source(args).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_verbose_version() {
+        let args = |args: &[&str]| -> Vec<OsString> {
+            ["pngme"].iter().chain(args).map(OsString::from).collect()
+        };
+
+        assert!(is_verbose_version(&args(&["--version", "--verbose"])));
+        assert!(is_verbose_version(&args(&["--verbose", "-V"])));
+        assert!(!is_verbose_version(&args(&["--version"])));
+        assert!(!is_verbose_version(&args(&["encode", "--version", "--verbose"])));
+    }
+}
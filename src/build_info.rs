@@ -0,0 +1,96 @@
+//! How this binary was built, printed by `pngme version` and
+//! `pngme --version --verbose`, listed by `capabilities` and added to the
+//! JSON errors, so a bug report says which build it is about.
+//!
+//! The target, profile, compiler and commit are recorded by `build.rs`.
+
+use std::fmt::{self, Display};
+
+use serde::Serialize;
+
+use crate::capabilities::FEATURES;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    /// Enabled cargo features
+    pub features: Vec<&'static str>,
+    /// Target triple, e.g. `x86_64-unknown-linux-gnu`
+    pub target: &'static str,
+    /// Output of `rustc --version`
+    pub rustc: &'static str,
+    /// Cargo profile, `debug` or `release`
+    pub profile: &'static str,
+    /// Git commit the binary was built from, unknown outside a checkout
+    pub commit: Option<&'static str>,
+}
+
+impl BuildInfo {
+    /// The build of this binary
+    pub fn current() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| *name)
+                .collect(),
+            target: env!("PNGME_TARGET"),
+            rustc: env!("PNGME_RUSTC_VERSION"),
+            profile: env!("PNGME_PROFILE"),
+            commit: Some(env!("PNGME_GIT_COMMIT")).filter(|commit| !commit.is_empty()),
+        }
+    }
+}
+
+impl Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", self.name, self.version)?;
+        let features = match self.features.as_slice() {
+            [] => "none".to_string(),
+            features => features.join(", "),
+        };
+        writeln!(f, "features: {features}")?;
+        writeln!(f, "target: {}", self.target)?;
+        writeln!(f, "rustc: {}", self.rustc)?;
+        writeln!(f, "profile: {}", self.profile)?;
+        write!(f, "commit: {}", self.commit.unwrap_or("unknown"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_build() {
+        let build = BuildInfo::current();
+
+        assert_eq!(build.version, env!("CARGO_PKG_VERSION"));
+        assert!(!build.target.is_empty());
+        assert!(build.rustc.starts_with("rustc "), "{}", build.rustc);
+        assert!(["debug", "release"].contains(&build.profile));
+        assert_eq!(build.features.contains(&"rayon"), cfg!(feature = "rayon"));
+    }
+
+    #[test]
+    fn test_display() {
+        let build = BuildInfo {
+            name: "pngme",
+            version: "1.2.3",
+            features: vec![],
+            target: "x86_64-unknown-linux-gnu",
+            rustc: "rustc 1.88.0",
+            profile: "release",
+            commit: None,
+        };
+
+        assert_eq!(
+            build.to_string(),
+            "pngme 1.2.3\nfeatures: none\ntarget: x86_64-unknown-linux-gnu\n\
+             rustc: rustc 1.88.0\nprofile: release\ncommit: unknown"
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Cache of downloaded images, for `--cache`.
+//!
+//! An entry is named after the SHA-256 of its URL: `<hash>.png` holds the
+//! body and `<hash>.json` the validators the server sent with it, its ETag
+//! and Last-Modified headers. Later downloads of the URL send them back
+//! (If-None-Match, If-Modified-Since) and reuse the body when the server
+//! answers 304 Not Modified. Entries not checked for longer than the maximum
+//! age are downloaded again without conditions.
+//!
+//! The cache is a convenience: an entry that can't be read is a miss, and
+//! one that can't be written only costs a download next time.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    codes::Code,
+    hash::sha256_hex,
+    sink::{FileSink, write_to_sink},
+};
+
+#[derive(Error, Debug)]
+pub enum CacheError {
+    #[error("There is no cache directory on this platform, pass --cache-dir")]
+    NoDirectory,
+
+    #[error("Could not clear the cache {}: {source}", dir.display())]
+    Io { dir: PathBuf, source: io::Error },
+}
+
+impl CacheError {
+    pub fn code(&self) -> Code {
+        match self {
+            CacheError::NoDirectory => Code::CacheUnavailable,
+            CacheError::Io { .. } => Code::CacheIoFailed,
+        }
+    }
+}
+
+/// Headers identifying a version of a body
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// What is known about a cached body, stored next to it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub url: String,
+    #[serde(flatten)]
+    pub validators: Validators,
+    /// Seconds since the epoch the body was downloaded or last confirmed
+    /// by the server
+    pub checked_at: u64,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl CacheEntry {
+    /// Whether the entry was checked `max_age` or longer before `now`
+    pub fn is_stale(&self, now: u64, max_age: Duration) -> bool {
+        now.saturating_sub(self.checked_at) >= max_age.as_secs()
+    }
+}
+
+/// A cache directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadCache {
+    pub dir: PathBuf,
+    /// Entries checked this long ago are downloaded again unconditionally
+    pub max_age: Duration,
+}
+
+impl DownloadCache {
+    pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// `pngme/downloads` under the cache directory of the platform
+    pub fn default_dir() -> Result<PathBuf, CacheError> {
+        dirs::cache_dir()
+            .map(|dir| dir.join("pngme").join("downloads"))
+            .ok_or(CacheError::NoDirectory)
+    }
+
+    fn paths(&self, url: &Url) -> (PathBuf, PathBuf) {
+        let name = sha256_hex(url.as_str().as_bytes());
+        (
+            self.dir.join(format!("{name}.png")),
+            self.dir.join(format!("{name}.json")),
+        )
+    }
+
+    /// The entry of `url` and its body, `None` when there is none or it is
+    /// damaged
+    pub fn lookup(&self, url: &Url) -> Option<(CacheEntry, Vec<u8>)> {
+        let (body_path, entry_path) = self.paths(url);
+        let entry: CacheEntry = serde_json::from_slice(&fs::read(entry_path).ok()?).ok()?;
+        let body = fs::read(body_path).ok()?;
+
+        (entry.url == url.as_str() && entry.sha256 == sha256_hex(&body)).then_some((entry, body))
+    }
+
+    /// Stores `body` as the current version of `url`, checked at `now`
+    pub fn store(
+        &self,
+        url: &Url,
+        validators: Validators,
+        body: &[u8],
+        now: u64,
+    ) -> io::Result<()> {
+        let (body_path, _) = self.paths(url);
+        fs::create_dir_all(&self.dir)?;
+        write_to_sink(&mut FileSink::new(&body_path), body)?;
+
+        self.save_entry(
+            url,
+            &CacheEntry {
+                url: url.to_string(),
+                validators,
+                checked_at: now,
+                size: body.len() as u64,
+                sha256: sha256_hex(body),
+            },
+        )
+    }
+
+    /// Rewrites the entry of `url`, e.g. once the server confirmed it
+    pub fn save_entry(&self, url: &Url, entry: &CacheEntry) -> io::Result<()> {
+        let (_, entry_path) = self.paths(url);
+        let json = serde_json::to_vec_pretty(entry)?;
+
+        write_to_sink(&mut FileSink::new(&entry_path), &json)
+    }
+
+    /// Removes every entry, returning how many there were. Other files in
+    /// the directory are left alone.
+    pub fn clear(dir: &Path) -> Result<usize, CacheError> {
+        let io_error = |source| CacheError::Io {
+            dir: dir.to_path_buf(),
+            source,
+        };
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(io_error(err)),
+        };
+
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry.map_err(io_error)?.path();
+            let is_entry = path.file_stem().is_some_and(|stem| {
+                stem.len() == 64
+                    && stem
+                        .to_string_lossy()
+                        .bytes()
+                        .all(|byte| byte.is_ascii_hexdigit())
+            });
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("json") if is_entry => {
+                    fs::remove_file(&path).map_err(io_error)?;
+                    removed += 1;
+                }
+                Some("png") if is_entry => fs::remove_file(&path).map_err(io_error)?,
+                _ => {}
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(dir: &Path) -> DownloadCache {
+        DownloadCache {
+            dir: dir.join("downloads"),
+            max_age: Duration::from_secs(60),
+        }
+    }
+
+    fn validators() -> Validators {
+        Validators {
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn test_store_and_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path());
+        let url = Url::parse("https://example.com/image.png").unwrap();
+
+        assert_eq!(cache.lookup(&url), None);
+        cache.store(&url, validators(), b"body", 1000).unwrap();
+
+        let (entry, body) = cache.lookup(&url).unwrap();
+        assert_eq!(body, b"body");
+        assert_eq!(entry.validators, validators());
+        assert!(!entry.is_stale(1059, cache.max_age));
+        assert!(entry.is_stale(1060, cache.max_age));
+
+        let other = Url::parse("https://example.com/other.png").unwrap();
+        assert_eq!(cache.lookup(&other), None);
+    }
+
+    #[test]
+    fn test_damaged_body_is_a_miss() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path());
+        let url = Url::parse("https://example.com/image.png").unwrap();
+        cache.store(&url, validators(), b"body", 1000).unwrap();
+
+        fs::write(cache.paths(&url).0, b"tampered").unwrap();
+
+        assert_eq!(cache.lookup(&url), None);
+    }
+
+    #[test]
+    fn test_clear_only_removes_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path());
+        for name in ["a", "b"] {
+            let url = Url::parse(&format!("https://example.com/{name}.png")).unwrap();
+            cache.store(&url, validators(), b"body", 1000).unwrap();
+        }
+        fs::write(cache.dir.join("notes.txt"), b"mine").unwrap();
+
+        assert_eq!(DownloadCache::clear(&cache.dir).unwrap(), 2);
+        let left: Vec<_> = fs::read_dir(&cache.dir).unwrap().collect();
+        assert_eq!(left.len(), 1);
+        assert_eq!(
+            DownloadCache::clear(&dir.path().join("missing")).unwrap(),
+            0
+        );
+    }
+}
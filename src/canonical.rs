@@ -0,0 +1,204 @@
+//! Canonical form of a file, written by `pngme canonicalize` so images kept
+//! in version control only change when their content does.
+//!
+//! Two files holding the same chunks canonicalize to the same bytes. The
+//! canonical form:
+//!
+//! - merges the IDAT chunks into a single one. The image data is one zlib
+//!   stream cut at arbitrary places, so the pixels are untouched.
+//! - orders the chunks as IHDR, the chunks required before PLTE, PLTE, the
+//!   chunks required before IDAT, IDAT, every other chunk, then IEND.
+//! - orders the chunks of each of these groups by type, then by data. For
+//!   textual chunks (tEXt, zTXt, iTXt), whose data starts with the keyword,
+//!   that is by keyword.
+//! - ends with a single empty IEND, without anything after it.
+//!
+//! Nothing else changes: the data of every other chunk is kept byte for
+//! byte, and the CRCs are computed again. Chunks pngme doesn't know have no
+//! ordering constraint it could follow, they go after IDAT. Animated images
+//! are refused, the order of their frame chunks is part of the animation.
+
+use std::{cmp::Ordering, str::FromStr};
+
+use thiserror::Error;
+
+use crate::{
+    chunk::{Chunk, ChunkParserError},
+    chunk_type::ChunkType,
+    codes::Code,
+    png::Png,
+};
+
+/// Ancillary chunks the specification requires before PLTE
+pub const BEFORE_PLTE: [&[u8; 4]; 8] = [
+    b"cHRM", b"cICP", b"cLLI", b"gAMA", b"iCCP", b"mDCV", b"sBIT", b"sRGB",
+];
+
+/// Ancillary chunks the specification requires before IDAT, after PLTE for
+/// those depending on the palette
+pub const BEFORE_IDAT: [&[u8; 4]; 6] = [b"bKGD", b"eXIf", b"hIST", b"pHYs", b"sPLT", b"tRNS"];
+
+/// Chunks of the APNG extension, found in animated images
+const ANIMATION: [&[u8; 4]; 3] = [b"acTL", b"fcTL", b"fdAT"];
+
+#[derive(Error, Debug)]
+pub enum CanonicalError {
+    #[error("The image has no IHDR chunk")]
+    MissingHeader,
+
+    #[error("The image is animated, the order of its frames can't be normalized")]
+    Animated,
+
+    #[error(transparent)]
+    Chunk(#[from] ChunkParserError),
+}
+
+impl CanonicalError {
+    pub fn code(&self) -> Code {
+        match self {
+            CanonicalError::MissingHeader => Code::MissingHeader,
+            CanonicalError::Animated => Code::AnimatedImage,
+            CanonicalError::Chunk(err) => err.code(),
+        }
+    }
+}
+
+/// The canonical form of `png`, see the module documentation
+pub fn canonicalize(png: &Png) -> Result<Png, CanonicalError> {
+    if png
+        .chunks()
+        .iter()
+        .any(|chunk| ANIMATION.contains(&&chunk.chunk_type().bytes()))
+    {
+        return Err(CanonicalError::Animated);
+    }
+    if png.chunk_by_type("IHDR").is_none() {
+        return Err(CanonicalError::MissingHeader);
+    }
+
+    let mut before_plte = Vec::new();
+    let mut before_idat = Vec::new();
+    let mut after_idat = Vec::new();
+    for chunk in png.chunks() {
+        let bytes = chunk.chunk_type().bytes();
+        match &bytes {
+            b"IHDR" | b"PLTE" | b"IDAT" | b"IEND" => {}
+            _ if BEFORE_PLTE.contains(&&bytes) => before_plte.push(chunk.clone()),
+            _ if BEFORE_IDAT.contains(&&bytes) => before_idat.push(chunk.clone()),
+            _ => after_idat.push(chunk.clone()),
+        }
+    }
+    for group in [&mut before_plte, &mut before_idat, &mut after_idat] {
+        group.sort_by(canonical_order);
+    }
+
+    let image_data: Vec<u8> = png
+        .chunks_by_type("IDAT")
+        .flat_map(|chunk| chunk.data())
+        .copied()
+        .collect();
+    let idat = match png.chunk_by_type("IDAT") {
+        Some(_) => Some(Chunk::try_new(chunk_type("IDAT"), image_data)?),
+        None => None,
+    };
+
+    let chunks = png
+        .chunks_by_type("IHDR")
+        .cloned()
+        .chain(before_plte)
+        .chain(png.chunks_by_type("PLTE").cloned())
+        .chain(before_idat)
+        .chain(idat)
+        .chain(after_idat)
+        .chain([Chunk::new(chunk_type("IEND"), Vec::new())])
+        .collect();
+
+    Ok(Png::from_chunks(chunks))
+}
+
+fn canonical_order(a: &Chunk, b: &Chunk) -> Ordering {
+    (a.chunk_type().bytes(), a.data()).cmp(&(b.chunk_type().bytes(), b.data()))
+}
+
+fn chunk_type(name: &str) -> ChunkType {
+    ChunkType::from_str(name).expect("standard chunk types are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(chunks: &[(&str, &[u8])]) -> Png {
+        Png::from_chunks(
+            chunks
+                .iter()
+                .map(|(name, data)| Chunk::new(chunk_type(name), data.to_vec()))
+                .collect(),
+        )
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        let png = png(&[
+            ("IHDR", b"header"),
+            ("tEXt", b"Title\0b"),
+            ("IDAT", b"ab"),
+            ("pHYs", b"phys"),
+            ("tEXt", b"Author\0a"),
+            ("PLTE", b"palette"),
+            ("gAMA", b"gama"),
+            ("IDAT", b"cd"),
+            ("IEND", b""),
+        ]);
+
+        let canonical = canonicalize(&png).unwrap();
+
+        assert_eq!(
+            types(&canonical),
+            [
+                "IHDR", "gAMA", "PLTE", "pHYs", "IDAT", "tEXt", "tEXt", "IEND"
+            ]
+        );
+        assert_eq!(canonical.chunks()[4].data(), b"abcd");
+        assert_eq!(canonical.chunks()[5].data(), b"Author\0a");
+    }
+
+    #[test]
+    fn test_canonical_form_is_stable() {
+        let png = png(&[
+            ("IHDR", b"header"),
+            ("IDAT", b"ab"),
+            ("ruSt", b"message"),
+            ("IEND", b""),
+        ]);
+
+        let canonical = canonicalize(&png).unwrap();
+
+        assert_eq!(
+            canonicalize(&canonical).unwrap().as_bytes(),
+            canonical.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_refused_images() {
+        let animated = png(&[("IHDR", b"header"), ("acTL", b"frames"), ("IEND", b"")]);
+        assert!(matches!(
+            canonicalize(&animated),
+            Err(CanonicalError::Animated)
+        ));
+
+        let headless = png(&[("IDAT", b"ab"), ("IEND", b"")]);
+        assert!(matches!(
+            canonicalize(&headless),
+            Err(CanonicalError::MissingHeader)
+        ));
+    }
+}
@@ -0,0 +1,251 @@
+//! What this build of pngme supports, printed by `pngme capabilities` for
+//! wrapper tools that shouldn't parse `--help`.
+//!
+//! The commands and flags are read from the clap [`Command`] tree, so they
+//! can't drift from what the parser accepts.
+
+use std::fmt::{self, Display};
+
+use clap::Command;
+use serde::Serialize;
+
+use crate::{build_info::BuildInfo, codes::Code, envelope, exit_status::ExitStatus};
+
+/// Cargo features and whether they are enabled in this build
+pub const FEATURES: [(&str, bool); 4] = [
+    ("archives", cfg!(feature = "archives")),
+    ("keyring", cfg!(feature = "keyring")),
+    ("rayon", cfg!(feature = "rayon")),
+    ("server", cfg!(feature = "server")),
+];
+
+/// Kinds of inputs accepted where a command reads an image
+pub const INPUT_SCHEMES: &[&str] = &[
+    "path",
+    "stdin",
+    "http",
+    "https",
+    "data",
+    #[cfg(feature = "archives")]
+    "archive",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,
+    /// Enabled cargo features
+    pub features: Vec<&'static str>,
+    /// Target, compiler, profile and commit of this build
+    pub build: BuildInfo,
+    pub input_schemes: Vec<&'static str>,
+    /// Versions of [`envelope::Envelope`] that can be read and written
+    pub envelope_versions: Vec<u8>,
+    /// Statuses pngme exits with, see [`ExitStatus`]
+    pub exit_statuses: Vec<ExitStatusInfo>,
+    /// Every error and warning code with the status it exits with, see
+    /// [`ExitStatus::of`]
+    pub codes: Vec<CodeInfo>,
+    /// Flags accepted by every command
+    pub global_flags: Vec<FlagInfo>,
+    pub commands: Vec<CommandInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExitStatusInfo {
+    pub code: i32,
+    pub name: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CodeInfo {
+    pub code: Code,
+    pub name: &'static str,
+    /// The number of the [`ExitStatus`] of the code
+    pub exit_status: i32,
+    pub status: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandInfo {
+    pub name: String,
+    pub about: Option<String>,
+    /// Names of the positional arguments, in order
+    pub arguments: Vec<String>,
+    pub flags: Vec<FlagInfo>,
+    pub subcommands: Vec<CommandInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlagInfo {
+    /// Long name, without the dashes
+    pub long: String,
+    pub short: Option<char>,
+    pub aliases: Vec<String>,
+    /// Whether the flag is followed by a value
+    pub takes_value: bool,
+}
+
+impl Capabilities {
+    /// Capabilities of this build, with the commands of `command` (the
+    /// top-level parser). Hidden commands and flags are left out.
+    pub fn of(command: &Command) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| *name)
+                .collect(),
+            build: BuildInfo::current(),
+            input_schemes: INPUT_SCHEMES.to_vec(),
+            envelope_versions: vec![envelope::VERSION],
+            exit_statuses: ExitStatus::ALL
+                .iter()
+                .map(|status| ExitStatusInfo {
+                    code: status.code(),
+                    name: status.name(),
+                })
+                .collect(),
+            codes: Code::ALL
+                .iter()
+                .map(|&code| {
+                    let status = ExitStatus::of(code);
+                    CodeInfo {
+                        code,
+                        name: code.name(),
+                        exit_status: status.code(),
+                        status: status.name(),
+                    }
+                })
+                .collect(),
+            global_flags: flags(command),
+            commands: subcommands(command),
+        }
+    }
+}
+
+fn subcommands(command: &Command) -> Vec<CommandInfo> {
+    command
+        .get_subcommands()
+        .filter(|command| !command.is_hide_set())
+        .map(|command| CommandInfo {
+            name: command.get_name().to_string(),
+            about: command.get_about().map(ToString::to_string),
+            arguments: command
+                .get_positionals()
+                .filter(|arg| !arg.is_hide_set())
+                .map(|arg| arg.get_id().to_string())
+                .collect(),
+            flags: flags(command),
+            subcommands: subcommands(command),
+        })
+        .collect()
+}
+
+fn flags(command: &Command) -> Vec<FlagInfo> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+        .filter_map(|arg| {
+            Some(FlagInfo {
+                long: arg.get_long()?.to_string(),
+                short: arg.get_short(),
+                aliases: arg
+                    .get_visible_aliases()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+                takes_value: arg.get_action().takes_values(),
+            })
+        })
+        .collect()
+}
+
+impl Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "pngme {}", self.version)?;
+        writeln!(f, "Features: {}", list(&self.features))?;
+        writeln!(
+            f,
+            "Built for {} ({}) with {}",
+            self.build.target, self.build.profile, self.build.rustc
+        )?;
+        writeln!(f, "Inputs: {}", self.input_schemes.join(", "))?;
+        writeln!(f, "Envelope versions: {}", list(&self.envelope_versions))?;
+        writeln!(f, "Exit statuses:")?;
+        for status in &self.exit_statuses {
+            writeln!(f, "  {} {}", status.code, status.name)?;
+        }
+        write!(f, "Commands:")?;
+        for command in &self.commands {
+            write!(f, "\n  {}", command.name)?;
+            for subcommand in &command.subcommands {
+                write!(f, "\n  {} {}", command.name, subcommand.name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn list(items: &[impl ToString]) -> String {
+    match items {
+        [] => "none".to_string(),
+        _ => items
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Arg, ArgAction};
+
+    use super::*;
+
+    #[test]
+    fn test_commands_from_the_clap_tree() {
+        let command = Command::new("tool")
+            .arg(
+                Arg::new("verbose")
+                    .long("verbose")
+                    .action(ArgAction::SetTrue),
+            )
+            .subcommand(
+                Command::new("copy")
+                    .about("Copy a file")
+                    .arg(Arg::new("file"))
+                    .arg(
+                        Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .visible_alias("out"),
+                    )
+                    .arg(Arg::new("secret").long("secret").hide(true)),
+            )
+            .subcommand(Command::new("internal").hide(true));
+
+        let capabilities = Capabilities::of(&command);
+
+        assert_eq!(capabilities.global_flags.len(), 1);
+        assert!(!capabilities.global_flags[0].takes_value);
+        assert_eq!(
+            capabilities.commands,
+            [CommandInfo {
+                name: "copy".to_string(),
+                about: Some("Copy a file".to_string()),
+                arguments: vec!["file".to_string()],
+                flags: vec![FlagInfo {
+                    long: "output".to_string(),
+                    short: Some('o'),
+                    aliases: vec!["out".to_string()],
+                    takes_value: true,
+                }],
+                subcommands: Vec::new(),
+            }]
+        );
+    }
+}
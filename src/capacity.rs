@@ -0,0 +1,172 @@
+//! How much data an image can take, reported by `pngme capacity`.
+//!
+//! A chunk holds at most [`MAX_CHUNK_DATA`] bytes, so a larger payload must
+//! be split with `encode --split-size`, every piece then carrying a
+//! [`split::HEADER_LEN`]-byte header. The payload size is the data as stored
+//! in the chunks, after any compression or encryption.
+
+use std::fmt::{self, Display};
+
+use serde::Serialize;
+
+use crate::{
+    consts::{CHUNK_OVERHEAD, MAX_CHUNK_DATA},
+    split,
+    upload_limits::SizeThreshold,
+};
+
+/// Largest piece of a split payload a chunk can hold
+pub const MAX_PIECE: u64 = MAX_CHUNK_DATA as u64 - split::HEADER_LEN as u64;
+
+/// What embedding a payload takes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PayloadPlan {
+    pub payload_size: u64,
+    /// Chunks the payload is written to
+    pub chunks: u64,
+    /// Whether the payload is larger than a chunk can hold
+    pub split: bool,
+    /// Bytes the payload adds to the file, chunk framing included
+    pub added: u64,
+}
+
+impl PayloadPlan {
+    /// The plan for a `payload_size`-byte payload, in as few chunks as
+    /// possible
+    pub fn new(payload_size: u64) -> Self {
+        let split = payload_size > MAX_CHUNK_DATA as u64;
+        let (chunks, header) = match split {
+            true => (payload_size.div_ceil(MAX_PIECE), split::HEADER_LEN as u64),
+            false => (1, 0),
+        };
+
+        Self {
+            payload_size,
+            chunks,
+            split,
+            added: payload_size.saturating_add(chunks * (CHUNK_OVERHEAD as u64 + header)),
+        }
+    }
+}
+
+/// The report of `pngme capacity`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Capacity {
+    /// Size of the image
+    pub size: u64,
+    /// Chunks of the image
+    pub chunks: usize,
+    /// Largest data a chunk can hold
+    pub max_chunk_data: u32,
+    pub payload: Option<PayloadPlan>,
+    /// Size of the image once the payload is embedded
+    pub projected_size: u64,
+    pub max_size: Option<u64>,
+    /// Whether the projected size is over `max_size`
+    pub exceeds_max_size: bool,
+}
+
+impl Capacity {
+    pub fn new(size: u64, chunks: usize, payload_size: Option<u64>, max_size: Option<u64>) -> Self {
+        let payload = payload_size.map(PayloadPlan::new);
+        let projected_size = size.saturating_add(payload.as_ref().map_or(0, |plan| plan.added));
+
+        Self {
+            size,
+            chunks,
+            max_chunk_data: MAX_CHUNK_DATA,
+            payload,
+            projected_size,
+            max_size,
+            exceeds_max_size: max_size.is_some_and(|max_size| projected_size > max_size),
+        }
+    }
+}
+
+impl Display for Capacity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Size: {} bytes in {} chunk(s)", self.size, self.chunks)?;
+        write!(f, "Largest chunk data: {} bytes", self.max_chunk_data)?;
+
+        if let Some(plan) = &self.payload {
+            write!(f, "\nPayload of {} bytes: ", plan.payload_size)?;
+            match plan.split {
+                true => write!(f, "{} chunks, split with encode --split-size", plan.chunks)?,
+                false => write!(f, "1 chunk, no split needed")?,
+            }
+            write!(
+                f,
+                "\nProjected size: {} bytes (+{})",
+                self.projected_size, plan.added
+            )?;
+        }
+        if let Some(max_size) = self.max_size {
+            let verdict = match self.exceeds_max_size {
+                true => "Over",
+                false => "Within",
+            };
+            write!(
+                f,
+                "\n{verdict} the maximum size of {}",
+                SizeThreshold::custom(max_size)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_fitting_a_chunk() {
+        let plan = PayloadPlan::new(5000);
+        assert_eq!(plan.chunks, 1);
+        assert!(!plan.split);
+        assert_eq!(plan.added, 5012);
+
+        let plan = PayloadPlan::new(MAX_CHUNK_DATA as u64);
+        assert_eq!(plan.chunks, 1);
+        assert!(!plan.split);
+
+        assert_eq!(PayloadPlan::new(0).added, 12, "an empty chunk");
+    }
+
+    #[test]
+    fn test_payload_needing_a_split() {
+        let plan = PayloadPlan::new(MAX_CHUNK_DATA as u64 + 1);
+        assert_eq!(plan.chunks, 2);
+        assert!(plan.split);
+        assert_eq!(plan.added, MAX_CHUNK_DATA as u64 + 1 + 2 * (12 + 14));
+
+        assert_eq!(PayloadPlan::new(MAX_PIECE * 3).chunks, 3);
+        assert_eq!(PayloadPlan::new(MAX_PIECE * 3 + 1).chunks, 4);
+        assert_eq!(PayloadPlan::new(u64::MAX).added, u64::MAX);
+    }
+
+    #[test]
+    fn test_max_size() {
+        assert!(!Capacity::new(1000, 5, Some(88), Some(1100)).exceeds_max_size);
+        assert!(Capacity::new(1000, 5, Some(89), Some(1100)).exceeds_max_size);
+        assert!(Capacity::new(1000, 5, None, Some(999)).exceeds_max_size);
+        assert!(!Capacity::new(1000, 5, Some(1 << 40), None).exceeds_max_size);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Capacity::new(1000, 5, Some(4096), Some(1 << 20)).to_string(),
+            "Size: 1000 bytes in 5 chunk(s)\n\
+             Largest chunk data: 2147483647 bytes\n\
+             Payload of 4096 bytes: 1 chunk, no split needed\n\
+             Projected size: 5108 bytes (+4108)\n\
+             Within the maximum size of 1 MiB"
+        );
+        assert_eq!(
+            Capacity::new(1000, 5, None, None).to_string(),
+            "Size: 1000 bytes in 5 chunk(s)\nLargest chunk data: 2147483647 bytes"
+        );
+    }
+}
@@ -1,15 +1,23 @@
-use crate::chunk_type::{ChunkType, ChunkTypeError};
+use crate::{
+    chunk_type::{ChunkNameError, ChunkType, ChunkTypeError},
+    codes::Code,
+    consts::{CHUNK_OVERHEAD, LENGTH_FIELD, MAX_CHUNK_DATA},
+    sanitize::escape_for_terminal,
+    text::{
+        ITXT_CHUNK_TYPE, ItxtChunk, TEXT_CHUNK_TYPE, TextError, ZTXT_CHUNK_TYPE, ZtxtChunk,
+        latin1_decode, split_text_chunk,
+    },
+};
 use crc::Crc;
 use std::{
     fmt::Display,
     io::{self, BufReader, Read},
+    str::FromStr,
     string::FromUtf8Error,
 };
 use thiserror::Error;
 
-const MIN_CHUNK_SIZE: u32 = 12;
-
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Chunk {
     data: Vec<u8>,
     chunk_type: ChunkType,
@@ -17,17 +25,19 @@ pub struct Chunk {
 }
 
 impl Chunk {
-    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
-        const CRC_ALG: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
-
-        let crc_bytes: Vec<u8> = chunk_type
-            .bytes()
-            .iter()
-            .chain(data.iter())
-            .cloned()
-            .collect();
+    /// Largest data length the specification allows (2^31 - 1 bytes)
+    pub const MAX_LENGTH: u32 = MAX_CHUNK_DATA;
 
-        let crc = CRC_ALG.checksum(&crc_bytes);
+    /// Builds a chunk, computing its CRC.
+    ///
+    /// Panics if `data` is longer than [`Chunk::MAX_LENGTH`], use
+    /// [`Chunk::try_new`] for data of arbitrary size.
+    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
+        assert!(
+            data.len() as u64 <= Self::MAX_LENGTH as u64,
+            "chunk data exceeds Chunk::MAX_LENGTH"
+        );
+        let crc = Self::compute_crc(&chunk_type, &data);
 
         Self {
             data,
@@ -36,10 +46,38 @@ impl Chunk {
         }
     }
 
+    /// Same as [`Chunk::new`], failing when `data` is too long for a chunk
+    pub fn try_new(chunk_type: ChunkType, data: Vec<u8>) -> Result<Self, ChunkParserError> {
+        if data.len() as u64 > Self::MAX_LENGTH as u64 {
+            return Err(ChunkParserError::TooLarge {
+                length: data.len() as u64,
+            });
+        }
+
+        Ok(Self::new(chunk_type, data))
+    }
+
+    /// CRC-32 of the chunk type followed by the data, as stored after the
+    /// chunk data in a PNG file
+    pub fn compute_crc(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+        const CRC_ALG: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+        let mut digest = CRC_ALG.digest();
+        digest.update(&chunk_type.bytes());
+        digest.update(data);
+        digest.finalize()
+    }
+
     pub fn length(&self) -> u32 {
+        // Fits, `new` caps the data at MAX_LENGTH
         self.data.len() as u32
     }
 
+    /// Zero-length chunks are valid, `data_as_string` alone can't tell them apart
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     pub fn chunk_type(&self) -> &ChunkType {
         &self.chunk_type
     }
@@ -56,9 +94,45 @@ impl Chunk {
         String::from_utf8(self.data.clone())
     }
 
+    /// The data as text: tEXt chunks are Latin-1 per the spec, any other
+    /// chunk is expected to hold UTF-8.
+    pub fn data_as_text(&self) -> Option<String> {
+        if self.chunk_type.bytes() == TEXT_CHUNK_TYPE {
+            Some(latin1_decode(&self.data))
+        } else {
+            self.data_as_string().ok()
+        }
+    }
+
+    /// Keyword and text of a tEXt chunk
+    pub fn text_keyword_and_value(&self) -> Option<(String, String)> {
+        if self.chunk_type.bytes() != TEXT_CHUNK_TYPE {
+            return None;
+        }
+
+        split_text_chunk(&self.data)
+    }
+
+    /// Fields of an iTXt chunk
+    pub fn itxt(&self) -> Option<ItxtChunk<'_>> {
+        if self.chunk_type.bytes() != ITXT_CHUNK_TYPE {
+            return None;
+        }
+
+        ItxtChunk::parse(&self.data)
+    }
+
+    /// Fields of a zTXt chunk, an error when they are malformed
+    pub fn ztxt(&self) -> Option<Result<ZtxtChunk<'_>, TextError>> {
+        if self.chunk_type.bytes() != ZTXT_CHUNK_TYPE {
+            return None;
+        }
+
+        Some(ZtxtChunk::parse(&self.data))
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
-        let length = self.data().len() as u32;
-        length
+        self.length()
             .to_be_bytes()
             .iter()
             .chain(self.chunk_type.bytes().iter())
@@ -78,7 +152,10 @@ pub enum ChunkParserError {
     Incomplete,
 
     #[error("invalid length field (expected {expected:?}, found {found:?})")]
-    InvalidLengthField { expected: u32, found: u32 },
+    InvalidLengthField { expected: u64, found: u32 },
+
+    #[error("chunk data of {length} bytes exceeds the supported size ({} bytes)", Chunk::MAX_LENGTH)]
+    TooLarge { length: u64 },
 
     #[error(transparent)]
     InvalidChunkType(#[from] ChunkTypeError),
@@ -87,19 +164,45 @@ pub enum ChunkParserError {
     InvalidChecksum,
 }
 
+impl ChunkParserError {
+    pub fn code(&self) -> Code {
+        match self {
+            ChunkParserError::ReaderError(_) => Code::ChunkReadFailed,
+            ChunkParserError::Incomplete => Code::IncompleteChunk,
+            ChunkParserError::InvalidLengthField { .. } => Code::InvalidLengthField,
+            ChunkParserError::TooLarge { .. } => Code::ChunkDataTooLarge,
+            ChunkParserError::InvalidChunkType(err) => err.code(),
+            ChunkParserError::InvalidChecksum => Code::ChecksumMismatch,
+        }
+    }
+}
+
 impl TryFrom<&[u8]> for Chunk {
     type Error = ChunkParserError;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        // length (4) + type (4) + data (0) + crc (4) => 12
-        // 12 is the smallest chunk that can exist. By checking the length
-        // beforehand we can ensure that there will be no panics.
-        if value.len() < 12 {
+        let (chunk, crc) = Chunk::parse_unchecked(value)?;
+
+        if crc != chunk.crc() {
+            return Err(ChunkParserError::InvalidChecksum);
+        }
+
+        Ok(chunk)
+    }
+}
+
+impl Chunk {
+    /// Parses a chunk without checking its CRC, returning it along with the
+    /// CRC stored in `value`. The chunk itself carries the computed CRC.
+    pub fn parse_unchecked(value: &[u8]) -> Result<(Chunk, u32), ChunkParserError> {
+        // An empty chunk is the smallest that can exist. By checking the
+        // length beforehand we can ensure that there will be no panics.
+        if value.len() < CHUNK_OVERHEAD {
             return Err(ChunkParserError::Incomplete);
         }
 
         let mut reader = BufReader::new(value);
-        let mut buffer: [u8; 4] = [0, 0, 0, 0];
+        let mut buffer = [0u8; LENGTH_FIELD];
 
         // The bytes are represented as follows:
         // +-------------+------------+-------------------+---------+
@@ -112,9 +215,17 @@ impl TryFrom<&[u8]> for Chunk {
         reader.read_exact(&mut buffer)?;
         let data_lenght = u32::from_be_bytes(buffer);
 
-        if (value.len() as u32) - MIN_CHUNK_SIZE != data_lenght {
+        if data_lenght > Chunk::MAX_LENGTH {
+            return Err(ChunkParserError::TooLarge {
+                length: data_lenght as u64,
+            });
+        }
+
+        // In u64: slices longer than u32::MAX must not wrap around
+        let expected = (value.len() - CHUNK_OVERHEAD) as u64;
+        if expected != data_lenght as u64 {
             return Err(ChunkParserError::InvalidLengthField {
-                expected: value.len() as u32 - 12,
+                expected,
                 found: data_lenght,
             });
         }
@@ -131,13 +242,7 @@ impl TryFrom<&[u8]> for Chunk {
         reader.read_exact(&mut buffer)?;
         let crc = u32::from_be_bytes(buffer);
 
-        let chunk = Self::new(chunk_type, data_buffer);
-
-        if crc != chunk.crc() {
-            return Err(ChunkParserError::InvalidChecksum);
-        }
-
-        Ok(chunk)
+        Ok((Self::new(chunk_type, data_buffer), crc))
     }
 }
 
@@ -145,47 +250,195 @@ impl Display for Chunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{{ length: {} type: {}, data: {}, crc {:10} }}",
+            "{{ length: {} type: {{ {}: {} }}, data: {}, crc {:10} }}",
             self.length(),
             self.chunk_type,
-            self.data_as_string()
-                .unwrap_or_else(|_| "<Invalid UTF-8>".to_string()),
+            self.chunk_type.properties(),
+            self.text_keyword_and_value()
+                .map(|(keyword, text)| format!("{keyword}: {text}"))
+                .or_else(|| self.data_as_text())
+                .map(|text| escape_for_terminal(&text))
+                .unwrap_or_else(|| "<Invalid UTF-8>".to_string()),
             self.crc
         )
     }
 }
 
+/// Why a compact chunk spec (`TYPE:hex` or `TYPE:"text"`) doesn't parse.
+/// Columns count characters from 1.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ChunkSpecError {
+    #[error("Expected TYPE:DATA, '{spec}' has no ':' after the chunk type")]
+    MissingSeparator { spec: String },
+
+    #[error(transparent)]
+    ChunkName(#[from] ChunkNameError),
+
+    #[error("Odd number of hex digits ({digits}), the last byte is missing a digit")]
+    OddHexLength { digits: usize },
+
+    #[error("'{character}' at column {column} is not a hex digit (quote the data to write text)")]
+    InvalidHexDigit { column: usize, character: char },
+
+    #[error("Unescaped quote at column {column}, write \\\" for a quote inside the text")]
+    UnescapedQuote { column: usize },
+
+    #[error("The text opened at column {column} has no closing quote")]
+    UnterminatedText { column: usize },
+
+    #[error("Unknown escape '\\{character}' at column {column}, expected \\\", \\\\, \\n, \\r or \\t")]
+    InvalidEscape { column: usize, character: char },
+
+    #[error("chunk data of {length} bytes exceeds the supported size ({} bytes)", Chunk::MAX_LENGTH)]
+    TooLarge { length: u64 },
+}
+
+impl ChunkSpecError {
+    pub fn code(&self) -> Code {
+        match self {
+            ChunkSpecError::MissingSeparator { .. } => Code::ChunkSpecSeparator,
+            ChunkSpecError::ChunkName(err) => err.code(),
+            ChunkSpecError::OddHexLength { .. } => Code::ChunkSpecOddHex,
+            ChunkSpecError::InvalidHexDigit { .. } => Code::ChunkSpecHexDigit,
+            ChunkSpecError::UnescapedQuote { .. } => Code::ChunkSpecUnescapedQuote,
+            ChunkSpecError::UnterminatedText { .. } => Code::ChunkSpecUnterminated,
+            ChunkSpecError::InvalidEscape { .. } => Code::ChunkSpecEscape,
+            ChunkSpecError::TooLarge { .. } => Code::ChunkDataTooLarge,
+        }
+    }
+}
+
+impl Chunk {
+    /// The chunk as `TYPE:"text"` when its data is UTF-8 without control
+    /// characters other than line breaks and tabs, else as `TYPE:hex`.
+    /// [`Chunk::from_str`] reads it back.
+    pub fn to_compact_string(&self) -> String {
+        let text = std::str::from_utf8(&self.data)
+            .ok()
+            .filter(|text| text.chars().all(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t')));
+
+        match text {
+            Some(text) => {
+                let mut quoted = String::with_capacity(text.len() + 2);
+                for c in text.chars() {
+                    match c {
+                        '"' => quoted.push_str("\\\""),
+                        '\\' => quoted.push_str("\\\\"),
+                        '\n' => quoted.push_str("\\n"),
+                        '\r' => quoted.push_str("\\r"),
+                        '\t' => quoted.push_str("\\t"),
+                        c => quoted.push(c),
+                    }
+                }
+                format!("{}:\"{quoted}\"", self.chunk_type)
+            }
+            None => {
+                let hex: String = self.data.iter().map(|byte| format!("{byte:02x}")).collect();
+                format!("{}:{hex}", self.chunk_type)
+            }
+        }
+    }
+}
+
+/// Bytes of the hex digits of a spec starting at `column`
+fn parse_spec_hex(digits: &str, column: usize) -> Result<Vec<u8>, ChunkSpecError> {
+    let values = digits
+        .chars()
+        .enumerate()
+        .map(|(offset, character)| {
+            character
+                .to_digit(16)
+                .map(|value| value as u8)
+                .ok_or(ChunkSpecError::InvalidHexDigit {
+                    column: column + offset,
+                    character,
+                })
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+    if !values.len().is_multiple_of(2) {
+        return Err(ChunkSpecError::OddHexLength { digits: values.len() });
+    }
+
+    Ok(values.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+}
+
+/// Bytes of the quoted text of a spec, the opening quote being at `column`
+fn parse_spec_text(quoted: &str, column: usize) -> Result<Vec<u8>, ChunkSpecError> {
+    let mut text = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars().enumerate().skip(1);
+
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '"' => {
+                return match chars.next() {
+                    None => Ok(text.into_bytes()),
+                    // The quote that looked closing was inside the text
+                    Some(_) => Err(ChunkSpecError::UnescapedQuote {
+                        column: column + offset,
+                    }),
+                };
+            }
+            '\\' => {
+                let Some((_, escaped)) = chars.next() else {
+                    break;
+                };
+                text.push(match escaped {
+                    '"' => '"',
+                    '\\' => '\\',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    character => {
+                        return Err(ChunkSpecError::InvalidEscape {
+                            column: column + offset,
+                            character,
+                        });
+                    }
+                });
+            }
+            c => text.push(c),
+        }
+    }
+
+    Err(ChunkSpecError::UnterminatedText { column })
+}
+
+impl FromStr for Chunk {
+    type Err = ChunkSpecError;
+
+    /// Parses the form of [`Chunk::to_compact_string`], computing the CRC.
+    /// Hex digits may be uppercase.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (name, data) = spec.split_once(':').ok_or_else(|| ChunkSpecError::MissingSeparator {
+            spec: spec.to_string(),
+        })?;
+        let chunk_type = ChunkType::parse_name(name)?;
+        // Columns of the data, past the type and the colon
+        let column = name.chars().count() + 2;
+
+        let data = match data.starts_with('"') {
+            true => parse_spec_text(data, column)?,
+            false => parse_spec_hex(data, column)?,
+        };
+        let length = data.len() as u64;
+        Chunk::try_new(chunk_type, data).map_err(|_| ChunkSpecError::TooLarge { length })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::chunk_type::ChunkType;
+    use crate::consts::{CRC_FIELD, DATA_OFFSET};
+    use crate::test_fixtures::{
+        TESTING_MESSAGE, testing_chunk, testing_chunk_bytes, testing_chunk_type,
+    };
     use std::str::FromStr;
 
-    fn testing_chunk() -> Chunk {
-        let data_length: u32 = 42;
-        let chunk_type = "RuSt".as_bytes();
-        let message_bytes = "This is where your secret message will be!".as_bytes();
-        let crc: u32 = 2882656334;
-
-        let chunk_data: Vec<u8> = data_length
-            .to_be_bytes()
-            .iter()
-            .chain(chunk_type.iter())
-            .chain(message_bytes.iter())
-            .chain(crc.to_be_bytes().iter())
-            .copied()
-            .collect();
-
-        Chunk::try_from(chunk_data.as_ref()).unwrap()
-    }
-
     #[test]
     fn test_new_chunk() {
-        let chunk_type = ChunkType::from_str("RuSt").unwrap();
-        let data = "This is where your secret message will be!"
-            .as_bytes()
-            .to_vec();
-        let chunk = Chunk::new(chunk_type, data);
+        let data = TESTING_MESSAGE.as_bytes().to_vec();
+        let chunk = Chunk::new(testing_chunk_type(), data);
         assert_eq!(chunk.length(), 42);
         assert_eq!(chunk.crc(), 2882656334);
     }
@@ -196,6 +449,21 @@ mod tests {
         assert_eq!(chunk.length(), 42);
     }
 
+    #[test]
+    fn test_framing_sizes() {
+        let chunk = testing_chunk();
+        let bytes = chunk.as_bytes();
+
+        assert_eq!(bytes.len(), chunk.length() as usize + CHUNK_OVERHEAD);
+        assert_eq!(&bytes[DATA_OFFSET..bytes.len() - CRC_FIELD], chunk.data());
+        assert!(matches!(
+            Chunk::try_from(&bytes[..CHUNK_OVERHEAD - 1]),
+            Err(ChunkParserError::Incomplete)
+        ));
+        let empty = Chunk::new(*chunk.chunk_type(), Vec::new());
+        assert_eq!(empty.as_bytes().len(), CHUNK_OVERHEAD);
+    }
+
     #[test]
     fn test_chunk_type() {
         let chunk = testing_chunk();
@@ -206,10 +474,40 @@ mod tests {
     fn test_chunk_string() {
         let chunk = testing_chunk();
         let chunk_string = chunk.data_as_string().unwrap();
-        let expected_chunk_string = String::from("This is where your secret message will be!");
+        let expected_chunk_string = String::from(TESTING_MESSAGE);
         assert_eq!(chunk_string, expected_chunk_string);
     }
 
+    #[test]
+    fn test_empty_chunk() {
+        let chunk = Chunk::new(testing_chunk_type(), Vec::new());
+        assert!(chunk.is_empty());
+        assert_eq!(chunk.length(), 0);
+        assert_eq!(chunk.data_as_string().unwrap(), "");
+
+        let parsed = Chunk::try_from(chunk.as_bytes().as_ref()).unwrap();
+        assert_eq!(parsed, chunk);
+        assert!(!testing_chunk().is_empty());
+    }
+
+    #[test]
+    fn test_text_chunk_is_latin1() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("tEXt").unwrap(),
+            vec![b'T', b'i', b't', b'l', b'e', 0, b'c', b'a', b'f', 0xE9],
+        );
+        assert!(chunk.data_as_string().is_err());
+        assert_eq!(chunk.data_as_text().unwrap(), "Title\0café");
+        assert_eq!(
+            chunk.text_keyword_and_value(),
+            Some(("Title".to_string(), "café".to_string()))
+        );
+        assert!(chunk.to_string().contains("data: Title: café,"));
+
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0xE9]);
+        assert!(chunk.data_as_text().is_none());
+    }
+
     #[test]
     fn test_chunk_crc() {
         let chunk = testing_chunk();
@@ -217,25 +515,28 @@ mod tests {
     }
 
     #[test]
-    fn test_valid_chunk_from_bytes() {
-        let data_length: u32 = 42;
-        let chunk_type = "RuSt".as_bytes();
-        let message_bytes = "This is where your secret message will be!".as_bytes();
-        let crc: u32 = 2882656334;
+    fn test_compute_crc() {
+        let data = TESTING_MESSAGE.as_bytes();
 
-        let chunk_data: Vec<u8> = data_length
-            .to_be_bytes()
-            .iter()
-            .chain(chunk_type.iter())
-            .chain(message_bytes.iter())
-            .chain(crc.to_be_bytes().iter())
-            .copied()
-            .collect();
+        assert_eq!(Chunk::compute_crc(&testing_chunk_type(), data), 2882656334);
+    }
+
+    #[test]
+    fn test_compute_crc_empty_data() {
+        // The CRC every PNG file ends with
+        let chunk_type = ChunkType::from_str("IEND").unwrap();
+
+        assert_eq!(Chunk::compute_crc(&chunk_type, &[]), 0xAE42_6082);
+    }
+
+    #[test]
+    fn test_valid_chunk_from_bytes() {
+        let chunk_data = testing_chunk_bytes();
 
         let chunk = Chunk::try_from(chunk_data.as_ref()).unwrap();
 
         let chunk_string = chunk.data_as_string().unwrap();
-        let expected_chunk_string = String::from("This is where your secret message will be!");
+        let expected_chunk_string = String::from(TESTING_MESSAGE);
 
         assert_eq!(chunk.length(), 42);
         assert_eq!(chunk.chunk_type().to_string(), String::from("RuSt"));
@@ -245,43 +546,123 @@ mod tests {
 
     #[test]
     fn test_invalid_chunk_from_bytes() {
-        let data_length: u32 = 42;
-        let chunk_type = "RuSt".as_bytes();
-        let message_bytes = "This is where your secret message will be!".as_bytes();
-        let crc: u32 = 2882656333;
-
-        let chunk_data: Vec<u8> = data_length
-            .to_be_bytes()
-            .iter()
-            .chain(chunk_type.iter())
-            .chain(message_bytes.iter())
-            .chain(crc.to_be_bytes().iter())
-            .copied()
-            .collect();
+        let mut chunk_data = testing_chunk_bytes();
+        *chunk_data.last_mut().unwrap() ^= 1;
 
         let chunk = Chunk::try_from(chunk_data.as_ref());
 
         assert!(chunk.is_err());
     }
 
+    /// A `ruSt` chunk framed by hand, holding `data` whose length field
+    /// says `length`, with a zero CRC
+    fn chunk_with_length_field(length: u32, data: &[u8]) -> Vec<u8> {
+        [&length.to_be_bytes()[..], b"ruSt", data, &0u32.to_be_bytes()].concat()
+    }
+
     #[test]
-    pub fn test_chunk_trait_impls() {
-        let data_length: u32 = 42;
-        let chunk_type = "RuSt".as_bytes();
-        let message_bytes = "This is where your secret message will be!".as_bytes();
-        let crc: u32 = 2882656334;
+    fn test_length_field_above_spec_limit() {
+        for length in [Chunk::MAX_LENGTH + 1, u32::MAX] {
+            let bytes = chunk_with_length_field(length, b"");
+
+            assert!(matches!(
+                Chunk::parse_unchecked(&bytes),
+                Err(ChunkParserError::TooLarge { length: found }) if found == length as u64
+            ));
+        }
+    }
 
-        let chunk_data: Vec<u8> = data_length
-            .to_be_bytes()
-            .iter()
-            .chain(chunk_type.iter())
-            .chain(message_bytes.iter())
-            .chain(crc.to_be_bytes().iter())
-            .copied()
-            .collect();
+    #[test]
+    fn test_length_field_at_spec_limit() {
+        let bytes = chunk_with_length_field(Chunk::MAX_LENGTH, b"data");
+
+        assert!(matches!(
+            Chunk::parse_unchecked(&bytes),
+            Err(ChunkParserError::InvalidLengthField {
+                expected: 4,
+                found: Chunk::MAX_LENGTH
+            })
+        ));
+    }
+
+    #[test]
+    fn test_length_field_mismatch() {
+        let bytes = chunk_with_length_field(3, b"data");
+
+        assert!(matches!(
+            Chunk::parse_unchecked(&bytes),
+            Err(ChunkParserError::InvalidLengthField {
+                expected: 4,
+                found: 3
+            })
+        ));
+    }
+
+    #[test]
+    pub fn test_chunk_trait_impls() {
+        let chunk_data = testing_chunk_bytes();
 
         let chunk: Chunk = TryFrom::try_from(chunk_data.as_ref()).unwrap();
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    fn test_compact_string_round_trips() {
+        let chunks = [
+            Chunk::new(ChunkType::from_str("noTe").unwrap(), b"hello".to_vec()),
+            Chunk::new(ChunkType::from_str("noTe").unwrap(), b"say \"hi\"\n\tC:\\".to_vec()),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0x00, 0xff, 0x41]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), Vec::new()),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), "caf\u{e9} \u{1f980}".into()),
+        ];
+        let compact = [
+            r#"noTe:"hello""#,
+            r#"noTe:"say \"hi\"\n\tC:\\""#,
+            "ruSt:00ff41",
+            r#"ruSt:"""#,
+            "ruSt:\"caf\u{e9} \u{1f980}\"",
+        ];
+
+        for (chunk, compact) in chunks.iter().zip(compact) {
+            assert_eq!(chunk.to_compact_string(), compact);
+            assert_eq!(&Chunk::from_str(compact).unwrap(), chunk);
+        }
+        // Control characters other than line breaks and tabs go in hex
+        let bell = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"a\x07".to_vec());
+        assert_eq!(bell.to_compact_string(), "ruSt:6107");
+        assert_eq!(Chunk::from_str("ruSt:6107").unwrap(), bell);
+    }
+
+    #[test]
+    fn test_compact_string_parse_computes_the_crc() {
+        let chunk = Chunk::from_str("RuSt:7468697320697320612074657374").unwrap();
+        let expected = Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"this is a test".to_vec());
+
+        assert_eq!(chunk.crc(), expected.crc());
+        assert_eq!(Chunk::from_str("RuSt:7468697320697320612074657374").unwrap(), Chunk::from_str(r#"RuSt:"this is a test""#).unwrap());
+        assert_eq!(Chunk::from_str("ruSt:00FF").unwrap().data(), [0x00, 0xff]);
+    }
+
+    #[test]
+    fn test_compact_string_errors() {
+        let err = |spec: &str| Chunk::from_str(spec).unwrap_err();
+
+        assert_eq!(err("noTe"), ChunkSpecError::MissingSeparator { spec: "noTe".to_string() });
+        assert_eq!(err("noT:00").code(), Code::InvalidNameLength);
+        assert_eq!(err("ruSt:abc"), ChunkSpecError::OddHexLength { digits: 3 });
+        assert_eq!(err("ruSt:0g"), ChunkSpecError::InvalidHexDigit { column: 7, character: 'g' });
+        assert_eq!(err("ruSt:00 ff"), ChunkSpecError::InvalidHexDigit { column: 8, character: ' ' });
+        assert_eq!(err(r#"noTe:"say "hi"""#), ChunkSpecError::UnescapedQuote { column: 11 });
+        assert_eq!(err(r#"noTe:"hello"!"#), ChunkSpecError::UnescapedQuote { column: 12 });
+        assert_eq!(err(r#"noTe:"hello"#), ChunkSpecError::UnterminatedText { column: 6 });
+        assert_eq!(err(r#"noTe:"hello\""#), ChunkSpecError::UnterminatedText { column: 6 });
+        assert_eq!(err(r#"noTe:"a\qb""#), ChunkSpecError::InvalidEscape { column: 8, character: 'q' });
+
+        assert_eq!(
+            err(r#"noTe:"say "hi"""#).to_string(),
+            r#"Unescaped quote at column 11, write \" for a quote inside the text"#
+        );
+        assert_eq!(err("ruSt:abc").code(), Code::ChunkSpecOddHex);
+    }
 }
@@ -0,0 +1,264 @@
+//! Chunks borrowed from the bytes of a file, for commands that only look at
+//! each chunk once and don't need to build a [`Png`].
+
+use crate::{
+    chunk::{Chunk, ChunkParserError},
+    chunk_type::ChunkType,
+    consts::{CHUNK_OVERHEAD, CRC_FIELD, DATA_OFFSET, LENGTH_FIELD, SIGNATURE_LEN, TYPE_FIELD},
+    png::{Png, PngError, PngParserError, check_header, chunk_span},
+};
+
+/// A chunk whose data points into the parsed bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRef<'a> {
+    pub chunk_type: ChunkType,
+    pub data: &'a [u8],
+    /// CRC stored in the file, only checked when asked to
+    pub crc: u32,
+    /// Offset of the chunk (its length field) from the start of the file
+    pub offset: u64,
+}
+
+impl ChunkRef<'_> {
+    pub fn length(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    /// CRC of the chunk type and data, to compare with the stored `crc`
+    pub fn computed_crc(&self) -> u32 {
+        Chunk::compute_crc(&self.chunk_type, self.data)
+    }
+
+    /// Copies the data into an owned [`Chunk`]
+    pub fn to_owned(&self) -> Chunk {
+        Chunk::new(self.chunk_type, self.data.to_vec())
+    }
+}
+
+/// Iterator over the chunks of a file, see [`chunk_refs`]
+#[derive(Debug, Clone)]
+pub struct ChunkRefs<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    verify_crc: bool,
+    max_chunks: usize,
+    /// Chunks read so far
+    count: usize,
+    failed: bool,
+}
+
+/// Walks the chunks of `bytes` without copying their data.
+///
+/// Chunks are checked like [`Png::parse`] does, except the CRCs which are
+/// only compared with `verify_crc`, and a chunk past `max_chunks` is an
+/// error as with [`ParseOptions::max_chunks`](crate::png::ParseOptions).
+/// Iteration stops after the first error, and a few bytes too short to hold
+/// a chunk after the last one are ignored.
+pub fn chunk_refs(bytes: &[u8], verify_crc: bool, max_chunks: usize) -> Result<ChunkRefs<'_>, PngError> {
+    check_header(bytes)?;
+
+    Ok(ChunkRefs {
+        bytes,
+        offset: SIGNATURE_LEN,
+        verify_crc,
+        max_chunks,
+        count: 0,
+        failed: false,
+    })
+}
+
+impl<'a> ChunkRefs<'a> {
+    fn read_chunk(&self) -> Result<ChunkRef<'a>, PngError> {
+        let rest = &self.bytes[self.offset..];
+        let truncated = |needed: usize| {
+            PngError::ParserError(PngParserError::Truncated {
+                offset: self.offset as u64,
+                needed: needed as u64,
+                available: rest.len() as u64,
+            })
+        };
+
+        let length_field: [u8; LENGTH_FIELD] = rest
+            .get(..LENGTH_FIELD)
+            .ok_or(truncated(CHUNK_OVERHEAD))?
+            .try_into()
+            .unwrap();
+        let span = chunk_span(
+            self.offset as u64,
+            u32::from_be_bytes(length_field),
+            rest.len() as u64,
+        )?;
+        // At most MAX_CHUNK_DATA + CHUNK_OVERHEAD, and no more than `rest`
+        // holds
+        let needed = (span.end - span.start) as usize;
+
+        let type_field: [u8; TYPE_FIELD] = rest[LENGTH_FIELD..DATA_OFFSET].try_into().unwrap();
+        let chunk_type = ChunkType::try_from(type_field)
+            .map_err(|err| PngParserError::InvalidChunk(err.into()))?;
+        let crc_start = needed - CRC_FIELD;
+        let crc = u32::from_be_bytes(rest[crc_start..needed].try_into().unwrap());
+        let chunk = ChunkRef {
+            chunk_type,
+            data: &rest[DATA_OFFSET..crc_start],
+            crc,
+            offset: self.offset as u64,
+        };
+
+        if self.verify_crc && chunk.computed_crc() != crc {
+            return Err(PngParserError::InvalidChunk(ChunkParserError::InvalidChecksum).into());
+        }
+
+        Ok(chunk)
+    }
+}
+
+impl<'a> Iterator for ChunkRefs<'a> {
+    type Item = Result<ChunkRef<'a>, PngError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let remaining = self.bytes.len() - self.offset;
+        if self.failed || (self.count > 0 && remaining < LENGTH_FIELD) {
+            return None;
+        }
+
+        let result = match self.count == self.max_chunks {
+            true => Err(PngError::ParserError(PngParserError::TooManyChunks {
+                limit: self.max_chunks,
+            })),
+            false => self.read_chunk(),
+        };
+        match &result {
+            Ok(chunk) => {
+                self.offset += chunk.data.len() + CHUNK_OVERHEAD;
+                self.count += 1;
+            }
+            Err(_) => self.failed = true,
+        }
+
+        Some(result)
+    }
+}
+
+impl Png {
+    /// Borrowed views of the chunks, with their offset in [`Png::as_bytes`]
+    pub fn chunk_refs(&self) -> impl Iterator<Item = ChunkRef<'_>> {
+        self.chunks()
+            .iter()
+            .scan(SIGNATURE_LEN as u64, |offset, chunk| {
+                let chunk_ref = ChunkRef {
+                    chunk_type: *chunk.chunk_type(),
+                    data: chunk.data(),
+                    crc: chunk.crc(),
+                    offset: *offset,
+                };
+                *offset += (chunk.data().len() + CHUNK_OVERHEAD) as u64;
+                Some(chunk_ref)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{FixtureKind, make_fixture, make_large};
+    use crate::png::ParseOptions;
+
+    fn collect(bytes: &[u8], verify_crc: bool) -> Result<Vec<ChunkRef<'_>>, PngError> {
+        chunk_refs(bytes, verify_crc, ParseOptions::DEFAULT_MAX_CHUNKS)?.collect()
+    }
+
+    #[test]
+    fn test_borrowed_matches_owned() {
+        let bytes = make_fixture(FixtureKind::Apng);
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+
+        let borrowed = collect(&bytes, true).unwrap();
+        let owned: Vec<ChunkRef> = png.chunk_refs().collect();
+
+        assert_eq!(borrowed, owned);
+        assert_eq!(
+            borrowed.iter().map(ChunkRef::to_owned).collect::<Vec<_>>(),
+            png.chunks()
+        );
+        assert_eq!(borrowed[0].offset, SIGNATURE_LEN as u64);
+        let last = borrowed.last().unwrap();
+        assert_eq!(last.offset + CHUNK_OVERHEAD as u64, bytes.len() as u64);
+    }
+
+    #[test]
+    fn test_crc_is_only_checked_on_demand() {
+        let bytes = make_fixture(FixtureKind::CorruptCrc);
+
+        assert!(matches!(
+            collect(&bytes, true),
+            Err(PngError::ParserError(PngParserError::InvalidChunk(
+                ChunkParserError::InvalidChecksum
+            )))
+        ));
+
+        let chunks = collect(&bytes, false).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_ne!(chunks[1].crc, chunks[1].computed_crc());
+    }
+
+    #[test]
+    fn test_truncated_stops_iteration() {
+        let bytes = make_fixture(FixtureKind::Truncated);
+        let results: Vec<_> = chunk_refs(&bytes, true, ParseOptions::DEFAULT_MAX_CHUNKS).unwrap().collect();
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(PngError::ParserError(PngParserError::Truncated { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_max_chunks() {
+        let bytes = make_fixture(FixtureKind::Apng);
+        let count = collect(&bytes, true).unwrap().len();
+
+        assert_eq!(chunk_refs(&bytes, true, count).unwrap().count(), count);
+        let results: Vec<_> = chunk_refs(&bytes, true, count - 1).unwrap().collect();
+        assert_eq!(results.len(), count);
+        assert!(matches!(
+            results[count - 1],
+            Err(PngError::ParserError(PngParserError::TooManyChunks { limit })) if limit == count - 1
+        ));
+    }
+
+    #[test]
+    fn test_degenerate_headers() {
+        assert!(matches!(
+            chunk_refs(b"", true, ParseOptions::DEFAULT_MAX_CHUNKS),
+            Err(PngError::ParserError(PngParserError::Empty))
+        ));
+        assert!(matches!(
+            chunk_refs(&Png::STANDARD_HEADER, true, ParseOptions::DEFAULT_MAX_CHUNKS),
+            Err(PngError::ParserError(PngParserError::NoChunks))
+        ));
+        assert!(matches!(
+            collect(&[&Png::STANDARD_HEADER[..], &[0, 0]].concat(), true),
+            Err(PngError::ParserError(PngParserError::Truncated {
+                offset,
+                needed,
+                available: 2
+            })) if offset == SIGNATURE_LEN as u64 && needed == CHUNK_OVERHEAD as u64
+        ));
+    }
+
+    #[test]
+    fn test_large_file_borrows_without_copies() {
+        let bytes = make_large(16 << 20, 8).as_bytes();
+        let range = bytes.as_ptr_range();
+
+        let chunks = collect(&bytes, false).unwrap();
+
+        assert_eq!(chunks.len(), 10);
+        for chunk in &chunks {
+            let data = chunk.data.as_ptr_range();
+            assert!(range.start <= data.start && data.end <= range.end);
+        }
+    }
+}
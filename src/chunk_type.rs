@@ -1,7 +1,11 @@
 use core::{convert::TryFrom, str::FromStr};
+use rand_core::RngCore;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::{self};
 use thiserror::Error;
 
+use crate::codes::Code;
+
 #[derive(Error, Debug)]
 pub enum ChunkTypeError {
     #[error("The chunk type is not ASCII letters")]
@@ -11,6 +15,47 @@ pub enum ChunkTypeError {
     InvalidNameLenght { expected: u8, actual: usize },
 }
 
+impl ChunkTypeError {
+    pub fn code(&self) -> Code {
+        match self {
+            ChunkTypeError::NotASCIILetters => Code::NotAsciiLetters,
+            ChunkTypeError::InvalidNameLenght { .. } => Code::InvalidTypeLength,
+        }
+    }
+}
+
+/// Diagnostics for a chunk name typed by the user, pointing at what is wrong
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ChunkNameError {
+    #[error("Chunk name '{name}' has {chars} characters ({bytes} bytes), expected 4 ASCII letters")]
+    Length {
+        name: String,
+        chars: usize,
+        bytes: usize,
+    },
+
+    #[error(
+        "Chunk name '{name}' must only contain ASCII letters\n  {name}\n  {:>width$} '{character}' is not an ASCII letter",
+        "^",
+        width = .position + 1
+    )]
+    NotLetter {
+        name: String,
+        /// Position of the offending character, in characters
+        position: usize,
+        character: char,
+    },
+}
+
+impl ChunkNameError {
+    pub fn code(&self) -> Code {
+        match self {
+            ChunkNameError::Length { .. } => Code::InvalidNameLength,
+            ChunkNameError::NotLetter { .. } => Code::InvalidNameCharacter,
+        }
+    }
+}
+
 enum ChunkTypeProperties {
     Ancillary = 0,
     Private = 1,
@@ -18,7 +63,24 @@ enum ChunkTypeProperties {
     SafeToCopy = 3,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// Standard ancillary chunks that change how the image renders: removing
+/// them shifts its colors, background or transparency. Other ancillary
+/// chunks (text, time, private...) are safe to remove.
+pub const RENDERING_CHUNK_TYPES: [&[u8; 4]; 7] =
+    [b"gAMA", b"sRGB", b"cHRM", b"iCCP", b"sBIT", b"bKGD", b"tRNS"];
+
+/// Critical chunks defined by the specification. Decoders must reject a
+/// file holding any other critical chunk.
+pub const CRITICAL_CHUNK_TYPES: [&[u8; 4]; 4] = [b"IHDR", b"PLTE", b"IDAT", b"IEND"];
+
+/// Ancillary chunks defined by the specification and its APNG extension
+pub const STANDARD_ANCILLARY_CHUNK_TYPES: [&[u8; 4]; 21] = [
+    b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP", b"mDCV", b"cLLI", b"bKGD", b"hIST",
+    b"tRNS", b"eXIf", b"pHYs", b"sPLT", b"tIME", b"iTXt", b"tEXt", b"zTXt", b"acTL", b"fcTL",
+    b"fdAT",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ChunkType {
     bytes: [u8; 4],
 }
@@ -60,6 +122,36 @@ impl ChunkType {
     pub fn is_safe_to_copy(&self) -> bool {
         !Self::bit_is_zero(self.bytes[ChunkTypeProperties::SafeToCopy as usize])
     }
+
+    /// Whether this is one of the [`RENDERING_CHUNK_TYPES`]
+    pub fn affects_rendering(&self) -> bool {
+        RENDERING_CHUNK_TYPES.contains(&&self.bytes)
+    }
+
+    /// One of the [`CRITICAL_CHUNK_TYPES`] or [`STANDARD_ANCILLARY_CHUNK_TYPES`]
+    pub fn is_standard(&self) -> bool {
+        CRITICAL_CHUNK_TYPES.contains(&&self.bytes)
+            || STANDARD_ANCILLARY_CHUNK_TYPES.contains(&&self.bytes)
+    }
+
+    /// Critical but not one of the [`CRITICAL_CHUNK_TYPES`]
+    pub fn is_unknown_critical(&self) -> bool {
+        self.is_critical() && !CRITICAL_CHUNK_TYPES.contains(&&self.bytes)
+    }
+}
+
+/// A random index into the 26 letters. Bytes from 234 (9 × 26) up are
+/// drawn again, they would make the first 22 letters likelier.
+fn random_letter(rng: &mut impl RngCore) -> u8 {
+    const LIMIT: u8 = 26 * 9;
+
+    loop {
+        let mut byte = [0];
+        rng.fill_bytes(&mut byte);
+        if byte[0] < LIMIT {
+            return byte[0] % 26;
+        }
+    }
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
@@ -87,10 +179,61 @@ impl FromStr for ChunkType {
     }
 }
 
-impl fmt::Display for ChunkType {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let name = String::from_utf8(self.bytes.to_vec()).unwrap();
+impl ChunkType {
+    /// Parses a chunk name typed by the user, with a diagnostic pointing at
+    /// the offending character on failure.
+    pub fn parse_name(name: &str) -> Result<ChunkType, ChunkNameError> {
+        let chars = name.chars().count();
+        if chars != 4 {
+            return Err(ChunkNameError::Length {
+                name: name.to_string(),
+                chars,
+                bytes: name.len(),
+            });
+        }
+
+        if let Some((position, character)) = name
+            .chars()
+            .enumerate()
+            .find(|(_, character)| !character.is_ascii_alphabetic())
+        {
+            return Err(ChunkNameError::NotLetter {
+                name: name.to_string(),
+                position,
+                character,
+            });
+        }
+
+        Ok(ChunkType::from_str(name).expect("four ASCII letters"))
+    }
+
+    /// Nearest valid type, differing only by the case of the reserved
+    /// (third) letter, or `None` if the type is already valid.
+    pub fn suggested_casing(&self) -> Option<ChunkType> {
+        if self.is_valid() {
+            return None;
+        }
 
+        let mut bytes = self.bytes;
+        let reserved = ChunkTypeProperties::Reserved as usize;
+        bytes[reserved] = bytes[reserved].to_ascii_uppercase();
+
+        Some(ChunkType { bytes })
+    }
+
+    /// A random ancillary, private and safe-to-copy type with a valid
+    /// reserved bit, lowercase but for the third letter like `xxXx`. Every
+    /// letter is equally likely.
+    pub fn new_private_safe(rng: &mut impl RngCore) -> ChunkType {
+        let mut bytes = [0; 4].map(|_| b'a' + random_letter(rng));
+        let reserved = ChunkTypeProperties::Reserved as usize;
+        bytes[reserved] = bytes[reserved].to_ascii_uppercase();
+
+        ChunkType { bytes }
+    }
+
+    /// Human readable summary of the property bits carried by the type name
+    pub fn properties(&self) -> String {
         let ancillary = if self.is_critical() {
             "critical"
         } else {
@@ -115,7 +258,59 @@ impl fmt::Display for ChunkType {
             "unsafe to copy"
         };
 
-        write!(f, "{{ {name}: {ancillary}, {private}, {valid}, {safe_to_copy} }}")
+        format!("{ancillary}, {private}, {valid}, {safe_to_copy}")
+    }
+}
+
+impl fmt::Display for ChunkType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Only types deserialized from their byte form can hold something
+        // other than letters, escape it rather than failing.
+        for byte in self.bytes {
+            if byte.is_ascii_alphabetic() {
+                write!(f, "{}", byte as char)?;
+            } else {
+                write!(f, "\\x{byte:02x}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fallback representation for types that aren't four ASCII letters
+#[derive(Serialize, Deserialize)]
+struct ChunkTypeBytes {
+    bytes: [u8; 4],
+}
+
+/// A chunk type is serialized as its four raw ASCII characters (`"RuSt"`),
+/// or as `{"bytes": [..]}` when the bytes aren't all ASCII letters. Both
+/// forms deserialize back to the exact same bytes.
+impl Serialize for ChunkType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.bytes.iter().all(u8::is_ascii_alphabetic) {
+            let name = std::str::from_utf8(&self.bytes).expect("ASCII letters are valid UTF-8");
+            serializer.serialize_str(name)
+        } else {
+            ChunkTypeBytes { bytes: self.bytes }.serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ChunkType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Name(String),
+            Bytes(ChunkTypeBytes),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Name(name) => ChunkType::from_str(&name).map_err(serde::de::Error::custom),
+            Repr::Bytes(ChunkTypeBytes { bytes }) => Ok(ChunkType { bytes }),
+        }
     }
 }
 
@@ -125,6 +320,8 @@ mod tests {
     use std::convert::TryFrom;
     use std::str::FromStr;
 
+    use crate::test_fixtures::testing_chunk_type;
+
     #[test]
     pub fn test_chunk_type_from_bytes() {
         let expected = [82, 117, 83, 116];
@@ -142,7 +339,7 @@ mod tests {
 
     #[test]
     pub fn test_chunk_type_is_critical() {
-        let chunk = ChunkType::from_str("RuSt").unwrap();
+        let chunk = testing_chunk_type();
         assert!(chunk.is_critical());
     }
 
@@ -160,13 +357,13 @@ mod tests {
 
     #[test]
     pub fn test_chunk_type_is_not_public() {
-        let chunk = ChunkType::from_str("RuSt").unwrap();
+        let chunk = testing_chunk_type();
         assert!(!chunk.is_public());
     }
 
     #[test]
     pub fn test_chunk_type_is_reserved_bit_valid() {
-        let chunk = ChunkType::from_str("RuSt").unwrap();
+        let chunk = testing_chunk_type();
         assert!(chunk.is_reserved_bit_valid());
     }
 
@@ -178,7 +375,7 @@ mod tests {
 
     #[test]
     pub fn test_chunk_type_is_safe_to_copy() {
-        let chunk = ChunkType::from_str("RuSt").unwrap();
+        let chunk = testing_chunk_type();
         assert!(chunk.is_safe_to_copy());
     }
 
@@ -190,7 +387,7 @@ mod tests {
 
     #[test]
     pub fn test_valid_chunk_is_valid() {
-        let chunk = ChunkType::from_str("RuSt").unwrap();
+        let chunk = testing_chunk_type();
         assert!(chunk.is_valid());
     }
 
@@ -205,10 +402,197 @@ mod tests {
 
     #[test]
     pub fn test_chunk_type_string() {
-        let chunk = ChunkType::from_str("RuSt").unwrap();
+        let chunk = testing_chunk_type();
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub fn test_chunk_type_json_is_raw_name() {
+        let chunk = testing_chunk_type();
+        let json = serde_json::to_string(&chunk).unwrap();
+
+        assert_eq!(json, "\"RuSt\"");
+        assert_eq!(serde_json::from_str::<ChunkType>(&json).unwrap(), chunk);
+    }
+
+    #[test]
+    pub fn test_chunk_type_json_reserved_bit() {
+        let chunk = ChunkType::from_str("Rust").unwrap();
+        let json = serde_json::to_string(&chunk).unwrap();
+
+        assert_eq!(json, "\"Rust\"");
+        assert_eq!(
+            serde_json::from_str::<ChunkType>(&json).unwrap().bytes(),
+            *b"Rust"
+        );
+    }
+
+    #[test]
+    pub fn test_chunk_type_json_bytes_fallback() {
+        let chunk = ChunkType {
+            bytes: [b'R', b'"', 0, 0xFF],
+        };
+        let json = serde_json::to_string(&chunk).unwrap();
+
+        assert_eq!(json, r#"{"bytes":[82,34,0,255]}"#);
+        assert_eq!(serde_json::from_str::<ChunkType>(&json).unwrap(), chunk);
+        assert_eq!(chunk.to_string(), "R\\x22\\x00\\xff");
+    }
+
+    #[test]
+    pub fn test_chunk_type_json_rejects_invalid_name() {
+        assert!(serde_json::from_str::<ChunkType>("\"Ru1t\"").is_err());
+        assert!(serde_json::from_str::<ChunkType>("\"RuStX\"").is_err());
+    }
+
+    #[test]
+    pub fn test_parse_name_caret_under_offending_character() {
+        let err = ChunkType::parse_name("ru5t").unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Chunk name 'ru5t' must only contain ASCII letters\n  ru5t\n    ^ '5' is not an ASCII letter"
+        );
+    }
+
+    #[test]
+    pub fn test_parse_name_caret_counts_characters() {
+        let err = ChunkType::parse_name("éRSt").unwrap_err();
+
+        assert_eq!(
+            err,
+            ChunkNameError::NotLetter {
+                name: "éRSt".to_string(),
+                position: 0,
+                character: 'é',
+            }
+        );
+        assert!(err.to_string().ends_with("\n  éRSt\n  ^ 'é' is not an ASCII letter"));
+    }
+
+    #[test]
+    pub fn test_parse_name_length() {
+        assert_eq!(
+            ChunkType::parse_name("ruSté").unwrap_err().to_string(),
+            "Chunk name 'ruSté' has 5 characters (6 bytes), expected 4 ASCII letters"
+        );
+        assert!(matches!(
+            ChunkType::parse_name("ruS"),
+            Err(ChunkNameError::Length { chars: 3, bytes: 3, .. })
+        ));
+    }
+
+    #[test]
+    pub fn test_parse_name_valid() {
+        assert_eq!(ChunkType::parse_name("RuSt").unwrap().to_string(), "RuSt");
+    }
+
+    #[test]
+    pub fn test_suggested_casing() {
+        for (name, expected) in [("Rust", "RuSt"), ("rust", "ruSt"), ("RUsT", "RUST"), ("tEXT", "tEXT")] {
+            let chunk_type = ChunkType::from_str(name).unwrap();
+            let suggestion = chunk_type.suggested_casing().map_or(name.to_string(), |t| t.to_string());
+
+            assert_eq!(suggestion, expected, "suggestion for {name}");
+        }
+    }
+
+    #[test]
+    pub fn test_affects_rendering() {
+        for name in ["gAMA", "sRGB", "cHRM", "iCCP", "sBIT", "bKGD", "tRNS"] {
+            assert!(ChunkType::from_str(name).unwrap().affects_rendering(), "{name}");
+        }
+        for name in ["tEXt", "tIME", "pHYs", "ruSt", "IDAT"] {
+            assert!(!ChunkType::from_str(name).unwrap().affects_rendering(), "{name}");
+        }
+    }
+
+    #[test]
+    pub fn test_is_standard() {
+        for name in ["IHDR", "IEND", "tEXt", "pHYs", "fcTL"] {
+            assert!(ChunkType::from_str(name).unwrap().is_standard(), "{name}");
+        }
+        for name in ["ruSt", "TEXT", "XXXX"] {
+            assert!(!ChunkType::from_str(name).unwrap().is_standard(), "{name}");
+        }
+    }
+
+    #[test]
+    pub fn test_suggested_casing_of_valid_type() {
+        assert_eq!(ChunkType::from_str("RuSt").unwrap().suggested_casing(), None);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_chunk_type_json_round_trip(bytes in proptest::array::uniform4(
+            proptest::sample::select(
+                (b'A'..=b'Z').chain(b'a'..=b'z').collect::<Vec<u8>>()
+            )
+        )) {
+            let chunk = ChunkType::try_from(bytes).unwrap();
+            let json = serde_json::to_string(&chunk).unwrap();
+
+            proptest::prop_assert_eq!(&json, &format!("\"{chunk}\""));
+            proptest::prop_assert_eq!(serde_json::from_str::<ChunkType>(&json).unwrap().bytes(), bytes);
+            proptest::prop_assert_eq!(ChunkType::from_str(&chunk.to_string()).unwrap().bytes(), bytes);
+        }
+
+        #[test]
+        fn test_chunk_type_bytes_fallback_round_trip(bytes in proptest::array::uniform4(proptest::num::u8::ANY)) {
+            let chunk = ChunkType { bytes };
+            let json = serde_json::to_string(&chunk).unwrap();
+
+            proptest::prop_assert_eq!(serde_json::from_str::<ChunkType>(&json).unwrap().bytes(), bytes);
+        }
+    }
+
+    #[test]
+    pub fn test_new_private_safe() {
+        let mut rng = rand_core::OsRng;
+        for _ in 0..1000 {
+            let chunk_type = ChunkType::new_private_safe(&mut rng);
+
+            assert!(chunk_type.is_valid(), "{chunk_type}");
+            assert!(!chunk_type.is_critical(), "{chunk_type}");
+            assert!(!chunk_type.is_public(), "{chunk_type}");
+            assert!(chunk_type.is_safe_to_copy(), "{chunk_type}");
+            assert!(ChunkType::parse_name(&chunk_type.to_string()).is_ok());
+        }
+    }
+
+    /// Returns the given bytes in turn
+    struct ReplayRng(Vec<u8>);
+
+    impl RngCore for ReplayRng {
+        fn next_u32(&mut self) -> u32 {
+            rand_core::impls::next_u32_via_fill(self)
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            rand_core::impls::next_u64_via_fill(self)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for byte in dest {
+                *byte = self.0.remove(0);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    pub fn test_new_private_safe_draws_again_above_the_last_full_alphabet() {
+        let mut rng = ReplayRng(vec![255, 0, 234, 233, 25, 26]);
+
+        // 255 and 234 would have been 'v' and 'a' again
+        assert_eq!(ChunkType::new_private_safe(&mut rng).to_string(), "azZa");
+        assert!(rng.0.is_empty());
+    }
+
     #[test]
     pub fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
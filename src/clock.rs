@@ -0,0 +1,60 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Source of the current time, in seconds since the Unix epoch (UTC)
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+/// The system clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+/// A clock stuck at a given time, for tests and reproducible runs
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Parses an RFC 3339 UTC timestamp (`2025-01-01T00:00:00Z`) into seconds
+/// since the epoch
+pub fn parse_timestamp(value: &str) -> Result<u64, String> {
+    let time = humantime::parse_rfc3339(value).map_err(|err| err.to_string())?;
+
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .map_err(|_| "timestamps before 1970 are not supported".to_string())
+}
+
+/// Formats seconds since the epoch as an RFC 3339 UTC timestamp
+pub fn format_timestamp(seconds: u64) -> String {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(seconds)).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_round_trip() {
+        let seconds = parse_timestamp("2025-01-01T00:00:00Z").unwrap();
+
+        assert_eq!(seconds, 1_735_689_600);
+        assert_eq!(format_timestamp(seconds), "2025-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_parse_invalid_timestamp() {
+        assert!(parse_timestamp("2025-01-01").is_err());
+        assert!(parse_timestamp("1969-12-31T23:59:59Z").is_err());
+    }
+}
@@ -0,0 +1,347 @@
+//! Catalog of the errors and warnings pngme reports.
+//!
+//! Every error and warning has a stable [`Code`] (`E0101`, `W0203`...)
+//! returned by its `code()` method and printed with it. Codes keep their
+//! meaning across releases, so an application showing pngme errors can map
+//! them to its own, translated, messages. [`Code::message`] is the default
+//! English summary, the `Display` of each error adds the details.
+
+use std::fmt::{self, Display};
+
+use serde::{Serialize, Serializer};
+
+macro_rules! catalog {
+    ($($variant:ident = $code:literal, $message:literal;)*) => {
+        /// Stable identifier of an error (`E`) or a warning (`W`)
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Code {
+            $($variant,)*
+        }
+
+        impl Code {
+            /// Every code, in catalog order
+            pub const ALL: &[Code] = &[$(Code::$variant,)*];
+
+            /// The code itself, e.g. `E0101`
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Code::$variant => $code,)*
+                }
+            }
+
+            /// Name of the code, e.g. `BadSignature`
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Code::$variant => stringify!($variant),)*
+                }
+            }
+
+            /// Default English summary
+            pub fn message(self) -> &'static str {
+                match self {
+                    $(Code::$variant => $message,)*
+                }
+            }
+        }
+    };
+}
+
+catalog! {
+    // Reading a file
+    BadSignature = "E0101", "not a PNG file";
+    EmptyFile = "E0102", "the file is empty";
+    NoChunks = "E0103", "the file contains no chunks";
+    TruncatedFile = "E0104", "the file is truncated";
+    TooManyChunks = "E0105", "the file has too many chunks";
+    ChunkTooLarge = "E0106", "a chunk declares more data than allowed";
+    FileTooLarge = "E0107", "the file exceeds the supported size";
+    ReadFailed = "E0108", "the file could not be read";
+
+    // Reading a chunk
+    IncompleteChunk = "E0201", "the chunk is incomplete";
+    InvalidLengthField = "E0202", "the chunk length field is wrong";
+    ChunkDataTooLarge = "E0203", "the chunk data is too large";
+    ChecksumMismatch = "E0204", "the chunk checksum is wrong";
+    ChunkReadFailed = "E0205", "the chunk could not be read";
+    ChunkSpecSeparator = "E0206", "the chunk spec has no ':' after the type";
+    ChunkSpecOddHex = "E0207", "odd number of hex digits in the chunk spec";
+    ChunkSpecHexDigit = "E0208", "invalid hex digit in the chunk spec";
+    ChunkSpecUnescapedQuote = "E0209", "unescaped quote in the chunk spec text";
+    ChunkSpecUnterminated = "E0210", "the chunk spec text has no closing quote";
+    ChunkSpecEscape = "E0211", "unknown escape in the chunk spec text";
+    SelectionEmptyTerm = "E0212", "empty term in the chunk selection";
+    SelectionUnknownTerm = "E0213", "unknown term in the chunk selection";
+
+    // Looking up chunks
+    ChunkNotFound = "E0301", "no chunk of this type";
+    IndexOutOfBounds = "E0302", "no chunk at this index";
+    MissingAnchor = "E0303", "no chunk to place the new chunk next to";
+    CriticalChunkEdit = "E0304", "critical chunks can't be renamed or moved";
+
+    // Chunk types and names
+    NotAsciiLetters = "E0401", "the chunk type is not ASCII letters";
+    InvalidTypeLength = "E0402", "the chunk type is not 4 bytes";
+    InvalidNameLength = "E0403", "the chunk name is not 4 characters";
+    InvalidNameCharacter = "E0404", "the chunk name is not ASCII letters";
+
+    // Commands
+    IoFailed = "E0501", "input or output failed";
+    NotConfirmed = "E0502", "the chunk name was not confirmed";
+    EmptyMessage = "E0503", "the message is empty";
+    PayloadMismatch = "E0504", "the payloads differ";
+    ColorProfileConflict = "E0505", "the image already has a color profile";
+    NotWritable = "E0506", "the destination is not writable";
+    UnknownCriticalOutput = "E0507", "the output would hold an unknown critical chunk";
+    VerifyFailed = "E0508", "the file has problems";
+    JsonFailed = "E0509", "JSON could not be written";
+    OutputExtension = "E0510", "the output is not a .png file";
+    ArchiveMemberOutput = "E0511", "archive members can't be written in place";
+    CorruptPayload = "E0512", "the compressed payload is corrupted";
+    MessageEncrypted = "E0513", "the message is encrypted";
+    DecryptionFailed = "E0514", "the message could not be decrypted";
+    InvalidSplitSize = "E0515", "the split size is invalid";
+    MissingPiece = "E0516", "a piece of a split message is missing";
+    SplitMismatch = "E0517", "the pieces of a split message don't match";
+    StorageFull = "E0518", "no space left on the device";
+    CriticalChunkType = "E0519", "refusing to write a critical chunk type";
+    ReservedBitChunkType = "E0520", "refusing to write a chunk type with the reserved bit set";
+    DecompressedTooLarge = "E0521", "the decompressed data exceeds the limit";
+    HmacMissing = "E0522", "the message carries no HMAC";
+    HmacMismatch = "E0523", "the HMAC of the message doesn't match";
+    SignatureMissing = "E0524", "the chunk has no signature";
+    SignatureMismatch = "E0525", "the signature of the chunk doesn't match";
+    InvalidSigningKey = "E0526", "invalid Ed25519 key";
+    RoundTripFailed = "E0527", "the written message doesn't decode back";
+    CapacityExceeded = "E0528", "the image would exceed the maximum size";
+    BatchFailed = "E0529", "some files of the batch failed";
+    MessageExpired = "E0530", "the message expired";
+
+    // Inputs
+    InputReadFailed = "E0601", "the input could not be read";
+    InputTooLarge = "E0602", "the input is too large";
+    InvalidDataUri = "E0603", "invalid data URI";
+    InvalidFileUrl = "E0604", "invalid file URL";
+    DownloadFailed = "E0605", "the URL could not be reached";
+    DownloadStatus = "E0606", "the server answered with an error";
+    DownloadReadFailed = "E0607", "the download failed";
+    DownloadTooLarge = "E0608", "the download is too large";
+    DownloadIncomplete = "E0609", "the download ended early";
+    ArchiveReadFailed = "E0610", "the archive could not be read";
+    ArchiveMemberNotFound = "E0611", "the archive has no such member";
+    ArchiveEncrypted = "E0612", "the archive member is encrypted";
+    CacheUnavailable = "E0613", "there is no download cache directory";
+    CacheIoFailed = "E0614", "the download cache could not be accessed";
+    DownloadStoreFailed = "E0615", "the download could not be stored";
+
+    // Text and color profiles
+    EmptyKeyword = "E0701", "the keyword is empty";
+    KeywordTooLong = "E0702", "the keyword is too long";
+    KeywordSpaces = "E0703", "the keyword has misplaced spaces";
+    KeywordInvalidCharacter = "E0704", "the keyword has a forbidden character";
+    NotLatin1 = "E0705", "the text is not Latin-1";
+    IccMissingSeparator = "E0706", "the iCCP chunk has no name separator";
+    IccMissingCompression = "E0707", "the iCCP chunk has no compression method";
+    IccUnsupportedCompression = "E0708", "the iCCP compression method is not supported";
+    IccInvalidZlib = "E0709", "the ICC profile is not valid zlib";
+    InvalidLanguageTag = "E0710", "the language tag is invalid";
+    TranslatedKeywordNul = "E0711", "the translated keyword holds a NUL character";
+    ZtxtMissingSeparator = "E0712", "the zTXt chunk has no keyword separator";
+    ZtxtMissingCompression = "E0713", "the zTXt chunk has no compression method";
+    ZtxtUnsupportedCompression = "E0714", "the zTXt compression method is not supported";
+
+    // Message encodings
+    InvalidHex = "E0801", "invalid hex";
+    InvalidBase64 = "E0802", "invalid base64";
+    InvalidBase32Character = "E0803", "invalid base32 character";
+    InvalidBase32Length = "E0804", "invalid base32 length";
+    InvalidBase45Character = "E0805", "invalid base45 character";
+    InvalidBase45Length = "E0806", "invalid base45 length";
+    Base45Overflow = "E0807", "base45 group out of range";
+    NotUtf8 = "E0808", "the payload is not UTF-8";
+    InvalidJson = "E0809", "the payload is not valid JSON";
+
+    // Sidecars and templates
+    UnsupportedSidecarVersion = "E0901", "unsupported sidecar version";
+    MetaConflict = "E0902", "the image already has this chunk";
+    TemplateUnclosed = "E0903", "unclosed template placeholder";
+    TemplateUnknown = "E0904", "unknown template placeholder";
+    TemplateNotDeterministic = "E0905", "the placeholder is not deterministic";
+    TemplateHashFailed = "E0906", "the file to hash could not be read";
+    HostnameUnavailable = "E0907", "the hostname is not available";
+    UnsupportedPatchVersion = "E0908", "unsupported patch version";
+    InvalidPatch = "E0909", "invalid patch";
+    PatchMismatch = "E0910", "the image is not the one the patch expects";
+    ForbiddenSidecarChunk = "E0911", "the sidecar holds a critical or invalid chunk type";
+
+    // Locks, undo and server
+    LockFailed = "E1001", "the file could not be locked";
+    FileLocked = "E1002", "the file is locked by another process";
+    UndoIoFailed = "E1003", "the undo state could not be accessed";
+    InvalidUndoManifest = "E1004", "invalid undo manifest";
+    NothingToUndo = "E1005", "nothing to undo";
+    UndoCorrupted = "E1006", "the undo state is corrupted";
+    ServerBindFailed = "E1007", "the server could not listen";
+    BackupExists = "E1008", "the backup file already exists";
+    BackupFailed = "E1009", "the backup could not be written";
+    RequestMalformed = "E1010", "the request is malformed";
+    RequestTimeout = "E1011", "the request took too long to arrive";
+    RequestUnauthorized = "E1012", "the request token is missing or wrong";
+    NoEndpoint = "E1013", "no endpoint at this path";
+    MethodNotAllowed = "E1014", "the endpoint doesn't take this method";
+    MissingParameter = "E1015", "a query parameter is missing";
+    RequestTooLarge = "E1016", "the request exceeds the size limit";
+    LengthRequired = "E1017", "the request body has no Content-Length";
+
+    // Passphrases
+    SecretReadFailed = "E1101", "the passphrase could not be read";
+    SecretNotFound = "E1102", "no passphrase with this name in the keychain";
+    KeychainUnavailable = "E1103", "the keychain is not available";
+    EmptySecret = "E1104", "the passphrase is empty";
+
+    // Image data
+    MissingHeader = "E1201", "the image has no IHDR chunk";
+    ImageDecodeFailed = "E1202", "the image data could not be decoded";
+    ImageEncodeFailed = "E1203", "the image data could not be encoded";
+    AnimatedImage = "E1204", "the image is animated";
+
+    // Command line
+    MissingArgumentFile = "E1301", "a file given as argument does not exist";
+    ConflictingArguments = "E1302", "the arguments can't be combined";
+    InvalidSize = "E1303", "invalid size";
+    InvalidArguments = "E1304", "the arguments have problems";
+    UnknownCommand = "E1305", "no such command, built-in or external";
+    InvalidCommandName = "E1306", "the command name holds a path separator";
+    ExternalCommandFailed = "E1307", "the external command could not be run";
+    GlobUnclosedClass = "E1308", "the file pattern has an unclosed '['";
+    GlobNoMatch = "E1309", "the file pattern matches no file";
+    UnknownCode = "E1310", "no error or warning has this code";
+
+    // Batch journals
+    JournalIoFailed = "E1401", "the batch journal could not be accessed";
+    InvalidJournal = "E1402", "the batch journal is malformed";
+    JournalMismatch = "E1403", "the batch journal records other options";
+
+    // Warnings found while parsing
+    ChunkAfterIend = "W0201", "chunk after IEND";
+    MissingIend = "W0202", "missing IEND chunk";
+    TrailingData = "W0203", "trailing bytes after the last chunk";
+    CrcMismatch = "W0204", "chunk CRC mismatch";
+    UnknownCriticalChunk = "W0205", "unknown critical chunk";
+    ReservedBitSet = "W0206", "chunk type with the reserved bit set";
+    ImageDataSize = "W0207", "the image data size doesn't match the header";
+    AnimationSequence = "W0208", "animation chunk out of sequence";
+
+    // Warnings about outputs
+    UploadLimit = "W0301", "the output exceeds an upload limit";
+    EmptySelection = "W0302", "the chunk selection matches no chunk";
+    OutputNotPng = "W0303", "the output file name doesn't end in .png";
+    PipelineConstraint = "W0304", "the chunk may not survive the pipeline";
+    CriticalChunkWritten = "W0305", "the chunk written is critical";
+
+    // Warnings about the messages embedded
+    WhitespaceMessage = "W0401", "the message only contains whitespace";
+    MessageAlreadyExpired = "W0402", "the message is already expired";
+    NestedEnvelope = "W0403", "the message is already a pngme envelope";
+    Latin1Substituted = "W0404", "characters outside Latin-1 were replaced";
+    DuplicateChunk = "W0405", "the image already has a chunk of this type";
+
+    // Warnings about repairs
+    RenameConflict = "W0501", "a chunk of the corrected type already exists";
+}
+
+impl Code {
+    pub fn is_warning(self) -> bool {
+        self.as_str().starts_with('W')
+    }
+
+    /// The code written `text`, either the code (`e0101` or `E0101`) or its
+    /// name (`BadSignature`)
+    pub fn find(text: &str) -> Option<Code> {
+        Code::ALL
+            .iter()
+            .copied()
+            .find(|code| code.as_str().eq_ignore_ascii_case(text) || code.name() == text)
+    }
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Code {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// How much a finding of `scan` or a parse warning should worry the reader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_codes_are_unique() {
+        let codes: HashSet<&str> = Code::ALL.iter().map(|code| code.as_str()).collect();
+        let names: HashSet<&str> = Code::ALL.iter().map(|code| code.name()).collect();
+
+        assert_eq!(codes.len(), Code::ALL.len());
+        assert_eq!(names.len(), Code::ALL.len());
+    }
+
+    #[test]
+    fn test_code_format() {
+        for code in Code::ALL {
+            let text = code.as_str();
+
+            assert_eq!(text.len(), 5, "{text}");
+            assert!(text.starts_with(['E', 'W']), "{text}");
+            assert!(text[1..].bytes().all(|byte| byte.is_ascii_digit()), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_codes_are_stable() {
+        // Changing these breaks applications that map codes to their own text
+        assert_eq!(Code::BadSignature.as_str(), "E0101");
+        assert_eq!(Code::TruncatedFile.as_str(), "E0104");
+        assert_eq!(Code::ChecksumMismatch.as_str(), "E0204");
+        assert_eq!(Code::ChunkNotFound.as_str(), "E0301");
+        assert_eq!(Code::NotWritable.as_str(), "E0506");
+        assert_eq!(Code::FileLocked.as_str(), "E1002");
+        assert_eq!(Code::TrailingData.as_str(), "W0203");
+        assert_eq!(Code::CrcMismatch.as_str(), "W0204");
+        assert!(Code::TrailingData.is_warning());
+        assert!(!Code::BadSignature.is_warning());
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(Code::find("W0401"), Some(Code::WhitespaceMessage));
+        assert_eq!(Code::find("e0101"), Some(Code::BadSignature));
+        assert_eq!(Code::find("BadSignature"), Some(Code::BadSignature));
+        assert_eq!(Code::find("E9999"), None);
+    }
+}
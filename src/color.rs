@@ -0,0 +1,268 @@
+//! Pixel formats of the IHDR chunk: a color type and a bit depth, among the
+//! combinations the PNG specification allows (section 11.2.2, table 11.1).
+//!
+//! | Color type | Name                 | Samples | Bit depths     |
+//! |------------|----------------------|---------|----------------|
+//! | 0          | grayscale            | 1       | 1, 2, 4, 8, 16 |
+//! | 2          | RGB                  | 3       | 8, 16          |
+//! | 3          | indexed              | 1       | 1, 2, 4, 8     |
+//! | 4          | grayscale with alpha | 2       | 8, 16          |
+//! | 6          | RGBA                 | 4       | 8, 16          |
+//!
+//! Below 8 bits, pixels are packed several to a byte, so the size of a
+//! scanline is counted in bits and rounded up to a whole byte.
+
+use std::fmt::{self, Display};
+
+/// Offset of the bit depth in the IHDR data, the color type follows
+pub const BIT_DEPTH: usize = 8;
+
+/// How the samples of a pixel are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    pub const ALL: [ColorType; 5] = [
+        ColorType::Grayscale,
+        ColorType::Rgb,
+        ColorType::Indexed,
+        ColorType::GrayscaleAlpha,
+        ColorType::Rgba,
+    ];
+
+    /// The color type of the IHDR byte, `None` for an undefined one
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|color_type| color_type.byte() == byte)
+    }
+
+    pub fn byte(self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+
+    /// Samples of a pixel, a palette index counting as one
+    pub fn samples(self) -> u8 {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    /// Bit depths the specification allows with this color type
+    pub fn bit_depths(self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+}
+
+impl Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "indexed",
+            ColorType::GrayscaleAlpha => "grayscale with alpha",
+            ColorType::Rgba => "RGBA",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A valid combination of color type and bit depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorFormat {
+    color_type: ColorType,
+    bit_depth: u8,
+}
+
+impl ColorFormat {
+    /// 8-bit RGBA, the format of the generated images
+    pub const RGBA8: ColorFormat = ColorFormat {
+        color_type: ColorType::Rgba,
+        bit_depth: 8,
+    };
+
+    /// The format of the IHDR bytes, `None` for a combination the
+    /// specification doesn't allow
+    pub fn new(color_type: u8, bit_depth: u8) -> Option<Self> {
+        let color_type = ColorType::from_byte(color_type)?;
+        color_type
+            .bit_depths()
+            .contains(&bit_depth)
+            .then_some(Self {
+                color_type,
+                bit_depth,
+            })
+    }
+
+    /// Whether the specification allows `bit_depth` with `color_type`
+    pub fn valid_combination(color_type: u8, bit_depth: u8) -> bool {
+        Self::new(color_type, bit_depth).is_some()
+    }
+
+    /// The format of IHDR data, `None` when it is too short or its
+    /// combination isn't allowed
+    pub fn from_ihdr(ihdr: &[u8]) -> Option<Self> {
+        let &[bit_depth, color_type] = ihdr.get(BIT_DEPTH..BIT_DEPTH + 2)? else {
+            return None;
+        };
+        Self::new(color_type, bit_depth)
+    }
+
+    /// Every valid format, by color type then bit depth
+    pub fn all() -> impl Iterator<Item = ColorFormat> {
+        ColorType::ALL.into_iter().flat_map(|color_type| {
+            color_type.bit_depths().iter().map(move |&bit_depth| Self {
+                color_type,
+                bit_depth,
+            })
+        })
+    }
+
+    pub fn color_type(self) -> ColorType {
+        self.color_type
+    }
+
+    pub fn bit_depth(self) -> u8 {
+        self.bit_depth
+    }
+
+    pub fn samples_per_pixel(self) -> u8 {
+        self.color_type.samples()
+    }
+
+    pub fn bits_per_pixel(self) -> u8 {
+        self.samples_per_pixel() * self.bit_depth
+    }
+
+    /// Bytes of a pixel rounded up to one, the distance filters look back
+    /// to (section 9.2)
+    pub fn bytes_per_pixel(self) -> usize {
+        usize::from(self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// Bytes of the pixels of a `width`-pixel row, without its filter byte
+    pub fn scanline_len(self, width: u32) -> u64 {
+        (u64::from(width) * u64::from(self.bits_per_pixel())).div_ceil(8)
+    }
+}
+
+impl Display for ColorFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-bit {}", self.bit_depth, self.color_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_valid_combination() {
+        // (color type, bit depth, bytes per pixel, bytes of a 3-pixel row)
+        let expected = [
+            (0, 1, 1, 1),
+            (0, 2, 1, 1),
+            (0, 4, 1, 2),
+            (0, 8, 1, 3),
+            (0, 16, 2, 6),
+            (2, 8, 3, 9),
+            (2, 16, 6, 18),
+            (3, 1, 1, 1),
+            (3, 2, 1, 1),
+            (3, 4, 1, 2),
+            (3, 8, 1, 3),
+            (4, 8, 2, 6),
+            (4, 16, 4, 12),
+            (6, 8, 4, 12),
+            (6, 16, 8, 24),
+        ];
+
+        let found: Vec<_> = ColorFormat::all()
+            .map(|format| {
+                (
+                    format.color_type().byte(),
+                    format.bit_depth(),
+                    format.bytes_per_pixel(),
+                    format.scanline_len(3),
+                )
+            })
+            .collect();
+        assert_eq!(found, expected);
+        for (color_type, bit_depth, _, _) in expected {
+            assert!(ColorFormat::valid_combination(color_type, bit_depth));
+        }
+    }
+
+    #[test]
+    fn test_invalid_combinations() {
+        for color_type in 0..=u8::MAX {
+            for bit_depth in 0..=u8::MAX {
+                let valid = ColorFormat::all().any(|format| {
+                    (format.color_type().byte(), format.bit_depth()) == (color_type, bit_depth)
+                });
+                assert_eq!(ColorFormat::valid_combination(color_type, bit_depth), valid);
+            }
+        }
+
+        assert_eq!(ColorFormat::new(3, 16), None);
+        assert_eq!(ColorFormat::new(2, 4), None);
+        assert_eq!(ColorFormat::new(6, 1), None);
+        assert_eq!(ColorFormat::new(5, 8), None);
+        assert_eq!(ColorFormat::new(0, 3), None);
+    }
+
+    #[test]
+    fn test_packed_scanlines() {
+        let gray1 = ColorFormat::new(0, 1).unwrap();
+        assert_eq!(gray1.samples_per_pixel(), 1);
+        assert_eq!(gray1.scanline_len(8), 1);
+        assert_eq!(gray1.scanline_len(9), 2);
+        assert_eq!(ColorFormat::new(3, 2).unwrap().scanline_len(5), 2);
+        assert_eq!(
+            ColorFormat::RGBA8.scanline_len(u32::MAX),
+            4 * u32::MAX as u64
+        );
+    }
+
+    #[test]
+    fn test_from_ihdr() {
+        let ihdr = |bit_depth, color_type| {
+            [
+                &[0, 0, 0, 1, 0, 0, 0, 1][..],
+                &[bit_depth, color_type, 0, 0, 0],
+            ]
+            .concat()
+        };
+
+        assert_eq!(
+            ColorFormat::from_ihdr(&ihdr(8, 6)),
+            Some(ColorFormat::RGBA8)
+        );
+        assert_eq!(ColorFormat::from_ihdr(&ihdr(16, 3)), None);
+        assert_eq!(ColorFormat::from_ihdr(&[0; 9]), None);
+        assert_eq!(ColorFormat::RGBA8.to_string(), "8-bit RGBA");
+        assert_eq!(
+            ColorFormat::new(4, 16).unwrap().to_string(),
+            "16-bit grayscale with alpha"
+        );
+    }
+}
@@ -1,64 +1,3141 @@
-use std::{fs::File, io::{BufReader, Read, Write}, path::PathBuf, str::FromStr};
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    fs::{self, OpenOptions},
+    io::{self, ErrorKind, IsTerminal, Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
-use crate::{chunk::Chunk, chunk_type::ChunkType, error::PngMeError, png::Png};
+use clap::CommandFactory;
+use ed25519_dalek::SigningKey;
+use rand_core::OsRng;
+use serde::Serialize;
 
-fn file_to_png(file: &PathBuf) -> Result<Png, PngMeError> {
-    let file = File::open(file)?;
-    let mut reader = BufReader::new(file);
-    let mut bytes = Vec::new();
+use crate::{
+    apng,
+    args::{Arguments, EncodeArgs, HmacKeyArgs, OutputFormat, PasswordArgs, RecursiveArgs},
+    cache::DownloadCache,
+    canonical,
+    build_info::BuildInfo,
+    capabilities::Capabilities,
+    capacity::Capacity,
+    chunk::Chunk,
+    chunk_ref::chunk_refs,
+    chunk_type::ChunkType,
+    clock::{Clock, SystemClock, format_timestamp},
+    codes::{Code, Severity},
+    color::{BIT_DEPTH, ColorFormat},
+    consts::{CHUNK_OVERHEAD, SIGNATURE_LEN},
+    envelope::{self, Envelope, Opened, Provenance},
+    error::PngMeError,
+    exit_status::ExitStatus,
+    fixtures::{self, FixtureKind},
+    glob::{self, Pattern},
+    format::{self, Encoding, EncodingWriter, FormatError, PayloadFormat, StreamDecoder, TextOptions},
+    hash::sha256_hex,
+    icc::{ICCP_CHUNK_TYPE, IccProfile},
+    image_data::{self, ImageDataCheck},
+    inflate::DEFAULT_MAX_DECOMPRESSED_SIZE,
+    input::{InputError, InputOptions, InputSource},
+    interlace::{INTERLACE_METHOD, deinterlace, is_interlaced},
+    interpret::Registry,
+    journal::{Journal, Status as JournalStatus},
+    lock::FileLock,
+    mac::MacError,
+    meta::{self, OnConflict, Sidecar},
+    metadata::{self, Metadata},
+    patch::{self, Patch},
+    observer::{NoopObserver, Observer, Stage},
+    png::{ParseOptions, ParseWarning, Png, PngError, PngParserError, Position},
+    profiles::Profile,
+    sanitize::escape_for_terminal,
+    signing,
+    sniff::{self, FileKind},
+    sink::{is_storage_full, sink_for, stage_and_read_back, write_to_sink},
+    split,
+    stats::{CorpusStats, TypeReport},
+    transaction::Transaction,
+    scan::{self, ScanOptions},
+    secret::{Keychain, SecretSource, default_keychain},
+    selector::Selection,
+    survivability::{self, Suggestion},
+    template::{self, Variables},
+    text::{ItxtHeader, latin1_decode, latin1_encode_lossy, text_chunk_data, ztxt_chunk_data},
+    timings::{Timings, TimingsReport},
+    undo::UndoStore,
+    upload_limits::{self, COMMON_LIMITS, SizeThreshold, SizeWarning},
+    validate::parse_size,
+    walk::{PngFiles, png_files},
+};
 
-    reader.read_to_end(&mut bytes)?;
+/// Settings shared by every command
+pub struct Context<'a> {
+    pub observer: &'a dyn Observer,
+    pub parse_options: ParseOptions,
+    pub input_options: InputOptions,
+    pub clock: &'a dyn Clock,
+    /// How long in-place edits wait for another process holding the file
+    pub lock_timeout: Duration,
+    /// Number of undo states kept per file edited in place, `None` to not
+    /// save any
+    pub keep_undo: Option<usize>,
+    /// Suffix of the copy of files edited in place made before reading
+    /// them, `None` to not make any
+    pub backup_suffix: Option<String>,
+    /// Replace an existing copy rather than fail
+    pub overwrite_backup: bool,
+    /// Let `encode` and `remove` write files holding a critical chunk
+    /// decoders don't know
+    pub allow_unknown_critical: bool,
+    /// Where passphrases given with `--password-keychain` are stored
+    pub keychain: &'a dyn Keychain,
+    /// Refuse to write images to a path not ending in `.png` or `.apng`
+    /// rather than warn
+    pub strict_extension: bool,
+    /// Add `.png` to output paths without an extension
+    pub extension_fixup: bool,
+    /// Describe the chunks shown by `print`, `info` and `scan`
+    pub interpreters: &'a Registry,
+    /// Bytes compressed payloads may inflate to
+    pub max_decompressed_size: u64,
+    /// Where `encode`, `remove`, `strip`, `inject` and `import-meta`
+    /// write the patch of their changes
+    pub emit_patch: Option<PathBuf>,
+    /// Commands writing several files write all of them or none
+    pub transactional: bool,
+    /// Where the outputs of a transactional batch are staged instead of
+    /// written, see [`encode_batch`]
+    pub transaction: RefCell<Option<Transaction>>,
+    /// Timings of `--stats`, added to the JSON reports of commands
+    pub stats: Option<&'a Timings>,
+    /// Whether a JSON report carried the [`stats`](Context::stats) already
+    pub stats_reported: Cell<bool>,
+}
+
+impl<'a> Context<'a> {
+    pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub fn new(observer: &'a dyn Observer) -> Self {
+        Self {
+            observer,
+            parse_options: ParseOptions::default(),
+            input_options: InputOptions::default(),
+            clock: &SystemClock,
+            lock_timeout: Context::DEFAULT_LOCK_TIMEOUT,
+            keep_undo: None,
+            backup_suffix: None,
+            overwrite_backup: false,
+            allow_unknown_critical: false,
+            keychain: default_keychain(),
+            strict_extension: false,
+            extension_fixup: true,
+            interpreters: Registry::builtin(),
+            max_decompressed_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+            emit_patch: None,
+            transactional: false,
+            transaction: RefCell::new(None),
+            stats: None,
+            stats_reported: Cell::new(false),
+        }
+    }
+}
+
+fn file_to_png(file: &InputSource, ctx: &Context) -> Result<Png, PngMeError> {
+    let input = file.resolve(&ctx.input_options, ctx.observer)?;
+
+    Ok(Png::parse(input.bytes.as_slice(), &ctx.parse_options, ctx.observer)?)
+}
+
+/// Extensions other tools recognize images by
+const IMAGE_EXTENSIONS: [&str; 2] = ["png", "apng"];
+
+/// Where an image read from `file` is written: `output`, or else the
+/// default output of `file`.
+///
+/// Paths other than the edited file itself get their extension checked: a
+/// missing one becomes `.png` unless `ctx.extension_fixup` is off, and
+/// another one is a warning, or an error with `ctx.strict_extension`.
+/// Archive members are read-only and need an `output`.
+fn output_path(file: &InputSource, output: &Option<PathBuf>, ctx: &Context) -> Result<PathBuf, PngMeError> {
+    #[cfg(feature = "archives")]
+    if matches!(file, InputSource::Archive { .. }) && output.is_none() {
+        return Err(PngMeError::ArchiveMemberOutput { input: file.to_string() });
+    }
+
+    let path = output.clone().unwrap_or_else(|| file.default_output());
+
+    // The standard output and files edited in place keep their name
+    let in_place = matches!(file, InputSource::Path(input) if *input == path);
+    if in_place || path == Path::new("-") {
+        return Ok(path);
+    }
+
+    match path.extension() {
+        None if ctx.extension_fixup => Ok(path.with_extension("png")),
+        None => Ok(path),
+        Some(extension)
+            if IMAGE_EXTENSIONS
+                .iter()
+                .any(|image| extension.eq_ignore_ascii_case(image)) =>
+        {
+            Ok(path)
+        }
+        Some(_) if ctx.strict_extension => Err(PngMeError::OutputExtension { path }),
+        Some(_) => {
+            eprintln!(
+                "warning[{}]: {} doesn't end in .png, other tools may not open it as an image",
+                Code::OutputNotPng,
+                path.display()
+            );
+            Ok(path)
+        }
+    }
+}
+
+/// Locks `file` for the whole read-modify-write when `output` rewrites it in
+/// place. Other inputs and outputs are not locked.
+fn lock_in_place(file: &InputSource, output: &Path, ctx: &Context) -> Result<Option<FileLock>, PngMeError> {
+    match file {
+        InputSource::Path(path) if path == output && path.exists() => {
+            Ok(Some(FileLock::acquire(path, ctx.lock_timeout)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Copies `file` to its path plus the backup suffix when `output` rewrites
+/// it in place. The copy is made from the bytes on disk before anything
+/// parses them, so the original survives whatever goes wrong afterwards.
+fn backup_in_place(file: &InputSource, output: &Path, ctx: &Context) -> Result<(), PngMeError> {
+    let (Some(suffix), InputSource::Path(path)) = (&ctx.backup_suffix, file) else {
+        return Ok(());
+    };
+    if path != output || !path.exists() {
+        return Ok(());
+    }
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(suffix);
+    let backup = PathBuf::from(backup);
+    if backup.exists() && !ctx.overwrite_backup {
+        return Err(PngMeError::BackupExists { path: backup });
+    }
+
+    // Also copies the permissions of the original
+    if let Err(source) = fs::copy(path, &backup) {
+        let _ = fs::remove_file(&backup);
+        return Err(PngMeError::BackupFailed { path: backup, source });
+    }
+
+    Ok(())
+}
+
+/// Saves the current content of `file` as an undo state when `output`
+/// rewrites it in place and undo is enabled
+fn save_undo_state(file: &InputSource, output: &Path, operation: &str, ctx: &Context) -> Result<(), PngMeError> {
+    let (Some(keep), InputSource::Path(path)) = (ctx.keep_undo, file) else {
+        return Ok(());
+    };
+    if path != output || !path.exists() {
+        return Ok(());
+    }
+
+    UndoStore::for_file(path)?.save(&fs::read(path)?, operation, ctx.clock.now(), keep)?;
+
+    Ok(())
+}
+
+/// `report` as one line of JSON, with a `stats` member holding the timings
+/// so far when `--stats` collects them
+fn json_report(report: &impl Serialize, ctx: &Context) -> Result<String, PngMeError> {
+    #[derive(Serialize)]
+    struct WithStats<'a, T> {
+        #[serde(flatten)]
+        report: &'a T,
+        stats: TimingsReport,
+    }
+
+    let Some(stats) = ctx.stats else {
+        return Ok(serde_json::to_string(report)?);
+    };
+    ctx.stats_reported.set(true);
+
+    Ok(serde_json::to_string(&WithStats {
+        report,
+        stats: stats.report(),
+    })?)
+}
+
+/// Refuses to write `png` when it holds an unknown critical chunk, since
+/// decoders would reject it
+pub(crate) fn check_unknown_critical(png: &Png, allow: bool) -> Result<(), PngMeError> {
+    if allow {
+        return Ok(());
+    }
+
+    match png.chunks().iter().find(|chunk| chunk.chunk_type().is_unknown_critical()) {
+        Some(chunk) => Err(PngMeError::UnknownCritical {
+            chunk_type: chunk.chunk_type().to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// What `encode` records along with the message
+#[derive(Debug, Clone, Default)]
+pub struct EncodeOptions {
+    /// Embed the message even if it is empty
+    pub allow_empty: bool,
+    /// Embed the message of a payload that already is an envelope, one
+    /// level down, instead of the envelope
+    pub unwrap: bool,
+    /// Embed a payload that already is an envelope without a warning
+    pub wrap_anyway: bool,
+    /// Format the message must be a valid document of
+    pub payload_format: Option<PayloadFormat>,
+    /// Seconds since the epoch after which `decode` hides the message
+    pub expires_at: Option<u64>,
+    /// Who wrote the chunk, from `--annotate`
+    pub provenance: Option<Provenance>,
+    /// Store the message compressed
+    pub compress: bool,
+    /// Passphrase the message is encrypted with, from `--encrypt`
+    pub encrypt: Option<SecretSource>,
+    /// Key of the HMAC stored with the message, from `--hmac`
+    pub hmac: Option<SecretSource>,
+    /// PEM file of the Ed25519 key signing the message, from `--sign-key`
+    pub sign_key: Option<PathBuf>,
+    /// Stored in a meTa chunk next to the message, from `--meta`
+    pub metadata: Option<Metadata>,
+    /// Split the payload across chunks of at most this many bytes
+    pub split_size: Option<usize>,
+    /// Overwrite the existing chunk of the type instead of adding another
+    pub replace: bool,
+    /// Where the chunk goes when it is added
+    pub position: Position,
+    /// Adapt the chunk type and position to survive this pipeline
+    pub evade: Option<&'static Profile>,
+    /// Write the message as the text of a tEXt chunk with this keyword
+    pub text_keyword: Option<String>,
+    /// Write the message as the text of an iTXt chunk with these fields
+    pub itxt: Option<ItxtHeader>,
+    /// Write the message as the compressed text of a zTXt chunk with this
+    /// keyword
+    pub ztxt: Option<String>,
+    /// Replace the characters of tEXt and zTXt text outside Latin-1 with
+    /// '?' rather than fail
+    pub latin1_lossy: bool,
+    /// Write critical chunk types and types with the reserved bit set
+    pub allow_unsafe_type: bool,
+    /// Print the chunks the image would have instead of writing it
+    pub dry_run: bool,
+    /// Read the output back and check that every message decodes before it
+    /// replaces the destination
+    pub verify_after: bool,
+    /// Write the output even when the check of `verify_after` fails
+    pub keep_on_failure: bool,
+    /// Sizes the output is warned about exceeding
+    pub size_thresholds: Vec<SizeThreshold>,
+    /// Format of the report on the output
+    pub format: OutputFormat,
+}
+
+/// Embeds `message`, wrapped in an [`Envelope`] when it expires, carries
+/// its provenance, is compressed or is encrypted
+pub fn encode(
+    file: &InputSource,
+    chunk_type: &str,
+    message: &[u8],
+    output: &Option<PathBuf>,
+    options: &EncodeOptions,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    encode_many(file, &[(chunk_type, message)], output, options, ctx)
+}
+
+/// Runs `encode` as given on the command line: the message is read once,
+/// then embedded in FILE or in every file of a `--glob` or `--recursive`
+/// batch
+pub fn encode_command(args: &EncodeArgs, assume_yes: bool, ctx: &Context) -> Result<(), PngMeError> {
+    // clap requires exactly one of the positional and named forms,
+    // unless the messages come as pairs or the type is random
+    let random_type = args.random_type.then(|| {
+        let chunk_type = ChunkType::new_private_safe(&mut OsRng).to_string();
+        eprintln!("Using the random chunk type {chunk_type}");
+        chunk_type
+    });
+    let chunk_name = args.chunk_name.as_ref().or(args.chunk.as_ref()).or(random_type.as_ref());
+    let output = args.output.clone().or(args.output_flag.clone());
+    let source = MessageSource::read(args, ctx)?;
+    let options = encode_options(args, source.from_stdin, ctx)?;
+
+    let encode_file = |file: &InputSource| {
+        let messages = source.messages(args, chunk_name, file, ctx)?;
+        // Every name is checked before the image is read. Without
+        // --allow-unsafe-type a reserved bit is refused, not confirmed
+        for (chunk_name, _) in &messages {
+            check_chunk_name(chunk_name, args.allow_unsafe_type, assume_yes)?;
+        }
+        let messages: Vec<_> = messages
+            .iter()
+            .map(|(chunk_name, message)| (chunk_name.as_str(), message.as_slice()))
+            .collect();
+        let options = EncodeOptions {
+            // Deterministic runs leave the time out, like annotations
+            metadata: (args.meta && !args.no_meta).then(|| {
+                Metadata::new(
+                    args.meta_author.clone(),
+                    (!args.deterministic).then(|| ctx.clock.now()),
+                    file.file_name(),
+                )
+            }),
+            ..options.clone()
+        };
+        encode_many(file, &messages, &output, &options, ctx)
+    };
+
+    let Some(file) = &args.file else {
+        let operation = operation_digest(args, &source);
+        let journal = match (&args.journal, &args.resume) {
+            (Some(path), _) => Some(Journal::create(path, &operation)?),
+            (None, Some(path)) => Some(Journal::resume(path, &operation)?),
+            (None, None) => None,
+        };
+        return encode_batch(&batch_files(args)?, args.fail_fast, args.format, journal, ctx, encode_file);
+    };
+
+    encode_file(file)
+}
+
+/// Digest of what a batch writes, recorded in its journal: the options and
+/// the message, but not which files the batch covers nor where secrets
+/// come from
+fn operation_digest(args: &EncodeArgs, source: &MessageSource) -> String {
+    let options = EncodeArgs {
+        file: None,
+        globs: Vec::new(),
+        recursive: RecursiveArgs::default(),
+        fail_fast: false,
+        journal: None,
+        resume: None,
+        password: PasswordArgs::default(),
+        hmac_key: HmacKeyArgs::default(),
+        format: OutputFormat::Human,
+        ..args.clone()
+    };
+
+    sha256_hex(format!("{options:?} {}", sha256_hex(&source.bytes)).as_bytes())
+}
+
+/// The files of a `--glob` or `--recursive` batch
+fn batch_files(args: &EncodeArgs) -> Result<Vec<PathBuf>, PngMeError> {
+    if let Some(walk) = args.recursive.walk() {
+        return Ok(walked_files(walk));
+    }
+
+    let patterns: Vec<Pattern> = args
+        .globs
+        .iter()
+        .map(|pattern| pattern.parse().expect("patterns are validated"))
+        .collect();
+    Ok(glob::files(&patterns)?)
+}
+
+/// The message of `encode` as read once, a batch embedding it in every file
+struct MessageSource {
+    bytes: Vec<u8>,
+    /// Read from stdin with `-`, where an empty input is an empty chunk
+    /// rather than a forgotten message
+    from_stdin: bool,
+    /// A template rendered for each file
+    rendered: bool,
+    /// Decoded from `--input-encoding` as it was read
+    streamed: bool,
+}
+
+impl MessageSource {
+    fn read(args: &EncodeArgs, ctx: &Context) -> Result<Self, PngMeError> {
+        let message = args.message.as_ref().or(args.message_flag.as_ref());
+        let from_stdin = message.is_some_and(|message| message == "-");
+        let rendered = args.message_template.is_some() || args.template;
+        // Encoded messages from a file or stdin are decoded as they are
+        // read, a large one is never held whole as text
+        let streamed =
+            args.input_encoding != Encoding::Text && !rendered && (args.message_file.is_some() || from_stdin);
+
+        let bytes = match (&args.message_file, &args.message_template, message) {
+            (Some(path), _, _) if streamed => {
+                read_encoded(fs::File::open(path)?, args.input_encoding, &path.display().to_string(), None)?
+            }
+            // Files are read as bytes, they needn't be UTF-8
+            (Some(path), _, _) => fs::read(path)?,
+            (None, Some(path), _) => fs::read_to_string(path)?.into_bytes(),
+            (None, None, Some(_)) if streamed => read_encoded(
+                io::stdin().lock(),
+                args.input_encoding,
+                &InputSource::Stdin.to_string(),
+                ctx.input_options.max_size,
+            )?,
+            (None, None, Some(_)) if from_stdin => InputSource::Stdin.resolve(&ctx.input_options, ctx.observer)?.bytes,
+            (None, None, message) => message.cloned().unwrap_or_default().into_bytes(),
+        };
+
+        Ok(Self {
+            bytes,
+            from_stdin,
+            rendered,
+            streamed,
+        })
+    }
+
+    /// The `(chunk name, message)` pairs embedded in `file`, with the
+    /// templates rendered for it and the messages decoded
+    fn messages(
+        &self,
+        args: &EncodeArgs,
+        chunk_name: Option<&String>,
+        file: &InputSource,
+        ctx: &Context,
+    ) -> Result<Vec<(String, Vec<u8>)>, PngMeError> {
+        let render = |source: &[u8]| {
+            render_message(&String::from_utf8_lossy(source), &args.vars, file, args.deterministic, ctx)
+                .map(String::into_bytes)
+        };
+        // Only text is cleaned up, decoded bytes are embedded as they are
+        let decoded = |message: Vec<u8>| match args.input_encoding {
+            Encoding::Text => Ok(args.text.apply(&message).into_owned()),
+            encoding => Ok::<_, PngMeError>(encoding.decode(&String::from_utf8_lossy(&message))?),
+        };
+
+        Ok(match chunk_name {
+            Some(chunk_name) if self.rendered => vec![(chunk_name.clone(), decoded(render(&self.bytes)?)?)],
+            // Decoded as it was read
+            Some(chunk_name) if self.streamed => vec![(chunk_name.clone(), self.bytes.clone())],
+            Some(chunk_name) => vec![(chunk_name.clone(), decoded(self.bytes.clone())?)],
+            None => args
+                .pairs
+                .iter()
+                .map(|(name, message)| {
+                    let message = match args.template {
+                        true => render(message.as_bytes())?,
+                        false => message.clone().into_bytes(),
+                    };
+                    Ok((name.clone(), decoded(message)?))
+                })
+                .collect::<Result<Vec<_>, PngMeError>>()?,
+        })
+    }
+}
+
+/// The [`EncodeOptions`] of the command line, but for the metadata which
+/// names each file
+fn encode_options(args: &EncodeArgs, from_stdin: bool, ctx: &Context) -> Result<EncodeOptions, PngMeError> {
+    let size = |value: &str| parse_size(value).expect("sizes are validated");
+    // Deterministic runs only record a time given explicitly
+    let created_at = args.annotation_date.or((!args.deterministic).then(|| ctx.clock.now()));
+    // Asked for once rather than for every file of a batch
+    let encrypt = match (args.encrypt, &args.file) {
+        (false, _) => None,
+        (true, Some(_)) => Some(args.password.source()),
+        (true, None) => Some(SecretSource::Flag(args.password.source().read(ctx.keychain)?.to_string())),
+    };
+
+    Ok(EncodeOptions {
+        allow_empty: args.allow_empty || from_stdin,
+        unwrap: args.unwrap,
+        wrap_anyway: args.wrap_anyway,
+        payload_format: args.payload_format,
+        expires_at: args.expires,
+        provenance: args.annotate.then(|| Provenance::new(created_at, args.annotation.clone())),
+        compress: args.compress,
+        encrypt,
+        hmac: args.hmac_key.source().filter(|_| args.hmac),
+        sign_key: args.sign_key.clone(),
+        metadata: None,
+        split_size: args.split_size.as_deref().map(|value| size(value) as usize),
+        replace: args.replace,
+        position: args.position,
+        evade: args.evade,
+        text_keyword: args.text_keyword.clone(),
+        itxt: args.itxt.as_ref().map(|keyword| ItxtHeader {
+            keyword: keyword.clone(),
+            language: args.language.clone().unwrap_or_default(),
+            translated_keyword: args.translated_keyword.clone().unwrap_or_default(),
+        }),
+        ztxt: args.ztxt.clone(),
+        latin1_lossy: args.force_latin1_lossy,
+        allow_unsafe_type: args.allow_unsafe_type,
+        dry_run: args.dry_run,
+        verify_after: args.verify_after,
+        keep_on_failure: args.keep_on_failure,
+        size_thresholds: match (args.no_size_warn, args.size_warn.as_slice()) {
+            (true, _) => Vec::new(),
+            (false, []) => COMMON_LIMITS.to_vec(),
+            (false, sizes) => sizes.iter().map(|value| SizeThreshold::custom(size(value))).collect(),
+        },
+        format: args.format,
+    })
+}
+
+/// Embeds every `(chunk type, message)` pair of `messages` in order, with
+/// a single read and write of the image. Nothing is written when any chunk
+/// type or message is refused.
+pub fn encode_many(
+    file: &InputSource,
+    messages: &[(&str, &[u8])],
+    output: &Option<PathBuf>,
+    options: &EncodeOptions,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let unwrapped = unwrap_messages(messages, options, ctx)?;
+    let bodies = message_bodies(messages, &unwrapped, options)?;
+
+    let output_file = &output_path(file, output, ctx)?;
+    ensure_writable(output_file)?;
+    // Asked for before the lock is taken, the prompt may take a while
+    let passphrase = options.encrypt.as_ref().map(|source| source.read(ctx.keychain)).transpose()?;
+    let hmac_key = options.hmac.as_ref().map(|source| source.read(ctx.keychain)).transpose()?;
+    let signing_key = options.sign_key.as_deref().map(signing::read_signing_key).transpose()?;
+    let lock = match options.dry_run {
+        true => None,
+        false => lock_in_place(file, output_file, ctx)?,
+    };
+    if !options.dry_run {
+        backup_in_place(file, output_file, ctx)?;
+    }
+
+    let png = file_to_png(file, ctx)?;
+    let size_before = image_size(&png);
+    let original = ctx.emit_patch.is_some().then(|| png.chunks().to_vec());
+    warn_about_placement(&png, options, ctx);
+
+    let start = Instant::now();
+    let keys = Keys {
+        passphrase: passphrase.as_deref().map(String::as_str),
+        hmac: hmac_key.as_deref().map(String::as_bytes),
+        signing: signing_key.as_ref(),
+    };
+    let batches = bodies
+        .iter()
+        .map(|(chunk_type, body)| Ok((*chunk_type, message_chunks(*chunk_type, body, &keys, options)?)))
+        .collect::<Result<Vec<_>, PngMeError>>()?;
+    let length: u64 = batches.iter().flat_map(|(_, chunks)| chunks).map(|chunk| chunk.length() as u64).sum();
+    let chunk_types: Vec<ChunkType> = batches.iter().map(|&(chunk_type, _)| chunk_type).collect();
+    let (mut png, spans) = embed_messages(png, batches, options, ctx)?;
+    ctx.observer.on_span(Stage::Embed, start.elapsed(), length);
+    renumber_animation(&mut png);
+
+    check_unknown_critical(&png, ctx.allow_unknown_critical)?;
+    if options.dry_run {
+        print_dry_run(&png, size_before, output_file)?;
+    } else {
+        save_undo_state(file, output_file, "encode", ctx)?;
+        // Only IEND chunks may be dropped on write, which moves the chunks
+        // after them
+        let written = |index: usize| {
+            if png.has_normal_iend() {
+                index
+            } else {
+                index - png.chunks()[..index].iter().filter(|chunk| chunk.chunk_type().bytes() == *b"IEND").count()
+            }
+        };
+        let round_trip = options.verify_after.then(|| RoundTrip {
+            messages: chunk_types
+                .iter()
+                .zip(&spans)
+                .zip(&unwrapped)
+                .map(|((&chunk_type, span), message)| (chunk_type, written(span.start)..written(span.end), &message[..]))
+                .collect(),
+            text: options.text_keyword.is_some() || options.itxt.is_some() || options.ztxt.is_some(),
+            passphrase: keys.passphrase,
+            keep_on_failure: options.keep_on_failure,
+        });
+        write_png_verified(&png, output_file, round_trip.as_ref(), ctx)?;
+        if let (Some(lock), Some(transaction)) = (lock, ctx.transaction.borrow_mut().as_mut()) {
+            transaction.hold(lock);
+        }
+        emit_patch(original, &png, ctx)?;
+    }
+
+    report_encoded(output_file, image_size(&png), &options.size_thresholds, options.format, ctx)
+}
+
+/// The messages of `encode_many`, with the message of those that already
+/// are an envelope instead with `unwrap`. A payload decoded from another
+/// image may still be an envelope, which wrapped again would leave decode
+/// showing the inner envelope.
+fn unwrap_messages<'a>(
+    messages: &[(&str, &'a [u8])],
+    options: &EncodeOptions,
+    ctx: &Context,
+) -> Result<Vec<Cow<'a, [u8]>>, PngMeError> {
+    let mut unwrapped = Vec::with_capacity(messages.len());
+    for &(name, message) in messages {
+        unwrapped.push(match Envelope::parse(message) {
+            Some(envelope) if options.unwrap => {
+                Cow::Owned(envelope.reveal(None, ctx.max_decompressed_size)?.message)
+            }
+            Some(_) if !options.wrap_anyway => {
+                eprintln!(
+                    "warning[{}]: the {name} payload appears to already be a pngme envelope; embedding as-is \
+                     — pass --wrap-anyway to silence or --unwrap to extract the inner payload first",
+                    Code::NestedEnvelope
+                );
+                Cow::Borrowed(message)
+            }
+            _ => Cow::Borrowed(message),
+        });
+    }
+
+    Ok(unwrapped)
+}
+
+/// A message as its chunk holds it, with the type of the chunk
+type Body<'a> = (ChunkType, Cow<'a, [u8]>);
+
+/// The chunk type of each message, adapted to the `--evade` profile, with
+/// the message as the chunk holds it: the text of a tEXt, iTXt or zTXt
+/// chunk, else the message itself. Refuses unsafe chunk types, empty
+/// messages and messages that aren't of `--payload-format`.
+fn message_bodies<'a>(
+    messages: &[(&str, &[u8])],
+    unwrapped: &'a [Cow<'a, [u8]>],
+    options: &EncodeOptions,
+) -> Result<Vec<Body<'a>>, PngMeError> {
+    let mut bodies = Vec::with_capacity(messages.len());
+    for (&(name, _), message) in messages.iter().zip(unwrapped) {
+        let message: &[u8] = message;
+        let mut chunk_type = ChunkType::from_str(name)?;
+        if let Some(profile) = options.evade {
+            let adapted = profile.chunk_type(chunk_type);
+            if adapted != chunk_type {
+                eprintln!("Writing {chunk_type} as {adapted} for the {} profile", profile.name);
+            }
+            chunk_type = adapted;
+        }
+        if !options.allow_unsafe_type {
+            check_chunk_safety(&chunk_type)?;
+        }
+
+        if message.is_empty() && !options.allow_empty {
+            return Err(PngMeError::EmptyMessage);
+        }
+
+        if !message.is_empty() && message.trim_ascii().is_empty() {
+            eprintln!("warning[{}]: the {chunk_type} message only contains whitespace", Code::WhitespaceMessage);
+        }
+        if let Some(format) = &options.payload_format {
+            format.validate(message)?;
+        }
+        // The text is read as UTF-8, other bytes would be replaced
+        let text = || std::str::from_utf8(message).map_err(|_| FormatError::NotUtf8);
+        // Then encoded as Latin-1 for tEXt and zTXt
+        let latin1 = || {
+            let text = text()?;
+            if !options.latin1_lossy {
+                return Ok::<_, PngMeError>(Cow::Borrowed(text));
+            }
+            let (bytes, substituted) = latin1_encode_lossy(text);
+            if substituted > 0 {
+                eprintln!(
+                    "warning[{}]: replaced {substituted} character(s) outside Latin-1 with '?'",
+                    Code::Latin1Substituted
+                );
+            }
+            Ok(Cow::Owned(latin1_decode(&bytes)))
+        };
+        let body = match (&options.text_keyword, &options.itxt, &options.ztxt) {
+            (Some(keyword), _, _) => Cow::Owned(text_chunk_data(keyword, &latin1()?)?),
+            (_, Some(header), _) => Cow::Owned(header.chunk_data(text()?)?),
+            (_, _, Some(keyword)) => Cow::Owned(ztxt_chunk_data(keyword, &latin1()?)?),
+            (None, None, None) => Cow::Borrowed(message),
+        };
+        bodies.push((chunk_type, body));
+    }
+
+    Ok(bodies)
+}
+
+/// Warns about what the image and the options mean for the chunks about to
+/// be added: a pipeline of `--evade` they may not survive, an expiry
+/// already past and an index between the frames of an animation
+fn warn_about_placement(png: &Png, options: &EncodeOptions, ctx: &Context) {
+    if let Some(profile) = options.evade {
+        for constraint in profile.image_problems(png) {
+            eprintln!(
+                "warning[{}]: the chunk may not survive {}, it needs the {constraint}",
+                Code::PipelineConstraint,
+                profile.description
+            );
+        }
+    }
+
+    if options.expires_at.is_some_and(|expires_at| expires_at <= ctx.clock.now()) {
+        eprintln!("warning[{}]: the message is already expired", Code::MessageAlreadyExpired);
+    }
+
+    if let Position::Index(index) = encode_position(options)
+        && apng::is_inside_animation(png, index)
+    {
+        eprintln!("Note: index {index} is between the frames of the animation, its sequence numbers are kept in order");
+    }
+}
+
+/// Where added chunks go, the `--evade` profile picking it when given
+fn encode_position(options: &EncodeOptions) -> Position {
+    options.evade.map_or(options.position, Profile::position)
+}
+
+/// The secrets of [`EncodeOptions`], read once per image
+struct Keys<'a> {
+    passphrase: Option<&'a str>,
+    hmac: Option<&'a [u8]>,
+    signing: Option<&'a SigningKey>,
+}
+
+/// The chunks of one message of `encode_many`: the pieces of the payload,
+/// wrapped in an [`Envelope`] when it expires, carries its provenance, is
+/// compressed, encrypted or tagged, followed by its meTa and siGn chunks
+fn message_chunks(
+    chunk_type: ChunkType,
+    message: &[u8],
+    keys: &Keys,
+    options: &EncodeOptions,
+) -> Result<Vec<Chunk>, PngMeError> {
+    let payload = if options.expires_at.is_some()
+        || options.provenance.is_some()
+        || options.compress
+        || keys.passphrase.is_some()
+        || keys.hmac.is_some()
+    {
+        let mut envelope = Envelope {
+            expires_at: options.expires_at,
+            provenance: options.provenance.clone(),
+            ..Envelope::new(message.to_vec())
+        };
+        // Compressed first, ciphertext doesn't compress
+        if options.compress {
+            envelope = envelope.deflate();
+        }
+        if let Some(passphrase) = keys.passphrase {
+            envelope = envelope.encrypt(passphrase);
+        }
+        // Last, the tag covers the envelope as stored
+        if let Some(key) = keys.hmac {
+            envelope = envelope.sign(key);
+        }
+        envelope.to_bytes()
+    } else {
+        message.to_vec()
+    };
+
+    // Over the whole payload, as `verify` joins the pieces back
+    let signature = keys.signing.map(|key| signing::sign(key, &chunk_type, &payload));
+    let pieces = match options.split_size {
+        Some(size) => split::split(&payload, size)?,
+        None => vec![payload],
+    };
+
+    let mut chunks = pieces
+        .into_iter()
+        .map(|data| Chunk::try_new(chunk_type, data).map_err(|err| PngError::from(PngParserError::from(err))))
+        .collect::<Result<Vec<_>, _>>()?;
+    chunks.extend(options.metadata.as_ref().map(|metadata| metadata.to_chunk(&chunk_type)));
+    chunks.extend(signature);
+
+    Ok(chunks)
+}
+
+/// Adds the chunks of each message of `batches` to `png`, at the position
+/// of the options or over the chunk it replaces with `--replace`. Returns
+/// the image with, for each message, the indices of the chunks holding its
+/// pieces.
+fn embed_messages(
+    png: Png,
+    batches: Vec<(ChunkType, Vec<Chunk>)>,
+    options: &EncodeOptions,
+    ctx: &Context,
+) -> Result<(Png, Vec<Range<usize>>), PngMeError> {
+    let total = batches.iter().map(|(_, chunks)| chunks.len() as u64).sum();
+    let mut done = 0;
+    ctx.observer.on_progress(Stage::Embed, 0, Some(total));
+
+    // Every index is planned on the image as read, so that a message only
+    // replaces a chunk the image had, never one added for another message.
+    // The indices of the replaced chunks, with the message replacing them:
+    let mut replaced: Vec<(usize, usize)> = Vec::new();
+    // The messages added at `position`, in the order of `messages`
+    let mut added = Vec::new();
+    for (number, (chunk_type, _)) in batches.iter().enumerate() {
+        let existing = png.chunks().iter().position(|chunk| chunk.chunk_type() == chunk_type);
+        match existing {
+            Some(index) if options.replace && !replaced.iter().any(|&(replaced, _)| replaced == index) => {
+                replaced.push((index, number));
+            }
+            _ => {
+                if existing.is_some() {
+                    eprintln!(
+                        "warning[{}]: the image already has a {chunk_type} chunk, adding another one (pass --replace to overwrite it)",
+                        Code::DuplicateChunk
+                    );
+                }
+                added.push(number);
+            }
+        }
+    }
+    let anchor = match added.is_empty() {
+        true => None,
+        false => Some(png.index_of(encode_position(options))?),
+    };
+    let chunk_types: Vec<ChunkType> = batches.iter().map(|&(chunk_type, _)| chunk_type).collect();
+    let replaced_types: Vec<ChunkType> = replaced.iter().map(|&(_, number)| chunk_types[number]).collect();
+    // Only the first chunk of a replaced type is overwritten. The others,
+    // e.g. the pieces of an earlier split message, would be read along with
+    // the new one, and the signatures and metadata of the replaced messages
+    // don't hold anymore, whether or not the new one gets its own.
+    let is_stale = |chunk: &Chunk| {
+        replaced_types.contains(chunk.chunk_type())
+            || (options.replace
+                && chunk_types.iter().any(|chunk_type| {
+                    signing::is_signature_of(chunk, chunk_type) || metadata::is_metadata_of(chunk, chunk_type)
+                }))
+    };
+
+    // Where the pieces of each message end up, for `--verify-after`
+    let mut spans = vec![0..0; batches.len()];
+    let mut batches: Vec<_> = batches.into_iter().map(|(_, chunks)| Some(chunks)).collect();
+    let mut embed = |number: usize, chunks: &mut Vec<Chunk>| {
+        let batch = batches[number].take().expect("each message is embedded once");
+        // The pieces come first, then the metadata and the signature
+        let pieces = batch.iter().take_while(|chunk| chunk.chunk_type() == batch[0].chunk_type()).count();
+        spans[number] = chunks.len()..chunks.len() + pieces;
+        for chunk in batch {
+            chunks.push(chunk);
+            done += 1;
+            ctx.observer.on_progress(Stage::Embed, done, Some(total));
+        }
+    };
+    let as_read = png.into_chunks();
+    let read_len = as_read.len();
+    let mut chunks = Vec::with_capacity(read_len + total as usize);
+    for (index, chunk) in as_read.into_iter().enumerate() {
+        if anchor == Some(index) {
+            added.iter().for_each(|&number| embed(number, &mut chunks));
+        }
+        match replaced.iter().find(|&&(replaced, _)| replaced == index) {
+            Some(&(_, number)) => embed(number, &mut chunks),
+            None if is_stale(&chunk) => {}
+            None => chunks.push(chunk),
+        }
+    }
+    if anchor == Some(read_len) {
+        added.iter().for_each(|&number| embed(number, &mut chunks));
+    }
+
+    Ok((Png::from_chunks(chunks), spans))
+}
+
+/// How one file of an `encode --glob` batch went
+#[derive(Serialize)]
+struct BatchFile<'a> {
+    path: &'a Path,
+    /// `None` when skipped after a failure with `--fail-fast`, or as done
+    /// by an earlier run with `--resume`
+    encoded: Option<bool>,
+    /// Encoded by an earlier run of the batch, per the journal of `--resume`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    done_before: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<FileError>,
+}
+
+#[derive(Serialize)]
+struct FileError {
+    code: Code,
+    message: String,
+}
+
+/// What `encode --glob --format json` prints after the reports of the files
+#[derive(Serialize)]
+struct BatchReport<'a> {
+    files: Vec<BatchFile<'a>>,
+    encoded: usize,
+    failed: usize,
+    skipped: usize,
+    /// Files encoded by an earlier run, only with `--resume`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    done_before: Option<usize>,
+    /// Whether the files were written, only with `--transactional`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    committed: Option<bool>,
+}
+
+/// Runs `encode` on each of `files` in order, then reports which were
+/// encoded. A failure doesn't stop the batch unless `fail_fast` is set, the
+/// files after it are then skipped. Fails when any file did.
+///
+/// With `--transactional` the outputs are staged in a [`Transaction`],
+/// committed only when every file was encoded.
+///
+/// With a `journal` each file is recorded as it starts and ends, a file
+/// the journal already marks done being skipped. Files of a transaction
+/// are recorded done once it is committed.
+pub fn encode_batch(
+    files: &[PathBuf],
+    fail_fast: bool,
+    format: OutputFormat,
+    mut journal: Option<Journal>,
+    ctx: &Context,
+    mut encode: impl FnMut(&InputSource) -> Result<(), PngMeError>,
+) -> Result<(), PngMeError> {
+    if ctx.transactional {
+        ctx.transaction.replace(Some(Transaction::new()));
+    }
+    let resumed = journal.is_some();
+    let mut results = Vec::with_capacity(files.len());
+    for path in files {
+        let skipped = |done_before| BatchFile {
+            path,
+            encoded: None,
+            done_before,
+            error: None,
+        };
+        if fail_fast && results.iter().any(|result: &BatchFile| result.error.is_some()) {
+            results.push(skipped(false));
+            continue;
+        }
+        if let Some(journal) = &mut journal {
+            if journal.is_done(path)? {
+                results.push(skipped(true));
+                continue;
+            }
+            journal.start(path)?;
+        }
+
+        let error = encode(&InputSource::Path(path.clone())).err();
+        if let Some(journal) = &mut journal {
+            match &error {
+                Some(_) => journal.record(path, JournalStatus::Failed, None)?,
+                None if !ctx.transactional => journal.record(path, JournalStatus::Done, None)?,
+                None => {}
+            }
+        }
+        results.push(BatchFile {
+            path,
+            encoded: Some(error.is_none()),
+            done_before: false,
+            error: error.map(|error| FileError {
+                code: error.code(),
+                message: error.to_string(),
+            }),
+        });
+    }
+
+    let count = |encoded| results.iter().filter(|result| result.encoded == encoded).count();
+    let failed = count(Some(false));
+    let done_before = results.iter().filter(|result| result.done_before).count();
+    let committed = match ctx.transaction.take() {
+        Some(transaction) if failed == 0 => {
+            transaction.commit().map_err(|(path, err)| {
+                eprintln!("Transaction failed while replacing {}, the files before it were changed", path.display());
+                err
+            })?;
+            if let Some(journal) = &mut journal {
+                for result in results.iter().filter(|result| result.encoded == Some(true)) {
+                    journal.record(result.path, JournalStatus::Done, None)?;
+                }
+            }
+            Some(true)
+        }
+        Some(_) => {
+            eprintln!("Transaction aborted, {failed} of {} file(s) failed, no file was changed", files.len());
+            Some(false)
+        }
+        None => None,
+    };
+    let report = BatchReport {
+        encoded: count(Some(true)),
+        failed,
+        skipped: count(None) - done_before,
+        done_before: resumed.then_some(done_before),
+        committed,
+        files: results,
+    };
+
+    let mut out = io::stdout().lock();
+    match format {
+        OutputFormat::Json => writeln!(out, "{}", json_report(&report, ctx)?)?,
+        OutputFormat::Human => {
+            for file in &report.files {
+                let path = file.path.display();
+                match (&file.encoded, &file.error) {
+                    (_, Some(error)) => writeln!(out, "{path}: failed, error[{}]: {}", error.code, error.message)?,
+                    (Some(_), None) if report.committed == Some(false) => writeln!(out, "{path}: rolled back")?,
+                    (Some(_), None) => writeln!(out, "{path}: encoded")?,
+                    (None, None) if file.done_before => writeln!(out, "{path}: done before")?,
+                    (None, None) => writeln!(out, "{path}: skipped")?,
+                }
+            }
+            write!(out, "Encoded {} of {} file(s)", report.encoded, files.len())?;
+            if done_before > 0 {
+                write!(out, ", {done_before} done before")?;
+            }
+            if report.failed > 0 {
+                write!(out, ", {} failed", report.failed)?;
+            }
+            if report.skipped > 0 {
+                write!(out, ", {} skipped after the first failure", report.skipped)?;
+            }
+            writeln!(out)?;
+        }
+    }
+
+    match report.failed {
+        0 => Ok(()),
+        failed => Err(PngMeError::BatchFailed { failed, total: files.len() }),
+    }
+}
+
+/// What `encode --format json` prints
+#[derive(Serialize)]
+struct EncodeReport<'a> {
+    output: &'a Path,
+    size: u64,
+    warnings: Vec<SizeWarning>,
+}
+
+/// Warns about the `thresholds` an output of `size` bytes exceeds, in the
+/// report with JSON
+fn report_encoded(
+    output: &Path,
+    size: u64,
+    thresholds: &[SizeThreshold],
+    format: OutputFormat,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let warnings = upload_limits::exceeded(size, thresholds);
+
+    let mut out = io::stdout().lock();
+    match format {
+        OutputFormat::Json => writeln!(out, "{}", json_report(&EncodeReport { output, size, warnings }, ctx)?)?,
+        OutputFormat::Human => {
+            for warning in warnings {
+                eprintln!("warning[{}]: {}", warning.code, warning.message);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Bytes `png` takes once written
+fn image_size(png: &Png) -> u64 {
+    let chunks: u64 = png
+        .chunks()
+        .iter()
+        .map(|chunk| (CHUNK_OVERHEAD as u64) + chunk.length() as u64)
+        .sum();
+
+    SIGNATURE_LEN as u64 + chunks
+}
+
+/// Prints what an edit would write to `output`: every chunk with its length
+/// and offset, and how the size of the image changes from `size_before`
+fn print_dry_run(png: &Png, size_before: u64, output: &Path) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    writeln!(out, "Dry run, nothing written to {}", output.display())?;
+
+    let mut offset = SIGNATURE_LEN as u64;
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        writeln!(
+            out,
+            "#{index} {} length {} at offset {offset}",
+            chunk.chunk_type(),
+            chunk.length()
+        )?;
+        offset += CHUNK_OVERHEAD as u64 + chunk.length() as u64;
+    }
+
+    let delta = offset as i128 - size_before as i128;
+    writeln!(out, "Size: {size_before} -> {offset} bytes ({delta:+})")
+}
+
+/// Closes the gaps adding or removing chunks left in the sequence numbers
+/// of an animated image, see [`apng`]
+fn renumber_animation(png: &mut Png) {
+    let renumbered = apng::renumber(png);
+    if renumbered > 0 {
+        eprintln!("Renumbered {renumbered} animation chunk(s) to keep the frames in sequence");
+    }
+}
+
+/// Refuses chunk types most decoders choke on: critical ones, which they
+/// reject when they don't know them, and ones with the reserved bit set.
+/// The error suggests the ancillary type with the reserved bit clear.
+pub(crate) fn check_chunk_safety(chunk_type: &ChunkType) -> Result<(), PngMeError> {
+    let mut bytes = chunk_type.bytes();
+    bytes[0] = bytes[0].to_ascii_lowercase();
+    bytes[2] = bytes[2].to_ascii_uppercase();
+    let suggestion = String::from_utf8_lossy(&bytes).into_owned();
+
+    if chunk_type.is_critical() {
+        return Err(PngMeError::CriticalChunkType {
+            chunk_type: chunk_type.to_string(),
+            suggestion,
+        });
+    }
+    if !chunk_type.is_reserved_bit_valid() {
+        return Err(PngMeError::ReservedBitChunkType {
+            chunk_type: chunk_type.to_string(),
+            suggestion,
+        });
+    }
+    Ok(())
+}
+
+/// Validates a chunk name given on the command line before any I/O.
+///
+/// A name with the reserved bit set gets a warning suggesting the valid
+/// casing, e.g. when looking up `Rust` instead of `RuSt`. When `writing` a
+/// chunk of that type, it also needs confirmation (or `assume_yes`), and a
+/// critical chunk about to be written gets a warning.
+pub fn check_chunk_name(name: &str, writing: bool, assume_yes: bool) -> Result<(), PngMeError> {
+    let chunk_type = ChunkType::parse_name(name)?;
+
+    if writing && chunk_type.is_critical() {
+        eprintln!(
+            "warning[{}]: '{chunk_type}' is critical, decoders that don't know it will reject the image",
+            Code::CriticalChunkWritten
+        );
+    }
+
+    let Some(suggestion) = chunk_type.suggested_casing() else {
+        return Ok(());
+    };
+
+    eprintln!(
+        "warning[{}]: '{chunk_type}' has the reserved bit set; did you mean '{suggestion}'?",
+        Code::ReservedBitSet
+    );
+    if !writing || assume_yes {
+        return Ok(());
+    }
+
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Continue with '{chunk_type}'? [y/N] ");
+        let mut answer = String::new();
+        stdin.read_line(&mut answer)?;
+
+        if matches!(answer.trim(), "y" | "Y" | "yes") {
+            return Ok(());
+        }
+    }
+
+    Err(PngMeError::NotConfirmed {
+        chunk_type: chunk_type.to_string(),
+    })
+}
+
+/// Fails early, before any parsing work, when an existing destination
+/// can't be written to.
+fn ensure_writable(path: &Path) -> Result<(), PngMeError> {
+    let not_writable = |reason| PngMeError::NotWritable {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    if path == Path::new("-") {
+        return Ok(());
+    }
+
+    let Ok(metadata) = fs::metadata(path) else {
+        // The destination will be created
+        return Ok(());
+    };
+
+    // Also covers the read-only attribute on Windows
+    if metadata.permissions().readonly() {
+        return Err(not_writable("read-only"));
+    }
+
+    match OpenOptions::new().write(true).open(path) {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+            Err(not_writable("permission denied"))
+        }
+        // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+        #[cfg(windows)]
+        Err(err) if matches!(err.raw_os_error(), Some(32) | Some(33)) => {
+            Err(not_writable("locked by another process"))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Writes through a [`WriteSink`](crate::sink::WriteSink) so that a failed write leaves an existing
+/// destination untouched. `-` writes to stdout.
+fn write_png(png: &Png, path: &Path, ctx: &Context) -> Result<(), PngMeError> {
+    write_png_verified(png, path, None, ctx)
+}
+
+/// Messages `encode --verify-after` reads back from its output
+struct RoundTrip<'a> {
+    /// Each message with the indices of the chunks holding its pieces
+    messages: Vec<(ChunkType, Range<usize>, &'a [u8])>,
+    /// Whether the messages were written as the text of tEXt, iTXt or zTXt
+    /// chunks
+    text: bool,
+    passphrase: Option<&'a str>,
+    keep_on_failure: bool,
+}
+
+impl RoundTrip<'_> {
+    /// Parses `bytes` and decodes each message from the chunks it was
+    /// written to, the way `decode` does, returning the chunk type of the
+    /// first one that doesn't match the original bytes with why
+    fn check(&self, bytes: &[u8], ctx: &Context) -> Result<(), (ChunkType, String)> {
+        let first = self.messages[0].0;
+        let png = Png::parse(bytes, &ctx.parse_options, &NoopObserver)
+            .map_err(|err| (first, format!("the image doesn't parse ({err})")))?;
+        let limit = ctx.max_decompressed_size;
+
+        for (chunk_type, span, expected) in &self.messages {
+            let failed = |reason: String| (*chunk_type, reason);
+            let pieces = png
+                .chunks()
+                .get(span.clone())
+                .filter(|pieces| pieces.iter().all(|chunk| chunk.chunk_type() == chunk_type))
+                .ok_or_else(|| failed("the chunk is missing".to_string()))?;
+            let chunk = match split::join(pieces.iter()).map_err(|err| failed(err.to_string()))? {
+                Some(joined) => Cow::Owned(joined),
+                None => Cow::Borrowed(&pieces[0]),
+            };
+            let decoded = match self.text {
+                true => payload_text(&chunk, self.passphrase, limit).map(String::into_bytes),
+                false => payload_bytes(&chunk, self.passphrase, limit),
+            };
+            let decoded = decoded.map_err(|err| failed(err.to_string()))?;
+
+            if decoded.len() != expected.len() {
+                return Err(failed(format!("it decodes to {} bytes instead of {}", decoded.len(), expected.len())));
+            }
+            if let Some(offset) = decoded.iter().zip(expected.iter()).position(|(a, b)| a != b) {
+                return Err(failed(format!("it decodes to different bytes from offset {offset}")));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes like [`write_png`]. With a `round_trip`, the output is read back
+/// from the temporary file before it is renamed over the destination, and
+/// only renamed when every message decodes as expected or
+/// `keep_on_failure` is set. A file output is only staged while
+/// [`Context::transaction`] holds a transaction, the round trip then reads
+/// the bytes about to be staged.
+fn write_png_verified(png: &Png, path: &Path, round_trip: Option<&RoundTrip>, ctx: &Context) -> Result<(), PngMeError> {
+    let start = Instant::now();
+    let bytes = png.as_bytes();
+    let total = bytes.len() as u64;
+    ctx.observer.on_span(Stage::Serialize, start.elapsed(), total);
+
+    let start = Instant::now();
+    let storage_full = |err: io::Error| match is_storage_full(&err) {
+        true => PngMeError::StorageFull { path: path.to_path_buf() },
+        false => err.into(),
+    };
+    let round_trip_failed = |round_trip: &RoundTrip, (chunk_type, reason): (ChunkType, String)| {
+        PngMeError::RoundTripFailed {
+            path: path.to_path_buf(),
+            chunk_type: chunk_type.to_string(),
+            reason,
+            kept: round_trip.keep_on_failure,
+        }
+    };
+    ctx.observer.on_progress(Stage::Write, 0, Some(total));
+    if let Some(transaction) = ctx.transaction.borrow_mut().as_mut()
+        && path != Path::new("-")
+    {
+        let checked = round_trip.map(|round_trip| (round_trip, round_trip.check(&bytes, ctx)));
+        if let Some((round_trip, Err(failure))) = checked {
+            if round_trip.keep_on_failure {
+                transaction.stage(path, &bytes).map_err(storage_full)?;
+            }
+            return Err(round_trip_failed(round_trip, failure));
+        }
+        transaction.stage(path, &bytes).map_err(storage_full)?;
+        ctx.observer.on_progress(Stage::Write, total, Some(total));
+        ctx.observer.on_span(Stage::Write, start.elapsed(), total);
+
+        return Ok(());
+    }
+    let mut sink = sink_for(path);
+    match round_trip {
+        None => write_to_sink(sink.as_mut(), &bytes).map_err(storage_full)?,
+        Some(round_trip) => {
+            let written = stage_and_read_back(sink.as_mut(), &bytes).map_err(storage_full)?;
+            let checked = round_trip.check(&written, ctx);
+            if checked.is_ok() || round_trip.keep_on_failure {
+                let committed = sink.commit();
+                if committed.is_err() {
+                    sink.abort();
+                }
+                committed.map_err(storage_full)?;
+            } else {
+                sink.abort();
+            }
+            if let Err(failure) = checked {
+                return Err(round_trip_failed(round_trip, failure));
+            }
+        }
+    }
+    ctx.observer.on_progress(Stage::Write, total, Some(total));
+    ctx.observer.on_span(Stage::Write, start.elapsed(), total);
+
+    Ok(())
+}
+
+/// Checks that the `chunk_type` chunk of `file` is signed with the Ed25519
+/// key of the PEM file `pubkey`
+pub fn verify_signature(file: &InputSource, chunk_type: &str, pubkey: &Path, ctx: &Context) -> Result<(), PngMeError> {
+    let key = signing::read_verifying_key(pubkey)?;
+    let png = file_to_png(file, ctx)?;
+    let chunk = message_chunk(&png, chunk_type)?.ok_or_else(|| PngError::ChunkNotFound {
+        chunk_type: chunk_type.to_string(),
+    })?;
+
+    signing::verify(png.chunks(), &chunk, &key)?;
+    let mut out = io::stdout().lock();
+    writeln!(out, "Good signature of the {chunk_type} chunk by {}", pubkey.display())?;
+
+    Ok(())
+}
+
+/// Writes the patch from `original` to `png` where `--emit-patch` asks,
+/// `original` being only kept when it does
+fn emit_patch(original: Option<Vec<Chunk>>, png: &Png, ctx: &Context) -> Result<(), PngMeError> {
+    let (Some(original), Some(path)) = (original, &ctx.emit_patch) else {
+        return Ok(());
+    };
+    let patch = patch::diff(&original, png.chunks());
+    fs::write(path, serde_json::to_string(&patch)? + "\n")?;
+
+    Ok(())
+}
+
+/// The chunk holding the `chunk_type` message: the pieces of a split
+/// message joined back, else the first chunk of the type
+pub(crate) fn message_chunk<'a>(png: &'a Png, chunk_type: &str) -> Result<Option<Cow<'a, Chunk>>, PngMeError> {
+    Ok(match split::join(png.chunks_by_type(chunk_type))? {
+        Some(joined) => Some(Cow::Owned(joined)),
+        None => png.chunk_by_type(chunk_type).map(Cow::Borrowed),
+    })
+}
+
+/// Where `decode --output` writes `payload` when `output` is a directory,
+/// named after the image and the chunk, or ends with a dot, with the
+/// extension of the format [`sniff`](sniff::sniff) finds. `None` for any
+/// other output, taken as it is.
+fn sniffed_output(
+    output: &Path,
+    file: &InputSource,
+    chunk_type: &ChunkType,
+    payload: &[u8],
+) -> Option<(PathBuf, FileKind)> {
+    let kind = sniff::sniff(payload);
+    let extension = kind.extension();
+    let path = if output.is_dir() {
+        let stem = file.file_name();
+        let stem = stem.as_deref().map(Path::new).and_then(Path::file_stem);
+        let name = match stem {
+            Some(stem) => format!("{}-{chunk_type}.{extension}", stem.to_string_lossy()),
+            None => format!("{chunk_type}.{extension}"),
+        };
+        output.join(name)
+    } else if output.as_os_str().as_encoded_bytes().ends_with(b".") {
+        let mut path = output.as_os_str().to_owned();
+        path.push(extension);
+        PathBuf::from(path)
+    } else {
+        return None;
+    };
+
+    Some((path, kind))
+}
+
+/// Payloads from this size on are encoded as they are printed rather than
+/// into a string first
+const STREAMED_PAYLOAD: usize = 1 << 20;
+
+/// Prints `payload` in `encoding`, wrapped after `wrap` characters
+fn write_encoded(out: &mut impl Write, encoding: Encoding, wrap: Option<usize>, payload: &[u8]) -> Result<(), PngMeError> {
+    if payload.len() >= STREAMED_PAYLOAD
+        && let Some(mut writer) = EncodingWriter::new(&mut *out, encoding, wrap)
+    {
+        writer.write_all(payload)?;
+        writer.finish()?;
+        return Ok(());
+    }
+
+    let text = encoding.encode(payload)?;
+    match wrap {
+        Some(width) => write!(out, "{}", format::wrap(&text, width))?,
+        None => write!(out, "{text}")?,
+    }
+    Ok(())
+}
+
+/// Decodes a message in `encoding` as it is read, so a large one is never
+/// held whole as text. `name` and `limit` bound it like any input
+pub fn read_encoded(mut reader: impl Read, encoding: Encoding, name: &str, limit: Option<u64>) -> Result<Vec<u8>, PngMeError> {
+    let Some(mut decoder) = StreamDecoder::new(encoding) else {
+        let mut text = Vec::new();
+        reader.read_to_end(&mut text)?;
+        return Ok(text);
+    };
+    let mut block = vec![0; 64 * 1024];
+    let mut size = 0;
+
+    loop {
+        let read = match reader.read(&mut block) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        size += read as u64;
+        if let Some(limit) = limit.filter(|&limit| size > limit) {
+            return Err(InputError::TooLarge { name: name.to_string(), size, limit }.into());
+        }
+        decoder.push(&block[..read])?;
+    }
+
+    Ok(decoder.finish()?)
+}
+
+/// The message of an envelope, or the whole payload
+fn payload_bytes(chunk: &Chunk, passphrase: Option<&str>, limit: u64) -> Result<Vec<u8>, PngMeError> {
+    match Envelope::parse(chunk.data()) {
+        Some(envelope) => Ok(envelope.reveal(passphrase, limit)?.message),
+        None => Ok(chunk.data().to_vec()),
+    }
+}
+
+/// Payload as text, replacing invalid UTF-8 sequences. Enveloped payloads
+/// give their message, tEXt and iTXt chunks their text without the other
+/// fields.
+fn payload_text(chunk: &Chunk, passphrase: Option<&str>, limit: u64) -> Result<String, PngMeError> {
+    if let Some(envelope) = Envelope::parse(chunk.data()) {
+        return Ok(String::from_utf8_lossy(&envelope.reveal(passphrase, limit)?.message).into_owned());
+    }
+    if let Some((_, text)) = chunk.text_keyword_and_value() {
+        return Ok(text);
+    }
+    if let Some(itxt) = chunk.itxt() {
+        return Ok(itxt.text(limit)?);
+    }
+    if let Some(ztxt) = chunk.ztxt() {
+        return Ok(ztxt?.text(limit)?);
+    }
+
+    Ok(chunk
+        .data_as_text()
+        .unwrap_or_else(|| String::from_utf8_lossy(chunk.data()).into_owned()))
+}
+
+/// JSON report of `decode`, `data` is `null` when the chunk was not found
+/// or its message expired
+#[derive(Serialize)]
+struct DecodeReport<'a> {
+    file: String,
+    chunk_type: &'a str,
+    found: bool,
+    length: Option<u32>,
+    data: Option<String>,
+    /// Keyword of a tEXt, zTXt or iTXt chunk, left out of `data`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keyword: Option<String>,
+    /// Language tag and translated keyword of an iTXt chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    translated_keyword: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    expired: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provenance: Option<Provenance>,
+    /// What the meTa chunk written by `encode --meta` records
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+    /// Encoding of `data` when it is not the text itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+    /// What the file holds instead, when the chunk was not found
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    suggestions: Vec<DecodeSuggestion>,
+}
+
+/// Hint given by `decode` when the chunk is not found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum DecodeSuggestion {
+    /// A chunk type with the same letters in another case
+    DifferentCase { chunk_type: String },
+    /// A chunk holding a pngme envelope
+    PngmeMessage { chunk_type: String },
+    /// A chunk type the specification doesn't define
+    NonStandard { chunk_type: String },
+}
+
+/// Non-standard chunk types listed at most by [`suggestions`]
+const MAX_NON_STANDARD_SUGGESTIONS: usize = 5;
+
+/// What the file holds instead of the missing `chunk_type`: types differing
+/// only by case, chunks holding a pngme envelope, then a handful of
+/// non-standard ancillary types.
+fn suggestions(png: &Png, chunk_type: &str) -> Vec<DecodeSuggestion> {
+    let mut types: Vec<&ChunkType> = Vec::new();
+    for chunk in png.chunks() {
+        if !types.contains(&chunk.chunk_type()) {
+            types.push(chunk.chunk_type());
+        }
+    }
+
+    let different_case = types
+        .iter()
+        .map(|chunk_type| chunk_type.to_string())
+        .filter(|name| name != chunk_type && name.eq_ignore_ascii_case(chunk_type))
+        .map(|chunk_type| DecodeSuggestion::DifferentCase { chunk_type });
+    let enveloped = types
+        .iter()
+        .filter(|candidate| {
+            png.chunks().iter().any(|chunk| {
+                chunk.chunk_type() == **candidate && Envelope::parse(chunk.data()).is_some()
+            })
+        })
+        .map(|chunk_type| DecodeSuggestion::PngmeMessage {
+            chunk_type: chunk_type.to_string(),
+        });
+    let non_standard = types
+        .iter()
+        .filter(|chunk_type| !chunk_type.is_critical() && !chunk_type.is_standard())
+        .take(MAX_NON_STANDARD_SUGGESTIONS)
+        .map(|chunk_type| DecodeSuggestion::NonStandard {
+            chunk_type: chunk_type.to_string(),
+        });
+
+    different_case.chain(enveloped).chain(non_standard).collect()
+}
+
+/// Prints the [`suggestions`] after a failed lookup
+fn print_suggestions(prefix: &str, suggestions: &[DecodeSuggestion]) {
+    let mut non_standard = Vec::new();
+
+    for suggestion in suggestions {
+        match suggestion {
+            DecodeSuggestion::DifferentCase { chunk_type } => {
+                eprintln!("{prefix}Did you mean {chunk_type}? It only differs by case")
+            }
+            DecodeSuggestion::PngmeMessage { chunk_type } => {
+                eprintln!("{prefix}A pngme message was found in a {chunk_type} chunk")
+            }
+            DecodeSuggestion::NonStandard { chunk_type } => non_standard.push(chunk_type.as_str()),
+        }
+    }
+
+    if non_standard.is_empty() {
+        eprintln!("{prefix}The file has no non-standard chunks");
+    } else {
+        eprintln!("{prefix}Non-standard chunks in the file: {}", non_standard.join(", "));
+    }
+}
+
+/// How `decode` shows what it finds
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    /// Only print the message
+    pub quiet: bool,
+    /// Write the stored bytes as they are
+    pub raw: bool,
+    /// Show expired messages
+    pub ignore_expiry: bool,
+    /// Show the innermost message of nested envelopes
+    pub unwrap_all: bool,
+    pub format: OutputFormat,
+    /// Encoding of the printed message
+    pub encoding: Encoding,
+    /// Characters per line of an encoded message, on a single line when
+    /// `None`
+    pub wrap: Option<usize>,
+    /// Print the message laid out as a document of this format
+    pub payload_format: Option<PayloadFormat>,
+    /// Clean-ups of a text message
+    pub text: TextOptions,
+    /// File the message bytes are written to, as they are
+    pub output: Option<PathBuf>,
+    /// Name the file after the format of the message when `output` is a
+    /// directory or ends with a dot
+    pub sniff: bool,
+    /// Passphrase of encrypted messages, from `--decrypt`
+    pub decrypt: Option<SecretSource>,
+    /// Key the HMAC of the message must match, from `--verify-hmac`
+    pub verify_hmac: Option<SecretSource>,
+    /// Prefix the result with the file name even for a single file, for
+    /// `--recursive`
+    pub name_files: bool,
+}
+
+/// Decodes the chunk from every file. With several files each result is
+/// prefixed by the file name (JSON reports are printed one per line).
+///
+/// Fails with [`PngError::ChunkNotFound`] if any file lacks the chunk, once
+/// every file has been decoded.
+///
+/// Human output escapes characters that could spoof the terminal, `quiet`,
+/// `raw`, `output` and JSON output are faithful to the payload.
+pub fn decode(
+    files: &[InputSource],
+    chunk_type: &str,
+    options: &DecodeOptions,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let DecodeOptions {
+        quiet,
+        raw,
+        ignore_expiry,
+        unwrap_all,
+        format,
+        encoding,
+        wrap,
+        payload_format,
+        text,
+        ref output,
+        sniff,
+        ref decrypt,
+        ref verify_hmac,
+        name_files,
+    } = *options;
+    let passphrase = decrypt.as_ref().map(|source| source.read(ctx.keychain)).transpose()?;
+    let hmac_key = verify_hmac.as_ref().map(|source| source.read(ctx.keychain)).transpose()?;
+    let passphrase = passphrase.as_deref().map(String::as_str);
+    let limit = ctx.max_decompressed_size;
+    let encoded = |chunk: &Chunk| Ok::<_, PngMeError>(encoding.encode(&payload_bytes(chunk, passphrase, limit)?)?);
+    let cleaned =
+        |chunk: &Chunk| Ok::<_, PngMeError>(text.apply_str(&payload_text(chunk, passphrase, limit)?).into_owned());
+    let mut out = io::stdout().lock();
+    let mut missing = 0;
+
+    for file in files {
+        let png = file_to_png(file, ctx)?;
+        let mut message = message_chunk(&png, chunk_type)?;
+        if message.is_none() {
+            missing += 1;
+        }
+        // Before anything of the message is shown
+        if let (Some(key), Some(chunk)) = (&hmac_key, message.as_deref()) {
+            Envelope::parse(chunk.data()).ok_or(MacError::Missing)?.verify(key.as_bytes())?;
+        }
+        // The rest reads the innermost envelope as if it were the payload
+        if let Some(outer) = message.as_deref().filter(|_| unwrap_all) {
+            let (inner, layers) = envelope::peel(outer.data(), ctx.clock, ignore_expiry, passphrase, limit)?;
+            if layers > 0 {
+                message = Some(Cow::Owned(Chunk::new(*outer.chunk_type(), inner)));
+            }
+        }
+        let chunk = message.as_deref();
+        let opened = chunk
+            .map(|chunk| {
+                envelope::open(chunk.data(), ctx.clock, ignore_expiry, passphrase, limit).map_err(|err| {
+                    let index = png.chunks().iter().position(|found| found.chunk_type() == chunk.chunk_type());
+                    err.in_chunk(chunk.chunk_type(), index.unwrap_or_default())
+                })
+            })
+            .transpose()?;
+        let expired = matches!(opened, Some(Opened::Expired { .. }));
+        // Checked for JSON reports too, they fail on an invalid document
+        let document = match (payload_format, chunk) {
+            (Some(format), Some(chunk)) if !expired => Some(format.pretty(&payload_bytes(chunk, passphrase, limit)?)?),
+            _ => None,
+        };
+        let provenance = chunk
+            .and_then(|chunk| Envelope::parse(chunk.data()))
+            .and_then(|envelope| envelope.provenance);
+        let metadata = chunk.and_then(|chunk| Metadata::find(png.chunks(), chunk.chunk_type()));
+
+        let itxt = chunk.and_then(Chunk::itxt);
+        let ztxt = chunk.and_then(Chunk::ztxt).transpose()?;
+
+        if format == OutputFormat::Json {
+            let data = match chunk.filter(|_| !expired) {
+                Some(chunk) if encoding != Encoding::Text => Some(encoded(chunk)?),
+                chunk => chunk.map(cleaned).transpose()?,
+            };
+            let report = DecodeReport {
+                file: file.to_string(),
+                chunk_type,
+                found: chunk.is_some(),
+                length: chunk.map(Chunk::length),
+                data,
+                keyword: chunk
+                    .and_then(Chunk::text_keyword_and_value)
+                    .map(|(keyword, _)| keyword)
+                    .or_else(|| itxt.as_ref().map(|itxt| itxt.header.keyword.clone()))
+                    .or_else(|| ztxt.as_ref().map(|ztxt| ztxt.keyword.clone())),
+                language: itxt.as_ref().map(|itxt| itxt.header.language.clone()),
+                translated_keyword: itxt.as_ref().map(|itxt| itxt.header.translated_keyword.clone()),
+                expires_at: chunk
+                    .and_then(|chunk| Envelope::parse(chunk.data()))
+                    .and_then(|envelope| envelope.expires_at),
+                expired,
+                provenance,
+                metadata,
+                encoding: (encoding != Encoding::Text).then(|| encoding.to_string()),
+                suggestions: match chunk {
+                    Some(_) => Vec::new(),
+                    None => suggestions(&png, chunk_type),
+                },
+            };
+            writeln!(out, "{}", json_report(&report, ctx)?)?;
+
+            continue;
+        }
+
+        let prefix = if files.len() > 1 || name_files {
+            format!("{file}: ")
+        } else {
+            String::new()
+        };
+
+        // Binary payloads are written without going through text
+        if let (Some(path), Some(chunk), false) = (output, chunk, expired) {
+            let payload = payload_bytes(chunk, passphrase, limit)?;
+            let path = match sniffed_output(path, file, chunk.chunk_type(), &payload).filter(|_| sniff) {
+                Some((sniffed, kind)) => {
+                    writeln!(out, "{prefix}Detected {kind}, writing {}", sniffed.display())?;
+                    Cow::Owned(sniffed)
+                }
+                None => Cow::Borrowed(path),
+            };
+            write_to_sink(sink_for(&path).as_mut(), &payload)?;
+            continue;
+        }
+
+        match (chunk, opened) {
+            (_, Some(Opened::Expired { expires_at })) => {
+                eprintln!("{prefix}Message expired on {}", format_timestamp(expires_at))
+            }
+            (Some(chunk), _) if raw => {
+                out.write_all(chunk.data())?;
+                out.flush()?;
+            }
+            (Some(_), _) if let Some(document) = &document => {
+                writeln!(out, "{prefix}{}", escape_for_terminal(document))?
+            }
+            (Some(chunk), _) if quiet && chunk.is_empty() => writeln!(out, "{prefix}\"\"")?,
+            (Some(chunk), opened) if encoding != Encoding::Text && !chunk.is_empty() => {
+                let expires_at = match opened {
+                    Some(Opened::Message(envelope)) if !quiet => envelope.expires_at,
+                    _ => None,
+                };
+                write!(out, "{prefix}")?;
+                write_encoded(&mut out, encoding, wrap, &payload_bytes(chunk, passphrase, limit)?)?;
+                match expires_at {
+                    Some(expires_at) => writeln!(out, " (expires on {})", format_timestamp(expires_at))?,
+                    None => writeln!(out)?,
+                }
+            }
+            (Some(chunk), _) if quiet => writeln!(out, "{prefix}{}", cleaned(chunk)?)?,
+            (Some(chunk), _) if chunk.is_empty() => writeln!(out, "{prefix}(empty payload, 0 bytes)")?,
+            (Some(_), Some(Opened::Message(envelope))) => {
+                let message = escape_for_terminal(&String::from_utf8_lossy(&text.apply(&envelope.message)));
+                match envelope.expires_at {
+                    Some(expires_at) => {
+                        writeln!(out, "{prefix}{message} (expires on {})", format_timestamp(expires_at))?
+                    }
+                    None => writeln!(out, "{prefix}{message}")?,
+                }
+            }
+            (Some(chunk), _) if let Some(itxt) = &itxt => {
+                let header = &itxt.header;
+                let field = |value: &str| escape_for_terminal(value);
+                writeln!(out, "{prefix}Keyword: {}", field(&header.keyword))?;
+                writeln!(out, "{prefix}Language: {}", field(&header.language))?;
+                writeln!(out, "{prefix}Translated keyword: {}", field(&header.translated_keyword))?;
+                writeln!(out, "{prefix}Compressed: {}", if itxt.compressed { "yes" } else { "no" })?;
+                writeln!(out, "{prefix}Text: {}", field(&cleaned(chunk)?))?;
+            }
+            (Some(chunk), _) if let Some(ztxt) = &ztxt => {
+                writeln!(out, "{prefix}Keyword: {}", escape_for_terminal(&ztxt.keyword))?;
+                writeln!(out, "{prefix}Text: {}", escape_for_terminal(&cleaned(chunk)?))?;
+            }
+            (Some(chunk), _) if text.is_noop() => writeln!(out, "{prefix}{chunk}")?,
+            (Some(chunk), _) => {
+                let cleaned = Chunk::new(*chunk.chunk_type(), text.apply(chunk.data()).into_owned());
+                writeln!(out, "{prefix}{cleaned}")?
+            }
+            // The error returned below tells the chunk is missing
+            (None, _) => print_suggestions(&prefix, &suggestions(&png, chunk_type)),
+        }
+
+        if let Some(provenance) = provenance.filter(|_| !quiet && !raw && !expired) {
+            writeln!(out, "{prefix}({})", escape_for_terminal(&provenance.to_string()))?;
+        }
+        if let Some(metadata) = metadata.filter(|_| !quiet && !raw && !expired) {
+            writeln!(out, "{prefix}({})", escape_for_terminal(&metadata.to_string()))?;
+        }
+    }
+
+    if missing > 0 {
+        return Err(PngError::ChunkNotFound { chunk_type: chunk_type.to_owned() }.into());
+    }
+
+    Ok(())
+}
+
+/// Decodes the chunk from every file and groups the payloads by SHA-256.
+///
+/// Fails if the payloads differ or if any file lacks the chunk.
+pub fn compare_payloads(files: &[InputSource], chunk_type: &str, ctx: &Context) -> Result<(), PngMeError> {
+    // (digest, payload, files) in order of first appearance
+    let mut groups: Vec<(String, Vec<u8>, Vec<&InputSource>)> = Vec::new();
+    let mut missing: Vec<&InputSource> = Vec::new();
+
+    for file in files {
+        let png = file_to_png(file, ctx)?;
+
+        let Some(chunk) = message_chunk(&png, chunk_type)? else {
+            missing.push(file);
+            continue;
+        };
+
+        let digest = sha256_hex(chunk.data());
+        match groups.iter_mut().find(|(hash, _, _)| *hash == digest) {
+            Some((_, _, group)) => group.push(file),
+            None => groups.push((digest, chunk.data().to_vec(), vec![file])),
+        }
+    }
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "{} distinct payload(s) for chunk {chunk_type}:", groups.len())?;
+    for (digest, payload, group) in &groups {
+        let names: Vec<String> = group.iter().map(|file| file.to_string()).collect();
+        writeln!(out, "  sha256 {digest} ({} file(s)): {}", group.len(), names.join(", "))?;
+        writeln!(out, "    {}", escape_for_terminal(&String::from_utf8_lossy(payload)))?;
+    }
+
+    if !missing.is_empty() {
+        let names: Vec<String> = missing.iter().map(|file| file.to_string()).collect();
+        writeln!(out, "Missing chunk {chunk_type}: {}", names.join(", "))?;
+    }
+
+    if groups.len() > 1 || !missing.is_empty() {
+        return Err(PngMeError::PayloadMismatch {
+            distinct: groups.len(),
+            missing: missing.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Which chunk `remove` should target
+pub enum ChunkSelector<'a> {
+    /// The first chunk of the given type
+    Type(&'a str),
+    /// The chunk at the given absolute index
+    Index(usize),
+}
+
+/// Removes the selected chunk. With `dry_run` the chunks left are printed
+/// and nothing is written, the chunk must exist all the same.
+pub fn remove(
+    file: &InputSource,
+    selector: ChunkSelector,
+    output: &Option<PathBuf>,
+    dry_run: bool,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let output_file = &output_path(file, output, ctx)?;
+    ensure_writable(output_file)?;
+    let _lock = match dry_run {
+        true => None,
+        false => lock_in_place(file, output_file, ctx)?,
+    };
+    if !dry_run {
+        backup_in_place(file, output_file, ctx)?;
+    }
+
+    let mut png = file_to_png(file, ctx)?;
+    let size_before = image_size(&png);
+    let original = ctx.emit_patch.is_some().then(|| png.chunks().to_vec());
 
-    Ok(Png::try_from(bytes.as_slice())?)
+    match selector {
+        ChunkSelector::Type(chunk_type) => png.remove_first_chunk(chunk_type)?,
+        ChunkSelector::Index(index) => png.remove_chunk_at(index)?,
+    };
+    renumber_animation(&mut png);
+
+    check_unknown_critical(&png, ctx.allow_unknown_critical)?;
+    if dry_run {
+        return Ok(print_dry_run(&png, size_before, output_file)?);
+    }
+    save_undo_state(file, output_file, "remove", ctx)?;
+    write_png(&png, output_file, ctx)?;
+    emit_patch(original, &png, ctx)
+}
+
+/// Removes the ancillary chunks, or with `expired_only` only those holding
+/// an expired message. Chunks affecting rendering are kept unless
+/// `strip_color` is set, either way they are listed.
+pub fn strip(
+    file: &InputSource,
+    expired_only: bool,
+    strip_color: bool,
+    selection: Option<&Selection>,
+    output: &Option<PathBuf>,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let output_file = &output_path(file, output, ctx)?;
+    ensure_writable(output_file)?;
+    let _lock = lock_in_place(file, output_file, ctx)?;
+    backup_in_place(file, output_file, ctx)?;
+
+    let mut png = file_to_png(file, ctx)?;
+    let original = ctx.emit_patch.is_some().then(|| png.chunks().to_vec());
+    let now = ctx.clock.now();
+    let mut kept = Vec::new();
+    let mut candidates = 0;
+
+    let removed = png.remove_chunks_where(|chunk| {
+        let chunk_type = chunk.chunk_type();
+        if chunk_type.is_critical() || (expired_only && scan::expired_at(chunk, now).is_none()) {
+            return false;
+        }
+        candidates += 1;
+
+        // A selection decides of the rendering chunks too
+        match selection {
+            Some(selection) => selection.matches(chunk_type),
+            None if chunk_type.affects_rendering() && !strip_color => {
+                kept.push(chunk_type.to_string());
+                false
+            }
+            None => true,
+        }
+    });
+    if let Some(warning) =
+        selection.and_then(|selection| selection.empty_warning(candidates, removed.len()))
+    {
+        eprintln!("{warning}");
+    }
+    let dropped: Vec<String> = removed
+        .iter()
+        .map(Chunk::chunk_type)
+        .filter(|chunk_type| chunk_type.affects_rendering())
+        .map(ChunkType::to_string)
+        .collect();
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "Removed {} chunk(s)", removed.len())?;
+    if !kept.is_empty() {
+        writeln!(
+            out,
+            "Kept rendering chunks: {} (pass --strip-color to remove them)",
+            kept.join(", ")
+        )?;
+    }
+    if !dropped.is_empty() {
+        writeln!(out, "Removed rendering chunks: {}", dropped.join(", "))?;
+    }
+
+    save_undo_state(file, output_file, "strip", ctx)?;
+    write_png(&png, output_file, ctx)?;
+    emit_patch(original, &png, ctx)
+}
+
+pub fn print(file: &InputSource, collapse: bool, ctx: &Context) -> Result<(), PngMeError> {
+    let png = file_to_png(file, ctx)?;
+
+    Ok(print_chunks(
+        &png,
+        collapse,
+        ctx.clock.now(),
+        ctx.interpreters,
+        &mut io::stdout().lock(),
+    )?)
+}
+
+/// `print` on every file of a `--recursive` walk, each listing under the
+/// path of the file. A file that can't be read is skipped with a warning
+pub fn print_many(files: &[PathBuf], collapse: bool, ctx: &Context) -> Result<(), PngMeError> {
+    let mut out = io::stdout().lock();
+    let mut printed = false;
+
+    for path in files {
+        let png = match file_to_png(&InputSource::Path(path.clone()), ctx) {
+            Ok(png) => png,
+            Err(err) => {
+                eprintln!("warning[{}]: skipping {}: {err}", err.code(), path.display());
+                continue;
+            }
+        };
+        if printed {
+            writeln!(out)?;
+        }
+        writeln!(out, "{}:", path.display())?;
+        print_chunks(&png, collapse, ctx.clock.now(), ctx.interpreters, &mut out)?;
+        printed = true;
+    }
+
+    Ok(())
+}
+
+/// The files of a `--recursive` walk, in path order. Entries that can't be
+/// read and files without the PNG signature are skipped with a warning
+/// rather than stopping the walk
+pub fn walked_files(walk: PngFiles) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    for path in walk {
+        let path = match path {
+            Ok(path) => path,
+            Err(err) => {
+                eprintln!("warning[{}]: skipping a directory entry: {err}", Code::IoFailed);
+                continue;
+            }
+        };
+
+        let mut signature = [0; SIGNATURE_LEN];
+        match fs::File::open(&path).and_then(|mut file| file.read_exact(&mut signature)) {
+            Ok(()) if signature == Png::STANDARD_HEADER => files.push(path),
+            Err(err) if err.kind() != ErrorKind::UnexpectedEof => {
+                eprintln!("warning[{}]: skipping {}: {err}", Code::IoFailed, path.display())
+            }
+            _ => eprintln!("warning[{}]: skipping {}: not a PNG file", Code::BadSignature, path.display()),
+        }
+    }
+
+    files
+}
+
+/// Writes the chunk listing of `print` to `out`, stopping at the first
+/// write error
+pub fn print_chunks(
+    png: &Png,
+    collapse: bool,
+    now: u64,
+    interpreters: &Registry,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    if !collapse {
+        writeln!(out, "Png {{ header: {:?} }}", png.header())?;
+
+        for (index, chunk) in png.chunks().iter().enumerate() {
+            let notes: Vec<String> = [
+                interpreters
+                    .describe(chunk.chunk_type(), chunk.data())
+                    .map(|description| escape_for_terminal(&description)),
+                scan::expired_at(chunk, now)
+                    .map(|expires_at| format!("message expired on {}", format_timestamp(expires_at))),
+                envelope::describe_provenance(chunk.data()).map(|provenance| escape_for_terminal(&provenance)),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+
+            match notes.as_slice() {
+                [] => writeln!(out, "#{index} {chunk}")?,
+                notes => writeln!(out, "#{index} {chunk} ({})", notes.join(", "))?,
+            }
+        }
+
+        return Ok(());
+    }
+
+    writeln!(out, "Png {{ header: {:?} }}", png.header())?;
+
+    // Collapsed runs keep the absolute indices of the chunks they cover so
+    // that `remove --at` keeps addressing the same chunk.
+    for run in png.chunk_runs() {
+        let chunks = &png.chunks()[run.clone()];
+
+        if let [chunk] = chunks {
+            writeln!(out, "#{} {chunk}", run.start)?;
+        } else {
+            let length: u64 = chunks.iter().map(|chunk| chunk.length() as u64).sum();
+            writeln!(
+                out,
+                "#{}-{} {{ type: {}, chunks: {}, total length: {} }}",
+                run.start,
+                run.end - 1,
+                chunks[0].chunk_type(),
+                chunks.len(),
+                length
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the survivability report of the first `chunk_type` chunk, checked
+/// against `profile` when given, and, with `apply_suggestions`, writes the
+/// image with the automatic suggestions done.
+pub fn survivability(
+    file: &InputSource,
+    chunk_type: &str,
+    apply_suggestions: bool,
+    profile: Option<&Profile>,
+    output: &Option<PathBuf>,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let output_file = &output_path(file, output, ctx)?;
+    let _lock = if apply_suggestions {
+        ensure_writable(output_file)?;
+        let lock = lock_in_place(file, output_file, ctx)?;
+        backup_in_place(file, output_file, ctx)?;
+        lock
+    } else {
+        None
+    };
+
+    let mut png = file_to_png(file, ctx)?;
+    let report = survivability::assess(&png, chunk_type)?;
+    let mut out = io::stdout().lock();
+    writeln!(out, "{report}")?;
+    if let Some(profile) = profile {
+        writeln!(out, "Profile: {} ({})", profile.name, profile.description)?;
+        for (constraint, met) in profile.check(&png, report.index) {
+            writeln!(out, "  {constraint}: {}", if met { "yes" } else { "no" })?;
+        }
+    }
+
+    if !apply_suggestions {
+        return Ok(());
+    }
+
+    let (automatic, manual): (Vec<&Suggestion>, Vec<_>) =
+        report.suggestions.iter().partition(|suggestion| suggestion.is_automatic());
+
+    if automatic.is_empty() {
+        writeln!(out, "Nothing to apply")?;
+    } else {
+        survivability::apply(&mut png, &report)?;
+        check_unknown_critical(&png, ctx.allow_unknown_critical)?;
+        save_undo_state(file, output_file, "survivability", ctx)?;
+        write_png(&png, output_file, ctx)?;
+
+        let applied: Vec<String> = automatic.iter().map(ToString::to_string).collect();
+        writeln!(out, "Applied: {}", applied.join(", "))?;
+    }
+
+    for suggestion in manual {
+        writeln!(out, "Not applied: {suggestion}")?;
+    }
+
+    Ok(())
+}
+
+/// JSON report of `verify`, one per warning
+#[derive(Serialize)]
+struct WarningReport {
+    code: Code,
+    name: &'static str,
+    severity: Severity,
+    range: Range<u64>,
+    message: String,
+}
+
+impl From<&ParseWarning> for WarningReport {
+    fn from(warning: &ParseWarning) -> Self {
+        Self {
+            code: warning.code(),
+            name: warning.name(),
+            severity: warning.severity(),
+            range: warning.range(),
+            message: warning.to_string(),
+        }
+    }
+}
+
+/// Parses leniently and prints every warning with the bytes it covers,
+/// including animation chunks out of sequence. With `deep`, the size of the
+/// image data is checked too. Fails if there
+/// is any warning.
+pub fn verify(file: &InputSource, deep: bool, format: OutputFormat, ctx: &Context) -> Result<(), PngMeError> {
+    let input = file.resolve(&ctx.input_options, ctx.observer)?;
+    let options = ParseOptions {
+        lenient: true,
+        ..ctx.parse_options
+    };
+    // Warnings are printed below, don't report them twice
+    let png = Png::parse(input.bytes.as_slice(), &options, &NoopObserver)?;
+    let mut warnings = png.warnings().to_vec();
+
+    // The chunks the lenient parse got past are already reported
+    let chunks: Vec<_> = chunk_refs(&input.bytes, false, options.max_chunks)?.collect::<Result<_, _>>()?;
+    warnings.extend(apng::check(&chunks));
+
+    if deep {
+        match image_data::check(&chunks) {
+            ImageDataCheck::Consistent => {}
+            ImageDataCheck::Skipped(reason) => eprintln!("Note: the image data was not checked, {reason}"),
+            ImageDataCheck::Mismatch(warning) => warnings.push(warning),
+        }
+    }
+
+    let mut out = io::stdout().lock();
+    for warning in &warnings {
+        match format {
+            OutputFormat::Json => writeln!(out, "{}", serde_json::to_string(&WarningReport::from(warning))?)?,
+            OutputFormat::Human => {
+                let range = warning.range();
+                writeln!(
+                    out,
+                    "[{}] {}..{} {} {}: {warning}",
+                    warning.severity(),
+                    range.start,
+                    range.end,
+                    warning.code(),
+                    warning.name()
+                )?;
+            }
+        }
+    }
+
+    match warnings.len() {
+        0 if format == OutputFormat::Human => {
+            writeln!(out, "No problems found")?;
+            Ok(())
+        }
+        0 => Ok(()),
+        count => Err(PngMeError::VerifyFailed { count }),
+    }
 }
 
-pub fn encode(file: &PathBuf, chunk_type: &str, message: &str, output: &Option<PathBuf>) -> Result<(), PngMeError> {
-    let mut png = file_to_png(file)?;
+/// Type and data length of each chunk of `path`
+fn chunk_lengths(path: &Path, ctx: &Context) -> Result<Vec<(ChunkType, u64)>, PngMeError> {
+    let input = InputSource::Path(path.to_path_buf()).resolve(&ctx.input_options, &NoopObserver)?;
+    let chunks = chunk_refs(&input.bytes, false, ctx.parse_options.max_chunks)?
+        .map(|chunk| chunk.map(|chunk| (chunk.chunk_type, chunk.data.len() as u64)))
+        .collect::<Result<_, _>>()?;
+
+    Ok(chunks)
+}
+
+/// Chunk-type statistics printed by [`types`]
+#[derive(Serialize)]
+struct TypesReport {
+    files: u64,
+    skipped: u64,
+    types: Vec<TypeReport>,
+}
+
+/// Counts the chunk types of the PNG files under `paths`, most frequent
+/// first, listing only the `top` ones if given. Files are read one at a
+/// time, those that can't be read or parsed are skipped with a warning.
+pub fn types(
+    paths: &[PathBuf],
+    recursive: bool,
+    top: Option<usize>,
+    format: OutputFormat,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let mut stats = CorpusStats::default();
+
+    for path in png_files(paths, recursive) {
+        let path = match path {
+            Ok(path) => path,
+            Err(err) => {
+                eprintln!("warning[{}]: skipping a directory entry: {err}", Code::IoFailed);
+                stats.skip_file();
+                continue;
+            }
+        };
+
+        match chunk_lengths(&path, ctx) {
+            Ok(chunks) => stats.add_file(chunks),
+            Err(err) => {
+                eprintln!("warning[{}]: skipping {}: {err}", err.code(), path.display());
+                stats.skip_file();
+            }
+        }
+    }
+
+    let types = stats.sorted();
+    let types = &types[..top.unwrap_or(types.len()).min(types.len())];
+
+    let mut out = io::stdout().lock();
+    match format {
+        OutputFormat::Json => {
+            let report = TypesReport {
+                files: stats.files,
+                skipped: stats.skipped,
+                types: types.iter().map(|&stats| TypeReport::from(stats)).collect(),
+            };
+            writeln!(out, "{}", json_report(&report, ctx)?)?;
+        }
+        OutputFormat::Human => {
+            writeln!(
+                out,
+                "{:<4}  {:>10}  {:>8}  {:>14}  {:>10}  {:>10}  {:>10}",
+                "Type", "Chunks", "Files", "Total bytes", "Min", "Median", "Max"
+            )?;
+            for stats in types {
+                writeln!(
+                    out,
+                    "{:<4}  {:>10}  {:>8}  {:>14}  {:>10}  {:>10}  {:>10}",
+                    stats.chunk_type.to_string(),
+                    stats.chunks,
+                    stats.files,
+                    stats.total_bytes,
+                    stats.min,
+                    stats.median(),
+                    stats.max
+                )?;
+            }
+            writeln!(out, "{} files, {} skipped", stats.files, stats.skipped)?;
+        }
+    }
+
+    Ok(())
+}
 
-    let chunk_type = ChunkType::from_str(chunk_type)?;
-    let chunk = Chunk::new(chunk_type, message.as_bytes().to_vec());
+/// Rewrites `file` without the problems found by a lenient parse, dropping
+/// unknown critical chunks. With
+/// `bootstrap`, a signature-only file becomes a minimal valid image. With
+/// `deinterlace`, an interlaced image is re-encoded without interlacing.
+pub fn fix(
+    file: &InputSource,
+    bootstrap: bool,
+    deinterlace_image: bool,
+    fix_reserved: bool,
+    output: &Option<PathBuf>,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let output_file = &output_path(file, output, ctx)?;
+    ensure_writable(output_file)?;
+    let _lock = lock_in_place(file, output_file, ctx)?;
+    backup_in_place(file, output_file, ctx)?;
 
-    png.append_chunk(chunk);
+    let input = file.resolve(&ctx.input_options, ctx.observer)?;
 
-    let output_file = if let Some(output) = output {
-        output
+    let mut out = io::stdout().lock();
+    let png = if bootstrap && input.bytes == Png::STANDARD_HEADER {
+        writeln!(out, "Bootstrapped a minimal 1x1 image")?;
+        Png::new_minimal()
     } else {
-        file
+        let options = ParseOptions {
+            lenient: true,
+            ..ctx.parse_options
+        };
+        let png = Png::parse(input.bytes.as_slice(), &options, ctx.observer)?;
+
+        // Chunks get serialized with their computed CRC
+        let iend = png.chunks().iter().position(|chunk| chunk.chunk_type().bytes() == *b"IEND");
+        let mut chunks = png.chunks().to_vec();
+        match iend {
+            Some(index) => chunks.truncate(index + 1),
+            None => chunks.push(Chunk::new(ChunkType::from_str("IEND")?, Vec::new())),
+        }
+        chunks.retain(|chunk| !chunk.chunk_type().is_unknown_critical());
+
+        let reserved = png
+            .warnings()
+            .iter()
+            .filter(|warning| matches!(warning, ParseWarning::ReservedBit { .. }))
+            .count();
+        let renamed = match fix_reserved {
+            true => fix_reserved_bits(&mut chunks),
+            false => 0,
+        };
+
+        let mut fixed = Png::from_chunks(chunks);
+        let resequenced = usize::from(apng::renumber(&mut fixed) > 0);
+
+        writeln!(out, "Fixed {} problem(s)", png.warnings().len() - reserved + renamed + resequenced)?;
+        fixed
+    };
+
+    let png = match deinterlace_image {
+        true if is_interlaced(&png) => {
+            writeln!(out, "Removed the interlacing of the image")?;
+            deinterlace(&png)?
+        }
+        true => {
+            writeln!(out, "The image is not interlaced")?;
+            png
+        }
+        false => png,
+    };
+
+    save_undo_state(file, output_file, "fix", ctx)?;
+    write_png(&png, output_file, ctx)
+}
+
+/// Removes the downloads cached in `dir`, or in the default directory
+pub fn clear_cache(dir: Option<&Path>) -> Result<(), PngMeError> {
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => DownloadCache::default_dir()?,
     };
 
-    let mut file = File::create(output_file)?;
-    file.write_all(&png.as_bytes())?;
+    let removed = DownloadCache::clear(&dir)?;
+    let mut out = io::stdout().lock();
+    writeln!(out, "Removed {removed} cached download(s) from {}", dir.display())?;
+    Ok(())
+}
+
+/// Rewrites `file` in the canonical form of [`canonical::canonicalize`]
+pub fn canonicalize(file: &InputSource, output: &Option<PathBuf>, ctx: &Context) -> Result<(), PngMeError> {
+    let output_file = &output_path(file, output, ctx)?;
+    ensure_writable(output_file)?;
+    let _lock = lock_in_place(file, output_file, ctx)?;
+    backup_in_place(file, output_file, ctx)?;
+
+    let png = file_to_png(file, ctx)?;
+    let canonical = canonical::canonicalize(&png)?;
+    let idat = png.chunks_by_type("IDAT").count();
+    let mut out = io::stdout().lock();
+    if idat > 1 {
+        writeln!(out, "Merged {idat} IDAT chunks")?;
+    }
+    writeln!(out, "Wrote {} chunk(s) in canonical order", canonical.chunks().len())?;
+
+    save_undo_state(file, output_file, "canonicalize", ctx)?;
+    write_png(&canonical, output_file, ctx)
+}
+
+/// Clears the reserved bit of the chunk types, leaving a chunk alone when
+/// the corrected type is already in the file. Returns the number of chunks
+/// renamed.
+fn fix_reserved_bits(chunks: &mut [Chunk]) -> usize {
+    let mut renamed = 0;
+
+    for index in 0..chunks.len() {
+        let Some(corrected) = chunks[index].chunk_type().suggested_casing() else {
+            continue;
+        };
+
+        if chunks.iter().any(|chunk| *chunk.chunk_type() == corrected) {
+            eprintln!(
+                "warning[{}]: not renaming {} to {corrected}, the file already has a {corrected} chunk",
+                Code::RenameConflict,
+                chunks[index].chunk_type()
+            );
+            continue;
+        }
+
+        // The new type changes the CRC, which is computed again
+        chunks[index] = Chunk::new(corrected, chunks[index].data().to_vec());
+        renamed += 1;
+    }
+
+    renamed
+}
+
+/// Prints the size of `file` and what embedding a `payload_size`-byte
+/// payload takes, failing when the result would exceed `max_size`
+pub fn capacity(
+    file: &InputSource,
+    payload_size: Option<u64>,
+    max_size: Option<u64>,
+    format: OutputFormat,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let input = file.resolve(&ctx.input_options, ctx.observer)?;
+    let png = Png::parse(input.bytes.as_slice(), &ctx.parse_options, ctx.observer)?;
+    let report = Capacity::new(input.bytes.len() as u64, png.chunks().len(), payload_size, max_size);
+
+    let mut out = io::stdout().lock();
+    match format {
+        OutputFormat::Json => writeln!(out, "{}", json_report(&report, ctx)?)?,
+        OutputFormat::Human => writeln!(out, "{report}")?,
+    }
+
+    match max_size {
+        Some(max_size) if report.exceeds_max_size => Err(PngMeError::CapacityExceeded {
+            projected_size: report.projected_size,
+            max_size,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Prints the scan findings, most severe first
+pub fn scan(file: &InputSource, options: &ScanOptions, ctx: &Context) -> Result<(), PngMeError> {
+    let input = file.resolve(&ctx.input_options, ctx.observer)?;
+    let chunks = chunk_refs(&input.bytes, true, ctx.parse_options.max_chunks)?.collect::<Result<Vec<_>, _>>()?;
+    let findings = scan::scan_chunks(&chunks, options);
+    let mut out = io::stdout().lock();
+
+    if findings.is_empty() {
+        writeln!(out, "No findings")?;
+    }
+
+    for finding in findings {
+        writeln!(out, "{finding}")?;
+        let chunk = &chunks[finding.index];
+        if let Some(description) = ctx.interpreters.describe(&chunk.chunk_type, chunk.data) {
+            writeln!(out, "    {}", escape_for_terminal(&description))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the chunks written by pngme, i.e. holding an [`Envelope`], with
+/// the provenance they record
+pub fn provenance(file: &InputSource, ctx: &Context) -> Result<(), PngMeError> {
+    let input = file.resolve(&ctx.input_options, ctx.observer)?;
+    let mut out = io::stdout().lock();
+    let mut found = false;
+
+    for (index, chunk) in chunk_refs(&input.bytes, true, ctx.parse_options.max_chunks)?.enumerate() {
+        let chunk = chunk?;
+        let Some(envelope) = Envelope::parse(chunk.data) else {
+            continue;
+        };
+        found = true;
+
+        match envelope.provenance {
+            Some(provenance) => writeln!(
+                out,
+                "#{index} {}: {}",
+                chunk.chunk_type,
+                escape_for_terminal(&provenance.to_string())
+            )?,
+            None => writeln!(out, "#{index} {}: no provenance recorded", chunk.chunk_type)?,
+        }
+    }
+
+    if !found {
+        writeln!(out, "No chunks written by pngme")?;
+    }
+
+    Ok(())
+}
+
+pub fn info(file: &InputSource, ctx: &Context) -> Result<(), PngMeError> {
+    let input = file.resolve(&ctx.input_options, ctx.observer)?;
+    let chunks = chunk_refs(&input.bytes, true, ctx.parse_options.max_chunks)?.collect::<Result<Vec<_>, _>>()?;
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "File: {file}")?;
+    writeln!(out, "Size: {} bytes", input.bytes.len())?;
+    writeln!(out, "Chunks: {}", chunks.len())?;
+
+    let ihdr = chunks.iter().find(|chunk| chunk.chunk_type.bytes() == *b"IHDR").map(|chunk| chunk.data);
+    match ihdr.map(|data| (ColorFormat::from_ihdr(data), data.get(BIT_DEPTH..BIT_DEPTH + 2))) {
+        Some((Some(format), _)) => writeln!(out, "Format: {format}")?,
+        Some((None, Some(&[bit_depth, color_type]))) => {
+            writeln!(out, "Format: invalid (color type {color_type} with bit depth {bit_depth})")?
+        }
+        Some((None, _)) => writeln!(out, "Format: unknown (malformed IHDR)")?,
+        None => writeln!(out, "Format: unknown (no IHDR)")?,
+    }
+
+    match ihdr.and_then(|data| data.get(INTERLACE_METHOD)) {
+        Some(0) => writeln!(out, "Interlace: none")?,
+        Some(1) => writeln!(out, "Interlace: Adam7")?,
+        Some(method) => writeln!(out, "Interlace: unknown method {method}")?,
+        None => writeln!(out, "Interlace: unknown (no IHDR)")?,
+    }
+
+    match chunks.iter().find(|chunk| chunk.chunk_type.bytes() == *b"iCCP") {
+        Some(chunk) => {
+            let index = chunks.iter().position(|found| found.offset == chunk.offset).unwrap_or_default();
+            let profile = IccProfile::from_chunk(&chunk.to_owned(), ctx.max_decompressed_size)
+                .map_err(|err| err.in_chunk(index))?;
+            writeln!(
+                out,
+                "ICC profile: {} ({} bytes decompressed)",
+                profile.name(),
+                profile.profile().len()
+            )?;
+        }
+        None => writeln!(out, "ICC profile: none")?,
+    }
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        if let Some(description) = ctx.interpreters.describe(&chunk.chunk_type, chunk.data) {
+            writeln!(out, "#{index} {}: {}", chunk.chunk_type, escape_for_terminal(&description))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the decompressed ICC profile of `file` to `output`
+pub fn extract_icc(file: &InputSource, output: &Path, ctx: &Context) -> Result<(), PngMeError> {
+    let png = file_to_png(file, ctx)?;
+
+    let index = png
+        .chunks()
+        .iter()
+        .position(|chunk| chunk.chunk_type().bytes() == ICCP_CHUNK_TYPE)
+        .ok_or_else(|| PngError::ChunkNotFound {
+            chunk_type: "iCCP".to_string(),
+        })?;
+    let profile =
+        IccProfile::from_chunk(&png.chunks()[index], ctx.max_decompressed_size).map_err(|err| err.in_chunk(index))?;
+
+    fs::write(output, profile.profile())?;
+
+    Ok(())
+}
+
+/// Writes the data of the chunk at `index` to `output`, `-` for stdout.
+/// `index` is the absolute index `print` shows and `remove --at` takes.
+pub fn extract_chunk(file: &InputSource, index: usize, output: &Path, ctx: &Context) -> Result<(), PngMeError> {
+    let png = file_to_png(file, ctx)?;
+    let chunk = png.chunks().get(index).ok_or(PngError::IndexOutOfBounds {
+        index,
+        len: png.chunks().len(),
+    })?;
+
+    write_to_sink(sink_for(output).as_mut(), chunk.data())?;
 
     Ok(())
 }
 
-pub fn decode(file: &PathBuf, chunk_type: &str) -> Result<(), PngMeError> {
-    let png = file_to_png(file)?;
+/// Embeds the ICC profile read from `profile` as an iCCP chunk placed before
+/// PLTE and IDAT as the spec requires.
+///
+/// An existing iCCP or sRGB chunk is only dropped when `replace` is set.
+pub fn inject_icc(
+    file: &InputSource,
+    profile: &Path,
+    name: &str,
+    replace: bool,
+    output: &Option<PathBuf>,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let output_file = &output_path(file, output, ctx)?;
+    ensure_writable(output_file)?;
+    let _lock = lock_in_place(file, output_file, ctx)?;
+    backup_in_place(file, output_file, ctx)?;
+
+    let chunk = IccProfile::new(name, fs::read(profile)?)?.to_chunk()?;
+    let mut png = file_to_png(file, ctx)?;
+    let original = ctx.emit_patch.is_some().then(|| png.chunks().to_vec());
+
+    for chunk_type in ["iCCP", "sRGB"] {
+        if png.chunk_by_type(chunk_type).is_none() {
+            continue;
+        }
+
+        if !replace {
+            return Err(PngMeError::ColorProfileConflict {
+                chunk_type: chunk_type.to_string(),
+            });
+        }
+
+        png.remove_chunks_by_type(chunk_type);
+    }
+
+    let position = png
+        .chunks()
+        .iter()
+        .position(|chunk| matches!(&chunk.chunk_type().bytes(), b"PLTE" | b"IDAT" | b"IEND"))
+        .unwrap_or(png.chunks().len());
+    png.insert_chunk(position, chunk)?;
+
+    save_undo_state(file, output_file, "inject-icc", ctx)?;
+    write_png(&png, output_file, ctx)?;
+    emit_patch(original, &png, ctx)
+}
+
+/// Adds `chunks` before IEND, in order, or with `replace` puts each in
+/// place of the first chunk of its type when there is one
+pub fn inject_chunks(
+    file: &InputSource,
+    chunks: &[Chunk],
+    replace: bool,
+    output: &Option<PathBuf>,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let output_file = &output_path(file, output, ctx)?;
+    ensure_writable(output_file)?;
+    let _lock = lock_in_place(file, output_file, ctx)?;
+    backup_in_place(file, output_file, ctx)?;
+
+    let mut png = file_to_png(file, ctx)?;
+    let original = ctx.emit_patch.is_some().then(|| png.chunks().to_vec());
+
+    for chunk in chunks {
+        let chunk_type = chunk.chunk_type().to_string();
+        if replace && png.chunk_by_type(&chunk_type).is_some() {
+            png.replace_chunk(&chunk_type, chunk.clone())?;
+        } else {
+            png.insert_chunk(png.index_of(Position::BeforeIend)?, chunk.clone())?;
+        }
+    }
+    let mut out = io::stdout().lock();
+    writeln!(out, "Injected {} chunk(s)", chunks.len())?;
+
+    check_unknown_critical(&png, ctx.allow_unknown_critical)?;
+    save_undo_state(file, output_file, "inject", ctx)?;
+    write_png(&png, output_file, ctx)?;
+    emit_patch(original, &png, ctx)
+}
+
+/// Writes the ancillary chunks of `file` to `sidecar` as JSON
+pub fn export_meta(
+    file: &InputSource,
+    sidecar: &Path,
+    selection: Option<&Selection>,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let png = file_to_png(file, ctx)?;
+    let mut exported = meta::export(&png);
+    select_sidecar_chunks(&mut exported, selection);
+    let json = serde_json::to_string_pretty(&exported)? + "\n";
 
-    if let Some(chunk) = png.chunk_by_type(chunk_type) {
-        println!("{chunk}")
+    let mut out = io::stdout().lock();
+    if sidecar == Path::new("-") {
+        write!(out, "{json}")?;
     } else {
-        eprintln!("Chunk type: {chunk_type} not found");
+        fs::write(sidecar, json)?;
+    }
+    eprintln!("Exported {} chunk(s)", exported.chunks.len());
+
+    Ok(())
+}
+
+/// Keeps the chunks of `sidecar` that `selection` matches, if any, warning
+/// when it matches none
+fn select_sidecar_chunks(sidecar: &mut Sidecar, selection: Option<&Selection>) {
+    let Some(selection) = selection else {
+        return;
+    };
+    let candidates = sidecar.chunks.len();
+    sidecar.chunks.retain(|chunk| selection.matches(&chunk.chunk_type));
+
+    if let Some(warning) = selection.empty_warning(candidates, sidecar.chunks.len()) {
+        eprintln!("{warning}");
+    }
+}
+
+/// Recreates the chunks saved in `sidecar` into `file`
+pub fn import_meta(
+    files: &[InputSource],
+    sidecar: &Path,
+    on_conflict: OnConflict,
+    selection: Option<&Selection>,
+    output: &Option<PathBuf>,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let mut sidecar: Sidecar = fs::read_to_string(sidecar)?.parse()?;
+    select_sidecar_chunks(&mut sidecar, selection);
+    if ctx.transactional {
+        return import_meta_transaction(files, &sidecar, on_conflict, output, ctx);
+    }
+
+    let mut out = io::stdout().lock();
+    for file in files {
+        let prefix = match files.len() {
+            1 => String::new(),
+            _ => format!("{file}: "),
+        };
+        let output_file = &output_path(file, output, ctx)?;
+        ensure_writable(output_file)?;
+        let _lock = lock_in_place(file, output_file, ctx)?;
+        backup_in_place(file, output_file, ctx)?;
+
+        let mut png = file_to_png(file, ctx)?;
+        let original = ctx.emit_patch.is_some().then(|| png.chunks().to_vec());
+        let imported = meta::import(&mut png, &sidecar, on_conflict)?;
+        writeln!(out, "{prefix}Imported {imported} chunk(s)")?;
+
+        save_undo_state(file, output_file, "import-meta", ctx)?;
+        write_png(&png, output_file, ctx)?;
+        emit_patch(original, &png, ctx)?;
+    }
+
+    Ok(())
+}
+
+/// [`import_meta`] staging every file in the [`Transaction`] of `ctx`,
+/// committed once all of them are ready. The files are locked until then,
+/// backups and undo states are only saved for a transaction about to
+/// commit. The standard output isn't staged, [`write_png`] writes it
+/// directly.
+fn import_meta_transaction(
+    files: &[InputSource],
+    sidecar: &Sidecar,
+    on_conflict: OnConflict,
+    output: &Option<PathBuf>,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    ctx.transaction.replace(Some(Transaction::new()));
+    let mut staged = Vec::with_capacity(files.len());
+
+    for (stage, file) in (1..).zip(files) {
+        let stage_file = || {
+            let output_file = output_path(file, output, ctx)?;
+            ensure_writable(&output_file)?;
+            let lock = lock_in_place(file, &output_file, ctx)?;
+
+            let mut png = file_to_png(file, ctx)?;
+            let original = ctx.emit_patch.is_some().then(|| png.chunks().to_vec());
+            let imported = meta::import(&mut png, sidecar, on_conflict)?;
+            write_png(&png, &output_file, ctx)?;
+            if let (Some(lock), Some(transaction)) = (lock, ctx.transaction.borrow_mut().as_mut()) {
+                transaction.hold(lock);
+            }
+
+            Ok::<_, PngMeError>((file, output_file, imported, original, png))
+        };
+        match stage_file() {
+            Ok(file) => staged.push(file),
+            Err(err) => {
+                ctx.transaction.take();
+                eprintln!(
+                    "Transaction aborted at stage {stage} of {} ({file}), no file was changed",
+                    files.len()
+                );
+                return Err(err);
+            }
+        }
+    }
+
+    let transaction = ctx.transaction.take().expect("the transaction is set above");
+    for (file, output_file, ..) in &staged {
+        backup_in_place(file, output_file, ctx)?;
+        save_undo_state(file, output_file, "import-meta", ctx)?;
+    }
+    transaction.commit().map_err(|(path, err)| {
+        eprintln!("Transaction failed while replacing {}, the files before it were changed", path.display());
+        err
+    })?;
+
+    let mut out = io::stdout().lock();
+    for (file, _, imported, original, png) in staged {
+        match files.len() {
+            1 => writeln!(out, "Imported {imported} chunk(s)")?,
+            _ => writeln!(out, "{file}: Imported {imported} chunk(s)")?,
+        }
+        emit_patch(original, &png, ctx)?;
+    }
+
+    Ok(())
+}
+
+/// Applies the patch saved in `patch` to `file`, or with `reverse` undoes
+/// it
+pub fn apply_patch(
+    file: &InputSource,
+    patch: &Path,
+    reverse: bool,
+    output: &Option<PathBuf>,
+    ctx: &Context,
+) -> Result<(), PngMeError> {
+    let output_file = &output_path(file, output, ctx)?;
+    ensure_writable(output_file)?;
+    let _lock = lock_in_place(file, output_file, ctx)?;
+    backup_in_place(file, output_file, ctx)?;
+
+    let patch: Patch = fs::read_to_string(patch)?.parse()?;
+    let mut png = file_to_png(file, ctx)?;
+    patch::apply(&mut png, &patch, reverse)?;
+    let mut out = io::stdout().lock();
+    match reverse {
+        false => writeln!(out, "Applied {} operation(s)", patch.operations.len())?,
+        true => writeln!(out, "Reversed {} operation(s)", patch.operations.len())?,
+    }
+
+    save_undo_state(file, output_file, "apply-patch", ctx)?;
+    write_png(&png, output_file, ctx)
+}
+
+/// Restores `file` to its most recent undo state, which is then dropped.
+/// With `list`, only prints the available states, most recent first.
+pub fn undo(file: &Path, list: bool, ctx: &Context) -> Result<(), PngMeError> {
+    let store = UndoStore::for_file(file)?;
+
+    let mut out = io::stdout().lock();
+    if list {
+        let states = store.states()?;
+        if states.is_empty() {
+            writeln!(out, "Nothing to undo for {}", file.display())?;
+        }
+        for (position, state) in states.iter().enumerate() {
+            writeln!(
+                out,
+                "{}. {} before {} ({} bytes)",
+                position + 1,
+                format_timestamp(state.manifest.created_at),
+                state.manifest.operation,
+                state.manifest.size
+            )?;
+        }
+
+        return Ok(());
+    }
+
+    ensure_writable(file)?;
+    let _lock = FileLock::acquire(file, ctx.lock_timeout)?;
+
+    let (state, bytes) = store.latest()?;
+    write_to_sink(sink_for(file).as_mut(), &bytes)?;
+    store.remove(&state)?;
+
+    writeln!(
+        out,
+        "Restored {} to before {} ({})",
+        file.display(),
+        state.manifest.operation,
+        format_timestamp(state.manifest.created_at)
+    )?;
+
+    Ok(())
+}
+
+/// Stores the passphrase read from `source` in the keychain as `name`
+pub fn store_key(name: &str, source: &SecretSource, ctx: &Context) -> Result<(), PngMeError> {
+    let secret = source.read(ctx.keychain)?;
+    ctx.keychain.set(name, &secret)?;
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "Stored the passphrase {name} in the keychain")?;
+
+    Ok(())
+}
+
+pub fn delete_key(name: &str, ctx: &Context) -> Result<(), PngMeError> {
+    ctx.keychain.delete(name)?;
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "Deleted the passphrase {name} from the keychain")?;
+
+    Ok(())
+}
+
+/// Fills the `{{var}}` placeholders of a message template
+pub fn render_message(
+    source: &str,
+    vars: &[(String, String)],
+    file: &InputSource,
+    deterministic: bool,
+    ctx: &Context,
+) -> Result<String, PngMeError> {
+    let variables = Variables {
+        vars: vars.iter().cloned().collect(),
+        file: file.to_string(),
+        clock: ctx.clock,
+        deterministic,
+    };
+
+    Ok(template::render(source, &variables)?)
+}
+
+/// Serves the HTTP API of [`crate::server`] until the process stops
+#[cfg(feature = "server")]
+pub fn serve(listen: &str, options: crate::server::ServerOptions) -> Result<(), PngMeError> {
+    let server = crate::server::Server::bind(listen, options)?;
+    if let Some(address) = server.local_addr() {
+        eprintln!("Listening on http://{address}");
+    }
+    server.run();
+
+    Ok(())
+}
+
+/// Prints the CRC of a chunk of `chunk_type` holding `data`
+pub fn print_crc(chunk_type: &str, data: &[u8]) -> Result<(), PngMeError> {
+    let chunk_type = ChunkType::parse_name(chunk_type)?;
+    let crc = Chunk::compute_crc(&chunk_type, data);
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "CRC: {crc} (0x{crc:08x})")?;
+
+    Ok(())
+}
+
+/// Prints the version, features, inputs and commands of this build
+pub fn capabilities(format: OutputFormat, ctx: &Context) -> Result<(), PngMeError> {
+    let capabilities = Capabilities::of(&Arguments::command());
+
+    let mut out = io::stdout().lock();
+    match format {
+        OutputFormat::Json => writeln!(out, "{}", json_report(&capabilities, ctx)?)?,
+        OutputFormat::Human => writeln!(out, "{capabilities}")?,
     }
 
     Ok(())
 }
 
-pub fn remove(file: &PathBuf, chunk_type: &str) -> Result<(), PngMeError> {
-    let mut png = file_to_png(file)?;
+/// Prints the code, name, summary and exit status of `code`, or of every
+/// code in the catalog without one
+pub fn explain(code: Option<&str>) -> Result<(), PngMeError> {
+    let mut out = io::stdout().lock();
 
-    png.remove_first_chunk(chunk_type)?;
+    let Some(text) = code else {
+        for code in Code::ALL {
+            writeln!(out, "{code}  {:<28}  {}", code.name(), code.message())?;
+        }
+        return Ok(());
+    };
+
+    let code = Code::find(text).ok_or_else(|| PngMeError::UnknownCode { code: text.to_string() })?;
+    writeln!(out, "{code} {}: {}", code.name(), code.message())?;
+    match code.is_warning() {
+        true => writeln!(out, "A warning, the command goes on")?,
+        false => writeln!(out, "An error, pngme exits with {}", ExitStatus::of(code))?,
+    }
+
+    Ok(())
+}
 
-    let mut file = File::create(file)?;
-    file.write_all(&png.as_bytes())?;
+/// Prints the version and build details of this binary
+pub fn version(format: OutputFormat, ctx: &Context) -> Result<(), PngMeError> {
+    let build = BuildInfo::current();
+
+    let mut out = io::stdout().lock();
+    match format {
+        OutputFormat::Json => writeln!(out, "{}", json_report(&build, ctx)?)?,
+        OutputFormat::Human => writeln!(out, "{build}")?,
+    }
 
     Ok(())
 }
 
-pub fn print(file: &PathBuf) -> Result<(), PngMeError> {
-    let png = file_to_png(file)?;
+/// Prints how long `file` takes to read, parse and serialize
+pub fn bench_parse(file: &InputSource, ctx: &Context) -> Result<(), PngMeError> {
+    let start = Instant::now();
+    let input = file.resolve(&ctx.input_options, &NoopObserver)?;
+    let mut out = io::stdout().lock();
+    writeln!(out, "read       {:>12.3?}  ({} bytes)", start.elapsed(), input.bytes.len())?;
 
-    println!("{png}");
+    let start = Instant::now();
+    let count = chunk_refs(&input.bytes, false, ctx.parse_options.max_chunks)?.count();
+    writeln!(out, "list       {:>12.3?}  ({count} chunks, CRCs not checked)", start.elapsed())?;
+
+    let start = Instant::now();
+    let png = Png::parse(input.bytes.as_slice(), &ctx.parse_options, &NoopObserver)?;
+    writeln!(out, "parse      {:>12.3?}  ({} chunks)", start.elapsed(), png.chunks().len())?;
+
+    let start = Instant::now();
+    let bytes = png.as_bytes();
+    writeln!(out, "as_bytes   {:>12.3?}  ({} bytes)", start.elapsed(), bytes.len())?;
+
+    let start = Instant::now();
+    png.write_to(&mut io::sink())?;
+    writeln!(out, "write_to   {:>12.3?}", start.elapsed())?;
+
+    Ok(())
+}
+
+pub fn make_fixture(kind: FixtureKind, output: &Path) -> Result<(), PngMeError> {
+    fs::write(output, fixtures::make_fixture(kind))?;
 
     Ok(())
-}
\ No newline at end of file
+}
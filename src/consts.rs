@@ -0,0 +1,42 @@
+//! Sizes of the PNG framing.
+//!
+//! A file is the signature followed by chunks, each stored as:
+//!
+//! ```text
+//! +--------------+------------+-------------------+-----------+
+//! | LENGTH_FIELD | TYPE_FIELD |       data        | CRC_FIELD |
+//! +--------------+------------+-------------------+-----------+
+//! ```
+//!
+//! Code computing offsets, sizes or capacities uses these rather than
+//! literals, so the layout is described in this one place.
+
+/// Bytes of the signature every PNG file starts with
+pub const SIGNATURE_LEN: usize = 8;
+
+/// Bytes of the big-endian data length starting a chunk
+pub const LENGTH_FIELD: usize = 4;
+
+/// Bytes of the chunk type, after the length
+pub const TYPE_FIELD: usize = 4;
+
+/// Bytes of the CRC ending a chunk, after the data
+pub const CRC_FIELD: usize = 4;
+
+/// Bytes of a chunk besides its data, the size of an empty chunk
+pub const CHUNK_OVERHEAD: usize = LENGTH_FIELD + TYPE_FIELD + CRC_FIELD;
+
+/// Offset of the data in a chunk
+pub const DATA_OFFSET: usize = LENGTH_FIELD + TYPE_FIELD;
+
+/// Largest data length the specification allows (2^31 - 1 bytes)
+pub const MAX_CHUNK_DATA: u32 = 0x7FFF_FFFF;
+
+const _: () = {
+    assert!(CHUNK_OVERHEAD == 12);
+    assert!(DATA_OFFSET + CRC_FIELD == CHUNK_OVERHEAD);
+    assert!(LENGTH_FIELD == size_of::<u32>());
+    assert!(CRC_FIELD == size_of::<u32>());
+    // The length field holds the data length in 31 bits
+    assert!(MAX_CHUNK_DATA as u64 == (1 << (8 * LENGTH_FIELD - 1)) - 1);
+};
@@ -0,0 +1,160 @@
+//! Passphrase encryption of messages, for `encode --encrypt`.
+//!
+//! The key is derived from the passphrase with Argon2id and a random salt,
+//! then the message is sealed with ChaCha20-Poly1305 under a random nonce.
+//! A sealed message is laid out as:
+//!
+//! | Field      | Size                        |
+//! |------------|-----------------------------|
+//! | salt       | [`SALT_LEN`] bytes          |
+//! | nonce      | [`NONCE_LEN`] bytes         |
+//! | ciphertext | message + [`TAG_LEN`] bytes |
+//!
+//! The tag authenticates the ciphertext along with associated data, the
+//! header of the envelope holding it, so a wrong passphrase, an altered
+//! message and an altered header all fail the same way instead of giving
+//! garbage.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng, Payload, rand_core::RngCore},
+};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::codes::Code;
+
+/// Bytes of the random salt of the key derivation
+pub const SALT_LEN: usize = 16;
+
+/// Bytes of the random nonce of the cipher
+pub const NONCE_LEN: usize = 12;
+
+/// Bytes of the authentication tag ending the ciphertext
+pub const TAG_LEN: usize = 16;
+
+/// KiB of memory the key derivation uses. The cost is pinned rather than
+/// left to the defaults of the argon2 crate, which would no longer derive
+/// the keys of existing messages if they changed.
+const ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+
+/// Passes of the key derivation over its memory
+const ARGON2_PASSES: u32 = 2;
+
+/// Lanes of the key derivation
+const ARGON2_LANES: u32 = 1;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CryptoError {
+    #[error("Decryption failed: wrong passphrase, or the message was altered")]
+    DecryptionFailed,
+}
+
+impl CryptoError {
+    pub fn code(&self) -> Code {
+        match self {
+            CryptoError::DecryptionFailed => Code::DecryptionFailed,
+        }
+    }
+}
+
+/// Encrypts `message` with a key derived from `passphrase`, authenticating
+/// `associated_data` along with it
+pub fn seal(message: &[u8], passphrase: &str, associated_data: &[u8]) -> Vec<u8> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let payload = Payload {
+        msg: message,
+        aad: associated_data,
+    };
+    let ciphertext = cipher(passphrase, &salt)
+        .encrypt(&nonce, payload)
+        .expect("messages fit in a chunk, far below the cipher limit");
+
+    [&salt[..], &nonce, &ciphertext].concat()
+}
+
+/// Decrypts a message sealed by [`seal`] with the same passphrase and
+/// associated data
+pub fn unseal(
+    sealed: &[u8],
+    passphrase: &str,
+    associated_data: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let (salt, rest) = sealed
+        .split_first_chunk::<SALT_LEN>()
+        .ok_or(CryptoError::DecryptionFailed)?;
+    let (nonce, ciphertext) = rest
+        .split_first_chunk::<NONCE_LEN>()
+        .ok_or(CryptoError::DecryptionFailed)?;
+
+    let payload = Payload {
+        msg: ciphertext,
+        aad: associated_data,
+    };
+    cipher(passphrase, salt)
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .map_err(|_| CryptoError::DecryptionFailed)
+}
+
+fn cipher(passphrase: &str, salt: &[u8; SALT_LEN]) -> ChaCha20Poly1305 {
+    let mut key = Zeroizing::new([0; 32]);
+    let params = Params::new(
+        ARGON2_MEMORY_KIB,
+        ARGON2_PASSES,
+        ARGON2_LANES,
+        Some(key.len()),
+    )
+    .expect("the Argon2 parameters are in range");
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .expect("the salt and key lengths are valid for Argon2");
+
+    ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_round_trip() {
+        let sealed = seal(b"meet at noon", "correct horse", b"header");
+
+        assert_eq!(sealed.len(), SALT_LEN + NONCE_LEN + 12 + TAG_LEN);
+        assert_eq!(
+            unseal(&sealed, "correct horse", b"header").unwrap(),
+            b"meet at noon"
+        );
+        // Fresh salt and nonce every time
+        assert_ne!(seal(b"meet at noon", "correct horse", b"header"), sealed);
+    }
+
+    #[test]
+    fn test_unseal_failures() {
+        let mut sealed = seal(b"meet at noon", "correct horse", b"header");
+
+        assert_eq!(
+            unseal(&sealed, "battery staple", b"header"),
+            Err(CryptoError::DecryptionFailed)
+        );
+        assert_eq!(
+            unseal(&sealed[..SALT_LEN], "correct horse", b"header"),
+            Err(CryptoError::DecryptionFailed)
+        );
+        assert_eq!(
+            unseal(&sealed, "correct horse", b"altered"),
+            Err(CryptoError::DecryptionFailed)
+        );
+
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert_eq!(
+            unseal(&sealed, "correct horse", b"header"),
+            Err(CryptoError::DecryptionFailed)
+        );
+    }
+}
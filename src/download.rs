@@ -0,0 +1,717 @@
+//! Downloads of remote images.
+//!
+//! The body is written to a temporary file as it arrives. A body cut short
+//! by the connection, or a connection lost before the next request could be
+//! sent, is fetched again after a pause, up to [`MAX_ATTEMPTS`] times. When
+//! the server advertised `Accept-Ranges: bytes` and a validator (a strong
+//! `ETag` or `Last-Modified`), only the missing bytes are requested with a
+//! `Range` header, made conditional with `If-Range` so that a body that
+//! changed since comes back whole, and appended to the temporary file.
+//! Otherwise the download starts over.
+//!
+//! With a [`DownloadCache`] in the options, a cached body is reused when the
+//! server tells it didn't change, see [`crate::cache`].
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Read, Seek, Write},
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use reqwest::{
+    StatusCode,
+    blocking::{Client, Response},
+    header::{
+        ACCEPT_RANGES, CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+    },
+};
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    cache::{CacheEntry, DownloadCache, Validators},
+    clock::{Clock, SystemClock},
+    codes::Code,
+    input::InputOptions,
+    observer::{Observer, Stage},
+    temp,
+};
+
+/// Requests made for one download before giving up on a body cut short
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// Pause before the second attempt, doubled before each following one
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Downloads started by the process, naming their temporary files
+static DOWNLOADS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Error, Debug)]
+pub enum DownloadError {
+    #[error("Could not reach {url}: {source}")]
+    Request { url: Url, source: reqwest::Error },
+
+    #[error("Request to {url} failed with status {status}")]
+    Status {
+        url: Url,
+        status: reqwest::StatusCode,
+    },
+
+    #[error("Could not download {url}: {source}")]
+    Read { url: Url, source: io::Error },
+
+    #[error("Could not store the download of {url}: {source}")]
+    Store { url: Url, source: io::Error },
+
+    #[error("{url} is too large ({size} bytes, the limit is {limit} bytes)")]
+    TooLarge { url: Url, size: u64, limit: u64 },
+
+    #[error(
+        "Download of {url} stopped at {size} of {expected} bytes after {MAX_ATTEMPTS} attempts"
+    )]
+    Incomplete { url: Url, size: u64, expected: u64 },
+}
+
+impl DownloadError {
+    pub fn code(&self) -> Code {
+        match self {
+            DownloadError::Request { .. } => Code::DownloadFailed,
+            DownloadError::Status { .. } => Code::DownloadStatus,
+            DownloadError::Read { .. } => Code::DownloadReadFailed,
+            DownloadError::Store { .. } => Code::DownloadStoreFailed,
+            DownloadError::TooLarge { .. } => Code::DownloadTooLarge,
+            DownloadError::Incomplete { .. } => Code::DownloadIncomplete,
+        }
+    }
+}
+
+/// Keeps a download under a number of bytes per second
+struct Throttle {
+    rate: Option<u64>,
+    start: Instant,
+    bytes: u64,
+}
+
+impl Throttle {
+    fn new(rate: Option<u64>) -> Self {
+        Self {
+            rate,
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    /// Size of the reads, so that a low rate still moves every second
+    fn buffer_size(&self) -> usize {
+        const MAX_BUFFER: usize = 64 * 1024;
+
+        self.rate
+            .map_or(MAX_BUFFER, |rate| (rate as usize).clamp(1, MAX_BUFFER))
+    }
+
+    /// Counts `read` more bytes, sleeping until they are due
+    fn consume(&mut self, read: usize) {
+        self.bytes += read as u64;
+
+        if let Some(rate) = self.rate.filter(|&rate| rate > 0) {
+            let due = Duration::from_secs_f64(self.bytes as f64 / rate as f64);
+            if let Some(wait) = due.checked_sub(self.start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+    }
+}
+
+/// Temporary file holding the body received so far, removed when dropped
+struct PartialBody {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl PartialBody {
+    fn create() -> io::Result<Self> {
+        let number = DOWNLOADS.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("pngme-download-{}-{number}.tmp", process::id()));
+        let file = temp::create(&path)?;
+
+        Ok(Self { path, file, size: 0 })
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.file.write_all(bytes)?;
+        self.size += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Drops the bytes received, for a body downloaded again from the start
+    fn clear(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        self.size = 0;
+        Ok(())
+    }
+
+    /// The whole body, once complete
+    fn read(&mut self) -> io::Result<Vec<u8>> {
+        self.file.flush()?;
+        fs::read(&self.path)
+    }
+}
+
+impl Drop for PartialBody {
+    fn drop(&mut self) {
+        temp::discard(&self.path);
+    }
+}
+
+/// What the first response told about the body
+struct BodyInfo {
+    total: Option<u64>,
+    accepts_ranges: bool,
+    validators: Validators,
+}
+
+impl BodyInfo {
+    fn of(resp: &Response) -> Self {
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Self {
+            total: resp.content_length(),
+            accepts_ranges: resp
+                .headers()
+                .get(ACCEPT_RANGES)
+                .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"bytes")),
+            validators: Validators {
+                etag: header(ETAG),
+                last_modified: header(LAST_MODIFIED),
+            },
+        }
+    }
+
+    /// Validator of the `If-Range` header resuming the body: the `ETag`
+    /// unless weak, which `If-Range` doesn't take, else `Last-Modified`
+    fn if_range(&self) -> Option<&str> {
+        let etag = self.validators.etag.as_deref().filter(|etag| !etag.starts_with("W/"));
+        etag.or(self.validators.last_modified.as_deref())
+    }
+}
+
+/// Downloads `url` through a temporary file. A body larger than `options.max_size` is
+/// rejected, before reading it when the server announces its length, and
+/// `options.limit_rate` caps the speed.
+///
+/// A body cut short is resumed from where it stopped when the server
+/// accepts ranges and gave a validator, and downloaded again otherwise. The
+/// final size must match the announced length.
+pub fn fetch(
+    url: &Url,
+    options: &InputOptions,
+    observer: &dyn Observer,
+) -> Result<Vec<u8>, DownloadError> {
+    let Some(cache) = &options.cache else {
+        let (bytes, _) = download(url, options, observer, None)?
+            .expect("only conditional requests are answered 304");
+        return Ok(bytes);
+    };
+
+    fetch_cached(url, cache, options, observer)
+}
+
+/// Downloads `url` unless `cache` holds the current version
+fn fetch_cached(
+    url: &Url,
+    cache: &DownloadCache,
+    options: &InputOptions,
+    observer: &dyn Observer,
+) -> Result<Vec<u8>, DownloadError> {
+    let now = SystemClock.now();
+    let cached = cache
+        .lookup(url)
+        .filter(|(entry, _)| !entry.is_stale(now, cache.max_age));
+
+    let conditions = cached.as_ref().map(|(entry, _)| &entry.validators);
+    let Some((bytes, validators)) = download(url, options, observer, conditions)? else {
+        let (entry, bytes) = cached.expect("only conditional requests are answered 304");
+        observer.on_note(&format!("{url} didn't change, using the cached copy"));
+        let entry = CacheEntry {
+            checked_at: now,
+            ..entry
+        };
+        if let Err(err) = cache.save_entry(url, &entry) {
+            observer.on_note(&format!("could not update the cache entry of {url}: {err}"));
+        }
+        return Ok(bytes);
+    };
+
+    // Without validators the server could never confirm the copy
+    if !validators.is_empty()
+        && let Err(err) = cache.store(url, validators, &bytes, now)
+    {
+        observer.on_note(&format!("could not cache {url}: {err}"));
+    }
+
+    Ok(bytes)
+}
+
+/// Downloads `url`, asking the server to answer 304 Not Modified when the
+/// body still matches `conditions`. Returns the body and its validators, or
+/// `None` on 304.
+fn download(
+    url: &Url,
+    options: &InputOptions,
+    observer: &dyn Observer,
+    conditions: Option<&Validators>,
+) -> Result<Option<(Vec<u8>, Validators)>, DownloadError> {
+    let request_error = |source| DownloadError::Request {
+        url: url.clone(),
+        source,
+    };
+    let too_large = |size| DownloadError::TooLarge {
+        url: url.clone(),
+        size,
+        limit: options.max_size.unwrap_or_default(),
+    };
+
+    let client = Client::builder()
+        .user_agent("PNGme/1.0")
+        .build()
+        .map_err(request_error)?;
+
+    let store_error = |source| DownloadError::Store {
+        url: url.clone(),
+        source,
+    };
+
+    let mut body = PartialBody::create().map_err(store_error)?;
+    let mut info: Option<BodyInfo> = None;
+    let mut throttle = Throttle::new(options.limit_rate);
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        if attempt > 1 {
+            thread::sleep(RETRY_DELAY * 2u32.pow(attempt - 2));
+        }
+
+        // Without a validator a body that changed since would be completed
+        // with the end of another one
+        let if_range = info
+            .as_ref()
+            .filter(|info| info.accepts_ranges && body.size > 0)
+            .and_then(BodyInfo::if_range);
+        let resume = if_range.is_some();
+        let mut request = client.get(url.clone());
+        if let Some(validator) = if_range {
+            request = request
+                .header(RANGE, format!("bytes={}-", body.size))
+                .header(IF_RANGE, validator);
+        }
+        // Only the first request is conditional, the others complete its body
+        if let Some(conditions) = conditions.filter(|_| attempt == 1) {
+            if let Some(etag) = &conditions.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &conditions.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let resp = match request.send() {
+            Ok(resp) => resp,
+            // The connection that cut the body short may still be down
+            Err(err) if attempt > 1 && attempt < MAX_ATTEMPTS => {
+                observer.on_note(&format!("could not reach {url} again ({err}), retrying"));
+                continue;
+            }
+            Err(err) => return Err(request_error(err)),
+        };
+        if attempt == 1 && conditions.is_some() && resp.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+
+        let resumed = resume
+            && resp.status() == StatusCode::PARTIAL_CONTENT
+            && resp
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|range| range.starts_with(&format!("bytes {}-", body.size)));
+
+        if !resumed {
+            // A part of the body other than the one asked for can't be used
+            if !resp.status().is_success() || resp.status() == StatusCode::PARTIAL_CONTENT {
+                return Err(DownloadError::Status {
+                    url: url.clone(),
+                    status: resp.status(),
+                });
+            }
+            // A 200, the whole body again: the server doesn't take ranges,
+            // or If-Range told the body changed
+            if body.size > 0 {
+                observer.on_note(&format!(
+                    "the download could not be resumed, downloading {url} again"
+                ));
+                body.clear().map_err(store_error)?;
+            }
+
+            let announced = BodyInfo::of(&resp);
+            if let (Some(size), Some(limit)) = (announced.total, options.max_size)
+                && size > limit
+            {
+                return Err(too_large(size));
+            }
+            info = Some(announced);
+        }
+        let total = info.as_ref().and_then(|info| info.total);
+
+        let limit = options.max_size.map_or(u64::MAX, |limit| limit + 1);
+        let mut resp = resp.take(limit.saturating_sub(body.size));
+        let mut buffer = vec![0u8; throttle.buffer_size()];
+
+        observer.on_progress(Stage::Download, body.size, total);
+
+        let failure = loop {
+            match resp.read(&mut buffer) {
+                Ok(0) => break None,
+                Ok(read) => {
+                    body.append(&buffer[..read]).map_err(store_error)?;
+                    throttle.consume(read);
+                    observer.on_progress(Stage::Download, body.size, total);
+                }
+                Err(err) => break Some(err),
+            }
+        };
+
+        if let Some(limit) = options.max_size
+            && body.size > limit
+        {
+            return Err(too_large(body.size));
+        }
+
+        let size = body.size;
+        if failure.is_none() && total.is_none_or(|total| size == total) {
+            let bytes = body.read().map_err(store_error)?;
+            let validators = info.map(|info| info.validators).unwrap_or_default();
+            return Ok(Some((bytes, validators)));
+        }
+
+        if attempt == MAX_ATTEMPTS {
+            return Err(match (total, failure) {
+                (Some(expected), _) => DownloadError::Incomplete {
+                    url: url.clone(),
+                    size,
+                    expected,
+                },
+                (None, failure) => DownloadError::Read {
+                    url: url.clone(),
+                    source: failure.expect("a body without length only fails on errors"),
+                },
+            });
+        }
+    }
+
+    unreachable!("the last attempt returns")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        io::Write,
+        net::{TcpListener, TcpStream},
+        sync::mpsc,
+    };
+
+    use super::*;
+    use crate::observer::NoopObserver;
+
+    fn body() -> Vec<u8> {
+        (0..1000).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// The body once changed on the server, with another `ETag`
+    fn changed_body() -> Vec<u8> {
+        body().into_iter().rev().collect()
+    }
+
+    const ETAG: &str = "\"v1\"";
+
+    /// How the stub server answers one request
+    enum Reply {
+        /// Announces the rest of the body but closes after `sent` bytes,
+        /// with an `ETag` when `validator` is set
+        Cut {
+            sent: usize,
+            ranges: bool,
+            validator: bool,
+        },
+        /// Sends the rest of the body
+        Full { ranges: bool },
+        /// Sends the changed body, whole since `If-Range` names the old one
+        Changed,
+        /// Stops listening, refusing connections, until signaled
+        Refuse(mpsc::Receiver<()>),
+    }
+
+    /// The `Range` and `If-Range` headers of the request read from `stream`
+    fn read_range(stream: &mut TcpStream) -> (Option<String>, Option<String>) {
+        let mut request = Vec::new();
+        let mut byte = [0u8];
+        while !request.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+            request.push(byte[0]);
+        }
+
+        let request = String::from_utf8(request).unwrap();
+        let header = |wanted: &str| {
+            request.lines().find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.eq_ignore_ascii_case(wanted)
+                    .then(|| value.trim().to_string())
+            })
+        };
+        (header("range"), header("if-range"))
+    }
+
+    /// The `Range` and `If-Range` headers of each request
+    type Requests = mpsc::Receiver<(Option<String>, Option<String>)>;
+
+    /// Serves `replies` on localhost, one per connection
+    fn serve(replies: Vec<Reply>) -> (Url, Requests) {
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for reply in replies {
+                if let Reply::Refuse(signal) = reply {
+                    drop(listener);
+                    signal.recv().unwrap();
+                    listener = TcpListener::bind(addr).unwrap();
+                    continue;
+                }
+
+                let (mut stream, _) = listener.accept().unwrap();
+                let (range, if_range) = read_range(&mut stream);
+                let (body, etag, ranges, sent) = match reply {
+                    Reply::Cut {
+                        sent,
+                        ranges,
+                        validator,
+                    } => (body(), validator.then_some(ETAG), ranges, Some(sent)),
+                    Reply::Full { ranges } => (body(), Some(ETAG), ranges, None),
+                    Reply::Changed => (changed_body(), Some("\"v2\""), true, None),
+                    Reply::Refuse(_) => unreachable!("handled before accepting"),
+                };
+
+                let start = match &range {
+                    Some(range) if ranges && if_range.as_deref() == etag => range["bytes=".len()..]
+                        .trim_end_matches('-')
+                        .parse()
+                        .unwrap(),
+                    _ => 0,
+                };
+                let rest = &body[start..];
+                let mut header = match start {
+                    0 => "HTTP/1.1 200 OK\r\n".to_string(),
+                    _ => format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {start}-{}/{}\r\n",
+                        body.len() - 1,
+                        body.len()
+                    ),
+                };
+                if ranges {
+                    header.push_str("Accept-Ranges: bytes\r\n");
+                }
+                if let Some(etag) = etag {
+                    header.push_str(&format!("ETag: {etag}\r\n"));
+                }
+                header.push_str(&format!(
+                    "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    rest.len()
+                ));
+
+                let _ = sender.send((range, if_range));
+                stream.write_all(header.as_bytes()).unwrap();
+                let _ = stream.write_all(&rest[..sent.unwrap_or(rest.len())]);
+            }
+        });
+
+        (
+            Url::parse(&format!("http://{addr}/large.png")).unwrap(),
+            receiver,
+        )
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        notes: RefCell<Vec<String>>,
+    }
+
+    impl Observer for RecordingObserver {
+        fn on_note(&self, note: &str) {
+            self.notes.borrow_mut().push(note.to_string());
+        }
+    }
+
+    /// Signals each note, telling the stub server the refused connection
+    /// was tried
+    struct SignalingObserver(mpsc::Sender<()>);
+
+    impl Observer for SignalingObserver {
+        fn on_note(&self, _note: &str) {
+            let _ = self.0.send(());
+        }
+    }
+
+    #[test]
+    fn test_resume_with_range() {
+        let (url, requests) = serve(vec![
+            Reply::Cut {
+                sent: 400,
+                ranges: true,
+                validator: true,
+            },
+            Reply::Full { ranges: true },
+        ]);
+
+        let bytes = fetch(&url, &InputOptions::default(), &NoopObserver).unwrap();
+
+        assert_eq!(bytes, body());
+        let requests: Vec<_> = requests.try_iter().collect();
+        assert_eq!(
+            requests,
+            [
+                (None, None),
+                (Some("bytes=400-".to_string()), Some(ETAG.to_string()))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_restart_when_the_body_changed() {
+        let (url, requests) = serve(vec![
+            Reply::Cut {
+                sent: 400,
+                ranges: true,
+                validator: true,
+            },
+            Reply::Changed,
+        ]);
+        let observer = RecordingObserver::default();
+
+        let bytes = fetch(&url, &InputOptions::default(), &observer).unwrap();
+
+        // Not the start of the old body followed by the end of the new one
+        assert_eq!(bytes, changed_body());
+        assert_eq!(requests.try_iter().count(), 2);
+        assert!(observer.notes.borrow()[0].contains("again"));
+    }
+
+    #[test]
+    fn test_restart_without_a_validator() {
+        let (url, requests) = serve(vec![
+            Reply::Cut {
+                sent: 400,
+                ranges: true,
+                validator: false,
+            },
+            Reply::Full { ranges: true },
+        ]);
+
+        let bytes = fetch(&url, &InputOptions::default(), &NoopObserver).unwrap();
+
+        assert_eq!(bytes, body());
+        assert_eq!(requests.try_iter().collect::<Vec<_>>(), [(None, None), (None, None)]);
+    }
+
+    #[test]
+    fn test_restart_without_range_support() {
+        let (url, requests) = serve(vec![
+            Reply::Cut {
+                sent: 400,
+                ranges: false,
+                validator: true,
+            },
+            Reply::Full { ranges: false },
+        ]);
+        let observer = RecordingObserver::default();
+
+        let bytes = fetch(&url, &InputOptions::default(), &observer).unwrap();
+
+        assert_eq!(bytes, body());
+        assert_eq!(requests.try_iter().collect::<Vec<_>>(), [(None, None), (None, None)]);
+        assert_eq!(observer.notes.borrow().len(), 1);
+        assert!(observer.notes.borrow()[0].contains("again"));
+    }
+
+    #[test]
+    fn test_retry_a_refused_connection() {
+        let (signal, refused) = mpsc::channel();
+        let (url, requests) = serve(vec![
+            Reply::Cut {
+                sent: 400,
+                ranges: true,
+                validator: true,
+            },
+            Reply::Refuse(refused),
+            Reply::Full { ranges: true },
+        ]);
+
+        let bytes = fetch(&url, &InputOptions::default(), &SignalingObserver(signal)).unwrap();
+
+        // The third request still resumes the body kept from the first
+        assert_eq!(bytes, body());
+        assert_eq!(
+            requests.try_iter().collect::<Vec<_>>(),
+            [
+                (None, None),
+                (Some("bytes=400-".to_string()), Some(ETAG.to_string()))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gives_up_after_the_last_attempt() {
+        let cut = || Reply::Cut {
+            sent: 100,
+            ranges: true,
+            validator: true,
+        };
+        let (url, _) = serve(vec![cut(), cut(), cut()]);
+
+        let result = fetch(&url, &InputOptions::default(), &NoopObserver);
+
+        assert!(matches!(
+            result,
+            Err(DownloadError::Incomplete {
+                size: 300,
+                expected: 1000,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_limit_rate() {
+        let (url, _) = serve(vec![Reply::Full { ranges: false }]);
+        let options = InputOptions {
+            limit_rate: Some(4000),
+            ..InputOptions::default()
+        };
+        let start = Instant::now();
+
+        let bytes = fetch(&url, &options, &NoopObserver).unwrap();
+
+        // 1000 bytes at 4000 bytes per second
+        assert_eq!(bytes.len(), 1000);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,856 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, Write},
+};
+
+use flate2::{Compression, write::ZlibEncoder};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    clock::{Clock, format_timestamp},
+    codes::Code,
+    crypto::{self, CryptoError},
+    inflate::{self, InflateError},
+    mac::{self, HMAC_LEN, MacError},
+};
+
+/// Envelope wrapping a message with metadata about it.
+///
+/// Layout: `PNGME`, a version byte, a flags byte, then the fields selected
+/// by the flags, in this order, and finally the message:
+///
+/// | Field          | Size                   | Present when          |
+/// |----------------|------------------------|-----------------------|
+/// | expires at     | 8 bytes                | [`FLAG_EXPIRES`]      |
+/// | pngme version  | 1 byte length + UTF-8  | [`FLAG_PROVENANCE`]   |
+/// | created at     | 8 bytes                | [`FLAG_CREATED`]      |
+/// | annotation     | 2 bytes length + UTF-8 | [`FLAG_ANNOTATION`]   |
+/// | HMAC-SHA256    | 32 bytes               | [`FLAG_HMAC`]         |
+///
+/// Integers are big endian. [`FLAG_CREATED`] and [`FLAG_ANNOTATION`] are
+/// only valid along with [`FLAG_PROVENANCE`]. With [`FLAG_COMPRESSED`] the
+/// message is zlib-compressed, and with [`FLAG_ENCRYPTED`] it is sealed as
+/// described in [`crate::crypto`], after compression. The HMAC covers the
+/// rest of the envelope, see [`crate::mac`]. Payloads without the magic are
+/// plain messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    /// Expiry in seconds since the epoch (UTC)
+    pub expires_at: Option<u64>,
+    pub provenance: Option<Provenance>,
+    /// The message is stored compressed, see [`Envelope::inflate`]
+    pub compressed: bool,
+    /// The message is stored encrypted, see [`Envelope::decrypt`]
+    pub encrypted: bool,
+    /// Tag authenticating the envelope, see [`Envelope::sign`]
+    pub hmac: Option<[u8; HMAC_LEN]>,
+    pub message: Vec<u8>,
+}
+
+/// Who wrote a chunk, recorded by `encode --annotate`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Provenance {
+    /// Version of pngme that wrote the chunk
+    pub version: String,
+    /// Seconds since the epoch (UTC), left out by deterministic runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    /// Free text, typically who wrote the chunk and why
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<String>,
+}
+
+pub const MAGIC: &[u8; 5] = b"PNGME";
+pub const VERSION: u8 = 1;
+pub const FLAG_EXPIRES: u8 = 1 << 0;
+pub const FLAG_PROVENANCE: u8 = 1 << 1;
+pub const FLAG_CREATED: u8 = 1 << 2;
+pub const FLAG_ANNOTATION: u8 = 1 << 3;
+pub const FLAG_COMPRESSED: u8 = 1 << 4;
+pub const FLAG_ENCRYPTED: u8 = 1 << 5;
+pub const FLAG_HMAC: u8 = 1 << 6;
+
+const KNOWN_FLAGS: u8 = FLAG_EXPIRES
+    | FLAG_PROVENANCE
+    | FLAG_CREATED
+    | FLAG_ANNOTATION
+    | FLAG_COMPRESSED
+    | FLAG_ENCRYPTED
+    | FLAG_HMAC;
+
+/// Why the message of an envelope can't be shown
+#[derive(Error, Debug)]
+pub enum OpenError {
+    #[error("The compressed payload is corrupted: {0}")]
+    Corrupt(io::Error),
+
+    #[error("The message is encrypted, pass --decrypt to read it")]
+    Encrypted,
+
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+
+    #[error(transparent)]
+    Inflate(InflateError),
+}
+
+impl OpenError {
+    pub fn code(&self) -> Code {
+        match self {
+            OpenError::Corrupt(_) => Code::CorruptPayload,
+            OpenError::Encrypted => Code::MessageEncrypted,
+            OpenError::Crypto(err) => err.code(),
+            OpenError::Inflate(err) => err.code(),
+        }
+    }
+
+    /// The error naming the chunk the payload comes from
+    pub fn in_chunk(self, chunk_type: impl Display, index: usize) -> Self {
+        match self {
+            OpenError::Inflate(err) => OpenError::Inflate(err.in_chunk(chunk_type, index)),
+            err => err,
+        }
+    }
+}
+
+impl Provenance {
+    /// Provenance written by this version of pngme
+    pub fn new(created_at: Option<u64>, annotation: Option<String>) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at,
+            annotation,
+        }
+    }
+}
+
+impl Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "written by pngme {}", self.version)?;
+        if let Some(created_at) = self.created_at {
+            write!(f, " on {}", format_timestamp(created_at))?;
+        }
+        if let Some(annotation) = &self.annotation {
+            write!(f, ": {annotation}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Envelope {
+    pub fn new(message: Vec<u8>) -> Self {
+        Self {
+            expires_at: None,
+            provenance: None,
+            compressed: false,
+            encrypted: false,
+            hmac: None,
+            message,
+        }
+    }
+
+    /// The envelope with its message compressed. Encrypted messages are
+    /// left as they are, they don't compress.
+    pub fn deflate(self) -> Self {
+        if self.compressed || self.encrypted {
+            return self;
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder
+            .write_all(&self.message)
+            .expect("writing to a Vec can't fail");
+
+        Self {
+            compressed: true,
+            message: encoder.finish().expect("writing to a Vec can't fail"),
+            ..self
+        }
+    }
+
+    /// The envelope with its message decompressed, failing when the
+    /// compressed stream is corrupted or inflates to more than `limit` bytes
+    pub fn inflate(self, limit: u64) -> Result<Self, InflateError> {
+        if !self.compressed {
+            return Ok(self);
+        }
+
+        Ok(Self {
+            compressed: false,
+            message: inflate::inflate(&self.message, limit)?,
+            ..self
+        })
+    }
+
+    /// The envelope with its message encrypted with `passphrase`
+    pub fn encrypt(self, passphrase: &str) -> Self {
+        if self.encrypted {
+            return self;
+        }
+
+        let mut encrypted = Self {
+            encrypted: true,
+            ..self
+        };
+        encrypted.message =
+            crypto::seal(&encrypted.message, passphrase, &encrypted.associated_data());
+
+        encrypted
+    }
+
+    /// The envelope with its message decrypted, failing on a wrong
+    /// passphrase
+    pub fn decrypt(self, passphrase: &str) -> Result<Self, CryptoError> {
+        if !self.encrypted {
+            return Ok(self);
+        }
+
+        Ok(Self {
+            encrypted: false,
+            message: crypto::unseal(&self.message, passphrase, &self.associated_data())?,
+            ..self
+        })
+    }
+
+    /// What the cipher authenticates along with the message: the header as
+    /// it is when encrypting, before an HMAC is added
+    fn associated_data(&self) -> Vec<u8> {
+        self.header_with(false)
+    }
+
+    /// The envelope with its message as it was given to `encode`: decrypted
+    /// with `passphrase`, then decompressed up to `limit` bytes
+    pub fn reveal(self, passphrase: Option<&str>, limit: u64) -> Result<Self, OpenError> {
+        let envelope = match passphrase {
+            Some(passphrase) => self.decrypt(passphrase)?,
+            None if self.encrypted => return Err(OpenError::Encrypted),
+            None => self,
+        };
+
+        envelope.inflate(limit).map_err(|err| match err {
+            InflateError::Corrupt(err) => OpenError::Corrupt(err),
+            err => OpenError::Inflate(err),
+        })
+    }
+
+    /// The envelope with an HMAC of its content under `key`. Changing it
+    /// afterwards, e.g. encrypting it, invalidates the tag.
+    pub fn sign(self, key: &[u8]) -> Self {
+        // The flag is part of what the tag covers
+        let mut signed = Self {
+            hmac: Some([0; HMAC_LEN]),
+            ..self
+        };
+        signed.hmac = Some(mac::tag(key, &signed.authenticated_bytes()));
+
+        signed
+    }
+
+    /// Checks the HMAC of the envelope against `key`
+    pub fn verify(&self, key: &[u8]) -> Result<(), MacError> {
+        let tag = self.hmac.as_ref().ok_or(MacError::Missing)?;
+
+        mac::verify(key, &self.authenticated_bytes(), tag)
+    }
+
+    /// What the HMAC covers: every byte of the envelope but the tag itself
+    fn authenticated_bytes(&self) -> Vec<u8> {
+        [self.header(), self.message.clone()].concat()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header();
+        if let Some(tag) = &self.hmac {
+            bytes.extend_from_slice(tag);
+        }
+
+        bytes.extend_from_slice(&self.message);
+        bytes
+    }
+
+    /// The magic, version, flags and fields, up to the HMAC
+    fn header(&self) -> Vec<u8> {
+        self.header_with(self.hmac.is_some())
+    }
+
+    /// [`Envelope::header`] with the HMAC flag set as `hmac` says
+    fn header_with(&self, hmac: bool) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+
+        let mut flags = 0;
+        if self.expires_at.is_some() {
+            flags |= FLAG_EXPIRES;
+        }
+        if self.compressed {
+            flags |= FLAG_COMPRESSED;
+        }
+        if self.encrypted {
+            flags |= FLAG_ENCRYPTED;
+        }
+        if hmac {
+            flags |= FLAG_HMAC;
+        }
+        if let Some(provenance) = &self.provenance {
+            flags |= FLAG_PROVENANCE;
+            if provenance.created_at.is_some() {
+                flags |= FLAG_CREATED;
+            }
+            if provenance.annotation.is_some() {
+                flags |= FLAG_ANNOTATION;
+            }
+        }
+        bytes.push(flags);
+
+        if let Some(expires_at) = self.expires_at {
+            bytes.extend_from_slice(&expires_at.to_be_bytes());
+        }
+
+        if let Some(provenance) = &self.provenance {
+            let version = truncated(&provenance.version, u8::MAX as usize);
+            bytes.push(version.len() as u8);
+            bytes.extend_from_slice(version.as_bytes());
+
+            if let Some(created_at) = provenance.created_at {
+                bytes.extend_from_slice(&created_at.to_be_bytes());
+            }
+
+            if let Some(annotation) = &provenance.annotation {
+                let annotation = truncated(annotation, u16::MAX as usize);
+                bytes.extend_from_slice(&(annotation.len() as u16).to_be_bytes());
+                bytes.extend_from_slice(annotation.as_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    /// Parses an envelope, `None` for plain payloads and unknown versions
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let rest = bytes.strip_prefix(MAGIC)?;
+        let (&[version, flags], mut rest) = rest.split_first_chunk::<2>()?;
+
+        if version != VERSION || flags & !KNOWN_FLAGS != 0 {
+            return None;
+        }
+        if flags & FLAG_PROVENANCE == 0 && flags & (FLAG_CREATED | FLAG_ANNOTATION) != 0 {
+            return None;
+        }
+
+        let mut expires_at = None;
+        if flags & FLAG_EXPIRES != 0 {
+            let (seconds, remaining) = rest.split_first_chunk::<8>()?;
+            expires_at = Some(u64::from_be_bytes(*seconds));
+            rest = remaining;
+        }
+
+        let mut provenance = None;
+        if flags & FLAG_PROVENANCE != 0 {
+            let (&[length], remaining) = rest.split_first_chunk::<1>()?;
+            let (version, remaining) = split_text(remaining, length as usize)?;
+            rest = remaining;
+
+            let mut created_at = None;
+            if flags & FLAG_CREATED != 0 {
+                let (seconds, remaining) = rest.split_first_chunk::<8>()?;
+                created_at = Some(u64::from_be_bytes(*seconds));
+                rest = remaining;
+            }
+
+            let mut annotation = None;
+            if flags & FLAG_ANNOTATION != 0 {
+                let (length, remaining) = rest.split_first_chunk::<2>()?;
+                let (text, remaining) =
+                    split_text(remaining, u16::from_be_bytes(*length) as usize)?;
+                annotation = Some(text);
+                rest = remaining;
+            }
+
+            provenance = Some(Provenance {
+                version,
+                created_at,
+                annotation,
+            });
+        }
+
+        let mut hmac = None;
+        if flags & FLAG_HMAC != 0 {
+            let (tag, remaining) = rest.split_first_chunk::<HMAC_LEN>()?;
+            hmac = Some(*tag);
+            rest = remaining;
+        }
+
+        Some(Self {
+            expires_at,
+            provenance,
+            compressed: flags & FLAG_COMPRESSED != 0,
+            encrypted: flags & FLAG_ENCRYPTED != 0,
+            hmac,
+            message: rest.to_vec(),
+        })
+    }
+
+    /// A message expires at the start of its expiry second
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// The first `length` bytes of `bytes` as UTF-8, and the remaining bytes
+fn split_text(bytes: &[u8], length: usize) -> Option<(String, &[u8])> {
+    if bytes.len() < length {
+        return None;
+    }
+    let (text, rest) = bytes.split_at(length);
+
+    Some((String::from_utf8(text.to_vec()).ok()?, rest))
+}
+
+/// `text` cut to at most `max` bytes, on a character boundary
+fn truncated(text: &str, max: usize) -> &str {
+    let mut end = text.len().min(max);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &text[..end]
+}
+
+/// What `decode` may show of a payload
+#[derive(Debug, PartialEq, Eq)]
+pub enum Opened<'a> {
+    /// Not an envelope, shown as is
+    Plain(&'a [u8]),
+    /// The message of a live envelope, or of an expired one when expiry is
+    /// ignored, revealed (see [`Envelope::reveal`])
+    Message(Envelope),
+    /// The message expired and must not be shown
+    Expired { expires_at: u64 },
+}
+
+/// Unwraps `payload`, hiding expired messages unless `ignore_expiry` is set.
+/// Encrypted messages need `passphrase`, compressed ones may inflate to
+/// `limit` bytes.
+pub fn open<'a>(
+    payload: &'a [u8],
+    clock: &dyn Clock,
+    ignore_expiry: bool,
+    passphrase: Option<&str>,
+    limit: u64,
+) -> Result<Opened<'a>, OpenError> {
+    Ok(match Envelope::parse(payload) {
+        None => Opened::Plain(payload),
+        Some(envelope) if envelope.is_expired(clock.now()) && !ignore_expiry => Opened::Expired {
+            expires_at: envelope
+                .expires_at
+                .expect("expired envelopes have an expiry"),
+        },
+        Some(envelope) => Opened::Message(envelope.reveal(passphrase, limit)?),
+    })
+}
+
+/// Peels the envelopes nested in `payload`, as left by encoding a payload
+/// that already was one: while the revealed message of an envelope is itself
+/// an envelope, that inner envelope replaces it. An expired layer stays
+/// closed unless `ignore_expiry` is set, for [`open`] to hide it. Returns
+/// the innermost payload and the number of layers peeled.
+pub fn peel(
+    payload: &[u8],
+    clock: &dyn Clock,
+    ignore_expiry: bool,
+    passphrase: Option<&str>,
+    limit: u64,
+) -> Result<(Vec<u8>, usize), OpenError> {
+    let mut payload = payload.to_vec();
+    let mut layers = 0;
+    while let Opened::Message(envelope) = open(&payload, clock, ignore_expiry, passphrase, limit)?
+        && Envelope::parse(&envelope.message).is_some()
+    {
+        payload = envelope.message;
+        layers += 1;
+    }
+
+    Ok((payload, layers))
+}
+
+/// Human readable expiry of a payload, if it is an envelope with one
+pub fn describe_expiry(payload: &[u8], now: u64) -> Option<String> {
+    let envelope = Envelope::parse(payload)?;
+    let expires_at = format_timestamp(envelope.expires_at?);
+
+    Some(if envelope.is_expired(now) {
+        format!("expired on {expires_at}")
+    } else {
+        format!("expires on {expires_at}")
+    })
+}
+
+/// Who wrote a payload, if it is an envelope recording it
+pub fn describe_provenance(payload: &[u8]) -> Option<String> {
+    Some(Envelope::parse(payload)?.provenance?.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::FixedClock, inflate::DEFAULT_MAX_DECOMPRESSED_SIZE};
+
+    const EXPIRES_AT: u64 = 1_735_689_600;
+
+    fn expiring(message: &[u8]) -> Vec<u8> {
+        Envelope {
+            expires_at: Some(EXPIRES_AT),
+            ..Envelope::new(message.to_vec())
+        }
+        .to_bytes()
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        for expires_at in [None, Some(EXPIRES_AT)] {
+            let envelope = Envelope {
+                expires_at,
+                ..Envelope::new(b"hello".to_vec())
+            };
+
+            assert_eq!(Envelope::parse(&envelope.to_bytes()), Some(envelope));
+        }
+    }
+
+    #[test]
+    fn test_provenance_round_trip() {
+        for (created_at, annotation) in [
+            (None, None),
+            (Some(EXPIRES_AT), None),
+            (None, Some("ops/rotation".to_string())),
+            (Some(EXPIRES_AT), Some("ops/rotation".to_string())),
+        ] {
+            let envelope = Envelope {
+                expires_at: Some(EXPIRES_AT),
+                provenance: Some(Provenance::new(created_at, annotation)),
+                ..Envelope::new(b"hello".to_vec())
+            };
+
+            assert_eq!(Envelope::parse(&envelope.to_bytes()), Some(envelope));
+        }
+    }
+
+    #[test]
+    fn test_provenance_layout() {
+        let envelope = Envelope {
+            provenance: Some(Provenance {
+                version: "1.2".to_string(),
+                created_at: None,
+                annotation: Some("me".to_string()),
+            }),
+            ..Envelope::new(b"hi".to_vec())
+        };
+
+        assert_eq!(envelope.to_bytes(), b"PNGME\x01\x0a\x031.2\x00\x02mehi");
+    }
+
+    #[test]
+    fn test_describe_provenance() {
+        let payload = Envelope {
+            provenance: Some(Provenance {
+                version: "1.2".to_string(),
+                created_at: Some(EXPIRES_AT),
+                annotation: Some("alice/audit".to_string()),
+            }),
+            ..Envelope::new(b"hi".to_vec())
+        }
+        .to_bytes();
+
+        assert_eq!(
+            describe_provenance(&payload).as_deref(),
+            Some("written by pngme 1.2 on 2025-01-01T00:00:00Z: alice/audit")
+        );
+        assert_eq!(describe_provenance(&expiring(b"hi")), None);
+    }
+
+    #[test]
+    fn test_envelope_layout() {
+        let mut expected = b"PNGME\x01\x01".to_vec();
+        expected.extend_from_slice(&EXPIRES_AT.to_be_bytes());
+        expected.extend_from_slice(b"hi");
+
+        assert_eq!(expiring(b"hi"), expected);
+    }
+
+    #[test]
+    fn test_plain_payloads_are_not_envelopes() {
+        assert_eq!(Envelope::parse(b"hello"), None);
+        assert_eq!(Envelope::parse(b"PNGME"), None);
+        // Unknown version and flags
+        assert_eq!(Envelope::parse(b"PNGME\x02\x00hi"), None);
+        assert_eq!(Envelope::parse(b"PNGME\x01\x80hi"), None);
+        // Truncated expiry
+        assert_eq!(Envelope::parse(b"PNGME\x01\x01\x00\x00"), None);
+        // Annotation without provenance, truncated version
+        assert_eq!(Envelope::parse(b"PNGME\x01\x08\x00\x00hi"), None);
+        assert_eq!(Envelope::parse(b"PNGME\x01\x02\x05ab"), None);
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let message = b"hello hello hello hello hello hello".to_vec();
+        let envelope = Envelope::new(message.clone()).deflate();
+        let bytes = envelope.to_bytes();
+
+        assert_eq!(bytes[MAGIC.len() + 1], FLAG_COMPRESSED);
+        assert!(envelope.message.len() < message.len());
+        let parsed = Envelope::parse(&bytes).unwrap();
+        assert_eq!(parsed, envelope);
+        assert_eq!(
+            parsed
+                .inflate(DEFAULT_MAX_DECOMPRESSED_SIZE)
+                .unwrap()
+                .message,
+            message
+        );
+    }
+
+    #[test]
+    fn test_open_corrupted_compressed_message() {
+        let mut payload = Envelope::new(b"hello".to_vec()).deflate().to_bytes();
+        let last = payload.len() - 1;
+        payload[last] ^= 0xFF;
+
+        assert!(
+            open(
+                &payload,
+                &FixedClock(0),
+                false,
+                None,
+                DEFAULT_MAX_DECOMPRESSED_SIZE
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let payload = Envelope::new(b"hello hello hello hello".to_vec())
+            .deflate()
+            .encrypt("correct horse")
+            .to_bytes();
+        let clock = FixedClock(0);
+
+        assert!(matches!(
+            open(&payload, &clock, false, None, DEFAULT_MAX_DECOMPRESSED_SIZE),
+            Err(OpenError::Encrypted)
+        ));
+        assert!(matches!(
+            open(
+                &payload,
+                &clock,
+                false,
+                Some("battery staple"),
+                DEFAULT_MAX_DECOMPRESSED_SIZE
+            ),
+            Err(OpenError::Crypto(CryptoError::DecryptionFailed))
+        ));
+        let opened = open(
+            &payload,
+            &clock,
+            false,
+            Some("correct horse"),
+            DEFAULT_MAX_DECOMPRESSED_SIZE,
+        )
+        .unwrap();
+        assert!(
+            matches!(opened, Opened::Message(envelope) if envelope.message == b"hello hello hello hello")
+        );
+    }
+
+    #[test]
+    fn test_encryption_covers_the_header() {
+        let envelope = Envelope {
+            expires_at: Some(EXPIRES_AT),
+            ..Envelope::new(b"hello".to_vec())
+        }
+        .encrypt("correct horse");
+        let bytes = envelope.to_bytes();
+
+        // A later expiry can't be passed off with the same ciphertext
+        let mut altered = bytes.clone();
+        altered[MAGIC.len() + 2 + 7] ^= 1;
+        let parsed = Envelope::parse(&altered).unwrap();
+        assert_eq!(
+            parsed.decrypt("correct horse"),
+            Err(CryptoError::DecryptionFailed)
+        );
+
+        // Signed after encryption, the tag isn't part of it
+        let signed = Envelope::parse(&envelope.sign(b"key").to_bytes()).unwrap();
+        assert_eq!(signed.decrypt("correct horse").unwrap().message, b"hello");
+    }
+
+    #[test]
+    fn test_signed_round_trip() {
+        let envelope = Envelope {
+            expires_at: Some(EXPIRES_AT),
+            ..Envelope::new(b"hello".to_vec())
+        }
+        .sign(b"key");
+        let bytes = envelope.to_bytes();
+
+        assert_eq!(bytes[MAGIC.len() + 1], FLAG_EXPIRES | FLAG_HMAC);
+        assert_eq!(bytes.len(), expiring(b"hello").len() + HMAC_LEN);
+        let parsed = Envelope::parse(&bytes).unwrap();
+        assert_eq!(parsed, envelope);
+        assert_eq!(parsed.verify(b"key"), Ok(()));
+        assert_eq!(parsed.verify(b"other key"), Err(MacError::Mismatch));
+        assert_eq!(
+            Envelope::parse(&expiring(b"hello")).unwrap().verify(b"key"),
+            Err(MacError::Missing)
+        );
+    }
+
+    #[test]
+    fn test_signature_covers_the_header() {
+        let bytes = Envelope {
+            expires_at: Some(EXPIRES_AT),
+            ..Envelope::new(b"hello".to_vec())
+        }
+        .sign(b"key")
+        .to_bytes();
+
+        // A later expiry, and another message
+        for index in [MAGIC.len() + 2 + 7, bytes.len() - 1] {
+            let mut altered = bytes.clone();
+            altered[index] ^= 1;
+            let parsed = Envelope::parse(&altered).unwrap();
+            assert_eq!(parsed.verify(b"key"), Err(MacError::Mismatch), "{index}");
+        }
+
+        // A truncated tag is not an envelope
+        assert_eq!(Envelope::parse(&bytes[..MAGIC.len() + 2 + 8 + 10]), None);
+    }
+
+    #[test]
+    fn test_open_not_yet_expired() {
+        let payload = expiring(b"secret");
+
+        let opened = open(
+            &payload,
+            &FixedClock(EXPIRES_AT - 1),
+            false,
+            None,
+            DEFAULT_MAX_DECOMPRESSED_SIZE,
+        )
+        .unwrap();
+
+        assert!(matches!(opened, Opened::Message(envelope) if envelope.message == b"secret"));
+    }
+
+    #[test]
+    fn test_open_just_expired() {
+        let payload = expiring(b"secret");
+
+        let opened = open(
+            &payload,
+            &FixedClock(EXPIRES_AT),
+            false,
+            None,
+            DEFAULT_MAX_DECOMPRESSED_SIZE,
+        )
+        .unwrap();
+
+        assert_eq!(
+            opened,
+            Opened::Expired {
+                expires_at: EXPIRES_AT
+            }
+        );
+    }
+
+    #[test]
+    fn test_open_ignore_expiry() {
+        let payload = expiring(b"secret");
+
+        let opened = open(
+            &payload,
+            &FixedClock(EXPIRES_AT + 3600),
+            true,
+            None,
+            DEFAULT_MAX_DECOMPRESSED_SIZE,
+        )
+        .unwrap();
+
+        assert!(matches!(opened, Opened::Message(envelope) if envelope.message == b"secret"));
+    }
+
+    #[test]
+    fn test_open_plain_payload() {
+        assert_eq!(
+            open(
+                b"secret",
+                &FixedClock(0),
+                false,
+                None,
+                DEFAULT_MAX_DECOMPRESSED_SIZE
+            )
+            .unwrap(),
+            Opened::Plain(b"secret")
+        );
+    }
+
+    #[test]
+    fn test_peel_nested_envelopes() {
+        let clock = FixedClock(0);
+        let limit = DEFAULT_MAX_DECOMPRESSED_SIZE;
+        let inner = Envelope::new(b"secret".to_vec()).deflate().to_bytes();
+        let twice = Envelope::new(inner.clone()).to_bytes();
+        let thrice = Envelope::new(twice.clone()).deflate().to_bytes();
+
+        assert_eq!(
+            peel(&twice, &clock, false, None, limit).unwrap(),
+            (inner.clone(), 1)
+        );
+        assert_eq!(
+            peel(&thrice, &clock, false, None, limit).unwrap(),
+            (inner.clone(), 2)
+        );
+        assert_eq!(
+            peel(&inner, &clock, false, None, limit).unwrap(),
+            (inner, 0)
+        );
+        assert_eq!(
+            peel(b"secret", &clock, false, None, limit).unwrap(),
+            (b"secret".to_vec(), 0)
+        );
+    }
+
+    #[test]
+    fn test_peel_stops_at_an_expired_layer() {
+        let nested = Envelope {
+            expires_at: Some(EXPIRES_AT),
+            ..Envelope::new(Envelope::new(b"secret".to_vec()).to_bytes())
+        }
+        .to_bytes();
+        let limit = DEFAULT_MAX_DECOMPRESSED_SIZE;
+
+        let (payload, layers) = peel(&nested, &FixedClock(EXPIRES_AT), false, None, limit).unwrap();
+        assert_eq!((payload, layers), (nested.clone(), 0));
+        let (_, layers) = peel(&nested, &FixedClock(EXPIRES_AT), true, None, limit).unwrap();
+        assert_eq!(layers, 1);
+    }
+
+    #[test]
+    fn test_describe_expiry() {
+        let payload = expiring(b"secret");
+
+        assert_eq!(
+            describe_expiry(&payload, EXPIRES_AT - 1).as_deref(),
+            Some("expires on 2025-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            describe_expiry(&payload, EXPIRES_AT).as_deref(),
+            Some("expired on 2025-01-01T00:00:00Z")
+        );
+        assert_eq!(describe_expiry(b"secret", 0), None);
+    }
+}
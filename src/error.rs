@@ -1,7 +1,7 @@
-use std::io;
+use std::{io, path::PathBuf};
 use thiserror::Error;
 
-use crate::{chunk_type::ChunkTypeError, png::PngError};
+use crate::{cache::CacheError, canonical::CanonicalError, chunk_type::{ChunkNameError, ChunkTypeError}, codes::Code, envelope::OpenError, format::FormatError, glob::GlobError, icc::IccError, inflate::InflateError, interlace::InterlaceError, input::InputError, journal::JournalError, lock::LockError, mac::MacError, meta::MetaError, patch::PatchError, png::PngError, secret::SecretError, signing::SignatureError, split::SplitError, template::TemplateError, text::TextError, undo::UndoError};
 
 
 #[derive(Error, Debug)]
@@ -14,4 +14,200 @@ pub enum PngMeError {
 
     #[error(transparent)]
     ChunkType(#[from] ChunkTypeError),
-}
\ No newline at end of file
+
+    #[error(transparent)]
+    ChunkName(#[from] ChunkNameError),
+
+    #[error("Refusing to write a {chunk_type} chunk (pass --assume-yes to proceed)")]
+    NotConfirmed { chunk_type: String },
+
+    #[error(
+        "Refusing to write the critical chunk {chunk_type}, decoders that don't know it reject the image; \
+         use an ancillary name such as {suggestion} (pass --allow-unsafe-type to write it anyway)"
+    )]
+    CriticalChunkType { chunk_type: String, suggestion: String },
+
+    #[error(
+        "Refusing to write a {chunk_type} chunk, its reserved bit is set; did you mean {suggestion}? \
+         (pass --allow-unsafe-type to write it anyway)"
+    )]
+    ReservedBitChunkType { chunk_type: String, suggestion: String },
+
+    #[error("Refusing to embed an empty message (pass --allow-empty to proceed)")]
+    EmptyMessage,
+
+    #[error("Payloads differ ({distinct} distinct values, {missing} files without the chunk)")]
+    PayloadMismatch { distinct: usize, missing: usize },
+
+    #[error(transparent)]
+    Icc(#[from] IccError),
+
+    #[error(transparent)]
+    Text(#[from] TextError),
+
+    #[error(transparent)]
+    Inflate(#[from] InflateError),
+
+    #[error(transparent)]
+    Meta(#[from] MetaError),
+
+    #[error(transparent)]
+    Template(#[from] TemplateError),
+
+    #[error(transparent)]
+    Patch(#[from] PatchError),
+
+    #[error("The image already has a {chunk_type} chunk (pass --replace to overwrite it)")]
+    ColorProfileConflict { chunk_type: String },
+
+    #[error("Destination is not writable: {} ({reason}), pass --output to write elsewhere", path.display())]
+    NotWritable { path: PathBuf, reason: &'static str },
+
+    #[error(
+        "{} doesn't end in .png or .apng (drop --strict-extension to write it anyway)",
+        path.display()
+    )]
+    OutputExtension { path: PathBuf },
+
+    #[error("{input} is inside an archive, which pngme never modifies (pass --output to write the image elsewhere)")]
+    ArchiveMemberOutput { input: String },
+
+    #[error(transparent)]
+    Open(#[from] OpenError),
+
+    #[error(transparent)]
+    Mac(#[from] MacError),
+
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+
+    #[error(transparent)]
+    Split(#[from] SplitError),
+
+    #[error(transparent)]
+    Canonical(#[from] CanonicalError),
+
+    #[error("No space left on the device to write {}", path.display())]
+    StorageFull { path: PathBuf },
+
+    #[error(
+        "The {chunk_type} message doesn't decode back from {}: {reason}; {}",
+        path.display(),
+        if *kept { "the output was kept" } else { "nothing was written" }
+    )]
+    RoundTripFailed { path: PathBuf, chunk_type: String, reason: String, kept: bool },
+
+    #[error(
+        "The image would be {projected_size} bytes, over the maximum size of {}",
+        crate::upload_limits::SizeThreshold::custom(*max_size)
+    )]
+    CapacityExceeded { projected_size: u64, max_size: u64 },
+
+    #[error("{failed} of {total} file(s) failed")]
+    BatchFailed { failed: usize, total: usize },
+
+    #[error("The backup {} already exists, pass --force to overwrite it", path.display())]
+    BackupExists { path: PathBuf },
+
+    #[error("Could not write the backup {}: {source}", path.display())]
+    BackupFailed { path: PathBuf, source: std::io::Error },
+
+    #[error(transparent)]
+    Input(#[from] InputError),
+
+    #[error(transparent)]
+    Glob(#[from] GlobError),
+
+    #[error(transparent)]
+    Format(#[from] FormatError),
+
+    #[error(transparent)]
+    Lock(#[from] LockError),
+
+    #[error(transparent)]
+    Journal(#[from] JournalError),
+
+    #[error(transparent)]
+    Undo(#[from] UndoError),
+
+    #[error(transparent)]
+    Cache(#[from] CacheError),
+
+    #[error(transparent)]
+    Secret(#[from] SecretError),
+
+    #[error(transparent)]
+    Interlace(#[from] InterlaceError),
+
+    #[error(
+        "The output would hold the unknown critical chunk {chunk_type}, which decoders reject \
+         (pass --allow-unknown-critical to write it anyway)"
+    )]
+    UnknownCritical { chunk_type: String },
+
+    #[error("Found {count} problem(s)")]
+    VerifyFailed { count: usize },
+
+    #[error("No error or warning has the code {code}, `pngme explain` lists them all")]
+    UnknownCode { code: String },
+
+    #[cfg(feature = "server")]
+    #[error(transparent)]
+    Server(#[from] crate::server::ServerError),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl PngMeError {
+    /// Stable code of the error, see [`crate::codes`]
+    pub fn code(&self) -> Code {
+        match self {
+            PngMeError::File(_) => Code::IoFailed,
+            PngMeError::Png(err) => err.code(),
+            PngMeError::ChunkType(err) => err.code(),
+            PngMeError::ChunkName(err) => err.code(),
+            PngMeError::NotConfirmed { .. } => Code::NotConfirmed,
+            PngMeError::CriticalChunkType { .. } => Code::CriticalChunkType,
+            PngMeError::ReservedBitChunkType { .. } => Code::ReservedBitChunkType,
+            PngMeError::EmptyMessage => Code::EmptyMessage,
+            PngMeError::PayloadMismatch { .. } => Code::PayloadMismatch,
+            PngMeError::Icc(err) => err.code(),
+            PngMeError::Text(err) => err.code(),
+            PngMeError::Inflate(err) => err.code(),
+            PngMeError::Meta(err) => err.code(),
+            PngMeError::Template(err) => err.code(),
+            PngMeError::Patch(err) => err.code(),
+            PngMeError::ColorProfileConflict { .. } => Code::ColorProfileConflict,
+            PngMeError::NotWritable { .. } => Code::NotWritable,
+            PngMeError::OutputExtension { .. } => Code::OutputExtension,
+            PngMeError::ArchiveMemberOutput { .. } => Code::ArchiveMemberOutput,
+            PngMeError::Open(err) => err.code(),
+            PngMeError::Mac(err) => err.code(),
+            PngMeError::Signature(err) => err.code(),
+            PngMeError::Split(err) => err.code(),
+            PngMeError::Canonical(err) => err.code(),
+            PngMeError::StorageFull { .. } => Code::StorageFull,
+            PngMeError::RoundTripFailed { .. } => Code::RoundTripFailed,
+            PngMeError::CapacityExceeded { .. } => Code::CapacityExceeded,
+            PngMeError::BatchFailed { .. } => Code::BatchFailed,
+            PngMeError::BackupExists { .. } => Code::BackupExists,
+            PngMeError::BackupFailed { .. } => Code::BackupFailed,
+            PngMeError::Input(err) => err.code(),
+            PngMeError::Glob(err) => err.code(),
+            PngMeError::Format(err) => err.code(),
+            PngMeError::Lock(err) => err.code(),
+            PngMeError::Journal(err) => err.code(),
+            PngMeError::Undo(err) => err.code(),
+            PngMeError::Cache(err) => err.code(),
+            PngMeError::Secret(err) => err.code(),
+            PngMeError::Interlace(err) => err.code(),
+            PngMeError::UnknownCritical { .. } => Code::UnknownCriticalOutput,
+            PngMeError::VerifyFailed { .. } => Code::VerifyFailed,
+            PngMeError::UnknownCode { .. } => Code::UnknownCode,
+            #[cfg(feature = "server")]
+            PngMeError::Server(err) => err.code(),
+            PngMeError::Json(_) => Code::JsonFailed,
+        }
+    }
+}
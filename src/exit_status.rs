@@ -0,0 +1,274 @@
+//! The exit statuses of pngme, a contract for scripts and wrapper tools.
+//!
+//! Every error [`Code`] belongs to one [`ExitStatus`], given by
+//! [`ExitStatus::of`], so a script can tell a missing chunk from a corrupted
+//! file without parsing messages. The statuses never change meaning, and
+//! `pngme capabilities` lists them.
+//!
+//! Two statuses come from outside the table: an external command exits with
+//! the status of the program it runs, and an interrupted pngme exits with
+//! 130 like a shell does. A closed standard output is not an error, pngme
+//! then exits with 0.
+
+use std::fmt::{self, Display};
+
+use crate::codes::Code;
+
+/// Exit status of pngme, by class of outcome
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExitStatus {
+    Success = 0,
+    /// Input or output failed, or a limit was reached
+    OperationalError = 1,
+    /// The command line is wrong, as clap reports it
+    UsageError = 2,
+    /// The file, chunk, member or passphrase asked for doesn't exist
+    NotFound = 3,
+    /// The file is not a valid PNG, or not the one expected
+    ValidationFailed = 4,
+    /// A checksum, a passphrase or a compressed payload doesn't check out
+    IntegrityFailed = 5,
+    /// An output could not be written for lack of space
+    StorageFull = 6,
+}
+
+impl ExitStatus {
+    /// Every status, in numeric order
+    pub const ALL: &[ExitStatus] = &[
+        ExitStatus::Success,
+        ExitStatus::OperationalError,
+        ExitStatus::UsageError,
+        ExitStatus::NotFound,
+        ExitStatus::ValidationFailed,
+        ExitStatus::IntegrityFailed,
+        ExitStatus::StorageFull,
+    ];
+
+    /// Status of a command failing with `code`.
+    ///
+    /// The match lists every code so that a new one can't be added without
+    /// deciding its status. Warnings only fail a command through `verify`.
+    pub fn of(code: Code) -> Self {
+        use Code::*;
+
+        match code {
+            NotAsciiLetters
+            | InvalidTypeLength
+            | InvalidNameLength
+            | InvalidNameCharacter
+            | ChunkSpecSeparator
+            | ChunkSpecOddHex
+            | ChunkSpecHexDigit
+            | ChunkSpecUnescapedQuote
+            | ChunkSpecUnterminated
+            | ChunkSpecEscape
+            | SelectionEmptyTerm
+            | SelectionUnknownTerm
+            | EmptyMessage
+            | InvalidSplitSize
+            | CriticalChunkType
+            | ReservedBitChunkType
+            | OutputExtension
+            | ArchiveMemberOutput
+            | MessageEncrypted
+            | InvalidDataUri
+            | InvalidFileUrl
+            | EmptyKeyword
+            | KeywordTooLong
+            | KeywordSpaces
+            | KeywordInvalidCharacter
+            | NotLatin1
+            | InvalidLanguageTag
+            | TranslatedKeywordNul
+            | InvalidHex
+            | InvalidBase64
+            | InvalidBase32Character
+            | InvalidBase32Length
+            | InvalidBase45Character
+            | InvalidBase45Length
+            | Base45Overflow
+            | TemplateUnclosed
+            | TemplateUnknown
+            | TemplateNotDeterministic
+            | EmptySecret
+            | ConflictingArguments
+            | BackupExists
+            | InvalidSize
+            | InvalidArguments
+            | UnknownCommand
+            | InvalidCommandName
+            | GlobUnclosedClass
+            | UnknownCode
+            | JournalMismatch
+            | CriticalChunkEdit
+            | RequestMalformed
+            | RequestUnauthorized
+            | MethodNotAllowed
+            | MissingParameter
+            | LengthRequired => ExitStatus::UsageError,
+
+            ChunkNotFound
+            | IndexOutOfBounds
+            | MissingAnchor
+            | ArchiveMemberNotFound
+            | SecretNotFound
+            | NothingToUndo
+            | SignatureMissing
+            | MissingArgumentFile
+            | GlobNoMatch
+            | MessageExpired
+            | NoEndpoint => ExitStatus::NotFound,
+
+            BadSignature
+            | EmptyFile
+            | NoChunks
+            | TruncatedFile
+            | IncompleteChunk
+            | InvalidLengthField
+            | ChunkDataTooLarge
+            | PayloadMismatch
+            | ColorProfileConflict
+            | UnknownCriticalOutput
+            | VerifyFailed
+            | IccMissingSeparator
+            | IccMissingCompression
+            | IccUnsupportedCompression
+            | IccInvalidZlib
+            | ZtxtMissingSeparator
+            | ZtxtMissingCompression
+            | ZtxtUnsupportedCompression
+            | NotUtf8
+            | InvalidJson
+            | UnsupportedSidecarVersion
+            | MetaConflict
+            | InvalidSigningKey
+            | UnsupportedPatchVersion
+            | InvalidPatch
+            | PatchMismatch
+            | ForbiddenSidecarChunk
+            | MissingHeader
+            | ImageDecodeFailed
+            | AnimatedImage
+            | ChunkAfterIend
+            | MissingIend
+            | TrailingData
+            | UnknownCriticalChunk
+            | ReservedBitSet
+            | ImageDataSize
+            | AnimationSequence => ExitStatus::ValidationFailed,
+
+            ChecksumMismatch | CorruptPayload | DecryptionFailed | MissingPiece | SplitMismatch
+            | InvalidUndoManifest | UndoCorrupted | InvalidJournal | CrcMismatch | HmacMissing | HmacMismatch
+            | SignatureMismatch | RoundTripFailed => ExitStatus::IntegrityFailed,
+
+            StorageFull => ExitStatus::StorageFull,
+
+            TooManyChunks
+            | ChunkTooLarge
+            | FileTooLarge
+            | ReadFailed
+            | ChunkReadFailed
+            | IoFailed
+            | NotConfirmed
+            | NotWritable
+            | JsonFailed
+            | DecompressedTooLarge
+            | CapacityExceeded
+            | BatchFailed
+            | InputReadFailed
+            | InputTooLarge
+            | DownloadFailed
+            | DownloadStatus
+            | DownloadReadFailed
+            | DownloadTooLarge
+            | DownloadIncomplete
+            | ArchiveReadFailed
+            | ArchiveEncrypted
+            | CacheUnavailable
+            | CacheIoFailed
+            | DownloadStoreFailed
+            | TemplateHashFailed
+            | HostnameUnavailable
+            | LockFailed
+            | FileLocked
+            | UndoIoFailed
+            | JournalIoFailed
+            | BackupFailed
+            | ServerBindFailed
+            | RequestTimeout
+            | RequestTooLarge
+            | SecretReadFailed
+            | KeychainUnavailable
+            | ImageEncodeFailed
+            | ExternalCommandFailed
+            | UploadLimit
+            | EmptySelection
+            | OutputNotPng
+            | PipelineConstraint
+            | CriticalChunkWritten
+            | WhitespaceMessage
+            | MessageAlreadyExpired
+            | NestedEnvelope
+            | Latin1Substituted
+            | DuplicateChunk
+            | RenameConflict => ExitStatus::OperationalError,
+        }
+    }
+
+    /// The number the process exits with
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// Name of the status, e.g. `not-found`
+    pub fn name(self) -> &'static str {
+        match self {
+            ExitStatus::Success => "success",
+            ExitStatus::OperationalError => "operational-error",
+            ExitStatus::UsageError => "usage-error",
+            ExitStatus::NotFound => "not-found",
+            ExitStatus::ValidationFailed => "validation-failed",
+            ExitStatus::IntegrityFailed => "integrity-failed",
+            ExitStatus::StorageFull => "storage-full",
+        }
+    }
+}
+
+impl Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code(), self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statuses_are_stable() {
+        // Changing these breaks the scripts checking them
+        let codes: Vec<i32> = ExitStatus::ALL.iter().map(|status| status.code()).collect();
+        assert_eq!(codes, [0, 1, 2, 3, 4, 5, 6]);
+
+        assert_eq!(ExitStatus::of(Code::ChunkNotFound), ExitStatus::NotFound);
+        assert_eq!(
+            ExitStatus::of(Code::VerifyFailed),
+            ExitStatus::ValidationFailed
+        );
+        assert_eq!(
+            ExitStatus::of(Code::DecryptionFailed),
+            ExitStatus::IntegrityFailed
+        );
+        assert_eq!(
+            ExitStatus::of(Code::ConflictingArguments),
+            ExitStatus::UsageError
+        );
+    }
+
+    #[test]
+    fn test_no_error_exits_with_success() {
+        for &code in Code::ALL {
+            assert_ne!(ExitStatus::of(code), ExitStatus::Success, "{code}");
+        }
+    }
+}
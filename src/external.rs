@@ -0,0 +1,128 @@
+//! External commands, like git and cargo have.
+//!
+//! `pngme foo args...` runs `pngme-foo args...` from the PATH when `foo`
+//! isn't a built-in command, so teams can ship their own commands without
+//! forking pngme. The exit status of the program becomes the one of pngme.
+
+use std::{ffi::OsString, io, process::Command};
+
+use clap::CommandFactory;
+use thiserror::Error;
+
+use crate::{args::Arguments, codes::Code};
+
+/// Prefix of the programs run as external commands
+pub const PREFIX: &str = "pngme-";
+
+#[derive(Error, Debug)]
+pub enum ExternalError {
+    #[error("'{name}' holds a path separator, external commands are only looked up on the PATH")]
+    InvalidName { name: String },
+
+    #[error(
+        "'{name}' is not a pngme command{}. The commands are: {}",
+        if *searched { format!(" and no {PREFIX}{name} program is on the PATH") } else { String::new() },
+        builtins.join(", ")
+    )]
+    NotFound {
+        name: String,
+        /// Whether the PATH was searched, it isn't with `--no-external`
+        searched: bool,
+        builtins: Vec<String>,
+    },
+
+    #[error("Could not run {program}: {source}")]
+    Spawn { program: String, source: io::Error },
+}
+
+impl ExternalError {
+    /// Stable code of the error, see [`crate::codes`]
+    pub fn code(&self) -> Code {
+        match self {
+            ExternalError::InvalidName { .. } => Code::InvalidCommandName,
+            ExternalError::NotFound { .. } => Code::UnknownCommand,
+            ExternalError::Spawn { .. } => Code::ExternalCommandFailed,
+        }
+    }
+}
+
+/// Names of the built-in commands, hidden ones left out
+pub fn builtins() -> Vec<String> {
+    Arguments::command()
+        .get_subcommands()
+        .filter(|command| !command.is_hide_set())
+        .map(|command| command.get_name().to_string())
+        .collect()
+}
+
+/// Runs the external command of `args`, its name followed by its
+/// arguments, and returns its exit status. `allow` is false with
+/// `--no-external`, every name is then unknown.
+pub fn run(args: &[OsString], allow: bool) -> Result<i32, ExternalError> {
+    let (name, args) = args.split_first().expect("clap passes the command name");
+    let name = name.to_string_lossy().into_owned();
+
+    if name.contains(['/', '\\']) {
+        return Err(ExternalError::InvalidName { name });
+    }
+    if !allow {
+        return Err(ExternalError::NotFound {
+            name,
+            searched: false,
+            builtins: builtins(),
+        });
+    }
+
+    let program = format!("{PREFIX}{name}");
+    let status = match Command::new(&program).args(args).status() {
+        Ok(status) => status,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(ExternalError::NotFound {
+                name,
+                searched: true,
+                builtins: builtins(),
+            });
+        }
+        Err(source) => return Err(ExternalError::Spawn { program, source }),
+    };
+
+    Ok(exit_code(status))
+}
+
+/// Exit code of a program, 128 plus the signal for one killed by a signal
+/// as shells report it
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+
+    status.code().unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_separators_are_refused() {
+        for name in ["../evil", "bin/tool", "dir\\tool"] {
+            let err = run(&[name.into()], true).unwrap_err();
+            assert!(matches!(err, ExternalError::InvalidName { .. }), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_no_external_lists_the_builtins() {
+        let err = run(&["tool".into()], false).unwrap_err();
+        let message = err.to_string();
+
+        assert_eq!(err.code(), Code::UnknownCommand);
+        assert!(
+            message.starts_with("'tool' is not a pngme command. "),
+            "{message}"
+        );
+        assert!(message.contains("encode, decode"), "{message}");
+        assert!(!message.contains("debug"), "{message}");
+    }
+}
@@ -0,0 +1,349 @@
+//! Programmatically built PNG files exhibiting specific defects, used by the
+//! test suites and available through `pngme debug make-fixture`.
+
+use std::io::Write;
+
+use clap::ValueEnum;
+use flate2::{Compression, write::ZlibEncoder};
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    color::ColorFormat,
+    consts::{CHUNK_OVERHEAD, DATA_OFFSET, LENGTH_FIELD, SIGNATURE_LEN},
+    png::Png,
+};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FixtureKind {
+    /// A valid 1x1 RGBA image
+    Minimal,
+    /// The CRC of the IDAT chunk is wrong
+    CorruptCrc,
+    /// IDAT comes before IHDR
+    OutOfOrder,
+    /// The file stops in the middle of the IDAT chunk
+    Truncated,
+    /// A two-frame animated PNG
+    Apng,
+    /// A ZIP archive is appended after IEND
+    TrailingZip,
+    /// A 5x5 RGBA gradient stored with Adam7 interlacing
+    Interlaced,
+}
+
+impl FixtureKind {
+    pub const ALL: [FixtureKind; 7] = [
+        FixtureKind::Minimal,
+        FixtureKind::CorruptCrc,
+        FixtureKind::OutOfOrder,
+        FixtureKind::Truncated,
+        FixtureKind::Apng,
+        FixtureKind::TrailingZip,
+        FixtureKind::Interlaced,
+    ];
+}
+
+/// Builds a chunk from a type known to be valid
+pub(crate) fn chunk(chunk_type: &[u8; 4], data: Vec<u8>) -> Chunk {
+    let chunk_type = ChunkType::try_from(*chunk_type).expect("fixture chunk types are valid");
+    Chunk::new(chunk_type, data)
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .expect("writing to a Vec can't fail");
+    encoder.finish().expect("writing to a Vec can't fail")
+}
+
+fn ihdr(width: u32, height: u32) -> Chunk {
+    ihdr_with_interlace(width, height, 0)
+}
+
+/// Format of every generated image, the pixels below are 4 bytes
+const FORMAT: ColorFormat = ColorFormat::RGBA8;
+
+fn ihdr_with_interlace(width: u32, height: u32, interlace: u8) -> Chunk {
+    let mut data = Vec::with_capacity(13);
+    data.extend_from_slice(&width.to_be_bytes());
+    data.extend_from_slice(&height.to_be_bytes());
+    data.extend_from_slice(&[FORMAT.bit_depth(), FORMAT.color_type().byte()]);
+    // compression, filter
+    data.extend_from_slice(&[0, 0, interlace]);
+    chunk(b"IHDR", data)
+}
+
+/// `(x start, y start, x step, y step)` of the seven Adam7 passes
+const ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Color of the pixel at `(x, y)` of the interlaced fixture
+fn gradient(x: u32, y: u32) -> [u8; 4] {
+    [(x * 50) as u8, (y * 50) as u8, ((x + y) * 25) as u8, 0xFF]
+}
+
+/// Image data of an Adam7 interlaced RGBA image: the scanlines of each
+/// pass in turn, empty passes being left out
+fn adam7_idat(width: u32, height: u32, pixel: fn(u32, u32) -> [u8; 4]) -> Chunk {
+    let mut raw = Vec::new();
+
+    for (x_start, y_start, x_step, y_step) in ADAM7_PASSES {
+        let columns: Vec<u32> = (x_start..width).step_by(x_step as usize).collect();
+        if columns.is_empty() {
+            continue;
+        }
+
+        for y in (y_start..height).step_by(y_step as usize) {
+            raw.push(0);
+            for &x in &columns {
+                raw.extend_from_slice(&pixel(x, y));
+            }
+        }
+    }
+
+    chunk(b"IDAT", zlib(&raw))
+}
+
+/// Image data of a 1x1 RGBA image: filter byte then one pixel
+fn idat() -> Chunk {
+    chunk(b"IDAT", zlib(&[0, 0xFF, 0x00, 0x00, 0xFF]))
+}
+
+fn iend() -> Chunk {
+    chunk(b"IEND", Vec::new())
+}
+
+/// Frame control chunk of a 1x1 frame
+fn fctl(sequence: u32) -> Chunk {
+    let mut data = Vec::with_capacity(26);
+    data.extend_from_slice(&sequence.to_be_bytes());
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.extend_from_slice(&[0; 8]);
+    // delay 1/10 s, no dispose, source blend
+    data.extend_from_slice(&[0, 1, 0, 10, 0, 0]);
+    chunk(b"fcTL", data)
+}
+
+impl Png {
+    /// A valid 1x1 RGBA image made of IHDR, IDAT and IEND
+    pub fn new_minimal() -> Self {
+        Png::from_chunks(vec![ihdr(1, 1), idat(), iend()])
+    }
+}
+
+/// Offset of the first chunk of the given type in a serialized PNG
+fn chunk_offset(bytes: &[u8], chunk_type: &[u8; 4]) -> usize {
+    let mut offset = SIGNATURE_LEN;
+    loop {
+        let length_field = &bytes[offset..offset + LENGTH_FIELD];
+        let length = u32::from_be_bytes(length_field.try_into().unwrap()) as usize;
+        if &bytes[offset + LENGTH_FIELD..offset + DATA_OFFSET] == chunk_type {
+            return offset;
+        }
+        offset += length + CHUNK_OVERHEAD;
+    }
+}
+
+/// Builds the bytes of a fixture of the given kind
+pub fn make_fixture(kind: FixtureKind) -> Vec<u8> {
+    match kind {
+        FixtureKind::Minimal => Png::new_minimal().as_bytes(),
+        FixtureKind::CorruptCrc => {
+            let mut bytes = Png::new_minimal().as_bytes();
+            let offset = chunk_offset(&bytes, b"IDAT");
+            let length_field = &bytes[offset..offset + LENGTH_FIELD];
+            let length = u32::from_be_bytes(length_field.try_into().unwrap());
+            let crc = offset + DATA_OFFSET + length as usize;
+            bytes[crc] ^= 0xFF;
+            bytes
+        }
+        FixtureKind::OutOfOrder => Png::from_chunks(vec![idat(), ihdr(1, 1), iend()]).as_bytes(),
+        FixtureKind::Truncated => {
+            let mut bytes = Png::new_minimal().as_bytes();
+            let offset = chunk_offset(&bytes, b"IDAT");
+            bytes.truncate(offset + 10);
+            bytes
+        }
+        FixtureKind::Apng => {
+            let mut actl = Vec::with_capacity(8);
+            actl.extend_from_slice(&2u32.to_be_bytes());
+            actl.extend_from_slice(&0u32.to_be_bytes());
+
+            let mut fdat = 2u32.to_be_bytes().to_vec();
+            fdat.extend_from_slice(&zlib(&[0, 0x00, 0xFF, 0x00, 0xFF]));
+
+            Png::from_chunks(vec![
+                ihdr(1, 1),
+                chunk(b"acTL", actl),
+                fctl(0),
+                idat(),
+                fctl(1),
+                chunk(b"fdAT", fdat),
+                iend(),
+            ])
+            .as_bytes()
+        }
+        FixtureKind::TrailingZip => {
+            let mut bytes = Png::new_minimal().as_bytes();
+            // An empty ZIP archive: only the end of central directory record
+            bytes.extend_from_slice(b"PK\x05\x06");
+            bytes.extend_from_slice(&[0; 18]);
+            bytes
+        }
+        FixtureKind::Interlaced => Png::from_chunks(vec![
+            ihdr_with_interlace(5, 5, 1),
+            adam7_idat(5, 5, gradient),
+            iend(),
+        ])
+        .as_bytes(),
+    }
+}
+
+/// A well-formed file of about `size` bytes whose image data is split into
+/// `idat_count` IDAT chunks, for benchmarks. The pixels are not a valid
+/// zlib stream.
+pub fn make_large(size: usize, idat_count: usize) -> Png {
+    let idat_size = size / idat_count.max(1);
+    let mut chunks = vec![ihdr(1, 1)];
+    chunks.extend((0..idat_count).map(|index| chunk(b"IDAT", vec![index as u8; idat_size])));
+    chunks.push(iend());
+
+    Png::from_chunks(chunks)
+}
+
+/// A minimal image followed by `count` small private chunks before IEND,
+/// for benchmarks
+pub fn make_many_chunks(count: usize) -> Png {
+    let mut chunks = vec![ihdr(1, 1), idat()];
+    chunks.extend((0..count).map(|index| chunk(b"ruSt", (index as u32).to_be_bytes().to_vec())));
+    chunks.push(iend());
+
+    Png::from_chunks(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_ref::chunk_refs;
+    use crate::image_data::{self, ImageDataCheck};
+    use crate::png::{ParseOptions, PngError};
+    use crate::{chunk::ChunkParserError, png::PngParserError};
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_minimal_is_valid() {
+        let png = Png::try_from(make_fixture(FixtureKind::Minimal).as_slice()).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "IEND"]);
+        assert_eq!(png, Png::new_minimal());
+    }
+
+    #[test]
+    fn test_image_data_fits_the_format() {
+        for kind in [FixtureKind::Minimal, FixtureKind::Apng] {
+            let bytes = make_fixture(kind);
+            let chunks = chunk_refs(&bytes, true, ParseOptions::DEFAULT_MAX_CHUNKS)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(image_data::check(&chunks), ImageDataCheck::Consistent);
+            assert_eq!(ColorFormat::from_ihdr(chunks[0].data), Some(FORMAT));
+        }
+    }
+
+    #[test]
+    fn test_corrupt_crc_fails_checksum() {
+        let result = Png::try_from(make_fixture(FixtureKind::CorruptCrc).as_slice());
+        assert!(matches!(
+            result,
+            Err(PngError::ParserError(PngParserError::InvalidChunk(
+                ChunkParserError::InvalidChecksum
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_out_of_order_has_idat_first() {
+        let png = Png::try_from(make_fixture(FixtureKind::OutOfOrder).as_slice()).unwrap();
+        assert_eq!(chunk_types(&png), ["IDAT", "IHDR", "IEND"]);
+    }
+
+    #[test]
+    fn test_truncated_fails_reading() {
+        let result = Png::try_from(make_fixture(FixtureKind::Truncated).as_slice());
+        assert!(matches!(
+            result,
+            Err(PngError::ParserError(PngParserError::Truncated { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_apng_has_animation_chunks() {
+        let png = Png::try_from(make_fixture(FixtureKind::Apng).as_slice()).unwrap();
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "IEND"]
+        );
+
+        let sequences: Vec<u32> = png
+            .chunks()
+            .iter()
+            .filter(|chunk| matches!(&chunk.chunk_type().bytes(), b"fcTL" | b"fdAT"))
+            .map(|chunk| u32::from_be_bytes(chunk.data()[..4].try_into().unwrap()))
+            .collect();
+        assert_eq!(sequences, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_interlaced_decodes_to_gradient() {
+        let bytes = make_fixture(FixtureKind::Interlaced);
+        let mut reader = ::png::Decoder::new(std::io::Cursor::new(bytes))
+            .read_info()
+            .unwrap();
+        assert!(reader.info().interlaced);
+
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut pixels).unwrap();
+
+        let expected: Vec<u8> = (0..5)
+            .flat_map(|y| (0..5).flat_map(move |x| gradient(x, y)))
+            .collect();
+        assert_eq!(pixels, expected);
+    }
+
+    #[test]
+    fn test_benchmark_inputs_parse() {
+        let large = make_large(1 << 20, 4).as_bytes();
+        let png = Png::try_from(large.as_slice()).unwrap();
+        assert_eq!(png.chunks_by_type("IDAT").count(), 4);
+        assert!(large.len() >= 1 << 20);
+
+        let many = make_many_chunks(1000).as_bytes();
+        assert_eq!(Png::try_from(many.as_slice()).unwrap().chunks().len(), 1003);
+    }
+
+    #[test]
+    fn test_trailing_zip_follows_iend() {
+        let bytes = make_fixture(FixtureKind::TrailingZip);
+        let minimal = make_fixture(FixtureKind::Minimal);
+
+        assert_eq!(&bytes[..minimal.len()], minimal.as_slice());
+        assert!(bytes[minimal.len()..].starts_with(b"PK\x05\x06"));
+        assert!(Png::try_from(bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,864 @@
+//! Text encodings of binary payloads for `encode --input-encoding` and
+//! `decode --output-encoding`, the clean-ups of text payloads and the
+//! documents `--payload-format` checks.
+
+use std::{
+    borrow::Cow,
+    fmt::{self, Display},
+    io::{self, Write},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use clap::{Args, ValueEnum};
+use thiserror::Error;
+
+use crate::codes::Code;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FormatError {
+    #[error("Invalid hex: {0}")]
+    Hex(String),
+
+    #[error("Invalid base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+
+    #[error("Invalid base32: '{character}' at position {position}")]
+    Base32Character { position: usize, character: char },
+
+    #[error("Invalid base32: {len} characters can't hold whole bytes")]
+    Base32Length { len: usize },
+
+    #[error("Invalid base45: '{character}' at position {position}")]
+    Base45Character { position: usize, character: char },
+
+    #[error("Invalid base45: {len} characters can't hold whole bytes")]
+    Base45Length { len: usize },
+
+    #[error("Invalid base45: '{group}' at position {position} is too large")]
+    Base45Overflow { position: usize, group: String },
+
+    #[error("The payload is not UTF-8, pick another --output-encoding")]
+    NotUtf8,
+
+    #[error("Invalid JSON at byte {offset} (line {line}, column {column}): {reason}")]
+    Json {
+        offset: usize,
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+}
+
+impl FormatError {
+    pub fn code(&self) -> Code {
+        match self {
+            FormatError::Hex(_) => Code::InvalidHex,
+            FormatError::Base64(_) => Code::InvalidBase64,
+            FormatError::Base32Character { .. } => Code::InvalidBase32Character,
+            FormatError::Base32Length { .. } => Code::InvalidBase32Length,
+            FormatError::Base45Character { .. } => Code::InvalidBase45Character,
+            FormatError::Base45Length { .. } => Code::InvalidBase45Length,
+            FormatError::Base45Overflow { .. } => Code::Base45Overflow,
+            FormatError::NotUtf8 => Code::NotUtf8,
+            FormatError::Json { .. } => Code::InvalidJson,
+        }
+    }
+
+    /// The error with its position moved `offset` characters further, for
+    /// text decoded a block at a time
+    fn shifted(self, offset: usize) -> Self {
+        use base64::DecodeError;
+
+        match self {
+            FormatError::Base64(DecodeError::InvalidByte(position, byte)) => {
+                FormatError::Base64(DecodeError::InvalidByte(position + offset, byte))
+            }
+            FormatError::Base64(DecodeError::InvalidLastSymbol(position, byte)) => {
+                FormatError::Base64(DecodeError::InvalidLastSymbol(position + offset, byte))
+            }
+            FormatError::Base64(DecodeError::InvalidLength(len)) => {
+                FormatError::Base64(DecodeError::InvalidLength(len + offset))
+            }
+            FormatError::Base32Character { position, character } => FormatError::Base32Character {
+                position: position + offset,
+                character,
+            },
+            FormatError::Base32Length { len } => FormatError::Base32Length { len: len + offset },
+            FormatError::Base45Character { position, character } => FormatError::Base45Character {
+                position: position + offset,
+                character,
+            },
+            FormatError::Base45Length { len } => FormatError::Base45Length { len: len + offset },
+            FormatError::Base45Overflow { position, group } => FormatError::Base45Overflow {
+                position: position + offset,
+                group,
+            },
+            err => err,
+        }
+    }
+}
+
+/// How a payload is written as text
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8 text, as is
+    #[default]
+    Text,
+    /// Lowercase hexadecimal digits
+    Hex,
+    /// Standard base64 with padding (RFC 4648)
+    Base64,
+    /// Uppercase base32 with padding (RFC 4648)
+    Base32,
+    /// Base45 (RFC 9285), the QR code alphanumeric alphabet
+    Base45,
+}
+
+impl Encoding {
+    pub fn encode(self, data: &[u8]) -> Result<String, FormatError> {
+        Ok(match self {
+            Encoding::Text => String::from_utf8(data.to_vec()).map_err(|_| FormatError::NotUtf8)?,
+            Encoding::Hex => data.iter().map(|byte| format!("{byte:02x}")).collect(),
+            Encoding::Base64 => STANDARD.encode(data),
+            Encoding::Base32 => encode_base32(data),
+            Encoding::Base45 => encode_base45(data),
+        })
+    }
+
+    pub fn decode(self, text: &str) -> Result<Vec<u8>, FormatError> {
+        match self {
+            Encoding::Text => Ok(text.as_bytes().to_vec()),
+            Encoding::Hex => decode_hex(text),
+            Encoding::Base64 => Ok(STANDARD.decode(text.trim())?),
+            Encoding::Base32 => decode_base32(text.trim()),
+            Encoding::Base45 => decode_base45(text),
+        }
+    }
+
+    /// Bytes and characters of the groups the encoding writes, `None` for
+    /// text
+    fn group(self) -> Option<(usize, usize)> {
+        match self {
+            Encoding::Text => None,
+            Encoding::Hex => Some((1, 2)),
+            Encoding::Base64 => Some((3, 4)),
+            Encoding::Base32 => Some((5, 8)),
+            Encoding::Base45 => Some((2, 3)),
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no encoding is skipped");
+        write!(f, "{}", value.get_name())
+    }
+}
+
+/// Bytes encoded, or characters decoded, at a time by [`EncodingWriter`]
+/// and [`StreamDecoder`]
+const BLOCK: usize = 48 * 1024;
+
+/// `text` with a newline after every `width` characters but the last ones,
+/// as is for a width of 0
+pub fn wrap(text: &str, width: usize) -> String {
+    let mut wrapped = String::with_capacity(text.len() + text.len() / width.max(1));
+    let mut column = 0;
+    for character in text.chars() {
+        if column == width && width > 0 {
+            wrapped.push('\n');
+            column = 0;
+        }
+        wrapped.push(character);
+        column += 1;
+    }
+
+    wrapped
+}
+
+/// Writes the encoding of the bytes written to it to `inner` a block at a
+/// time, for payloads too large to encode whole. The text is the one of
+/// [`Encoding::encode`], wrapped like [`wrap`] does with a width.
+pub struct EncodingWriter<W: Write> {
+    inner: W,
+    encoding: Encoding,
+    group: usize,
+    /// Bytes short of a whole group, encoded with the next ones
+    pending: Vec<u8>,
+    width: Option<usize>,
+    column: usize,
+}
+
+impl<W: Write> EncodingWriter<W> {
+    /// `None` for text, which isn't encoded
+    pub fn new(inner: W, encoding: Encoding, width: Option<usize>) -> Option<Self> {
+        let (group, _) = encoding.group()?;
+        Some(Self {
+            inner,
+            encoding,
+            group,
+            pending: Vec::with_capacity(group),
+            width: width.filter(|&width| width > 0),
+            column: 0,
+        })
+    }
+
+    fn emit(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let text = self.encoding.encode(bytes).expect("only text encoding fails");
+        let Some(width) = self.width else {
+            return self.inner.write_all(text.as_bytes());
+        };
+
+        // Every encoding but text is ASCII
+        let mut text = text.as_bytes();
+        while !text.is_empty() {
+            if self.column == width {
+                self.inner.write_all(b"\n")?;
+                self.column = 0;
+            }
+            let (line, rest) = text.split_at(text.len().min(width - self.column));
+            self.inner.write_all(line)?;
+            self.column += line.len();
+            text = rest;
+        }
+
+        Ok(())
+    }
+
+    /// Encodes the last bytes, with padding, and gives back the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let pending = std::mem::take(&mut self.pending);
+        self.emit(&pending)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncodingWriter<W> {
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
+        let written = buf.len();
+        if !self.pending.is_empty() {
+            let missing = (self.group - self.pending.len()).min(buf.len());
+            self.pending.extend_from_slice(&buf[..missing]);
+            buf = &buf[missing..];
+            if self.pending.len() < self.group {
+                return Ok(written);
+            }
+            let pending = std::mem::take(&mut self.pending);
+            self.emit(&pending)?;
+        }
+
+        let whole = buf.len() - buf.len() % self.group;
+        // A multiple of every group length, so no block but the last pads
+        for block in buf[..whole].chunks(BLOCK / 120 * 120) {
+            self.emit(block)?;
+        }
+        self.pending.extend_from_slice(&buf[whole..]);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decodes text given a block at a time, for payloads too large to read
+/// whole as text. Line breaks are ignored, as is any whitespace for hex,
+/// base64 and base32.
+pub struct StreamDecoder {
+    encoding: Encoding,
+    group: usize,
+    /// Characters short of a whole group, decoded with the next ones
+    pending: Vec<u8>,
+    /// Characters decoded so far, to tell where an error is
+    position: usize,
+    data: Vec<u8>,
+}
+
+impl StreamDecoder {
+    /// `None` for text, which isn't decoded
+    pub fn new(encoding: Encoding) -> Option<Self> {
+        let (_, group) = encoding.group()?;
+        Some(Self {
+            encoding,
+            group,
+            pending: Vec::new(),
+            position: 0,
+            data: Vec::new(),
+        })
+    }
+
+    fn decode(&mut self, characters: &[u8]) -> Result<(), FormatError> {
+        let text = String::from_utf8_lossy(characters);
+        let data = self.encoding.decode(&text).map_err(|err| err.shifted(self.position))?;
+        self.data.extend_from_slice(&data);
+        self.position += text.chars().count();
+        Ok(())
+    }
+
+    pub fn push(&mut self, text: &[u8]) -> Result<(), FormatError> {
+        // The base45 alphabet has a space
+        let skipped = |byte: &u8| match self.encoding {
+            Encoding::Base45 => matches!(byte, b'\r' | b'\n'),
+            _ => byte.is_ascii_whitespace(),
+        };
+        self.pending.extend(text.iter().filter(|byte| !skipped(byte)));
+
+        // Padding ends the text, the group it starts in is left for `finish`
+        let padding = self.pending.iter().position(|&byte| byte == b'=');
+        let end = padding.unwrap_or(self.pending.len());
+        let characters: Vec<u8> = self.pending.drain(..end - end % self.group).collect();
+        for block in characters.chunks(BLOCK / self.group * self.group) {
+            self.decode(block)?;
+        }
+
+        // Anything after the padding is an error, found without waiting
+        let mut padded = self.pending.iter().skip_while(|&&byte| byte != b'=');
+        if padded.any(|&byte| byte != b'=') {
+            let pending = std::mem::take(&mut self.pending);
+            self.decode(&pending)?;
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the last characters and gives back the data
+    pub fn finish(mut self) -> Result<Vec<u8>, FormatError> {
+        let pending = std::mem::take(&mut self.pending);
+        self.decode(&pending)?;
+        Ok(self.data)
+    }
+}
+
+/// Kind of document a payload must be, for `--payload-format`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayloadFormat {
+    /// A JSON document, pretty-printed by `decode`
+    Json,
+}
+
+impl PayloadFormat {
+    /// Checks that `data` is a document of this format
+    pub fn validate(self, data: &[u8]) -> Result<(), FormatError> {
+        match self {
+            PayloadFormat::Json => parse_json(data).map(drop),
+        }
+    }
+
+    /// The document `data` holds, laid out for reading
+    pub fn pretty(self, data: &[u8]) -> Result<String, FormatError> {
+        match self {
+            PayloadFormat::Json => Ok(serde_json::to_string_pretty(&parse_json(data)?)
+                .expect("a parsed document serializes")),
+        }
+    }
+}
+
+fn parse_json(data: &[u8]) -> Result<serde_json::Value, FormatError> {
+    serde_json::from_slice(data).map_err(|err| {
+        let (line, column) = (err.line(), err.column());
+        // The column counts the bytes read on the line, up to the
+        // offending one
+        let line_start: usize = data
+            .split(|&byte| byte == b'\n')
+            .take(line.saturating_sub(1))
+            .map(|line| line.len() + 1)
+            .sum();
+        let reason = err.to_string();
+        let suffix = format!(" at line {line} column {column}");
+
+        FormatError::Json {
+            offset: (line_start + column).saturating_sub(1),
+            line,
+            column,
+            reason: reason.strip_suffix(&suffix).unwrap_or(&reason).to_string(),
+        }
+    })
+}
+
+/// Decodes hexadecimal digits, whitespace is ignored
+pub fn decode_hex(text: &str) -> Result<Vec<u8>, FormatError> {
+    let digits: Vec<u8> = text
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(FormatError::Hex(
+            "expected an even number of hexadecimal digits".to_string(),
+        ));
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| {
+                    FormatError::Hex(format!(
+                        "'{}' is not a hexadecimal byte",
+                        String::from_utf8_lossy(pair)
+                    ))
+                })
+        })
+        .collect()
+}
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+fn encode_base32(data: &[u8]) -> String {
+    let mut text = String::with_capacity(data.len().div_ceil(5) * 8);
+
+    for group in data.chunks(5) {
+        let mut block = [0u8; 5];
+        block[..group.len()].copy_from_slice(group);
+        let bits = block
+            .iter()
+            .fold(0u64, |bits, &byte| bits << 8 | byte as u64);
+
+        // 8 bits per byte, 5 per character, rounded up
+        let characters = (group.len() * 8).div_ceil(5);
+        for index in 0..8 {
+            if index < characters {
+                let value = (bits >> (35 - index * 5)) & 0x1F;
+                text.push(BASE32_ALPHABET[value as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+
+    text
+}
+
+/// Case-insensitive, the padding is optional
+fn decode_base32(text: &str) -> Result<Vec<u8>, FormatError> {
+    let text = text.trim_end_matches('=');
+    // Lengths a partial group of 1 to 4 bytes encodes to
+    if matches!(text.len() % 8, 1 | 3 | 6) {
+        return Err(FormatError::Base32Length { len: text.len() });
+    }
+
+    let mut data = Vec::with_capacity(text.len() * 5 / 8);
+    let (mut bits, mut count) = (0u32, 0);
+
+    for (position, character) in text.chars().enumerate() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&letter| letter as char == character.to_ascii_uppercase())
+            .ok_or(FormatError::Base32Character {
+                position,
+                character,
+            })?;
+
+        bits = bits << 5 | value as u32;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            data.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+
+    Ok(data)
+}
+
+const BASE45_ALPHABET: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+fn encode_base45(data: &[u8]) -> String {
+    let mut text = String::with_capacity(data.len().div_ceil(2) * 3);
+
+    for pair in data.chunks(2) {
+        let (mut value, digits) = match *pair {
+            [first, second] => ((first as usize) << 8 | second as usize, 3),
+            [single] => (single as usize, 2),
+            _ => unreachable!("chunks(2) yields one or two bytes"),
+        };
+
+        // Least significant digit first
+        for _ in 0..digits {
+            text.push(BASE45_ALPHABET[value % 45] as char);
+            value /= 45;
+        }
+    }
+
+    text
+}
+
+fn decode_base45(text: &str) -> Result<Vec<u8>, FormatError> {
+    let values = text
+        .chars()
+        .enumerate()
+        .map(|(position, character)| {
+            BASE45_ALPHABET
+                .iter()
+                .position(|&letter| letter as char == character)
+                .ok_or(FormatError::Base45Character {
+                    position,
+                    character,
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if values.len() % 3 == 1 {
+        return Err(FormatError::Base45Length { len: values.len() });
+    }
+
+    let mut data = Vec::with_capacity(values.len() / 3 * 2 + 1);
+    for (index, group) in values.chunks(3).enumerate() {
+        let value = group
+            .iter()
+            .rev()
+            .fold(0, |value, &digit| value * 45 + digit);
+        let overflow = || FormatError::Base45Overflow {
+            position: index * 3,
+            group: text.chars().skip(index * 3).take(group.len()).collect(),
+        };
+
+        if group.len() == 3 {
+            let value = u16::try_from(value).map_err(|_| overflow())?;
+            data.extend_from_slice(&value.to_be_bytes());
+        } else {
+            data.push(u8::try_from(value).map_err(|_| overflow())?);
+        }
+    }
+
+    Ok(data)
+}
+
+/// UTF-8 byte order mark, added by some Windows editors
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Line endings given to text payloads
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Newlines {
+    /// `\r\n` becomes `\n`
+    Lf,
+    /// A `\n` not preceded by `\r` becomes `\r\n`
+    Crlf,
+    /// Line endings are left as they are
+    #[default]
+    Keep,
+}
+
+/// Opt-in clean-ups of text payloads, e.g. copied from files written on
+/// Windows. Binary payloads (any other [`Encoding`] than text, a
+/// `--message-file`, raw output) are never cleaned up.
+#[derive(Args, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextOptions {
+    /// Remove a UTF-8 byte order mark starting the text
+    #[arg(long)]
+    pub strip_bom: bool,
+
+    /// Line endings of the text
+    #[arg(long, value_enum, default_value_t = Newlines::Keep)]
+    pub normalize_newlines: Newlines,
+}
+
+impl TextOptions {
+    pub fn is_noop(&self) -> bool {
+        !self.strip_bom && self.normalize_newlines == Newlines::Keep
+    }
+
+    pub fn apply<'a>(&self, text: &'a [u8]) -> Cow<'a, [u8]> {
+        let text = match text.strip_prefix(BOM) {
+            Some(rest) if self.strip_bom => rest,
+            _ => text,
+        };
+
+        if self.normalize_newlines == Newlines::Keep {
+            return Cow::Borrowed(text);
+        }
+
+        let mut normalized = Vec::with_capacity(text.len());
+        match self.normalize_newlines {
+            Newlines::Keep => {}
+            Newlines::Lf => {
+                for (index, &byte) in text.iter().enumerate() {
+                    if !(byte == b'\r' && text.get(index + 1) == Some(&b'\n')) {
+                        normalized.push(byte);
+                    }
+                }
+            }
+            Newlines::Crlf => {
+                for (index, &byte) in text.iter().enumerate() {
+                    if byte == b'\n' && (index == 0 || text[index - 1] != b'\r') {
+                        normalized.push(b'\r');
+                    }
+                    normalized.push(byte);
+                }
+            }
+        }
+
+        Cow::Owned(normalized)
+    }
+
+    /// [`TextOptions::apply`] on a string
+    pub fn apply_str<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self.apply(text.as_bytes()) {
+            Cow::Borrowed(bytes) => {
+                Cow::Borrowed(std::str::from_utf8(bytes).expect("a whole BOM was removed"))
+            }
+            Cow::Owned(bytes) => {
+                Cow::Owned(String::from_utf8(bytes).expect("only ASCII line endings were changed"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // RFC 9285 section 4.3 and 4.4
+    const BASE45_VECTORS: [(&str, &str); 4] = [
+        ("AB", "BB8"),
+        ("Hello!!", "%69 VD92EX0"),
+        ("base-45", "UJCLQE7W581"),
+        ("ietf!", "QED8WEX0"),
+    ];
+
+    // RFC 4648 section 10
+    const BASE32_VECTORS: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "MY======"),
+        ("fo", "MZXQ===="),
+        ("foo", "MZXW6==="),
+        ("foob", "MZXW6YQ="),
+        ("fooba", "MZXW6YTB"),
+        ("foobar", "MZXW6YTBOI======"),
+    ];
+
+    #[test]
+    fn test_base45_rfc_vectors() {
+        for (data, text) in BASE45_VECTORS {
+            assert_eq!(Encoding::Base45.encode(data.as_bytes()).unwrap(), text);
+            assert_eq!(Encoding::Base45.decode(text).unwrap(), data.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_base45_invalid() {
+        // RFC 9285 section 6: "GGW" is 65535 + 1
+        assert!(matches!(
+            Encoding::Base45.decode("GGW"),
+            Err(FormatError::Base45Overflow { position: 0, .. })
+        ));
+        assert!(matches!(
+            Encoding::Base45.decode("BB8a"),
+            Err(FormatError::Base45Character {
+                position: 3,
+                character: 'a'
+            })
+        ));
+        assert!(matches!(
+            Encoding::Base45.decode("BB8B"),
+            Err(FormatError::Base45Length { len: 4 })
+        ));
+        assert!(matches!(
+            Encoding::Base45.decode(":::"),
+            Err(FormatError::Base45Overflow { .. })
+        ));
+    }
+
+    #[test]
+    fn test_base32_rfc_vectors() {
+        for (data, text) in BASE32_VECTORS {
+            assert_eq!(Encoding::Base32.encode(data.as_bytes()).unwrap(), text);
+            assert_eq!(Encoding::Base32.decode(text).unwrap(), data.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_base32_lenient_decoding() {
+        assert_eq!(Encoding::Base32.decode("mzxw6ytboi").unwrap(), b"foobar");
+        assert!(matches!(
+            Encoding::Base32.decode("MZ1W"),
+            Err(FormatError::Base32Character {
+                position: 2,
+                character: '1'
+            })
+        ));
+        assert!(matches!(
+            Encoding::Base32.decode("MZX"),
+            Err(FormatError::Base32Length { len: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_hex_and_text() {
+        assert_eq!(Encoding::Hex.encode(b"\x00\xffA").unwrap(), "00ff41");
+        assert_eq!(Encoding::Hex.decode("00 FF 41").unwrap(), b"\x00\xffA");
+        assert!(Encoding::Hex.decode("0").is_err());
+        assert_eq!(Encoding::Text.encode(b"\xff"), Err(FormatError::NotUtf8));
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(data in proptest::collection::vec(any::<u8>(), 0..256)) {
+            for encoding in [Encoding::Hex, Encoding::Base64, Encoding::Base32, Encoding::Base45] {
+                let text = encoding.encode(&data).unwrap();
+                prop_assert_eq!(encoding.decode(&text).unwrap(), data.clone());
+            }
+        }
+
+        #[test]
+        fn test_streaming_matches_whole(
+            data in proptest::collection::vec(any::<u8>(), 0..600),
+            piece in 1usize..40,
+            width in proptest::option::of(1usize..80),
+        ) {
+            for encoding in [Encoding::Hex, Encoding::Base64, Encoding::Base32, Encoding::Base45] {
+                let text = encoding.encode(&data).unwrap();
+                let text = width.map_or(text.clone(), |width| wrap(&text, width));
+                prop_assert_eq!(streamed(encoding, &data, piece, width), text.clone());
+
+                let mut decoder = StreamDecoder::new(encoding).unwrap();
+                for block in text.as_bytes().chunks(piece) {
+                    decoder.push(block).unwrap();
+                }
+                prop_assert_eq!(decoder.finish().unwrap(), data.clone());
+            }
+        }
+    }
+
+    /// `data` encoded by an [`EncodingWriter`], written `piece` bytes at a
+    /// time
+    fn streamed(encoding: Encoding, data: &[u8], piece: usize, width: Option<usize>) -> String {
+        let mut writer = EncodingWriter::new(Vec::new(), encoding, width).unwrap();
+        for block in data.chunks(piece) {
+            writer.write_all(block).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_streaming_across_blocks() {
+        let data: Vec<u8> = (0..BLOCK * 3 + 7).map(|index| (index * 31 % 251) as u8).collect();
+        for encoding in [Encoding::Hex, Encoding::Base64, Encoding::Base32, Encoding::Base45] {
+            let text = wrap(&encoding.encode(&data).unwrap(), 76);
+            assert_eq!(streamed(encoding, &data, data.len(), Some(76)), text);
+            assert!(text.lines().all(|line| line.len() <= 76));
+
+            let mut decoder = StreamDecoder::new(encoding).unwrap();
+            decoder.push(text.as_bytes()).unwrap();
+            assert_eq!(decoder.finish().unwrap(), data);
+        }
+
+        assert!(EncodingWriter::new(Vec::new(), Encoding::Text, None).is_none());
+        assert!(StreamDecoder::new(Encoding::Text).is_none());
+    }
+
+    #[test]
+    fn test_streaming_errors_count_every_block() {
+        let decoded = |encoding, blocks: &[&str]| {
+            let mut decoder = StreamDecoder::new(encoding).unwrap();
+            for block in blocks {
+                decoder.push(block.as_bytes())?;
+            }
+            decoder.finish()
+        };
+
+        assert_eq!(
+            decoded(Encoding::Base64, &["QUJD\n", "Q!JD"]),
+            Err(FormatError::Base64(base64::DecodeError::InvalidByte(5, b'!')))
+        );
+        assert_eq!(
+            decoded(Encoding::Base32, &["MZXW6YTB", "MZ1W"]),
+            Err(FormatError::Base32Character {
+                position: 10,
+                character: '1'
+            })
+        );
+        assert_eq!(
+            decoded(Encoding::Base45, &["BB8", "BB8a"]),
+            Err(FormatError::Base45Character {
+                position: 6,
+                character: 'a'
+            })
+        );
+        // Padding ends the text
+        assert!(decoded(Encoding::Base64, &["QQ==", "QUJD"]).is_err());
+        assert_eq!(decoded(Encoding::Base64, &["QUJD", "QQ", "=="]).unwrap(), b"ABCA");
+        assert_eq!(decoded(Encoding::Base32, &["MZXW6", "===\n"]).unwrap(), b"foo");
+    }
+
+    #[test]
+    fn test_strip_bom() {
+        let options = TextOptions {
+            strip_bom: true,
+            ..TextOptions::default()
+        };
+
+        assert_eq!(options.apply(b"\xEF\xBB\xBFhello"), &b"hello"[..]);
+        // Only a leading BOM is a byte order mark
+        assert_eq!(options.apply(b"a\xEF\xBB\xBFb"), &b"a\xEF\xBB\xBFb"[..]);
+        assert_eq!(
+            TextOptions::default().apply(b"\xEF\xBB\xBFhi"),
+            &b"\xEF\xBB\xBFhi"[..]
+        );
+    }
+
+    #[test]
+    fn test_normalize_newlines() {
+        let newlines = |normalize_newlines| TextOptions {
+            normalize_newlines,
+            ..TextOptions::default()
+        };
+        let text = b"a\r\nb\nc\rd\r\n";
+
+        assert_eq!(newlines(Newlines::Lf).apply(text), &b"a\nb\nc\rd\n"[..]);
+        assert_eq!(
+            newlines(Newlines::Crlf).apply(text),
+            &b"a\r\nb\r\nc\rd\r\n"[..]
+        );
+        assert_eq!(newlines(Newlines::Crlf).apply(b"\n"), &b"\r\n"[..]);
+        assert_eq!(newlines(Newlines::Keep).apply(text), &text[..]);
+    }
+
+    #[test]
+    fn test_apply_str_keeps_utf8() {
+        let options = TextOptions {
+            strip_bom: true,
+            normalize_newlines: Newlines::Lf,
+        };
+
+        assert_eq!(options.apply_str("\u{FEFF}héllo\r\nwörld"), "héllo\nwörld");
+        assert!(!options.is_noop());
+        assert!(TextOptions::default().is_noop());
+    }
+
+    #[test]
+    fn test_json_payloads() {
+        let json = PayloadFormat::Json;
+
+        json.validate(br#"{"retries": 3}"#).unwrap();
+        assert_eq!(
+            json.pretty(br#"{"a":[1,2]}"#).unwrap(),
+            "{\n  \"a\": [\n    1,\n    2\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn test_json_error_offset() {
+        let document = b"{\n  \"a\": 1,\n  \"b\": x\n}";
+        let err = PayloadFormat::Json.validate(document).unwrap_err();
+
+        assert_eq!(err.code(), Code::InvalidJson);
+        assert_eq!(
+            err,
+            FormatError::Json {
+                offset: 19,
+                line: 3,
+                column: 8,
+                reason: "expected value".to_string(),
+            }
+        );
+        assert_eq!(document[19], b'x');
+        assert_eq!(
+            err.to_string(),
+            "Invalid JSON at byte 19 (line 3, column 8): expected value"
+        );
+    }
+}
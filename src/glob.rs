@@ -0,0 +1,518 @@
+//! Shell-style patterns naming many files, for `encode --glob`, and paths
+//! to skip, for `--exclude`.
+//!
+//! Within a path component, `*` matches any run of characters, `?` a single
+//! character and `[abc]`, `[a-z]` or `[!abc]` one character of a set. A
+//! whole `**` component matches any number of directories, so
+//! `assets/**/*.png` is every PNG file under `assets`. Like in shells,
+//! wildcards don't match a leading `.`, and a pattern without wildcards
+//! names a single file.
+//!
+//! Matches are files, in path order. Symbolic links to directories are not
+//! followed, as in [`crate::walk`].
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::codes::Code;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PatternError {
+    #[error("The pattern '{pattern}' has a '[' without its ']'")]
+    UnclosedClass { pattern: String },
+}
+
+impl PatternError {
+    pub fn code(&self) -> Code {
+        match self {
+            PatternError::UnclosedClass { .. } => Code::GlobUnclosedClass,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum GlobError {
+    #[error("The pattern '{pattern}' matches no file")]
+    NoMatch { pattern: String },
+
+    #[error("Could not list {}: {source}", path.display())]
+    ReadDir { path: PathBuf, source: io::Error },
+}
+
+impl GlobError {
+    pub fn code(&self) -> Code {
+        match self {
+            GlobError::NoMatch { .. } => Code::GlobNoMatch,
+            GlobError::ReadDir { .. } => Code::IoFailed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `?`
+    One,
+    /// `*`
+    Any,
+    /// `[...]`, of inclusive ranges
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Char(expected) => *expected == c,
+            Token::One | Token::Any => true,
+            Token::Class { negated, ranges } => {
+                ranges.iter().any(|&(low, high)| (low..=high).contains(&c)) != *negated
+            }
+        }
+    }
+}
+
+/// Whether `pattern` matches the whole of `items`, where the elements for
+/// which `is_star` holds match any run of items and the others exactly one.
+///
+/// Only the last star seen is backtracked to: when a later one is reached,
+/// any split that failed with the previous one would fail with it too. The
+/// match takes at most `pattern.len() * items.len()` steps, where trying
+/// every split of every star takes exponential time.
+fn wildcard_match<P, I>(
+    pattern: &[P],
+    items: &[I],
+    is_star: impl Fn(&P) -> bool,
+    matches_one: impl Fn(&P, &I) -> bool,
+) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // The last star, and the first item it hasn't been tried to cover
+    let mut backtrack = None;
+
+    while i < items.len() {
+        match pattern.get(p) {
+            Some(star) if is_star(star) => {
+                backtrack = Some((p, i));
+                p += 1;
+            }
+            Some(element) if matches_one(element, &items[i]) => {
+                p += 1;
+                i += 1;
+            }
+            _ => {
+                let Some((star, covered)) = backtrack else {
+                    return false;
+                };
+                backtrack = Some((star, covered + 1));
+                p = star + 1;
+                i = covered + 1;
+            }
+        }
+    }
+
+    pattern[p..].iter().all(is_star)
+}
+
+/// Whether `tokens` match the whole of `name`
+fn matches(tokens: &[Token], name: &[char]) -> bool {
+    wildcard_match(
+        tokens,
+        name,
+        |token| *token == Token::Any,
+        |token, &c| token.matches(c),
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Component {
+    /// A name without wildcards
+    Literal(String),
+    Wildcards(Vec<Token>),
+    /// `**`
+    Directories,
+}
+
+impl Component {
+    fn parse(component: &str, pattern: &str) -> Result<Self, PatternError> {
+        if component == "**" {
+            return Ok(Component::Directories);
+        }
+        if !component.contains(['*', '?', '[']) {
+            return Ok(Component::Literal(component.to_string()));
+        }
+
+        let mut tokens = Vec::new();
+        let mut chars = component.chars();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '*' if tokens.last() == Some(&Token::Any) => continue,
+                '*' => Token::Any,
+                '?' => Token::One,
+                '[' => Self::class(&mut chars).ok_or_else(|| PatternError::UnclosedClass {
+                    pattern: pattern.to_string(),
+                })?,
+                c => Token::Char(c),
+            });
+        }
+
+        Ok(Component::Wildcards(tokens))
+    }
+
+    /// The set after a `[`, `None` without its `]`. A `]` first in the set
+    /// is a member, not its end
+    fn class(chars: &mut std::str::Chars) -> Option<Token> {
+        let mut rest = chars.as_str();
+        let negated = match rest.strip_prefix(['!', '^']) {
+            Some(stripped) => {
+                rest = stripped;
+                true
+            }
+            None => false,
+        };
+        let first = rest.chars().next()?.len_utf8();
+        let end = rest[first..].find(']')? + first;
+        let members: Vec<char> = rest[..end].chars().collect();
+
+        let mut ranges = Vec::new();
+        let mut index = 0;
+        while index < members.len() {
+            match members.get(index + 1..index + 3) {
+                Some(&['-', high]) => {
+                    ranges.push((members[index], high));
+                    index += 3;
+                }
+                _ => {
+                    ranges.push((members[index], members[index]));
+                    index += 1;
+                }
+            }
+        }
+
+        *chars = rest[end + 1..].chars();
+        Some(Token::Class { negated, ranges })
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Component::Literal(literal) => literal == name,
+            Component::Wildcards(tokens) => {
+                let hidden = name.starts_with('.') && tokens.first() != Some(&Token::Char('.'));
+                !hidden && matches(tokens, &name.chars().collect::<Vec<_>>())
+            }
+            Component::Directories => true,
+        }
+    }
+}
+
+/// A parsed `--glob` value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pattern: String,
+    absolute: bool,
+    components: Vec<Component>,
+}
+
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut components = s
+            .split('/')
+            .filter(|component| !component.is_empty() && *component != ".")
+            .map(|component| Component::parse(component, s))
+            .collect::<Result<Vec<_>, _>>()?;
+        // A trailing `**` is every file below
+        if components.last() == Some(&Component::Directories) {
+            components.push(Component::Wildcards(vec![Token::Any]));
+        }
+
+        Ok(Self {
+            pattern: s.to_string(),
+            absolute: s.starts_with('/'),
+            components,
+        })
+    }
+}
+
+/// Entry of a directory listing
+struct Entry {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+}
+
+/// Whether `components` match the whole of `names`
+fn matches_names(components: &[Component], names: &[String]) -> bool {
+    wildcard_match(
+        components,
+        names,
+        |component| *component == Component::Directories,
+        |component, name| component.matches(name),
+    )
+}
+
+impl Pattern {
+    /// Whether `path`, relative to the directory a walk starts from,
+    /// matches the pattern. A pattern of one component, like `vendor` or
+    /// `*.orig.png`, is matched against the last name of the path alone,
+    /// so at any depth
+    pub fn matches(&self, path: &Path) -> bool {
+        let names: Vec<String> = path
+            .components()
+            .filter_map(|component| match component {
+                std::path::Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+
+        match self.components.as_slice() {
+            [component] => names.last().is_some_and(|name| component.matches(name)),
+            components => matches_names(components, &names),
+        }
+    }
+
+    /// The files matching the pattern, in path order, failing when there is
+    /// none
+    pub fn files(&self) -> Result<Vec<PathBuf>, GlobError> {
+        let base = match self.absolute {
+            true => PathBuf::from("/"),
+            false => PathBuf::new(),
+        };
+        let mut files = Vec::new();
+        expand(&base, &self.components, &mut files)?;
+        files.sort();
+        files.dedup();
+
+        match files.is_empty() {
+            true => Err(GlobError::NoMatch {
+                pattern: self.pattern.clone(),
+            }),
+            false => Ok(files),
+        }
+    }
+}
+
+/// The files matching any of `patterns`, in the order of the patterns and
+/// each once
+pub fn files(patterns: &[Pattern]) -> Result<Vec<PathBuf>, GlobError> {
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for pattern in patterns {
+        for file in pattern.files()? {
+            if seen.insert(file.clone()) {
+                files.push(file);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Adds the files below `dir` matching `components` to `files`
+fn expand(dir: &Path, components: &[Component], files: &mut Vec<PathBuf>) -> Result<(), GlobError> {
+    let Some((component, rest)) = components.split_first() else {
+        return Ok(());
+    };
+
+    match component {
+        // Looked up rather than listed, the directory may not be readable
+        Component::Literal(name) => {
+            let path = dir.join(name);
+            match rest {
+                [] if path.exists() && !path.is_dir() => files.push(path),
+                [] => {}
+                _ if path.is_dir() => expand(&path, rest, files)?,
+                _ => {}
+            }
+        }
+        Component::Directories => {
+            expand(dir, rest, files)?;
+            for entry in list(dir)? {
+                if entry.is_dir && !entry.name.starts_with('.') {
+                    expand(&entry.path, components, files)?;
+                }
+            }
+        }
+        Component::Wildcards(_) => {
+            for entry in list(dir)? {
+                if !component.matches(&entry.name) {
+                    continue;
+                }
+                match (rest, entry.is_dir) {
+                    ([], false) => files.push(entry.path),
+                    ([], true) => {}
+                    (_, true) => expand(&entry.path, rest, files)?,
+                    (_, false) => {}
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The entries of `dir`, the working directory when empty
+fn list(dir: &Path) -> Result<Vec<Entry>, GlobError> {
+    let read_dir = |path: &Path| -> io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            entries.push(Entry {
+                path: dir.join(entry.file_name()),
+                name: entry.file_name().to_string_lossy().into_owned(),
+                is_dir: entry.file_type()?.is_dir(),
+            });
+        }
+        Ok(entries)
+    };
+
+    let listed = match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => dir,
+    };
+    read_dir(listed).map_err(|source| GlobError::ReadDir {
+        path: listed.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn component(value: &str) -> Component {
+        Component::parse(value, value).unwrap()
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(component("*.png").matches("image.png"));
+        assert!(!component("*.png").matches(".png"));
+        assert!(!component("*.png").matches("image.apng.txt"));
+        assert!(component("img-??.png").matches("img-01.png"));
+        assert!(!component("img-??.png").matches("img-1.png"));
+        assert!(component("*a*b*").matches("xaybz"));
+        assert!(!component("*a*b*").matches("xbya"));
+        assert!(component("**.png").matches("a.png"), "like a single *");
+        assert!(component("*").matches(""));
+        assert!(component("a*").matches("a"));
+        assert!(!component("a*b").matches("a"));
+        assert!(component("*ab").matches("aab"));
+    }
+
+    #[test]
+    fn test_many_stars_stay_fast() {
+        // Exponential when every split of every `*` is tried
+        let name = format!("{}b", "a".repeat(43));
+        assert!(!component("*a*a*a*a*a*a*a*a*a*c").matches(&name));
+        assert!(component("*a*a*a*a*a*a*a*a*a*b").matches(&name));
+
+        let pattern = "**/a/**/a/**/a/**/a/**/a/**/a/**/c"
+            .parse::<Pattern>()
+            .unwrap();
+        let path = PathBuf::from_iter(std::iter::repeat_n("a", 40).chain(["b"]));
+        assert!(!pattern.matches(&path));
+    }
+
+    #[test]
+    fn test_classes() {
+        assert!(component("[ab].png").matches("a.png"));
+        assert!(!component("[ab].png").matches("c.png"));
+        assert!(component("[0-9]").matches("7"));
+        assert!(component("[!0-9]").matches("x"));
+        assert!(!component("[^0-9]").matches("7"));
+        assert!(component("[]]").matches("]"));
+        assert!(component("[a-]").matches("-"));
+        assert!(matches!(
+            "assets/[ab.png".parse::<Pattern>(),
+            Err(PatternError::UnclosedClass { .. })
+        ));
+        assert!("[]".parse::<Pattern>().is_err());
+    }
+
+    #[test]
+    fn test_hidden_names() {
+        assert!(!component("*").matches(".hidden.png"));
+        assert!(!component("?hidden.png").matches(".hidden.png"));
+        assert!(component(".*.png").matches(".hidden.png"));
+        assert!(component(".hidden.png").matches(".hidden.png"));
+    }
+
+    #[test]
+    fn test_matches_paths() {
+        let pattern = |value: &str| value.parse::<Pattern>().unwrap();
+        let path = Path::new;
+
+        assert!(pattern("vendor").matches(path("vendor")));
+        assert!(pattern("vendor").matches(path("assets/vendor")));
+        assert!(!pattern("vendor").matches(path("vendor/a.png")));
+        assert!(pattern("*.orig.png").matches(path("icons/a.orig.png")));
+        assert!(pattern("icons/*.png").matches(path("icons/a.png")));
+        assert!(!pattern("icons/*.png").matches(path("old/icons/a.png")));
+        assert!(pattern("**/icons/*.png").matches(path("old/icons/a.png")));
+        assert!(pattern("**/icons/*.png").matches(path("icons/a.png")));
+        assert!(pattern("build/**").matches(path("build/x/y.png")));
+    }
+
+    fn touch(dir: &Path, names: &[&str]) {
+        for name in names {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+    }
+
+    fn found(dir: &Path, pattern: &str) -> Vec<String> {
+        let pattern = format!("{}/{pattern}", dir.display());
+        match pattern.parse::<Pattern>().unwrap().files() {
+            Ok(files) => files
+                .iter()
+                .map(|file| {
+                    let file = file.strip_prefix(dir).unwrap();
+                    file.to_string_lossy().into_owned()
+                })
+                .collect(),
+            Err(GlobError::NoMatch { .. }) => vec![],
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        touch(
+            dir,
+            &[
+                "a.png",
+                "b.png",
+                "notes.txt",
+                "icons/c.png",
+                "icons/small/d.png",
+                ".cache/e.png",
+            ],
+        );
+
+        assert_eq!(found(dir, "*.png"), ["a.png", "b.png"]);
+        assert_eq!(
+            found(dir, "**/*.png"),
+            ["a.png", "b.png", "icons/c.png", "icons/small/d.png"]
+        );
+        assert_eq!(found(dir, "icons/**"), ["icons/c.png", "icons/small/d.png"]);
+        assert_eq!(found(dir, "*/*.png"), ["icons/c.png"]);
+        assert_eq!(found(dir, ".cache/*.png"), [".cache/e.png"]);
+        assert_eq!(found(dir, "notes.txt"), ["notes.txt"]);
+        // Directories are not files
+        assert_eq!(found(dir, "icons"), Vec::<String>::new());
+        assert_eq!(found(dir, "*.gif"), Vec::<String>::new());
+        assert_eq!(found(dir, "missing/*.png"), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,206 @@
+use std::io::{self, Write};
+
+use flate2::{Compression, write::ZlibEncoder};
+use thiserror::Error;
+
+use crate::{
+    chunk::{Chunk, ChunkParserError},
+    chunk_type::ChunkType,
+    codes::Code,
+    inflate::{DEFAULT_MAX_DECOMPRESSED_SIZE, InflateError, inflate},
+    text::{TextError, latin1_decode, latin1_encode, validate_text_keyword},
+};
+
+/// Type of the chunk embedding an ICC color profile
+pub const ICCP_CHUNK_TYPE: [u8; 4] = *b"iCCP";
+
+#[derive(Error, Debug)]
+pub enum IccError {
+    #[error("Invalid profile name: {0}")]
+    InvalidName(#[from] TextError),
+
+    #[error("iCCP chunk has no null separator after the profile name")]
+    MissingSeparator,
+
+    #[error("iCCP chunk has no compression method")]
+    MissingCompressionMethod,
+
+    #[error("Unsupported iCCP compression method {0} (only 0 is defined)")]
+    UnsupportedCompression(u8),
+
+    #[error("Invalid ICC profile zlib stream: {0}")]
+    Zlib(io::Error),
+
+    #[error(transparent)]
+    Inflate(InflateError),
+
+    #[error(transparent)]
+    Chunk(#[from] ChunkParserError),
+}
+
+impl IccError {
+    pub fn code(&self) -> Code {
+        match self {
+            IccError::InvalidName(err) => err.code(),
+            IccError::MissingSeparator => Code::IccMissingSeparator,
+            IccError::MissingCompressionMethod => Code::IccMissingCompression,
+            IccError::UnsupportedCompression(_) => Code::IccUnsupportedCompression,
+            IccError::Zlib(_) => Code::IccInvalidZlib,
+            IccError::Inflate(err) => err.code(),
+            IccError::Chunk(err) => err.code(),
+        }
+    }
+
+    /// The error naming the iCCP chunk at `index`
+    pub fn in_chunk(self, index: usize) -> Self {
+        match self {
+            IccError::Inflate(err) => IccError::Inflate(err.in_chunk("iCCP", index)),
+            err => err,
+        }
+    }
+}
+
+/// An ICC color profile as stored in an iCCP chunk
+#[derive(Debug, PartialEq, Eq)]
+pub struct IccProfile {
+    name: String,
+    profile: Vec<u8>,
+}
+
+impl IccProfile {
+    pub fn new(name: &str, profile: Vec<u8>) -> Result<Self, IccError> {
+        validate_text_keyword(name)?;
+
+        Ok(Self {
+            name: name.to_owned(),
+            profile,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The decompressed profile
+    pub fn profile(&self) -> &[u8] {
+        &self.profile
+    }
+
+    /// Builds the iCCP chunk: name, null separator, compression method 0 and
+    /// the zlib-compressed profile.
+    pub fn to_chunk(&self) -> Result<Chunk, IccError> {
+        let mut data = latin1_encode(&self.name)?;
+        data.extend_from_slice(&[0, 0]);
+
+        let mut encoder = ZlibEncoder::new(data, Compression::default());
+        encoder.write_all(&self.profile).map_err(IccError::Zlib)?;
+        let data = encoder.finish().map_err(IccError::Zlib)?;
+
+        let chunk_type = ChunkType::try_from(ICCP_CHUNK_TYPE).expect("iCCP is a valid chunk type");
+
+        Ok(Chunk::try_new(chunk_type, data)?)
+    }
+
+    /// Reads the profile of an iCCP chunk, refusing one that inflates to
+    /// more than `limit` bytes
+    pub fn from_chunk(chunk: &Chunk, limit: u64) -> Result<Self, IccError> {
+        let data = chunk.data();
+
+        let separator = data
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or(IccError::MissingSeparator)?;
+        let name = latin1_decode(&data[..separator]);
+
+        let method = *data
+            .get(separator + 1)
+            .ok_or(IccError::MissingCompressionMethod)?;
+        if method != 0 {
+            return Err(IccError::UnsupportedCompression(method));
+        }
+
+        let profile = inflate(&data[separator + 2..], limit).map_err(|err| match err {
+            InflateError::Corrupt(err) => IccError::Zlib(err),
+            err => IccError::Inflate(err),
+        })?;
+
+        Ok(Self { name, profile })
+    }
+}
+
+impl TryFrom<&Chunk> for IccProfile {
+    type Error = IccError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        Self::from_chunk(chunk, DEFAULT_MAX_DECOMPRESSED_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_profile() -> IccProfile {
+        let profile: Vec<u8> = (0..=255).cycle().take(2048).collect();
+        IccProfile::new("Display P3", profile).unwrap()
+    }
+
+    #[test]
+    fn test_icc_chunk_layout() {
+        let chunk = testing_profile().to_chunk().unwrap();
+
+        assert_eq!(chunk.chunk_type().bytes(), ICCP_CHUNK_TYPE);
+        assert!(chunk.data().starts_with(b"Display P3\0\0"));
+        // zlib header
+        assert_eq!(chunk.data()[12], 0x78);
+    }
+
+    #[test]
+    fn test_icc_round_trip() {
+        let profile = testing_profile();
+        let chunk = profile.to_chunk().unwrap();
+
+        assert_eq!(IccProfile::try_from(&chunk).unwrap(), profile);
+    }
+
+    #[test]
+    fn test_icc_invalid_name() {
+        assert!(matches!(
+            IccProfile::new(" P3", Vec::new()),
+            Err(IccError::InvalidName(TextError::KeywordSpaces))
+        ));
+    }
+
+    #[test]
+    fn test_icc_unsupported_compression() {
+        let chunk_type = ChunkType::try_from(ICCP_CHUNK_TYPE).unwrap();
+        let chunk = Chunk::new(chunk_type, b"P3\0\x01data".to_vec());
+
+        assert!(matches!(
+            IccProfile::try_from(&chunk),
+            Err(IccError::UnsupportedCompression(1))
+        ));
+    }
+
+    #[test]
+    fn test_icc_missing_separator() {
+        let chunk_type = ChunkType::try_from(ICCP_CHUNK_TYPE).unwrap();
+        let chunk = Chunk::new(chunk_type, b"P3".to_vec());
+
+        assert!(matches!(
+            IccProfile::try_from(&chunk),
+            Err(IccError::MissingSeparator)
+        ));
+    }
+
+    #[test]
+    fn test_icc_corrupted_stream() {
+        let chunk_type = ChunkType::try_from(ICCP_CHUNK_TYPE).unwrap();
+        let chunk = Chunk::new(chunk_type, b"P3\0\0not zlib".to_vec());
+
+        assert!(matches!(
+            IccProfile::try_from(&chunk),
+            Err(IccError::Zlib(_))
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+//! Deep check of the image data, for `verify --deep`.
+//!
+//! The IDAT chunks hold one zlib stream which inflates to the scanlines of
+//! the image, each a filter byte followed by the pixels of the row. Its size
+//! is fixed by the IHDR dimensions, so a stream inflating to fewer or more
+//! bytes is corrupt, even when every chunk has the right CRC. The stream is
+//! inflated through a fixed buffer and only counted, never held in memory.
+
+use std::io::{self, Read};
+
+use flate2::read::ZlibDecoder;
+
+use crate::{
+    chunk_ref::ChunkRef,
+    color::{BIT_DEPTH, ColorFormat},
+    consts::CHUNK_OVERHEAD,
+    interlace::INTERLACE_METHOD,
+    png::ParseWarning,
+};
+
+/// Outcome of [`check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageDataCheck {
+    /// The image data has the size the header calls for
+    Consistent,
+    /// The check could not be done, for the given reason
+    Skipped(&'static str),
+    /// The image data is too short or too long
+    Mismatch(ParseWarning),
+}
+
+/// Bytes of the inflated image data of a non-interlaced image, from its
+/// IHDR data. `None` for a malformed header or a color type and bit depth
+/// the specification doesn't allow together.
+pub fn expected_size(ihdr: &[u8]) -> Option<u64> {
+    let (width, rest) = ihdr.split_first_chunk::<4>()?;
+    let height = rest.first_chunk::<4>()?;
+    let format = ColorFormat::from_ihdr(ihdr)?;
+    let row = format.scanline_len(u32::from_be_bytes(*width));
+
+    // Every row starts with its filter type
+    Some(u64::from(u32::from_be_bytes(*height)) * (1 + row))
+}
+
+/// Compares the inflated size of the IDAT chunks of `chunks` with the size
+/// their IHDR calls for
+pub fn check(chunks: &[ChunkRef]) -> ImageDataCheck {
+    let Some(ihdr) = chunks
+        .iter()
+        .find(|chunk| chunk.chunk_type.bytes() == *b"IHDR")
+    else {
+        return ImageDataCheck::Skipped("the image has no IHDR chunk");
+    };
+    if ihdr
+        .data
+        .get(INTERLACE_METHOD)
+        .is_some_and(|&method| method != 0)
+    {
+        return ImageDataCheck::Skipped("interlaced images are not checked yet");
+    }
+    let Some(expected) = expected_size(ihdr.data) else {
+        return ImageDataCheck::Skipped(match ihdr.data.get(BIT_DEPTH + 1) {
+            Some(_) => "the IHDR chunk has an invalid color type and bit depth",
+            None => "the IHDR chunk is malformed",
+        });
+    };
+
+    let idat: Vec<&ChunkRef> = chunks
+        .iter()
+        .filter(|chunk| chunk.chunk_type.bytes() == *b"IDAT")
+        .collect();
+    let (Some(first), Some(last)) = (idat.first(), idat.last()) else {
+        return ImageDataCheck::Skipped("the image has no IDAT chunk");
+    };
+
+    let (actual, complete) = inflated_size(idat.iter().map(|chunk| chunk.data));
+    if actual == expected && complete {
+        return ImageDataCheck::Consistent;
+    }
+
+    ImageDataCheck::Mismatch(ParseWarning::ImageDataSize {
+        range: first.offset..last.offset + CHUNK_OVERHEAD as u64 + last.data.len() as u64,
+        expected,
+        actual,
+        complete,
+    })
+}
+
+/// Bytes the zlib stream split across `parts` inflates to, and whether the
+/// stream is complete rather than cut short or corrupt
+fn inflated_size<'a>(parts: impl Iterator<Item = &'a [u8]>) -> (u64, bool) {
+    let mut decoder = ZlibDecoder::new(Parts {
+        parts,
+        current: &[],
+    });
+    let mut buffer = [0; 16 * 1024];
+    let mut total = 0;
+
+    loop {
+        match decoder.read(&mut buffer) {
+            Ok(0) => return (total, true),
+            Ok(read) => total += read as u64,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => return (total, false),
+        }
+    }
+}
+
+/// Reads the slices of `parts` one after the other
+struct Parts<'a, I> {
+    parts: I,
+    current: &'a [u8],
+}
+
+impl<'a, I: Iterator<Item = &'a [u8]>> Read for Parts<'a, I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.parts.next() {
+                Some(part) => self.current = part,
+                None => return Ok(0),
+            }
+        }
+
+        self.current.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::ZlibEncoder};
+
+    use super::*;
+
+    fn ihdr(width: u32, height: u32, bit_depth: u8, color_type: u8) -> Vec<u8> {
+        [
+            &width.to_be_bytes()[..],
+            &height.to_be_bytes(),
+            &[bit_depth, color_type, 0, 0, 0],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_expected_size() {
+        // RGBA 8 bits: 4 bytes per pixel
+        assert_eq!(expected_size(&ihdr(4000, 3000, 8, 6)), Some(3000 * 16001));
+        // 1-bit grayscale: 3 pixels fit in one byte
+        assert_eq!(expected_size(&ihdr(3, 2, 1, 0)), Some(2 * 2));
+        // 16-bit RGB: 6 bytes per pixel
+        assert_eq!(expected_size(&ihdr(1, 1, 16, 2)), Some(7));
+        // 2-bit indexed: 4 pixels per byte
+        assert_eq!(expected_size(&ihdr(5, 1, 2, 3)), Some(1 + 2));
+        assert_eq!(expected_size(&ihdr(1, 1, 8, 5)), None);
+        // Indexed images have no 16-bit palette index
+        assert_eq!(expected_size(&ihdr(1, 1, 16, 3)), None);
+        assert_eq!(expected_size(&ihdr(1, 1, 4, 2)), None);
+        assert_eq!(expected_size(&[0, 0]), None);
+    }
+
+    #[test]
+    fn test_inflated_size_across_parts() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[7; 1000]).unwrap();
+        let stream = encoder.finish().unwrap();
+        let (head, tail) = stream.split_at(stream.len() / 2);
+
+        assert_eq!(inflated_size([head, tail].into_iter()), (1000, true));
+        assert!(!inflated_size([head].into_iter()).1);
+    }
+}
@@ -0,0 +1,130 @@
+//! Inflating zlib streams with a cap on the output.
+//!
+//! A few hundred compressed bytes can inflate to gigabytes, so every
+//! compressed chunk payload (zTXt, iTXt, iCCP and `encode --compress`
+//! messages) goes through [`inflate`]. The stream is inflated as it is
+//! read and given up once it passes the limit, nothing is allocated from a
+//! size the data declares.
+
+use std::{
+    fmt::Display,
+    io::{self, Read},
+};
+
+use flate2::read::ZlibDecoder;
+use thiserror::Error;
+
+use crate::codes::Code;
+
+/// Limit of [`inflate`] unless `--max-decompressed-size` raises it
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: u64 = 16 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum InflateError {
+    #[error(
+        "Decompressed data exceeds the limit of {limit} bytes{} (pass --max-decompressed-size to raise it)",
+        chunk.as_ref().map(|chunk| format!(" (chunk {chunk})")).unwrap_or_default()
+    )]
+    TooLarge {
+        limit: u64,
+        /// Type and index of the chunk holding the data, when known
+        chunk: Option<String>,
+    },
+
+    #[error("The compressed data is corrupted: {0}")]
+    Corrupt(io::Error),
+}
+
+impl InflateError {
+    pub fn code(&self) -> Code {
+        match self {
+            InflateError::TooLarge { .. } => Code::DecompressedTooLarge,
+            InflateError::Corrupt(_) => Code::CorruptPayload,
+        }
+    }
+
+    /// The error naming the chunk the data comes from, e.g. `zTXt #3`
+    pub fn in_chunk(self, chunk_type: impl Display, index: usize) -> Self {
+        match self {
+            InflateError::TooLarge { limit, .. } => InflateError::TooLarge {
+                limit,
+                chunk: Some(format!("{chunk_type} #{index}")),
+            },
+            err => err,
+        }
+    }
+}
+
+/// Inflates the zlib stream `data`, failing once the output passes `limit`
+/// bytes
+pub fn inflate(data: &[u8], limit: u64) -> Result<Vec<u8>, InflateError> {
+    let mut output = Vec::new();
+    // One byte more tells a stream ending right at the limit from a longer one
+    ZlibDecoder::new(data)
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut output)
+        .map_err(InflateError::Corrupt)?;
+
+    if output.len() as u64 > limit {
+        return Err(InflateError::TooLarge { limit, chunk: None });
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::ZlibEncoder};
+
+    use super::*;
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_inflate_up_to_the_limit() {
+        let data = deflate(&[b'a'; 100]);
+
+        assert_eq!(inflate(&data, 100).unwrap(), [b'a'; 100]);
+        assert!(matches!(
+            inflate(&data, 99),
+            Err(InflateError::TooLarge {
+                limit: 99,
+                chunk: None
+            })
+        ));
+    }
+
+    #[test]
+    fn test_high_ratio_stream_stops_at_the_limit() {
+        // 64 MiB of zeros deflate to about 64 KiB
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        for _ in 0..64 {
+            encoder.write_all(&[0; 1024 * 1024]).unwrap();
+        }
+        let bomb = encoder.finish().unwrap();
+        assert!(bomb.len() < 128 * 1024);
+
+        let err = inflate(&bomb, DEFAULT_MAX_DECOMPRESSED_SIZE)
+            .unwrap_err()
+            .in_chunk("zTXt", 3);
+        assert_eq!(err.code(), Code::DecompressedTooLarge);
+        assert!(
+            err.to_string().starts_with(
+                "Decompressed data exceeds the limit of 16777216 bytes (chunk zTXt #3)"
+            )
+        );
+    }
+
+    #[test]
+    fn test_corrupt_stream() {
+        let err = inflate(b"not zlib", 100).unwrap_err();
+
+        assert!(matches!(err, InflateError::Corrupt(_)));
+        assert_eq!(err.in_chunk("zTXt", 3).code(), Code::CorruptPayload);
+    }
+}
@@ -0,0 +1,427 @@
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io::{self, Read},
+    path::PathBuf,
+    str::FromStr,
+    time::Instant,
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use percent_encoding::percent_decode_str;
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    cache::DownloadCache,
+    codes::Code,
+    download,
+    observer::{Observer, Stage},
+};
+
+#[derive(Error, Debug)]
+pub enum InputError {
+    #[error("Could not read {name}: {source}")]
+    Io { name: String, source: io::Error },
+
+    #[error("{name} is too large ({size} bytes, the limit is {limit} bytes)")]
+    TooLarge { name: String, size: u64, limit: u64 },
+
+    #[error("Invalid data URI: {0}")]
+    InvalidDataUri(&'static str),
+
+    #[error("Invalid file URL: {0}")]
+    InvalidFileUrl(Url),
+
+    #[error(transparent)]
+    Download(#[from] download::DownloadError),
+
+    #[cfg(feature = "archives")]
+    #[error(transparent)]
+    Archive(#[from] crate::archive::ArchiveError),
+}
+
+impl InputError {
+    pub fn code(&self) -> Code {
+        match self {
+            InputError::Io { .. } => Code::InputReadFailed,
+            InputError::TooLarge { .. } => Code::InputTooLarge,
+            InputError::InvalidDataUri(_) => Code::InvalidDataUri,
+            InputError::InvalidFileUrl(_) => Code::InvalidFileUrl,
+            InputError::Download(err) => err.code(),
+            #[cfg(feature = "archives")]
+            InputError::Archive(err) => err.code(),
+        }
+    }
+}
+
+/// Where the bytes of an image come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputSource {
+    /// A local file, also used for `file://` URLs
+    Path(PathBuf),
+    /// An http(s) URL to download
+    Url(Url),
+    /// The standard input, written `-` on the command line
+    Stdin,
+    /// Bytes already in memory, e.g. from a `data:` URI or a library caller
+    Bytes { name: String, bytes: Vec<u8> },
+    /// A member of a zip or tar archive, written `bundle.zip!sprites/hero.png`
+    #[cfg(feature = "archives")]
+    Archive { archive: PathBuf, member: String },
+}
+
+/// Limits applied when reading any input
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputOptions {
+    /// Inputs larger than this many bytes are rejected
+    pub max_size: Option<u64>,
+    /// Downloads are slowed down to this many bytes per second
+    pub limit_rate: Option<u64>,
+    /// Where downloads are cached, `None` to always download
+    pub cache: Option<DownloadCache>,
+}
+
+/// The resolved content of an input
+#[derive(Debug, PartialEq, Eq)]
+pub struct InputData {
+    /// Name used in messages: the path, the URL or a placeholder
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+impl InputSource {
+    /// Reads the whole input, reporting progress to `observer`
+    pub fn resolve(
+        &self,
+        options: &InputOptions,
+        observer: &dyn Observer,
+    ) -> Result<InputData, InputError> {
+        let name = self.to_string();
+        let start = Instant::now();
+        let io_error = |source| InputError::Io {
+            name: name.clone(),
+            source,
+        };
+
+        let bytes = match self {
+            InputSource::Path(path) => {
+                let file = File::open(path).map_err(io_error)?;
+                let total = file.metadata().map_err(io_error)?.len();
+                check_size(&name, total, options)?;
+
+                observer.on_progress(Stage::Read, 0, Some(total));
+                let bytes = read_limited(file, options).map_err(io_error)?;
+                observer.on_progress(Stage::Read, bytes.len() as u64, Some(total));
+
+                bytes
+            }
+            InputSource::Url(url) => download::fetch(url, options, observer)?,
+            InputSource::Stdin => read_limited(io::stdin().lock(), options).map_err(io_error)?,
+            InputSource::Bytes { bytes, .. } => bytes.clone(),
+            #[cfg(feature = "archives")]
+            InputSource::Archive { archive, member } => {
+                crate::archive::read_member(archive, member, options.max_size)?
+            }
+        };
+
+        check_size(&name, bytes.len() as u64, options)?;
+
+        let stage = match self {
+            InputSource::Url(_) => Stage::Download,
+            _ => Stage::Read,
+        };
+        observer.on_span(stage, start.elapsed(), bytes.len() as u64);
+
+        Ok(InputData { name, bytes })
+    }
+
+    /// File written by in-place operations: the file itself for local paths,
+    /// the URL's file name for downloads and `output.png` otherwise.
+    pub fn default_output(&self) -> PathBuf {
+        match self {
+            InputSource::Path(path) => path.clone(),
+            _ => PathBuf::from(self.file_name().unwrap_or_else(|| "output.png".to_string())),
+        }
+    }
+
+    /// Name of the file read, without its directory, `None` for the
+    /// standard input and bytes in memory
+    pub fn file_name(&self) -> Option<String> {
+        match self {
+            InputSource::Path(path) => Some(path.file_name()?.to_string_lossy().into_owned()),
+            InputSource::Url(url) => url
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .filter(|name| !name.is_empty())
+                .map(str::to_string),
+            InputSource::Stdin | InputSource::Bytes { .. } => None,
+            #[cfg(feature = "archives")]
+            InputSource::Archive { member, .. } => member
+                .rsplit('/')
+                .next()
+                .filter(|name| !name.is_empty())
+                .map(str::to_string),
+        }
+    }
+
+    /// Parses a command line argument, a `data:` URI is decoded right away
+    pub fn parse(arg: &str) -> Result<Self, InputError> {
+        if arg == "-" {
+            return Ok(InputSource::Stdin);
+        }
+
+        // A file whose name holds a `!` is still read as is
+        #[cfg(feature = "archives")]
+        if let Some((archive, member)) = crate::archive::split_member(arg)
+            && !std::path::Path::new(arg).exists()
+        {
+            return Ok(InputSource::Archive { archive, member });
+        }
+
+        let Ok(url) = Url::parse(arg) else {
+            return Ok(InputSource::Path(PathBuf::from(arg)));
+        };
+
+        match url.scheme() {
+            "http" | "https" => Ok(InputSource::Url(url)),
+            "file" => url
+                .to_file_path()
+                .map(InputSource::Path)
+                .map_err(|_| InputError::InvalidFileUrl(url)),
+            "data" => Ok(InputSource::Bytes {
+                name: "<data URI>".to_string(),
+                bytes: decode_data_uri(arg)?,
+            }),
+            // Anything else, e.g. a Windows path like C:\\image.png
+            _ => Ok(InputSource::Path(PathBuf::from(arg))),
+        }
+    }
+}
+
+impl FromStr for InputSource {
+    type Err = InputError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        InputSource::parse(s)
+    }
+}
+
+impl From<PathBuf> for InputSource {
+    fn from(path: PathBuf) -> Self {
+        InputSource::Path(path)
+    }
+}
+
+impl Display for InputSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputSource::Path(path) => write!(f, "{}", path.display()),
+            InputSource::Url(url) => write!(f, "{url}"),
+            InputSource::Stdin => write!(f, "<stdin>"),
+            InputSource::Bytes { name, .. } => write!(f, "{name}"),
+            #[cfg(feature = "archives")]
+            InputSource::Archive { archive, member } => write!(f, "{}!{member}", archive.display()),
+        }
+    }
+}
+
+fn check_size(name: &str, size: u64, options: &InputOptions) -> Result<(), InputError> {
+    match options.max_size {
+        Some(limit) if size > limit => Err(InputError::TooLarge {
+            name: name.to_string(),
+            size,
+            limit,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Reads at most one byte past the limit so oversized streams are detected
+/// without being read entirely.
+fn read_limited(reader: impl Read, options: &InputOptions) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    match options.max_size {
+        Some(limit) => reader.take(limit + 1).read_to_end(&mut bytes)?,
+        None => { reader }.read_to_end(&mut bytes)?,
+    };
+
+    Ok(bytes)
+}
+
+/// Decodes `data:[<media type>][;base64],<data>`
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>, InputError> {
+    let rest = uri
+        .strip_prefix("data:")
+        .ok_or(InputError::InvalidDataUri("missing data: prefix"))?;
+    let (metadata, data) = rest
+        .split_once(',')
+        .ok_or(InputError::InvalidDataUri("missing ',' separator"))?;
+
+    if metadata.ends_with(";base64") {
+        STANDARD
+            .decode(data)
+            .map_err(|_| InputError::InvalidDataUri("invalid base64 data"))
+    } else {
+        Ok(percent_decode_str(data).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observer::NoopObserver;
+    use std::{io::Write, net::TcpListener, thread};
+
+    fn resolve(source: &InputSource, max_size: Option<u64>) -> Result<InputData, InputError> {
+        source.resolve(
+            &InputOptions {
+                max_size,
+                ..InputOptions::default()
+            },
+            &NoopObserver,
+        )
+    }
+
+    /// Serves a single HTTP response on localhost and returns its URL
+    fn serve_once(body: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(body).unwrap();
+        });
+
+        format!("http://{addr}/images/cat.png")
+    }
+
+    #[test]
+    fn test_parse_stdin() {
+        assert_eq!(InputSource::parse("-").unwrap(), InputSource::Stdin);
+    }
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            InputSource::parse("images/cat.png").unwrap(),
+            InputSource::Path(PathBuf::from("images/cat.png"))
+        );
+    }
+
+    #[test]
+    fn test_parse_file_url() {
+        assert_eq!(
+            InputSource::parse("file:///tmp/cat.png").unwrap(),
+            InputSource::Path(PathBuf::from("/tmp/cat.png"))
+        );
+    }
+
+    #[test]
+    fn test_parse_http_url() {
+        let source = InputSource::parse("https://example.com/cat.png").unwrap();
+
+        assert!(matches!(source, InputSource::Url(_)));
+        assert_eq!(source.default_output(), PathBuf::from("cat.png"));
+    }
+
+    #[test]
+    fn test_parse_base64_data_uri() {
+        let source = InputSource::parse("data:image/png;base64,iVBORw==").unwrap();
+
+        assert_eq!(resolve(&source, None).unwrap().bytes, b"\x89PNG");
+    }
+
+    #[test]
+    fn test_parse_percent_data_uri() {
+        let source = InputSource::parse("data:,%89PNG").unwrap();
+
+        assert_eq!(resolve(&source, None).unwrap().bytes, b"\x89PNG");
+    }
+
+    #[test]
+    fn test_parse_invalid_data_uri() {
+        assert!(InputSource::parse("data:image/png;base64").is_err());
+        assert!(InputSource::parse("data:;base64,!!!").is_err());
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cat.png");
+        std::fs::write(&path, b"bytes").unwrap();
+
+        let data = resolve(&InputSource::Path(path.clone()), None).unwrap();
+
+        assert_eq!(data.bytes, b"bytes");
+        assert_eq!(data.name, path.display().to_string());
+    }
+
+    #[test]
+    fn test_resolve_missing_path() {
+        let source = InputSource::Path(PathBuf::from("does/not/exist.png"));
+
+        assert!(matches!(resolve(&source, None), Err(InputError::Io { .. })));
+    }
+
+    #[test]
+    fn test_resolve_bytes() {
+        let source = InputSource::Bytes {
+            name: "memory".to_string(),
+            bytes: vec![1, 2, 3],
+        };
+
+        let data = resolve(&source, None).unwrap();
+
+        assert_eq!(data.name, "memory");
+        assert_eq!(data.bytes, [1, 2, 3]);
+        assert_eq!(source.default_output(), PathBuf::from("output.png"));
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        let source = InputSource::parse(&serve_once(b"remote bytes")).unwrap();
+
+        assert_eq!(resolve(&source, None).unwrap().bytes, b"remote bytes");
+    }
+
+    #[test]
+    fn test_resolve_url_too_large() {
+        let source = InputSource::parse(&serve_once(b"remote bytes")).unwrap();
+
+        assert!(matches!(
+            resolve(&source, Some(4)),
+            Err(InputError::Download(download::DownloadError::TooLarge {
+                size: 12,
+                limit: 4,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_too_large() {
+        let source = InputSource::Bytes {
+            name: "memory".to_string(),
+            bytes: vec![0; 10],
+        };
+
+        assert!(matches!(
+            resolve(&source, Some(9)),
+            Err(InputError::TooLarge {
+                size: 10,
+                limit: 9,
+                ..
+            })
+        ));
+        assert!(resolve(&source, Some(10)).is_ok());
+    }
+}
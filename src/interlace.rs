@@ -0,0 +1,185 @@
+//! Adam7 interlacing.
+//!
+//! An interlaced image stores its pixels in seven passes instead of row by
+//! row. [`deinterlace`] re-encodes the image data of such a file without
+//! interlacing, with the `png` crate, and leaves every other chunk as is.
+//! Animated images are refused: the frames of their fdAT chunks would stay
+//! interlaced under an IHDR saying they are not.
+
+use thiserror::Error;
+
+use crate::{
+    apng,
+    chunk::{Chunk, ChunkParserError},
+    chunk_ref::chunk_refs,
+    codes::Code,
+    png::{ParseOptions, Png, PngError},
+};
+
+/// Offset of the interlace method in the IHDR data
+pub const INTERLACE_METHOD: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum InterlaceError {
+    #[error("The image has no IHDR chunk")]
+    MissingHeader,
+
+    #[error("The image is animated, only its first frame could be deinterlaced")]
+    Animated,
+
+    #[error("Could not decode the image data: {0}")]
+    Decode(#[from] ::png::DecodingError),
+
+    #[error("Could not encode the image data: {0}")]
+    Encode(#[from] ::png::EncodingError),
+
+    #[error(transparent)]
+    Png(#[from] PngError),
+
+    #[error(transparent)]
+    Chunk(#[from] ChunkParserError),
+}
+
+impl InterlaceError {
+    pub fn code(&self) -> Code {
+        match self {
+            InterlaceError::MissingHeader => Code::MissingHeader,
+            InterlaceError::Animated => Code::AnimatedImage,
+            InterlaceError::Decode(_) => Code::ImageDecodeFailed,
+            InterlaceError::Encode(_) => Code::ImageEncodeFailed,
+            InterlaceError::Png(err) => err.code(),
+            InterlaceError::Chunk(err) => err.code(),
+        }
+    }
+}
+
+/// Whether the IHDR of `png` declares Adam7 interlacing
+pub fn is_interlaced(png: &Png) -> bool {
+    png.chunk_by_type("IHDR")
+        .and_then(|ihdr| ihdr.data().get(INTERLACE_METHOD))
+        .is_some_and(|&method| method != 0)
+}
+
+/// `png` with its image data stored without interlacing. The IHDR only
+/// changes its interlace method, the new IDAT chunks take the place of the
+/// old ones and the other chunks are kept in place.
+pub fn deinterlace(png: &Png) -> Result<Png, InterlaceError> {
+    if apng::is_animated(png) || png.chunk_by_type("fdAT").is_some() {
+        return Err(InterlaceError::Animated);
+    }
+    let header = png
+        .chunk_by_type("IHDR")
+        .ok_or(InterlaceError::MissingHeader)?;
+
+    let mut decoder = ::png::Decoder::new(std::io::Cursor::new(png.as_bytes()));
+    decoder.set_transformations(::png::Transformations::IDENTITY);
+    let mut reader = decoder.read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let frame = reader.next_frame(&mut pixels)?;
+    let info = reader.info();
+
+    let mut encoded = Vec::new();
+    {
+        let mut encoder = ::png::Encoder::new(&mut encoded, info.width, info.height);
+        encoder.set_color(info.color_type);
+        encoder.set_depth(info.bit_depth);
+        if let Some(palette) = &info.palette {
+            encoder.set_palette(palette.to_vec());
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&pixels[..frame.buffer_size()])?;
+    }
+
+    let mut idats = Vec::new();
+    for chunk in chunk_refs(&encoded, false, ParseOptions::DEFAULT_MAX_CHUNKS)? {
+        let chunk = chunk?;
+        if chunk.chunk_type.bytes() == *b"IDAT" {
+            idats.push(chunk.to_owned());
+        }
+    }
+
+    let mut data = header.data().to_vec();
+    data[INTERLACE_METHOD] = 0;
+    let header = Chunk::try_new(*header.chunk_type(), data)?;
+
+    let mut chunks = Vec::with_capacity(png.chunks().len());
+    for chunk in png.chunks() {
+        match &chunk.chunk_type().bytes() {
+            b"IHDR" => chunks.push(header.clone()),
+            // The whole new run replaces the first IDAT, the others go
+            b"IDAT" => chunks.append(&mut idats),
+            _ => chunks.push(chunk.clone()),
+        }
+    }
+
+    Ok(Png::from_chunks(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{FixtureKind, make_fixture};
+
+    fn pixels(bytes: &[u8]) -> Vec<u8> {
+        let mut reader = ::png::Decoder::new(std::io::Cursor::new(bytes))
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        let frame = reader.next_frame(&mut pixels).unwrap();
+        pixels.truncate(frame.buffer_size());
+        pixels
+    }
+
+    #[test]
+    fn test_deinterlace_keeps_pixels() {
+        let bytes = make_fixture(FixtureKind::Interlaced);
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        assert!(is_interlaced(&png));
+
+        let deinterlaced = deinterlace(&png).unwrap();
+
+        assert!(!is_interlaced(&deinterlaced));
+        assert_eq!(pixels(&deinterlaced.as_bytes()), pixels(&bytes));
+    }
+
+    #[test]
+    fn test_deinterlace_keeps_other_chunks() {
+        let bytes = make_fixture(FixtureKind::Interlaced);
+        let mut png = Png::try_from(bytes.as_slice()).unwrap();
+        let note = Chunk::new("ruSt".parse().unwrap(), b"hidden".to_vec());
+        png.append_chunk(note.clone());
+
+        let deinterlaced = deinterlace(&png).unwrap();
+
+        assert_eq!(deinterlaced.chunk_by_type("ruSt"), Some(&note));
+        let types: Vec<String> = deinterlaced
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types.first().map(String::as_str), Some("IHDR"));
+        assert_eq!(types.last().map(String::as_str), Some("ruSt"));
+    }
+
+    #[test]
+    fn test_deinterlace_refuses_animations() {
+        let bytes = make_fixture(FixtureKind::Interlaced);
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+
+        for chunk_type in ["acTL", "fdAT"] {
+            let mut animated = Png::from_chunks(png.chunks().to_vec());
+            let chunk = Chunk::new(chunk_type.parse().unwrap(), vec![0; 8]);
+            animated.insert_chunk(1, chunk).unwrap();
+
+            assert!(
+                matches!(deinterlace(&animated), Err(InterlaceError::Animated)),
+                "{chunk_type}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_minimal_image_is_not_interlaced() {
+        assert!(!is_interlaced(&Png::new_minimal()));
+    }
+}
@@ -0,0 +1,363 @@
+//! Human-readable descriptions of the data of known chunk types, shown by
+//! `print`, `info` and `scan`.
+//!
+//! Each chunk type is described by a [`ChunkInterpreter`]. A [`Registry`]
+//! starts with the built-in interpreters and takes more with
+//! [`Registry::register`], so applications using pngme as a library can
+//! describe their own private chunks:
+//!
+//! ```
+//! use pngme::{chunk_type::ChunkType, interpret::{ChunkInterpreter, Registry}};
+//!
+//! struct Version;
+//!
+//! impl ChunkInterpreter for Version {
+//!     fn matches(&self, chunk_type: &ChunkType) -> bool {
+//!         chunk_type.bytes() == *b"vrSn"
+//!     }
+//!
+//!     fn describe(&self, data: &[u8]) -> Option<String> {
+//!         Some(format!("version {}", data.first()?))
+//!     }
+//! }
+//!
+//! let mut registry = Registry::default();
+//! registry.register(Version);
+//! ```
+
+use std::sync::LazyLock;
+
+use crate::{
+    chunk_type::ChunkType,
+    inflate::{DEFAULT_MAX_DECOMPRESSED_SIZE, InflateError},
+    metadata::{METADATA_CHUNK, Metadata},
+    text::{ItxtChunk, ZtxtChunk, split_text_chunk},
+};
+
+/// Describes the data of the chunk types it matches
+pub trait ChunkInterpreter: Send + Sync {
+    fn matches(&self, chunk_type: &ChunkType) -> bool;
+
+    /// One-line description of `data`, `None` when it is malformed
+    fn describe(&self, data: &[u8]) -> Option<String>;
+}
+
+/// Interpreters consulted in turn, the last registered first
+pub struct Registry {
+    interpreters: Vec<Box<dyn ChunkInterpreter>>,
+}
+
+static BUILTIN: LazyLock<Registry> = LazyLock::new(Registry::default);
+
+impl Registry {
+    /// A registry without any interpreter
+    pub fn empty() -> Self {
+        Self {
+            interpreters: Vec::new(),
+        }
+    }
+
+    /// The built-in interpreters, shared
+    pub fn builtin() -> &'static Registry {
+        &BUILTIN
+    }
+
+    /// Adds `interpreter`, consulted before the ones already registered so
+    /// that it can override them
+    pub fn register(&mut self, interpreter: impl ChunkInterpreter + 'static) {
+        self.interpreters.push(Box::new(interpreter));
+    }
+
+    /// Description of a chunk by the last registered interpreter matching
+    /// its type and understanding its data
+    pub fn describe(&self, chunk_type: &ChunkType, data: &[u8]) -> Option<String> {
+        self.interpreters
+            .iter()
+            .rev()
+            .filter(|interpreter| interpreter.matches(chunk_type))
+            .find_map(|interpreter| interpreter.describe(data))
+    }
+}
+
+impl Default for Registry {
+    /// The built-in interpreters: tEXt, zTXt, iTXt, tIME, pHYs, gAMA and the
+    /// meTa chunks of `encode --meta`
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Text);
+        registry.register(CompressedText::default());
+        registry.register(InternationalText::default());
+        registry.register(Time);
+        registry.register(PhysicalDimensions);
+        registry.register(Gamma);
+        registry.register(PayloadMetadata);
+        registry
+    }
+}
+
+/// tEXt: a Latin-1 keyword and text, separated by a NUL byte
+pub struct Text;
+
+impl ChunkInterpreter for Text {
+    fn matches(&self, chunk_type: &ChunkType) -> bool {
+        chunk_type.bytes() == *b"tEXt"
+    }
+
+    fn describe(&self, data: &[u8]) -> Option<String> {
+        let (keyword, text) = split_text_chunk(data)?;
+
+        Some(format!("{keyword}: {text}"))
+    }
+}
+
+/// zTXt: a Latin-1 keyword, a NUL byte, the compression method and the
+/// compressed text
+pub struct CompressedText {
+    /// Bytes the text may inflate to
+    pub limit: u64,
+}
+
+impl Default for CompressedText {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_MAX_DECOMPRESSED_SIZE,
+        }
+    }
+}
+
+impl ChunkInterpreter for CompressedText {
+    fn matches(&self, chunk_type: &ChunkType) -> bool {
+        chunk_type.bytes() == *b"zTXt"
+    }
+
+    fn describe(&self, data: &[u8]) -> Option<String> {
+        let chunk = ZtxtChunk::parse(data).ok()?;
+
+        let text = describe_inflated(chunk.text(self.limit))?;
+        Some(format!("{}: {text}", chunk.keyword))
+    }
+}
+
+/// iTXt: a Latin-1 keyword, compression flag and method, language tag,
+/// translated keyword and the UTF-8 text, compressed or not
+pub struct InternationalText {
+    /// Bytes a compressed text may inflate to
+    pub limit: u64,
+}
+
+impl Default for InternationalText {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_MAX_DECOMPRESSED_SIZE,
+        }
+    }
+}
+
+impl ChunkInterpreter for InternationalText {
+    fn matches(&self, chunk_type: &ChunkType) -> bool {
+        chunk_type.bytes() == *b"iTXt"
+    }
+
+    fn describe(&self, data: &[u8]) -> Option<String> {
+        let chunk = ItxtChunk::parse(data)?;
+
+        let text = describe_inflated(chunk.text(self.limit))?;
+        Some(format!("{}: {text}", chunk.header.keyword))
+    }
+}
+
+/// The inflated text, or a note when it passes the limit. `None` when the
+/// stream is corrupted.
+fn describe_inflated(text: Result<String, InflateError>) -> Option<String> {
+    match text {
+        Ok(text) => Some(text),
+        Err(InflateError::TooLarge { limit, .. }) => Some(format!(
+            "[decompressed data exceeds the limit of {limit} bytes]"
+        )),
+        Err(InflateError::Corrupt(_)) => None,
+    }
+}
+
+/// tIME: the UTC time of the last modification
+pub struct Time;
+
+impl ChunkInterpreter for Time {
+    fn matches(&self, chunk_type: &ChunkType) -> bool {
+        chunk_type.bytes() == *b"tIME"
+    }
+
+    fn describe(&self, data: &[u8]) -> Option<String> {
+        let &[year_high, year_low, month, day, hour, minute, second] = data else {
+            return None;
+        };
+        let year = u16::from_be_bytes([year_high, year_low]);
+
+        Some(format!(
+            "modified on {year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02} UTC"
+        ))
+    }
+}
+
+/// pHYs: the pixel density, or only the aspect ratio of the pixels
+pub struct PhysicalDimensions;
+
+impl ChunkInterpreter for PhysicalDimensions {
+    fn matches(&self, chunk_type: &ChunkType) -> bool {
+        chunk_type.bytes() == *b"pHYs"
+    }
+
+    fn describe(&self, data: &[u8]) -> Option<String> {
+        let (x, rest) = data.split_first_chunk::<4>()?;
+        let (y, unit) = rest.split_first_chunk::<4>()?;
+        let (x, y) = (u32::from_be_bytes(*x), u32::from_be_bytes(*y));
+
+        match unit {
+            [0] => Some(format!("pixel aspect ratio {x}:{y}")),
+            [1] if x == y => Some(format!(
+                "{x} pixels per meter ({:.0} dpi)",
+                x as f64 * 0.0254
+            )),
+            [1] => Some(format!("{x} x {y} pixels per meter")),
+            _ => None,
+        }
+    }
+}
+
+/// gAMA: the gamma of the image, stored times 100000
+pub struct Gamma;
+
+impl ChunkInterpreter for Gamma {
+    fn matches(&self, chunk_type: &ChunkType) -> bool {
+        chunk_type.bytes() == *b"gAMA"
+    }
+
+    fn describe(&self, data: &[u8]) -> Option<String> {
+        let gamma = u32::from_be_bytes(data.try_into().ok()?);
+
+        Some(format!("gamma {:.5}", gamma as f64 / 100_000.0))
+    }
+}
+
+/// meTa: the metadata `encode --meta` stores next to a message
+pub struct PayloadMetadata;
+
+impl ChunkInterpreter for PayloadMetadata {
+    fn matches(&self, chunk_type: &ChunkType) -> bool {
+        chunk_type.to_string() == METADATA_CHUNK
+    }
+
+    fn describe(&self, data: &[u8]) -> Option<String> {
+        Some(Metadata::parse(data)?.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, str::FromStr};
+
+    use flate2::{Compression, write::ZlibEncoder};
+
+    use super::*;
+    use crate::text::latin1_decode;
+
+    fn describe(chunk_type: &str, data: &[u8]) -> Option<String> {
+        Registry::builtin().describe(&ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    #[test]
+    fn test_builtin_interpreters() {
+        assert_eq!(
+            describe("tEXt", b"Title\0Caf\xe9").as_deref(),
+            Some("Title: Café")
+        );
+        assert_eq!(
+            describe("tIME", &[0x07, 0xe8, 2, 29, 13, 5, 9]).as_deref(),
+            Some("modified on 2024-02-29 13:05:09 UTC")
+        );
+        assert_eq!(
+            describe("pHYs", &[0, 0, 11, 19, 0, 0, 11, 19, 1]).as_deref(),
+            Some("2835 pixels per meter (72 dpi)")
+        );
+        assert_eq!(
+            describe("pHYs", &[0, 0, 0, 2, 0, 0, 0, 1, 0]).as_deref(),
+            Some("pixel aspect ratio 2:1")
+        );
+        assert_eq!(
+            describe("gAMA", &45455_u32.to_be_bytes()).as_deref(),
+            Some("gamma 0.45455")
+        );
+    }
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_compressed_text() {
+        let ztxt = [&b"Title\0\0"[..], &deflate(b"Caf\xe9")].concat();
+        assert_eq!(describe("zTXt", &ztxt).as_deref(), Some("Title: Café"));
+
+        let itxt = [
+            &b"Title\0\x01\0fr\0Titre\0"[..],
+            &deflate("Café".as_bytes()),
+        ]
+        .concat();
+        assert_eq!(describe("iTXt", &itxt).as_deref(), Some("Title: Café"));
+        assert_eq!(
+            describe("iTXt", b"Title\0\0\0\0\0plain").as_deref(),
+            Some("Title: plain")
+        );
+    }
+
+    #[test]
+    fn test_compressed_text_over_the_limit() {
+        let ztxt = [&b"Title\0\0"[..], &deflate(&[b'a'; 100])].concat();
+        let limited = CompressedText { limit: 99 };
+
+        assert_eq!(
+            limited.describe(&ztxt).as_deref(),
+            Some("Title: [decompressed data exceeds the limit of 99 bytes]")
+        );
+        assert_eq!(
+            CompressedText { limit: 100 }.describe(&ztxt).unwrap().len(),
+            107
+        );
+    }
+
+    #[test]
+    fn test_malformed_or_unknown_chunks_are_not_described() {
+        assert_eq!(describe("zTXt", b"Title\0\0not zlib"), None);
+        assert_eq!(describe("iTXt", b"Title\0\x01\x05\0\0text"), None);
+        assert_eq!(describe("tEXt", b"no separator"), None);
+        assert_eq!(describe("tIME", &[0x07, 0xe8]), None);
+        assert_eq!(describe("gAMA", &[1, 2, 3, 4, 5]), None);
+        assert_eq!(describe("ruSt", b"hidden message"), None);
+    }
+
+    #[test]
+    fn test_registered_interpreters_come_first() {
+        struct Shouting;
+
+        impl ChunkInterpreter for Shouting {
+            fn matches(&self, chunk_type: &ChunkType) -> bool {
+                chunk_type.bytes() == *b"tEXt"
+            }
+
+            fn describe(&self, data: &[u8]) -> Option<String> {
+                Some(latin1_decode(data).to_uppercase())
+            }
+        }
+
+        let mut registry = Registry::default();
+        registry.register(Shouting);
+
+        assert_eq!(
+            registry
+                .describe(&ChunkType::from_str("tEXt").unwrap(), b"hi")
+                .as_deref(),
+            Some("HI")
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Journal of a batch, for `encode --journal` and `--resume`.
+//!
+//! Each line is a JSON object recording one step on one file of the batch:
+//! `started` before the file is read, with the SHA-256 of its content, then
+//! `done` or `failed`. Lines are synced to disk as they are written, so a
+//! batch that dies leaves at most its last line cut short, which resuming
+//! drops.
+//!
+//! Every line carries the digest of the operation, computed from the
+//! options and the message. Resuming with other ones would leave the files
+//! edited two different ways, it fails instead.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{codes::Code, hash::sha256_hex};
+
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("Could not access the journal {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Invalid journal {}, line {line}: {source}", path.display())]
+    Invalid {
+        path: PathBuf,
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error(
+        "The journal {} records another operation, the options or the message changed since; \
+         start a new journal with --journal",
+        path.display()
+    )]
+    OtherOperation { path: PathBuf },
+}
+
+impl JournalError {
+    pub fn code(&self) -> Code {
+        match self {
+            JournalError::Io { .. } => Code::JournalIoFailed,
+            JournalError::Invalid { .. } => Code::InvalidJournal,
+            JournalError::OtherOperation { .. } => Code::JournalMismatch,
+        }
+    }
+}
+
+/// How far a file of the batch got
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// About to be read, the output may or may not have been written
+    Started,
+    Done,
+    Failed,
+}
+
+/// One line of the journal
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    path: PathBuf,
+    operation: String,
+    status: Status,
+    /// Digest of the file before the operation, on `started` lines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+}
+
+/// The journal of a running batch, appended to as the files are processed
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    operation: String,
+    /// The last line recorded for each file, with the digest of its
+    /// `started` line
+    files: HashMap<PathBuf, (Status, Option<String>)>,
+}
+
+impl Journal {
+    /// Starts a new journal at `path`, replacing any file there
+    pub fn create(path: &Path, operation: &str) -> Result<Self, JournalError> {
+        let io_error = |source| JournalError::Io { path: path.to_path_buf(), source };
+        let file = File::create(path).map_err(io_error)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            operation: operation.to_string(),
+            files: HashMap::new(),
+        })
+    }
+
+    /// Reopens the journal at `path` to go on with the batch it records. A
+    /// last line cut short by the end of the previous run is dropped. Fails
+    /// when a line records another operation.
+    pub fn resume(path: &Path, operation: &str) -> Result<Self, JournalError> {
+        let io_error = |source| JournalError::Io { path: path.to_path_buf(), source };
+        let content = fs::read(path).map_err(io_error)?;
+        // Only complete lines count
+        let complete = content.iter().rposition(|&byte| byte == b'\n').map_or(0, |end| end + 1);
+
+        let mut files = HashMap::new();
+        for (number, line) in content[..complete].split(|&byte| byte == b'\n').enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let entry: Entry = serde_json::from_slice(line).map_err(|source| JournalError::Invalid {
+                path: path.to_path_buf(),
+                line: number + 1,
+                source,
+            })?;
+            if entry.operation != operation {
+                return Err(JournalError::OtherOperation { path: path.to_path_buf() });
+            }
+            let sha256 = match entry.status {
+                Status::Started => entry.sha256,
+                _ => files.remove(&entry.path).and_then(|(_, sha256)| sha256),
+            };
+            files.insert(entry.path, (entry.status, sha256));
+        }
+
+        let file = OpenOptions::new().write(true).open(path).map_err(io_error)?;
+        file.set_len(complete as u64).map_err(io_error)?;
+        let file = OpenOptions::new().append(true).open(path).map_err(io_error)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            operation: operation.to_string(),
+            files,
+        })
+    }
+
+    /// Whether an earlier run already processed `file`: it is recorded
+    /// `done`, or `started` and its content changed since, the run having
+    /// stopped after its output replaced it. The latter is recorded `done`
+    /// now.
+    pub fn is_done(&mut self, file: &Path) -> Result<bool, JournalError> {
+        match self.files.get(file) {
+            Some((Status::Done, _)) => Ok(true),
+            Some((Status::Started, Some(before))) if content_digest(file).is_some_and(|now| now != *before) => {
+                self.record(file, Status::Done, None)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Records that `file` is about to be processed, along with the digest
+    /// of its content
+    pub fn start(&mut self, file: &Path) -> Result<(), JournalError> {
+        self.record(file, Status::Started, content_digest(file))
+    }
+
+    /// Appends a line and syncs it to disk
+    pub fn record(&mut self, file: &Path, status: Status, sha256: Option<String>) -> Result<(), JournalError> {
+        let entry = Entry {
+            path: file.to_path_buf(),
+            operation: self.operation.clone(),
+            status,
+            sha256,
+        };
+        let mut line = serde_json::to_vec(&entry).expect("journal entries serialize");
+        line.push(b'\n');
+
+        let io_error = |source| JournalError::Io { path: self.path.clone(), source };
+        self.file.write_all(&line).map_err(io_error)?;
+        self.file.sync_data().map_err(io_error)?;
+        self.files.insert(entry.path, (entry.status, entry.sha256));
+
+        Ok(())
+    }
+}
+
+/// SHA-256 of the content of `file`, `None` when it can't be read
+fn content_digest(file: &Path) -> Option<String> {
+    fs::read(file).ok().map(|bytes| sha256_hex(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_drops_a_cut_line_and_appends_after_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("batch.jsonl");
+        let image = dir.path().join("a.png");
+        fs::write(&image, b"before").unwrap();
+
+        let mut journal = Journal::create(&path, "op").unwrap();
+        journal.start(&image).unwrap();
+        journal.record(&image, Status::Done, None).unwrap();
+        drop(journal);
+        let mut content = fs::read(&path).unwrap();
+        content.extend_from_slice(br#"{"path":"b.png","oper"#);
+        fs::write(&path, content).unwrap();
+
+        let mut journal = Journal::resume(&path, "op").unwrap();
+        assert!(journal.is_done(&image).unwrap());
+        journal.start(Path::new("b.png")).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 3);
+        assert!(content.lines().all(|line| serde_json::from_str::<Entry>(line).is_ok()), "{content}");
+    }
+
+    #[test]
+    fn started_files_are_done_once_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("batch.jsonl");
+        let image = dir.path().join("a.png");
+        fs::write(&image, b"before").unwrap();
+
+        Journal::create(&path, "op").unwrap().start(&image).unwrap();
+        assert!(!Journal::resume(&path, "op").unwrap().is_done(&image).unwrap());
+
+        fs::write(&image, b"after").unwrap();
+        assert!(Journal::resume(&path, "op").unwrap().is_done(&image).unwrap());
+    }
+
+    #[test]
+    fn resume_refuses_another_operation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("batch.jsonl");
+        Journal::create(&path, "op").unwrap().start(Path::new("a.png")).unwrap();
+
+        let err = Journal::resume(&path, "other").err().unwrap();
+        assert_eq!(err.code(), Code::JournalMismatch);
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(feature = "archives")]
+pub mod archive;
+pub mod apng;
+pub mod args;
+pub mod build_info;
+pub mod cache;
+pub mod canonical;
+pub mod capacity;
+pub mod capabilities;
+pub mod chunk;
+pub mod chunk_ref;
+pub mod chunk_type;
+pub mod clock;
+pub mod codes;
+pub mod color;
+pub mod commands;
+pub mod consts;
+pub mod crypto;
+pub mod download;
+pub mod envelope;
+pub mod error;
+pub mod exit_status;
+pub mod external;
+pub mod fixtures;
+pub mod format;
+pub mod glob;
+pub mod hash;
+pub mod icc;
+pub mod image_data;
+pub mod inflate;
+pub mod input;
+pub mod interlace;
+pub mod interpret;
+pub mod journal;
+pub mod lock;
+pub mod mac;
+pub mod meta;
+pub mod metadata;
+pub mod observer;
+pub mod patch;
+pub mod pipe;
+pub mod png;
+pub mod profiles;
+pub mod sanitize;
+pub mod sink;
+pub mod split;
+pub mod stats;
+pub mod scan;
+pub mod secret;
+pub mod selector;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod signing;
+pub mod sniff;
+pub mod survivability;
+pub mod temp;
+pub mod template;
+#[cfg(test)]
+pub(crate) mod test_fixtures;
+pub mod text;
+pub mod timings;
+pub mod transaction;
+pub mod undo;
+pub mod upload_limits;
+pub mod validate;
+pub mod walk;
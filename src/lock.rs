@@ -0,0 +1,127 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+use crate::codes::Code;
+
+/// Delay between two attempts at taking a busy lock
+const RETRY_DELAY: Duration = Duration::from_millis(50);
+
+#[derive(Error, Debug)]
+pub enum LockError {
+    #[error("Could not lock {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{path}: file is locked by another process")]
+    Locked { path: PathBuf },
+}
+
+impl LockError {
+    pub fn code(&self) -> Code {
+        match self {
+            LockError::Io { .. } => Code::LockFailed,
+            LockError::Locked { .. } => Code::FileLocked,
+        }
+    }
+}
+
+/// Exclusive advisory lock (`flock` on Unix, `LockFileEx` on Windows) held
+/// on a file during a read-modify-write, released when dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    /// Takes the lock on `path`, waiting up to `timeout` for other holders
+    pub fn acquire(path: &Path, timeout: Duration) -> Result<Self, LockError> {
+        let io_error = |source| LockError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let file = OpenOptions::new().read(true).open(path).map_err(io_error)?;
+
+            match file.try_lock() {
+                // Writes replace the file, so the lock we waited for may be on
+                // a file that is no longer at `path`
+                Ok(()) if is_current(&file, path).map_err(io_error)? => return Ok(Self { file }),
+                Ok(()) => continue,
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    thread::sleep(RETRY_DELAY)
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(LockError::Locked {
+                        path: path.to_path_buf(),
+                    });
+                }
+                Err(TryLockError::Error(source)) => return Err(io_error(source)),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_current(file: &File, path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (locked, current) = (file.metadata()?, std::fs::metadata(path)?);
+
+    Ok(locked.dev() == current.dev() && locked.ino() == current.ino())
+}
+
+// No stable file identity on Windows, trust the lock
+#[cfg(not(unix))]
+fn is_current(_file: &File, _path: &Path) -> io::Result<bool> {
+    Ok(true)
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock anyway
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_exclusive_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        std::fs::write(&path, b"png").unwrap();
+
+        let lock = FileLock::acquire(&path, Duration::ZERO).unwrap();
+        assert!(matches!(
+            FileLock::acquire(&path, Duration::from_millis(100)),
+            Err(LockError::Locked { .. })
+        ));
+
+        drop(lock);
+        assert!(FileLock::acquire(&path, Duration::ZERO).is_ok());
+    }
+
+    #[test]
+    fn test_lock_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert!(matches!(
+            FileLock::acquire(&dir.path().join("missing.png"), Duration::ZERO),
+            Err(LockError::Io { .. })
+        ));
+    }
+}
@@ -0,0 +1,78 @@
+//! Authentication of messages with a shared key, for `encode --hmac`.
+//!
+//! The CRC of a chunk only catches accidental corruption: anyone changing
+//! the data can compute it again. An HMAC-SHA256 tag can only be computed
+//! with the key, so `decode --verify-hmac` tells a message written by a key
+//! holder from one altered since. The tag is stored in the
+//! [envelope](crate::envelope::Envelope) of the message and covers all of
+//! it, header included, so the expiry or provenance can't be changed either.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::codes::Code;
+
+/// Bytes of an HMAC-SHA256 tag
+pub const HMAC_LEN: usize = 32;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MacError {
+    #[error("The message carries no HMAC, it may have been replaced")]
+    Missing,
+
+    #[error("The HMAC doesn't match: wrong key, or the message was altered")]
+    Mismatch,
+}
+
+impl MacError {
+    pub fn code(&self) -> Code {
+        match self {
+            MacError::Missing => Code::HmacMissing,
+            MacError::Mismatch => Code::HmacMismatch,
+        }
+    }
+}
+
+fn keyed(key: &[u8]) -> Hmac<Sha256> {
+    Hmac::new_from_slice(key).expect("HMAC takes keys of any length")
+}
+
+/// The tag of `data` under `key`
+pub fn tag(key: &[u8], data: &[u8]) -> [u8; HMAC_LEN] {
+    let mut mac = keyed(key);
+    mac.update(data);
+
+    mac.finalize().into_bytes().into()
+}
+
+/// Checks `tag` against `data`, in constant time
+pub fn verify(key: &[u8], data: &[u8], tag: &[u8; HMAC_LEN]) -> Result<(), MacError> {
+    let mut mac = keyed(key);
+    mac.update(data);
+
+    mac.verify_slice(tag).map_err(|_| MacError::Mismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc_4231_case_2() {
+        let expected = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+        let tag = tag(b"Jefe", b"what do ya want for nothing?");
+
+        let hex: String = tag.iter().map(|byte| format!("{byte:02x}")).collect();
+        assert_eq!(hex, expected);
+    }
+
+    #[test]
+    fn test_verify() {
+        let tag = tag(b"key", b"message");
+
+        assert_eq!(verify(b"key", b"message", &tag), Ok(()));
+        assert_eq!(verify(b"kez", b"message", &tag), Err(MacError::Mismatch));
+        assert_eq!(verify(b"key", b"massage", &tag), Err(MacError::Mismatch));
+    }
+}
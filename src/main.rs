@@ -1,90 +1,514 @@
-use std::{fs::File, io::Write, path::PathBuf};
+use std::{
+    cell::{Cell, RefCell},
+    env, fs,
+    io::{self, Write},
+    num::NonZeroUsize,
+    process,
+    time::Duration,
+};
 
 use clap::Parser;
-use url::Url;
 
-use crate::{
-    args::{Arguments, Commands},
-    commands::{decode, encode, print, remove},
+use pngme::{
+    args::{decode_inputs, is_verbose_version, Arguments, CacheCommands, Commands, DebugCommands, HexBytes, OutputFormat},
+    build_info::BuildInfo,
+    cache::DownloadCache,
+    chunk::Chunk,
+    clock::SystemClock,
+    codes::Code,
+    commands::{
+        apply_patch, bench_parse, canonicalize, capabilities, capacity, clear_cache, compare_payloads, decode, encode_command, explain, export_meta, extract_chunk, extract_icc, fix, import_meta, info, inject_chunks, inject_icc, make_fixture, print, print_crc, provenance,
+        print_many, remove, scan, strip, survivability, types, undo, verify, verify_signature, version, walked_files,
+        check_chunk_name, ChunkSelector, Context, DecodeOptions,
+    },
+    error::PngMeError,
+    exit_status::ExitStatus,
+    external,
+    input::{InputOptions, InputSource},
+    inflate::DEFAULT_MAX_DECOMPRESSED_SIZE,
+    interpret::{CompressedText, InternationalText, Registry},
+    observer::{Observer, StderrObserver},
+    pipe,
+    png::ParseOptions,
+    scan::ScanOptions,
+    secret::default_keychain,
+    selector::Selection,
+    temp,
+    timings::StatsObserver,
+    validate::{exit_status, format_problems, parse_size, problems_code, validate, ResolvedOptions},
 };
 
-mod args;
-mod chunk;
-mod chunk_type;
-mod commands;
-mod error;
-mod png;
-
-fn download_image(url: Url) -> PathBuf {
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("PNGme/1.0")
-        .build()
-        .expect("Could not build client");
-
-
-    let file_name = PathBuf::from(url.path())
-        .file_name()
-        .expect("Could not get file name")
-        .to_str()
-        .expect("Could not parse path into string")
-        .to_string();
-
-    let resp = client.get(url)
-        .send()
-        .expect("Could not reach url");
-
-    if !resp.status().is_success() {
-        panic!("Request failed: {:?}", resp.status())
+#[cfg(feature = "server")]
+use pngme::{commands::serve, server::ServerOptions};
+
+#[cfg(feature = "keyring")]
+use pngme::{
+    args::KeyCommands,
+    commands::{delete_key, store_key},
+};
+
+fn main() {
+    // clap would print the short version for `--version` before seeing
+    // `--verbose`
+    if is_verbose_version(&env::args_os().collect::<Vec<_>>()) {
+        // A closed stdout exits with 0 like after any other command
+        let _ = writeln!(io::stdout().lock(), "{}", BuildInfo::current());
+        return;
     }
 
-    let file_path = PathBuf::from(file_name);
+    #[cfg_attr(not(feature = "archives"), allow(unused_mut))]
+    let mut cli = Arguments::parse();
 
-    let mut out_file = File::create(&file_path).expect("Could not create file");
-    
-    let image = resp
-        .bytes()
-        .expect("Could not get image bytes");
-    out_file.write_all(&image).expect("Could not write image data");
+    let mut problems = Vec::new();
+    #[cfg(feature = "archives")]
+    problems.extend(cli.resolve_archive_member().err());
+    problems.extend(validate(&ResolvedOptions::resolve(&cli), &|path| path.exists()));
+    if !problems.is_empty() {
+        let status = exit_status(&problems);
+        match cli.command.format() {
+            OutputFormat::Json => {
+                let message = problems.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ");
+                let mut error = json_error(problems_code(&problems), status, &message);
+                // Each problem keeps its own code, as in the human report
+                error["problems"] = problems
+                    .iter()
+                    .map(|problem| serde_json::json!({ "code": problem.code(), "message": problem.to_string() }))
+                    .collect();
+                print_json_error(error);
+            }
+            OutputFormat::Human => eprint!("{}", format_problems(&problems)),
+        }
+        process::exit(status.code());
+    }
+    let size = |value: &str| parse_size(value).expect("sizes are validated");
+    let selection = |value: &Option<String>| {
+        value.as_deref().map(|value| value.parse::<Selection>().expect("selections are validated"))
+    };
 
-    file_path
-}
+    if let Commands::External(args) = &cli.command {
+        let status = external::run(args, !cli.no_external).unwrap_or_else(|err| {
+            eprintln!("error[{}]: Could not run the command: {err}", err.code());
+            ExitStatus::of(err.code()).code()
+        });
+        process::exit(status);
+    }
 
-fn main() {
-    let cli = Arguments::parse();
+    let temp_guard = temp::install(cli.keep_temp);
+
+    let stats = StatsObserver::new(&StderrObserver);
+    let observer: &dyn Observer = if cli.stats { &stats } else { &StderrObserver };
+
+    // The compressed text interpreters take the limit too, registered last
+    // they are consulted before the built-in ones
+    let max_decompressed_size = cli
+        .max_decompressed_size
+        .as_deref()
+        .map_or(DEFAULT_MAX_DECOMPRESSED_SIZE, size);
+    let mut registry;
+    let interpreters = match cli.max_decompressed_size {
+        None => Registry::builtin(),
+        Some(_) => {
+            registry = Registry::default();
+            registry.register(CompressedText { limit: max_decompressed_size });
+            registry.register(InternationalText { limit: max_decompressed_size });
+            &registry
+        }
+    };
+
+    let cache = match cli.cache && !cli.no_cache {
+        true => match cli.cache_dir.clone().map_or_else(DownloadCache::default_dir, Ok) {
+            Ok(dir) => Some(DownloadCache { dir, max_age: cli.cache_max_age }),
+            Err(err) => {
+                eprintln!("warning[{}]: {err}; downloading without the cache", err.code());
+                None
+            }
+        },
+        false => None,
+    };
+
+    let ctx = Context {
+        observer,
+        parse_options: ParseOptions {
+            max_chunks: cli.max_chunks,
+            ..ParseOptions::default()
+        },
+        input_options: InputOptions {
+            max_size: cli.max_input_size.as_deref().map(size),
+            limit_rate: cli.limit_rate.as_deref().map(size),
+            cache,
+        },
+        clock: &SystemClock,
+        lock_timeout: Duration::from_secs(cli.lock_timeout),
+        keep_undo: cli.undoable.then_some(cli.keep_backups),
+        backup_suffix: cli.backup.clone(),
+        overwrite_backup: cli.force,
+        allow_unknown_critical: cli.allow_unknown_critical,
+        keychain: default_keychain(),
+        strict_extension: cli.strict_extension,
+        extension_fixup: !cli.no_ext_fixup,
+        interpreters,
+        max_decompressed_size,
+        emit_patch: cli.emit_patch.clone(),
+        transactional: cli.transactional,
+        transaction: RefCell::new(None),
+        stats: cli.stats.then_some(&stats.timings),
+        stats_reported: Cell::new(false),
+    };
+
+    let (context, result) = match &cli.command {
+        Commands::Encode(args) => (
+            "Could not encode message into the file",
+            encode_command(args, cli.assume_yes, &ctx),
+        ),
+        Commands::Decode {
+            files,
+            chunk,
+            quiet,
+            format,
+            compare,
+            raw,
+            ignore_expiry,
+            unwrap_all,
+            output_encoding,
+            wrap,
+            payload_format,
+            text,
+            output,
+            no_sniff,
+            decrypt,
+            password,
+            verify_hmac,
+            hmac_key,
+            recursive,
+        } => {
+            let (mut files, chunk_name) =
+                decode_inputs(files, chunk, recursive.recursive.is_some()).unwrap_or_else(|err| err.exit());
+            if let Some(walk) = recursive.walk() {
+                files = walked_files(walk).into_iter().map(InputSource::Path).collect();
+            }
+            let options = DecodeOptions {
+                quiet: *quiet,
+                raw: *raw,
+                ignore_expiry: *ignore_expiry,
+                unwrap_all: *unwrap_all,
+                format: *format,
+                encoding: *output_encoding,
+                wrap: wrap.map(NonZeroUsize::get),
+                payload_format: *payload_format,
+                text: *text,
+                output: output.clone(),
+                sniff: !*no_sniff,
+                decrypt: decrypt.then(|| password.source()),
+                verify_hmac: hmac_key.source().filter(|_| *verify_hmac),
+                name_files: recursive.recursive.is_some(),
+            };
+
+            let result = check_chunk_name(&chunk_name, false, cli.assume_yes).and_then(|()| {
+                if *compare {
+                    compare_payloads(&files, &chunk_name, &ctx)
+                } else {
+                    decode(&files, &chunk_name, &options, &ctx)
+                }
+            });
 
-    match &cli.command {
-        Commands::Encode {
+            ("Could not decode the file", result)
+        }
+        Commands::Remove {
             file,
             chunk_name,
-            message,
+            chunk,
+            at,
             output,
+            dry_run,
         } => {
-            let file_path = if let Ok(url) =
-                Url::parse(&file.clone().into_os_string().into_string().unwrap())
-            {
-                download_image(url)
-            } else {
-                file.clone()
+            let selector = match (chunk_name.as_ref().or(chunk.as_ref()), at) {
+                (_, Some(index)) => ChunkSelector::Index(*index),
+                (Some(chunk_name), None) => ChunkSelector::Type(chunk_name),
+                (None, None) => unreachable!("clap requires either a chunk name or --at"),
+            };
+
+            let result = match selector {
+                ChunkSelector::Type(chunk_name) => check_chunk_name(chunk_name, false, cli.assume_yes),
+                ChunkSelector::Index(_) => Ok(()),
             };
 
-            if let Err(err) = encode(&file_path, chunk_name, message, output) {
-                eprintln!("Could not encode message into the file: {err}")
+            (
+                "Could not remove the chunk",
+                result.and_then(|()| remove(file, selector, output, *dry_run, &ctx)),
+            )
+        }
+        Commands::Info { file } => ("Could not read the file", info(file, &ctx)),
+        Commands::Extract {
+            file,
+            icc: Some(icc),
+            ..
+        } => ("Could not extract the color profile", extract_icc(file, icc, &ctx)),
+        Commands::Extract {
+            file,
+            index: Some(index),
+            output: Some(output),
+            ..
+        } => ("Could not extract the chunk", extract_chunk(file, *index, output, &ctx)),
+        Commands::Extract { .. } => unreachable!("clap requires either --icc or --index with --output"),
+        Commands::Inject {
+            file,
+            icc: Some(icc),
+            name: Some(name),
+            replace,
+            output,
+            ..
+        } => (
+            "Could not inject the color profile",
+            inject_icc(file, icc, name, *replace, output, &ctx),
+        ),
+        Commands::Inject {
+            file,
+            chunk_spec,
+            replace,
+            output,
+            ..
+        } => {
+            let chunks: Vec<Chunk> = chunk_spec
+                .iter()
+                .map(|spec| spec.parse().expect("chunk specs are validated"))
+                .collect();
+
+            ("Could not inject the chunks", inject_chunks(file, &chunks, *replace, output, &ctx))
+        }
+        Commands::Debug { command } => match command {
+            DebugCommands::BenchParse { file } => ("Could not benchmark the file", bench_parse(file, &ctx)),
+            DebugCommands::MakeFixture { kind, output } => {
+                ("Could not write the fixture", make_fixture(*kind, output))
+            }
+            DebugCommands::Crc {
+                chunk_type,
+                data_file,
+                data_hex,
+            } => {
+                let data = match (data_file, data_hex) {
+                    (Some(path), _) => fs::read(path).map_err(PngMeError::from),
+                    (None, Some(HexBytes(bytes))) => Ok(bytes.clone()),
+                    (None, None) => Ok(Vec::new()),
+                };
+
+                (
+                    "Could not compute the CRC",
+                    data.and_then(|data| print_crc(chunk_type, &data)),
+                )
             }
+        },
+        Commands::Print {
+            file,
+            collapse,
+            recursive,
+        } => {
+            let result = match (file, recursive.walk()) {
+                (Some(file), _) => print(file, *collapse, &ctx),
+                (None, Some(walk)) => print_many(&walked_files(walk), *collapse, &ctx),
+                (None, None) => unreachable!("clap requires FILE or --recursive"),
+            };
+            ("Could not print the file chunks", result)
+        }
+        Commands::Survivability {
+            file,
+            chunk_type,
+            apply_suggestions,
+            profile,
+            output,
+        } => (
+            "Could not rate the chunk",
+            check_chunk_name(chunk_type, false, cli.assume_yes)
+                .and_then(|()| survivability(file, chunk_type, *apply_suggestions, *profile, output, &ctx)),
+        ),
+        Commands::Strip {
+            file,
+            expired_only,
+            strip_color,
+            select,
+            output,
+        } => (
+            "Could not strip the file",
+            strip(file, *expired_only, *strip_color, selection(select).as_ref(), output, &ctx),
+        ),
+        Commands::ExportMeta { file, sidecar, select } => (
+            "Could not export the chunks",
+            export_meta(file, sidecar, selection(select).as_ref(), &ctx),
+        ),
+        Commands::ImportMeta {
+            files,
+            sidecar,
+            on_conflict,
+            select,
+            output,
+        } => (
+            "Could not import the chunks",
+            import_meta(files, sidecar, *on_conflict, selection(select).as_ref(), output, &ctx),
+        ),
+        Commands::ApplyPatch {
+            file,
+            patch,
+            reverse,
+            output,
+        } => (
+            "Could not apply the patch",
+            apply_patch(file, patch, *reverse, output, &ctx),
+        ),
+        Commands::Fix {
+            file,
+            bootstrap,
+            deinterlace,
+            fix_reserved,
+            output,
+        } => (
+            "Could not fix the file",
+            fix(file, *bootstrap, *deinterlace, *fix_reserved, output, &ctx),
+        ),
+        Commands::Canonicalize { file, output } => (
+            "Could not canonicalize the file",
+            canonicalize(file, output, &ctx),
+        ),
+        Commands::Undo { file, list } => ("Could not undo the last change", undo(file, *list, &ctx)),
+        Commands::Capabilities { format } => ("Could not describe the capabilities", capabilities(*format, &ctx)),
+        Commands::Version { format } => ("Could not describe the build", version(*format, &ctx)),
+        Commands::Explain { code } => ("Could not explain the code", explain(code.as_deref())),
+        #[cfg(feature = "server")]
+        Commands::Serve {
+            listen,
+            token,
+            max_body_size,
+            timeout,
+            workers,
+        } => {
+            let options = ServerOptions {
+                max_body_size: size(max_body_size),
+                timeout: Duration::from_secs(*timeout),
+                token: token.clone(),
+                workers: *workers,
+                parse_options: ctx.parse_options,
+                max_decompressed_size,
+                allow_unknown_critical: cli.allow_unknown_critical,
+            };
+            ("Could not run the server", serve(listen, options))
+        }
+        Commands::Types {
+            paths,
+            recursive,
+            top,
+            format,
+        } => ("Could not count the chunk types", types(paths, *recursive, *top, *format, &ctx)),
+        Commands::Capacity {
+            file,
+            payload_size,
+            max_size,
+            format,
+        } => (
+            "Could not measure the capacity",
+            capacity(
+                file,
+                payload_size.as_deref().map(size),
+                max_size.as_deref().map(size),
+                *format,
+                &ctx,
+            ),
+        ),
+        Commands::Verify {
+            file,
+            chunk: Some(chunk),
+            pubkey: Some(pubkey),
+            ..
+        } => ("Could not verify the signature", verify_signature(file, chunk, pubkey, &ctx)),
+        Commands::Verify { file, deep, format, .. } => {
+            ("Could not verify the file", verify(file, *deep, *format, &ctx))
         }
-        Commands::Decode { file, chunk_name } => {
-            if let Err(err) = decode(file, chunk_name) {
-                eprintln!("Could not decode the file: {err}")
+        Commands::Scan {
+            file,
+            max_private_size,
+            max_idat_ratio,
+            provenance: false,
+        } => {
+            let options = ScanOptions {
+                max_private_size: size(max_private_size),
+                max_idat_ratio: *max_idat_ratio,
+                now: Some(ctx.clock.now()),
+            };
+
+            ("Could not scan the file", scan(file, &options, &ctx))
+        }
+        #[cfg(feature = "keyring")]
+        Commands::Key { command } => match command {
+            KeyCommands::Store { name, password } => {
+                ("Could not store the passphrase", store_key(name, &password.source(), &ctx))
             }
+            KeyCommands::Delete { name } => ("Could not delete the passphrase", delete_key(name, &ctx)),
+        },
+        Commands::Scan { file, .. } => ("Could not scan the file", provenance(file, &ctx)),
+        Commands::Cache { command: CacheCommands::Clear } => {
+            ("Could not clear the cache", clear_cache(cli.cache_dir.as_deref()))
         }
-        Commands::Remove { file, chunk_name } => {
-            if let Err(err) = remove(file, chunk_name) {
-                eprintln!("Could not remove the chunk: {err}")
+        Commands::External(_) => unreachable!("external commands are run above"),
+    };
+
+    if let Err(err) = &result
+        && pipe::is_closed_output(err)
+    {
+        drop(temp_guard);
+        process::exit(pipe::BROKEN_PIPE_STATUS);
+    }
+
+    // With JSON, the timings are part of the report or the error, and only a
+    // command that printed no report gets a document of its own
+    if cli.stats {
+        let report = stats.timings.report();
+        match (cli.command.format(), &result) {
+            (OutputFormat::Json, Ok(())) if !ctx.stats_reported.get() => {
+                let _ = writeln!(io::stdout().lock(), "{}", serde_json::json!({ "stats": report }));
             }
+            (OutputFormat::Json, _) => {}
+            (OutputFormat::Human, _) => eprintln!("{report}"),
         }
-        Commands::Print { file } => {
-            if let Err(err) = print(file) {
-                eprintln!("Could not print the file chunks: {err}")
+    }
+
+    if let Err(err) = result {
+        let status = ExitStatus::of(err.code());
+        match cli.command.format() {
+            OutputFormat::Json => {
+                let mut error = json_error(err.code(), status, &format!("{context}: {err}"));
+                if cli.stats {
+                    error["stats"] = serde_json::json!(stats.timings.report());
+                }
+                print_json_error(error);
             }
+            OutputFormat::Human => eprintln!("error[{}]: {context}: {err}", err.code()),
         }
+        // `process::exit` skips destructors
+        drop(temp_guard);
+        process::exit(status.code());
     }
 }
+
+/// The `error` object of [`print_json_error`]
+fn json_error(code: Code, status: ExitStatus, message: &str) -> serde_json::Value {
+    serde_json::json!({
+        "code": code,
+        "exit_status": status.code(),
+        "status": status.name(),
+        "message": message,
+    })
+}
+
+/// Prints an error as a `{"tool": ..., "error": ...}` line on stderr, for
+/// commands run with `--format json`. `tool` describes the build, so a
+/// captured error says which one failed.
+fn print_json_error(error: serde_json::Value) {
+    #[derive(serde::Serialize)]
+    struct JsonError {
+        tool: BuildInfo,
+        error: serde_json::Value,
+    }
+
+    let error = JsonError {
+        tool: BuildInfo::current(),
+        error,
+    };
+    eprintln!("{}", serde_json::to_string(&error).expect("errors serialize"));
+}
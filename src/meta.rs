@@ -0,0 +1,406 @@
+use std::str::FromStr;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    codes::Code,
+    png::{Png, PngError, PngParserError},
+};
+
+/// Version written to new sidecar files, older versions must keep importing
+pub const SIDECAR_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum MetaError {
+    #[error("Unsupported sidecar version {version} (this pngme reads version {SIDECAR_VERSION})")]
+    UnsupportedVersion { version: u32 },
+
+    #[error("The image already has a {chunk_type} chunk (choose what to do with --on-conflict)")]
+    Conflict { chunk_type: ChunkType },
+
+    #[error("The sidecar holds a {chunk_type} chunk, only valid ancillary chunk types can be imported")]
+    ForbiddenChunk { chunk_type: ChunkType },
+
+    #[error(transparent)]
+    Png(#[from] PngError),
+}
+
+impl MetaError {
+    pub fn code(&self) -> Code {
+        match self {
+            MetaError::UnsupportedVersion { .. } => Code::UnsupportedSidecarVersion,
+            MetaError::Conflict { .. } => Code::MetaConflict,
+            MetaError::ForbiddenChunk { .. } => Code::ForbiddenSidecarChunk,
+            MetaError::Png(err) => err.code(),
+        }
+    }
+}
+
+/// Where an ancillary chunk sits relative to the image data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Placement {
+    /// Before PLTE (or IDAT when there is no palette), e.g. iCCP or gAMA
+    BeforePlte,
+    /// Between PLTE and the first IDAT, e.g. tRNS or bKGD
+    BeforeIdat,
+    /// After the image data
+    AfterIdat,
+}
+
+/// Property bits of the chunk type, informative only: the type holds them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Flags {
+    pub public: bool,
+    pub safe_to_copy: bool,
+}
+
+/// One ancillary chunk of a sidecar file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetaChunk {
+    #[serde(rename = "type")]
+    pub chunk_type: ChunkType,
+    pub flags: Flags,
+    pub placement: Placement,
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub data: Vec<u8>,
+}
+
+/// Every ancillary chunk of an image, in file order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sidecar {
+    pub version: u32,
+    pub chunks: Vec<MetaChunk>,
+}
+
+/// What `import` does with a chunk whose type the image already has
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Stop without changing anything
+    Fail,
+    /// Keep the image's chunks and drop the imported ones
+    Skip,
+    /// Drop the image's chunks in favor of the imported ones
+    Replace,
+    /// Keep both
+    Append,
+}
+
+fn serialize_base64<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(data))
+}
+
+fn deserialize_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+}
+
+fn is_type(chunk: &Chunk, name: &[u8; 4]) -> bool {
+    chunk.chunk_type().bytes() == *name
+}
+
+/// Collects the ancillary chunks of `png` with their placement
+pub fn export(png: &Png) -> Sidecar {
+    let mut placement = Placement::BeforePlte;
+    let mut chunks = Vec::new();
+
+    for chunk in png.chunks() {
+        if is_type(chunk, b"PLTE") {
+            placement = Placement::BeforeIdat;
+        } else if is_type(chunk, b"IDAT") {
+            placement = Placement::AfterIdat;
+        }
+
+        let chunk_type = *chunk.chunk_type();
+        if chunk_type.is_critical() {
+            continue;
+        }
+
+        chunks.push(MetaChunk {
+            chunk_type,
+            flags: Flags {
+                public: chunk_type.is_public(),
+                safe_to_copy: chunk_type.is_safe_to_copy(),
+            },
+            placement,
+            data: chunk.data().to_vec(),
+        });
+    }
+
+    Sidecar {
+        version: SIDECAR_VERSION,
+        chunks,
+    }
+}
+
+/// Index at which a chunk with `placement` goes: after the chunks already
+/// in that region, so that imported chunks keep their order.
+fn insertion_index(png: &Png, placement: Placement) -> usize {
+    let chunks = png.chunks();
+    let position = |name| chunks.iter().position(|chunk| is_type(chunk, name));
+    let first_idat = position(b"IDAT");
+
+    match placement {
+        Placement::BeforePlte => position(b"PLTE").or(first_idat),
+        Placement::BeforeIdat => first_idat,
+        Placement::AfterIdat => position(b"IEND"),
+    }
+    .unwrap_or(chunks.len())
+}
+
+/// Recreates the chunks of `sidecar` in `png`, returns how many were added
+pub fn import(
+    png: &mut Png,
+    sidecar: &Sidecar,
+    on_conflict: OnConflict,
+) -> Result<usize, MetaError> {
+    if sidecar.version > SIDECAR_VERSION {
+        return Err(MetaError::UnsupportedVersion {
+            version: sidecar.version,
+        });
+    }
+
+    // A sidecar is untrusted input: `export` never writes critical chunks,
+    // and one inserted would change or break the image data
+    let forbidden = |chunk_type: &ChunkType| {
+        chunk_type.is_critical()
+            || !chunk_type.is_valid()
+            || !chunk_type.bytes().iter().all(u8::is_ascii_alphabetic)
+    };
+    if let Some(chunk) = sidecar.chunks.iter().find(|chunk| forbidden(&chunk.chunk_type)) {
+        return Err(MetaError::ForbiddenChunk {
+            chunk_type: chunk.chunk_type,
+        });
+    }
+
+    let conflicts: Vec<ChunkType> = sidecar
+        .chunks
+        .iter()
+        .map(|chunk| chunk.chunk_type)
+        .filter(|chunk_type| png.chunk_by_type(&chunk_type.to_string()).is_some())
+        .collect();
+
+    if let (OnConflict::Fail, Some(&chunk_type)) = (on_conflict, conflicts.first()) {
+        return Err(MetaError::Conflict { chunk_type });
+    }
+
+    if on_conflict == OnConflict::Replace {
+        for chunk_type in &conflicts {
+            png.remove_chunks_by_type(&chunk_type.to_string());
+        }
+    }
+
+    let mut imported = 0;
+    for chunk in &sidecar.chunks {
+        if on_conflict == OnConflict::Skip && conflicts.contains(&chunk.chunk_type) {
+            continue;
+        }
+
+        let index = insertion_index(png, chunk.placement);
+        let data = Chunk::try_new(chunk.chunk_type, chunk.data.clone())
+            .map_err(|err| PngError::from(PngParserError::from(err)))?;
+        png.insert_chunk(index, data)?;
+        imported += 1;
+    }
+
+    Ok(imported)
+}
+
+impl FromStr for Sidecar {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn image(chunks: &[(&str, &[u8])]) -> Png {
+        Png::from_chunks(chunks.iter().map(|(t, d)| chunk(t, d)).collect())
+    }
+
+    fn ancillary_bytes(png: &Png) -> Vec<Vec<u8>> {
+        png.chunks()
+            .iter()
+            .filter(|chunk| !chunk.chunk_type().is_critical())
+            .map(Chunk::as_bytes)
+            .collect()
+    }
+
+    fn rich_image() -> Png {
+        image(&[
+            ("IHDR", b"header"),
+            ("gAMA", b"\0\0\xb1\x8f"),
+            ("PLTE", b"rgb"),
+            ("tRNS", b"\0"),
+            ("IDAT", b"pixels"),
+            ("tEXt", b"Author\0me"),
+            ("ruSt", b"secret"),
+            ("IEND", b""),
+        ])
+    }
+
+    fn stripped(png: &Png) -> Png {
+        Png::from_chunks(
+            png.chunks()
+                .iter()
+                .filter(|chunk| chunk.chunk_type().is_critical())
+                .cloned()
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_export_placements() {
+        let sidecar = export(&rich_image());
+
+        let placements: Vec<(String, Placement)> = sidecar
+            .chunks
+            .iter()
+            .map(|chunk| (chunk.chunk_type.to_string(), chunk.placement))
+            .collect();
+        assert_eq!(
+            placements,
+            [
+                ("gAMA".to_string(), Placement::BeforePlte),
+                ("tRNS".to_string(), Placement::BeforeIdat),
+                ("tEXt".to_string(), Placement::AfterIdat),
+                ("ruSt".to_string(), Placement::AfterIdat),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip_restores_identical_image() {
+        let original = rich_image();
+        let json = serde_json::to_string(&export(&original)).unwrap();
+
+        let mut png = stripped(&original);
+        let imported = import(&mut png, &json.parse().unwrap(), OnConflict::Fail).unwrap();
+
+        assert_eq!(imported, 4);
+        assert_eq!(ancillary_bytes(&png), ancillary_bytes(&original));
+        assert_eq!(png.as_bytes(), original.as_bytes());
+    }
+
+    #[test]
+    fn test_json_format() {
+        let sidecar = export(&image(&[
+            ("IHDR", b""),
+            ("IDAT", b""),
+            ("ruSt", b"hi"),
+            ("IEND", b""),
+        ]));
+
+        assert_eq!(
+            serde_json::to_string(&sidecar).unwrap(),
+            r#"{"version":1,"chunks":[{"type":"ruSt","flags":{"public":false,"safe_to_copy":true},"placement":"after-idat","data":"aGk="}]}"#
+        );
+    }
+
+    fn conflicting() -> (Png, Sidecar) {
+        let sidecar = export(&image(&[
+            ("IHDR", b""),
+            ("IDAT", b""),
+            ("tEXt", b"new"),
+            ("ruSt", b"new"),
+            ("IEND", b""),
+        ]));
+        let png = image(&[
+            ("IHDR", b""),
+            ("IDAT", b""),
+            ("tEXt", b"old"),
+            ("IEND", b""),
+        ]);
+        (png, sidecar)
+    }
+
+    fn text_payloads(png: &Png) -> Vec<&[u8]> {
+        png.chunks_by_type("tEXt").map(Chunk::data).collect()
+    }
+
+    #[test]
+    fn test_conflict_fail() {
+        let (mut png, sidecar) = conflicting();
+        let before = png.as_bytes();
+
+        let result = import(&mut png, &sidecar, OnConflict::Fail);
+
+        assert!(
+            matches!(result, Err(MetaError::Conflict { chunk_type }) if chunk_type.to_string() == "tEXt")
+        );
+        assert_eq!(png.as_bytes(), before);
+    }
+
+    #[test]
+    fn test_conflict_skip() {
+        let (mut png, sidecar) = conflicting();
+
+        assert_eq!(import(&mut png, &sidecar, OnConflict::Skip).unwrap(), 1);
+        assert_eq!(text_payloads(&png), [b"old"]);
+        assert!(png.chunk_by_type("ruSt").is_some());
+    }
+
+    #[test]
+    fn test_conflict_replace() {
+        let (mut png, sidecar) = conflicting();
+
+        assert_eq!(import(&mut png, &sidecar, OnConflict::Replace).unwrap(), 2);
+        assert_eq!(text_payloads(&png), [b"new"]);
+    }
+
+    #[test]
+    fn test_conflict_append() {
+        let (mut png, sidecar) = conflicting();
+
+        assert_eq!(import(&mut png, &sidecar, OnConflict::Append).unwrap(), 2);
+        assert_eq!(text_payloads(&png), [b"old", b"new"]);
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let sidecar = Sidecar {
+            version: SIDECAR_VERSION + 1,
+            chunks: Vec::new(),
+        };
+
+        assert!(matches!(
+            import(&mut rich_image(), &sidecar, OnConflict::Fail),
+            Err(MetaError::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn test_critical_and_invalid_types_are_rejected() {
+        for chunk_type in [r#""IDAT""#, r#""rust""#, r#"{"bytes":[114,117,0,116]}"#] {
+            let sidecar: Sidecar = format!(
+                r#"{{"version":1,"chunks":[{{"type":"tEXt","flags":{{"public":true,"safe_to_copy":true}},"placement":"after-idat","data":""}},{{"type":{chunk_type},"flags":{{"public":true,"safe_to_copy":true}},"placement":"before-idat","data":"aGk="}}]}}"#
+            )
+            .parse()
+            .unwrap();
+            let mut png = stripped(&rich_image());
+            let before = png.as_bytes();
+
+            let err = import(&mut png, &sidecar, OnConflict::Append).unwrap_err();
+
+            assert!(matches!(err, MetaError::ForbiddenChunk { .. }), "{chunk_type}");
+            assert_eq!(err.code(), Code::ForbiddenSidecarChunk);
+            assert_eq!(png.as_bytes(), before);
+        }
+    }
+}
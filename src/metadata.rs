@@ -0,0 +1,168 @@
+//! Metadata stored next to a message by `encode --meta`.
+//!
+//! A `meTa` chunk follows the chunks of the message, holding a small JSON
+//! object: the type of the chunk it describes, who wrote it and when, the
+//! pngme version and the name of the original file. Unlike the provenance
+//! recorded by `--annotate`, it stays outside the payload, so other tools
+//! read the message unchanged and the metadata without pngme.
+//!
+//! `--deterministic` leaves the time out, two runs on the same input then
+//! write the same bytes.
+
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, clock::format_timestamp};
+
+/// Type of the chunks holding metadata: ancillary, private and safe to copy
+pub const METADATA_CHUNK: &str = "meTa";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Type of the chunk described
+    pub chunk: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// RFC 3339 UTC time the message was written, left out by deterministic
+    /// runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// Version of pngme that wrote the message
+    pub pngme: String,
+    /// Name of the image the message was added to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+}
+
+impl Metadata {
+    /// Metadata of a message written by this version of pngme, for a chunk
+    /// set with [`Metadata::to_chunk`]
+    pub fn new(author: Option<String>, created_at: Option<u64>, filename: Option<String>) -> Self {
+        Self {
+            chunk: String::new(),
+            author,
+            created: created_at.map(format_timestamp),
+            pngme: env!("CARGO_PKG_VERSION").to_string(),
+            filename,
+        }
+    }
+
+    /// The `meTa` chunk describing the `chunk_type` chunk
+    pub fn to_chunk(&self, chunk_type: &ChunkType) -> Chunk {
+        let metadata = Metadata {
+            chunk: chunk_type.to_string(),
+            ..self.clone()
+        };
+        let json = serde_json::to_vec(&metadata).expect("metadata serializes");
+
+        Chunk::new(metadata_chunk_type(), json)
+    }
+
+    /// The metadata `data` holds, `None` when it is not a metadata object
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        serde_json::from_slice(data).ok()
+    }
+
+    /// The metadata of the `chunk_type` chunk among `chunks`, the first
+    /// found
+    pub fn find(chunks: &[Chunk], chunk_type: &ChunkType) -> Option<Self> {
+        chunks
+            .iter()
+            .filter_map(|chunk| metadata_of(chunk, chunk_type))
+            .next()
+    }
+}
+
+/// The metadata `chunk` holds if it is a `meTa` chunk describing a chunk of
+/// type `chunk_type`
+fn metadata_of(chunk: &Chunk, chunk_type: &ChunkType) -> Option<Metadata> {
+    if *chunk.chunk_type() != metadata_chunk_type() {
+        return None;
+    }
+    Metadata::parse(chunk.data()).filter(|metadata| metadata.chunk == chunk_type.to_string())
+}
+
+/// Whether `chunk` is a `meTa` chunk describing a chunk of type `chunk_type`
+pub fn is_metadata_of(chunk: &Chunk, chunk_type: &ChunkType) -> bool {
+    metadata_of(chunk, chunk_type).is_some()
+}
+
+fn metadata_chunk_type() -> ChunkType {
+    METADATA_CHUNK.parse().expect("a valid chunk type")
+}
+
+impl Display for Metadata {
+    /// `metadata of ruSt: by Alice on 2025-01-01T00:00:00Z with pngme 0.1.0
+    /// in photo.png`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "metadata of {}:", self.chunk)?;
+        if let Some(author) = &self.author {
+            write!(f, " by {author}")?;
+        }
+        if let Some(created) = &self.created {
+            write!(f, " on {created}")?;
+        }
+        write!(f, " with pngme {}", self.pngme)?;
+        if let Some(filename) = &self.filename {
+            write!(f, " in {filename}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rust_type() -> ChunkType {
+        "ruSt".parse().unwrap()
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let metadata = Metadata::new(
+            Some("Alice".to_string()),
+            Some(1_735_689_600),
+            Some("photo.png".to_string()),
+        );
+        let chunk = metadata.to_chunk(&rust_type());
+
+        assert_eq!(chunk.chunk_type().to_string(), "meTa");
+        let parsed = Metadata::find(&[chunk], &rust_type()).unwrap();
+        assert_eq!(parsed.chunk, "ruSt");
+        assert_eq!(parsed.created.as_deref(), Some("2025-01-01T00:00:00Z"));
+        assert_eq!(
+            parsed.to_string(),
+            format!(
+                "metadata of ruSt: by Alice on 2025-01-01T00:00:00Z with pngme {} in photo.png",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn test_deterministic_metadata_leaves_the_time_out() {
+        let chunk = Metadata::new(None, None, None).to_chunk(&rust_type());
+        let json = String::from_utf8(chunk.data().to_vec()).unwrap();
+
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"chunk":"ruSt","pngme":"{}"}}"#,
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn test_find_matches_the_chunk_type() {
+        let chunk = Metadata::new(None, None, None).to_chunk(&rust_type());
+
+        assert!(is_metadata_of(&chunk, &rust_type()));
+        assert!(!is_metadata_of(&chunk, &"abCd".parse().unwrap()));
+        assert!(Metadata::find(&[chunk], &"abCd".parse().unwrap()).is_none());
+        assert!(Metadata::parse(b"not json").is_none());
+    }
+}
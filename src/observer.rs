@@ -0,0 +1,82 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::png::ParseWarning;
+
+/// Steps of the long-running operations reported to an [`Observer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// Fetching a remote image, in bytes
+    Download,
+    /// Reading the input file, in bytes
+    Read,
+    /// Parsing the chunks, in bytes of input consumed
+    Parse,
+    /// Adding the chunk to the image, in chunks
+    Embed,
+    /// Turning the image back into bytes, only reported as a span
+    Serialize,
+    /// Writing the output file, in bytes
+    Write,
+}
+
+/// Receives progress and warnings from the library instead of having them
+/// printed to stderr, e.g. to drive the progress bar of a GUI.
+///
+/// Every method does nothing by default.
+pub trait Observer {
+    fn on_progress(&self, _stage: Stage, _done: u64, _total: Option<u64>) {}
+
+    fn on_warning(&self, _warning: &ParseWarning) {}
+
+    /// A stage took `elapsed`, handling `bytes` bytes: the input read, the
+    /// payload embedded, the image written...
+    fn on_span(&self, _stage: Stage, _elapsed: Duration, _bytes: u64) {}
+
+    /// Something worth telling the user that is not a problem, e.g. a
+    /// download starting over
+    fn on_note(&self, _note: &str) {}
+}
+
+/// Observer ignoring every event
+pub struct NoopObserver;
+
+impl Observer for NoopObserver {}
+
+/// Observer used by the CLI: warnings go to stderr and progress is only
+/// drawn when stderr is a terminal.
+pub struct StderrObserver;
+
+impl Observer for StderrObserver {
+    fn on_progress(&self, stage: Stage, done: u64, total: Option<u64>) {
+        let mut stderr = io::stderr();
+
+        if !stderr.is_terminal() {
+            return;
+        }
+
+        let _ = match total {
+            Some(total) if total > 0 => {
+                write!(stderr, "\r{stage:?}: {}%", done * 100 / total)
+            }
+            _ => write!(stderr, "\r{stage:?}: {done}"),
+        };
+
+        if total == Some(done) {
+            let _ = writeln!(stderr);
+        }
+    }
+
+    fn on_warning(&self, warning: &ParseWarning) {
+        eprintln!("warning[{}]: {warning}", warning.code());
+    }
+
+    fn on_note(&self, note: &str) {
+        eprintln!("note: {note}");
+    }
+}
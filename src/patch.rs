@@ -0,0 +1,441 @@
+//! Reversible patches of the chunks of an image, for `--emit-patch` and
+//! `apply-patch`.
+//!
+//! A patch lists the operations turning the chunks of an image into those
+//! of the edited image: inserting, removing or replacing the chunk at an
+//! index. Each carries the whole chunks involved, length, type, data and
+//! CRC, in base64, and indices refer to the chunk list as the previous
+//! operations left it. Reversing a patch undoes the operations from the
+//! last one.
+//!
+//! The header holds the SHA-256 of every chunk before and after the edit,
+//! so a patch only applies to the image it was made from and only reverses
+//! on the image it made, down to the chunks it doesn't touch.
+
+use std::str::FromStr;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, codes::Code, hash::sha256_hex, png::Png};
+
+/// Version written to new patches, older versions must keep applying
+pub const PATCH_VERSION: u32 = 1;
+
+/// Chunks compared pairwise to find the smallest patch of an edit, past
+/// which the changed region is replaced as a whole
+const MAX_DIFF_CELLS: usize = 1 << 22;
+
+#[derive(Error, Debug)]
+pub enum PatchError {
+    #[error("Unsupported patch version {version} (this pngme reads version {PATCH_VERSION})")]
+    UnsupportedVersion { version: u32 },
+
+    #[error("The patch is invalid: {reason}")]
+    Invalid { reason: String },
+
+    #[error("The image is not the one the patch {expected}: {reason}")]
+    Mismatch {
+        expected: &'static str,
+        reason: String,
+    },
+}
+
+impl PatchError {
+    pub fn code(&self) -> Code {
+        match self {
+            PatchError::UnsupportedVersion { .. } => Code::UnsupportedPatchVersion,
+            PatchError::Invalid { .. } => Code::InvalidPatch,
+            PatchError::Mismatch { .. } => Code::PatchMismatch,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Op {
+    Insert,
+    Remove,
+    Replace,
+}
+
+/// One change, `old` being set for removals and replacements and `new` for
+/// insertions and replacements
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Operation {
+    pub op: Op,
+    pub index: usize,
+    /// Type of the new chunk, else of the old one, for reviewers
+    pub chunk_type: ChunkType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Patch {
+    pub version: u32,
+    /// SHA-256 of every chunk of the original image
+    pub before: Vec<String>,
+    /// SHA-256 of every chunk of the edited image
+    pub after: Vec<String>,
+    pub operations: Vec<Operation>,
+}
+
+impl FromStr for Patch {
+    type Err = PatchError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(|err| PatchError::Invalid {
+            reason: err.to_string(),
+        })
+    }
+}
+
+fn chunk_hashes(chunks: &[Chunk]) -> Vec<String> {
+    chunks
+        .iter()
+        .map(|chunk| sha256_hex(&chunk.as_bytes()))
+        .collect()
+}
+
+fn operation(op: Op, index: usize, old: Option<&Chunk>, new: Option<&Chunk>) -> Operation {
+    let encode = |chunk: &Chunk| STANDARD.encode(chunk.as_bytes());
+
+    Operation {
+        op,
+        index,
+        chunk_type: *new.or(old).expect("an operation has a chunk").chunk_type(),
+        old: old.map(encode),
+        new: new.map(encode),
+    }
+}
+
+/// Pairs of indices of the chunks `before` and `after` share, in order, as
+/// many as possible
+fn common_chunks(before: &[Chunk], after: &[Chunk]) -> Vec<(usize, usize)> {
+    if before.len().saturating_mul(after.len()) > MAX_DIFF_CELLS {
+        return Vec::new();
+    }
+
+    // lengths[i][j]: chunks in common between before[i..] and after[j..]
+    let width = after.len() + 1;
+    let mut lengths = vec![0_u32; (before.len() + 1) * width];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            lengths[i * width + j] = if before[i] == after[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = Vec::new();
+    while i < before.len() && j < after.len() {
+        if before[i] == after[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    pairs
+}
+
+/// The patch turning `before` into `after`. Chunks removed and inserted at
+/// the same place become replacements.
+pub fn diff(before: &[Chunk], after: &[Chunk]) -> Patch {
+    let (old, new) = (before, after);
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut operations = Vec::new();
+    // Index in the list being edited, where the next change happens
+    let mut index = prefix;
+    let (mut i, mut j) = (0, 0);
+    let ends = [(old_middle.len(), new_middle.len())];
+    for (common_i, common_j) in common_chunks(old_middle, new_middle)
+        .into_iter()
+        .chain(ends)
+    {
+        while i < common_i && j < common_j {
+            operations.push(operation(
+                Op::Replace,
+                index,
+                Some(&old_middle[i]),
+                Some(&new_middle[j]),
+            ));
+            (i, j, index) = (i + 1, j + 1, index + 1);
+        }
+        for chunk in &old_middle[i..common_i] {
+            operations.push(operation(Op::Remove, index, Some(chunk), None));
+        }
+        for chunk in &new_middle[j..common_j] {
+            operations.push(operation(Op::Insert, index, None, Some(chunk)));
+            index += 1;
+        }
+        // Past the chunk in common
+        (i, j, index) = (common_i + 1, common_j + 1, index + 1);
+    }
+
+    Patch {
+        version: PATCH_VERSION,
+        before: chunk_hashes(before),
+        after: chunk_hashes(after),
+        operations,
+    }
+}
+
+fn decode_chunk(encoded: Option<&str>, index: usize) -> Result<Chunk, PatchError> {
+    let invalid = |reason: String| PatchError::Invalid {
+        reason: format!("operation at index {index}: {reason}"),
+    };
+    let encoded = encoded.ok_or_else(|| invalid("a chunk is missing".to_string()))?;
+    let bytes = STANDARD
+        .decode(encoded)
+        .map_err(|err| invalid(err.to_string()))?;
+
+    Chunk::try_from(bytes.as_slice()).map_err(|err| invalid(err.to_string()))
+}
+
+/// Applies `patch` to `png`, or with `reverse` undoes it. Nothing changes
+/// when the image is not the one expected.
+pub fn apply(png: &mut Png, patch: &Patch, reverse: bool) -> Result<(), PatchError> {
+    if patch.version > PATCH_VERSION {
+        return Err(PatchError::UnsupportedVersion {
+            version: patch.version,
+        });
+    }
+    let (expected, from, to) = match reverse {
+        false => ("was made from", &patch.before, &patch.after),
+        true => ("made", &patch.after, &patch.before),
+    };
+    let mismatch = |reason: String| PatchError::Mismatch { expected, reason };
+
+    let hashes = chunk_hashes(png.chunks());
+    if hashes.len() != from.len() {
+        return Err(mismatch(format!(
+            "it has {} chunks instead of {}",
+            hashes.len(),
+            from.len()
+        )));
+    }
+    if let Some(index) = hashes
+        .iter()
+        .zip(from)
+        .position(|(hash, from)| hash != from)
+    {
+        let chunk_type = png.chunks()[index].chunk_type();
+        return Err(mismatch(format!("chunk #{index} ({chunk_type}) differs")));
+    }
+
+    let mut chunks = png.chunks().to_vec();
+    let operations: Box<dyn Iterator<Item = &Operation>> = match reverse {
+        false => Box::new(patch.operations.iter()),
+        true => Box::new(patch.operations.iter().rev()),
+    };
+    for operation in operations {
+        let Operation { op, index, .. } = *operation;
+        let (old, new) = match reverse {
+            false => (operation.old.as_deref(), operation.new.as_deref()),
+            true => (operation.new.as_deref(), operation.old.as_deref()),
+        };
+        let op = match (op, reverse) {
+            (Op::Insert, true) => Op::Remove,
+            (Op::Remove, true) => Op::Insert,
+            (op, _) => op,
+        };
+
+        let bound = match op {
+            Op::Insert => chunks.len() + 1,
+            Op::Remove | Op::Replace => chunks.len(),
+        };
+        if index >= bound {
+            return Err(PatchError::Invalid {
+                reason: format!("index {index} is out of bounds"),
+            });
+        }
+        // The old chunks make the reversed patch, they must be the ones
+        // going away
+        if op != Op::Insert && decode_chunk(old, index)? != chunks[index] {
+            return Err(PatchError::Invalid {
+                reason: format!("the chunk at index {index} is not the one the operation expects"),
+            });
+        }
+        match op {
+            Op::Insert => chunks.insert(index, decode_chunk(new, index)?),
+            Op::Remove => drop(chunks.remove(index)),
+            Op::Replace => chunks[index] = decode_chunk(new, index)?,
+        }
+    }
+
+    if chunk_hashes(&chunks) != *to {
+        return Err(PatchError::Invalid {
+            reason: "the operations don't give the image the header describes".to_string(),
+        });
+    }
+
+    *png = Png::from_chunks(chunks);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn image(chunks: &[(&str, &[u8])]) -> Vec<Chunk> {
+        chunks.iter().map(|(t, d)| chunk(t, d)).collect()
+    }
+
+    fn ops(patch: &Patch) -> Vec<(Op, usize, String)> {
+        patch
+            .operations
+            .iter()
+            .map(|operation| {
+                (
+                    operation.op,
+                    operation.index,
+                    operation.chunk_type.to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn round_trip(before: &[Chunk], after: &[Chunk]) -> Patch {
+        let patch = diff(before, after);
+
+        let mut applied = Png::from_chunks(before.to_vec());
+        apply(&mut applied, &patch, false).unwrap();
+        assert_eq!(applied.chunks(), after);
+        apply(&mut applied, &patch, true).unwrap();
+        assert_eq!(applied.chunks(), before);
+
+        patch
+    }
+
+    #[test]
+    fn test_insert_remove_replace() {
+        let before = image(&[
+            ("IHDR", b"h"),
+            ("tEXt", b"a"),
+            ("IDAT", b"d"),
+            ("IEND", b""),
+        ]);
+        let after = image(&[
+            ("IHDR", b"h"),
+            ("tEXt", b"b"),
+            ("IDAT", b"d"),
+            ("ruSt", b"x"),
+            ("IEND", b""),
+        ]);
+        assert_eq!(
+            ops(&round_trip(&before, &after)),
+            [
+                (Op::Replace, 1, "tEXt".to_string()),
+                (Op::Insert, 3, "ruSt".to_string())
+            ]
+        );
+
+        let removed = image(&[("IHDR", b"h"), ("IDAT", b"d"), ("IEND", b"")]);
+        assert_eq!(
+            ops(&round_trip(&before, &removed)),
+            [(Op::Remove, 1, "tEXt".to_string())]
+        );
+        assert!(round_trip(&before, &before).operations.is_empty());
+    }
+
+    #[test]
+    fn test_separate_changes() {
+        let before = image(&[
+            ("IHDR", b"h"),
+            ("aaAa", b"1"),
+            ("IDAT", b"d"),
+            ("bbBb", b"2"),
+            ("IEND", b""),
+        ]);
+        let after = image(&[
+            ("IHDR", b"h"),
+            ("IDAT", b"d"),
+            ("ccCc", b"3"),
+            ("ddDd", b"4"),
+            ("IEND", b""),
+        ]);
+
+        assert_eq!(
+            ops(&round_trip(&before, &after)),
+            [
+                (Op::Remove, 1, "aaAa".to_string()),
+                (Op::Replace, 2, "ccCc".to_string()),
+                (Op::Insert, 3, "ddDd".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_other_images_are_refused() {
+        let before = image(&[("IHDR", b"h"), ("IEND", b"")]);
+        let after = image(&[("IHDR", b"h"), ("ruSt", b"x"), ("IEND", b"")]);
+        let patch = diff(&before, &after);
+
+        let mut other = Png::from_chunks(image(&[("IHDR", b"H"), ("IEND", b"")]));
+        let err = apply(&mut other, &patch, false).unwrap_err();
+        assert_eq!(err.code(), Code::PatchMismatch);
+        assert_eq!(
+            err.to_string(),
+            "The image is not the one the patch was made from: chunk #0 (IHDR) differs"
+        );
+        assert_eq!(other.chunks(), image(&[("IHDR", b"H"), ("IEND", b"")]));
+
+        // Reversing needs the edited image
+        let mut original = Png::from_chunks(before);
+        assert!(apply(&mut original, &patch, true).is_err());
+    }
+
+    #[test]
+    fn test_patch_format() {
+        let before = image(&[("IHDR", b"h"), ("IEND", b"")]);
+        let after = image(&[("IHDR", b"h"), ("ruSt", b"x"), ("IEND", b"")]);
+        let json = serde_json::to_string(&diff(&before, &after)).unwrap();
+
+        assert!(
+            json.contains(r#""op":"insert","index":1,"chunk_type":"ruSt","new":""#),
+            "{json}"
+        );
+        assert!(!json.contains("\"old\""), "{json}");
+        assert_eq!(json.parse::<Patch>().unwrap(), diff(&before, &after));
+
+        let newer = json.replace("\"version\":1", "\"version\":2");
+        let err = apply(
+            &mut Png::from_chunks(before),
+            &newer.parse().unwrap(),
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), Code::UnsupportedPatchVersion);
+        assert_eq!(
+            "{}".parse::<Patch>().unwrap_err().code(),
+            Code::InvalidPatch
+        );
+    }
+}
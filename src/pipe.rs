@@ -0,0 +1,28 @@
+//! Output to a reader that may stop early, e.g. `pngme print big.png | head`.
+//!
+//! The Rust runtime ignores SIGPIPE, so writing to a closed pipe fails with
+//! [`ErrorKind::BrokenPipe`] instead of killing the process. pngme keeps it
+//! that way: the error unwinds like any other, dropping the temporary files
+//! on the way, and `main` then exits quietly with [`BROKEN_PIPE_STATUS`].
+//! Every report goes to a locked stdout through `writeln!` rather than
+//! `println!`, which panics on a closed stdout.
+
+use std::io::{self, ErrorKind};
+
+use crate::error::PngMeError;
+
+/// Exit status once the reader of stdout went away. The reader stopping
+/// early is its choice (`head` has what it wanted), not a failure of pngme,
+/// so this is a success rather than the shell's 141 (128 + SIGPIPE).
+pub const BROKEN_PIPE_STATUS: i32 = 0;
+
+/// Whether `err` comes from writing to a reader that went away
+pub fn is_broken_pipe(err: &io::Error) -> bool {
+    err.kind() == ErrorKind::BrokenPipe
+}
+
+/// Whether a command failed only because its output was no longer read,
+/// which is reported by exiting with [`BROKEN_PIPE_STATUS`] and no message
+pub fn is_closed_output(err: &PngMeError) -> bool {
+    matches!(err, PngMeError::File(err) if is_broken_pipe(err))
+}
@@ -1,44 +1,182 @@
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     fmt::Display,
-    io::{self, BufReader, Read},
+    io::{self, BufReader, Read, Write},
+    ops::Range,
+    str::FromStr,
+    time::Instant,
 };
 
 use thiserror::Error;
 
-use crate::chunk::{Chunk, ChunkParserError};
+use crate::{
+    chunk::{Chunk, ChunkParserError},
+    chunk_type::ChunkType,
+    codes::{Code, Severity},
+    consts::{CHUNK_OVERHEAD, CRC_FIELD, LENGTH_FIELD, SIGNATURE_LEN, TYPE_FIELD},
+    observer::{NoopObserver, Observer, Stage},
+};
 
 #[derive(Error, Debug)]
 pub enum PngError {
     #[error("Could not find chunk of type: {chunk_type}")]
     ChunkNotFound { chunk_type: String },
 
+    #[error("No chunk at index {index} (the file has {len} chunks)")]
+    IndexOutOfBounds { index: usize, len: usize },
+
+    #[error("The file has no {chunk_type} chunk to place the new chunk next to")]
+    MissingAnchor { chunk_type: &'static str },
+
+    #[error("{chunk_type} is a critical chunk, renaming or moving it would break the image")]
+    CriticalChunk { chunk_type: String },
+
     #[error(transparent)]
     ParserError(#[from] PngParserError),
 }
 
+impl PngError {
+    pub fn code(&self) -> Code {
+        match self {
+            PngError::ChunkNotFound { .. } => Code::ChunkNotFound,
+            PngError::IndexOutOfBounds { .. } => Code::IndexOutOfBounds,
+            PngError::MissingAnchor { .. } => Code::MissingAnchor,
+            PngError::CriticalChunk { .. } => Code::CriticalChunkEdit,
+            PngError::ParserError(err) => err.code(),
+        }
+    }
+}
+
+/// Where a new chunk goes, see [`Png::index_of`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Position {
+    /// Just before IEND, after the image data
+    #[default]
+    BeforeIend,
+    /// Just after IHDR, before the image data
+    AfterIhdr,
+    /// At this absolute zero-based index, as shown by `print`
+    Index(usize),
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "before-iend" => Ok(Position::BeforeIend),
+            "after-ihdr" => Ok(Position::AfterIhdr),
+            index => index
+                .parse()
+                .map(Position::Index)
+                .map_err(|_| format!("expected before-iend, after-ihdr or an index, got '{value}'")),
+        }
+    }
+}
+
+/// How [`Png::as_bytes_with`] and [`Png::write_to_with`] lay out the chunks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Serialization {
+    /// With exactly one IEND chunk, empty and last, as after
+    /// [`Png::normalize_iend`]
+    #[default]
+    Normalized,
+    /// The chunks exactly as they are, e.g. to reproduce an abnormal file
+    Raw,
+}
+
+/// A parsed image. `Png` owns all its data and has no interior mutability,
+/// so it can be shared across threads; any cache added later must keep it so.
+///
+/// The chunks may be in any order: the lenient parser and the mutation
+/// methods can leave IEND missing, duplicated or before other chunks. Only
+/// serialization guarantees a single empty IEND at the end, unless
+/// [`Serialization::Raw`] is asked for.
 #[derive(Debug, PartialEq, Eq)]
 pub struct Png {
     chunks: Vec<Chunk>,
+    /// Positions of the chunks of each type, kept in sync by every mutation
+    /// so that by-type lookups don't scan the whole chunk list.
+    index: HashMap<ChunkType, Vec<usize>>,
+    /// Warnings found by [`Png::parse`], empty for built images
+    warnings: Vec<ParseWarning>,
 }
 
+static_assertions::assert_impl_all!(Png: Send, Sync);
+
 impl Png {
-    const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    pub const STANDARD_HEADER: [u8; SIGNATURE_LEN] = [137, 80, 78, 71, 13, 10, 26, 10];
 
     pub fn append_chunk(&mut self, chunk: Chunk) {
+        self.index
+            .entry(*chunk.chunk_type())
+            .or_default()
+            .push(self.chunks.len());
         self.chunks.push(chunk);
     }
 
+    /// Inserts `chunk` at `index`, shifting the following chunks
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) -> Result<(), PngError> {
+        if index > self.chunks.len() {
+            return Err(PngError::IndexOutOfBounds {
+                index,
+                len: self.chunks.len(),
+            });
+        }
+
+        for positions in self.index.values_mut() {
+            positions
+                .iter_mut()
+                .filter(|position| **position >= index)
+                .for_each(|position| *position += 1);
+        }
+
+        let positions = self.index.entry(*chunk.chunk_type()).or_default();
+        let at = positions.partition_point(|&position| position < index);
+        positions.insert(at, index);
+
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+
     pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
-        Self { chunks }
+        let mut png = Self {
+            chunks,
+            index: HashMap::new(),
+            warnings: Vec::new(),
+        };
+        png.reindex();
+        png
+    }
+
+    /// The chunks in file order, taken out of the image
+    pub fn into_chunks(self) -> Vec<Chunk> {
+        self.chunks
+    }
+
+    fn reindex(&mut self) {
+        self.index.clear();
+
+        for (position, chunk) in self.chunks.iter().enumerate() {
+            self.index
+                .entry(*chunk.chunk_type())
+                .or_default()
+                .push(position);
+        }
+    }
+
+    /// Positions of the chunks of the given type, in file order
+    fn positions(&self, chunk_type: &str) -> &[usize] {
+        ChunkType::from_str(chunk_type)
+            .ok()
+            .and_then(|chunk_type| self.index.get(&chunk_type))
+            .map_or(&[], Vec::as_slice)
     }
 
     pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk, PngError> {
-        if let Some(pos) = self
-            .chunks
-            .iter()
-            .position(|chunk| chunk.chunk_type().bytes() == chunk_type.as_bytes())
-        {
-            Ok(self.chunks.remove(pos))
+        if let Some(&pos) = self.positions(chunk_type).first() {
+            self.remove_chunk_at(pos)
         } else {
             Err(PngError::ChunkNotFound {
                 chunk_type: chunk_type.to_owned(),
@@ -46,22 +184,219 @@ impl Png {
         }
     }
 
+    /// Index at which [`Png::insert_chunk`] puts a chunk meant for
+    /// `position`. IEND and IHDR positions need the file to have that chunk.
+    pub fn index_of(&self, position: Position) -> Result<usize, PngError> {
+        match position {
+            // A misplaced IEND is dropped on write, the last one stays
+            Position::BeforeIend => self
+                .positions("IEND")
+                .last()
+                .copied()
+                .ok_or(PngError::MissingAnchor { chunk_type: "IEND" }),
+            Position::AfterIhdr => self
+                .positions("IHDR")
+                .first()
+                .map(|&position| position + 1)
+                .ok_or(PngError::MissingAnchor { chunk_type: "IHDR" }),
+            Position::Index(index) if index <= self.chunks.len() => Ok(index),
+            Position::Index(index) => Err(PngError::IndexOutOfBounds {
+                index,
+                len: self.chunks.len(),
+            }),
+        }
+    }
+
+    /// Puts `chunk` in place of the first chunk of the given type, keeping
+    /// its position, and returns the chunk it replaced
+    pub fn replace_chunk(&mut self, chunk_type: &str, chunk: Chunk) -> Result<Chunk, PngError> {
+        let Some(&position) = self.positions(chunk_type).first() else {
+            return Err(PngError::ChunkNotFound {
+                chunk_type: chunk_type.to_owned(),
+            });
+        };
+
+        let replaced = std::mem::replace(&mut self.chunks[position], chunk);
+        if replaced.chunk_type() != self.chunks[position].chunk_type() {
+            self.reindex();
+        }
+
+        Ok(replaced)
+    }
+
+    /// Removes every chunk of the given type in a single pass
+    pub fn remove_chunks_by_type(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        if self.positions(chunk_type).is_empty() {
+            return Vec::new();
+        }
+
+        let (removed, kept) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|chunk| chunk.chunk_type().bytes() == chunk_type.as_bytes());
+        self.chunks = kept;
+        self.reindex();
+
+        removed
+    }
+
+    /// Removes every chunk matching `predicate`, returning them in file order
+    pub fn remove_chunks_where(&mut self, mut predicate: impl FnMut(&Chunk) -> bool) -> Vec<Chunk> {
+        let (removed, kept) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|chunk| predicate(chunk));
+
+        self.chunks = kept;
+        self.reindex();
+        removed
+    }
+
+    /// Removes the chunk at `index`, the absolute zero-based position shown by `print`
+    pub fn remove_chunk_at(&mut self, index: usize) -> Result<Chunk, PngError> {
+        if index >= self.chunks.len() {
+            return Err(PngError::IndexOutOfBounds {
+                index,
+                len: self.chunks.len(),
+            });
+        }
+
+        let chunk = self.chunks.remove(index);
+
+        if let Some(positions) = self.index.get_mut(chunk.chunk_type()) {
+            positions.retain(|&position| position != index);
+            if positions.is_empty() {
+                self.index.remove(chunk.chunk_type());
+            }
+        }
+
+        for positions in self.index.values_mut() {
+            positions
+                .iter_mut()
+                .filter(|position| **position > index)
+                .for_each(|position| *position -= 1);
+        }
+
+        Ok(chunk)
+    }
+
+    /// Puts `chunk` in place of the chunk at `index` and returns the chunk
+    /// it replaced
+    pub fn replace_chunk_at(&mut self, index: usize, chunk: Chunk) -> Result<Chunk, PngError> {
+        if index >= self.chunks.len() {
+            return Err(PngError::IndexOutOfBounds {
+                index,
+                len: self.chunks.len(),
+            });
+        }
+
+        let replaced = std::mem::replace(&mut self.chunks[index], chunk);
+        if replaced.chunk_type() != self.chunks[index].chunk_type() {
+            self.reindex();
+        }
+
+        Ok(replaced)
+    }
+
     pub fn header(&self) -> &[u8; 8] {
         &Self::STANDARD_HEADER
     }
 
+    /// Warnings found while parsing, in file order
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
     pub fn chunks(&self) -> &[Chunk] {
         &self.chunks
     }
 
+    /// Parallel iterator over the chunks, for per-chunk work like hashing
+    #[cfg(feature = "rayon")]
+    pub fn par_chunks(&self) -> rayon::slice::Iter<'_, Chunk> {
+        use rayon::prelude::*;
+
+        self.chunks.par_iter()
+    }
+
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
-        self.chunks()
+        self.positions(chunk_type)
+            .first()
+            .map(|&position| &self.chunks[position])
+    }
+
+    /// Every chunk of the given type, in file order
+    pub fn chunks_by_type<'a>(&'a self, chunk_type: &str) -> impl Iterator<Item = &'a Chunk> + 'a {
+        self.positions(chunk_type)
             .iter()
-            .find(|&chunk| chunk.chunk_type().bytes() == chunk_type.as_bytes())
+            .map(|&position| &self.chunks[position])
     }
 
+    /// Groups consecutive chunks sharing the same type (e.g. IDAT runs).
+    ///
+    /// Ranges are expressed over the absolute chunk indices so collapsed
+    /// views keep addressing the same chunks as the uncollapsed list.
+    pub fn chunk_runs(&self) -> Vec<Range<usize>> {
+        let mut runs: Vec<Range<usize>> = Vec::new();
+
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            match runs.last_mut() {
+                Some(run) if self.chunks[run.start].chunk_type() == chunk.chunk_type() => {
+                    run.end = index + 1
+                }
+                _ => runs.push(index..index + 1),
+            }
+        }
+
+        runs
+    }
+
+    /// Whether the only IEND chunk is the last one and is empty
+    pub fn has_normal_iend(&self) -> bool {
+        match (self.positions("IEND"), self.chunks.last()) {
+            (&[position], Some(last)) => position == self.chunks.len() - 1 && last.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Leaves exactly one IEND chunk, empty and last: extra IEND chunks are
+    /// dropped, the chunks found after the first one move before it, and a
+    /// missing IEND is added
+    pub fn normalize_iend(&mut self) {
+        if self.has_normal_iend() {
+            return;
+        }
+
+        self.chunks
+            .retain(|chunk| chunk.chunk_type().bytes() != *b"IEND");
+        self.chunks.push(Chunk::new(
+            ChunkType::from_str("IEND").expect("IEND is a valid type"),
+            Vec::new(),
+        ));
+        self.reindex();
+    }
+
+    /// The chunks to serialize in `mode`, only copied when IEND has to be
+    /// normalized
+    fn serialized_chunks(&self, mode: Serialization) -> Cow<'_, [Chunk]> {
+        if mode == Serialization::Raw || self.has_normal_iend() {
+            return Cow::Borrowed(&self.chunks);
+        }
+
+        let mut png = Png::from_chunks(self.chunks.clone());
+        png.normalize_iend();
+        Cow::Owned(png.chunks)
+    }
+
+    /// The file bytes, with IEND normalized
     pub fn as_bytes(&self) -> Vec<u8> {
-        let bytes: Vec<u8> = self.chunks.iter().flat_map(|chunk| chunk.as_bytes()).collect();
+        self.as_bytes_with(Serialization::Normalized)
+    }
+
+    pub fn as_bytes_with(&self, mode: Serialization) -> Vec<u8> {
+        let bytes: Vec<u8> = self
+            .serialized_chunks(mode)
+            .iter()
+            .flat_map(|chunk| chunk.as_bytes())
+            .collect();
 
         self.header()
             .iter()
@@ -69,6 +404,25 @@ impl Png {
             .cloned()
             .collect()
     }
+
+    /// Writes the same bytes as [`Png::as_bytes`] without building them in memory
+    pub fn write_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.write_to_with(writer, Serialization::Normalized)
+    }
+
+    /// Writes the same bytes as [`Png::as_bytes_with`]
+    pub fn write_to_with(&self, writer: &mut dyn Write, mode: Serialization) -> io::Result<()> {
+        writer.write_all(self.header())?;
+
+        for chunk in self.serialized_chunks(mode).iter() {
+            writer.write_all(&chunk.length().to_be_bytes())?;
+            writer.write_all(&chunk.chunk_type().bytes())?;
+            writer.write_all(chunk.data())?;
+            writer.write_all(&chunk.crc().to_be_bytes())?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Error, Debug)]
@@ -76,6 +430,24 @@ pub enum PngParserError {
     #[error("Invalid header")]
     InvaLidHeader,
 
+    #[error("The file is empty")]
+    Empty,
+
+    #[error("The file contains no chunks (signature only)")]
+    NoChunks,
+
+    #[error("The file is truncated: the chunk at byte {offset} needs {needed} bytes, only {available} left")]
+    Truncated { offset: u64, needed: u64, available: u64 },
+
+    #[error("The file has more than {limit} chunks (raise the limit with --max-chunks)")]
+    TooManyChunks { limit: usize },
+
+    #[error("The chunk at byte {offset} declares {length} bytes, more than the {} allowed", Chunk::MAX_LENGTH)]
+    ChunkTooLarge { offset: u64, length: u32 },
+
+    #[error("The file exceeds the supported size")]
+    FileTooLarge,
+
     #[error(transparent)]
     InvalidChunk(#[from] ChunkParserError),
 
@@ -83,29 +455,258 @@ pub enum PngParserError {
     ReaderError(#[from] io::Error),
 }
 
-impl TryFrom<&[u8]> for Png {
-    type Error = PngError;
+impl PngParserError {
+    pub fn code(&self) -> Code {
+        match self {
+            PngParserError::InvaLidHeader => Code::BadSignature,
+            PngParserError::Empty => Code::EmptyFile,
+            PngParserError::NoChunks => Code::NoChunks,
+            PngParserError::Truncated { .. } => Code::TruncatedFile,
+            PngParserError::TooManyChunks { .. } => Code::TooManyChunks,
+            PngParserError::ChunkTooLarge { .. } => Code::ChunkTooLarge,
+            PngParserError::FileTooLarge => Code::FileTooLarge,
+            PngParserError::InvalidChunk(err) => err.code(),
+            PngParserError::ReaderError(_) => Code::ReadFailed,
+        }
+    }
+}
 
-    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let mut reader = BufReader::new(value);
-        let mut header_buffer = [0u8; 8];
+/// Recoverable oddities found while parsing, reported to the [`Observer`]
+/// and kept by the [`Png`].
+///
+/// `range` covers the offending bytes of the file. Warnings about something
+/// missing have an empty range at the offset where it was expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+    /// A chunk was found after IEND, `range` covers the whole chunk
+    ChunkAfterIend { range: Range<u64>, chunk_type: String },
+
+    /// The file ended without an IEND chunk
+    MissingIend { range: Range<u64> },
+
+    /// Bytes too short to form a chunk were left at the end of the file
+    TrailingBytes { range: Range<u64> },
+
+    /// A chunk's stored CRC doesn't match its content, `range` covers the
+    /// CRC bytes. Only reported by lenient parsing, otherwise an error.
+    CrcMismatch {
+        range: Range<u64>,
+        chunk_type: String,
+        stored: u32,
+        computed: u32,
+    },
+
+    /// A critical chunk the specification doesn't define, which decoders
+    /// reject. `range` covers the whole chunk. Only reported by lenient
+    /// parsing.
+    UnknownCriticalChunk { range: Range<u64>, chunk_type: String },
+
+    /// A chunk type with the reserved bit set (lowercase third letter),
+    /// which no version of the specification allows. `range` covers the
+    /// type bytes. Only reported by lenient parsing.
+    ReservedBit { range: Range<u64>, chunk_type: String },
+
+    /// The IDAT stream inflates to another size than the IHDR dimensions
+    /// call for, `complete` being false when the stream is cut short or
+    /// corrupt. `range` covers the IDAT chunks. Only reported by the deep
+    /// check of [`crate::image_data`].
+    ImageDataSize {
+        range: Range<u64>,
+        expected: u64,
+        actual: u64,
+        complete: bool,
+    },
+
+    /// An fcTL or fdAT chunk of an animated image doesn't have the next
+    /// sequence number, `range` covers the number. Only reported by
+    /// [`crate::apng::check`].
+    AnimationSequence {
+        range: Range<u64>,
+        chunk_type: String,
+        expected: u32,
+        found: u32,
+    },
+}
+
+impl ParseWarning {
+    /// Bytes of the file the warning is about
+    pub fn range(&self) -> Range<u64> {
+        match self {
+            ParseWarning::ChunkAfterIend { range, .. }
+            | ParseWarning::UnknownCriticalChunk { range, .. }
+            | ParseWarning::ReservedBit { range, .. }
+            | ParseWarning::ImageDataSize { range, .. }
+            | ParseWarning::AnimationSequence { range, .. }
+            | ParseWarning::MissingIend { range }
+            | ParseWarning::TrailingBytes { range }
+            | ParseWarning::CrcMismatch { range, .. } => range.clone(),
+        }
+    }
+
+    /// Stable code of the warning, see [`crate::codes`]
+    pub fn code(&self) -> Code {
+        match self {
+            ParseWarning::ChunkAfterIend { .. } => Code::ChunkAfterIend,
+            ParseWarning::MissingIend { .. } => Code::MissingIend,
+            ParseWarning::TrailingBytes { .. } => Code::TrailingData,
+            ParseWarning::CrcMismatch { .. } => Code::CrcMismatch,
+            ParseWarning::UnknownCriticalChunk { .. } => Code::UnknownCriticalChunk,
+            ParseWarning::ReservedBit { .. } => Code::ReservedBitSet,
+            ParseWarning::ImageDataSize { .. } => Code::ImageDataSize,
+            ParseWarning::AnimationSequence { .. } => Code::AnimationSequence,
+        }
+    }
+
+    /// Readable identifier that stays the same across releases
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParseWarning::ChunkAfterIend { .. } => "chunk-after-iend",
+            ParseWarning::MissingIend { .. } => "missing-iend",
+            ParseWarning::TrailingBytes { .. } => "trailing-bytes",
+            ParseWarning::CrcMismatch { .. } => "crc-mismatch",
+            ParseWarning::UnknownCriticalChunk { .. } => "unknown-critical-chunk",
+            ParseWarning::ReservedBit { .. } => "reserved-bit",
+            ParseWarning::ImageDataSize { .. } => "image-data-size",
+            ParseWarning::AnimationSequence { .. } => "animation-sequence",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            ParseWarning::CrcMismatch { .. }
+            | ParseWarning::UnknownCriticalChunk { .. }
+            | ParseWarning::ImageDataSize { .. }
+            | ParseWarning::AnimationSequence { .. } => Severity::Critical,
+            ParseWarning::ChunkAfterIend { .. }
+            | ParseWarning::MissingIend { .. }
+            | ParseWarning::TrailingBytes { .. }
+            | ParseWarning::ReservedBit { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseWarning::ChunkAfterIend { range, chunk_type } => {
+                write!(f, "chunk {chunk_type} found after IEND at offset {}", range.start)
+            }
+            ParseWarning::MissingIend { .. } => write!(f, "the file has no IEND chunk"),
+            ParseWarning::TrailingBytes { range } => write!(
+                f,
+                "{} trailing bytes ignored at offset {}",
+                range.end - range.start,
+                range.start
+            ),
+            ParseWarning::CrcMismatch {
+                chunk_type,
+                stored,
+                computed,
+                ..
+            } => write!(
+                f,
+                "chunk {chunk_type} has CRC {stored:08x}, expected {computed:08x}"
+            ),
+            ParseWarning::UnknownCriticalChunk { range, chunk_type } => write!(
+                f,
+                "unknown critical chunk {chunk_type} at offset {}, decoders will reject the file",
+                range.start
+            ),
+            ParseWarning::ReservedBit { range, chunk_type } => write!(
+                f,
+                "chunk type {chunk_type} at offset {} has the reserved bit set (lowercase third letter)",
+                range.start
+            ),
+            ParseWarning::ImageDataSize {
+                expected,
+                actual,
+                complete,
+                ..
+            } => {
+                let difference = match actual < expected {
+                    true => format!("{} bytes short", expected - actual),
+                    false => format!("{} bytes too many", actual - expected),
+                };
+                write!(
+                    f,
+                    "the image data inflates to {actual} bytes, the IHDR dimensions need {expected} ({difference})"
+                )?;
+                match complete {
+                    true => Ok(()),
+                    false => write!(f, ", the compressed stream is cut short or corrupt"),
+                }
+            }
+            ParseWarning::AnimationSequence {
+                range,
+                chunk_type,
+                expected,
+                found,
+            } => write!(
+                f,
+                "chunk {chunk_type} at offset {} has sequence number {found}, expected {expected}; browsers will only show the first frame",
+                range.start
+            ),
+        }
+    }
+}
 
-        reader
-            .read_exact(&mut header_buffer)
-            .map_err(|err| PngError::ParserError(PngParserError::ReaderError(err)))?;
+/// Limits applied while parsing untrusted files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Parsing fails once the file declares more chunks than this
+    pub max_chunks: usize,
+    /// Keep chunks with a wrong CRC, reporting a warning instead of failing
+    pub lenient: bool,
+}
+
+impl ParseOptions {
+    pub const DEFAULT_MAX_CHUNKS: usize = 1_000_000;
+}
 
-        if header_buffer != Png::STANDARD_HEADER {
-            return Err(PngError::ParserError(PngParserError::InvaLidHeader));
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_chunks: Self::DEFAULT_MAX_CHUNKS,
+            lenient: false,
         }
+    }
+}
+
+impl Png {
+    /// Parses a PNG, reporting parse progress (in bytes) and warnings to `observer`
+    pub fn parse(
+        value: &[u8],
+        options: &ParseOptions,
+        observer: &dyn Observer,
+    ) -> Result<Self, PngError> {
+        let start = Instant::now();
+        let total = value.len() as u64;
+        check_header(value)?;
+        let mut reader = BufReader::new(&value[SIGNATURE_LEN..]);
 
         let mut chunks: Vec<Chunk> = Vec::new();
+        let mut offset = SIGNATURE_LEN as u64;
+        let mut seen_iend = false;
+        let mut warnings = Vec::new();
+
+        observer.on_progress(Stage::Parse, offset, Some(total));
 
-        let mut data_length_buffer = [0u8; 4];
+        let mut data_length_buffer = [0u8; LENGTH_FIELD];
         // Read chunks until there is no more
         while reader.read_exact(&mut data_length_buffer).is_ok() {
+            if chunks.len() == options.max_chunks {
+                return Err(PngError::ParserError(PngParserError::TooManyChunks {
+                    limit: options.max_chunks,
+                }));
+            }
+
             let data_length = u32::from_be_bytes(data_length_buffer);
-            // We get read the chunk_type (4 bytes) + data bytes + crc (4 bytes)
-            let mut chunk_bytes: Vec<u8> = vec![0u8; data_length as usize + 8];
+
+            // Don't allocate a buffer for a length the input can't hold
+            let span = chunk_span(offset, data_length, total - offset)?;
+
+            // We get read the chunk_type + data bytes + crc
+            let mut chunk_bytes: Vec<u8> = vec![0u8; data_length as usize + TYPE_FIELD + CRC_FIELD];
 
             reader
                 .read_exact(&mut chunk_bytes)
@@ -119,22 +720,157 @@ impl TryFrom<&[u8]> for Png {
                 .collect();
 
             // We then try to form the chunk giving it the length buffer and all the other bytes read
-            let chunk = Chunk::try_from(all_bytes.as_slice())
+            let (chunk, stored_crc) = Chunk::parse_unchecked(all_bytes.as_slice())
                 .map_err(|err| PngError::ParserError(PngParserError::InvalidChunk(err)))?;
+            let chunk_end = span.end;
+
+            if stored_crc != chunk.crc() {
+                if !options.lenient {
+                    return Err(PngError::ParserError(PngParserError::InvalidChunk(
+                        ChunkParserError::InvalidChecksum,
+                    )));
+                }
+
+                warnings.push(ParseWarning::CrcMismatch {
+                    range: chunk_end - CRC_FIELD as u64..chunk_end,
+                    chunk_type: chunk.chunk_type().to_string(),
+                    stored: stored_crc,
+                    computed: chunk.crc(),
+                });
+            }
+
+            if options.lenient && chunk.chunk_type().is_unknown_critical() {
+                warnings.push(ParseWarning::UnknownCriticalChunk {
+                    range: offset..chunk_end,
+                    chunk_type: chunk.chunk_type().to_string(),
+                });
+            }
+
+            if options.lenient && !chunk.chunk_type().is_reserved_bit_valid() {
+                let type_start = offset + LENGTH_FIELD as u64;
+                warnings.push(ParseWarning::ReservedBit {
+                    range: type_start..type_start + TYPE_FIELD as u64,
+                    chunk_type: chunk.chunk_type().to_string(),
+                });
+            }
+
+            if seen_iend {
+                warnings.push(ParseWarning::ChunkAfterIend {
+                    range: offset..chunk_end,
+                    chunk_type: chunk.chunk_type().to_string(),
+                });
+            }
+            seen_iend |= chunk.chunk_type().bytes() == *b"IEND";
+
+            offset = chunk_end;
+            observer.on_progress(Stage::Parse, offset, Some(total));
 
             chunks.push(chunk);
         }
 
-        Ok(Png::from_chunks(chunks))
+        // Not even the length of a first chunk
+        if chunks.is_empty() {
+            return Err(PngError::ParserError(PngParserError::Truncated {
+                offset,
+                needed: CHUNK_OVERHEAD as u64,
+                available: total - offset,
+            }));
+        }
+
+        if offset < total {
+            warnings.push(ParseWarning::TrailingBytes {
+                range: offset..total,
+            });
+            observer.on_progress(Stage::Parse, total, Some(total));
+        }
+
+        if !seen_iend {
+            warnings.push(ParseWarning::MissingIend {
+                range: offset..offset,
+            });
+        }
+
+        for warning in &warnings {
+            observer.on_warning(warning);
+        }
+
+        let mut png = Png::from_chunks(chunks);
+        png.warnings = warnings;
+        observer.on_span(Stage::Parse, start.elapsed(), total);
+        Ok(png)
+    }
+}
+
+/// Bytes of the file covered by the chunk at `offset` whose length field is
+/// `length`, with `available` bytes left from `offset`. Offsets are kept in
+/// u64 so that walking files over 4 GiB can't wrap around.
+pub(crate) fn chunk_span(
+    offset: u64,
+    length: u32,
+    available: u64,
+) -> Result<Range<u64>, PngParserError> {
+    if length > Chunk::MAX_LENGTH {
+        return Err(PngParserError::ChunkTooLarge { offset, length });
+    }
+
+    let needed = length as u64 + CHUNK_OVERHEAD as u64;
+    if needed > available {
+        return Err(PngParserError::Truncated {
+            offset,
+            needed,
+            available,
+        });
+    }
+
+    let end = offset
+        .checked_add(needed)
+        .ok_or(PngParserError::FileTooLarge)?;
+
+    Ok(offset..end)
+}
+
+/// Checks the signature, telling empty, signature-only and cut files apart
+/// from files that are not PNGs.
+pub(crate) fn check_header(value: &[u8]) -> Result<(), PngParserError> {
+    let header = &Png::STANDARD_HEADER;
+
+    if value.is_empty() {
+        return Err(PngParserError::Empty);
+    }
+
+    if value.len() < header.len() && header.starts_with(value) {
+        return Err(PngParserError::Truncated {
+            offset: 0,
+            needed: header.len() as u64,
+            available: value.len() as u64,
+        });
+    }
+
+    if !value.starts_with(header) {
+        return Err(PngParserError::InvaLidHeader);
+    }
+
+    if value.len() == header.len() {
+        return Err(PngParserError::NoChunks);
+    }
+
+    Ok(())
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = PngError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Png::parse(value, &ParseOptions::default(), &NoopObserver)
     }
 }
 
 impl Display for Png {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Png {{ header: {:?} }}\n", Png::STANDARD_HEADER)?;
+        writeln!(f, "Png {{ header: {:?} }}", Png::STANDARD_HEADER)?;
 
-        for chunk in self.chunks.iter() {
-            write!(f, "{chunk}\n")?;
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            writeln!(f, "#{index} {chunk}")?;
         }
         write!(f, "")
     }
@@ -144,7 +880,9 @@ impl Display for Png {
 mod tests {
     use super::*;
     use crate::chunk::Chunk;
+    use crate::consts::MAX_CHUNK_DATA;
     use crate::chunk_type::{ChunkType, ChunkTypeError};
+    use crate::test_fixtures::{minimal_png_bytes, png_with_bad_crc, png_with_duplicate_chunks};
     use std::convert::TryFrom;
 
     fn testing_chunks() -> Vec<Chunk> {
@@ -268,12 +1006,566 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_first_chunk_keeps_the_duplicate() {
+        let mut png = Png::try_from(png_with_duplicate_chunks().as_slice()).unwrap();
+
+        let removed = png.remove_first_chunk("ruSt").unwrap();
+
+        assert_eq!(png.chunks_by_type("ruSt").count(), 1);
+        assert_eq!(png.chunk_by_type("ruSt").unwrap(), &removed);
+        assert_eq!(png.chunks()[2], removed);
+    }
+
+    #[test]
+    fn test_index_of_position() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "data").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        assert_eq!(png.index_of(Position::BeforeIend).unwrap(), 2);
+        assert_eq!(png.index_of(Position::AfterIhdr).unwrap(), 1);
+        assert_eq!(png.index_of(Position::Index(3)).unwrap(), 3);
+        assert!(matches!(
+            png.index_of(Position::Index(4)),
+            Err(PngError::IndexOutOfBounds { index: 4, len: 3 })
+        ));
+        assert!(matches!(
+            testing_png().index_of(Position::BeforeIend),
+            Err(PngError::MissingAnchor { chunk_type: "IEND" })
+        ));
+
+        assert_eq!("after-ihdr".parse(), Ok(Position::AfterIhdr));
+        assert_eq!("7".parse(), Ok(Position::Index(7)));
+        assert!("middle".parse::<Position>().is_err());
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+
+        let replaced = png
+            .replace_chunk("miDl", chunk_from_strings("miDl", "New").unwrap())
+            .unwrap();
+
+        assert_eq!(replaced.data_as_string().unwrap(), "I am another chunk");
+        assert_eq!(png.chunks()[1].data_as_string().unwrap(), "New");
+        assert_eq!(png.chunk_by_type("miDl").unwrap().data_as_string().unwrap(), "New");
+        assert!(matches!(
+            png.replace_chunk("NoNe", chunk_from_strings("NoNe", "Value").unwrap()),
+            Err(PngError::ChunkNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = testing_png();
+        png.insert_chunk(1, chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+        png.insert_chunk(4, chunk_from_strings("EnDs", "At the end").unwrap())
+            .unwrap();
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "TeSt", "miDl", "LASt", "EnDs"]);
+
+        assert!(matches!(
+            png.insert_chunk(6, chunk_from_strings("TeSt", "Message").unwrap()),
+            Err(PngError::IndexOutOfBounds { index: 6, len: 5 })
+        ));
+    }
+
+    #[test]
+    fn test_remove_chunk_at() {
+        let mut png = testing_png();
+        let removed = png.remove_chunk_at(1).unwrap();
+        assert_eq!(&removed.chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "LASt");
+    }
+
+    #[test]
+    fn test_remove_chunk_at_out_of_bounds() {
+        let mut png = testing_png();
+        let result = png.remove_chunk_at(3);
+        assert!(matches!(
+            result,
+            Err(PngError::IndexOutOfBounds { index: 3, len: 3 })
+        ));
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_chunk_runs() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("LASt", "Another last chunk").unwrap());
+        png.append_chunk(chunk_from_strings("LASt", "Yet another").unwrap());
+        png.append_chunk(chunk_from_strings("FrSt", "Back to first").unwrap());
+
+        assert_eq!(png.chunk_runs(), vec![0..1, 1..2, 2..5, 5..6]);
+    }
+
+    #[test]
+    fn test_display_shows_absolute_index() {
+        let png = testing_png();
+        let display = png.to_string();
+        let lines: Vec<&str> = display.lines().skip(1).collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("#0 "));
+        assert!(lines[2].starts_with("#2 ") && lines[2].contains("LASt"));
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        warnings: std::cell::RefCell<Vec<ParseWarning>>,
+    }
+
+    impl Observer for RecordingObserver {
+        fn on_warning(&self, warning: &ParseWarning) {
+            self.warnings.borrow_mut().push(warning.clone());
+        }
+    }
+
+    /// The chunks serialized as they are, abnormal IEND included
+    fn png_bytes(chunks: Vec<Chunk>) -> Vec<u8> {
+        Png::from_chunks(chunks).as_bytes_with(Serialization::Raw)
+    }
+
+    #[test]
+    fn test_parse_warns_about_missing_iend() {
+        let bytes = png_bytes(testing_chunks());
+        let observer = RecordingObserver::default();
+        let png = Png::parse(&bytes, &ParseOptions::default(), &observer).unwrap();
+
+        // Empty range where IEND should have been
+        let end = bytes.len() as u64;
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(
+            *observer.warnings.borrow(),
+            vec![ParseWarning::MissingIend { range: end..end }]
+        );
+        assert_eq!(png.warnings(), observer.warnings.borrow().as_slice());
+    }
+
+    #[test]
+    fn test_parse_warns_about_chunk_after_iend() {
+        let mut chunks = testing_chunks();
+        chunks.insert(1, Chunk::new(ChunkType::try_from(*b"IEND").unwrap(), Vec::new()));
+
+        let observer = RecordingObserver::default();
+        Png::parse(&png_bytes(chunks), &ParseOptions::default(), &observer).unwrap();
+
+        // signature (8) + FrSt (12 + 20) + IEND (12)
+        assert_eq!(
+            *observer.warnings.borrow(),
+            vec![
+                ParseWarning::ChunkAfterIend {
+                    range: 52..82,
+                    chunk_type: "miDl".to_string()
+                },
+                ParseWarning::ChunkAfterIend {
+                    range: 82..113,
+                    chunk_type: "LASt".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_warns_about_trailing_bytes() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(&[1, 2, 3]);
+
+        let observer = RecordingObserver::default();
+        let png = Png::parse(&bytes, &ParseOptions::default(), &observer).unwrap();
+
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+        assert_eq!(
+            *observer.warnings.borrow(),
+            vec![ParseWarning::TrailingBytes {
+                range: PNG_FILE.len() as u64..PNG_FILE.len() as u64 + 3
+            }]
+        );
+        assert_eq!(png.warnings()[0].name(), "trailing-bytes");
+        assert_eq!(png.warnings()[0].code().as_str(), "W0203");
+    }
+
+    /// Testing chunks with the stored CRC of `miDl` (at 40..70) corrupted
+    fn bytes_with_bad_crc() -> Vec<u8> {
+        let mut bytes = png_bytes(testing_chunks());
+        bytes[69] ^= 0xff;
+        bytes
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_crc() {
+        let result = Png::parse(&bytes_with_bad_crc(), &ParseOptions::default(), &NoopObserver);
+
+        assert!(matches!(
+            result,
+            Err(PngError::ParserError(PngParserError::InvalidChunk(
+                ChunkParserError::InvalidChecksum
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_lenient_parse_keeps_the_image_data_with_a_bad_crc() {
+        let options = ParseOptions {
+            lenient: true,
+            ..ParseOptions::default()
+        };
+        let png = Png::parse(&png_with_bad_crc(), &options, &NoopObserver).unwrap();
+
+        let names: Vec<_> = png.warnings().iter().map(ParseWarning::name).collect();
+        assert_eq!(names, ["crc-mismatch"]);
+        // Written again with its computed CRC, the image is the valid one
+        assert_eq!(png.as_bytes(), minimal_png_bytes());
+    }
+
+    #[test]
+    fn test_lenient_parse_warns_about_reserved_bit() {
+        let options = ParseOptions {
+            lenient: true,
+            ..ParseOptions::default()
+        };
+        let chunks = vec![chunk_from_strings("IHDR", "").unwrap(), chunk_from_strings("rust", "").unwrap()];
+        let bytes = Png::from_chunks(chunks).as_bytes();
+
+        let png = Png::parse(&bytes, &options, &NoopObserver).unwrap();
+
+        let warning = png
+            .warnings()
+            .iter()
+            .find(|warning| warning.name() == "reserved-bit")
+            .unwrap();
+        assert!(matches!(warning, ParseWarning::ReservedBit { chunk_type, .. } if chunk_type == "rust"));
+        assert_eq!(warning.range(), 24..28);
+        assert_eq!(warning.severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn test_lenient_parse_warns_about_bad_crc() {
+        let options = ParseOptions {
+            lenient: true,
+            ..ParseOptions::default()
+        };
+        let png = Png::parse(&bytes_with_bad_crc(), &options, &NoopObserver).unwrap();
+
+        // The testing chunks also hold unknown critical chunks
+        let warning = png
+            .warnings()
+            .iter()
+            .find(|warning| warning.name() == "crc-mismatch")
+            .unwrap();
+        assert!(matches!(warning, ParseWarning::CrcMismatch { chunk_type, .. } if chunk_type == "miDl"));
+        assert_eq!(warning.range(), 66..70);
+        assert_eq!(warning.name(), "crc-mismatch");
+        assert_eq!(warning.code().as_str(), "W0204");
+        assert_eq!(warning.severity(), Severity::Critical);
+        // The chunk is kept, with its computed CRC
+        assert_eq!(
+            png.as_bytes_with(Serialization::Raw),
+            png_bytes(testing_chunks())
+        );
+    }
+
+    fn many_chunks(count: usize) -> Vec<Chunk> {
+        (0..count)
+            .map(|i| {
+                let chunk_type = if i % 2 == 0 { "evEn" } else { "odDs" };
+                chunk_from_strings(chunk_type, "").unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_index_lookups_on_many_chunks() {
+        let mut chunks = many_chunks(100_000);
+        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
+        let png = Png::from_chunks(chunks);
+
+        assert_eq!(
+            png.chunk_by_type("LASt").unwrap().data_as_string().unwrap(),
+            "I am the last chunk"
+        );
+        assert_eq!(png.chunks_by_type("odDs").count(), 50_000);
+        assert!(png.chunk_by_type("miSs").is_none());
+        assert!(png.chunk_by_type("not a type").is_none());
+    }
+
+    #[test]
+    fn test_remove_chunks_where() {
+        let mut png = testing_png();
+
+        let removed = png.remove_chunks_where(|chunk| chunk.chunk_type().is_critical());
+
+        let removed: Vec<String> = removed.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(removed, ["FrSt", "LASt"]);
+        assert_eq!(png.chunks().len(), 1);
+        assert!(png.chunk_by_type("LASt").is_none());
+        assert!(png.chunk_by_type("miDl").is_some());
+    }
+
+    #[test]
+    fn test_remove_chunks_by_type_on_many_chunks() {
+        let mut png = Png::from_chunks(many_chunks(100_000));
+
+        let removed = png.remove_chunks_by_type("evEn");
+
+        assert_eq!(removed.len(), 50_000);
+        assert_eq!(png.chunks().len(), 50_000);
+        assert!(png.chunk_by_type("evEn").is_none());
+        assert_eq!(png.chunks_by_type("odDs").count(), 50_000);
+        assert!(png.remove_chunks_by_type("evEn").is_empty());
+    }
+
+    #[test]
+    fn test_index_stays_in_sync_with_mutations() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "Appended").unwrap());
+        png.insert_chunk(0, chunk_from_strings("miDl", "Inserted").unwrap())
+            .unwrap();
+        png.remove_chunk_at(2).unwrap();
+        png.remove_first_chunk("LASt").unwrap();
+
+        let data: Vec<String> = png
+            .chunks_by_type("miDl")
+            .map(|chunk| chunk.data_as_string().unwrap())
+            .collect();
+        assert_eq!(data, ["Inserted", "Appended"]);
+        assert_eq!(png, Png::from_chunks(png.chunks().to_vec()));
+    }
+
+    #[test]
+    fn test_parse_chunk_count_limit() {
+        let bytes = png_bytes(many_chunks(1001));
+        let options = ParseOptions {
+            max_chunks: 1000,
+            ..ParseOptions::default()
+        };
+
+        let result = Png::parse(&bytes, &options, &NoopObserver);
+        assert!(matches!(
+            result,
+            Err(PngError::ParserError(PngParserError::TooManyChunks { limit: 1000 }))
+        ));
+
+        let options = ParseOptions {
+            max_chunks: 1001,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            Png::parse(&bytes, &options, &NoopObserver).unwrap().chunks().len(),
+            1001
+        );
+    }
+
+    #[test]
+    fn test_parse_degenerate_files() {
+        let parse = |bytes: &[u8]| match Png::try_from(bytes) {
+            Err(PngError::ParserError(err)) => err,
+            other => panic!("expected a parser error, got {other:?}"),
+        };
+
+        assert!(matches!(parse(b""), PngParserError::Empty));
+        assert!(matches!(
+            parse(&Png::STANDARD_HEADER[..5]),
+            PngParserError::Truncated { offset: 0, needed: 8, available: 5 }
+        ));
+        assert!(matches!(parse(&Png::STANDARD_HEADER), PngParserError::NoChunks));
+
+        let partial_ihdr = &testing_png().as_bytes()[..SIGNATURE_LEN + 10];
+        assert!(matches!(
+            parse(partial_ihdr),
+            PngParserError::Truncated { offset: 8, needed: 32, available: 10 }
+        ));
+        assert!(matches!(
+            parse(&partial_ihdr[..SIGNATURE_LEN + 2]),
+            PngParserError::Truncated { offset, needed, available: 2 }
+                if offset == SIGNATURE_LEN as u64 && needed == CHUNK_OVERHEAD as u64
+        ));
+    }
+
+    #[test]
+    fn test_parse_length_field_above_spec_limit() {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+        bytes.extend_from_slice(b"IDAT");
+
+        assert!(matches!(
+            Png::try_from(bytes.as_slice()),
+            Err(PngError::ParserError(PngParserError::ChunkTooLarge {
+                offset: 8,
+                length: u32::MAX
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_chunk_span_past_4_gib() {
+        // Walks the layout of a file made of three maximum-size chunks and an
+        // IEND, going past u32::MAX without allocating it
+        let overhead = CHUNK_OVERHEAD as u64;
+        let largest = MAX_CHUNK_DATA as u64 + overhead;
+        let total = SIGNATURE_LEN as u64 + 3 * largest + overhead;
+        let mut offset = SIGNATURE_LEN as u64;
+
+        for _ in 0..3 {
+            let span = chunk_span(offset, MAX_CHUNK_DATA, total - offset).unwrap();
+            assert_eq!(span.end - span.start, largest);
+            offset = span.end;
+        }
+
+        assert!(offset > u32::MAX as u64);
+        assert_eq!(chunk_span(offset, 0, total - offset).unwrap(), offset..total);
+        assert!(matches!(
+            chunk_span(offset, 1, total - offset),
+            Err(PngParserError::Truncated { offset: at, needed, available })
+                if at == offset && needed == overhead + 1 && available == overhead
+        ));
+    }
+
+    #[test]
+    fn test_chunk_span_limits() {
+        assert!(matches!(
+            chunk_span(8, MAX_CHUNK_DATA + 1, u64::MAX),
+            Err(PngParserError::ChunkTooLarge { offset: 8, .. })
+        ));
+        assert!(matches!(
+            chunk_span(u64::MAX - CHUNK_OVERHEAD as u64 + 1, 0, u64::MAX),
+            Err(PngParserError::FileTooLarge)
+        ));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_chunks_matches_serial() {
+        use crate::hash::sha256_hex;
+        use rayon::prelude::*;
+
+        let chunks = (0..10_000)
+            .map(|i| chunk_from_strings("daTa", &format!("chunk number {i}")).unwrap())
+            .collect();
+        let png = Png::from_chunks(chunks);
+
+        let serial: Vec<String> = png.chunks().iter().map(|chunk| sha256_hex(chunk.data())).collect();
+        let parallel: Vec<String> = png.par_chunks().map(|chunk| sha256_hex(chunk.data())).collect();
+
+        assert_eq!(parallel, serial);
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
         assert!(png.is_ok());
     }
 
+    #[test]
+    fn test_write_to_matches_as_bytes() {
+        let png = testing_png();
+        let mut bytes = Vec::new();
+
+        png.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, png.as_bytes());
+    }
+
+    fn iend_chunk(data: &str) -> Chunk {
+        chunk_from_strings("IEND", data).unwrap()
+    }
+
+    /// Types of the chunks of `png` once written and parsed again
+    fn written_types(png: &Png, mode: Serialization) -> Vec<String> {
+        let mut bytes = Vec::new();
+        png.write_to_with(&mut bytes, mode).unwrap();
+        assert_eq!(bytes, png.as_bytes_with(mode));
+
+        let options = ParseOptions {
+            lenient: true,
+            ..ParseOptions::default()
+        };
+        Png::parse(&bytes, &options, &NoopObserver)
+            .unwrap()
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_missing_iend_is_added_on_write() {
+        let mut png = testing_png();
+        png.remove_first_chunk("miDl").unwrap();
+
+        assert!(!png.has_normal_iend());
+        assert_eq!(
+            written_types(&png, Serialization::Normalized),
+            ["FrSt", "LASt", "IEND"]
+        );
+        assert_eq!(
+            written_types(&png, Serialization::Raw),
+            ["FrSt", "LASt"]
+        );
+    }
+
+    #[test]
+    fn test_double_iend_is_written_once() {
+        let mut png = testing_png();
+        png.append_chunk(iend_chunk(""));
+        png.append_chunk(iend_chunk(""));
+
+        assert!(!png.has_normal_iend());
+        assert_eq!(
+            written_types(&png, Serialization::Normalized),
+            ["FrSt", "miDl", "LASt", "IEND"]
+        );
+        assert_eq!(
+            written_types(&png, Serialization::Raw),
+            ["FrSt", "miDl", "LASt", "IEND", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_iend_in_the_middle_is_moved_last() {
+        let mut png = testing_png();
+        png.insert_chunk(1, iend_chunk("not empty")).unwrap();
+        png.append_chunk(chunk_from_strings("afTr", "after").unwrap());
+
+        assert_eq!(
+            written_types(&png, Serialization::Normalized),
+            ["FrSt", "miDl", "LASt", "afTr", "IEND"]
+        );
+        assert_eq!(
+            written_types(&png, Serialization::Raw),
+            ["FrSt", "IEND", "miDl", "LASt", "afTr"]
+        );
+        // The in-memory image keeps its abnormal layout until normalized
+        assert_eq!(png.chunks()[1].chunk_type().to_string(), "IEND");
+
+        png.normalize_iend();
+
+        assert!(png.has_normal_iend());
+        assert_eq!(png.chunks().last().unwrap().data(), b"");
+        assert_eq!(png.chunk_by_type("afTr").unwrap().data(), b"after");
+        assert_eq!(png, Png::from_chunks(png.chunks().to_vec()));
+    }
+
+    #[test]
+    fn test_normal_iend_is_written_as_is() {
+        for bytes in [PNG_FILE.to_vec(), minimal_png_bytes()] {
+            let png = Png::try_from(bytes.as_slice()).unwrap();
+
+            assert_eq!(png.as_bytes(), png.as_bytes_with(Serialization::Raw));
+            assert_eq!(png.as_bytes(), bytes);
+        }
+    }
+
     #[test]
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
@@ -0,0 +1,284 @@
+//! Pipelines known to drop chunks, for `encode --evade` and
+//! `survivability --profile`.
+//!
+//! A [`Profile`] lists the [`Constraint`]s a chunk must meet to come out of
+//! a pipeline unchanged. The tables follow the documented behaviour of each
+//! tool, cited next to its entry. [`Profile::chunk_type`] and
+//! [`Profile::position`] pick a chunk type and a place meeting them, and
+//! [`Profile::check`] tells which ones an existing chunk meets.
+
+use std::fmt::{self, Display};
+
+use crate::{
+    chunk_type::ChunkType,
+    png::{Png, Position},
+};
+
+/// A property a chunk needs to survive a pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constraint {
+    /// The first letter is lowercase, decoders may skip the chunk
+    Ancillary,
+    /// The fourth letter is lowercase, tools changing the image data may copy
+    /// the chunk without understanding it
+    SafeToCopy,
+    /// The chunk comes before the first IDAT chunk
+    BeforeIdat,
+    /// Neither dimension of the image is larger, bigger images are
+    /// re-encoded
+    MaxDimension(u32),
+}
+
+impl Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constraint::Ancillary => write!(f, "ancillary"),
+            Constraint::SafeToCopy => write!(f, "safe to copy"),
+            Constraint::BeforeIdat => write!(f, "before IDAT"),
+            Constraint::MaxDimension(pixels) => {
+                write!(f, "image at most {pixels} px wide and high")
+            }
+        }
+    }
+}
+
+/// What a pipeline keeps
+#[derive(Debug, PartialEq, Eq)]
+pub struct Profile {
+    /// Name given to `--evade` and `--profile`
+    pub name: &'static str,
+    pub description: &'static str,
+    pub constraints: &'static [Constraint],
+}
+
+pub const PROFILES: &[Profile] = &[
+    // oxipng keeps every chunk unless given --strip (README, "--strip"), but
+    // it rewrites IDAT, and PNG 1.2 section 14.2 lets an editor changing
+    // critical data drop the unknown chunks that are not safe to copy.
+    Profile {
+        name: "oxipng-default",
+        description: "oxipng without --strip",
+        constraints: &[Constraint::Ancillary, Constraint::SafeToCopy],
+    },
+    // pngcrush copies ancillary chunks unless told to -rem them (pngcrush
+    // -help). It goes through libpng, which puts unknown chunks back
+    // relative to PLTE and IDAT (libpng manual, "Unknown-chunk handling"),
+    // chunks after IDAT being the first lost by older builds.
+    Profile {
+        name: "pngcrush-default",
+        description: "pngcrush without -rem",
+        constraints: &[
+            Constraint::Ancillary,
+            Constraint::SafeToCopy,
+            Constraint::BeforeIdat,
+        ],
+    },
+    // WordPress stores the uploaded file as is, but since 5.3 replaces
+    // images over 2560 px with a scaled copy (big_image_size_threshold),
+    // and every resized copy is re-encoded by GD or Imagick, which write no
+    // unknown chunk.
+    Profile {
+        name: "wordpress",
+        description: "WordPress media uploads, original size",
+        constraints: &[
+            Constraint::Ancillary,
+            Constraint::SafeToCopy,
+            Constraint::MaxDimension(2560),
+        ],
+    },
+];
+
+/// The profile named `name`
+pub fn find(name: &str) -> Option<&'static Profile> {
+    PROFILES.iter().find(|profile| profile.name == name)
+}
+
+/// Parses `--evade` and `--profile`
+pub fn parse_profile(name: &str) -> Result<&'static Profile, String> {
+    find(name).ok_or_else(|| {
+        let names: Vec<&str> = PROFILES.iter().map(|profile| profile.name).collect();
+        format!(
+            "unknown profile '{name}', expected one of {}",
+            names.join(", ")
+        )
+    })
+}
+
+impl Profile {
+    /// `requested` with the casing the constraints call for. The reserved
+    /// bit is always cleared.
+    pub fn chunk_type(&self, requested: ChunkType) -> ChunkType {
+        let mut bytes = requested.bytes();
+        bytes[2] = bytes[2].to_ascii_uppercase();
+        for constraint in self.constraints {
+            match constraint {
+                Constraint::Ancillary => bytes[0] = bytes[0].to_ascii_lowercase(),
+                Constraint::SafeToCopy => bytes[3] = bytes[3].to_ascii_lowercase(),
+                Constraint::BeforeIdat | Constraint::MaxDimension(_) => {}
+            }
+        }
+
+        ChunkType::try_from(bytes).expect("changing the case keeps a valid type")
+    }
+
+    /// Where a new chunk goes
+    pub fn position(&self) -> Position {
+        match self.constraints.contains(&Constraint::BeforeIdat) {
+            true => Position::AfterIhdr,
+            false => Position::BeforeIend,
+        }
+    }
+
+    /// Whether the chunk at `index` of `png` meets each constraint
+    pub fn check(&self, png: &Png, index: usize) -> Vec<(Constraint, bool)> {
+        let chunk_type = png.chunks()[index].chunk_type();
+        let first_idat = png
+            .chunks()
+            .iter()
+            .position(|chunk| chunk.chunk_type().bytes() == *b"IDAT");
+
+        self.constraints
+            .iter()
+            .map(|&constraint| {
+                let met = match constraint {
+                    Constraint::Ancillary => !chunk_type.is_critical(),
+                    Constraint::SafeToCopy => chunk_type.is_safe_to_copy(),
+                    Constraint::BeforeIdat => first_idat.is_none_or(|idat| index < idat),
+                    Constraint::MaxDimension(pixels) => fits(png, pixels),
+                };
+                (constraint, met)
+            })
+            .collect()
+    }
+
+    /// Constraints on the whole image that `png` doesn't meet
+    pub fn image_problems(&self, png: &Png) -> Vec<Constraint> {
+        self.constraints
+            .iter()
+            .copied()
+            .filter(|&constraint| match constraint {
+                Constraint::MaxDimension(pixels) => !fits(png, pixels),
+                _ => false,
+            })
+            .collect()
+    }
+}
+
+/// Whether both dimensions of `png` are at most `pixels`. Images without a
+/// readable header are given the benefit of the doubt.
+fn fits(png: &Png, pixels: u32) -> bool {
+    let Some(ihdr) = png.chunk_by_type("IHDR") else {
+        return true;
+    };
+    let Some((width, rest)) = ihdr.data().split_first_chunk::<4>() else {
+        return true;
+    };
+    let Some(height) = rest.first_chunk::<4>() else {
+        return true;
+    };
+
+    u32::from_be_bytes(*width) <= pixels && u32::from_be_bytes(*height) <= pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::chunk::Chunk;
+
+    use super::*;
+
+    fn image(width: u32, chunks: &[&str]) -> Png {
+        let ihdr = [
+            &width.to_be_bytes()[..],
+            &1_u32.to_be_bytes(),
+            &[8, 6, 0, 0, 0],
+        ]
+        .concat();
+        let chunks = chunks
+            .iter()
+            .map(|&chunk_type| {
+                let data = match chunk_type {
+                    "IHDR" => ihdr.clone(),
+                    _ => Vec::new(),
+                };
+                Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+            })
+            .collect();
+
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_generated_chunks_meet_every_constraint() {
+        for profile in PROFILES {
+            for requested in ["RUST", "ruSt", "rust", "tEXt", "ABcD"] {
+                let chunk_type = profile.chunk_type(ChunkType::from_str(requested).unwrap());
+                assert!(chunk_type.is_reserved_bit_valid(), "{}", profile.name);
+
+                // The new chunk goes where `position` puts it in a small image
+                let mut png = image(16, &["IHDR", "IDAT", "IEND"]);
+                let index = png.index_of(profile.position()).unwrap();
+                png.insert_chunk(index, Chunk::new(chunk_type, b"hi".to_vec()))
+                    .unwrap();
+
+                for (constraint, met) in profile.check(&png, index) {
+                    assert!(met, "{} {requested}: {constraint}", profile.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunk_type_keeps_conforming_names() {
+        let profile = find("pngcrush-default").unwrap();
+
+        assert_eq!(
+            profile.chunk_type(ChunkType::from_str("ruSt").unwrap()),
+            ChunkType::from_str("ruSt").unwrap()
+        );
+        assert_eq!(
+            profile.chunk_type(ChunkType::from_str("RUST").unwrap()),
+            ChunkType::from_str("rUSt").unwrap()
+        );
+        assert_eq!(profile.position(), Position::AfterIhdr);
+        assert_eq!(
+            find("oxipng-default").unwrap().position(),
+            Position::BeforeIend
+        );
+    }
+
+    #[test]
+    fn test_check_reports_unmet_constraints() {
+        let profile = find("wordpress").unwrap();
+        let png = image(4000, &["IHDR", "IDAT", "ruST", "IEND"]);
+
+        assert_eq!(
+            profile.check(&png, 2),
+            [
+                (Constraint::Ancillary, true),
+                (Constraint::SafeToCopy, false),
+                (Constraint::MaxDimension(2560), false),
+            ]
+        );
+        assert_eq!(
+            profile.image_problems(&png),
+            [Constraint::MaxDimension(2560)]
+        );
+        assert!(
+            profile
+                .image_problems(&image(2560, &["IHDR", "IEND"]))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_parse_profile() {
+        assert_eq!(parse_profile("wordpress").unwrap().name, "wordpress");
+        assert!(
+            parse_profile("gimp")
+                .unwrap_err()
+                .contains("oxipng-default")
+        );
+    }
+}
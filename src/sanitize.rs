@@ -0,0 +1,60 @@
+/// Whether `c` could rearrange or hide the surrounding terminal output:
+/// control characters other than newline and tab, bidi controls and
+/// invisible format characters.
+fn is_unsafe_for_terminal(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'                  // soft hyphen
+        | '\u{061C}'                // Arabic letter mark
+        | '\u{200B}'..='\u{200F}'   // zero-width space, joiners, LRM, RLM
+        | '\u{202A}'..='\u{202E}'   // bidi embeddings and overrides
+        | '\u{2060}'..='\u{2064}'   // word joiner, invisible operators
+        | '\u{2066}'..='\u{2069}'   // bidi isolates
+        | '\u{FEFF}'                // zero-width no-break space
+    ) || (c.is_control() && c != '\n' && c != '\t')
+}
+
+/// Escapes the characters of `text` that could spoof terminal output as
+/// their code point (`\u{202e}`). Meant for human readable output only,
+/// programmatic output must stay faithful.
+pub fn escape_for_terminal(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if is_unsafe_for_terminal(c) {
+            escaped.push_str(&format!("\\u{{{:04x}}}", c as u32));
+        } else {
+            escaped.push(c);
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_right_to_left_override() {
+        let escaped = escape_for_terminal("found\u{202E}dnuof ton");
+
+        assert_eq!(escaped, "found\\u{202e}dnuof ton");
+        assert!(!escaped.contains('\u{202E}'));
+    }
+
+    #[test]
+    fn test_escape_invisible_characters() {
+        assert_eq!(
+            escape_for_terminal("a\u{200D}b\u{2067}c\u{FEFF}\u{1b}[2J"),
+            "a\\u{200d}b\\u{2067}c\\u{feff}\\u{001b}[2J"
+        );
+    }
+
+    #[test]
+    fn test_keep_regular_text() {
+        let text = "Hello, wörld!\nשלום\tمرحبا 👋";
+
+        assert_eq!(escape_for_terminal(text), text);
+    }
+}
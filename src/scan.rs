@@ -0,0 +1,349 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    chunk::Chunk, chunk_ref::ChunkRef, clock::format_timestamp, codes::Severity, envelope::Envelope,
+    png::Png, sniff,
+};
+
+/// Size a standard ancillary chunk is expected to have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpectedSize {
+    /// Fixed by the spec, any other size is malformed
+    Exact(u32),
+    /// Typical upper bound, larger chunks are legal but unusual
+    AtMost(u32),
+}
+
+/// Expected sizes of standard ancillary chunks and the severity of a chunk
+/// that doesn't match.
+pub const EXPECTED_SIZES: &[(&[u8; 4], ExpectedSize, Severity)] = &[
+    (b"cHRM", ExpectedSize::Exact(32), Severity::Critical),
+    (b"gAMA", ExpectedSize::Exact(4), Severity::Critical),
+    (b"sRGB", ExpectedSize::Exact(1), Severity::Critical),
+    (b"pHYs", ExpectedSize::Exact(9), Severity::Critical),
+    (b"oFFs", ExpectedSize::Exact(9), Severity::Critical),
+    (b"tIME", ExpectedSize::Exact(7), Severity::Critical),
+    (b"bKGD", ExpectedSize::AtMost(6), Severity::Critical),
+    (b"tEXt", ExpectedSize::AtMost(4 * 1024), Severity::Warning),
+    (b"zTXt", ExpectedSize::AtMost(4 * 1024), Severity::Warning),
+    (b"iTXt", ExpectedSize::AtMost(8 * 1024), Severity::Warning),
+];
+
+/// Thresholds of the heuristics that don't come from the spec
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanOptions {
+    /// Private chunks larger than this many bytes are flagged
+    pub max_private_size: u64,
+    /// Ancillary chunks larger than this fraction of the IDAT data are flagged
+    pub max_idat_ratio: f64,
+    /// Current time in seconds since the epoch, expired messages are only
+    /// flagged when it is set
+    pub now: Option<u64>,
+}
+
+impl ScanOptions {
+    pub const DEFAULT_MAX_PRIVATE_SIZE: u64 = 64 * 1024;
+    pub const DEFAULT_MAX_IDAT_RATIO: f64 = 0.5;
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            max_private_size: Self::DEFAULT_MAX_PRIVATE_SIZE,
+            max_idat_ratio: Self::DEFAULT_MAX_IDAT_RATIO,
+            now: None,
+        }
+    }
+}
+
+/// Something unusual about a chunk
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// Absolute index of the chunk, as shown by `print`
+    pub index: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] #{} {}", self.severity, self.index, self.message)
+    }
+}
+
+/// Flags ancillary chunks whose size doesn't fit their type or the image,
+/// or holding a whole file of another format.
+/// Findings are sorted by decreasing severity, then by chunk index.
+pub fn scan(png: &Png, options: &ScanOptions) -> Vec<Finding> {
+    scan_chunks(&png.chunk_refs().collect::<Vec<_>>(), options)
+}
+
+/// Same as [`scan`] over chunks borrowed from the bytes of a file
+pub fn scan_chunks(chunks: &[ChunkRef], options: &ScanOptions) -> Vec<Finding> {
+    let idat_size: u64 = chunks
+        .iter()
+        .filter(|chunk| chunk.chunk_type.bytes() == *b"IDAT")
+        .map(|chunk| chunk.length() as u64)
+        .sum();
+
+    let mut findings = Vec::new();
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let finding = |severity, message| Finding {
+            index,
+            severity,
+            message,
+        };
+
+        if chunk.chunk_type.is_unknown_critical() {
+            findings.push(finding(
+                Severity::Critical,
+                format!(
+                    "unknown critical {} chunk with {} bytes, decoders reject the file; a common way to smuggle data",
+                    chunk.chunk_type,
+                    chunk.length()
+                ),
+            ));
+        }
+        if chunk.chunk_type.is_critical() {
+            continue;
+        }
+
+        if let Some((severity, message)) = check_expected_size(chunk) {
+            findings.push(finding(severity, message));
+        }
+
+        if let Some(expires_at) = options.now.and_then(|now| expiry_of(chunk.data, now)) {
+            findings.push(finding(
+                Severity::Info,
+                format!(
+                    "{} chunk holds a message that expired on {}, remove it with `strip --expired-only`",
+                    chunk.chunk_type,
+                    format_timestamp(expires_at)
+                ),
+            ));
+        }
+
+        if let Some(kind) = sniff::magic(chunk.data) {
+            findings.push(finding(
+                Severity::Warning,
+                format!(
+                    "{} chunk holds a {kind}, the file may also open as one",
+                    chunk.chunk_type
+                ),
+            ));
+        }
+
+        let length = chunk.length() as u64;
+        let too_large = !chunk.chunk_type.is_public() && length > options.max_private_size;
+        let ratio = length as f64 / idat_size as f64;
+        let too_large_for_image = idat_size > 0 && ratio > options.max_idat_ratio;
+
+        // One finding per chunk, critical when it is both
+        let size = format!("{} chunk has {length} bytes", chunk.chunk_type);
+        let share = format!("{:.0}% of the {idat_size} bytes of image data", ratio * 100.0);
+        let limit = options.max_private_size;
+        match (too_large, too_large_for_image) {
+            (true, true) => findings.push(finding(
+                Severity::Critical,
+                format!("private {size}, more than {limit} bytes and {share}"),
+            )),
+            (true, false) => findings.push(finding(
+                Severity::Warning,
+                format!("private {size}, more than {limit} bytes"),
+            )),
+            (false, true) => findings.push(finding(Severity::Warning, format!("{size}, {share}"))),
+            (false, false) => {}
+        }
+    }
+
+    findings.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.index.cmp(&b.index)));
+    findings
+}
+
+/// Expiry of the pngme message carried by `chunk`, if it expired
+pub fn expired_at(chunk: &Chunk, now: u64) -> Option<u64> {
+    expiry_of(chunk.data(), now)
+}
+
+fn expiry_of(data: &[u8], now: u64) -> Option<u64> {
+    Envelope::parse(data)
+        .filter(|envelope| envelope.is_expired(now))
+        .and_then(|envelope| envelope.expires_at)
+}
+
+fn check_expected_size(chunk: &ChunkRef) -> Option<(Severity, String)> {
+    let chunk_type = chunk.chunk_type;
+    let (_, expected, severity) = EXPECTED_SIZES
+        .iter()
+        .find(|(name, _, _)| **name == chunk_type.bytes())?;
+    let length = chunk.length();
+
+    match *expected {
+        ExpectedSize::Exact(size) if length != size => Some((
+            *severity,
+            format!("{chunk_type} chunk has {length} bytes, expected {size}"),
+        )),
+        ExpectedSize::AtMost(size) if length > size => Some((
+            *severity,
+            format!("{chunk_type} chunk has {length} bytes, usually at most {size}"),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, length: usize) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; length])
+    }
+
+    fn image(extra: Vec<Chunk>) -> Png {
+        let mut chunks = vec![chunk("IHDR", 13), chunk("IDAT", 50 * 1024)];
+        chunks.extend(extra);
+        chunks.push(chunk("IEND", 0));
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_scan_clean_image() {
+        let png = image(vec![chunk("pHYs", 9), chunk("tIME", 7), chunk("tEXt", 100)]);
+
+        assert!(scan(&png, &ScanOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_scan_wrong_length_time() {
+        let png = image(vec![chunk("tIME", 12)]);
+
+        let findings = scan(&png, &ScanOptions::default());
+
+        assert_eq!(
+            findings,
+            [Finding {
+                index: 2,
+                severity: Severity::Critical,
+                message: "tIME chunk has 12 bytes, expected 7".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_scan_large_text() {
+        let png = image(vec![chunk("tEXt", 5000)]);
+
+        let findings = scan(&png, &ScanOptions::default());
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert_eq!(
+            findings[0].message,
+            "tEXt chunk has 5000 bytes, usually at most 4096"
+        );
+    }
+
+    #[test]
+    fn test_scan_huge_private_chunk() {
+        let png = image(vec![chunk("ruSt", 2 * 1024 * 1024)]);
+
+        let findings = scan(&png, &ScanOptions::default());
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Critical);
+        assert_eq!(
+            findings[0].message,
+            "private ruSt chunk has 2097152 bytes, more than 65536 bytes and 4096% of the 51200 bytes of image data"
+        );
+    }
+
+    #[test]
+    fn test_scan_one_size_finding_per_chunk() {
+        let png = image(vec![chunk("ruSt", 2 * 1024 * 1024)]);
+        let only = |max_private_size, max_idat_ratio| {
+            scan(
+                &png,
+                &ScanOptions {
+                    max_private_size,
+                    max_idat_ratio,
+                    ..ScanOptions::default()
+                },
+            )
+        };
+
+        let findings = only(ScanOptions::DEFAULT_MAX_PRIVATE_SIZE, 100.0);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert_eq!(
+            findings[0].message,
+            "private ruSt chunk has 2097152 bytes, more than 65536 bytes"
+        );
+
+        let findings = only(4 * 1024 * 1024, ScanOptions::DEFAULT_MAX_IDAT_RATIO);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert_eq!(
+            findings[0].message,
+            "ruSt chunk has 2097152 bytes, 4096% of the 51200 bytes of image data"
+        );
+    }
+
+    #[test]
+    fn test_scan_thresholds_are_configurable() {
+        let png = image(vec![chunk("ruSt", 2 * 1024 * 1024)]);
+        let options = ScanOptions {
+            max_private_size: 4 * 1024 * 1024,
+            max_idat_ratio: 100.0,
+            ..ScanOptions::default()
+        };
+
+        assert!(scan(&png, &options).is_empty());
+    }
+
+    #[test]
+    fn test_scan_expired_message() {
+        let payload = Envelope {
+            expires_at: Some(1_000),
+            ..Envelope::new(b"hi".to_vec())
+        }
+        .to_bytes();
+        let png = image(vec![Chunk::new(ChunkType::from_str("ruSt").unwrap(), payload)]);
+
+        let at = |now| ScanOptions {
+            now,
+            ..ScanOptions::default()
+        };
+
+        assert!(scan(&png, &at(None)).is_empty());
+        assert!(scan(&png, &at(Some(999))).is_empty());
+        assert_eq!(
+            scan(&png, &at(Some(1_000)))[0].message,
+            "ruSt chunk holds a message that expired on 1970-01-01T00:16:40Z, remove it with `strip --expired-only`"
+        );
+    }
+
+    #[test]
+    fn test_scan_embedded_file() {
+        let zip = b"PK\x03\x04rest of the archive".to_vec();
+        let png = image(vec![Chunk::new(ChunkType::from_str("ruSt").unwrap(), zip)]);
+
+        let findings = scan(&png, &ScanOptions::default());
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert_eq!(
+            findings[0].message,
+            "ruSt chunk holds a ZIP archive, the file may also open as one"
+        );
+    }
+
+    #[test]
+    fn test_scan_small_private_chunk_in_large_image() {
+        let png = image(vec![chunk("ruSt", 1024)]);
+
+        assert!(scan(&png, &ScanOptions::default()).is_empty());
+    }
+}
@@ -0,0 +1,350 @@
+//! Passphrases given to pngme.
+//!
+//! A passphrase comes from a [`SecretSource`]: the command line, a file, a
+//! file descriptor, the OS keychain or an interactive prompt. Commands only
+//! see the resolved passphrase, so every source works wherever one is
+//! expected. Keychain access goes through the [`Keychain`] trait,
+//! [`MemoryKeychain`] standing in for the OS keychain in tests.
+//!
+//! Passphrases are [`Zeroizing`], their memory is wiped once dropped.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env,
+    fs::File,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::codes::Code;
+
+/// Service name of the keychain entries written by pngme
+pub const KEYCHAIN_SERVICE: &str = "pngme";
+
+/// Environment variable read when no passphrase flag is given
+pub const PASSPHRASE_VARIABLE: &str = "PNGME_PASSPHRASE";
+
+#[derive(Error, Debug)]
+pub enum SecretError {
+    #[error("Could not read the passphrase: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("No passphrase named {name} in the keychain")]
+    NotFound { name: String },
+
+    #[error(
+        "The keychain is not available ({reason}), pass the passphrase with --password-file instead"
+    )]
+    NoKeychain { reason: String },
+
+    #[error("The passphrase is empty")]
+    Empty,
+}
+
+impl SecretError {
+    pub fn code(&self) -> Code {
+        match self {
+            SecretError::Io(_) => Code::SecretReadFailed,
+            SecretError::NotFound { .. } => Code::SecretNotFound,
+            SecretError::NoKeychain { .. } => Code::KeychainUnavailable,
+            SecretError::Empty => Code::EmptySecret,
+        }
+    }
+}
+
+/// Named passphrases kept by the system
+pub trait Keychain {
+    fn get(&self, name: &str) -> Result<String, SecretError>;
+
+    fn set(&self, name: &str, secret: &str) -> Result<(), SecretError>;
+
+    fn delete(&self, name: &str) -> Result<(), SecretError>;
+}
+
+/// Keychain of the operating system: Keychain on macOS, Credential Manager
+/// on Windows and the Secret Service (GNOME Keyring, KWallet) on Linux
+#[cfg(feature = "keyring")]
+pub struct OsKeychain;
+
+#[cfg(feature = "keyring")]
+impl OsKeychain {
+    fn entry(name: &str) -> Result<keyring::Entry, SecretError> {
+        keyring::Entry::new(KEYCHAIN_SERVICE, name).map_err(Self::unavailable)
+    }
+
+    fn unavailable(err: keyring::Error) -> SecretError {
+        SecretError::NoKeychain {
+            reason: err.to_string(),
+        }
+    }
+
+    fn lookup_error(name: &str, err: keyring::Error) -> SecretError {
+        match err {
+            keyring::Error::NoEntry => SecretError::NotFound {
+                name: name.to_string(),
+            },
+            err => Self::unavailable(err),
+        }
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl Keychain for OsKeychain {
+    fn get(&self, name: &str) -> Result<String, SecretError> {
+        Self::entry(name)?
+            .get_password()
+            .map_err(|err| Self::lookup_error(name, err))
+    }
+
+    fn set(&self, name: &str, secret: &str) -> Result<(), SecretError> {
+        // Any failure here means there is nowhere to store the passphrase
+        Self::entry(name)?
+            .set_password(secret)
+            .map_err(Self::unavailable)
+    }
+
+    fn delete(&self, name: &str) -> Result<(), SecretError> {
+        Self::entry(name)?
+            .delete_credential()
+            .map_err(|err| Self::lookup_error(name, err))
+    }
+}
+
+/// Stand-in for builds without the `keyring` feature
+pub struct NoKeychain;
+
+impl NoKeychain {
+    fn error() -> SecretError {
+        SecretError::NoKeychain {
+            reason: "pngme was built without the keyring feature".to_string(),
+        }
+    }
+}
+
+impl Keychain for NoKeychain {
+    fn get(&self, _name: &str) -> Result<String, SecretError> {
+        Err(Self::error())
+    }
+
+    fn set(&self, _name: &str, _secret: &str) -> Result<(), SecretError> {
+        Err(Self::error())
+    }
+
+    fn delete(&self, _name: &str) -> Result<(), SecretError> {
+        Err(Self::error())
+    }
+}
+
+/// The keychain of this build: [`OsKeychain`] with the `keyring` feature,
+/// [`NoKeychain`] otherwise
+pub fn default_keychain() -> &'static dyn Keychain {
+    #[cfg(feature = "keyring")]
+    return &OsKeychain;
+
+    #[cfg(not(feature = "keyring"))]
+    return &NoKeychain;
+}
+
+/// Keeps passphrases in memory
+#[derive(Debug, Default)]
+pub struct MemoryKeychain {
+    entries: RefCell<HashMap<String, String>>,
+}
+
+impl Keychain for MemoryKeychain {
+    fn get(&self, name: &str) -> Result<String, SecretError> {
+        self.entries
+            .borrow()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SecretError::NotFound {
+                name: name.to_string(),
+            })
+    }
+
+    fn set(&self, name: &str, secret: &str) -> Result<(), SecretError> {
+        self.entries
+            .borrow_mut()
+            .insert(name.to_string(), secret.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> Result<(), SecretError> {
+        self.entries
+            .borrow_mut()
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| SecretError::NotFound {
+                name: name.to_string(),
+            })
+    }
+}
+
+/// Where a passphrase comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretSource {
+    /// Given on the command line
+    Flag(String),
+    /// Content of a file, less one trailing newline
+    File(PathBuf),
+    /// Read from an inherited file descriptor, less one trailing newline
+    #[cfg(unix)]
+    Fd(i32),
+    /// Stored in the keychain under this name
+    Keychain(String),
+    /// Value of an environment variable
+    Variable(String),
+    /// Typed by the user, without echo
+    Prompt,
+}
+
+impl SecretSource {
+    pub fn read(&self, keychain: &dyn Keychain) -> Result<Zeroizing<String>, SecretError> {
+        let secret = match self {
+            SecretSource::Flag(secret) => Zeroizing::new(secret.clone()),
+            SecretSource::File(path) => read_secret(&mut File::open(path)?)?,
+            // The descriptor is opened again rather than taken over
+            #[cfg(unix)]
+            SecretSource::Fd(fd) => read_secret(&mut File::open(format!("/dev/fd/{fd}"))?)?,
+            SecretSource::Keychain(name) => Zeroizing::new(keychain.get(name)?),
+            SecretSource::Variable(name) => {
+                Zeroizing::new(env::var(name).map_err(io::Error::other)?)
+            }
+            SecretSource::Prompt => Zeroizing::new(rpassword::prompt_password("Passphrase: ")?),
+        };
+
+        if secret.is_empty() {
+            return Err(SecretError::Empty);
+        }
+
+        Ok(secret)
+    }
+}
+
+/// Everything `reader` holds, less one trailing newline (`\n` or `\r\n`),
+/// so that `echo secret > file` gives `secret`
+fn read_secret(reader: &mut dyn Read) -> Result<Zeroizing<String>, SecretError> {
+    let mut secret = Zeroizing::new(String::new());
+    reader.read_to_string(&mut secret)?;
+
+    if secret.ends_with('\n') {
+        secret.pop();
+        if secret.ends_with('\r') {
+            secret.pop();
+        }
+    }
+
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sources_are_interchangeable() {
+        let keychain = MemoryKeychain::default();
+        keychain.set("work", "correct horse").unwrap();
+
+        let flag = SecretSource::Flag("correct horse".to_string());
+        let stored = SecretSource::Keychain("work".to_string());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passphrase");
+        std::fs::write(&path, "correct horse\n").unwrap();
+        let file = SecretSource::File(path);
+
+        for source in [flag, stored, file] {
+            assert_eq!(
+                *source.read(&keychain).unwrap(),
+                "correct horse",
+                "{source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_keychain_entry() {
+        let keychain = MemoryKeychain::default();
+        let source = SecretSource::Keychain("missing".to_string());
+
+        assert!(matches!(
+            source.read(&keychain),
+            Err(SecretError::NotFound { name }) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn test_deleted_entry_is_gone() {
+        let keychain = MemoryKeychain::default();
+        keychain.set("work", "secret").unwrap();
+
+        keychain.delete("work").unwrap();
+
+        assert!(matches!(
+            keychain.get("work"),
+            Err(SecretError::NotFound { .. })
+        ));
+        assert!(matches!(
+            keychain.delete("work"),
+            Err(SecretError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_no_keychain_suggests_password_file() {
+        let source = SecretSource::Keychain("work".to_string());
+        let err = source.read(&NoKeychain).unwrap_err();
+
+        assert_eq!(err.code(), Code::KeychainUnavailable);
+        assert!(err.to_string().contains("--password-file"), "{err}");
+    }
+
+    #[test]
+    fn test_empty_passphrase() {
+        let source = SecretSource::Flag(String::new());
+
+        assert!(matches!(
+            source.read(&MemoryKeychain::default()),
+            Err(SecretError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_one_trailing_newline_is_trimmed() {
+        let read = |bytes: &[u8]| read_secret(&mut &bytes[..]).unwrap().to_string();
+
+        assert_eq!(read(b"secret\n"), "secret");
+        assert_eq!(read(b"secret\r\n"), "secret");
+        assert_eq!(read(b"secret"), "secret");
+        // Only one newline is part of the file format, the rest is passphrase
+        assert_eq!(read(b"secret\n\n"), "secret\n");
+        assert_eq!(read(b" secret \n"), " secret ");
+    }
+
+    #[test]
+    fn test_file_with_only_a_newline_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passphrase");
+        std::fs::write(&path, "\n").unwrap();
+
+        assert!(matches!(
+            SecretSource::File(path).read(&MemoryKeychain::default()),
+            Err(SecretError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let source = SecretSource::File(PathBuf::from("/nonexistent/passphrase"));
+
+        assert!(matches!(
+            source.read(&MemoryKeychain::default()),
+            Err(SecretError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,337 @@
+//! Chunk selections given with `--select`, shared by `export-meta`,
+//! `import-meta` and `strip` so that a selection means the same to each.
+//!
+//! A selection is a comma-separated list of terms read left to right. A term
+//! adds the chunks it matches, or removes them when it starts with `!`, so a
+//! later term wins over an earlier one: `private,text,!zTXt` selects the
+//! private chunks and the text chunks but zTXt, `text,!text` nothing. A
+//! selection starting with a negation starts from every chunk instead of
+//! none, `!iCCP` alone selecting everything but iCCP.
+//!
+//! | Term      | Chunks                                                  |
+//! |-----------|---------------------------------------------------------|
+//! | `all`     | every chunk                                             |
+//! | `private` | private types, whose second letter is lowercase         |
+//! | `public`  | public types                                            |
+//! | `text`    | tEXt, zTXt and iTXt                                     |
+//! | `color`   | the [`RENDERING_CHUNK_TYPES`], colors and transparency  |
+//! | `time`    | tIME                                                    |
+//! | `ruSt`    | chunks of that type                                     |
+//!
+//! Categories are lowercase, any other term is read as a chunk type, whose
+//! case matters.
+//!
+//! [`RENDERING_CHUNK_TYPES`]: crate::chunk_type::RENDERING_CHUNK_TYPES
+
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::{chunk_type::ChunkType, codes::Code};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SelectionError {
+    #[error("Term {position} of the selection is empty")]
+    EmptyTerm { position: usize },
+
+    #[error(
+        "Unknown term '{term}', expected a chunk type or one of {}",
+        Category::ALL.map(|category| category.name()).join(", ")
+    )]
+    UnknownTerm { term: String },
+}
+
+impl SelectionError {
+    pub fn code(&self) -> Code {
+        match self {
+            SelectionError::EmptyTerm { .. } => Code::SelectionEmptyTerm,
+            SelectionError::UnknownTerm { .. } => Code::SelectionUnknownTerm,
+        }
+    }
+}
+
+/// Groups of chunk types a term can name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    All,
+    Private,
+    Public,
+    Text,
+    Color,
+    Time,
+}
+
+impl Category {
+    pub const ALL: [Category; 6] = [
+        Category::All,
+        Category::Private,
+        Category::Public,
+        Category::Text,
+        Category::Color,
+        Category::Time,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::All => "all",
+            Category::Private => "private",
+            Category::Public => "public",
+            Category::Text => "text",
+            Category::Color => "color",
+            Category::Time => "time",
+        }
+    }
+
+    pub fn contains(self, chunk_type: &ChunkType) -> bool {
+        match self {
+            Category::All => true,
+            Category::Private => !chunk_type.is_public(),
+            Category::Public => chunk_type.is_public(),
+            Category::Text => [b"tEXt", b"zTXt", b"iTXt"].contains(&&chunk_type.bytes()),
+            Category::Color => chunk_type.affects_rendering(),
+            Category::Time => chunk_type.bytes() == *b"tIME",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Matcher {
+    Category(Category),
+    Type(ChunkType),
+}
+
+impl Matcher {
+    fn matches(self, chunk_type: &ChunkType) -> bool {
+        match self {
+            Matcher::Category(category) => category.contains(chunk_type),
+            Matcher::Type(selected) => selected == *chunk_type,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Term {
+    /// Whether matching chunks are added, else removed
+    include: bool,
+    matcher: Matcher,
+}
+
+/// A parsed `--select` value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    terms: Vec<Term>,
+}
+
+impl Selection {
+    /// Whether the selection keeps chunks of type `chunk_type`
+    pub fn matches(&self, chunk_type: &ChunkType) -> bool {
+        let start = self.terms.first().is_some_and(|term| !term.include);
+
+        self.terms.iter().fold(start, |selected, term| {
+            match term.matcher.matches(chunk_type) {
+                true => term.include,
+                false => selected,
+            }
+        })
+    }
+
+    /// The warning about keeping `selected` of `candidates` chunks: none
+    /// out of some is most likely a mistyped term
+    pub fn empty_warning(&self, candidates: usize, selected: usize) -> Option<SelectionWarning> {
+        (candidates > 0 && selected == 0).then(|| SelectionWarning {
+            code: Code::EmptySelection,
+            message: format!("--select {self} matches none of the {candidates} chunk(s)"),
+        })
+    }
+}
+
+impl FromStr for Selection {
+    type Err = SelectionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let terms = s
+            .split(',')
+            .enumerate()
+            .map(|(index, term)| {
+                let term = term.trim();
+                let (include, name) = match term.strip_prefix('!') {
+                    Some(name) => (false, name.trim_start()),
+                    None => (true, term),
+                };
+                if name.is_empty() {
+                    return Err(SelectionError::EmptyTerm {
+                        position: index + 1,
+                    });
+                }
+
+                let category = Category::ALL
+                    .into_iter()
+                    .find(|category| category.name() == name);
+                let matcher = match category {
+                    Some(category) => Matcher::Category(category),
+                    None => {
+                        Matcher::Type(name.parse().map_err(|_| SelectionError::UnknownTerm {
+                            term: name.to_string(),
+                        })?)
+                    }
+                };
+
+                Ok(Term { include, matcher })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { terms })
+    }
+}
+
+impl Display for Selection {
+    /// The terms as given, without spaces: `private,!ruSt`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, term) in self.terms.iter().enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+            if !term.include {
+                write!(f, "!")?;
+            }
+            match term.matcher {
+                Matcher::Category(category) => write!(f, "{}", category.name())?,
+                Matcher::Type(chunk_type) => write!(f, "{chunk_type}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A selection that kept no chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionWarning {
+    pub code: Code,
+    pub message: String,
+}
+
+impl Display for SelectionWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "warning[{}]: {}", self.code, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(value: &str) -> Selection {
+        value.parse().unwrap()
+    }
+
+    fn selected(value: &str) -> Vec<&'static str> {
+        let selection = selection(value);
+        [
+            "tEXt", "zTXt", "iTXt", "iCCP", "gAMA", "tIME", "pHYs", "ruSt", "abCd",
+        ]
+        .into_iter()
+        .filter(|chunk_type| selection.matches(&chunk_type.parse().unwrap()))
+        .collect()
+    }
+
+    #[test]
+    fn test_categories() {
+        assert_eq!(selected("text"), ["tEXt", "zTXt", "iTXt"]);
+        assert_eq!(selected("color"), ["iCCP", "gAMA"]);
+        assert_eq!(selected("time"), ["tIME"]);
+        assert_eq!(selected("private"), ["ruSt", "abCd"]);
+        assert_eq!(
+            selected("public"),
+            ["tEXt", "zTXt", "iTXt", "iCCP", "gAMA", "tIME", "pHYs"]
+        );
+        assert_eq!(selected("all").len(), 9);
+        assert_eq!(selected("ruSt"), ["ruSt"]);
+    }
+
+    #[test]
+    fn test_terms_are_read_left_to_right() {
+        assert_eq!(
+            selected("private,text,!zTXt"),
+            ["tEXt", "iTXt", "ruSt", "abCd"]
+        );
+        assert_eq!(selected("!zTXt,private,text"), selected("all"));
+        assert_eq!(selected("text,!text"), Vec::<&str>::new());
+        assert_eq!(selected("!text,tEXt").len(), 7);
+        assert_eq!(selected("all,!color,iCCP,!private").len(), 6);
+    }
+
+    #[test]
+    fn test_a_leading_negation_starts_from_everything() {
+        assert_eq!(selected("!iCCP").len(), 8);
+        assert_eq!(
+            selected("!public"),
+            ["ruSt", "abCd"],
+            "the complement of public"
+        );
+        assert_eq!(selected("!all"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_spaces_and_display() {
+        let parsed = selection(" private , ! iCCP,text ");
+        assert_eq!(parsed.to_string(), "private,!iCCP,text");
+        assert_eq!(parsed, selection("private,!iCCP,text"));
+    }
+
+    #[test]
+    fn test_invalid_terms() {
+        assert_eq!(
+            "private,colour".parse::<Selection>(),
+            Err(SelectionError::UnknownTerm {
+                term: "colour".to_string()
+            })
+        );
+        // Categories are lowercase, chunk types need four letters
+        assert!(matches!(
+            "PRIVATE".parse::<Selection>(),
+            Err(SelectionError::UnknownTerm { .. })
+        ));
+        assert!(matches!(
+            "ru5t".parse::<Selection>(),
+            Err(SelectionError::UnknownTerm { .. })
+        ));
+        assert_eq!(
+            "text,,time".parse::<Selection>(),
+            Err(SelectionError::EmptyTerm { position: 2 })
+        );
+        assert_eq!(
+            "!".parse::<Selection>(),
+            Err(SelectionError::EmptyTerm { position: 1 })
+        );
+        assert_eq!(
+            "".parse::<Selection>(),
+            Err(SelectionError::EmptyTerm { position: 1 })
+        );
+        assert_eq!(
+            SelectionError::UnknownTerm {
+                term: "colour".to_string()
+            }
+            .to_string(),
+            "Unknown term 'colour', expected a chunk type or one of all, private, public, text, color, time"
+        );
+    }
+
+    #[test]
+    fn test_empty_warning() {
+        let selection = selection("private,!ruSt");
+
+        assert_eq!(selection.empty_warning(3, 1), None);
+        // Nothing to select is no mistake
+        assert_eq!(selection.empty_warning(0, 0), None);
+        let warning = selection.empty_warning(4, 0).unwrap();
+        assert_eq!(warning.code, Code::EmptySelection);
+        assert_eq!(
+            warning.to_string(),
+            "warning[W0302]: --select private,!ruSt matches none of the 4 chunk(s)"
+        );
+    }
+}
@@ -0,0 +1,595 @@
+//! `pngme serve`: a small local HTTP API over encode and decode.
+//!
+//! | Endpoint       | Body      | Query                   | Response             |
+//! |----------------|-----------|-------------------------|----------------------|
+//! | `GET /health`  |           |                         | `{"status":"ok"}`    |
+//! | `POST /decode` | PNG bytes | `chunk_type`            | JSON payload         |
+//! | `POST /encode` | PNG bytes | `chunk_type`, `message` | the modified PNG     |
+//!
+//! Errors are JSON: `{"error":{"code":"E0301","message":"..."}}`, with the
+//! [`Code`] of the catalog the command line reports too.
+//!
+//! The HTTP/1.1 handled is the little these endpoints need: one request per
+//! connection, with a `Content-Length` body. Connections are served by a
+//! fixed number of workers, and every read of a connection times out, so a
+//! client that stops sending can't hold a worker.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    num::NonZeroUsize,
+    sync::{Mutex, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+use url::form_urlencoded;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    clock::SystemClock,
+    codes::Code,
+    commands::{check_chunk_safety, check_unknown_critical, message_chunk},
+    envelope::{self, Opened},
+    inflate::DEFAULT_MAX_DECOMPRESSED_SIZE,
+    observer::NoopObserver,
+    png::{ParseOptions, Png},
+};
+
+/// Header carrying the shared secret given with `--token`
+pub const TOKEN_HEADER: &str = "X-Pngme-Token";
+
+/// Longest request line and headers accepted, larger ones get 431
+const MAX_HEAD_SIZE: u64 = 16 * 1024;
+
+/// Unread body bytes discarded after replying, so that the client gets the
+/// reply rather than a reset connection
+const MAX_DRAIN_SIZE: u64 = 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum ServerError {
+    #[error("Could not listen on {address}: {message}")]
+    Bind { address: String, message: String },
+}
+
+impl ServerError {
+    pub fn code(&self) -> Code {
+        match self {
+            ServerError::Bind { .. } => Code::ServerBindFailed,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// Larger request bodies are rejected with 413
+    pub max_body_size: u64,
+    /// Time allowed to receive a request, slower clients get 408. Also the
+    /// longest a single read may wait for data
+    pub timeout: Duration,
+    /// Required in the [`TOKEN_HEADER`] of every request but `/health`
+    pub token: Option<String>,
+    /// Requests handled at the same time, the others wait their turn
+    pub workers: NonZeroUsize,
+    /// How posted images are parsed, from `--max-chunks`
+    pub parse_options: ParseOptions,
+    /// Bytes compressed payloads may inflate to, from
+    /// `--max-decompressed-size`
+    pub max_decompressed_size: u64,
+    /// Let `/encode` return images holding a critical chunk decoders don't
+    /// know, from `--allow-unknown-critical`
+    pub allow_unknown_critical: bool,
+}
+
+impl ServerOptions {
+    pub const DEFAULT_MAX_BODY_SIZE: u64 = 64 * 1024 * 1024;
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+    pub const DEFAULT_WORKERS: NonZeroUsize = NonZeroUsize::new(8).unwrap();
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            max_body_size: Self::DEFAULT_MAX_BODY_SIZE,
+            timeout: Self::DEFAULT_TIMEOUT,
+            token: None,
+            workers: Self::DEFAULT_WORKERS,
+            parse_options: ParseOptions::default(),
+            max_decompressed_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+            allow_unknown_critical: false,
+        }
+    }
+}
+
+pub struct Server {
+    listener: TcpListener,
+    options: ServerOptions,
+}
+
+impl Server {
+    pub fn bind(address: &str, options: ServerOptions) -> Result<Self, ServerError> {
+        let listener = TcpListener::bind(address).map_err(|err| ServerError::Bind {
+            address: address.to_string(),
+            message: err.to_string(),
+        })?;
+
+        Ok(Self { listener, options })
+    }
+
+    /// Address actually listened on, e.g. the port picked for `127.0.0.1:0`
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.local_addr().ok()
+    }
+
+    /// Serves requests until the process stops. Accepted connections queue
+    /// for the workers, and once the queue is full the next ones wait in the
+    /// listen backlog.
+    pub fn run(&self) {
+        let workers = self.options.workers.get();
+        let (sender, receiver) = mpsc::sync_channel::<TcpStream>(workers);
+        let receiver = Mutex::new(receiver);
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    loop {
+                        // The lock is released as soon as a stream is taken
+                        let stream = receiver.lock().expect("workers don't panic").recv();
+                        match stream {
+                            Ok(stream) => serve_connection(stream, &self.options),
+                            Err(_) => return,
+                        }
+                    }
+                });
+            }
+
+            for stream in self.listener.incoming() {
+                // A connection that failed before being accepted, e.g. reset
+                // by the client, takes nothing from the others
+                let Ok(stream) = stream else { continue };
+                if sender.send(stream).is_err() {
+                    break;
+                }
+            }
+            drop(sender);
+        });
+    }
+}
+
+/// What the handlers need of a request, its body aside
+#[derive(Debug)]
+struct Request {
+    method: String,
+    target: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Value of the header `name`, in any case
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+fn timed_out() -> Reply {
+    Reply::error(
+        408,
+        Code::RequestTimeout,
+        "The request took too long to arrive",
+    )
+}
+
+fn serve_connection(stream: TcpStream, options: &ServerOptions) {
+    // Without a timeout, a read from a client that stopped sending would
+    // block forever. The client may be gone already, then nothing is lost
+    if stream.set_read_timeout(Some(options.timeout)).is_err()
+        || stream.set_write_timeout(Some(options.timeout)).is_err()
+    {
+        return;
+    }
+
+    let deadline = Instant::now() + options.timeout;
+    let mut reader = BufReader::new(&stream);
+    let reply = read_head(&mut reader, deadline)
+        .and_then(|request| handle(&request, &mut reader, deadline, options))
+        .unwrap_or_else(|reply| reply);
+
+    // The client may be gone, nobody else to tell
+    let _ = write_reply(&stream, &reply);
+    let _ = stream.shutdown(Shutdown::Write);
+    // The reads above may have left only a few milliseconds of timeout
+    let _ = stream.set_read_timeout(Some(options.timeout));
+    let _ = io::copy(&mut reader.take(MAX_DRAIN_SIZE), &mut io::sink());
+}
+
+/// Gives the next read of `stream` no longer than what is left until
+/// `deadline`, so that a client sending a byte at a time can't outlast it
+fn read_timeout_until(stream: &TcpStream, deadline: Instant) -> Result<(), Reply> {
+    let left = deadline.saturating_duration_since(Instant::now());
+    if left.is_zero() || stream.set_read_timeout(Some(left)).is_err() {
+        return Err(timed_out());
+    }
+
+    Ok(())
+}
+
+/// Length of the request line and headers at the start of `bytes`, up to
+/// and including the blank line ending them
+fn head_length(bytes: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    for end in (0..bytes.len()).filter(|&index| bytes[index] == b'\n') {
+        let line = &bytes[start..end];
+        if line.is_empty() || line == b"\r" {
+            return Some(end + 1);
+        }
+        start = end + 1;
+    }
+
+    None
+}
+
+/// Reads the request line and the headers, no more than [`MAX_HEAD_SIZE`]
+/// bytes of them, leaving the body in `reader`
+fn read_head(reader: &mut BufReader<&TcpStream>, deadline: Instant) -> Result<Request, Reply> {
+    let mut head = Vec::new();
+
+    loop {
+        read_timeout_until(reader.get_ref(), deadline)?;
+        let available = match reader.fill_buf() {
+            Ok([]) => {
+                return Err(Reply::error(
+                    400,
+                    Code::RequestMalformed,
+                    "Incomplete request",
+                ));
+            }
+            Ok(available) => available,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) if is_timeout(&err) => return Err(timed_out()),
+            Err(err) => return Err(Reply::error(400, Code::RequestMalformed, err)),
+        };
+
+        let read = head.len();
+        let room = MAX_HEAD_SIZE as usize - read;
+        head.extend_from_slice(&available[..available.len().min(room)]);
+        match head_length(&head) {
+            Some(length) => {
+                reader.consume(length - read);
+                head.truncate(length);
+                break;
+            }
+            None if head.len() == MAX_HEAD_SIZE as usize => {
+                return Err(Reply::error(
+                    431,
+                    Code::RequestTooLarge,
+                    format!("The request line and headers exceed {MAX_HEAD_SIZE} bytes"),
+                ));
+            }
+            None => reader.consume(head.len() - read),
+        }
+    }
+
+    let head = std::str::from_utf8(&head).map_err(|err| Reply::error(400, Code::RequestMalformed, err))?;
+    let lines: Vec<&str> = head.lines().take_while(|line| !line.is_empty()).collect();
+
+    let bad_request = || Reply::error(400, Code::RequestMalformed, "Malformed request");
+    let (request_line, header_lines) = lines.split_first().ok_or_else(bad_request)?;
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(bad_request());
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(bad_request());
+    }
+
+    let headers = header_lines
+        .iter()
+        .map(|line| {
+            let (field, value) = line.split_once(':').ok_or_else(bad_request)?;
+            Ok((field.trim().to_string(), value.trim().to_string()))
+        })
+        .collect::<Result<_, Reply>>()?;
+
+    Ok(Request {
+        method: method.to_string(),
+        target: target.to_string(),
+        headers,
+    })
+}
+
+fn write_reply(mut stream: &TcpStream, reply: &Reply) -> io::Result<()> {
+    let reason = match reply.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        422 => "Unprocessable Content",
+        431 => "Request Header Fields Too Large",
+        _ => "",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        reply.status,
+        reply.content_type,
+        reply.body.len()
+    )?;
+    stream.write_all(&reply.body)?;
+    stream.flush()
+}
+
+/// A response before it is written out
+#[derive(Debug)]
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn json(status: u16, value: &impl Serialize) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec(value).expect("replies serialize to JSON"),
+        }
+    }
+
+    fn error(status: u16, code: Code, message: impl ToString) -> Self {
+        #[derive(Serialize)]
+        struct Detail {
+            code: Code,
+            message: String,
+        }
+
+        #[derive(Serialize)]
+        struct ErrorBody {
+            error: Detail,
+        }
+
+        Self::json(
+            status,
+            &ErrorBody {
+                error: Detail {
+                    code,
+                    message: message.to_string(),
+                },
+            },
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct DecodeReply {
+    chunk_type: String,
+    length: u32,
+    data: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+}
+
+fn handle(
+    request: &Request,
+    reader: &mut BufReader<&TcpStream>,
+    deadline: Instant,
+    options: &ServerOptions,
+) -> Result<Reply, Reply> {
+    let (path, query) = request
+        .target
+        .split_once('?')
+        .unwrap_or((&request.target, ""));
+    let query: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    let param = |name| {
+        query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .ok_or_else(|| {
+                Reply::error(
+                    400,
+                    Code::MissingParameter,
+                    format!("Missing the {name} query parameter"),
+                )
+            })
+    };
+
+    match (request.method.as_str(), path) {
+        ("GET", "/health") => {
+            return Ok(Reply::json(200, &serde_json::json!({ "status": "ok" })));
+        }
+        (method, "/decode" | "/encode") if method != "POST" => {
+            return Err(Reply::error(405, Code::MethodNotAllowed, "Use POST"));
+        }
+        (_, "/decode" | "/encode") => {}
+        _ => {
+            return Err(Reply::error(
+                404,
+                Code::NoEndpoint,
+                format!("No endpoint at {path}"),
+            ));
+        }
+    }
+
+    check_token(request, options)?;
+    let chunk_type = ChunkType::parse_name(param("chunk_type")?)
+        .map_err(|err| Reply::error(400, err.code(), err))?;
+    let message = if path == "/encode" {
+        // Refused as `encode` refuses them without --allow-unsafe-type
+        check_chunk_safety(&chunk_type).map_err(|err| Reply::error(400, err.code(), err))?;
+        Some(param("message")?.to_string())
+    } else {
+        None
+    };
+
+    let body = read_body(request, reader, deadline, options)?;
+    let mut png = Png::parse(&body, &options.parse_options, &NoopObserver)
+        .map_err(|err| Reply::error(400, err.code(), err))?;
+
+    match message {
+        Some(message) => {
+            let chunk = Chunk::try_new(chunk_type, message.into_bytes())
+                .map_err(|err| Reply::error(413, err.code(), err))?;
+            png.append_chunk(chunk);
+            check_unknown_critical(&png, options.allow_unknown_critical)
+                .map_err(|err| Reply::error(422, err.code(), err))?;
+
+            Ok(Reply {
+                status: 200,
+                content_type: "image/png",
+                body: png.as_bytes(),
+            })
+        }
+        None => decode(&png, chunk_type, options),
+    }
+}
+
+fn check_token(request: &Request, options: &ServerOptions) -> Result<(), Reply> {
+    let Some(token) = &options.token else {
+        return Ok(());
+    };
+
+    match request.header(TOKEN_HEADER) {
+        // Compared in constant time, not to tell how much of it is right
+        Some(given) if bool::from(given.as_bytes().ct_eq(token.as_bytes())) => Ok(()),
+        Some(_) => Err(Reply::error(401, Code::RequestUnauthorized, "Wrong token")),
+        None => Err(Reply::error(
+            401,
+            Code::RequestUnauthorized,
+            format!("Missing the {TOKEN_HEADER} header"),
+        )),
+    }
+}
+
+/// Reads the body, giving up when it exceeds the size limit or takes longer
+/// than the timeout to arrive
+fn read_body(
+    request: &Request,
+    reader: &mut BufReader<&TcpStream>,
+    deadline: Instant,
+    options: &ServerOptions,
+) -> Result<Vec<u8>, Reply> {
+    if request.header("Transfer-Encoding").is_some() {
+        return Err(Reply::error(
+            411,
+            Code::LengthRequired,
+            "Send the body with a Content-Length",
+        ));
+    }
+    let length = match request.header("Content-Length") {
+        Some(length) => length.parse::<u64>().map_err(|_| {
+            Reply::error(
+                400,
+                Code::RequestMalformed,
+                format!("Invalid Content-Length: {length}"),
+            )
+        })?,
+        None => 0,
+    };
+
+    let limit = options.max_body_size;
+    if length > limit {
+        return Err(Reply::error(
+            413,
+            Code::RequestTooLarge,
+            format!("The body has {length} bytes, the limit is {limit}"),
+        ));
+    }
+
+    // curl waits for this before sending large bodies
+    if request
+        .header("Expect")
+        .is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue"))
+    {
+        let mut stream = *reader.get_ref();
+        stream
+            .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+            .map_err(|err| Reply::error(400, Code::RequestMalformed, err))?;
+    }
+
+    let mut reader = reader.take(length);
+    let mut body = Vec::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        read_timeout_until(reader.get_ref().get_ref(), deadline)?;
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => body.extend_from_slice(&buffer[..read]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) if is_timeout(&err) => return Err(timed_out()),
+            Err(err) => return Err(Reply::error(400, Code::RequestMalformed, err)),
+        }
+    }
+
+    if (body.len() as u64) < length {
+        return Err(Reply::error(400, Code::RequestMalformed, "Incomplete body"));
+    }
+
+    Ok(body)
+}
+
+fn decode(png: &Png, chunk_type: ChunkType, options: &ServerOptions) -> Result<Reply, Reply> {
+    let name = chunk_type.to_string();
+    // The pieces of a split message are joined, as `decode` does
+    let chunk = message_chunk(png, &name)
+        .map_err(|err| Reply::error(422, err.code(), err))?
+        .ok_or_else(|| {
+            Reply::error(
+                404,
+                Code::ChunkNotFound,
+                format!("Chunk type: {name} not found"),
+            )
+        })?;
+
+    let opened = envelope::open(
+        chunk.data(),
+        &SystemClock,
+        false,
+        None,
+        options.max_decompressed_size,
+    )
+    .map_err(|err| Reply::error(422, err.code(), err))?;
+    let (data, expires_at) = match opened {
+        Opened::Plain(data) => (data.to_vec(), None),
+        Opened::Message(envelope) => (envelope.message, envelope.expires_at),
+        Opened::Expired { .. } => {
+            return Err(Reply::error(
+                404,
+                Code::MessageExpired,
+                format!("The {name} message expired"),
+            ));
+        }
+    };
+
+    Ok(Reply::json(
+        200,
+        &DecodeReply {
+            chunk_type: name,
+            length: chunk.length(),
+            data: String::from_utf8_lossy(&data).into_owned(),
+            expires_at,
+        },
+    ))
+}
@@ -0,0 +1,519 @@
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    process, thread,
+    time::Duration,
+};
+
+use crate::temp;
+
+/// Whether `err` means the destination ran out of space. A write accepting
+/// no bytes at all is how some file systems and pipes report it.
+pub fn is_storage_full(err: &io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::StorageFull | ErrorKind::WriteZero)
+}
+
+/// Destination of an output image.
+///
+/// Bytes go to [`WriteSink::writer`] and only become visible at the
+/// destination on [`WriteSink::commit`]. After [`WriteSink::abort`] the
+/// destination must hold what it held before, as far as the sink allows.
+pub trait WriteSink {
+    /// Writer receiving the output bytes
+    fn writer(&mut self) -> io::Result<&mut dyn Write>;
+
+    /// Makes the written bytes visible at the destination
+    fn commit(&mut self) -> io::Result<()>;
+
+    /// Discards the written bytes
+    fn abort(&mut self);
+
+    /// The bytes written and not yet committed, read back from where they
+    /// are held
+    fn read_back(&mut self) -> io::Result<Vec<u8>>;
+}
+
+/// Writes `bytes` to `sink` and commits them, aborting on any failure
+pub fn write_to_sink(sink: &mut dyn WriteSink, bytes: &[u8]) -> io::Result<()> {
+    let result = sink
+        .writer()
+        .and_then(|writer| {
+            writer.write_all(bytes)?;
+            writer.flush()
+        })
+        .and_then(|()| sink.commit());
+
+    if result.is_err() {
+        sink.abort();
+    }
+
+    result
+}
+
+/// Writes `bytes` to `sink` without committing them and reads them back,
+/// for a check before [`WriteSink::commit`]. Aborts on any failure.
+pub fn stage_and_read_back(sink: &mut dyn WriteSink, bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let result = sink
+        .writer()
+        .and_then(|writer| {
+            writer.write_all(bytes)?;
+            writer.flush()
+        })
+        .and_then(|()| sink.read_back());
+
+    if result.is_err() {
+        sink.abort();
+    }
+
+    result
+}
+
+/// Sink for an output path, `-` being stdout
+pub fn sink_for(path: &Path) -> Box<dyn WriteSink> {
+    if path == Path::new("-") {
+        Box::new(StdoutSink::default())
+    } else {
+        Box::new(FileSink::new(path))
+    }
+}
+
+/// Atomically replaces a file: bytes go to a [`temp`] file next to the
+/// destination, renamed over it on commit.
+pub struct FileSink {
+    path: PathBuf,
+    temp_path: PathBuf,
+    temp: Option<File>,
+}
+
+/// Mode of an output file that didn't exist before, less the umask
+#[cfg(unix)]
+const NEW_FILE_MODE: u32 = 0o666;
+
+impl FileSink {
+    pub fn new(path: &Path) -> Self {
+        let path = &resolve_destination(path);
+        let name = path.file_name().map_or_else(
+            || "output".into(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let temp_path = path.with_file_name(format!(".{name}.pngme-{}.tmp", process::id()));
+
+        Self {
+            path: path.to_path_buf(),
+            temp_path,
+            temp: None,
+        }
+    }
+}
+
+impl WriteSink for FileSink {
+    fn writer(&mut self) -> io::Result<&mut dyn Write> {
+        if self.temp.is_none() {
+            self.temp = Some(create_temp(&self.path, &self.temp_path)?);
+        }
+
+        Ok(self
+            .temp
+            .as_mut()
+            .expect("the temporary file was just created"))
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        let Some(temp) = self.temp.take() else {
+            return Ok(());
+        };
+
+        temp.sync_all()?;
+        drop(temp);
+
+        // Keep the permissions of the file being replaced, the umask may
+        // have cleared some of them when the temporary file was created
+        if let Ok(metadata) = fs::metadata(&self.path) {
+            fs::set_permissions(&self.temp_path, metadata.permissions())?;
+        }
+
+        rename_over(&self.temp_path, &self.path)?;
+        temp::release(&self.temp_path);
+        #[cfg(unix)]
+        sync_parent(&self.path);
+
+        Ok(())
+    }
+
+    fn abort(&mut self) {
+        self.temp = None;
+        temp::discard(&self.temp_path);
+    }
+
+    fn read_back(&mut self) -> io::Result<Vec<u8>> {
+        if let Some(temp) = &mut self.temp {
+            temp.flush()?;
+        }
+
+        fs::read(&self.temp_path)
+    }
+}
+
+impl Drop for FileSink {
+    /// A sink dropped without commit leaves no temporary file behind
+    fn drop(&mut self) {
+        if self.temp.is_some() {
+            self.abort();
+        }
+    }
+}
+
+/// The file written for `path`: the target of a symlink, like
+/// `File::create` follows it, so that the rename replaces the target and
+/// keeps the link. A dangling link names the file to create.
+fn resolve_destination(path: &Path) -> PathBuf {
+    if let Ok(target) = fs::canonicalize(path) {
+        return target;
+    }
+
+    match fs::read_link(path) {
+        Ok(target) => path.parent().map_or(target.clone(), |parent| parent.join(&target)),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Creates the temporary file of `path` with the permissions of the file it
+/// replaces, or those of a new file, so that the umask applies
+#[cfg(unix)]
+fn create_temp(path: &Path, temp_path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path).map_or(NEW_FILE_MODE, |metadata| metadata.permissions().mode() & 0o777);
+    temp::create_with_mode(temp_path, mode)
+}
+
+#[cfg(not(unix))]
+fn create_temp(_path: &Path, temp_path: &Path) -> io::Result<File> {
+    temp::create(temp_path)
+}
+
+/// Times a rename is tried while the destination is held open
+const RENAME_ATTEMPTS: u32 = 10;
+
+const RENAME_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Renames `from` over the existing file `to`. Windows refuses while another
+/// process has `to` open, often an antivirus or the search indexer for a
+/// moment, so the rename is tried again for a while.
+fn rename_over(from: &Path, to: &Path) -> io::Result<()> {
+    let mut attempt = 1;
+    loop {
+        match fs::rename(from, to) {
+            Err(err) if is_held_open(&err) && attempt < RENAME_ATTEMPTS => {
+                attempt += 1;
+                thread::sleep(RENAME_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// ERROR_ACCESS_DENIED, ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+#[cfg(windows)]
+fn is_held_open(err: &io::Error) -> bool {
+    matches!(err.raw_os_error(), Some(5 | 32 | 33))
+}
+
+/// Unix replaces a file even while it is open
+#[cfg(not(windows))]
+fn is_held_open(_err: &io::Error) -> bool {
+    false
+}
+
+/// Flushes the directory entry of `path` so that the rename survives a
+/// crash. Failing only loses that guarantee, the write itself succeeded.
+#[cfg(unix)]
+fn sync_parent(path: &Path) {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let _ = File::open(dir).and_then(|dir| dir.sync_all());
+}
+
+/// Writes to stdout, which can't be rolled back. The bytes are held until
+/// commit, so a consumer never reads part of an image that failed.
+#[derive(Debug, Default)]
+pub struct StdoutSink {
+    pending: Vec<u8>,
+}
+
+impl WriteSink for StdoutSink {
+    fn writer(&mut self) -> io::Result<&mut dyn Write> {
+        Ok(&mut self.pending)
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&self.pending)?;
+        self.pending.clear();
+        stdout.flush()
+    }
+
+    fn abort(&mut self) {
+        self.pending.clear();
+    }
+
+    fn read_back(&mut self) -> io::Result<Vec<u8>> {
+        Ok(self.pending.clone())
+    }
+}
+
+/// Keeps the output in memory, committed bytes are in `committed`
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    pending: Vec<u8>,
+    pub committed: Option<Vec<u8>>,
+}
+
+impl WriteSink for MemorySink {
+    fn writer(&mut self) -> io::Result<&mut dyn Write> {
+        Ok(&mut self.pending)
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        self.committed = Some(std::mem::take(&mut self.pending));
+        Ok(())
+    }
+
+    fn abort(&mut self) {
+        self.pending.clear();
+    }
+
+    fn read_back(&mut self) -> io::Result<Vec<u8>> {
+        Ok(self.pending.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Passes the first `fail_after` bytes to `inner`, then fails with `kind`
+    struct FailingSink<S> {
+        inner: S,
+        fail_after: usize,
+        written: usize,
+        kind: ErrorKind,
+    }
+
+    impl<S: WriteSink> FailingSink<S> {
+        fn new(inner: S, fail_after: usize) -> Self {
+            Self {
+                inner,
+                fail_after,
+                written: 0,
+                kind: ErrorKind::Other,
+            }
+        }
+    }
+
+    impl<S: WriteSink> Write for FailingSink<S> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let allowed = (self.fail_after - self.written).min(buf.len());
+            if allowed == 0 {
+                return Err(io::Error::new(self.kind, "injected failure"));
+            }
+
+            let written = self.inner.writer()?.write(&buf[..allowed])?;
+            self.written += written;
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.writer()?.flush()
+        }
+    }
+
+    impl<S: WriteSink> WriteSink for FailingSink<S> {
+        fn writer(&mut self) -> io::Result<&mut dyn Write> {
+            Ok(self)
+        }
+
+        fn commit(&mut self) -> io::Result<()> {
+            self.inner.commit()
+        }
+
+        fn abort(&mut self) {
+            self.inner.abort()
+        }
+
+        fn read_back(&mut self) -> io::Result<Vec<u8>> {
+            self.inner.read_back()
+        }
+    }
+
+    fn dir_entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_file_sink_replaces_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"original").unwrap();
+
+        write_to_sink(&mut FileSink::new(&path), b"new bytes").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new bytes");
+        assert_eq!(dir_entries(dir.path()), ["image.png"]);
+    }
+
+    #[test]
+    fn test_failure_keeps_original_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"original").unwrap();
+
+        let mut sink = FailingSink::new(FileSink::new(&path), 4);
+        let result = write_to_sink(&mut sink, b"new bytes");
+
+        assert!(result.is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+        // The temporary file is cleaned up
+        assert_eq!(dir_entries(dir.path()), ["image.png"]);
+    }
+
+    #[test]
+    fn test_storage_full_is_detected_and_rolled_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"original").unwrap();
+
+        let mut sink = FailingSink {
+            kind: ErrorKind::StorageFull,
+            ..FailingSink::new(FileSink::new(&path), 4)
+        };
+        let err = write_to_sink(&mut sink, b"new bytes").unwrap_err();
+
+        assert!(is_storage_full(&err));
+        assert!(is_storage_full(&io::Error::from(ErrorKind::WriteZero)));
+        assert!(!is_storage_full(&io::Error::other("other")));
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+        assert_eq!(dir_entries(dir.path()), ["image.png"]);
+    }
+
+    #[test]
+    fn test_failure_creates_no_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+
+        for fail_after in [0, 1, 8] {
+            let mut sink = FailingSink::new(FileSink::new(&path), fail_after);
+
+            assert!(write_to_sink(&mut sink, b"new bytes").is_err());
+            assert!(dir_entries(dir.path()).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_failed_rename_cleans_up() {
+        let dir = tempfile::tempdir().unwrap();
+        // A file can't be renamed over a non-empty directory
+        let path = dir.path().join("image.png");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("inside"), b"original").unwrap();
+
+        assert!(write_to_sink(&mut FileSink::new(&path), b"new bytes").is_err());
+
+        assert_eq!(dir_entries(dir.path()), ["image.png"]);
+        assert_eq!(fs::read(path.join("inside")).unwrap(), b"original");
+    }
+
+    #[test]
+    fn test_staged_bytes_read_back_before_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"original").unwrap();
+
+        let mut sink = FileSink::new(&path);
+        let staged = stage_and_read_back(&mut sink, b"new bytes").unwrap();
+        assert_eq!(staged, b"new bytes");
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+
+        sink.commit().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new bytes");
+        assert_eq!(dir_entries(dir.path()), ["image.png"]);
+
+        let mut sink = MemorySink::default();
+        assert_eq!(stage_and_read_back(&mut sink, b"bytes").unwrap(), b"bytes");
+        assert_eq!(sink.committed, None);
+    }
+
+    #[test]
+    fn test_dropped_sink_cleans_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+
+        let mut sink = FileSink::new(&path);
+        sink.writer().unwrap().write_all(b"partial").unwrap();
+        drop(sink);
+
+        assert!(dir_entries(dir.path()).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_sink_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"original").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        write_to_sink(&mut FileSink::new(&path), b"new bytes").unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_new_file_follows_the_umask() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        // Created the way any other program creates a file
+        let reference = dir.path().join("reference");
+        File::create(&reference).unwrap();
+
+        write_to_sink(&mut FileSink::new(&path), b"new bytes").unwrap();
+
+        let mode = |path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), mode(&reference));
+    }
+
+    #[test]
+    fn test_memory_sink() {
+        let mut sink = MemorySink::default();
+        write_to_sink(&mut sink, b"bytes").unwrap();
+        assert_eq!(sink.committed.as_deref(), Some(&b"bytes"[..]));
+
+        let mut sink = FailingSink::new(MemorySink::default(), 2);
+        assert!(write_to_sink(&mut sink, b"bytes").is_err());
+        assert_eq!(sink.inner.committed, None);
+        assert!(sink.inner.pending.is_empty());
+    }
+
+    #[test]
+    fn test_failed_stdout_write_sends_nothing() {
+        // Failing before commit, nothing reaches stdout
+        let mut sink = FailingSink::new(StdoutSink::default(), 2);
+
+        assert!(write_to_sink(&mut sink, b"bytes").is_err());
+        assert!(sink.inner.pending.is_empty());
+    }
+}
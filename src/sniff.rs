@@ -0,0 +1,118 @@
+//! Guessing the format of a payload from its first bytes.
+//!
+//! `decode --output` names extracted payloads after it, and `scan` flags
+//! chunks holding a whole file of another format, which turns the image into
+//! a polyglot. Both read [`SIGNATURES`], so they never disagree.
+
+use std::fmt::{self, Display};
+
+/// Formats told apart by [`sniff`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Png,
+    Jpeg,
+    Pdf,
+    Zip,
+    Gzip,
+    /// UTF-8 without control characters other than whitespace
+    Text,
+    Unknown,
+}
+
+impl FileKind {
+    /// Extension of the files of this format, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            FileKind::Png => "png",
+            FileKind::Jpeg => "jpg",
+            FileKind::Pdf => "pdf",
+            FileKind::Zip => "zip",
+            FileKind::Gzip => "gz",
+            FileKind::Text => "txt",
+            FileKind::Unknown => "bin",
+        }
+    }
+}
+
+impl Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            FileKind::Png => "PNG image",
+            FileKind::Jpeg => "JPEG image",
+            FileKind::Pdf => "PDF document",
+            FileKind::Zip => "ZIP archive",
+            FileKind::Gzip => "gzip stream",
+            FileKind::Text => "UTF-8 text",
+            FileKind::Unknown => "unknown data",
+        };
+        write!(f, "{description}")
+    }
+}
+
+/// Magic bytes starting the files of each binary format
+pub const SIGNATURES: &[(&[u8], FileKind)] = &[
+    (b"\x89PNG\r\n\x1a\n", FileKind::Png),
+    (b"\xff\xd8\xff", FileKind::Jpeg),
+    (b"%PDF-", FileKind::Pdf),
+    (b"PK\x03\x04", FileKind::Zip),
+    // An empty archive has nothing but its end of central directory
+    (b"PK\x05\x06", FileKind::Zip),
+    (b"\x1f\x8b", FileKind::Gzip),
+];
+
+/// The binary format whose magic bytes start `data`
+pub fn magic(data: &[u8]) -> Option<FileKind> {
+    SIGNATURES
+        .iter()
+        .find(|(signature, _)| data.starts_with(signature))
+        .map(|&(_, kind)| kind)
+}
+
+/// The format of `data`: by its magic bytes, else text when it reads as
+/// such, else [`FileKind::Unknown`]
+pub fn sniff(data: &[u8]) -> FileKind {
+    if let Some(kind) = magic(data) {
+        return kind;
+    }
+    match std::str::from_utf8(data) {
+        Ok(text)
+            if !text.is_empty()
+                && !text
+                    .chars()
+                    .any(|c| c.is_control() && !c.is_ascii_whitespace()) =>
+        {
+            FileKind::Text
+        }
+        _ => FileKind::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), FileKind::Png);
+        assert_eq!(sniff(b"\xff\xd8\xff\xe0\0\x10JFIF"), FileKind::Jpeg);
+        assert_eq!(sniff(b"%PDF-1.7\n"), FileKind::Pdf);
+        assert_eq!(sniff(b"PK\x03\x04\x14\0"), FileKind::Zip);
+        assert_eq!(sniff(b"\x1f\x8b\x08\0"), FileKind::Gzip);
+        assert_eq!(sniff("héllo\r\n\tworld".as_bytes()), FileKind::Text);
+    }
+
+    #[test]
+    fn test_unknown() {
+        assert_eq!(sniff(b""), FileKind::Unknown);
+        assert_eq!(sniff(b"\0\x01\x02"), FileKind::Unknown);
+        assert_eq!(sniff(b"text with a \x1b[31m escape"), FileKind::Unknown);
+        assert_eq!(sniff(b"\xff\xfe"), FileKind::Unknown);
+        assert_eq!(FileKind::Unknown.extension(), "bin");
+    }
+
+    #[test]
+    fn test_magic_ignores_text() {
+        assert_eq!(magic(b"hello"), None);
+        assert_eq!(magic(b"%PDF-1.4"), Some(FileKind::Pdf));
+    }
+}
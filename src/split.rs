@@ -0,0 +1,228 @@
+//! Messages split across several chunks of the same type, written by
+//! `encode --split-size`.
+//!
+//! Each piece starts with a small header: `PNGMP`, a version byte, then the
+//! zero-based index of the piece and the number of pieces, both 4 bytes big
+//! endian. The payload split is the one a single chunk would hold, envelope
+//! included, so `decode` joins the pieces back before anything else.
+
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::{chunk::Chunk, codes::Code};
+
+pub const MAGIC: &[u8; 5] = b"PNGMP";
+pub const VERSION: u8 = 1;
+
+/// Bytes of the header starting each piece
+pub const HEADER_LEN: usize = MAGIC.len() + 1 + 4 + 4;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SplitError {
+    #[error("The split size must be at least 1 byte")]
+    ZeroSize,
+
+    #[error("Piece {} of {total} of the split message is missing", index + 1)]
+    MissingPiece { index: u32, total: u32 },
+
+    #[error("The {chunk_type} chunks hold pieces of several split messages")]
+    Mismatch { chunk_type: String },
+
+    #[error(
+        "The joined message of {length} bytes exceeds the size of a chunk ({} bytes)",
+        Chunk::MAX_LENGTH
+    )]
+    TooLarge { length: u64 },
+}
+
+impl SplitError {
+    pub fn code(&self) -> Code {
+        match self {
+            SplitError::ZeroSize => Code::InvalidSplitSize,
+            SplitError::MissingPiece { .. } => Code::MissingPiece,
+            SplitError::Mismatch { .. } => Code::SplitMismatch,
+            SplitError::TooLarge { .. } => Code::ChunkDataTooLarge,
+        }
+    }
+}
+
+/// One piece of a split payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Piece<'a> {
+    pub index: u32,
+    pub total: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> Piece<'a> {
+    /// Parses a piece, `None` for data without the header
+    pub fn parse(bytes: &'a [u8]) -> Option<Self> {
+        let rest = bytes.strip_prefix(MAGIC)?;
+        let (&[version], rest) = rest.split_first_chunk::<1>()?;
+        let (index, rest) = rest.split_first_chunk::<4>()?;
+        let (total, data) = rest.split_first_chunk::<4>()?;
+
+        let index = u32::from_be_bytes(*index);
+        let total = u32::from_be_bytes(*total);
+        (version == VERSION && index < total).then_some(Self { index, total, data })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.data.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.index.to_be_bytes());
+        bytes.extend_from_slice(&self.total.to_be_bytes());
+        bytes.extend_from_slice(self.data);
+        bytes
+    }
+}
+
+/// Cuts `payload` into pieces of at most `size` bytes, each with its header
+pub fn split(payload: &[u8], size: usize) -> Result<Vec<Vec<u8>>, SplitError> {
+    if size == 0 {
+        return Err(SplitError::ZeroSize);
+    }
+
+    // An empty payload is still one (empty) piece
+    let pieces: Vec<&[u8]> = match payload {
+        [] => vec![payload],
+        _ => payload.chunks(size).collect(),
+    };
+    let total = pieces.len() as u32;
+
+    Ok(pieces
+        .iter()
+        .zip(0..)
+        .map(|(data, index)| Piece { index, total, data }.to_bytes())
+        .collect())
+}
+
+/// Joins the pieces held by `chunks`, all of the same type, in file order.
+///
+/// Returns `None` when the first chunk is not a piece, the chunk then holds
+/// a whole payload. Chunks without the header are ignored otherwise.
+pub fn join<'a>(chunks: impl Iterator<Item = &'a Chunk>) -> Result<Option<Chunk>, SplitError> {
+    let chunks: Vec<&Chunk> = chunks.collect();
+    let Some(first) = chunks.first() else {
+        return Ok(None);
+    };
+    let Some(piece) = Piece::parse(first.data()) else {
+        return Ok(None);
+    };
+
+    let chunk_type = *first.chunk_type();
+    let mismatch = || SplitError::Mismatch {
+        chunk_type: chunk_type.to_string(),
+    };
+    let total = piece.total;
+    // The total comes from the file and may be anything: pieces are keyed by
+    // index rather than slotted into a table of that size, and the first
+    // missing one is found within one more than the number of chunks
+    let mut pieces = BTreeMap::new();
+
+    for piece in chunks.iter().filter_map(|chunk| Piece::parse(chunk.data())) {
+        if piece.total != total || pieces.insert(piece.index, piece.data).is_some() {
+            return Err(mismatch());
+        }
+    }
+
+    let mut payload = Vec::new();
+    for index in 0..total {
+        let data = pieces
+            .remove(&index)
+            .ok_or(SplitError::MissingPiece { index, total })?;
+        payload.extend_from_slice(data);
+    }
+
+    let length = payload.len() as u64;
+    Chunk::try_new(chunk_type, payload)
+        .map(Some)
+        .map_err(|_| SplitError::TooLarge { length })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::chunk_type::ChunkType;
+
+    fn chunks(pieces: &[Vec<u8>]) -> Vec<Chunk> {
+        pieces
+            .iter()
+            .map(|data| Chunk::new(ChunkType::from_str("abCd").unwrap(), data.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_layout() {
+        let pieces = split(b"hello", 2).unwrap();
+
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(pieces[2], b"PNGMP\x01\x00\x00\x00\x02\x00\x00\x00\x03o");
+        assert_eq!(split(b"", 2).unwrap().len(), 1);
+        assert_eq!(split(b"hello", 0), Err(SplitError::ZeroSize));
+    }
+
+    #[test]
+    fn test_join_in_any_order() {
+        let mut pieces = split(b"hello world", 3).unwrap();
+        pieces.swap(0, 3);
+        pieces.swap(1, 2);
+
+        let joined = join(chunks(&pieces).iter()).unwrap().unwrap();
+
+        assert_eq!(joined.data(), b"hello world");
+    }
+
+    #[test]
+    fn test_join_failures() {
+        let pieces = split(b"hello world", 3).unwrap();
+
+        let mut missing = pieces.clone();
+        missing.remove(2);
+        assert_eq!(
+            join(chunks(&missing).iter()),
+            Err(SplitError::MissingPiece { index: 2, total: 4 })
+        );
+
+        let mut duplicated = pieces.clone();
+        duplicated.push(pieces[1].clone());
+        assert!(matches!(
+            join(chunks(&duplicated).iter()),
+            Err(SplitError::Mismatch { .. })
+        ));
+
+        let mut other_message = pieces.clone();
+        other_message.extend(split(b"bye", 1).unwrap());
+        assert!(matches!(
+            join(chunks(&other_message).iter()),
+            Err(SplitError::Mismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_total_beyond_the_chunks() {
+        let piece = Piece {
+            index: 0,
+            total: u32::MAX,
+            data: b"hi",
+        };
+
+        assert_eq!(
+            join(chunks(&[piece.to_bytes()]).iter()),
+            Err(SplitError::MissingPiece {
+                index: 1,
+                total: u32::MAX
+            })
+        );
+    }
+
+    #[test]
+    fn test_whole_payloads_are_not_joined() {
+        assert_eq!(join(chunks(&[b"hello".to_vec()]).iter()), Ok(None));
+        assert_eq!(join(std::iter::empty()), Ok(None));
+    }
+}
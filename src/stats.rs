@@ -0,0 +1,303 @@
+//! Chunk-type statistics over many files, gathered by `pngme types`.
+//!
+//! Files are folded into [`CorpusStats`] one at a time and only per-type
+//! aggregates are kept, so memory doesn't grow with the number of files.
+//! The median size is estimated with the P² algorithm, exact up to five
+//! chunks and approximate beyond.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::chunk_type::ChunkType;
+
+/// Streaming estimate of a median with the P² algorithm (Jain and
+/// Chlamtac, 1985): five markers track the minimum, the quartiles, the
+/// median and the maximum, and are nudged towards their ideal position with
+/// every value.
+#[derive(Debug, Clone, Default)]
+pub struct MedianEstimator {
+    count: u64,
+    /// Marker heights, i.e. the estimated quantiles
+    heights: [f64; 5],
+    /// Actual marker positions, 1-based
+    positions: [f64; 5],
+    /// Ideal marker positions
+    desired: [f64; 5],
+}
+
+impl MedianEstimator {
+    /// Growth of the ideal positions per value, for the 0.5 quantile
+    const INCREMENTS: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+    pub fn add(&mut self, value: f64) {
+        if self.count < 5 {
+            self.heights[self.count as usize] = value;
+            self.count += 1;
+
+            if self.count == 5 {
+                self.heights.sort_by(f64::total_cmp);
+                self.positions = [1.0, 2.0, 3.0, 4.0, 5.0];
+                self.desired = [1.0, 2.0, 3.0, 4.0, 5.0];
+            }
+            return;
+        }
+        self.count += 1;
+
+        // Cell holding the value, extending the extremes when needed
+        let cell = if value < self.heights[0] {
+            self.heights[0] = value;
+            0
+        } else if value >= self.heights[4] {
+            self.heights[4] = value;
+            3
+        } else {
+            (1..5)
+                .find(|&i| value < self.heights[i])
+                .expect("the value is below the maximum")
+                - 1
+        };
+
+        for position in &mut self.positions[cell + 1..] {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(Self::INCREMENTS) {
+            *desired += increment;
+        }
+
+        for i in 1..4 {
+            let offset = self.desired[i] - self.positions[i];
+            let room_above = self.positions[i + 1] - self.positions[i];
+            let room_below = self.positions[i - 1] - self.positions[i];
+
+            if (offset >= 1.0 && room_above > 1.0) || (offset <= -1.0 && room_below < -1.0) {
+                let step = offset.signum();
+                let parabolic = self.parabolic(i, step);
+
+                self.heights[i] =
+                    if self.heights[i - 1] < parabolic && parabolic < self.heights[i + 1] {
+                        parabolic
+                    } else {
+                        self.linear(i, step)
+                    };
+                self.positions[i] += step;
+            }
+        }
+    }
+
+    fn parabolic(&self, i: usize, step: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+
+        q[i] + step / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + step) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - step) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, step: f64) -> f64 {
+        let neighbour = if step > 0.0 { i + 1 } else { i - 1 };
+
+        self.heights[i]
+            + step * (self.heights[neighbour] - self.heights[i])
+                / (self.positions[neighbour] - self.positions[i])
+    }
+
+    /// The median, `None` before the first value
+    pub fn median(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            1..5 => {
+                let mut values = self.heights[..self.count as usize].to_vec();
+                values.sort_by(f64::total_cmp);
+                let middle = values.len() / 2;
+
+                Some(if values.len().is_multiple_of(2) {
+                    (values[middle - 1] + values[middle]) / 2.0
+                } else {
+                    values[middle]
+                })
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+}
+
+/// Aggregates of one chunk type
+#[derive(Debug, Clone)]
+pub struct TypeStats {
+    pub chunk_type: ChunkType,
+    pub chunks: u64,
+    /// Number of files holding at least one chunk of the type
+    pub files: u64,
+    pub total_bytes: u64,
+    pub min: u64,
+    pub max: u64,
+    median: MedianEstimator,
+}
+
+impl TypeStats {
+    fn new(chunk_type: ChunkType) -> Self {
+        Self {
+            chunk_type,
+            chunks: 0,
+            files: 0,
+            total_bytes: 0,
+            min: u64::MAX,
+            max: 0,
+            median: MedianEstimator::default(),
+        }
+    }
+
+    fn add(&mut self, length: u64) {
+        self.chunks += 1;
+        self.total_bytes += length;
+        self.min = self.min.min(length);
+        self.max = self.max.max(length);
+        self.median.add(length as f64);
+    }
+
+    /// Median data length, rounded to the byte
+    pub fn median(&self) -> u64 {
+        self.median
+            .median()
+            .map_or(0, |median| median.round() as u64)
+    }
+}
+
+/// Serialized form of [`TypeStats`]
+#[derive(Debug, Serialize)]
+pub struct TypeReport {
+    pub chunk_type: String,
+    pub chunks: u64,
+    pub files: u64,
+    pub total_bytes: u64,
+    pub min: u64,
+    pub median: u64,
+    pub max: u64,
+}
+
+impl From<&TypeStats> for TypeReport {
+    fn from(stats: &TypeStats) -> Self {
+        Self {
+            chunk_type: stats.chunk_type.to_string(),
+            chunks: stats.chunks,
+            files: stats.files,
+            total_bytes: stats.total_bytes,
+            min: stats.min,
+            median: stats.median(),
+            max: stats.max,
+        }
+    }
+}
+
+/// Chunk-type statistics of the files read so far
+#[derive(Debug, Default)]
+pub struct CorpusStats {
+    pub files: u64,
+    /// Files that could not be read or parsed
+    pub skipped: u64,
+    types: HashMap<ChunkType, TypeStats>,
+}
+
+impl CorpusStats {
+    /// Adds a file given the type and data length of each of its chunks
+    pub fn add_file(&mut self, chunks: impl IntoIterator<Item = (ChunkType, u64)>) {
+        let mut seen = Vec::new();
+
+        for (chunk_type, length) in chunks {
+            self.types
+                .entry(chunk_type)
+                .or_insert_with(|| TypeStats::new(chunk_type))
+                .add(length);
+
+            if !seen.contains(&chunk_type) {
+                seen.push(chunk_type);
+            }
+        }
+
+        for chunk_type in seen {
+            self.types
+                .get_mut(&chunk_type)
+                .expect("the type was just added")
+                .files += 1;
+        }
+        self.files += 1;
+    }
+
+    pub fn skip_file(&mut self) {
+        self.skipped += 1;
+    }
+
+    /// Statistics of each type, most frequent first
+    pub fn sorted(&self) -> Vec<&TypeStats> {
+        let mut types: Vec<&TypeStats> = self.types.values().collect();
+        types.sort_by(|a, b| {
+            b.chunks
+                .cmp(&a.chunks)
+                .then_with(|| a.chunk_type.to_string().cmp(&b.chunk_type.to_string()))
+        });
+
+        types
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn median_of(values: impl IntoIterator<Item = f64>) -> Option<f64> {
+        let mut estimator = MedianEstimator::default();
+        for value in values {
+            estimator.add(value);
+        }
+        estimator.median()
+    }
+
+    #[test]
+    fn test_median_is_exact_for_few_values() {
+        assert_eq!(median_of([]), None);
+        assert_eq!(median_of([7.0]), Some(7.0));
+        assert_eq!(median_of([9.0, 1.0]), Some(5.0));
+        assert_eq!(median_of([9.0, 1.0, 4.0]), Some(4.0));
+        assert_eq!(median_of([5.0, 3.0, 1.0, 4.0, 2.0]), Some(3.0));
+    }
+
+    #[test]
+    fn test_median_estimate() {
+        // 0..=1000 in a scrambled order, median 500
+        let values = (0..=1000u64).map(|n| (n * 389 % 1001) as f64);
+        let median = median_of(values).unwrap();
+
+        assert!((median - 500.0).abs() < 25.0, "{median}");
+    }
+
+    #[test]
+    fn test_median_of_constant_values() {
+        assert_eq!(median_of(std::iter::repeat_n(42.0, 100)), Some(42.0));
+    }
+
+    #[test]
+    fn test_corpus_aggregates() {
+        let chunk_type = |name| ChunkType::from_str(name).unwrap();
+        let mut stats = CorpusStats::default();
+
+        stats.add_file([
+            (chunk_type("IHDR"), 13),
+            (chunk_type("IDAT"), 10),
+            (chunk_type("IDAT"), 30),
+        ]);
+        stats.add_file([(chunk_type("IHDR"), 13), (chunk_type("ruSt"), 4)]);
+        stats.skip_file();
+
+        let types = stats.sorted();
+        let names: Vec<String> = types.iter().map(|t| t.chunk_type.to_string()).collect();
+        assert_eq!(names, ["IDAT", "IHDR", "ruSt"]);
+
+        let idat = types[0];
+        assert_eq!((idat.chunks, idat.files, idat.total_bytes), (2, 1, 40));
+        assert_eq!((idat.min, idat.median(), idat.max), (10, 20, 30));
+        assert_eq!(types[1].files, 2);
+        assert_eq!((stats.files, stats.skipped), (2, 1));
+    }
+}
@@ -0,0 +1,304 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    png::{Png, PngError},
+};
+
+/// Chunk types that image editors usually drop or rewrite on save
+pub const REWRITTEN_CHUNK_TYPES: [&[u8; 4]; 4] = [b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+/// How likely a chunk is to be kept by a re-encoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rating {
+    Good,
+    Fair,
+    Poor,
+}
+
+impl Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Rating::Good => "good",
+            Rating::Fair => "fair",
+            Rating::Poor => "poor",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A change that makes the chunk more likely to survive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suggestion {
+    /// Lowercase the fourth letter of the type
+    SetSafeToCopy,
+    /// Place the chunk before the first IDAT chunk
+    MoveBeforeIdat,
+    /// Pick a private type instead of one editors rewrite, left to the user
+    UsePrivateType,
+}
+
+impl Suggestion {
+    /// Whether `apply` can perform the suggestion
+    pub fn is_automatic(&self) -> bool {
+        !matches!(self, Suggestion::UsePrivateType)
+    }
+}
+
+impl Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Suggestion::SetSafeToCopy => "set safe-to-copy bit",
+            Suggestion::MoveBeforeIdat => "move before IDAT",
+            Suggestion::UsePrivateType => "use a private chunk type",
+        };
+        write!(f, "{text}")
+    }
+}
+
+/// Survivability of the first chunk of a type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub index: usize,
+    pub chunk_type: ChunkType,
+    pub safe_to_copy: bool,
+    pub before_idat: bool,
+    pub rewritten_by_editors: bool,
+    pub rating: Rating,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |value| if value { "yes" } else { "no" };
+
+        writeln!(f, "Chunk: #{} {}", self.index, self.chunk_type)?;
+        writeln!(f, "Safe to copy: {}", yes_no(self.safe_to_copy))?;
+        writeln!(f, "Before IDAT: {}", yes_no(self.before_idat))?;
+        writeln!(
+            f,
+            "Rewritten by editors: {}",
+            yes_no(self.rewritten_by_editors)
+        )?;
+        write!(f, "Survivability: {}", self.rating)?;
+
+        if !self.suggestions.is_empty() {
+            let suggestions: Vec<String> =
+                self.suggestions.iter().map(Suggestion::to_string).collect();
+            write!(f, "\nSuggestions: {}", suggestions.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn first_position(png: &Png, chunk_type: &str) -> Option<usize> {
+    png.chunks()
+        .iter()
+        .position(|chunk| chunk.chunk_type().to_string() == chunk_type)
+}
+
+/// Rates the first chunk of `chunk_type`. Every problem found lowers the
+/// rating and comes with a suggestion.
+///
+/// Critical chunks get no suggestion to rename or move them: decoders need
+/// them under their own type and at their place, and re-encoders write them
+/// anyway.
+pub fn assess(png: &Png, chunk_type: &str) -> Result<Report, PngError> {
+    let index = first_position(png, chunk_type).ok_or_else(|| PngError::ChunkNotFound {
+        chunk_type: chunk_type.to_string(),
+    })?;
+    let chunk_type = *png.chunks()[index].chunk_type();
+
+    let safe_to_copy = chunk_type.is_safe_to_copy();
+    let before_idat = first_position(png, "IDAT").is_none_or(|idat| index < idat);
+    let rewritten_by_editors = REWRITTEN_CHUNK_TYPES.contains(&&chunk_type.bytes());
+
+    let critical = chunk_type.is_critical();
+
+    let mut suggestions = Vec::new();
+    if !safe_to_copy && !critical {
+        suggestions.push(Suggestion::SetSafeToCopy);
+    }
+    if !before_idat && !critical {
+        suggestions.push(Suggestion::MoveBeforeIdat);
+    }
+    if rewritten_by_editors {
+        suggestions.push(Suggestion::UsePrivateType);
+    }
+
+    let rating = match suggestions.len() {
+        0 => Rating::Good,
+        1 => Rating::Fair,
+        _ => Rating::Poor,
+    };
+
+    Ok(Report {
+        index,
+        chunk_type,
+        safe_to_copy,
+        before_idat,
+        rewritten_by_editors,
+        rating,
+        suggestions,
+    })
+}
+
+/// Performs the automatic suggestions of `report`. A new type means a new
+/// chunk, and so a new CRC. Critical chunks are refused.
+pub fn apply(png: &mut Png, report: &Report) -> Result<(), PngError> {
+    if report.chunk_type.is_critical() {
+        return Err(PngError::CriticalChunk {
+            chunk_type: report.chunk_type.to_string(),
+        });
+    }
+
+    let mut chunk = png.remove_chunk_at(report.index)?;
+    let mut index = report.index;
+
+    if report.suggestions.contains(&Suggestion::SetSafeToCopy) {
+        let mut bytes = chunk.chunk_type().bytes();
+        bytes[3] = bytes[3].to_ascii_lowercase();
+        let chunk_type = ChunkType::try_from(bytes).expect("lowercasing keeps a valid type");
+
+        chunk = Chunk::new(chunk_type, chunk.data().to_vec());
+    }
+
+    if report.suggestions.contains(&Suggestion::MoveBeforeIdat) {
+        index = first_position(png, "IDAT").unwrap_or(index);
+    }
+
+    png.insert_chunk(index, chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn image(chunks: &[(&str, &[u8])]) -> Png {
+        Png::from_chunks(chunks.iter().map(|(t, d)| chunk(t, d)).collect())
+    }
+
+    #[test]
+    fn test_assess_good() {
+        let png = image(&[("IHDR", b""), ("ruSt", b"hi"), ("IDAT", b""), ("IEND", b"")]);
+
+        let report = assess(&png, "ruSt").unwrap();
+
+        assert_eq!(report.rating, Rating::Good);
+        assert!(report.suggestions.is_empty());
+    }
+
+    #[test]
+    fn test_assess_fair_after_idat() {
+        let png = image(&[("IHDR", b""), ("IDAT", b""), ("ruSt", b"hi"), ("IEND", b"")]);
+
+        let report = assess(&png, "ruSt").unwrap();
+
+        assert_eq!(report.rating, Rating::Fair);
+        assert_eq!(report.suggestions, [Suggestion::MoveBeforeIdat]);
+    }
+
+    #[test]
+    fn test_assess_fair_rewritten_type() {
+        let png = image(&[
+            ("IHDR", b""),
+            ("tEXt", b"k\0v"),
+            ("IDAT", b""),
+            ("IEND", b""),
+        ]);
+
+        let report = assess(&png, "tEXt").unwrap();
+
+        assert!(report.rewritten_by_editors);
+        assert_eq!(report.rating, Rating::Fair);
+        assert_eq!(report.suggestions, [Suggestion::UsePrivateType]);
+    }
+
+    #[test]
+    fn test_assess_poor() {
+        let png = image(&[("IHDR", b""), ("IDAT", b""), ("ruST", b"hi"), ("IEND", b"")]);
+
+        let report = assess(&png, "ruST").unwrap();
+
+        assert!(!report.safe_to_copy);
+        assert_eq!(report.rating, Rating::Poor);
+        assert_eq!(
+            report.suggestions,
+            [Suggestion::SetSafeToCopy, Suggestion::MoveBeforeIdat]
+        );
+    }
+
+    #[test]
+    fn test_assess_missing_chunk() {
+        let png = image(&[("IHDR", b""), ("IEND", b"")]);
+
+        assert!(matches!(
+            assess(&png, "ruSt"),
+            Err(PngError::ChunkNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_apply_renames_and_moves() {
+        let mut png = image(&[
+            ("IHDR", b""),
+            ("IDAT", b"x"),
+            ("ruST", b"hi"),
+            ("IEND", b""),
+        ]);
+        let report = assess(&png, "ruST").unwrap();
+
+        apply(&mut png, &report).unwrap();
+
+        let expected = image(&[
+            ("IHDR", b""),
+            ("ruSt", b"hi"),
+            ("IDAT", b"x"),
+            ("IEND", b""),
+        ]);
+        assert_eq!(png.as_bytes(), expected.as_bytes());
+        assert_eq!(png.chunks()[1].crc(), chunk("ruSt", b"hi").crc());
+        assert_eq!(assess(&png, "ruSt").unwrap().rating, Rating::Good);
+    }
+
+    #[test]
+    fn test_apply_keeps_manual_suggestions() {
+        let mut png = image(&[
+            ("IHDR", b""),
+            ("tEXt", b"k\0v"),
+            ("IDAT", b""),
+            ("IEND", b""),
+        ]);
+        let before = png.as_bytes();
+        let report = assess(&png, "tEXt").unwrap();
+
+        apply(&mut png, &report).unwrap();
+
+        assert_eq!(png.as_bytes(), before);
+    }
+
+    #[test]
+    fn test_critical_chunks_are_left_alone() {
+        let mut png = image(&[("IHDR", b""), ("IDAT", b"x"), ("IEND", b"")]);
+        let before = png.as_bytes();
+
+        let mut report = assess(&png, "IEND").unwrap();
+        assert!(!report.safe_to_copy && !report.before_idat);
+        assert!(report.suggestions.is_empty());
+        assert_eq!(assess(&png, "IHDR").unwrap().suggestions, []);
+
+        report.suggestions = vec![Suggestion::SetSafeToCopy, Suggestion::MoveBeforeIdat];
+        assert!(matches!(
+            apply(&mut png, &report),
+            Err(PngError::CriticalChunk { .. })
+        ));
+        assert_eq!(png.as_bytes(), before);
+    }
+}
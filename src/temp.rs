@@ -0,0 +1,237 @@
+//! Temporary files created by pngme.
+//!
+//! Every temporary file is created through [`create`], which registers its
+//! path. Registered files are removed when the [`CleanupGuard`] held by
+//! `main` is dropped and when the process is interrupted, so Ctrl-C doesn't
+//! leave half-written images behind. `--keep-temp` keeps them for debugging.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Exit status of a process interrupted by SIGINT
+const INTERRUPTED_STATUS: i32 = 130;
+
+/// Registry of the temporary files used by the process
+static REGISTRY: TempRegistry = TempRegistry::new();
+
+/// Paths of the temporary files not yet committed or discarded
+#[derive(Debug, Default)]
+pub struct TempRegistry {
+    paths: Mutex<Vec<PathBuf>>,
+    keep: AtomicBool,
+}
+
+impl TempRegistry {
+    pub const fn new() -> Self {
+        Self {
+            paths: Mutex::new(Vec::new()),
+            keep: AtomicBool::new(false),
+        }
+    }
+
+    /// Creates `path`, readable and writable by the owner only since it may
+    /// hold a payload, and registers it
+    pub fn create(&self, path: &Path) -> io::Result<File> {
+        self.create_with_mode(path, 0o600)
+    }
+
+    /// Creates `path` like [`TempRegistry::create`], with the permissions
+    /// `mode` less the umask on Unix
+    pub fn create_with_mode(&self, path: &Path, mode: u32) -> io::Result<File> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(mode);
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+
+        let file = options.open(path)?;
+        self.paths().push(path.to_path_buf());
+
+        Ok(file)
+    }
+
+    /// Forgets `path`, e.g. once it has been renamed to its destination
+    pub fn release(&self, path: &Path) {
+        self.paths().retain(|registered| registered != path);
+    }
+
+    /// Removes `path`, unless temporary files are kept, and forgets it
+    pub fn discard(&self, path: &Path) {
+        self.release(path);
+        if !self.keep.load(Ordering::Relaxed) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /// Removes every registered file, unless temporary files are kept
+    pub fn cleanup(&self) {
+        let paths = std::mem::take(&mut *self.paths());
+        if self.keep.load(Ordering::Relaxed) {
+            return;
+        }
+
+        for path in paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    pub fn set_keep(&self, keep: bool) {
+        self.keep.store(keep, Ordering::Relaxed);
+    }
+
+    pub fn registered(&self) -> Vec<PathBuf> {
+        self.paths().clone()
+    }
+
+    fn paths(&self) -> std::sync::MutexGuard<'_, Vec<PathBuf>> {
+        // A panic while holding the lock leaves a usable list
+        self.paths.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Cleans up a registry when dropped
+#[must_use = "the registry is cleaned up when the guard is dropped"]
+pub struct CleanupGuard<'a> {
+    registry: &'a TempRegistry,
+}
+
+impl<'a> CleanupGuard<'a> {
+    pub fn new(registry: &'a TempRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Drop for CleanupGuard<'_> {
+    fn drop(&mut self) {
+        self.registry.cleanup();
+    }
+}
+
+/// Sets up the process registry: `keep` keeps temporary files around, and
+/// an interrupt removes them before exiting. The returned guard removes them
+/// when dropped.
+pub fn install(keep: bool) -> CleanupGuard<'static> {
+    REGISTRY.set_keep(keep);
+
+    // Best effort: without a handler an interrupt leaves the files behind
+    let _ = ctrlc::set_handler(|| {
+        REGISTRY.cleanup();
+        process::exit(INTERRUPTED_STATUS);
+    });
+
+    CleanupGuard::new(&REGISTRY)
+}
+
+/// [`TempRegistry::create`] on the process registry
+pub fn create(path: &Path) -> io::Result<File> {
+    REGISTRY.create(path)
+}
+
+/// [`TempRegistry::create_with_mode`] on the process registry
+pub fn create_with_mode(path: &Path, mode: u32) -> io::Result<File> {
+    REGISTRY.create_with_mode(path, mode)
+}
+
+/// [`TempRegistry::release`] on the process registry
+pub fn release(path: &Path) {
+    REGISTRY.release(path);
+}
+
+/// [`TempRegistry::discard`] on the process registry
+pub fn discard(path: &Path) {
+    REGISTRY.discard(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_removes_registered_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = TempRegistry::new();
+        let first = dir.path().join("first.tmp");
+        let second = dir.path().join("second.tmp");
+        let released = dir.path().join("released.tmp");
+
+        {
+            let _guard = CleanupGuard::new(&registry);
+            registry.create(&first).unwrap();
+            registry.create(&second).unwrap();
+            registry.create(&released).unwrap();
+            registry.release(&released);
+
+            assert_eq!(registry.registered(), [first.clone(), second.clone()]);
+        }
+
+        assert!(!first.exists());
+        assert!(!second.exists());
+        assert!(released.exists());
+        assert!(registry.registered().is_empty());
+    }
+
+    #[test]
+    fn test_kept_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = TempRegistry::new();
+        registry.set_keep(true);
+        let path = dir.path().join("kept.tmp");
+
+        registry.create(&path).unwrap();
+        registry.cleanup();
+
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_discard() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = TempRegistry::new();
+        let path = dir.path().join("discarded.tmp");
+
+        registry.create(&path).unwrap();
+        registry.discard(&path);
+
+        assert!(!path.exists());
+        assert!(registry.registered().is_empty());
+    }
+
+    #[test]
+    fn test_existing_file_is_not_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = TempRegistry::new();
+        let path = dir.path().join("existing.tmp");
+        fs::write(&path, b"someone else's").unwrap();
+
+        assert!(registry.create(&path).is_err());
+        assert!(registry.registered().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_owner_only_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let registry = TempRegistry::new();
+        let path = dir.path().join("private.tmp");
+
+        registry.create(&path).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,258 @@
+use std::{collections::BTreeMap, env, fs, io, path::PathBuf};
+
+use thiserror::Error;
+
+use crate::{
+    clock::{Clock, format_timestamp},
+    codes::Code,
+    hash::sha256_hex,
+};
+
+/// Built-ins reading the time or the machine, rejected in deterministic mode
+const NON_DETERMINISTIC: [&str; 2] = ["date", "hostname"];
+
+#[derive(Error, Debug)]
+pub enum TemplateError {
+    #[error("Unclosed placeholder at byte {offset} (write \\{{{{ for a literal {{{{)")]
+    Unclosed { offset: usize },
+
+    #[error("Unknown placeholder {{{{{name}}}}} (available: {available})")]
+    Unknown { name: String, available: String },
+
+    #[error("{{{{{name}}}}} is not deterministic (pass --var {name}=... to set it)")]
+    NotDeterministic { name: String },
+
+    #[error("Could not hash {path}: {source}")]
+    Hash {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Could not find the hostname")]
+    Hostname,
+}
+
+impl TemplateError {
+    pub fn code(&self) -> Code {
+        match self {
+            TemplateError::Unclosed { .. } => Code::TemplateUnclosed,
+            TemplateError::Unknown { .. } => Code::TemplateUnknown,
+            TemplateError::NotDeterministic { .. } => Code::TemplateNotDeterministic,
+            TemplateError::Hash { .. } => Code::TemplateHashFailed,
+            TemplateError::Hostname => Code::HostnameUnavailable,
+        }
+    }
+}
+
+/// Values available to `{{var}}` placeholders
+pub struct Variables<'a> {
+    /// Variables given with `--var`, shadowing the built-ins
+    pub vars: BTreeMap<String, String>,
+    /// Value of `{{file}}`
+    pub file: String,
+    pub clock: &'a dyn Clock,
+    /// Reject `{{date}}` and `{{hostname}}` unless set with `vars`
+    pub deterministic: bool,
+}
+
+impl Variables<'_> {
+    fn lookup(&self, name: &str) -> Result<String, TemplateError> {
+        if let Some(value) = self.vars.get(name) {
+            return Ok(value.clone());
+        }
+
+        if self.deterministic && NON_DETERMINISTIC.contains(&name) {
+            return Err(TemplateError::NotDeterministic {
+                name: name.to_string(),
+            });
+        }
+
+        match name {
+            "date" => Ok(format_timestamp(self.clock.now())[..10].to_string()),
+            "hostname" => hostname(),
+            "file" => Ok(self.file.clone()),
+            _ => match name.strip_prefix("sha256:") {
+                Some(path) => fs::read(path)
+                    .map(|data| sha256_hex(&data))
+                    .map_err(|source| TemplateError::Hash {
+                        path: path.into(),
+                        source,
+                    }),
+                None => Err(TemplateError::Unknown {
+                    name: name.to_string(),
+                    available: self.available(),
+                }),
+            },
+        }
+    }
+
+    fn available(&self) -> String {
+        let builtins = ["date", "file", "hostname", "sha256:<path>"];
+
+        self.vars
+            .keys()
+            .map(String::as_str)
+            .chain(
+                builtins
+                    .into_iter()
+                    .filter(|name| !self.vars.contains_key(*name)),
+            )
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn hostname() -> Result<String, TemplateError> {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .into_iter()
+        .find_map(|name| env::var(name).ok())
+        .or_else(|| {
+            ["/etc/hostname", "/proc/sys/kernel/hostname"]
+                .into_iter()
+                .find_map(|path| fs::read_to_string(path).ok())
+        })
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .ok_or(TemplateError::Hostname)
+}
+
+/// Replaces every `{{name}}` of `template`, `\{{` being a literal `{{`
+pub fn render(template: &str, variables: &Variables) -> Result<String, TemplateError> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let offset = template.len() - rest.len() + start;
+
+        if let Some(literal) = rest[..start].strip_suffix('\\') {
+            output.push_str(literal);
+            output.push_str("{{");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        output.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find("}}")
+            .ok_or(TemplateError::Unclosed { offset })?;
+        output.push_str(&variables.lookup(rest[start + 2..start + end].trim())?);
+        rest = &rest[start + end + 2..];
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+/// Parses a `--var name=value` argument
+pub fn parse_var(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected NAME=VALUE, got '{value}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+
+    // 2025-01-01T00:00:00Z
+    const CLOCK: FixedClock = FixedClock(1_735_689_600);
+
+    fn variables(vars: &[(&str, &str)], deterministic: bool) -> Variables<'static> {
+        Variables {
+            vars: vars
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            file: "image.png".to_string(),
+            clock: &CLOCK,
+            deterministic,
+        }
+    }
+
+    #[test]
+    fn test_render_vars() {
+        let result = render(
+            "v{{version}} of {{ name }}",
+            &variables(&[("version", "1.2"), ("name", "app")], false),
+        );
+
+        assert_eq!(result.unwrap(), "v1.2 of app");
+    }
+
+    #[test]
+    fn test_render_escaped_braces() {
+        let result = render(
+            r"\{{version}} is {{version}}, {single} }}",
+            &variables(&[("version", "2")], false),
+        );
+
+        assert_eq!(result.unwrap(), "{{version}} is 2, {single} }}");
+    }
+
+    #[test]
+    fn test_render_builtins() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data");
+        fs::write(&path, b"abc").unwrap();
+
+        let template = format!(
+            "{{{{date}}}} {{{{file}}}} {{{{sha256:{}}}}}",
+            path.display()
+        );
+        let result = render(&template, &variables(&[], false)).unwrap();
+
+        assert_eq!(
+            result,
+            "2025-01-01 image.png ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_render_unknown_lists_available() {
+        let err = render("{{nope}}", &variables(&[("version", "1")], false)).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Unknown placeholder {{nope}} (available: version, date, file, hostname, sha256:<path>)"
+        );
+    }
+
+    #[test]
+    fn test_render_unclosed() {
+        let err = render("abc {{date", &variables(&[], false)).unwrap_err();
+
+        assert!(matches!(err, TemplateError::Unclosed { offset: 4 }));
+    }
+
+    #[test]
+    fn test_deterministic_rejects_time_and_host() {
+        for name in NON_DETERMINISTIC {
+            let err = render(&format!("{{{{{name}}}}}"), &variables(&[], true)).unwrap_err();
+            assert!(matches!(err, TemplateError::NotDeterministic { .. }));
+        }
+
+        let result = render("{{date}} {{file}}", &variables(&[("date", "today")], true));
+        assert_eq!(result.unwrap(), "today image.png");
+    }
+
+    #[test]
+    fn test_missing_hash_file() {
+        let err = render("{{sha256:/does/not/exist}}", &variables(&[], false)).unwrap_err();
+
+        assert!(matches!(err, TemplateError::Hash { .. }));
+    }
+
+    #[test]
+    fn test_parse_var() {
+        assert_eq!(
+            parse_var("a=b=c").unwrap(),
+            ("a".to_string(), "b=c".to_string())
+        );
+        assert!(parse_var("novalue").is_err());
+        assert!(parse_var("=value").is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! Data shared by the unit tests, built in code rather than stored as
+//! binary files so that each fixture shows how it is made.
+//!
+//! Every fixture comes with a test checking the property it exists for, so
+//! a test using it fails for its own reason and not a broken fixture.
+
+use std::str::FromStr;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    fixtures::{self, FixtureKind, make_fixture},
+    png::Png,
+};
+
+/// Type of the chunk most unit tests use: critical, private, safe to copy
+pub(crate) const TESTING_CHUNK_TYPE: &str = "RuSt";
+
+/// Data of the testing chunk, 42 bytes
+pub(crate) const TESTING_MESSAGE: &str = "This is where your secret message will be!";
+
+/// CRC of the testing chunk
+pub(crate) const TESTING_CRC: u32 = 2882656334;
+
+pub(crate) fn testing_chunk_type() -> ChunkType {
+    ChunkType::from_str(TESTING_CHUNK_TYPE).unwrap()
+}
+
+pub(crate) fn testing_chunk() -> Chunk {
+    let chunk_type = TESTING_CHUNK_TYPE.as_bytes().try_into().unwrap();
+    fixtures::chunk(chunk_type, TESTING_MESSAGE.as_bytes().to_vec())
+}
+
+/// Bytes of the testing chunk, framed by hand rather than by the
+/// serializer the chunk tests check
+pub(crate) fn testing_chunk_bytes() -> Vec<u8> {
+    [
+        &(TESTING_MESSAGE.len() as u32).to_be_bytes()[..],
+        TESTING_CHUNK_TYPE.as_bytes(),
+        TESTING_MESSAGE.as_bytes(),
+        &TESTING_CRC.to_be_bytes(),
+    ]
+    .concat()
+}
+
+/// A valid 1x1 RGBA image made of IHDR, IDAT and IEND
+pub(crate) fn minimal_png_bytes() -> Vec<u8> {
+    make_fixture(FixtureKind::Minimal)
+}
+
+/// The minimal image with two identical `ruSt` chunks before IEND
+pub(crate) fn png_with_duplicate_chunks() -> Vec<u8> {
+    let mut png = Png::new_minimal();
+    let chunk = fixtures::chunk(b"ruSt", TESTING_MESSAGE.as_bytes().to_vec());
+    for _ in 0..2 {
+        let index = png.chunks().len() - 1;
+        png.insert_chunk(index, chunk.clone()).unwrap();
+    }
+
+    png.as_bytes()
+}
+
+/// The minimal image whose IDAT chunk has a wrong CRC
+pub(crate) fn png_with_bad_crc() -> Vec<u8> {
+    make_fixture(FixtureKind::CorruptCrc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::ChunkParserError,
+        png::{PngError, PngParserError},
+    };
+
+    fn chunk_types(bytes: &[u8]) -> Vec<String> {
+        Png::try_from(bytes)
+            .unwrap()
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_testing_chunk_is_consistent() {
+        let chunk = testing_chunk();
+
+        assert_eq!(chunk.length() as usize, TESTING_MESSAGE.len());
+        assert_eq!(*chunk.chunk_type(), testing_chunk_type());
+        assert_eq!(chunk.data(), TESTING_MESSAGE.as_bytes());
+        assert_eq!(
+            Chunk::compute_crc(&testing_chunk_type(), TESTING_MESSAGE.as_bytes()),
+            TESTING_CRC
+        );
+        assert_eq!(chunk.as_bytes(), testing_chunk_bytes());
+    }
+
+    #[test]
+    fn test_minimal_png_is_valid() {
+        assert_eq!(chunk_types(&minimal_png_bytes()), ["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_duplicate_chunks_are_identical() {
+        let bytes = png_with_duplicate_chunks();
+        assert_eq!(
+            chunk_types(&bytes),
+            ["IHDR", "IDAT", "ruSt", "ruSt", "IEND"]
+        );
+
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(png.chunks()[2], png.chunks()[3]);
+    }
+
+    #[test]
+    fn test_bad_crc_is_the_only_defect() {
+        let bytes = png_with_bad_crc();
+        assert!(matches!(
+            Png::try_from(bytes.as_slice()),
+            Err(PngError::ParserError(PngParserError::InvalidChunk(
+                ChunkParserError::InvalidChecksum
+            )))
+        ));
+        assert_eq!(bytes.len(), minimal_png_bytes().len());
+    }
+}
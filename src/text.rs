@@ -0,0 +1,505 @@
+use std::io::Write;
+
+use flate2::{Compression, write::ZlibEncoder};
+use thiserror::Error;
+
+use crate::{
+    codes::Code,
+    inflate::{InflateError, inflate},
+};
+
+/// Type of the spec's Latin-1 textual chunk
+pub const TEXT_CHUNK_TYPE: [u8; 4] = *b"tEXt";
+
+/// Type of the spec's UTF-8 textual chunk
+pub const ITXT_CHUNK_TYPE: [u8; 4] = *b"iTXt";
+
+/// Type of the spec's compressed Latin-1 textual chunk
+pub const ZTXT_CHUNK_TYPE: [u8; 4] = *b"zTXt";
+
+const MAX_KEYWORD_LENGTH: usize = 79;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TextError {
+    #[error("Keyword must contain at least one character")]
+    EmptyKeyword,
+
+    #[error("Keyword is too long (expected at most {MAX_KEYWORD_LENGTH} bytes, got {actual})")]
+    KeywordTooLong { actual: usize },
+
+    #[error("Keyword must not have leading, trailing or consecutive spaces")]
+    KeywordSpaces,
+
+    #[error("Keyword contains the forbidden character {character:?} at position {position}")]
+    KeywordInvalidCharacter { character: char, position: usize },
+
+    #[error(
+        "Character {character:?} at position {position} is not Latin-1, use an iTXt chunk for UTF-8 text"
+    )]
+    NotLatin1 { character: char, position: usize },
+
+    #[error(
+        "Language tag {tag:?} is invalid, expected hyphen-separated groups of 1 to 8 ASCII letters or digits"
+    )]
+    InvalidLanguageTag { tag: String },
+
+    #[error("The translated keyword must not contain a NUL character")]
+    TranslatedKeywordNul,
+
+    #[error("zTXt chunk has no null separator after the keyword")]
+    ZtxtMissingSeparator,
+
+    #[error("zTXt chunk has no compression method")]
+    ZtxtMissingCompressionMethod,
+
+    #[error("Unsupported zTXt compression method {0} (only 0 is defined)")]
+    ZtxtUnsupportedCompression(u8),
+}
+
+impl TextError {
+    pub fn code(&self) -> Code {
+        match self {
+            TextError::EmptyKeyword => Code::EmptyKeyword,
+            TextError::KeywordTooLong { .. } => Code::KeywordTooLong,
+            TextError::KeywordSpaces => Code::KeywordSpaces,
+            TextError::KeywordInvalidCharacter { .. } => Code::KeywordInvalidCharacter,
+            TextError::NotLatin1 { .. } => Code::NotLatin1,
+            TextError::InvalidLanguageTag { .. } => Code::InvalidLanguageTag,
+            TextError::TranslatedKeywordNul => Code::TranslatedKeywordNul,
+            TextError::ZtxtMissingSeparator => Code::ZtxtMissingSeparator,
+            TextError::ZtxtMissingCompressionMethod => Code::ZtxtMissingCompression,
+            TextError::ZtxtUnsupportedCompression(_) => Code::ZtxtUnsupportedCompression,
+        }
+    }
+}
+
+/// Checks a tEXt/zTXt/iTXt keyword: 1-79 printable Latin-1 characters
+/// without leading, trailing or consecutive spaces.
+pub fn validate_text_keyword(keyword: &str) -> Result<(), TextError> {
+    if keyword.is_empty() {
+        return Err(TextError::EmptyKeyword);
+    }
+
+    // Every accepted character is a single Latin-1 byte so the length in
+    // characters is the length on disk.
+    for (position, character) in keyword.chars().enumerate() {
+        if !matches!(character as u32, 0x20..=0x7E | 0xA1..=0xFF) {
+            return Err(TextError::KeywordInvalidCharacter {
+                character,
+                position,
+            });
+        }
+    }
+
+    let length = keyword.chars().count();
+    if length > MAX_KEYWORD_LENGTH {
+        return Err(TextError::KeywordTooLong { actual: length });
+    }
+
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return Err(TextError::KeywordSpaces);
+    }
+
+    Ok(())
+}
+
+/// Encodes `text` as Latin-1, failing on the first character outside of it
+pub fn latin1_encode(text: &str) -> Result<Vec<u8>, TextError> {
+    text.chars()
+        .enumerate()
+        .map(|(position, character)| {
+            u8::try_from(character as u32).map_err(|_| TextError::NotLatin1 {
+                character,
+                position,
+            })
+        })
+        .collect()
+}
+
+/// Encodes `text` as Latin-1, replacing characters outside of it with '?'.
+///
+/// Returns the bytes and the number of substituted characters.
+pub fn latin1_encode_lossy(text: &str) -> (Vec<u8>, usize) {
+    let mut substituted = 0;
+    let bytes = text
+        .chars()
+        .map(|character| {
+            u8::try_from(character as u32).unwrap_or_else(|_| {
+                substituted += 1;
+                b'?'
+            })
+        })
+        .collect();
+
+    (bytes, substituted)
+}
+
+/// Decodes Latin-1 bytes, every byte maps to the code point of the same value
+pub fn latin1_decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| byte as char).collect()
+}
+
+/// Data of a tEXt chunk: the keyword, a NUL separator and the text, both
+/// Latin-1
+pub fn text_chunk_data(keyword: &str, text: &str) -> Result<Vec<u8>, TextError> {
+    validate_text_keyword(keyword)?;
+
+    let mut data = latin1_encode(keyword)?;
+    data.push(0);
+    data.extend(latin1_encode(text)?);
+    Ok(data)
+}
+
+/// Keyword and text of tEXt chunk data, `None` without the NUL separator
+pub fn split_text_chunk(data: &[u8]) -> Option<(String, String)> {
+    let separator = data.iter().position(|&byte| byte == 0)?;
+
+    Some((
+        latin1_decode(&data[..separator]),
+        latin1_decode(&data[separator + 1..]),
+    ))
+}
+
+/// Data of a zTXt chunk: the keyword, a NUL separator, the compression
+/// method 0 and the deflated text, both Latin-1
+pub fn ztxt_chunk_data(keyword: &str, text: &str) -> Result<Vec<u8>, TextError> {
+    validate_text_keyword(keyword)?;
+    let text = latin1_encode(text)?;
+
+    let mut data = latin1_encode(keyword)?;
+    data.extend([0, 0]);
+    let mut encoder = ZlibEncoder::new(data, Compression::best());
+    encoder
+        .write_all(&text)
+        .expect("writing to a Vec can't fail");
+    Ok(encoder.finish().expect("writing to a Vec can't fail"))
+}
+
+/// A zTXt chunk split into its fields
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZtxtChunk<'a> {
+    pub keyword: String,
+    /// The deflated text
+    pub compressed: &'a [u8],
+}
+
+impl<'a> ZtxtChunk<'a> {
+    /// Splits zTXt chunk data
+    pub fn parse(data: &'a [u8]) -> Result<Self, TextError> {
+        let separator = data
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or(TextError::ZtxtMissingSeparator)?;
+
+        let (&method, compressed) = data[separator + 1..]
+            .split_first()
+            .ok_or(TextError::ZtxtMissingCompressionMethod)?;
+        if method != 0 {
+            return Err(TextError::ZtxtUnsupportedCompression(method));
+        }
+
+        Ok(Self {
+            keyword: latin1_decode(&data[..separator]),
+            compressed,
+        })
+    }
+
+    /// The text, inflated to at most `limit` bytes
+    pub fn text(&self, limit: u64) -> Result<String, InflateError> {
+        Ok(latin1_decode(&inflate(self.compressed, limit)?))
+    }
+}
+
+/// Checks an iTXt language tag: empty, or RFC 3066 style groups of 1 to 8
+/// ASCII letters or digits separated by hyphens, e.g. `fr` or `en-GB`
+pub fn validate_language_tag(tag: &str) -> Result<(), TextError> {
+    let valid = tag.is_empty()
+        || tag.split('-').all(|group| {
+            (1..=8).contains(&group.len()) && group.bytes().all(|byte| byte.is_ascii_alphanumeric())
+        });
+
+    match valid {
+        true => Ok(()),
+        false => Err(TextError::InvalidLanguageTag {
+            tag: tag.to_string(),
+        }),
+    }
+}
+
+/// The fields of an iTXt chunk before its text
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ItxtHeader {
+    /// Latin-1 keyword, as in tEXt chunks
+    pub keyword: String,
+    /// Language of the text, empty when unknown
+    pub language: String,
+    /// The keyword in that language, empty when not given
+    pub translated_keyword: String,
+}
+
+impl ItxtHeader {
+    /// Data of an uncompressed iTXt chunk holding `text`: the keyword, a
+    /// NUL, the compression flag and method, the language tag, a NUL, the
+    /// translated keyword, a NUL and the UTF-8 text
+    pub fn chunk_data(&self, text: &str) -> Result<Vec<u8>, TextError> {
+        validate_text_keyword(&self.keyword)?;
+        validate_language_tag(&self.language)?;
+        if self.translated_keyword.contains('\0') {
+            return Err(TextError::TranslatedKeywordNul);
+        }
+
+        let mut data = latin1_encode(&self.keyword)?;
+        data.extend([0, 0, 0]);
+        data.extend(self.language.as_bytes());
+        data.push(0);
+        data.extend(self.translated_keyword.as_bytes());
+        data.push(0);
+        data.extend(text.as_bytes());
+        Ok(data)
+    }
+}
+
+/// An iTXt chunk split into its fields
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItxtChunk<'a> {
+    pub header: ItxtHeader,
+    /// Whether the text is zlib compressed
+    pub compressed: bool,
+    /// The text as stored, compressed or not
+    pub stored_text: &'a [u8],
+}
+
+impl<'a> ItxtChunk<'a> {
+    /// Splits iTXt chunk data, `None` when a separator is missing or the
+    /// compression method is unknown
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let separator = data.iter().position(|&byte| byte == 0)?;
+        let (keyword, rest) = (&data[..separator], &data[separator + 1..]);
+        let (&[compressed, method], rest) = rest.split_first_chunk::<2>()?;
+        let mut fields = rest.splitn(3, |&byte| byte == 0);
+        let (language, translated, stored_text) = (fields.next()?, fields.next()?, fields.next()?);
+
+        let compressed = match (compressed, method) {
+            (0, _) => false,
+            (1, 0) => true,
+            _ => return None,
+        };
+        let utf8 = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+
+        Some(Self {
+            header: ItxtHeader {
+                keyword: latin1_decode(keyword),
+                language: utf8(language),
+                translated_keyword: utf8(translated),
+            },
+            compressed,
+            stored_text,
+        })
+    }
+
+    /// The text, inflated to at most `limit` bytes when compressed. Invalid
+    /// UTF-8 sequences are replaced.
+    pub fn text(&self, limit: u64) -> Result<String, InflateError> {
+        let text = match self.compressed {
+            true => inflate(self.stored_text, limit)?,
+            false => self.stored_text.to_vec(),
+        };
+
+        Ok(String::from_utf8_lossy(&text).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_keywords() {
+        assert!(validate_text_keyword("Title").is_ok());
+        assert!(validate_text_keyword("Creation Time").is_ok());
+        assert!(validate_text_keyword("Légende").is_ok());
+        assert!(validate_text_keyword(&"k".repeat(79)).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_keywords() {
+        assert_eq!(validate_text_keyword(""), Err(TextError::EmptyKeyword));
+        assert_eq!(
+            validate_text_keyword(&"k".repeat(80)),
+            Err(TextError::KeywordTooLong { actual: 80 })
+        );
+        assert_eq!(
+            validate_text_keyword(" Title"),
+            Err(TextError::KeywordSpaces)
+        );
+        assert_eq!(
+            validate_text_keyword("Title "),
+            Err(TextError::KeywordSpaces)
+        );
+        assert_eq!(
+            validate_text_keyword("Ti  tle"),
+            Err(TextError::KeywordSpaces)
+        );
+        assert_eq!(
+            validate_text_keyword("Ti\0tle"),
+            Err(TextError::KeywordInvalidCharacter {
+                character: '\0',
+                position: 2
+            })
+        );
+        assert_eq!(
+            validate_text_keyword("Ti🦀"),
+            Err(TextError::KeywordInvalidCharacter {
+                character: '🦀',
+                position: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_latin1_round_trip() {
+        let bytes = latin1_encode("café").unwrap();
+        assert_eq!(bytes, vec![b'c', b'a', b'f', 0xE9]);
+        assert_eq!(latin1_decode(&bytes), "café");
+    }
+
+    #[test]
+    fn test_latin1_rejects_emoji() {
+        assert_eq!(
+            latin1_encode("hi 🦀"),
+            Err(TextError::NotLatin1 {
+                character: '🦀',
+                position: 3
+            })
+        );
+        assert!(
+            latin1_encode("🦀")
+                .unwrap_err()
+                .to_string()
+                .contains("iTXt")
+        );
+    }
+
+    #[test]
+    fn test_latin1_lossy() {
+        assert_eq!(
+            latin1_encode_lossy("é 🦀!"),
+            (vec![0xE9, b' ', b'?', b'!'], 1)
+        );
+        assert_eq!(latin1_encode_lossy("plain"), (b"plain".to_vec(), 0));
+    }
+
+    #[test]
+    fn test_text_chunk_data() {
+        let data = text_chunk_data("Title", "café").unwrap();
+
+        assert_eq!(data, b"Title\0caf\xe9");
+        assert_eq!(
+            split_text_chunk(&data),
+            Some(("Title".to_string(), "café".to_string()))
+        );
+        assert_eq!(split_text_chunk(b"no separator"), None);
+        assert_eq!(
+            text_chunk_data(" Title", "text"),
+            Err(TextError::KeywordSpaces)
+        );
+        assert!(matches!(
+            text_chunk_data("Title", "🦀"),
+            Err(TextError::NotLatin1 { position: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_itxt_round_trip() {
+        let header = ItxtHeader {
+            keyword: "Title".to_string(),
+            language: "ja-JP".to_string(),
+            translated_keyword: "タイトル".to_string(),
+        };
+        let data = header.chunk_data("こんにちは").unwrap();
+
+        assert!(data.starts_with(b"Title\0\0\0ja-JP\0"));
+        let chunk = ItxtChunk::parse(&data).unwrap();
+        assert_eq!(chunk.header, header);
+        assert!(!chunk.compressed);
+        assert_eq!(chunk.text(100).unwrap(), "こんにちは");
+    }
+
+    #[test]
+    fn test_compressed_itxt_is_inflated() {
+        use std::io::Write;
+
+        use flate2::{Compression, write::ZlibEncoder};
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all("Café".as_bytes()).unwrap();
+        let data = [&b"Title\0\x01\0fr\0Titre\0"[..], &encoder.finish().unwrap()].concat();
+
+        let chunk = ItxtChunk::parse(&data).unwrap();
+        assert!(chunk.compressed);
+        assert_eq!(chunk.header.language, "fr");
+        assert_eq!(chunk.text(100).unwrap(), "Café");
+        assert!(matches!(chunk.text(2), Err(InflateError::TooLarge { .. })));
+
+        // Compression method 5 doesn't exist
+        assert_eq!(ItxtChunk::parse(b"Title\0\x01\x05\0\0text"), None);
+    }
+
+    #[test]
+    fn test_itxt_header_checks() {
+        let header = |language: &str, translated_keyword: &str| ItxtHeader {
+            keyword: "Title".to_string(),
+            language: language.to_string(),
+            translated_keyword: translated_keyword.to_string(),
+        };
+
+        assert!(header("", "").chunk_data("text").is_ok());
+        assert!(header("x-klingon", "").chunk_data("text").is_ok());
+        assert_eq!(
+            header("en_GB", "").chunk_data("text"),
+            Err(TextError::InvalidLanguageTag {
+                tag: "en_GB".to_string()
+            })
+        );
+        assert!(header("fr--", "").chunk_data("text").is_err());
+        assert!(header("languages", "").chunk_data("text").is_err());
+        assert_eq!(
+            header("fr", "Ti\0tre").chunk_data("text"),
+            Err(TextError::TranslatedKeywordNul)
+        );
+    }
+
+    #[test]
+    fn test_ztxt_round_trip() {
+        let data = ztxt_chunk_data("Title", "café").unwrap();
+
+        assert!(data.starts_with(b"Title\0\0"));
+        let chunk = ZtxtChunk::parse(&data).unwrap();
+        assert_eq!(chunk.keyword, "Title");
+        assert_eq!(chunk.text(100).unwrap(), "café");
+        assert!(matches!(
+            ztxt_chunk_data("Title", "🦀"),
+            Err(TextError::NotLatin1 { .. })
+        ));
+    }
+
+    #[test]
+    fn test_malformed_ztxt() {
+        assert_eq!(
+            ZtxtChunk::parse(b"Title"),
+            Err(TextError::ZtxtMissingSeparator)
+        );
+        assert_eq!(
+            ZtxtChunk::parse(b"Title\0"),
+            Err(TextError::ZtxtMissingCompressionMethod)
+        );
+        assert_eq!(
+            ZtxtChunk::parse(b"Title\0\x01"),
+            Err(TextError::ZtxtUnsupportedCompression(1))
+        );
+        assert!(matches!(
+            ZtxtChunk::parse(b"Title\0\0\x78").unwrap().text(100),
+            Err(InflateError::Corrupt(_))
+        ));
+    }
+}
@@ -0,0 +1,215 @@
+//! Where the time of a command goes, printed by `--stats`.
+//!
+//! The library reports a span for every stage it goes through (see
+//! [`Observer::on_span`]). [`StatsObserver`] adds them up into [`Timings`]
+//! while passing every event on, and the time not covered by any span is
+//! reported as the command's own work.
+
+use std::{
+    fmt::{self, Display},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{
+    observer::{Observer, Stage},
+    png::ParseWarning,
+};
+
+/// Time spent in one stage over a whole command
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StageTiming {
+    pub stage: Stage,
+    /// Number of spans, e.g. one read per input file
+    pub count: u32,
+    pub seconds: f64,
+    /// Largest amount of data handled by a single span
+    pub peak_bytes: u64,
+}
+
+/// Spans recorded since the command started
+#[derive(Debug)]
+pub struct Timings {
+    start: Instant,
+    stages: Mutex<Vec<StageTiming>>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            stages: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn record(&self, stage: Stage, elapsed: Duration, bytes: u64) {
+        // A panic while holding the lock leaves usable totals
+        let mut stages = self.stages.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match stages.iter_mut().find(|timing| timing.stage == stage) {
+            Some(timing) => {
+                timing.count += 1;
+                timing.seconds += elapsed.as_secs_f64();
+                timing.peak_bytes = timing.peak_bytes.max(bytes);
+            }
+            None => stages.push(StageTiming {
+                stage,
+                count: 1,
+                seconds: elapsed.as_secs_f64(),
+                peak_bytes: bytes,
+            }),
+        }
+    }
+
+    /// The stages in the order they first ran, up to now
+    pub fn report(&self) -> TimingsReport {
+        let stages = self
+            .stages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let total_seconds = self.start.elapsed().as_secs_f64();
+        let spans: f64 = stages.iter().map(|timing| timing.seconds).sum();
+
+        TimingsReport {
+            stages,
+            // Spans may overlap by rounding, never report negative work
+            other_seconds: (total_seconds - spans).max(0.0),
+            total_seconds,
+        }
+    }
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What `--stats` prints: a table on stderr, or a `stats` object with JSON
+/// output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimingsReport {
+    pub stages: Vec<StageTiming>,
+    /// Time outside of the recorded stages: the command's own work
+    pub other_seconds: f64,
+    pub total_seconds: f64,
+}
+
+impl Display for TimingsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let milliseconds = |seconds: f64| seconds * 1000.0;
+
+        writeln!(
+            f,
+            "{:<10} {:>6} {:>12} {:>12}",
+            "Stage", "Count", "Time (ms)", "Peak bytes"
+        )?;
+        for timing in &self.stages {
+            writeln!(
+                f,
+                "{:<10} {:>6} {:>12.3} {:>12}",
+                format!("{:?}", timing.stage).to_lowercase(),
+                timing.count,
+                milliseconds(timing.seconds),
+                timing.peak_bytes
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<10} {:>6} {:>12.3}",
+            "other",
+            "",
+            milliseconds(self.other_seconds)
+        )?;
+        write!(
+            f,
+            "{:<10} {:>6} {:>12.3}",
+            "total",
+            "",
+            milliseconds(self.total_seconds)
+        )
+    }
+}
+
+/// Records the spans into [`Timings`] and forwards every event to `inner`
+pub struct StatsObserver<'a> {
+    inner: &'a dyn Observer,
+    pub timings: Timings,
+}
+
+impl<'a> StatsObserver<'a> {
+    pub fn new(inner: &'a dyn Observer) -> Self {
+        Self {
+            inner,
+            timings: Timings::new(),
+        }
+    }
+}
+
+impl Observer for StatsObserver<'_> {
+    fn on_progress(&self, stage: Stage, done: u64, total: Option<u64>) {
+        self.inner.on_progress(stage, done, total);
+    }
+
+    fn on_warning(&self, warning: &ParseWarning) {
+        self.inner.on_warning(warning);
+    }
+
+    fn on_span(&self, stage: Stage, elapsed: Duration, bytes: u64) {
+        self.timings.record(stage, elapsed, bytes);
+        self.inner.on_span(stage, elapsed, bytes);
+    }
+
+    fn on_note(&self, note: &str) {
+        self.inner.on_note(note);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_add_up_per_stage() {
+        let timings = Timings::new();
+        timings.record(Stage::Read, Duration::from_millis(2), 100);
+        timings.record(Stage::Parse, Duration::from_millis(1), 100);
+        timings.record(Stage::Read, Duration::from_millis(3), 300);
+
+        let report = timings.report();
+
+        let stages: Vec<(Stage, u32, u64)> = report
+            .stages
+            .iter()
+            .map(|timing| (timing.stage, timing.count, timing.peak_bytes))
+            .collect();
+        assert_eq!(stages, [(Stage::Read, 2, 300), (Stage::Parse, 1, 100)]);
+        assert!((report.stages[0].seconds - 0.005).abs() < 1e-9);
+        assert!(report.other_seconds >= 0.0);
+    }
+
+    #[test]
+    fn test_human_report() {
+        let report = TimingsReport {
+            stages: vec![StageTiming {
+                stage: Stage::Write,
+                count: 1,
+                seconds: 0.0025,
+                peak_bytes: 4096,
+            }],
+            other_seconds: 0.0005,
+            total_seconds: 0.003,
+        };
+
+        assert_eq!(
+            report.to_string(),
+            "Stage       Count    Time (ms)   Peak bytes\n\
+             write           1        2.500         4096\n\
+             other                    0.500\n\
+             total                    3.000"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! All-or-nothing writes of several files, for `--transactional`.
+//!
+//! Each output is staged in a temporary file next to its destination, the
+//! way a [`FileSink`] writes a single one. Only once every output is staged
+//! are the temporary files renamed over their destinations. A failure before
+//! that discards every staged file and leaves every destination as it was.
+//!
+//! The renames themselves are not atomic as a whole: should one fail, the
+//! destinations renamed before it keep their new content. The window is
+//! short, every byte having been written and synced before the first one.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    lock::FileLock,
+    sink::{FileSink, WriteSink},
+};
+
+/// Outputs staged for [`Transaction::commit`], dropping it discards them
+#[derive(Default)]
+pub struct Transaction {
+    staged: Vec<(PathBuf, FileSink)>,
+    /// Locks of the files edited in place, released once committed
+    locks: Vec<FileLock>,
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes `bytes` to a temporary file to be renamed over `path` on
+    /// commit. On failure the file is discarded, the transaction should be
+    /// dropped.
+    pub fn stage(&mut self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        if self.staged.iter().any(|(staged, _)| staged == path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is staged twice", path.display()),
+            ));
+        }
+
+        let mut sink = FileSink::new(path);
+        let writer = sink.writer()?;
+        writer.write_all(bytes)?;
+        writer.flush()?;
+        self.staged.push((path.to_path_buf(), sink));
+
+        Ok(())
+    }
+
+    /// Keeps `lock` until the transaction is committed or dropped, so that
+    /// no other process changes the file in between
+    pub fn hold(&mut self, lock: FileLock) {
+        self.locks.push(lock);
+    }
+
+    /// Number of staged outputs
+    pub fn len(&self) -> usize {
+        self.staged.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.staged.is_empty()
+    }
+
+    /// Renames every staged file over its destination, in staging order.
+    /// The files not renamed when one fails are discarded.
+    pub fn commit(mut self) -> Result<(), (PathBuf, io::Error)> {
+        for (path, sink) in &mut self.staged {
+            sink.commit().map_err(|err| (path.clone(), err))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_commit_replaces_every_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = ["a", "b", "c"].map(|name| dir.path().join(name)).into();
+        for path in &paths {
+            fs::write(path, "old").unwrap();
+        }
+
+        let mut transaction = Transaction::new();
+        for path in &paths {
+            transaction.stage(path, b"new").unwrap();
+            assert_eq!(fs::read(path).unwrap(), b"old");
+        }
+        assert_eq!(transaction.len(), 3);
+        transaction.commit().unwrap();
+
+        for path in &paths {
+            assert_eq!(fs::read(path).unwrap(), b"new");
+        }
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_a_failed_stage_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first");
+        let third = dir.path().join("third");
+        fs::write(&first, "old").unwrap();
+        fs::write(&third, "old").unwrap();
+        // No temporary file can be created in a missing directory
+        let second = dir.path().join("missing").join("second");
+
+        let mut transaction = Transaction::new();
+        transaction.stage(&first, b"new").unwrap();
+        assert!(transaction.stage(&second, b"new").is_err());
+        drop(transaction);
+
+        assert_eq!(fs::read(&first).unwrap(), b"old");
+        assert_eq!(fs::read(&third).unwrap(), b"old");
+        // The staged temporary file is gone too
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_a_path_is_staged_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+
+        let mut transaction = Transaction::new();
+        transaction.stage(&path, b"first").unwrap();
+        let err = transaction.stage(&path, b"second").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{codes::Code, hash::sha256_hex};
+
+/// Directory, next to the edited file, holding its undo states
+pub const UNDO_DIR: &str = ".pngme/undo";
+
+#[derive(Error, Debug)]
+pub enum UndoError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("Invalid undo manifest {name}: {source}")]
+    Manifest {
+        name: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("Nothing to undo for {path}")]
+    Empty { path: PathBuf },
+
+    #[error("The undo state {name} is corrupted (checksum mismatch)")]
+    Corrupted { name: String },
+}
+
+impl UndoError {
+    pub fn code(&self) -> Code {
+        match self {
+            UndoError::Io(_) => Code::UndoIoFailed,
+            UndoError::Manifest { .. } => Code::InvalidUndoManifest,
+            UndoError::Empty { .. } => Code::NothingToUndo,
+            UndoError::Corrupted { .. } => Code::UndoCorrupted,
+        }
+    }
+}
+
+/// What produced an undo state, stored next to the original bytes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub file: String,
+    /// Command that rewrote the file, e.g. `encode`
+    pub operation: String,
+    /// Seconds since the epoch
+    pub created_at: u64,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// A saved pre-modification copy of a file
+#[derive(Debug, Clone)]
+pub struct UndoState {
+    /// `<hash>-<timestamp>-<sequence>`, shared by the `.png` and `.json` files
+    pub name: String,
+    pub sequence: u64,
+    pub manifest: Manifest,
+}
+
+/// Undo states of one file, kept in [`UNDO_DIR`] next to it. States are
+/// named after a hash of the file's canonical path so that files sharing a
+/// directory do not mix.
+pub struct UndoStore {
+    dir: PathBuf,
+    prefix: String,
+    file: PathBuf,
+}
+
+impl UndoStore {
+    pub const DEFAULT_KEEP: usize = 3;
+
+    pub fn for_file(path: &Path) -> Result<Self, UndoError> {
+        let file = fs::canonicalize(path)?;
+        let dir = file
+            .parent()
+            .map_or_else(|| PathBuf::from(UNDO_DIR), |parent| parent.join(UNDO_DIR));
+        let prefix = sha256_hex(file.as_os_str().as_encoded_bytes())[..16].to_string();
+
+        Ok(Self { dir, prefix, file })
+    }
+
+    /// Available states, most recent first
+    pub fn states(&self) -> Result<Vec<UndoState>, UndoError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut states = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let Some(name) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json"))
+                .filter(|name| name.starts_with(&format!("{}-", self.prefix)))
+            else {
+                continue;
+            };
+            let Some(sequence) = name.rsplit('-').next().and_then(|seq| seq.parse().ok()) else {
+                continue;
+            };
+
+            let manifest = serde_json::from_slice(&fs::read(&path)?).map_err(|source| {
+                UndoError::Manifest {
+                    name: name.to_string(),
+                    source,
+                }
+            })?;
+            states.push(UndoState {
+                name: name.to_string(),
+                sequence,
+                manifest,
+            });
+        }
+
+        states.sort_by_key(|state| std::cmp::Reverse(state.sequence));
+
+        Ok(states)
+    }
+
+    /// Stores `bytes` as the newest state and drops all but the `keep` most
+    /// recent ones
+    pub fn save(
+        &self,
+        bytes: &[u8],
+        operation: &str,
+        now: u64,
+        keep: usize,
+    ) -> Result<UndoState, UndoError> {
+        fs::create_dir_all(&self.dir)?;
+
+        let states = self.states()?;
+        let sequence = states.first().map_or(1, |state| state.sequence + 1);
+        let name = format!("{}-{now}-{sequence}", self.prefix);
+        let manifest = Manifest {
+            file: self.file.display().to_string(),
+            operation: operation.to_string(),
+            created_at: now,
+            size: bytes.len() as u64,
+            sha256: sha256_hex(bytes),
+        };
+
+        fs::write(self.dir.join(format!("{name}.png")), bytes)?;
+        let json = serde_json::to_vec_pretty(&manifest).map_err(|source| UndoError::Manifest {
+            name: name.clone(),
+            source,
+        })?;
+        fs::write(self.dir.join(format!("{name}.json")), json)?;
+
+        for state in states.iter().skip(keep.saturating_sub(1)) {
+            self.remove(state)?;
+        }
+
+        Ok(UndoState {
+            name,
+            sequence,
+            manifest,
+        })
+    }
+
+    /// The most recent state and its bytes, checked against the manifest
+    pub fn latest(&self) -> Result<(UndoState, Vec<u8>), UndoError> {
+        let state = self
+            .states()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| UndoError::Empty {
+                path: self.file.clone(),
+            })?;
+        let bytes = fs::read(self.dir.join(format!("{}.png", state.name)))?;
+
+        if sha256_hex(&bytes) != state.manifest.sha256 {
+            return Err(UndoError::Corrupted { name: state.name });
+        }
+
+        Ok((state, bytes))
+    }
+
+    pub fn remove(&self, state: &UndoState) -> Result<(), UndoError> {
+        for extension in ["png", "json"] {
+            match fs::remove_file(self.dir.join(format!("{}.{extension}", state.name))) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> (tempfile::TempDir, UndoStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("image.png");
+        fs::write(&file, b"current").unwrap();
+        let store = UndoStore::for_file(&file).unwrap();
+
+        (dir, store)
+    }
+
+    #[test]
+    fn test_latest_state_comes_first() {
+        let (_dir, store) = store();
+
+        store.save(b"first", "encode", 10, 3).unwrap();
+        store.save(b"second", "remove", 10, 3).unwrap();
+
+        let (state, bytes) = store.latest().unwrap();
+        assert_eq!(bytes, b"second");
+        assert_eq!(state.manifest.operation, "remove");
+        assert_eq!(store.states().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_old_states_are_rotated() {
+        let (_dir, store) = store();
+
+        for n in 0..5u8 {
+            store.save(&[n], "encode", 10, 3).unwrap();
+        }
+
+        let states = store.states().unwrap();
+        let sequences: Vec<u64> = states.iter().map(|state| state.sequence).collect();
+        assert_eq!(sequences, vec![5, 4, 3]);
+    }
+
+    #[test]
+    fn test_corrupted_state() {
+        let (dir, store) = store();
+        let state = store.save(b"original", "encode", 10, 3).unwrap();
+
+        fs::write(
+            dir.path()
+                .join(UNDO_DIR)
+                .join(format!("{}.png", state.name)),
+            b"tampered",
+        )
+        .unwrap();
+
+        assert!(matches!(store.latest(), Err(UndoError::Corrupted { .. })));
+    }
+
+    #[test]
+    fn test_nothing_to_undo() {
+        let (_dir, store) = store();
+
+        assert!(matches!(store.latest(), Err(UndoError::Empty { .. })));
+    }
+}
@@ -0,0 +1,134 @@
+//! Sizes past which images are commonly refused as attachments.
+//!
+//! A payload can push an image over the limit of a chat service or a mail
+//! server, and the upload then fails far from pngme with no hint why.
+//! `encode` compares the size of its output with [`COMMON_LIMITS`], or the
+//! thresholds given with `--size-warn`, and warns about each one it exceeds.
+//! An image of exactly the limit is accepted by these services.
+
+use std::fmt::{self, Display};
+
+use serde::Serialize;
+
+use crate::codes::Code;
+
+/// A size not to exceed, with who enforces it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeThreshold {
+    pub bytes: u64,
+    /// The services refusing larger files, `None` for a threshold given on
+    /// the command line
+    pub services: Option<&'static str>,
+}
+
+impl SizeThreshold {
+    /// A threshold given on the command line
+    pub fn custom(bytes: u64) -> Self {
+        Self {
+            bytes,
+            services: None,
+        }
+    }
+}
+
+impl Display for SizeThreshold {
+    /// `8 MiB (Discord)`, or the number of bytes when not a whole number of
+    /// MiB
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bytes {
+            bytes if bytes > 0 && bytes % (1 << 20) == 0 => write!(f, "{} MiB", bytes >> 20)?,
+            bytes => write!(f, "{bytes} bytes")?,
+        }
+        match self.services {
+            Some(services) => write!(f, " ({services})"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Attachment limits of common services, smallest first
+pub const COMMON_LIMITS: &[SizeThreshold] = &[
+    SizeThreshold {
+        bytes: 8 << 20,
+        services: Some("Discord without boosts, many chat bots"),
+    },
+    SizeThreshold {
+        bytes: 10 << 20,
+        services: Some("Discord, many mail servers"),
+    },
+    SizeThreshold {
+        bytes: 25 << 20,
+        services: Some("Gmail, Outlook"),
+    },
+];
+
+/// A threshold an output exceeds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SizeWarning {
+    pub code: Code,
+    /// Size of the output
+    pub size: u64,
+    pub threshold: u64,
+    pub message: String,
+}
+
+/// The thresholds of `thresholds` a `size`-byte output exceeds, in order
+pub fn exceeded(size: u64, thresholds: &[SizeThreshold]) -> Vec<SizeWarning> {
+    thresholds
+        .iter()
+        .filter(|threshold| size > threshold.bytes)
+        .map(|threshold| SizeWarning {
+            code: Code::UploadLimit,
+            size,
+            threshold: threshold.bytes,
+            message: format!("the output is {size} bytes, over the limit of {threshold}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thresholds(size: u64) -> Vec<u64> {
+        exceeded(size, COMMON_LIMITS)
+            .iter()
+            .map(|warning| warning.threshold)
+            .collect()
+    }
+
+    #[test]
+    fn test_each_boundary() {
+        for limit in COMMON_LIMITS {
+            let below = thresholds(limit.bytes);
+            let above = thresholds(limit.bytes + 1);
+
+            assert!(!below.contains(&limit.bytes), "{limit}");
+            assert!(above.contains(&limit.bytes), "{limit}");
+        }
+
+        assert!(thresholds(8 << 20).is_empty());
+        assert_eq!(thresholds((10 << 20) + 1), [8 << 20, 10 << 20]);
+        assert_eq!(thresholds(u64::MAX), [8 << 20, 10 << 20, 25 << 20]);
+    }
+
+    #[test]
+    fn test_custom_threshold() {
+        let warnings = exceeded(1001, &[SizeThreshold::custom(1000)]);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message,
+            "the output is 1001 bytes, over the limit of 1000 bytes"
+        );
+        assert!(exceeded(1000, &[SizeThreshold::custom(1000)]).is_empty());
+        assert!(exceeded(1, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(COMMON_LIMITS[2].to_string(), "25 MiB (Gmail, Outlook)");
+        assert_eq!(SizeThreshold::custom(2 << 20).to_string(), "2 MiB");
+        assert_eq!(SizeThreshold::custom(0).to_string(), "0 bytes");
+    }
+}
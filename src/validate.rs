@@ -0,0 +1,866 @@
+//! Checks of the command line run before any command.
+//!
+//! clap stops at the first invalid argument, and commands at the first
+//! error. [`validate`] instead collects every problem it can find without
+//! doing any work: invalid chunk names, missing files, unparsable sizes and
+//! combinations of flags clap can't express. `main` prints them all as a
+//! numbered list and exits with [`exit_status`], the usage error status of
+//! clap unless the only problems are missing files.
+//!
+//! [`ResolvedOptions`] is what the checks need to know about the command
+//! line. [`validate`] only looks at it and at the `exists` predicate, so
+//! broken command lines are tested without a file system.
+
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::{
+    args::{
+        Arguments, Commands, DebugCommands, EncodeArgs, HmacKeyArgs, OutputFormat, PasswordArgs, RecursiveArgs,
+        decode_inputs,
+    },
+    chunk::{Chunk, ChunkSpecError},
+    chunk_type::{ChunkNameError, ChunkType},
+    codes::Code,
+    exit_status::ExitStatus,
+    glob::{Pattern, PatternError},
+    input::InputSource,
+    selector::{Selection, SelectionError},
+};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Problem {
+    #[error("{argument}: {error}")]
+    ChunkName {
+        argument: &'static str,
+        error: ChunkNameError,
+    },
+
+    #[error("{argument}: {} does not exist", path.display())]
+    MissingFile {
+        argument: &'static str,
+        path: PathBuf,
+    },
+
+    #[error("{first} can't be combined with {second}: {reason}")]
+    Conflict {
+        first: &'static str,
+        second: &'static str,
+        reason: &'static str,
+    },
+
+    #[error("{argument}: '{value}' is not a size, {error}")]
+    Size {
+        argument: &'static str,
+        value: String,
+        error: SizeError,
+    },
+
+    #[error("{argument}: {error}")]
+    ChunkSpec {
+        argument: &'static str,
+        error: ChunkSpecError,
+    },
+
+    #[error("{argument}: {error}")]
+    Selection {
+        argument: &'static str,
+        error: SelectionError,
+    },
+
+    #[error("{argument}: {error}")]
+    Glob {
+        argument: &'static str,
+        error: PatternError,
+    },
+}
+
+impl Problem {
+    pub fn code(&self) -> Code {
+        match self {
+            Problem::ChunkName { error, .. } => error.code(),
+            Problem::MissingFile { .. } => Code::MissingArgumentFile,
+            Problem::Conflict { .. } => Code::ConflictingArguments,
+            Problem::Size { .. } => Code::InvalidSize,
+            Problem::ChunkSpec { error, .. } => error.code(),
+            Problem::Selection { error, .. } => error.code(),
+            Problem::Glob { error, .. } => error.code(),
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SizeError {
+    #[error("expected a number of bytes, optionally followed by K, M or G")]
+    Syntax,
+
+    #[error("it doesn't fit in 64 bits")]
+    Overflow,
+}
+
+/// Parses a size in bytes, optionally followed by a binary unit: `512`,
+/// `64K`, `10MiB`, `1G`. Units are case-insensitive.
+pub fn parse_size(value: &str) -> Result<u64, SizeError> {
+    let value = value.trim();
+    let digits = value
+        .find(|character: char| !character.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(digits);
+
+    let shift = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 10,
+        "m" | "mb" | "mib" => 20,
+        "g" | "gb" | "gib" => 30,
+        _ => return Err(SizeError::Syntax),
+    };
+    let number: u64 = number.parse().map_err(|_| match number {
+        "" => SizeError::Syntax,
+        _ => SizeError::Overflow,
+    })?;
+
+    number.checked_mul(1 << shift).ok_or(SizeError::Overflow)
+}
+
+/// What the checks need to know about a command line, once the positional
+/// and named forms of each argument are resolved
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolvedOptions {
+    /// Chunk names, with the argument giving them
+    pub chunk_names: Vec<(&'static str, String)>,
+    /// Files the command reads, with the argument giving them
+    pub files: Vec<(&'static str, PathBuf)>,
+    /// Size limits as typed, with their flag
+    pub sizes: Vec<(&'static str, String)>,
+    /// Chunks in the compact `TYPE:DATA` form, with their flag
+    pub chunk_specs: Vec<(&'static str, String)>,
+    /// Chunk selections as typed, with their flag
+    pub selections: Vec<(&'static str, String)>,
+    /// File patterns as typed, with their flag
+    pub globs: Vec<(&'static str, String)>,
+    /// `--undoable` is given
+    pub undoable: bool,
+    /// The command writes to `--output` rather than editing its input
+    pub output_elsewhere: bool,
+    /// `--var` is given
+    pub vars: bool,
+    /// `--template` or `--message-template` is given
+    pub template: bool,
+    /// Arguments given as `-` to read the standard input
+    pub stdin_readers: Vec<&'static str>,
+    /// A single `--output` is given for several inputs
+    pub shared_output: bool,
+    /// `--emit-patch` is given for several inputs
+    pub shared_patch: bool,
+    /// `--format json` is given while the image is written to the standard
+    /// output
+    pub json_to_stdout: bool,
+    /// Chunk name given with `--text-keyword`
+    pub text_keyword_chunk: Option<String>,
+    /// Chunk name given with `--itxt`
+    pub itxt_chunk: Option<String>,
+    /// Chunk name given with `--ztxt`
+    pub ztxt_chunk: Option<String>,
+    /// A flag of the walk, e.g. `--exclude`, given without `--recursive`
+    pub walk_flag_alone: Option<&'static str>,
+    /// `--replace` is given with two `--pair` of the same chunk name
+    pub pair_replaced_twice: bool,
+}
+
+impl ResolvedOptions {
+    pub fn resolve(cli: &Arguments) -> Self {
+        let mut options = ResolvedOptions {
+            undoable: cli.undoable,
+            ..ResolvedOptions::default()
+        };
+        if let Some(size) = &cli.max_input_size {
+            options.sizes.push(("--max-input-size", size.clone()));
+        }
+        if let Some(rate) = &cli.limit_rate {
+            options.sizes.push(("--limit-rate", rate.clone()));
+        }
+        if let Some(size) = &cli.max_decompressed_size {
+            options
+                .sizes
+                .push(("--max-decompressed-size", size.clone()));
+        }
+
+        match &cli.command {
+            Commands::Encode(EncodeArgs {
+                file,
+                globs,
+                recursive,
+                chunk_name,
+                chunk,
+                message,
+                message_flag,
+                pairs,
+                replace,
+                output,
+                output_flag,
+                message_template,
+                message_file,
+                template,
+                vars,
+                password,
+                hmac_key,
+                sign_key,
+                resume,
+                split_size,
+                text_keyword,
+                itxt,
+                ztxt,
+                size_warn,
+                format,
+                ..
+            }) => {
+                if text_keyword.is_some() {
+                    options.text_keyword_chunk = chunk_name.clone().or(chunk.clone());
+                }
+                if itxt.is_some() {
+                    options.itxt_chunk = chunk_name.clone().or(chunk.clone());
+                }
+                if ztxt.is_some() {
+                    options.ztxt_chunk = chunk_name.clone().or(chunk.clone());
+                }
+                if let Some(name) = chunk_name {
+                    options.chunk_names.push(("CHUNK_NAME", name.clone()));
+                }
+                if let Some(name) = chunk {
+                    options.chunk_names.push(("--chunk", name.clone()));
+                }
+                if let Some(file) = file {
+                    options.input(file);
+                    options.output(file, output.as_ref().or(output_flag.as_ref()));
+                }
+                for pattern in globs {
+                    options.globs.push(("--glob", pattern.clone()));
+                }
+                options.recursive(recursive);
+                if message
+                    .as_ref()
+                    .or(message_flag.as_ref())
+                    .is_some_and(|message| message == "-")
+                {
+                    options.stdin_readers.push("MESSAGE");
+                }
+                if let Some(path) = message_template {
+                    options.files.push(("--message-template", path.clone()));
+                }
+                if let Some(path) = message_file {
+                    options.files.push(("--message-file", path.clone()));
+                }
+                options.template = *template || message_template.is_some();
+                options.vars = !vars.is_empty();
+                options.password(password);
+                options.hmac_key(hmac_key);
+                if let Some(path) = sign_key {
+                    options.files.push(("--sign-key", path.clone()));
+                }
+                if let Some(path) = resume {
+                    options.files.push(("--resume", path.clone()));
+                }
+                if let Some(size) = split_size {
+                    options.sizes.push(("--split-size", size.clone()));
+                }
+                for size in size_warn {
+                    options.sizes.push(("--size-warn", size.clone()));
+                }
+                options.pair_replaced_twice = *replace
+                    && pairs.iter().enumerate().any(|(index, (name, _))| {
+                        pairs[..index].iter().any(|(other, _)| other == name)
+                    });
+                options.json_to_stdout = *format == OutputFormat::Json
+                    && output
+                        .as_ref()
+                        .or(output_flag.as_ref())
+                        .is_some_and(|output| output == Path::new("-"));
+            }
+            Commands::Decode {
+                files,
+                chunk,
+                output,
+                password,
+                hmac_key,
+                recursive,
+                ..
+            } => {
+                options.password(password);
+                options.hmac_key(hmac_key);
+                options.recursive(recursive);
+                let walk = recursive.recursive.is_some();
+                // Inputs clap can't split are reported by `decode_inputs`
+                if let Ok((files, name)) = decode_inputs(files, chunk, walk) {
+                    options.shared_output = output.is_some() && (files.len() > 1 || walk);
+                    let argument = if chunk.is_some() {
+                        "--chunk"
+                    } else {
+                        "CHUNK_NAME"
+                    };
+                    options.chunk_names.push((argument, name));
+                    for file in &files {
+                        options.input(file);
+                    }
+                }
+            }
+            Commands::Remove {
+                file,
+                chunk_name,
+                chunk,
+                output,
+                ..
+            } => {
+                if let Some(name) = chunk_name {
+                    options.chunk_names.push(("CHUNK_NAME", name.clone()));
+                }
+                if let Some(name) = chunk {
+                    options.chunk_names.push(("--chunk", name.clone()));
+                }
+                options.input(file);
+                options.output(file, output.as_ref());
+            }
+            Commands::Survivability {
+                file,
+                chunk_type,
+                output,
+                ..
+            } => {
+                options.chunk_names.push(("CHUNK_TYPE", chunk_type.clone()));
+                options.input(file);
+                options.output(file, output.as_ref());
+            }
+            Commands::Inject {
+                file,
+                icc,
+                chunk_spec,
+                output,
+                ..
+            } => {
+                options.input(file);
+                if let Some(icc) = icc {
+                    options.files.push(("--icc", icc.clone()));
+                }
+                for spec in chunk_spec {
+                    options.chunk_specs.push(("--chunk-spec", spec.clone()));
+                }
+                options.output(file, output.as_ref());
+            }
+            Commands::ImportMeta {
+                files,
+                sidecar,
+                select,
+                output,
+                ..
+            } => {
+                options.selection(select);
+                for file in files {
+                    options.input(file);
+                    options.output(file, output.as_ref());
+                }
+                options.files.push(("SIDECAR", sidecar.clone()));
+                options.shared_output = output.is_some() && files.len() > 1;
+                options.shared_patch = cli.emit_patch.is_some() && files.len() > 1;
+            }
+            Commands::ApplyPatch {
+                file,
+                patch,
+                output,
+                ..
+            } => {
+                options.input(file);
+                options.files.push(("PATCH", patch.clone()));
+                options.output(file, output.as_ref());
+            }
+            Commands::Strip {
+                file,
+                select,
+                output,
+                ..
+            } => {
+                options.input(file);
+                options.output(file, output.as_ref());
+                options.selection(select);
+            }
+            Commands::ExportMeta { file, select, .. } => {
+                options.input(file);
+                options.selection(select);
+            }
+            Commands::Fix { file, output, .. } | Commands::Canonicalize { file, output } => {
+                options.input(file);
+                options.output(file, output.as_ref());
+            }
+            Commands::Info { file } | Commands::Extract { file, .. } => options.input(file),
+            Commands::Print {
+                file, recursive, ..
+            } => {
+                if let Some(file) = file {
+                    options.input(file);
+                }
+                options.recursive(recursive);
+            }
+            Commands::Verify {
+                file,
+                chunk,
+                pubkey,
+                ..
+            } => {
+                options.input(file);
+                if let Some(name) = chunk {
+                    options.chunk_names.push(("CHUNK", name.clone()));
+                }
+                if let Some(path) = pubkey {
+                    options.files.push(("--pubkey", path.clone()));
+                }
+            }
+            Commands::Capacity {
+                file,
+                payload_size,
+                max_size,
+                ..
+            } => {
+                options.input(file);
+                if let Some(size) = payload_size {
+                    options.sizes.push(("--payload-size", size.clone()));
+                }
+                if let Some(size) = max_size {
+                    options.sizes.push(("--max-size", size.clone()));
+                }
+            }
+            Commands::Scan {
+                file,
+                max_private_size,
+                ..
+            } => {
+                options.input(file);
+                options
+                    .sizes
+                    .push(("--max-private-size", max_private_size.clone()));
+            }
+            Commands::Debug {
+                command:
+                    DebugCommands::Crc {
+                        chunk_type,
+                        data_file,
+                        ..
+                    },
+            } => {
+                options.chunk_names.push(("CHUNK_TYPE", chunk_type.clone()));
+                if let Some(path) = data_file {
+                    options.files.push(("--data-file", path.clone()));
+                }
+            }
+            #[cfg(feature = "server")]
+            Commands::Serve { max_body_size, .. } => {
+                options
+                    .sizes
+                    .push(("--max-body-size", max_body_size.clone()));
+            }
+            #[cfg(feature = "keyring")]
+            Commands::Key {
+                command: crate::args::KeyCommands::Store { password, .. },
+            } => options.password(password),
+            _ => {}
+        }
+
+        options
+    }
+
+    fn password(&mut self, password: &PasswordArgs) {
+        if let Some(path) = &password.password_file {
+            self.files.push(("--password-file", path.clone()));
+        }
+    }
+
+    fn hmac_key(&mut self, hmac_key: &HmacKeyArgs) {
+        if let Some(path) = &hmac_key.hmac_key_file {
+            self.files.push(("--hmac-key-file", path.clone()));
+        }
+    }
+
+    fn recursive(&mut self, recursive: &RecursiveArgs) {
+        if let Some(dir) = &recursive.recursive {
+            self.files.push(("--recursive", dir.clone()));
+        }
+        for pattern in &recursive.exclude {
+            self.globs.push(("--exclude", pattern.clone()));
+        }
+        // clap waives `requires` once FILE, which conflicts with
+        // --recursive, is given
+        if recursive.recursive.is_none() {
+            self.walk_flag_alone = match (recursive.follow_symlinks, recursive.exclude.is_empty()) {
+                (true, _) => Some("--follow-symlinks"),
+                (false, false) => Some("--exclude"),
+                (false, true) => None,
+            };
+        }
+    }
+
+    fn input(&mut self, file: &InputSource) {
+        match file {
+            InputSource::Path(path) => self.files.push(("FILE", path.clone())),
+            InputSource::Stdin => self.stdin_readers.push("FILE"),
+            #[cfg(feature = "archives")]
+            InputSource::Archive { archive, .. } => self.files.push(("FILE", archive.clone())),
+            _ => {}
+        }
+    }
+
+    fn output(&mut self, file: &InputSource, output: Option<&PathBuf>) {
+        self.output_elsewhere = match (file, output) {
+            (_, None) => false,
+            (InputSource::Path(path), Some(output)) => path != output,
+            (_, Some(_)) => true,
+        };
+    }
+
+    fn selection(&mut self, select: &Option<String>) {
+        if let Some(selection) = select {
+            self.selections.push(("--select", selection.clone()));
+        }
+    }
+}
+
+/// Every problem of `options`, in the order of the arguments. `exists`
+/// tells whether a file is there.
+pub fn validate(options: &ResolvedOptions, exists: &dyn Fn(&Path) -> bool) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    for (argument, name) in &options.chunk_names {
+        if let Err(error) = ChunkType::parse_name(name) {
+            problems.push(Problem::ChunkName { argument, error });
+        }
+    }
+
+    for (argument, path) in &options.files {
+        // `-` is the standard input or output
+        if path != Path::new("-") && !exists(path) {
+            problems.push(Problem::MissingFile {
+                argument,
+                path: path.clone(),
+            });
+        }
+    }
+
+    for (argument, spec) in &options.chunk_specs {
+        if let Err(error) = spec.parse::<Chunk>() {
+            problems.push(Problem::ChunkSpec { argument, error });
+        }
+    }
+
+    for (argument, selection) in &options.selections {
+        if let Err(error) = selection.parse::<Selection>() {
+            problems.push(Problem::Selection { argument, error });
+        }
+    }
+
+    for (argument, pattern) in &options.globs {
+        if let Err(error) = pattern.parse::<Pattern>() {
+            problems.push(Problem::Glob { argument, error });
+        }
+    }
+
+    for (argument, value) in &options.sizes {
+        if let Err(error) = parse_size(value) {
+            problems.push(Problem::Size {
+                argument,
+                value: value.clone(),
+                error,
+            });
+        }
+    }
+
+    if options.undoable && options.output_elsewhere {
+        problems.push(Problem::Conflict {
+            first: "--undoable",
+            second: "--output",
+            reason: "only files edited in place are saved for undo",
+        });
+    }
+
+    if let [first, second, ..] = options.stdin_readers[..] {
+        problems.push(Problem::Conflict {
+            first,
+            second,
+            reason: "the standard input can only be read once",
+        });
+    }
+
+    if options.shared_output {
+        problems.push(Problem::Conflict {
+            first: "--output",
+            second: "several files",
+            reason: "each message would overwrite the previous one",
+        });
+    }
+
+    if options.shared_patch {
+        problems.push(Problem::Conflict {
+            first: "--emit-patch",
+            second: "several files",
+            reason: "each patch would overwrite the previous one",
+        });
+    }
+
+    if let Some(flag) = options.walk_flag_alone {
+        problems.push(Problem::Conflict {
+            first: flag,
+            second: "FILE",
+            reason: "the flag only applies to the files of --recursive",
+        });
+    }
+
+    if options.pair_replaced_twice {
+        problems.push(Problem::Conflict {
+            first: "--replace",
+            second: "two --pair of the same chunk name",
+            reason: "both messages would replace the same chunk",
+        });
+    }
+
+    if options.json_to_stdout {
+        problems.push(Problem::Conflict {
+            first: "--format json",
+            second: "an output of -",
+            reason: "the standard output holds the image",
+        });
+    }
+
+    for (flag, chunk_name, chunk_type, second) in [
+        (
+            "--text-keyword",
+            &options.text_keyword_chunk,
+            "tEXt",
+            "a chunk name other than tEXt",
+        ),
+        (
+            "--itxt",
+            &options.itxt_chunk,
+            "iTXt",
+            "a chunk name other than iTXt",
+        ),
+        (
+            "--ztxt",
+            &options.ztxt_chunk,
+            "zTXt",
+            "a chunk name other than zTXt",
+        ),
+    ] {
+        if chunk_name.as_ref().is_some_and(|name| name != chunk_type) {
+            problems.push(Problem::Conflict {
+                first: flag,
+                second,
+                reason: "the flag writes the data layout of that standard chunk type",
+            });
+        }
+    }
+
+    if options.vars && !options.template {
+        problems.push(Problem::Conflict {
+            first: "--var",
+            second: "a message without --template",
+            reason: "placeholders are only filled with --template or --message-template",
+        });
+    }
+
+    problems
+}
+
+/// Code reported for `problems` as a whole: the missing file one when files
+/// are all that's missing, the invalid arguments one otherwise
+pub fn problems_code(problems: &[Problem]) -> Code {
+    if problems
+        .iter()
+        .all(|problem| matches!(problem, Problem::MissingFile { .. }))
+    {
+        Code::MissingArgumentFile
+    } else {
+        Code::InvalidArguments
+    }
+}
+
+/// Status to exit with for `problems`, the one of [`problems_code`]: not
+/// found when files are all that's missing, a usage error otherwise
+pub fn exit_status(problems: &[Problem]) -> ExitStatus {
+    ExitStatus::of(problems_code(problems))
+}
+
+/// The numbered list printed for `problems`
+pub fn format_problems(problems: &[Problem]) -> String {
+    let count = match problems.len() {
+        1 => "1 problem".to_string(),
+        count => format!("{count} problems"),
+    };
+    let mut report = format!(
+        "error[{}]: {count} with the arguments:\n",
+        problems_code(problems)
+    );
+
+    for (index, problem) in problems.iter().enumerate() {
+        let bullet = format!("  {}. ", index + 1);
+        // Continuation lines, e.g. the caret under a chunk name, are
+        // aligned with the first one
+        let message = problem
+            .to_string()
+            .replace('\n', &format!("\n{}", " ".repeat(bullet.len())));
+        report.push_str(&format!("{bullet}[{}] {message}\n", problem.code()));
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nothing_exists(_: &Path) -> bool {
+        false
+    }
+
+    #[test]
+    fn test_valid_options() {
+        let options = ResolvedOptions {
+            chunk_names: vec![("CHUNK_NAME", "ruSt".to_string())],
+            files: vec![("FILE", PathBuf::from("image.png"))],
+            sizes: vec![("--max-input-size", "10M".to_string())],
+            undoable: true,
+            template: true,
+            vars: true,
+            text_keyword_chunk: Some("tEXt".to_string()),
+            itxt_chunk: Some("iTXt".to_string()),
+            ztxt_chunk: Some("zTXt".to_string()),
+            ..ResolvedOptions::default()
+        };
+
+        assert_eq!(validate(&options, &|_| true), []);
+    }
+
+    #[test]
+    fn test_every_problem_is_reported() {
+        let options = ResolvedOptions {
+            chunk_names: vec![
+                ("CHUNK_NAME", "ru5t".to_string()),
+                ("--chunk", "toolong".to_string()),
+            ],
+            files: vec![
+                ("FILE", PathBuf::from("missing.png")),
+                ("--message-template", PathBuf::from("missing.txt")),
+                ("SIDECAR", PathBuf::from("-")),
+            ],
+            sizes: vec![("--max-input-size", "ten".to_string())],
+            chunk_specs: vec![("--chunk-spec", "noTe:0".to_string())],
+            selections: vec![("--select", "text,colour".to_string())],
+            globs: vec![("--glob", "assets/[ab.png".to_string())],
+            undoable: true,
+            output_elsewhere: true,
+            vars: true,
+            template: false,
+            stdin_readers: vec!["FILE", "MESSAGE"],
+            shared_output: true,
+            shared_patch: true,
+            json_to_stdout: true,
+            text_keyword_chunk: Some("ruSt".to_string()),
+            itxt_chunk: Some("tEXt".to_string()),
+            ztxt_chunk: Some("iTXt".to_string()),
+            walk_flag_alone: Some("--exclude"),
+            pair_replaced_twice: true,
+        };
+
+        let codes: Vec<Code> = validate(&options, &nothing_exists)
+            .iter()
+            .map(Problem::code)
+            .collect();
+
+        assert_eq!(
+            codes,
+            [
+                Code::InvalidNameCharacter,
+                Code::InvalidNameLength,
+                Code::MissingArgumentFile,
+                Code::MissingArgumentFile,
+                Code::ChunkSpecOddHex,
+                Code::SelectionUnknownTerm,
+                Code::GlobUnclosedClass,
+                Code::InvalidSize,
+                Code::ConflictingArguments,
+                Code::ConflictingArguments,
+                Code::ConflictingArguments,
+                Code::ConflictingArguments,
+                Code::ConflictingArguments,
+                Code::ConflictingArguments,
+                Code::ConflictingArguments,
+                Code::ConflictingArguments,
+                Code::ConflictingArguments,
+                Code::ConflictingArguments,
+                Code::ConflictingArguments,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undoable_in_place() {
+        let options = ResolvedOptions {
+            undoable: true,
+            ..ResolvedOptions::default()
+        };
+
+        assert_eq!(validate(&options, &nothing_exists), []);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("64K"), Ok(64 << 10));
+        assert_eq!(parse_size("10MiB"), Ok(10 << 20));
+        assert_eq!(parse_size("1g"), Ok(1 << 30));
+        assert_eq!(parse_size("M"), Err(SizeError::Syntax));
+        assert_eq!(parse_size("10 parsecs"), Err(SizeError::Syntax));
+        assert_eq!(parse_size("-1"), Err(SizeError::Syntax));
+        assert_eq!(parse_size("99999999999999999999"), Err(SizeError::Overflow));
+        assert_eq!(parse_size("17179869184G"), Err(SizeError::Overflow));
+    }
+
+    #[test]
+    fn test_problems_code_matches_the_status() {
+        let missing = || Problem::MissingFile {
+            argument: "FILE",
+            path: PathBuf::from("missing.png"),
+        };
+        let conflict = Problem::Conflict {
+            first: "--undoable",
+            second: "--output",
+            reason: "the file is not edited in place",
+        };
+
+        let problems = [missing()];
+        assert_eq!(problems_code(&problems), Code::MissingArgumentFile);
+        assert_eq!(exit_status(&problems), ExitStatus::NotFound);
+
+        let problems = [missing(), conflict];
+        assert_eq!(problems_code(&problems), Code::InvalidArguments);
+        assert_eq!(exit_status(&problems), ExitStatus::UsageError);
+    }
+
+    #[test]
+    fn test_format_problems() {
+        let problems = [
+            Problem::MissingFile {
+                argument: "FILE",
+                path: PathBuf::from("missing.png"),
+            },
+            Problem::ChunkName {
+                argument: "CHUNK_NAME",
+                error: ChunkType::parse_name("ru5t").unwrap_err(),
+            },
+        ];
+
+        assert_eq!(
+            format_problems(&problems),
+            "error[E1304]: 2 problems with the arguments:\n\
+             \x20 1. [E1301] FILE: missing.png does not exist\n\
+             \x20 2. [E0404] CHUNK_NAME: Chunk name 'ru5t' must only contain ASCII letters\n\
+             \x20      ru5t\n\
+             \x20        ^ '5' is not an ASCII letter\n"
+        );
+    }
+}
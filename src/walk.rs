@@ -0,0 +1,306 @@
+//! Lists the PNG files under a set of paths, for commands working on many
+//! files at once.
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::glob::Pattern;
+
+/// What a walk does with the symbolic links it finds in directories
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Links {
+    /// Links to files are listed, links to directories are not entered
+    #[default]
+    Files,
+    /// Links are left out
+    Skip,
+    /// Links are followed, each directory being entered once
+    Follow,
+}
+
+/// A path still to visit
+#[derive(Debug)]
+struct Pending {
+    path: PathBuf,
+    /// Index of the given path it was found under
+    root: usize,
+    /// Whether it was given rather than found in a directory
+    given: bool,
+}
+
+/// Iterator over the PNG files under some paths, see [`png_files`]
+#[derive(Debug)]
+pub struct PngFiles {
+    roots: Vec<PathBuf>,
+    /// Paths still to visit, the next one last
+    pending: Vec<Pending>,
+    recursive: bool,
+    links: Links,
+    /// Entries left out, matched against their path under the given one
+    exclude: Vec<Pattern>,
+    /// Directories entered while following links
+    visited: HashSet<PathBuf>,
+}
+
+/// Walks `paths` in order: files are yielded as given, directories are
+/// replaced by the `.png` files they hold, and with `recursive` by those
+/// of their subdirectories too.
+///
+/// Entries are visited in name order and symbolic links to directories are
+/// not followed, unless [`PngFiles::links`] says otherwise. Hidden
+/// directories found along the way, such as the undo states of
+/// [`crate::undo::UNDO_DIR`], are not entered, as with the `**` of a glob.
+/// Paths are read lazily, one directory at a time.
+pub fn png_files(paths: &[PathBuf], recursive: bool) -> PngFiles {
+    PngFiles {
+        roots: paths.to_vec(),
+        pending: paths
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(root, path)| Pending {
+                path: path.clone(),
+                root,
+                given: true,
+            })
+            .collect(),
+        recursive,
+        links: Links::Files,
+        exclude: Vec::new(),
+        visited: HashSet::new(),
+    }
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+}
+
+impl PngFiles {
+    pub fn links(mut self, links: Links) -> Self {
+        self.links = links;
+        self
+    }
+
+    /// Leaves out the files and directories found under a given path that
+    /// match one of `patterns`, see [`Pattern::matches`]. Given paths are
+    /// always visited
+    pub fn exclude(mut self, patterns: Vec<Pattern>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Queues the entries of `dir`
+    fn enter(&mut self, dir: &Path, root: usize) -> io::Result<()> {
+        let mut entries = Vec::new();
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let mut file_type = entry.file_type()?;
+            let path = entry.path();
+
+            if file_type.is_symlink() {
+                match self.links {
+                    Links::Files => {}
+                    Links::Skip => continue,
+                    // A broken link is listed, reading it reports the error
+                    Links::Follow => {
+                        if let Ok(metadata) = fs::metadata(&path) {
+                            file_type = metadata.file_type();
+                        }
+                    }
+                }
+            }
+            let relative = path.strip_prefix(&self.roots[root]).unwrap_or(&path);
+            if self.exclude.iter().any(|pattern| pattern.matches(relative)) {
+                continue;
+            }
+
+            if file_type.is_dir() {
+                if self.recursive && !entry.file_name().to_string_lossy().starts_with('.') {
+                    entries.push(path);
+                }
+            } else if is_png(&path) {
+                entries.push(path);
+            }
+        }
+
+        entries.sort();
+        self.pending
+            .extend(entries.into_iter().rev().map(|path| Pending {
+                path,
+                root,
+                given: false,
+            }));
+
+        Ok(())
+    }
+}
+
+impl Iterator for PngFiles {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Pending { path, root, given } = self.pending.pop()?;
+
+            // Only directories given as is and found by a recursive walk are
+            // entered, `symlink_metadata` doesn't follow links
+            let metadata = if given || self.links == Links::Follow {
+                fs::metadata(&path)
+            } else {
+                fs::symlink_metadata(&path)
+            };
+            match metadata {
+                Ok(metadata) if metadata.is_dir() => {
+                    // Links may lead back to a directory being walked
+                    if self.links == Links::Follow {
+                        match fs::canonicalize(&path) {
+                            Ok(canonical) => {
+                                if !self.visited.insert(canonical) {
+                                    continue;
+                                }
+                            }
+                            Err(err) => return Some(Err(err)),
+                        }
+                    }
+                    if let Err(err) = self.enter(&path, root) {
+                        return Some(Err(err));
+                    }
+                }
+                Ok(_) => return Some(Ok(path)),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for path in [
+            "b.png",
+            "a.PNG",
+            "notes.txt",
+            "sub/c.png",
+            "sub/deep/d.png",
+            ".pngme/undo/e.png",
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+        dir
+    }
+
+    fn names(dir: &Path, files: PngFiles) -> Vec<String> {
+        files
+            .map(|path| {
+                let path = path.unwrap();
+                let relative = path.strip_prefix(dir).unwrap_or(&path);
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_top_level_only() {
+        let dir = tree();
+        let files = png_files(&[dir.path().to_path_buf()], false);
+
+        assert_eq!(names(dir.path(), files), ["a.PNG", "b.png"]);
+    }
+
+    #[test]
+    fn test_recursive() {
+        let dir = tree();
+        let files = png_files(&[dir.path().to_path_buf()], true);
+
+        assert_eq!(
+            names(dir.path(), files),
+            ["a.PNG", "b.png", "sub/c.png", "sub/deep/d.png"]
+        );
+    }
+
+    #[test]
+    fn test_given_files_are_kept() {
+        let dir = tree();
+        let files = png_files(
+            &[dir.path().join("notes.txt"), dir.path().join("sub")],
+            false,
+        );
+
+        assert_eq!(names(dir.path(), files), ["notes.txt", "sub/c.png"]);
+    }
+
+    #[test]
+    fn test_hidden_directories_are_entered_only_when_given() {
+        let dir = tree();
+        let files = png_files(&[dir.path().join(".pngme/undo")], true);
+
+        assert_eq!(names(dir.path(), files), [".pngme/undo/e.png"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_links() {
+        let dir = tree();
+        let root = dir.path();
+        std::os::unix::fs::symlink(root.join("sub"), root.join("linked")).unwrap();
+        std::os::unix::fs::symlink(root.join("b.png"), root.join("e.png")).unwrap();
+        // A loop back to the top
+        std::os::unix::fs::symlink(root, root.join("sub/up")).unwrap();
+        let walk = |links| png_files(&[root.to_path_buf()], true).links(links);
+
+        assert_eq!(
+            names(root, walk(Links::Files)),
+            ["a.PNG", "b.png", "e.png", "sub/c.png", "sub/deep/d.png"]
+        );
+        assert_eq!(
+            names(root, walk(Links::Skip)),
+            ["a.PNG", "b.png", "sub/c.png", "sub/deep/d.png"]
+        );
+        assert_eq!(
+            names(root, walk(Links::Follow)),
+            [
+                "a.PNG",
+                "b.png",
+                "e.png",
+                "linked/c.png",
+                "linked/deep/d.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exclude() {
+        let dir = tree();
+        let exclude = |patterns: &[&str]| {
+            let patterns = patterns.iter().map(|pattern| pattern.parse().unwrap());
+            png_files(&[dir.path().to_path_buf()], true).exclude(patterns.collect())
+        };
+
+        assert_eq!(
+            names(dir.path(), exclude(&["deep"])),
+            ["a.PNG", "b.png", "sub/c.png"]
+        );
+        assert_eq!(
+            names(dir.path(), exclude(&["sub/*.png", "a.*"])),
+            ["b.png", "sub/deep/d.png"]
+        );
+    }
+
+    #[test]
+    fn test_missing_path() {
+        let mut files = png_files(&[PathBuf::from("/nonexistent/pngme")], true);
+
+        assert!(files.next().unwrap().is_err());
+        assert!(files.next().is_none());
+    }
+}
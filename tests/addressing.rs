@@ -0,0 +1,192 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn print_shows_absolute_indices() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["print".as_ref(), file.as_os_str()]);
+    let chunks = printed_chunks(&stdout(&output));
+
+    let types: Vec<&str> = chunks.iter().map(|(_, t)| t.as_str()).collect();
+    assert_eq!(
+        types,
+        ["IHDR", "teXt", "IDAT", "IDAT", "IDAT", "ruSt", "IEND"]
+    );
+    assert!(chunks.iter().enumerate().all(|(i, (index, _))| i == *index));
+}
+
+#[test]
+fn remove_at_index_picked_from_print() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["print".as_ref(), file.as_os_str()]);
+    let (index, _) = printed_chunks(&stdout(&output))
+        .into_iter()
+        .find(|(_, chunk_type)| chunk_type == "ruSt")
+        .expect("ruSt chunk should be printed");
+
+    let output = pngme([
+        "remove".as_ref(),
+        file.as_os_str(),
+        "--at".as_ref(),
+        index.to_string().as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(chunk_types(&file), ["IHDR", "teXt", "IDAT", "IDAT", "IDAT", "IEND"]);
+}
+
+#[test]
+fn collapsed_print_keeps_absolute_indices() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["print".as_ref(), file.as_os_str(), "--collapse".as_ref()]);
+    let printed = stdout(&output);
+
+    assert!(printed.contains("#2-4 { type: IDAT, chunks: 3, total length: 9 }"));
+    assert_eq!(
+        printed_chunks(&printed),
+        [
+            (0, "IHDR".to_string()),
+            (1, "teXt".to_string()),
+            (5, "ruSt".to_string()),
+            (6, "IEND".to_string()),
+        ]
+    );
+
+    // The index shown after a collapsed run still addresses the same chunk
+    pngme([
+        "remove".as_ref(),
+        file.as_os_str(),
+        "--at".as_ref(),
+        "5".as_ref(),
+    ]);
+    let output = pngme(["print".as_ref(), file.as_os_str()]);
+    assert!(!stdout(&output).contains("ruSt"));
+}
+
+#[test]
+fn remove_at_out_of_bounds_leaves_file_untouched() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "remove".as_ref(),
+        file.as_os_str(),
+        "--at".as_ref(),
+        "7".as_ref(),
+    ]);
+
+    assert!(stderr(&output).contains("No chunk at index 7 (the file has 7 chunks)"));
+    assert_eq!(std::fs::read(&file).unwrap(), fixture_png());
+}
+
+#[test]
+fn remove_rejects_both_name_and_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "remove".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+        "--at".as_ref(),
+        "5".as_ref(),
+    ]);
+
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read(&file).unwrap(), fixture_png());
+}
+
+/// The data `extract --index` writes to stdout
+fn extracted(file: &std::path::Path, index: usize) -> Vec<u8> {
+    let output = pngme([
+        "extract".as_ref(),
+        file.as_os_str(),
+        "--index".as_ref(),
+        index.to_string().as_ref(),
+        "--output".as_ref(),
+        "-".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    output.stdout
+}
+
+#[test]
+fn extract_index_picked_from_print() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["print".as_ref(), file.as_os_str()]);
+    let (index, _) = printed_chunks(&stdout(&output))
+        .into_iter()
+        .find(|(_, chunk_type)| chunk_type == "ruSt")
+        .expect("ruSt chunk should be printed");
+
+    assert_eq!(extracted(&file, index), b"hidden message");
+
+    let out = dir.path().join("chunk.bin");
+    let output = pngme([
+        "extract".as_ref(),
+        file.as_os_str(),
+        "--index".as_ref(),
+        "1".as_ref(),
+        "--output".as_ref(),
+        out.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(std::fs::read(&out).unwrap(), b"first ancillary");
+}
+
+#[test]
+fn print_extract_and_remove_agree_inside_a_collapsed_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["print".as_ref(), file.as_os_str(), "--collapse".as_ref()]);
+    assert!(stdout(&output).contains("#2-4 { type: IDAT, chunks: 3, total length: 9 }"));
+
+    // Every index of the run addresses one IDAT chunk, in file order
+    assert_eq!(extracted(&file, 2), [0x78, 0x9c]);
+    assert_eq!(extracted(&file, 3), [0x62, 0x00, 0x01]);
+    assert_eq!(extracted(&file, 4), [0x00, 0x00, 0xff, 0xff]);
+
+    let output = pngme([
+        "remove".as_ref(),
+        file.as_os_str(),
+        "--at".as_ref(),
+        "3".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // The middle chunk went, the ones after it moved up by one
+    let output = pngme(["print".as_ref(), file.as_os_str(), "--collapse".as_ref()]);
+    assert!(stdout(&output).contains("#2-3 { type: IDAT, chunks: 2, total length: 6 }"));
+    assert_eq!(extracted(&file, 2), [0x78, 0x9c]);
+    assert_eq!(extracted(&file, 3), [0x00, 0x00, 0xff, 0xff]);
+    assert_eq!(extracted(&file, 4), b"hidden message");
+}
+
+#[test]
+fn extract_index_out_of_bounds() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "extract".as_ref(),
+        file.as_os_str(),
+        "--index".as_ref(),
+        "7".as_ref(),
+        "--output".as_ref(),
+        "-".as_ref(),
+    ]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("No chunk at index 7 (the file has 7 chunks)"));
+    assert!(output.stdout.is_empty());
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::*;
+use pngme::{fixtures::FixtureKind, png::Png};
+
+fn sequence_numbers(file: &std::path::Path) -> Vec<u32> {
+    let png = Png::try_from(std::fs::read(file).unwrap().as_slice()).unwrap();
+    png.chunks()
+        .iter()
+        .filter(|chunk| matches!(&chunk.chunk_type().bytes(), b"fcTL" | b"fdAT"))
+        .map(|chunk| u32::from_be_bytes(chunk.data()[..4].try_into().unwrap()))
+        .collect()
+}
+
+#[test]
+fn chunk_inserted_mid_animation_passes_the_sequence_check() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = fixture(dir.path(), FixtureKind::Apng);
+    let file = file.to_str().unwrap();
+
+    // Between the second fcTL and its fdAT
+    let output = pngme(["encode", file, "ruSt", "hello", "--position", "5"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("between the frames"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = pngme(["verify", file]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(sequence_numbers(file.as_ref()), [0, 1, 2]);
+}
+
+#[test]
+fn removed_frame_chunk_is_renumbered() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = fixture(dir.path(), FixtureKind::Apng);
+    let file = file.to_str().unwrap();
+
+    // The second fcTL
+    let output = pngme(["remove", file, "--at", "4"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Renumbered 1 animation chunk(s)"));
+
+    assert_eq!(sequence_numbers(file.as_ref()), [0, 1]);
+    assert!(pngme(["verify", file]).status.success());
+}
+
+#[test]
+fn verify_reports_and_fix_closes_a_sequence_gap() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut png =
+        Png::try_from(pngme::fixtures::make_fixture(FixtureKind::Apng).as_slice()).unwrap();
+    png.remove_chunk_at(4).unwrap();
+    let file = write_fixture(dir.path(), "gap.png", &png.as_bytes());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["verify", file]);
+    assert_eq!(output.status.code(), Some(4));
+    let report = stdout(&output);
+    assert!(report.contains("W0208 animation-sequence"), "{report}");
+    assert!(
+        report.contains("has sequence number 2, expected 1"),
+        "{report}"
+    );
+
+    let output = pngme(["fix", file]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Fixed 1 problem(s)"));
+    assert!(pngme(["verify", file]).status.success());
+}
@@ -0,0 +1,159 @@
+#![cfg(feature = "archives")]
+
+mod common;
+
+use std::{
+    fs,
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+use common::*;
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+/// A zip holding the fixture as `sprites/hero.png` and a text file
+fn zip_bytes(method: CompressionMethod) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(method);
+
+    zip.start_file("README.txt", options).unwrap();
+    zip.write_all(b"sprites").unwrap();
+    zip.start_file("sprites/hero.png", options).unwrap();
+    zip.write_all(&fixture_png()).unwrap();
+
+    zip.finish().unwrap().into_inner()
+}
+
+fn write_zip(dir: &Path) -> PathBuf {
+    write_fixture(dir, "bundle.zip", &zip_bytes(CompressionMethod::Deflated))
+}
+
+fn member(archive: &Path, member: &str) -> String {
+    format!("{}!{member}", archive.display())
+}
+
+#[test]
+fn print_and_decode_read_zip_members() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = write_zip(dir.path());
+    let hero = member(&archive, "sprites/hero.png");
+
+    let output = pngme(["print", &hero]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("ruSt"), "{}", stdout(&output));
+
+    let output = pngme(["decode", "--quiet", &hero, "ruSt"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "hidden message\n");
+}
+
+#[test]
+fn archive_member_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = write_zip(dir.path());
+
+    let output = pngme([
+        "info",
+        archive.to_str().unwrap(),
+        "--archive-member",
+        "sprites/hero.png",
+    ]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn tar_gz_members_are_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = fixture_png();
+    let mut header = tar::Header::new_gnu();
+    header.set_size(png.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    let gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    let mut tar = tar::Builder::new(gzip);
+    tar.append_data(&mut header, "sprites/hero.png", png.as_slice())
+        .unwrap();
+    let bytes = tar.into_inner().unwrap().finish().unwrap();
+    let archive = write_fixture(dir.path(), "bundle.tar.gz", &bytes);
+
+    let output = pngme([
+        "decode",
+        "--quiet",
+        &member(&archive, "sprites/hero.png"),
+        "ruSt",
+    ]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "hidden message\n");
+}
+
+#[test]
+fn missing_member_lists_near_matches() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = write_zip(dir.path());
+
+    let output = pngme(["print", &member(&archive, "sprite/Hero.png")]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("error[E0611]"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(
+        stderr(&output).contains("did you mean sprites/hero.png?"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn encrypted_members_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut bytes = zip_bytes(CompressionMethod::Stored);
+    // Set the encryption flag in the local and central headers
+    for (signature, flags) in [(b"PK\x03\x04", 6), (b"PK\x01\x02", 8)] {
+        let mut start = 0;
+        while let Some(at) = bytes[start..]
+            .windows(4)
+            .position(|window| window == signature)
+        {
+            bytes[start + at + flags] |= 1;
+            start += at + 4;
+        }
+    }
+    let archive = write_fixture(dir.path(), "bundle.zip", &bytes);
+
+    let output = pngme(["print", &member(&archive, "sprites/hero.png")]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("error[E0612]"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn members_are_never_written_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = write_zip(dir.path());
+    let before = fs::read(&archive).unwrap();
+    let hero = member(&archive, "sprites/hero.png");
+
+    let output = pngme(["encode", &hero, "abCd", "hello"]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("error[E0511]"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(fs::read(&archive).unwrap(), before);
+
+    let copy = dir.path().join("hero.png");
+    let output = pngme(["encode", &hero, "abCd", "hello", copy.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read(&archive).unwrap(), before);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+use pngme::fixtures::{FixtureKind, make_fixture};
+
+#[test]
+fn backup_is_the_original_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["--backup", "encode", file, "abCd", "hello"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(
+        fs::read(dir.path().join("image.png.bak")).unwrap(),
+        fixture_png()
+    );
+    assert_ne!(fs::read(file).unwrap(), fixture_png());
+}
+
+#[test]
+fn existing_backup_is_kept_without_force() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let backup = write_fixture(dir.path(), "image.png.orig", b"older backup");
+
+    let output = pngme(["remove", file, "ruSt", "--backup=.orig"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("error[E1008]"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(stderr(&output).contains("--force"));
+    assert_eq!(fs::read(&backup).unwrap(), b"older backup");
+    assert_eq!(fs::read(file).unwrap(), fixture_png());
+
+    let output = pngme(["remove", file, "ruSt", "--backup=.orig", "--force"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read(&backup).unwrap(), fixture_png());
+}
+
+#[test]
+fn backup_is_taken_before_parsing() {
+    let dir = tempfile::tempdir().unwrap();
+    let corrupt = make_fixture(FixtureKind::CorruptCrc);
+    let file = write_fixture(dir.path(), "image.png", &corrupt);
+
+    let output = pngme(["--backup", "encode", file.to_str().unwrap(), "abCd", "hi"]);
+    assert!(!output.status.success());
+
+    assert_eq!(fs::read(dir.path().join("image.png.bak")).unwrap(), corrupt);
+}
+
+#[test]
+fn other_outputs_are_not_backed_up() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let out = dir.path().join("out.png");
+
+    let output = pngme([
+        "--backup",
+        "encode",
+        file.to_str().unwrap(),
+        "abCd",
+        "hello",
+        out.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert!(!dir.path().join("image.png.bak").exists());
+    assert!(!dir.path().join("out.png.bak").exists());
+}
+
+#[test]
+fn force_needs_backup_and_suffix_is_not_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    assert_eq!(
+        pngme(["remove", file, "ruSt", "--force"]).status.code(),
+        Some(2)
+    );
+    assert_eq!(
+        pngme(["remove", file, "ruSt", "--backup="]).status.code(),
+        Some(2)
+    );
+    assert_eq!(fs::read(file).unwrap(), fixture_png());
+}
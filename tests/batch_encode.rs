@@ -0,0 +1,204 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+use serde_json::Value;
+
+/// Two valid images under `dir/assets`, one in a subdirectory, and a
+/// broken one in between
+fn assets(dir: &Path) -> String {
+    let assets = dir.join("assets");
+    fs::create_dir_all(assets.join("icons")).unwrap();
+    write_fixture(&assets, "a.png", &fixture_png());
+    write_fixture(&assets, "b.png", b"not a png");
+    write_fixture(&assets.join("icons"), "c.png", &fixture_png());
+    write_fixture(&assets, "notes.txt", b"notes");
+    assets.to_str().unwrap().to_string()
+}
+
+fn decoded(file: &str) -> String {
+    stdout(&pngme(["decode", "--quiet", file, "abCd"]))
+}
+
+#[test]
+fn encodes_every_matching_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let assets = assets(dir.path());
+    fs::remove_file(format!("{assets}/b.png")).unwrap();
+
+    let output = pngme([
+        "encode",
+        "--glob",
+        &format!("{assets}/**/*.png"),
+        "--chunk",
+        "abCd",
+        "--message",
+        "watermark",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        format!("{assets}/a.png: encoded\n{assets}/icons/c.png: encoded\nEncoded 2 of 2 file(s)\n")
+    );
+    assert_eq!(decoded(&format!("{assets}/a.png")), "watermark\n");
+    assert_eq!(decoded(&format!("{assets}/icons/c.png")), "watermark\n");
+    assert_eq!(fs::read(format!("{assets}/notes.txt")).unwrap(), b"notes");
+}
+
+#[test]
+fn a_failure_does_not_stop_the_batch() {
+    let dir = tempfile::tempdir().unwrap();
+    let assets = assets(dir.path());
+
+    let output = pngme([
+        "encode",
+        "--glob",
+        &format!("{assets}/*.png"),
+        &format!("{assets}/icons/c.png"),
+        "--chunk",
+        "abCd",
+        "--message",
+        "watermark",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    let printed = stdout(&output);
+    assert!(
+        printed.contains("/b.png: failed, error[E0101]"),
+        "{printed}"
+    );
+    assert!(
+        printed.ends_with("Encoded 2 of 3 file(s), 1 failed\n"),
+        "{printed}"
+    );
+    assert!(
+        stderr(&output).contains(
+            "error[E0529]: Could not encode message into the file: 1 of 3 file(s) failed"
+        ),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(decoded(&format!("{assets}/icons/c.png")), "watermark\n");
+}
+
+#[test]
+fn fail_fast_skips_the_rest() {
+    let dir = tempfile::tempdir().unwrap();
+    let assets = assets(dir.path());
+
+    let output = pngme([
+        "encode",
+        "--glob",
+        &format!("{assets}/**/*.png"),
+        "--chunk",
+        "abCd",
+        "--message",
+        "watermark",
+        "--fail-fast",
+        "--format",
+        "json",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    let report: Value =
+        serde_json::from_slice(output.stdout.split(|&byte| byte == b'\n').nth(1).unwrap()).unwrap();
+    assert_eq!(report["encoded"], 1);
+    assert_eq!(report["failed"], 1);
+    assert_eq!(report["skipped"], 1);
+    assert_eq!(report["files"][1]["error"]["code"], "E0101");
+    assert_eq!(report["files"][2]["encoded"], Value::Null);
+
+    let printed = stdout(&pngme(["print", &format!("{assets}/icons/c.png")]));
+    assert!(!printed.contains("abCd"), "{printed}");
+}
+
+#[test]
+fn patterns_are_checked() {
+    let dir = tempfile::tempdir().unwrap();
+    let assets = assets(dir.path());
+    let encode = |pattern: &str| {
+        pngme([
+            "encode",
+            "--glob",
+            pattern,
+            "--chunk",
+            "abCd",
+            "--message",
+            "hi",
+        ])
+    };
+
+    let output = encode(&format!("{assets}/[ab.png"));
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("E1308"), "{}", stderr(&output));
+
+    let output = encode(&format!("{assets}/*.gif"));
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("E1309"), "{}", stderr(&output));
+
+    // FILE and --glob, or --glob and an output, don't go together
+    let file = format!("{assets}/a.png");
+    let output = pngme(["encode", &file, "abCd", "hi", "--glob", &file]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = pngme([
+        "encode",
+        "--glob",
+        &file,
+        "--chunk",
+        "abCd",
+        "--message",
+        "hi",
+        "--output",
+        "out.png",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn a_transactional_batch_changes_nothing_when_a_file_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let assets = assets(dir.path());
+    let pattern = format!("{assets}/**/*.png");
+    let encode = |extra: &[&str]| {
+        let mut args = vec![
+            "--transactional",
+            "encode",
+            "--glob",
+            &pattern,
+            "--chunk",
+            "abCd",
+            "--message",
+            "watermark",
+        ];
+        args.extend(extra);
+        pngme(args)
+    };
+
+    let output = encode(&[]);
+    assert_eq!(output.status.code(), Some(1));
+    let printed = stdout(&output);
+    assert!(
+        printed.contains(&format!("{assets}/a.png: rolled back")),
+        "{printed}"
+    );
+    assert!(
+        stderr(&output).contains("Transaction aborted, 1 of 3 file(s) failed, no file was changed"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(fs::read(format!("{assets}/a.png")).unwrap(), fixture_png());
+    assert_eq!(
+        fs::read(format!("{assets}/icons/c.png")).unwrap(),
+        fixture_png()
+    );
+    // No staged temporary file is left behind
+    assert_eq!(fs::read_dir(&assets).unwrap().count(), 4);
+
+    fs::remove_file(format!("{assets}/b.png")).unwrap();
+    let output = encode(&["--format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    // The reports of the two files come first
+    let report: Value = serde_json::from_str(stdout(&output).lines().nth(2).unwrap()).unwrap();
+    assert_eq!(report["committed"], true);
+    assert_eq!(decoded(&format!("{assets}/a.png")), "watermark\n");
+    assert_eq!(decoded(&format!("{assets}/icons/c.png")), "watermark\n");
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+/// The start of a JPEG: SOI, a JFIF APP0 segment and EOI, with bytes that
+/// are not UTF-8
+const JPEG: &[u8] = b"\xFF\xD8\xFF\xE0\x00\x10JFIF\x00\x01\x01\x00\x00\x01\x00\x01\x00\x00\xFF\xD9";
+
+#[test]
+fn binary_files_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let photo = write_fixture(dir.path(), "photo.jpg", JPEG);
+    let restored = dir.path().join("restored.jpg");
+
+    let output = pngme([
+        "encode",
+        file,
+        "jpEg",
+        "--input-file",
+        photo.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme([
+        "decode",
+        file,
+        "jpEg",
+        "--output",
+        restored.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"");
+
+    assert_eq!(fs::read(restored).unwrap(), JPEG);
+}
+
+#[test]
+fn output_dash_writes_to_stdout() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let photo = write_fixture(dir.path(), "photo.jpg", JPEG);
+    let output = pngme([
+        "encode",
+        file,
+        "jpEg",
+        "--message-file",
+        photo.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["decode", file, "jpEg", "--output", "-"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(output.stdout, JPEG);
+}
+
+#[test]
+fn one_output_for_several_files_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = write_fixture(dir.path(), "first.png", &fixture_png());
+    let second = write_fixture(dir.path(), "second.png", &fixture_png());
+
+    let output = pngme([
+        "decode",
+        first.to_str().unwrap(),
+        second.to_str().unwrap(),
+        "ruSt",
+        "--output",
+        "message.bin",
+    ]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("--output can't be combined with several files"),
+        "{}",
+        stderr(&output)
+    );
+}
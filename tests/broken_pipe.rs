@@ -0,0 +1,126 @@
+mod common;
+
+use std::{
+    ffi::OsStr,
+    io::{self, ErrorKind, Write},
+    process::{Command, Output, Stdio},
+};
+
+use common::*;
+use pngme::{commands::print_chunks, error::PngMeError, interpret::Registry, pipe, png::Png};
+
+/// Accepts `remaining` bytes, then fails like a pipe whose reader exited
+struct ClosingPipe {
+    remaining: usize,
+}
+
+impl Write for ClosingPipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Err(io::Error::from(ErrorKind::BrokenPipe));
+        }
+        let written = buf.len().min(self.remaining);
+        self.remaining -= written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An image with enough chunks to print well past a pipe buffer
+fn many_chunks() -> Vec<u8> {
+    let mut chunks: Vec<(&str, &[u8])> = vec![("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0])];
+    chunks.extend(std::iter::repeat_n(("teXt", &b"some text"[..]), 20_000));
+    chunks.push(("IEND", &[]));
+
+    png_bytes(&chunks)
+}
+
+#[test]
+fn listing_stops_at_the_broken_pipe() {
+    let png = Png::try_from(many_chunks().as_slice()).unwrap();
+
+    let err = print_chunks(
+        &png,
+        false,
+        0,
+        Registry::builtin(),
+        &mut ClosingPipe { remaining: 100 },
+    )
+    .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+    assert!(pipe::is_closed_output(&PngMeError::from(err)));
+    assert!(!pipe::is_closed_output(&PngMeError::from(io::Error::from(
+        ErrorKind::PermissionDenied
+    ))));
+}
+
+/// Runs pngme with `args`, its stdout closed before it writes anything
+fn with_closed_stdout<I, S>(args: I) -> Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The reader goes away before pngme is done, like `| head -5`
+    drop(child.stdout.take());
+
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn closed_stdout_ends_quietly() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &many_chunks());
+
+    for command in ["print", "scan", "info", "capacity"] {
+        let output = with_closed_stdout([command.as_ref(), file.as_os_str()]);
+        assert_eq!(output.status.code(), Some(0), "{command}");
+        assert_eq!(stderr(&output), "", "{command}");
+    }
+}
+
+#[test]
+fn closed_stdout_ends_short_reports_quietly() {
+    for args in [
+        &["capabilities"][..],
+        &["capabilities", "--format", "json"],
+        &["version"],
+        &["--version", "--verbose"],
+        &["explain"],
+        &["debug", "crc", "RuSt", "--data-hex", "00"],
+    ] {
+        let output = with_closed_stdout(args);
+        assert_eq!(output.status.code(), Some(0), "{args:?}");
+        assert_eq!(stderr(&output), "", "{args:?}");
+    }
+}
+
+#[test]
+fn closed_stdout_ends_compare_quietly() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        ("maNi", b"v1.2.0"),
+        ("IEND", &[]),
+    ]);
+    let files = ["a.png", "b.png"].map(|name| write_fixture(dir.path(), name, &manifest));
+
+    let output = with_closed_stdout([
+        "decode".as_ref(),
+        files[0].as_os_str(),
+        files[1].as_os_str(),
+        "maNi".as_ref(),
+        "--compare".as_ref(),
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stderr(&output), "");
+}
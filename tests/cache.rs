@@ -0,0 +1,120 @@
+mod common;
+
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    path::Path,
+    sync::mpsc,
+    thread,
+};
+
+use common::*;
+
+/// Serves `fixture_png` with an ETag on localhost for `requests` requests,
+/// answering 304 to those carrying it. The receiver gets the
+/// `If-None-Match` header of each request.
+fn serve(requests: usize) -> (String, mpsc::Receiver<Option<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for _ in 0..requests {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut byte = [0u8];
+            while !request.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+                request.push(byte[0]);
+            }
+            let if_none_match = String::from_utf8(request)
+                .unwrap()
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("if-none-match")
+                        .then(|| value.trim().to_string())
+                });
+
+            let response = match if_none_match.as_deref() {
+                Some("\"v1\"") => {
+                    b"HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n"
+                        .to_vec()
+                }
+                _ => {
+                    let body = fixture_png();
+                    let mut response = format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(&body);
+                    response
+                }
+            };
+            let _ = sender.send(if_none_match);
+            stream.write_all(&response).unwrap();
+        }
+    });
+
+    (format!("http://{addr}/image.png"), receiver)
+}
+
+fn decode(url: &str, cache_dir: &Path, extra: &[&str]) -> std::process::Output {
+    let mut args = vec![
+        "--cache",
+        "--cache-dir",
+        cache_dir.to_str().unwrap(),
+        "decode",
+        url,
+        "ruSt",
+    ];
+    args.extend_from_slice(extra);
+    pngme(args)
+}
+
+#[test]
+fn second_download_is_revalidated_and_reused() {
+    let dir = tempfile::tempdir().unwrap();
+    let (url, requests) = serve(2);
+
+    for _ in 0..2 {
+        let output = decode(&url, dir.path(), &[]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(stdout(&output).contains("hidden message"));
+    }
+
+    let requests: Vec<_> = requests.try_iter().collect();
+    assert_eq!(requests, [None, Some("\"v1\"".to_string())]);
+}
+
+#[test]
+fn stale_entries_and_no_cache_download_unconditionally() {
+    let dir = tempfile::tempdir().unwrap();
+    let (url, requests) = serve(3);
+
+    assert!(decode(&url, dir.path(), &[]).status.success());
+    let output = decode(&url, dir.path(), &["--cache-max-age", "0s"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = decode(&url, dir.path(), &["--no-cache"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let requests: Vec<_> = requests.try_iter().collect();
+    assert_eq!(requests, [None, None, None]);
+}
+
+#[test]
+fn cache_clear_empties_the_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let (url, _) = serve(1);
+    assert!(decode(&url, dir.path(), &[]).status.success());
+
+    let output = pngme([
+        "cache",
+        "clear",
+        "--cache-dir",
+        dir.path().to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Removed 1 cached download(s)"));
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+use pngme::{
+    fixtures::{FixtureKind, make_fixture},
+    png::Png,
+};
+
+/// The `(type, data)` chunks of the minimal fixture, a real 1x1 image
+fn minimal_chunks() -> Vec<(String, Vec<u8>)> {
+    Png::try_from(make_fixture(FixtureKind::Minimal).as_slice())
+        .unwrap()
+        .chunks()
+        .iter()
+        .map(|chunk| (chunk.chunk_type().to_string(), chunk.data().to_vec()))
+        .collect()
+}
+
+/// A 1x1 RGBA image of the given pixel
+fn encode_pixel(pixel: [u8; 4]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut encoder = ::png::Encoder::new(&mut bytes, 1, 1);
+    encoder.set_color(::png::ColorType::Rgba);
+    encoder.set_depth(::png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&pixel).unwrap();
+    writer.finish().unwrap();
+    bytes
+}
+
+fn pixels(bytes: &[u8]) -> Vec<u8> {
+    let mut reader = ::png::Decoder::new(std::io::Cursor::new(bytes))
+        .read_info()
+        .unwrap();
+    let mut buffer = vec![0; reader.output_buffer_size().unwrap()];
+    let info = reader.next_frame(&mut buffer).unwrap();
+    buffer.truncate(info.buffer_size());
+    buffer
+}
+
+fn canonicalize(dir: &std::path::Path, name: &str, bytes: &[u8]) -> Vec<u8> {
+    let input = write_fixture(dir, name, bytes);
+    let output = dir.join(format!("canonical-{name}"));
+
+    let result = pngme([
+        "canonicalize",
+        input.to_str().unwrap(),
+        "--output",
+        output.to_str().unwrap(),
+    ]);
+    assert!(result.status.success(), "{}", stderr(&result));
+
+    fs::read(output).unwrap()
+}
+
+#[test]
+fn equivalent_files_canonicalize_to_the_same_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let chunks = minimal_chunks();
+    let (ihdr, idat) = (&chunks[0].1, &chunks[1].1);
+    let (head, tail) = idat.split_at(idat.len() / 2);
+
+    let first = png_bytes(&[
+        ("IHDR", ihdr),
+        ("tEXt", b"Title\0Sunset"),
+        ("pHYs", &[0, 0, 11, 19, 0, 0, 11, 19, 1]),
+        ("IDAT", head),
+        ("IDAT", tail),
+        ("tEXt", b"Author\0Ann"),
+        ("ruSt", b"hidden message"),
+        ("IEND", &[]),
+    ]);
+    let second = png_bytes(&[
+        ("IHDR", ihdr),
+        ("pHYs", &[0, 0, 11, 19, 0, 0, 11, 19, 1]),
+        ("tEXt", b"Author\0Ann"),
+        ("ruSt", b"hidden message"),
+        ("IDAT", idat),
+        ("tEXt", b"Title\0Sunset"),
+        ("IEND", &[]),
+    ]);
+
+    let first_canonical = canonicalize(dir.path(), "first.png", &first);
+    let second_canonical = canonicalize(dir.path(), "second.png", &second);
+
+    assert_ne!(first, second);
+    assert_eq!(first_canonical, second_canonical);
+    assert_eq!(pixels(&first_canonical), pixels(&first));
+
+    assert_eq!(
+        chunk_types(dir.path().join("canonical-first.png")),
+        ["IHDR", "pHYs", "IDAT", "ruSt", "tEXt", "tEXt", "IEND"]
+    );
+}
+
+#[test]
+fn different_pixels_do_not_collide() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let first = canonicalize(dir.path(), "first.png", &encode_pixel([255, 0, 0, 255]));
+    let second = canonicalize(dir.path(), "second.png", &encode_pixel([255, 0, 1, 255]));
+
+    assert_ne!(first, second);
+    assert_eq!(pixels(&second), [255, 0, 1, 255]);
+}
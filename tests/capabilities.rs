@@ -0,0 +1,124 @@
+mod common;
+
+use common::*;
+use serde_json::Value;
+
+fn capabilities() -> Value {
+    let output = pngme(["capabilities", "--format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+fn names(values: &Value) -> Vec<&str> {
+    values
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|value| value.as_str().unwrap())
+        .collect()
+}
+
+fn command<'a>(capabilities: &'a Value, name: &str) -> Option<&'a Value> {
+    capabilities["commands"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|command| command["name"] == name)
+}
+
+#[test]
+fn features_match_the_build() {
+    let capabilities = capabilities();
+    let features = names(&capabilities["features"]);
+
+    assert_eq!(capabilities["version"], env!("CARGO_PKG_VERSION"));
+    for (feature, enabled) in [
+        ("archives", cfg!(feature = "archives")),
+        ("keyring", cfg!(feature = "keyring")),
+        ("rayon", cfg!(feature = "rayon")),
+        ("server", cfg!(feature = "server")),
+    ] {
+        assert_eq!(features.contains(&feature), enabled, "{feature}");
+    }
+
+    assert_eq!(
+        command(&capabilities, "serve").is_some(),
+        cfg!(feature = "server")
+    );
+    assert_eq!(
+        command(&capabilities, "key").is_some(),
+        cfg!(feature = "keyring")
+    );
+    assert_eq!(
+        names(&capabilities["input_schemes"]).contains(&"archive"),
+        cfg!(feature = "archives")
+    );
+    assert_eq!(capabilities["envelope_versions"], serde_json::json!([1]));
+}
+
+#[test]
+fn exit_statuses_are_listed() {
+    let capabilities = capabilities();
+    let statuses = capabilities["exit_statuses"].as_array().unwrap();
+
+    assert_eq!(statuses.len(), 7);
+    assert_eq!(
+        statuses[3],
+        serde_json::json!({"code": 3, "name": "not-found"})
+    );
+    assert_eq!(
+        statuses[5],
+        serde_json::json!({"code": 5, "name": "integrity-failed"})
+    );
+}
+
+#[test]
+fn codes_map_to_their_exit_status() {
+    let capabilities = capabilities();
+    let codes = capabilities["codes"].as_array().unwrap();
+    let code = |code: &str| codes.iter().find(|info| info["code"] == code).unwrap();
+
+    assert_eq!(
+        *code("E0301"),
+        serde_json::json!({
+            "code": "E0301",
+            "name": "ChunkNotFound",
+            "exit_status": 3,
+            "status": "not-found",
+        })
+    );
+    assert_eq!(code("E1304")["exit_status"], 2);
+    assert_eq!(code("E0518")["status"], "storage-full");
+}
+
+#[test]
+fn commands_list_their_flags() {
+    let capabilities = capabilities();
+
+    let encode = command(&capabilities, "encode").unwrap();
+    let message_file = encode["flags"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|flag| flag["long"] == "message-file")
+        .unwrap();
+    assert_eq!(message_file["aliases"], serde_json::json!(["input-file"]));
+    assert_eq!(message_file["takes_value"], true);
+    assert_eq!(names(&encode["arguments"])[0], "file");
+
+    let globals = capabilities["global_flags"].as_array().unwrap();
+    assert!(globals.iter().any(|flag| flag["long"] == "max-chunks"));
+
+    // Hidden commands are left out
+    assert!(command(&capabilities, "debug").is_none());
+}
+
+#[test]
+fn human_output_lists_the_commands() {
+    let output = pngme(["capabilities"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(stdout.starts_with(&format!("pngme {}\n", env!("CARGO_PKG_VERSION"))));
+    assert!(stdout.contains("\n  verify\n"), "{stdout}");
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::*;
+use serde_json::Value;
+
+#[test]
+fn reports_the_image() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["capacity", file.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "Size: {} bytes in 7 chunk(s)\nLargest chunk data: 2147483647 bytes\n",
+            fixture_png().len()
+        )
+    );
+}
+
+#[test]
+fn projects_the_size_with_a_payload() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let size = fixture_png().len() as u64;
+
+    let output = pngme(["capacity", file, "--payload-size", "1K", "--format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["size"], size);
+    assert_eq!(report["payload"]["chunks"], 1);
+    assert_eq!(report["payload"]["split"], false);
+    assert_eq!(report["projected_size"], size + 1024 + 12);
+
+    // Encoding the payload gives the projected size
+    let message = "x".repeat(1024);
+    let output = pngme(["encode", file, "abCd", &message]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(std::fs::metadata(file).unwrap().len(), size + 1024 + 12);
+}
+
+#[test]
+fn large_payloads_need_a_split() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["capacity", file.to_str().unwrap(), "--payload-size", "5G"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output)
+            .contains("Payload of 5368709120 bytes: 3 chunks, split with encode --split-size"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn fails_over_the_maximum_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["capacity", file, "--payload-size", "2K", "--max-size", "1M"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("Within the maximum size of 1 MiB"),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = pngme(["capacity", file, "--payload-size", "2K", "--max-size", "2K"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stdout(&output).contains("Over the maximum size of 2048 bytes"),
+        "{}",
+        stdout(&output)
+    );
+    assert!(
+        stderr(&output).contains("error[E0528]"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = pngme(["capacity", file, "--max-size", "lots"]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn invalid_chunk_name_is_reported_with_the_missing_file() {
+    let output = pngme(["encode", "does-not-exist.png", "ru5t", "message"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("       ru5t\n         ^ '5' is not an ASCII letter"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("FILE: does-not-exist.png does not exist"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn reserved_bit_needs_confirmation() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "Rust".as_ref(),
+        "hi".as_ref(),
+        "--allow-unsafe-type".as_ref(),
+    ]);
+    assert!(!output.status.success());
+    let stderr_text = stderr(&output);
+    assert!(stderr_text.contains("'Rust' has the reserved bit set; did you mean 'RuSt'?"));
+    assert!(stderr_text.contains("pass --assume-yes"), "{stderr_text}");
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "Rust".as_ref(),
+        "hi".as_ref(),
+        "--allow-unsafe-type".as_ref(),
+        "--assume-yes".as_ref(),
+        "--allow-unknown-critical".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn reserved_bit_is_hinted_without_allow_unsafe_type() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["encode".as_ref(), file.as_os_str(), "Rust".as_ref(), "hi".as_ref()]);
+    assert!(!output.status.success());
+    let stderr_text = stderr(&output);
+    assert!(
+        stderr_text.contains("warning[W0206]: 'Rust' has the reserved bit set; did you mean 'RuSt'?"),
+        "{stderr_text}"
+    );
+    assert!(!stderr_text.contains("pass --assume-yes"), "{stderr_text}");
+}
+
+#[test]
+fn reserved_bit_is_hinted_when_decoding() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["decode".as_ref(), file.as_os_str(), "Rust".as_ref()]);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr_text = stderr(&output);
+    assert!(
+        stderr_text.contains("'Rust' has the reserved bit set; did you mean 'RuSt'?"),
+        "{stderr_text}"
+    );
+}
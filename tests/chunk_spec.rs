@@ -0,0 +1,72 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+#[test]
+fn inject_adds_the_chunks_of_the_specs_before_iend() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "inject",
+        file,
+        "--chunk-spec",
+        r#"noTe:"say \"hi\"""#,
+        "--chunk-spec",
+        "raWb:00ff41",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "Injected 2 chunk(s)\n");
+
+    let bytes = fs::read(file).unwrap();
+    let note = chunk_bytes("noTe", br#"say "hi""#);
+    let raw = chunk_bytes("raWb", &[0x00, 0xff, 0x41]);
+    let iend = chunk_bytes("IEND", b"");
+    assert!(bytes.ends_with(&[note, raw, iend].concat()));
+}
+
+#[test]
+fn replace_puts_the_chunk_in_place_of_the_first_of_its_type() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["inject", file, "--chunk-spec", r#"ruSt:"new""#, "--replace"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let printed = stdout(&pngme(["print", file]));
+    assert_eq!(printed.matches("ruSt").count(), 1, "{printed}");
+    assert!(printed.contains("data: new,"), "{printed}");
+}
+
+#[test]
+fn ambiguous_specs_are_refused_with_the_column() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["inject", file, "--chunk-spec", r#"noTe:"say "hi"""#]);
+    assert_eq!(output.status.code(), Some(2));
+    let message = stderr(&output);
+    assert!(message.contains("E0209"), "{message}");
+    assert!(message.contains("Unescaped quote at column 11"), "{message}");
+
+    let output = pngme(["inject", file, "--chunk-spec", "ruSt:abc"]);
+    let message = stderr(&output);
+    assert!(message.contains("E0207"), "{message}");
+    assert!(message.contains("Odd number of hex digits (3)"), "{message}");
+
+    assert_eq!(fs::read(file).unwrap(), fixture_png());
+}
+
+#[test]
+fn inject_needs_a_profile_or_a_spec() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["inject", file.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::*;
+use pngme::{
+    chunk::ChunkParserError,
+    codes::Code,
+    error::PngMeError,
+    png::{Png, PngError, PngParserError},
+};
+
+fn parse_error(bytes: &[u8]) -> PngMeError {
+    PngMeError::from(Png::try_from(bytes).unwrap_err())
+}
+
+#[test]
+fn main_errors_keep_their_codes() {
+    let mut bad_crc = png_bytes(&[("IHDR", b"header"), ("IEND", b"")]);
+    bad_crc[8 + 14] ^= 0xff;
+
+    let cases = [
+        (parse_error(b"GIF89a"), "E0101"),
+        (parse_error(b""), "E0102"),
+        (parse_error(&Png::STANDARD_HEADER), "E0103"),
+        (parse_error(&fixture_png()[..20]), "E0104"),
+        (parse_error(&bad_crc), "E0204"),
+        (
+            PngError::ChunkNotFound {
+                chunk_type: "ruSt".to_string(),
+            }
+            .into(),
+            "E0301",
+        ),
+        (PngMeError::EmptyMessage, "E0503"),
+        (PngMeError::VerifyFailed { count: 1 }, "E0508"),
+        (
+            PngError::from(PngParserError::InvalidChunk(ChunkParserError::Incomplete)).into(),
+            "E0201",
+        ),
+    ];
+
+    for (err, code) in cases {
+        assert_eq!(err.code().as_str(), code, "{err}");
+    }
+}
+
+#[test]
+fn catalog_names_and_messages() {
+    assert_eq!(Code::BadSignature.name(), "BadSignature");
+    assert_eq!(Code::BadSignature.message(), "not a PNG file");
+    assert_eq!(Code::TrailingData.name(), "TrailingData");
+    assert!(Code::ALL.iter().all(|code| !code.message().is_empty()));
+}
+
+#[test]
+fn errors_are_printed_with_their_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.gif", b"GIF89a");
+
+    let output = pngme(["print".as_ref(), file.as_os_str()]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).starts_with("error[E0101]: "),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn warnings_are_printed_with_their_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut bytes = fixture_png();
+    bytes.extend_from_slice(b"zip");
+    let file = write_fixture(dir.path(), "image.png", &bytes);
+
+    let output = pngme(["print".as_ref(), file.as_os_str()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("warning[W0203]: 3 trailing bytes ignored"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn encode_warnings_are_printed_with_their_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["encode".as_ref(), file.as_os_str(), "noTe".as_ref(), " \n".as_ref()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("warning[W0401]: the noTe message only contains whitespace"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn explain_describes_a_code() {
+    let output = pngme(["explain", "w0401"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "W0401 WhitespaceMessage: the message only contains whitespace\nA warning, the command goes on\n"
+    );
+
+    let output = pngme(["explain", "BadSignature"]);
+    assert_eq!(
+        stdout(&output),
+        "E0101 BadSignature: not a PNG file\nAn error, pngme exits with 4 validation-failed\n"
+    );
+
+    let output = pngme(["explain"]);
+    assert_eq!(stdout(&output).lines().count(), Code::ALL.len());
+
+    let output = pngme(["explain", "E9999"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("error[E1310]: "), "{}", stderr(&output));
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use std::io::Write;
+
+use common::*;
+use flate2::{Compression, write::ZlibEncoder};
+
+fn header(width: u32, height: u32, bit_depth: u8, color_type: u8) -> Vec<u8> {
+    [
+        &width.to_be_bytes()[..],
+        &height.to_be_bytes(),
+        &[bit_depth, color_type, 0, 0, 0],
+    ]
+    .concat()
+}
+
+/// An image whose IDAT inflates to `scanlines` bytes
+fn image(header: &[u8], scanlines: usize) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&vec![0; scanlines]).unwrap();
+    let idat = encoder.finish().unwrap();
+    png_bytes(&[("IHDR", header), ("IDAT", &idat), ("IEND", &[])])
+}
+
+#[test]
+fn info_prints_the_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let info = |name: &str, bytes: &[u8]| {
+        let file = write_fixture(dir.path(), name, bytes);
+        let output = pngme(["info", file.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        stdout(&output)
+    };
+
+    assert!(info("rgba.png", &fixture_png()).contains("Format: 8-bit RGBA\n"));
+    let printed = info("gray.png", &image(&header(9, 1, 2, 0), 4));
+    assert!(printed.contains("Format: 2-bit grayscale\n"), "{printed}");
+    let printed = info("invalid.png", &image(&header(1, 1, 16, 3), 3));
+    assert!(
+        printed.contains("Format: invalid (color type 3 with bit depth 16)\n"),
+        "{printed}"
+    );
+}
+
+#[test]
+fn deep_check_handles_every_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let verify = |name: &str, bytes: &[u8]| {
+        let file = write_fixture(dir.path(), name, bytes);
+        pngme(["verify", "--deep", file.to_str().unwrap()])
+    };
+
+    // 9 pixels of 2 bits take 3 bytes, after the filter byte
+    let output = verify("gray2.png", &image(&header(9, 2, 2, 0), 2 * 4));
+    assert_eq!(stdout(&output), "No problems found\n");
+    // 6 bytes per 16-bit RGB pixel
+    let output = verify("rgb16.png", &image(&header(2, 1, 16, 2), 1 + 12));
+    assert_eq!(stdout(&output), "No problems found\n");
+    let output = verify("rgb16-short.png", &image(&header(2, 1, 16, 2), 1 + 6));
+    assert!(
+        stdout(&output).contains("(6 bytes short)"),
+        "{}",
+        stdout(&output)
+    );
+
+    // No size is made up for a format that doesn't exist
+    let output = verify("indexed16.png", &image(&header(1, 1, 16, 3), 3));
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(
+        stderr(&output).contains("an invalid color type and bit depth"),
+        "{}",
+        stderr(&output)
+    );
+}
@@ -0,0 +1,117 @@
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    str::FromStr,
+};
+
+use pngme::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    fixtures::{FixtureKind, make_fixture},
+    png::{Png, Serialization},
+};
+
+pub const PNG_SIGNATURE: [u8; 8] = Png::STANDARD_HEADER;
+
+fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+    let chunk_type = ChunkType::from_str(chunk_type).expect("fixture chunk types are valid");
+    Chunk::new(chunk_type, data.to_vec())
+}
+
+/// Builds the on-disk bytes of a chunk: length, type, data and CRC
+pub fn chunk_bytes(chunk_type: &str, data: &[u8]) -> Vec<u8> {
+    chunk(chunk_type, data).as_bytes()
+}
+
+/// Builds a PNG file made of the given `(type, data)` chunks, as they are
+pub fn png_bytes(chunks: &[(&str, &[u8])]) -> Vec<u8> {
+    let chunks = chunks
+        .iter()
+        .map(|(chunk_type, data)| chunk(chunk_type, data))
+        .collect();
+    Png::from_chunks(chunks).as_bytes_with(Serialization::Raw)
+}
+
+/// A small image with an IDAT run and a couple of ancillary chunks
+pub fn fixture_png() -> Vec<u8> {
+    png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        ("teXt", b"first ancillary"),
+        ("IDAT", &[0x78, 0x9c]),
+        ("IDAT", &[0x62, 0x00, 0x01]),
+        ("IDAT", &[0x00, 0x00, 0xff, 0xff]),
+        ("ruSt", b"hidden message"),
+        ("IEND", &[]),
+    ])
+}
+
+/// Writes a generated fixture of the given kind into `dir`
+pub fn fixture(dir: &Path, kind: FixtureKind) -> PathBuf {
+    write_fixture(dir, &format!("{kind:?}.png"), &make_fixture(kind))
+}
+
+pub fn write_fixture(dir: &Path, name: &str, bytes: &[u8]) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, bytes).expect("Could not write fixture");
+    path
+}
+
+pub fn pngme<I, S>(args: I) -> Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(args)
+        .output()
+        .expect("Could not run pngme")
+}
+
+/// Runs pngme with `input` on its standard input
+pub fn pngme_with_stdin(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// Parses `print` output into `(index, chunk type)` pairs
+pub fn printed_chunks(output: &str) -> Vec<(usize, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix('#')?;
+            let (index, rest) = rest.split_once(' ')?;
+            let index = index.parse().ok()?;
+            let chunk_type = rest.split("type: { ").nth(1)?.get(..4)?.to_string();
+            Some((index, chunk_type))
+        })
+        .collect()
+}
+
+/// Types of the chunks of `file`, in order, as `print` lists them
+pub fn chunk_types(file: impl AsRef<Path>) -> Vec<String> {
+    let output = pngme(["print".as_ref(), file.as_ref().as_os_str()]);
+    printed_chunks(&stdout(&output))
+        .into_iter()
+        .map(|(_, chunk_type)| chunk_type)
+        .collect()
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn compressed_message_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let message = "all work and no play makes jack a dull boy\n".repeat(50);
+
+    let output = pngme(["encode", file, "abCd", &message, "--compress"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["decode", "--raw", file, "abCd"]);
+    assert!(output.stdout.starts_with(b"PNGME\x01\x10"));
+    assert!(output.stdout.len() < message.len() / 10);
+
+    let output = pngme(["decode", "--quiet", file, "abCd"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), format!("{message}\n"));
+}
+
+#[test]
+fn compression_keeps_the_envelope_fields() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "hello",
+        "--compress",
+        "--expires",
+        "2999-01-01T00:00:00Z",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["decode", file, "abCd"]);
+    assert_eq!(stdout(&output), "hello (expires on 2999-01-01T00:00:00Z)\n");
+}
+
+#[test]
+fn uncompressed_chunks_decode_unchanged() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", file, "abCd", "hello"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["decode", "--raw", file, "abCd"]);
+    assert_eq!(output.stdout, b"hello");
+    let output = pngme(["decode", "--quiet", file, "abCd"]);
+    assert_eq!(stdout(&output), "hello\n");
+}
+
+#[test]
+fn corrupted_stream_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let bytes = png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+        ("abCd", b"PNGME\x01\x10not zlib at all"),
+        ("IEND", &[]),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &bytes);
+    let file = file.to_str().unwrap();
+
+    for args in [
+        &["decode", file, "abCd"][..],
+        &["decode", "--output-encoding", "hex", file, "abCd"],
+    ] {
+        let output = pngme(args);
+        assert!(!output.status.success());
+        assert!(
+            stderr(&output).contains("error[E0512]"),
+            "{}",
+            stderr(&output)
+        );
+    }
+
+    let output = pngme(["decode", "--format", "json", file, "abCd"]);
+    assert!(!output.status.success());
+    let error: serde_json::Value = serde_json::from_str(&stderr(&output)).unwrap();
+    assert_eq!(error["error"]["code"], "E0512");
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use common::*;
+
+const MESSAGE: &str = "This is where your secret message will be!";
+
+#[test]
+fn debug_crc_from_hex_and_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_file = write_fixture(dir.path(), "data.bin", MESSAGE.as_bytes());
+    let hex: String = MESSAGE.bytes().map(|byte| format!("{byte:02x}")).collect();
+
+    let from_hex = pngme(["debug", "crc", "RuSt", "--data-hex", &hex]);
+    let from_file = pngme([
+        "debug".as_ref(),
+        "crc".as_ref(),
+        "RuSt".as_ref(),
+        "--data-file".as_ref(),
+        data_file.as_os_str(),
+    ]);
+
+    for output in [from_hex, from_file] {
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(stdout(&output).trim(), "CRC: 2882656334 (0xabd1d84e)");
+    }
+}
+
+#[test]
+fn debug_crc_of_empty_data() {
+    let output = pngme(["debug", "crc", "IEND"]);
+
+    assert_eq!(stdout(&output).trim(), "CRC: 2923585666 (0xae426082)");
+}
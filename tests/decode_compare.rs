@@ -0,0 +1,123 @@
+mod common;
+
+use std::{ffi::OsString, path::PathBuf};
+
+use common::*;
+
+fn manifest_png(payload: Option<&[u8]>) -> Vec<u8> {
+    let mut chunks: Vec<(&str, &[u8])> = vec![("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0])];
+    if let Some(payload) = payload {
+        chunks.push(("maNi", payload));
+    }
+    chunks.push(("IEND", &[]));
+    png_bytes(&chunks)
+}
+
+fn decode_args(files: &[PathBuf], extra: &[&str]) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["decode".into()];
+    args.extend(files.iter().map(|file| file.clone().into_os_string()));
+    args.push("maNi".into());
+    args.extend(extra.iter().map(OsString::from));
+    args
+}
+
+#[test]
+fn compare_identical_payloads_succeeds() {
+    let dir = tempfile::tempdir().unwrap();
+    let files: Vec<PathBuf> = ["a.png", "b.png", "c.png"]
+        .iter()
+        .map(|name| write_fixture(dir.path(), name, &manifest_png(Some(b"v1.2.0"))))
+        .collect();
+
+    let output = pngme(decode_args(&files, &["--compare"]));
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("1 distinct payload(s) for chunk maNi:"));
+}
+
+#[test]
+fn compare_reports_differing_and_missing_payloads() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = vec![
+        write_fixture(dir.path(), "a.png", &manifest_png(Some(b"v1.2.0"))),
+        write_fixture(dir.path(), "b.png", &manifest_png(Some(b"v1.3.0"))),
+        write_fixture(dir.path(), "c.png", &manifest_png(None)),
+    ];
+
+    let output = pngme(decode_args(&files, &["--compare"]));
+    let printed = stdout(&output);
+
+    assert!(!output.status.success());
+    assert!(printed.starts_with("2 distinct payload(s) for chunk maNi:"));
+    assert!(printed.contains(
+        "sha256 34bd659feb530efab079c898eb496cfabe7dfa512811185f24ff115b0263ccff (1 file(s))"
+    ));
+    assert!(printed.contains(
+        "sha256 0b27bac776e7501089020bad0490967ac0a95297d795a52e635aa4fade51ca10 (1 file(s))"
+    ));
+    assert!(printed.contains(&format!("Missing chunk maNi: {}", files[2].display())));
+    assert!(stderr(&output).contains("2 distinct values, 1 files without the chunk"));
+}
+
+#[test]
+fn compare_fails_when_only_one_file_is_missing_the_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = vec![
+        write_fixture(dir.path(), "a.png", &manifest_png(Some(b"v1.2.0"))),
+        write_fixture(dir.path(), "b.png", &manifest_png(Some(b"v1.2.0"))),
+        write_fixture(dir.path(), "c.png", &manifest_png(None)),
+    ];
+
+    let output = pngme(decode_args(&files, &["--compare"]));
+
+    assert!(!output.status.success());
+    assert!(stdout(&output).starts_with("1 distinct payload(s)"));
+}
+
+#[test]
+fn multi_file_decode_prefixes_file_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = vec![
+        write_fixture(dir.path(), "a.png", &manifest_png(Some(b"v1.2.0"))),
+        write_fixture(dir.path(), "b.png", &manifest_png(Some(b"v1.3.0"))),
+        write_fixture(dir.path(), "c.png", &manifest_png(None)),
+    ];
+
+    let output = pngme(decode_args(&files, &["--quiet"]));
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}: v1.2.0\n{}: v1.3.0\n",
+            files[0].display(),
+            files[1].display()
+        )
+    );
+    assert!(stderr(&output).contains(&format!(
+        "{}: The file has no non-standard chunks",
+        files[2].display()
+    )));
+}
+
+#[test]
+fn decode_fails_when_a_file_lacks_the_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = vec![
+        write_fixture(dir.path(), "a.png", &manifest_png(Some(b"v1.2.0"))),
+        write_fixture(dir.path(), "b.png", &manifest_png(None)),
+    ];
+
+    let output = pngme(decode_args(&files, &[]));
+
+    // The file holding the chunk is still decoded
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(stdout(&output).contains("v1.2.0"), "{}", stdout(&output));
+    assert_eq!(
+        stderr(&output).matches("Could not find chunk of type: maNi").count(),
+        1,
+        "{}",
+        stderr(&output)
+    );
+    assert!(!stderr(&output).contains("not found"), "{}", stderr(&output));
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use std::io::Write;
+
+use common::*;
+use flate2::{Compression, write::ZlibEncoder};
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn image_with(chunk_type: &str, data: &[u8]) -> Vec<u8> {
+    png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        (chunk_type, data),
+        ("IDAT", &deflate(&[0; 5])),
+        ("IEND", &[]),
+    ])
+}
+
+#[test]
+fn zip_bomb_in_ztxt_is_not_inflated_past_the_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    // 32 MiB of spaces in about 32 KiB
+    let bomb = [&b"Comment\0\0"[..], &deflate(&vec![b' '; 32 * 1024 * 1024])].concat();
+    assert!(bomb.len() < 64 * 1024);
+    let file = write_fixture(dir.path(), "bomb.png", &image_with("zTXt", &bomb));
+
+    let output = pngme(["print", file.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output)
+            .contains("(Comment: [decompressed data exceeds the limit of 16777216 bytes])"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn limit_can_be_changed() {
+    let dir = tempfile::tempdir().unwrap();
+    let ztxt = [&b"Comment\0\0"[..], &deflate(&[b'a'; 100 * 1024])].concat();
+    let file = write_fixture(dir.path(), "image.png", &image_with("zTXt", &ztxt));
+    let file = file.to_str().unwrap();
+
+    let output = stdout(&pngme(["print", file]));
+    assert!(output.contains("(Comment: aaaa"), "{output}");
+
+    let output = stdout(&pngme(["print", file, "--max-decompressed-size", "64K"]));
+    assert!(
+        output.contains("(Comment: [decompressed data exceeds the limit of 65536 bytes])"),
+        "{output}"
+    );
+}
+
+#[test]
+fn compressed_message_over_the_limit_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let message = "a".repeat(100 * 1024);
+
+    let output = pngme(["encode", file, "abCd", &message, "--compress"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["decode", "--quiet", file, "abCd"]);
+    assert_eq!(stdout(&output).trim_end(), message);
+
+    let output = pngme([
+        "decode",
+        "--quiet",
+        file,
+        "abCd",
+        "--max-decompressed-size",
+        "64K",
+    ]);
+    assert!(!output.status.success());
+    let stderr = stderr(&output);
+    assert!(stderr.contains("error[E0521]"), "{stderr}");
+    assert!(
+        stderr.contains("Decompressed data exceeds the limit of 65536 bytes (chunk abCd #6)"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn icc_profile_over_the_limit_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let iccp = [&b"Big\0\0"[..], &deflate(&[0; 100 * 1024])].concat();
+    let file = write_fixture(dir.path(), "image.png", &image_with("iCCP", &iccp));
+    let file = file.to_str().unwrap();
+    let profile = dir.path().join("profile.icc");
+
+    let output = pngme([
+        "extract",
+        file,
+        "--icc",
+        profile.to_str().unwrap(),
+        "--max-decompressed-size",
+        "64K",
+    ]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("exceeds the limit of 65536 bytes (chunk iCCP #1)"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = pngme(["extract", file, "--icc", profile.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
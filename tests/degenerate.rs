@@ -0,0 +1,92 @@
+mod common;
+
+use std::path::Path;
+
+use common::*;
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+fn partial_ihdr() -> Vec<u8> {
+    png_bytes(&[("IHDR", &[0; 13])])[..8 + 10].to_vec()
+}
+
+fn assert_fails_everywhere(file: &Path, expected: &str) {
+    let commands: [&[&str]; 3] = [&["print"], &["decode", "ruSt"], &["verify"]];
+
+    for args in commands {
+        let output = pngme(
+            std::iter::once(args[0].as_ref())
+                .chain([file.as_os_str()])
+                .chain(args[1..].iter().map(|arg| arg.as_ref())),
+        );
+
+        assert!(!output.status.success(), "{} succeeded", args[0]);
+        assert!(
+            stderr(&output).contains(expected),
+            "{}: {}",
+            args[0],
+            stderr(&output)
+        );
+    }
+}
+
+#[test]
+fn empty_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "empty.png", b"");
+
+    assert_fails_everywhere(&file, "The file is empty");
+}
+
+#[test]
+fn signature_only_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "signature.png", &SIGNATURE);
+
+    assert_fails_everywhere(&file, "The file contains no chunks (signature only)");
+}
+
+#[test]
+fn signature_and_partial_ihdr() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "partial.png", &partial_ihdr());
+
+    assert_fails_everywhere(
+        &file,
+        "The file is truncated: the chunk at byte 8 needs 25 bytes, only 10 left",
+    );
+}
+
+#[test]
+fn fix_bootstraps_signature_only_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "signature.png", &SIGNATURE);
+
+    let output = pngme(["fix".as_ref(), file.as_os_str()]);
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read(&file).unwrap(), SIGNATURE);
+
+    let output = pngme(["fix".as_ref(), file.as_os_str(), "--bootstrap".as_ref()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["verify".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output).trim(), "No problems found");
+}
+
+#[test]
+fn fix_repairs_verify_problems() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut bytes = fixture_png();
+    // Corrupt the CRC of IEND, then add a chunk and garbage after it
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+    bytes.extend(chunk_bytes("ruSt", b"late"));
+    bytes.extend(b"xyz");
+    let file = write_fixture(dir.path(), "broken.png", &bytes);
+
+    let output = pngme(["fix".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output).trim(), "Fixed 3 problem(s)");
+    assert_eq!(std::fs::read(&file).unwrap(), fixture_png());
+}
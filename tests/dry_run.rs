@@ -0,0 +1,68 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+#[test]
+fn encode_dry_run_prints_the_layout_and_writes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", file, "abCd", "hello", "--dry-run"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Dry run, nothing written to"), "{stdout}");
+    assert!(stdout.contains("#0 IHDR length 13 at offset 8"), "{stdout}");
+    assert!(stdout.contains(" abCd length 5 at offset "), "{stdout}");
+    let size = fixture_png().len();
+    assert!(
+        stdout.contains(&format!("Size: {size} -> {} bytes (+17)", size + 17)),
+        "{stdout}"
+    );
+
+    assert_eq!(fs::read(file).unwrap(), fixture_png());
+    let entries: Vec<_> = fs::read_dir(dir.path()).unwrap().collect();
+    assert_eq!(entries.len(), 1);
+}
+
+#[test]
+fn remove_dry_run_shrinks_the_image_on_paper() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let copy = dir.path().join("copy.png");
+
+    let output = pngme([
+        "remove",
+        file,
+        "ruSt",
+        "--output",
+        copy.to_str().unwrap(),
+        "--dry-run",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let stdout = stdout(&output);
+    assert!(!stdout.contains("ruSt"), "{stdout}");
+    assert!(stdout.contains("bytes (-26)"), "{stdout}");
+    assert!(!copy.exists());
+    assert_eq!(fs::read(file).unwrap(), fixture_png());
+}
+
+#[test]
+fn dry_run_fails_like_the_edit_would() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["remove", file, "miSs", "--dry-run"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(!stdout(&output).contains("Dry run"));
+
+    let output = pngme(["remove", file, "--at", "99", "--dry-run"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(fs::read(file).unwrap(), fixture_png());
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::*;
+
+fn fixture_with_empty_chunk() -> Vec<u8> {
+    png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        ("emPt", &[]),
+        ("IDAT", &[0x78, 0x9c]),
+        ("IEND", &[]),
+    ])
+}
+
+#[test]
+fn encode_refuses_empty_message_without_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "noTe".as_ref(),
+        "".as_ref(),
+    ]);
+
+    assert!(stderr(&output).contains("--allow-empty"));
+    assert_eq!(std::fs::read(&file).unwrap(), fixture_png());
+}
+
+#[test]
+fn encode_embeds_empty_message_with_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "noTe".as_ref(),
+        "".as_ref(),
+        "--allow-empty".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["print".as_ref(), file.as_os_str()]);
+    assert!(stdout(&output).contains("{ length: 0 type: { noTe:"));
+}
+
+#[test]
+fn encode_warns_on_whitespace_message() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "noTe".as_ref(),
+        " \n".as_ref(),
+    ]);
+
+    assert!(stderr(&output).contains("only contains whitespace"));
+    assert_ne!(std::fs::read(&file).unwrap(), fixture_png());
+}
+
+#[test]
+fn decode_reports_empty_payload() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_with_empty_chunk());
+
+    let output = pngme(["decode".as_ref(), file.as_os_str(), "emPt".as_ref()]);
+    assert_eq!(stdout(&output), "(empty payload, 0 bytes)\n");
+
+    let output = pngme([
+        "decode".as_ref(),
+        file.as_os_str(),
+        "emPt".as_ref(),
+        "--quiet".as_ref(),
+    ]);
+    assert_eq!(stdout(&output), "\"\"\n");
+}
+
+#[test]
+fn decode_json_distinguishes_empty_from_not_found() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_with_empty_chunk());
+
+    let output = pngme([
+        "decode".as_ref(),
+        file.as_os_str(),
+        "emPt".as_ref(),
+        "--format".as_ref(),
+        "json".as_ref(),
+    ]);
+    let found: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(found["found"], true);
+    assert_eq!(found["length"], 0);
+    assert_eq!(found["data"], "");
+
+    let output = pngme([
+        "decode".as_ref(),
+        file.as_os_str(),
+        "noNe".as_ref(),
+        "--format".as_ref(),
+        "json".as_ref(),
+    ]);
+    let missing: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(missing["found"], false);
+    assert!(missing["data"].is_null());
+}
+
+#[test]
+fn print_and_remove_handle_empty_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_with_empty_chunk());
+
+    let output = pngme(["print".as_ref(), file.as_os_str()]);
+    assert!(stdout(&output).contains("#1 { length: 0 type: { emPt:"));
+
+    let output = pngme(["remove".as_ref(), file.as_os_str(), "emPt".as_ref()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["print".as_ref(), file.as_os_str()]);
+    assert!(!stdout(&output).contains("emPt"));
+}
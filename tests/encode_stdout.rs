@@ -0,0 +1,33 @@
+mod common;
+
+use common::*;
+use pngme::png::Png;
+
+#[test]
+fn dash_output_writes_only_the_image_to_stdout() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    // A whitespace message makes encode warn
+    let output = pngme(["encode", file, "abCd", "   ", "-"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let png = Png::try_from(output.stdout.as_slice()).unwrap();
+    assert_eq!(png.chunk_by_type("abCd").unwrap().data(), b"   ");
+    assert!(stderr(&output).contains("only contains whitespace"));
+    // The input is left alone
+    assert_eq!(std::fs::read(file).unwrap(), fixture_png());
+}
+
+#[test]
+fn failed_encode_writes_nothing_to_stdout() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["encode", file.to_str().unwrap(), "ABCD", "hello", "-"]);
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(stderr(&output).contains("error["), "{}", stderr(&output));
+}
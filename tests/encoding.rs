@@ -0,0 +1,100 @@
+mod common;
+
+use std::path::Path;
+
+use common::*;
+
+fn encode(file: &Path, message: &str, encoding: &str) {
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "enCo".as_ref(),
+        message.as_ref(),
+        "--input-encoding".as_ref(),
+        encoding.as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+fn decode(file: &Path, encoding: &str, extra: &[&str]) -> String {
+    let mut args: Vec<&std::ffi::OsStr> = vec![
+        "decode".as_ref(),
+        file.as_os_str(),
+        "enCo".as_ref(),
+        "--output-encoding".as_ref(),
+        encoding.as_ref(),
+    ];
+    args.extend(extra.iter().map(std::ffi::OsStr::new));
+
+    let output = pngme(args);
+    assert!(output.status.success(), "{}", stderr(&output));
+    stdout(&output).trim_end_matches('\n').to_string()
+}
+
+#[test]
+fn base45_in_and_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    encode(&file, "%69 VD92EX0", "base45");
+
+    assert_eq!(decode(&file, "text", &["-q"]), "Hello!!");
+    assert_eq!(decode(&file, "base45", &["-q"]), "%69 VD92EX0");
+    assert_eq!(decode(&file, "base32", &[]), "JBSWY3DPEEQQ====");
+    assert_eq!(
+        decode(&file, "base45", &["--format", "json"]),
+        r#"{"file":"FILE","chunk_type":"enCo","found":true,"length":7,"data":"%69 VD92EX0","encoding":"base45"}"#
+            .replace("FILE", &file.display().to_string())
+    );
+}
+
+#[test]
+fn invalid_input_leaves_file_untouched() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "enCo".as_ref(),
+        "GGW".as_ref(),
+        "--input-encoding".as_ref(),
+        "base45".as_ref(),
+    ]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("Invalid base45"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(std::fs::read(&file).unwrap(), fixture_png());
+}
+
+#[test]
+fn randomized_binary_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut state: u32 = 0x2545_f491;
+
+    for length in [1, 2, 3, 16, 45, 100] {
+        let payload: Vec<u8> = (0..length)
+            .map(|_| {
+                // xorshift32
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let hex: String = payload.iter().map(|byte| format!("{byte:02x}")).collect();
+        let file = write_fixture(dir.path(), "image.png", &fixture_png());
+        encode(&file, &hex, "hex");
+
+        for encoding in ["base32", "base45", "base64"] {
+            let text = decode(&file, encoding, &["-q"]);
+
+            let copy = write_fixture(dir.path(), "copy.png", &fixture_png());
+            encode(&copy, &text, encoding);
+            assert_eq!(decode(&copy, "hex", &["-q"]), hex, "{encoding}");
+        }
+    }
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use std::{fs, process::Command};
+
+use common::*;
+
+fn encrypted_image(dir: &tempfile::TempDir, extra: &[&str]) -> String {
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap().to_string();
+
+    let mut args = vec![
+        "encode",
+        &file,
+        "abCd",
+        "meet at noon",
+        "--encrypt",
+        "--password",
+        "correct horse",
+    ];
+    args.extend_from_slice(extra);
+    let output = pngme(&args);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    file
+}
+
+#[test]
+fn encrypted_message_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = encrypted_image(&dir, &[]);
+
+    let stored = fs::read(&file).unwrap();
+    assert!(!stored.windows(4).any(|window| window == b"noon"));
+
+    let output = pngme([
+        "decode",
+        "--quiet",
+        "--decrypt",
+        "--password",
+        "correct horse",
+        &file,
+        "abCd",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "meet at noon\n");
+}
+
+#[test]
+fn passphrase_from_the_environment() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = encrypted_image(&dir, &["--compress"]);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(["decode", "--quiet", "--decrypt", &file, "abCd"])
+        .env("PNGME_PASSPHRASE", "correct horse")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "meet at noon\n");
+}
+
+#[test]
+fn wrong_passphrase_fails_cleanly() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = encrypted_image(&dir, &[]);
+    let passphrase = write_fixture(dir.path(), "passphrase.txt", b"battery staple\n");
+
+    let output = pngme([
+        "decode",
+        "--decrypt",
+        "--password-file",
+        passphrase.to_str().unwrap(),
+        &file,
+        "abCd",
+    ]);
+
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), "");
+    assert!(
+        stderr(&output).contains("error[E0514]") && stderr(&output).contains("Decryption failed"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn decode_without_decrypt_refuses() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = encrypted_image(&dir, &[]);
+
+    let output = pngme(["decode", &file, "abCd"]);
+
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), "");
+    assert!(
+        stderr(&output).contains("error[E0513]") && stderr(&output).contains("--decrypt"),
+        "{}",
+        stderr(&output)
+    );
+}
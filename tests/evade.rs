@@ -0,0 +1,79 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn profile_picks_the_name_and_position() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCD",
+        "hello",
+        "--evade",
+        "pngcrush-default",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Writing abCD as abCd for the pngcrush-default profile"),
+        "{}",
+        stderr(&output)
+    );
+
+    assert_eq!(
+        chunk_types(file),
+        [
+            "IHDR", "abCd", "teXt", "IDAT", "IDAT", "IDAT", "ruSt", "IEND"
+        ]
+    );
+    assert_eq!(
+        stdout(&pngme(["decode", "--quiet", file, "abCd"])),
+        "hello\n"
+    );
+}
+
+#[test]
+fn survivability_checks_the_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "survivability",
+        file,
+        "ruSt",
+        "--profile",
+        "pngcrush-default",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = stdout(&output);
+    assert!(
+        output.contains("Profile: pngcrush-default (pngcrush without -rem)\n"),
+        "{output}"
+    );
+    assert!(output.contains("  safe to copy: yes\n"), "{output}");
+    assert!(output.contains("  before IDAT: no\n"), "{output}");
+}
+
+#[test]
+fn unknown_profile_is_a_usage_error() {
+    let output = pngme(["encode", "image.png", "abCd", "hello", "--evade", "gimp"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("expected one of oxipng-default"));
+
+    let output = pngme([
+        "encode",
+        "image.png",
+        "abCd",
+        "hello",
+        "--evade",
+        "wordpress",
+        "--position",
+        "after-ihdr",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,124 @@
+//! One failure of each class, pinned to the status scripts rely on
+
+mod common;
+
+use common::*;
+use serde_json::Value;
+
+#[test]
+fn missing_file_is_not_found() {
+    let output = pngme(["info", "missing.png"]);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("[E1301]"), "{}", stderr(&output));
+}
+
+#[test]
+fn bad_flag_combination_is_a_usage_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "--undoable".as_ref(),
+        "encode".as_ref(),
+        file.as_os_str(),
+        "abCd".as_ref(),
+        "hello".as_ref(),
+        "out.png".as_ref(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("[E1302]"), "{}", stderr(&output));
+}
+
+#[test]
+fn missing_chunk_is_not_found() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["decode".as_ref(), file.as_os_str(), "abCd".as_ref()]);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(
+        stderr(&output).contains("error[E0301]"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn verify_failure_is_a_validation_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut png = fixture_png();
+    png.extend_from_slice(b"zip");
+    let file = write_fixture(dir.path(), "image.png", &png);
+
+    let output = pngme(["verify".as_ref(), file.as_os_str()]);
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(
+        stderr(&output).contains("error[E0508]"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn wrong_passphrase_is_an_integrity_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "meet at noon",
+        "--encrypt",
+        "--password",
+        "correct horse",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme([
+        "decode",
+        "--decrypt",
+        "--password",
+        "battery staple",
+        file,
+        "abCd",
+    ]);
+
+    assert_eq!(output.status.code(), Some(5));
+    assert!(
+        stderr(&output).contains("error[E0514]"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn json_errors_carry_the_exit_status() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "decode".as_ref(),
+        "--format".as_ref(),
+        "json".as_ref(),
+        file.as_os_str(),
+        "abCd".as_ref(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(3));
+    let error: Value = serde_json::from_str(&stderr(&output)).unwrap();
+    assert_eq!(error["error"]["code"], "E0301");
+    assert_eq!(error["error"]["exit_status"], 3);
+    assert_eq!(error["error"]["status"], "not-found");
+
+    let output = pngme(["decode", "--format", "json", "missing.png", "abCd"]);
+    let error: Value = serde_json::from_str(&stderr(&output)).unwrap();
+    assert_eq!(error["error"]["code"], "E1301");
+    assert_eq!(error["error"]["exit_status"], 3);
+    assert_eq!(error["error"]["status"], "not-found");
+}
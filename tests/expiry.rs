@@ -0,0 +1,81 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::*;
+
+const PAST: &str = "2001-01-01T00:00:00Z";
+const FUTURE: &str = "2999-01-01T00:00:00Z";
+
+fn encode_expiring(dir: &Path, expires: &str) -> PathBuf {
+    let file = write_fixture(dir, "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "exPi".as_ref(),
+        "ephemeral".as_ref(),
+        "--expires".as_ref(),
+        expires.as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    file
+}
+
+#[test]
+fn decode_shows_live_message() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = encode_expiring(dir.path(), FUTURE);
+
+    let output = pngme(["decode".as_ref(), file.as_os_str(), "exPi".as_ref()]);
+    assert_eq!(
+        stdout(&output).trim(),
+        format!("ephemeral (expires on {FUTURE})")
+    );
+
+    let output = pngme([
+        "decode".as_ref(),
+        file.as_os_str(),
+        "exPi".as_ref(),
+        "-q".as_ref(),
+    ]);
+    assert_eq!(stdout(&output).trim(), "ephemeral");
+}
+
+#[test]
+fn decode_hides_expired_message_unless_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = encode_expiring(dir.path(), PAST);
+
+    let output = pngme(["decode".as_ref(), file.as_os_str(), "exPi".as_ref()]);
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains(&format!("Message expired on {PAST}")));
+
+    let output = pngme([
+        "decode".as_ref(),
+        file.as_os_str(),
+        "exPi".as_ref(),
+        "--ignore-expiry".as_ref(),
+        "-q".as_ref(),
+    ]);
+    assert_eq!(stdout(&output).trim(), "ephemeral");
+}
+
+#[test]
+fn strip_expired_only_removes_expired_messages() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = encode_expiring(dir.path(), PAST);
+
+    let output = pngme(["print".as_ref(), file.as_os_str()]);
+    assert!(stdout(&output).contains(&format!("(message expired on {PAST})")));
+
+    let output = pngme([
+        "strip".as_ref(),
+        file.as_os_str(),
+        "--expired-only".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output).trim(), "Removed 1 chunk(s)");
+    assert_eq!(std::fs::read(&file).unwrap(), fixture_png());
+}
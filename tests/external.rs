@@ -0,0 +1,109 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::{Command, Output},
+};
+
+use common::*;
+
+/// Installs a `pngme-hello` script printing its arguments and exiting with
+/// the status given as its first argument
+fn install_stub(dir: &Path) {
+    let script = dir.join("pngme-hello");
+    fs::write(
+        &script,
+        "#!/bin/sh\nstatus=$1\nshift\necho \"hello from the stub: $*\"\nexit \"$status\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+fn pngme_with_path(dir: &Path, args: &[&str]) -> Output {
+    let path = std::env::join_paths(std::iter::once(dir.to_path_buf()).chain(
+        std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default()),
+    ))
+    .unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(args)
+        .env("PATH", path)
+        .output()
+        .expect("Could not run pngme")
+}
+
+#[test]
+fn unknown_command_runs_the_external_program() {
+    let dir = tempfile::tempdir().unwrap();
+    install_stub(dir.path());
+
+    let output = pngme_with_path(
+        dir.path(),
+        &["hello", "0", "image.png", "--flag", "two words"],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "hello from the stub: image.png --flag two words\n"
+    );
+}
+
+#[test]
+fn exit_status_is_propagated() {
+    let dir = tempfile::tempdir().unwrap();
+    install_stub(dir.path());
+
+    let output = pngme_with_path(dir.path(), &["hello", "7"]);
+
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+fn no_external_skips_the_program() {
+    let dir = tempfile::tempdir().unwrap();
+    install_stub(dir.path());
+
+    let output = pngme_with_path(dir.path(), &["--no-external", "hello", "0"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stdout(&output).is_empty());
+    assert!(
+        stderr(&output).contains("error[E1305]"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn missing_program_lists_the_builtins() {
+    let output = pngme(["no-such-command"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("error[E1305]"), "{stderr}");
+    assert!(
+        stderr.contains("no pngme-no-such-command program is on the PATH"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("encode, decode"), "{stderr}");
+}
+
+#[test]
+fn path_separators_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    install_stub(dir.path());
+
+    let output = pngme_with_path(dir.path(), &["../hello", "0"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("error[E1306]"),
+        "{}",
+        stderr(&output)
+    );
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use std::fs;
+
+use clap::ValueEnum;
+use common::*;
+use pngme::fixtures::{FixtureKind, make_fixture};
+
+#[test]
+fn debug_make_fixture_writes_every_kind() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for kind in FixtureKind::ALL {
+        let name = kind.to_possible_value().unwrap().get_name().to_string();
+        let output = dir.path().join(format!("{name}.png"));
+
+        let result = pngme([
+            "debug".as_ref(),
+            "make-fixture".as_ref(),
+            name.as_ref(),
+            output.as_os_str(),
+        ]);
+
+        assert!(result.status.success(), "{kind:?}: {}", stderr(&result));
+        assert_eq!(fs::read(&output).unwrap(), make_fixture(kind));
+    }
+}
+
+#[test]
+fn shared_helper_fixtures_behave_as_described() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = pngme([
+        "print".as_ref(),
+        fixture(dir.path(), FixtureKind::Minimal).as_os_str(),
+    ]);
+    assert!(output.status.success());
+
+    let output = pngme([
+        "print".as_ref(),
+        fixture(dir.path(), FixtureKind::CorruptCrc).as_os_str(),
+    ]);
+    assert!(stderr(&output).contains("checksum"));
+
+    let output = pngme([
+        "print".as_ref(),
+        fixture(dir.path(), FixtureKind::Truncated).as_os_str(),
+    ]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn debug_bench_parse_prints_stage_timings() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = pngme([
+        "debug".as_ref(),
+        "bench-parse".as_ref(),
+        fixture(dir.path(), FixtureKind::Apng).as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let report = stdout(&output);
+    let stages: Vec<&str> = report
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .collect();
+    assert_eq!(stages, ["read", "list", "parse", "as_bytes", "write_to"]);
+    assert!(report.contains("(7 chunks)"));
+}
+
+#[test]
+fn debug_is_hidden_from_help() {
+    let output = pngme(["--help"]);
+    assert!(!stdout(&output).contains("debug"));
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+fn signed_image(dir: &tempfile::TempDir, extra: &[&str]) -> String {
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap().to_string();
+
+    let mut args = vec![
+        "encode",
+        &file,
+        "abCd",
+        "meet at noon",
+        "--hmac",
+        "--hmac-key",
+        "shared key",
+    ];
+    args.extend_from_slice(extra);
+    let output = pngme(&args);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    file
+}
+
+fn verify(file: &str, key: &str) -> std::process::Output {
+    pngme([
+        "decode",
+        "--quiet",
+        "--verify-hmac",
+        "--hmac-key",
+        key,
+        file,
+        "abCd",
+    ])
+}
+
+#[test]
+fn signed_message_verifies() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = signed_image(&dir, &[]);
+
+    let output = verify(&file, "shared key");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "meet at noon\n");
+
+    // Without --verify-hmac the message decodes as any other
+    let output = pngme(["decode", "--quiet", &file, "abCd"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "meet at noon\n");
+}
+
+#[test]
+fn key_from_a_file_with_compression_and_encryption() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write_fixture(dir.path(), "key.txt", b"shared key\n");
+    let key = key.to_str().unwrap();
+    let file = signed_image(
+        &dir,
+        &["--compress", "--encrypt", "--password", "correct horse"],
+    );
+
+    let output = pngme([
+        "decode",
+        "--quiet",
+        "--decrypt",
+        "--password",
+        "correct horse",
+        "--verify-hmac",
+        "--hmac-key-file",
+        key,
+        &file,
+        "abCd",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "meet at noon\n");
+}
+
+#[test]
+fn tampered_message_fails_loudly() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = signed_image(&dir, &[]);
+
+    // Alter the message and fix the CRC, as an attacker would
+    let mut bytes = fs::read(&file).unwrap();
+    let at = bytes
+        .windows(4)
+        .position(|window| window == b"noon")
+        .unwrap();
+    bytes[at] = b'm';
+    fs::write(&file, &bytes).unwrap();
+    let output = pngme(["fix", &file]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = verify(&file, "shared key");
+    assert_eq!(output.status.code(), Some(5));
+    assert!(
+        stderr(&output).contains("error[E0523]"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(stdout(&output), "");
+
+    let output = verify(&signed_image(&dir, &[]), "other key");
+    assert_eq!(output.status.code(), Some(5));
+    assert!(
+        stderr(&output).contains("error[E0523]"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn unsigned_message_fails_verification() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let output = pngme(["encode", file, "abCd", "meet at noon"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = verify(file, "shared key");
+    assert_eq!(output.status.code(), Some(5));
+    assert!(
+        stderr(&output).contains("error[E0522]"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn hmac_needs_a_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["encode", file.to_str().unwrap(), "abCd", "hi", "--hmac"]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+fn profile_blob() -> Vec<u8> {
+    (0u8..=255).cycle().take(3000).collect()
+}
+
+#[test]
+fn inject_then_extract_round_trips_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let profile = write_fixture(dir.path(), "in.icc", &profile_blob());
+    let extracted = dir.path().join("out.icc");
+
+    let output = pngme([
+        "inject".as_ref(),
+        file.as_os_str(),
+        "--icc".as_ref(),
+        profile.as_os_str(),
+        "--name".as_ref(),
+        "Display P3".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(
+        chunk_types(&file),
+        [
+            "IHDR", "teXt", "iCCP", "IDAT", "IDAT", "IDAT", "ruSt", "IEND"
+        ]
+    );
+
+    let output = pngme(["info".as_ref(), file.as_os_str()]);
+    assert!(stdout(&output).contains("ICC profile: Display P3 (3000 bytes decompressed)"));
+
+    let output = pngme([
+        "extract".as_ref(),
+        file.as_os_str(),
+        "--icc".as_ref(),
+        extracted.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read(&extracted).unwrap(), profile_blob());
+}
+
+#[test]
+fn inject_places_profile_before_plte() {
+    let dir = tempfile::tempdir().unwrap();
+    let bytes = png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0]),
+        ("PLTE", &[0, 0, 0]),
+        ("IDAT", &[0x78, 0x9c]),
+        ("IEND", &[]),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &bytes);
+    let profile = write_fixture(dir.path(), "in.icc", &profile_blob());
+
+    pngme([
+        "inject".as_ref(),
+        file.as_os_str(),
+        "--icc".as_ref(),
+        profile.as_os_str(),
+        "--name".as_ref(),
+        "sRGB IEC61966-2.1".as_ref(),
+    ]);
+
+    assert_eq!(
+        chunk_types(&file),
+        ["IHDR", "iCCP", "PLTE", "IDAT", "IEND"]
+    );
+}
+
+#[test]
+fn inject_refuses_existing_srgb_without_replace() {
+    let dir = tempfile::tempdir().unwrap();
+    let bytes = png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        ("sRGB", &[0]),
+        ("IDAT", &[0x78, 0x9c]),
+        ("IEND", &[]),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &bytes);
+    let profile = write_fixture(dir.path(), "in.icc", &profile_blob());
+    let args = [
+        "inject".as_ref(),
+        file.as_os_str(),
+        "--icc".as_ref(),
+        profile.as_os_str(),
+        "--name".as_ref(),
+        "Display P3".as_ref(),
+    ];
+
+    let output = pngme(args);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("already has a sRGB chunk"));
+    assert_eq!(fs::read(&file).unwrap(), bytes);
+
+    let output = pngme(args.iter().chain([&"--replace".as_ref()]));
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(chunk_types(&file), ["IHDR", "iCCP", "IDAT", "IEND"]);
+}
+
+#[test]
+fn extract_without_profile_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let extracted = dir.path().join("out.icc");
+
+    let output = pngme([
+        "extract".as_ref(),
+        file.as_os_str(),
+        "--icc".as_ref(),
+        extracted.as_os_str(),
+    ]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Could not find chunk of type: iCCP"));
+    assert!(!extracted.exists());
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn decode_reads_stdin() {
+    let png = png_bytes(&[("IHDR", b"header"), ("ruSt", b"from stdin"), ("IEND", b"")]);
+
+    let output = pngme_with_stdin(&["decode", "-", "ruSt", "--quiet"], &png);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output).trim(), "from stdin");
+}
+
+#[test]
+fn encode_from_stdin_writes_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out.png");
+    let png = png_bytes(&[("IHDR", b"header"), ("IEND", b"")]);
+
+    let output = pngme_with_stdin(
+        &["encode", "-", "ruSt", "hello", out.to_str().unwrap()],
+        &png,
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["decode", out.to_str().unwrap(), "ruSt", "--quiet"]);
+    assert_eq!(stdout(&output).trim(), "hello");
+}
+
+#[test]
+fn max_input_size_rejects_large_stdin() {
+    let png = png_bytes(&[("IHDR", b"header"), ("IEND", b"")]);
+
+    let output = pngme_with_stdin(&["--max-input-size", "8", "info", "-"], &png);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("too large"), "{}", stderr(&output));
+}
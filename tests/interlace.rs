@@ -0,0 +1,65 @@
+mod common;
+
+use std::str::FromStr;
+
+use common::*;
+use pngme::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    fixtures::{FixtureKind, make_fixture},
+    png::Png,
+};
+
+#[test]
+fn deinterlacing_keeps_the_message() {
+    let dir = tempfile::tempdir().unwrap();
+    // The message goes before IEND, fix drops what follows it
+    let mut png = Png::try_from(make_fixture(FixtureKind::Interlaced).as_slice()).unwrap();
+    let message = Chunk::new(
+        ChunkType::from_str("ruSt").unwrap(),
+        b"hidden message".to_vec(),
+    );
+    png.insert_chunk(2, message).unwrap();
+    let file = write_fixture(dir.path(), "interlaced.png", &png.as_bytes());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["info", file]);
+    assert!(
+        stdout(&output).contains("Interlace: Adam7"),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = pngme(["fix", file, "--deinterlace"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("Removed the interlacing"),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = pngme(["info", file]);
+    assert!(
+        stdout(&output).contains("Interlace: none"),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = pngme(["decode", "--quiet", file, "ruSt"]);
+    assert_eq!(stdout(&output), "hidden message\n");
+
+    let output = pngme(["verify", file]);
+    assert!(output.status.success(), "{}", stdout(&output));
+}
+
+#[test]
+fn deinterlacing_a_progressive_image_changes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = fixture(dir.path(), FixtureKind::Minimal);
+    let before = std::fs::read(&file).unwrap();
+
+    let output = pngme(["fix", file.to_str().unwrap(), "--deinterlace"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("The image is not interlaced"));
+    assert_eq!(std::fs::read(&file).unwrap(), before);
+}
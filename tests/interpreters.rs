@@ -0,0 +1,69 @@
+mod common;
+
+use common::*;
+use pngme::{
+    chunk_type::ChunkType,
+    commands::print_chunks,
+    interpret::{ChunkInterpreter, Registry},
+    png::Png,
+};
+
+/// Describes the private `vrSn` chunk of an imaginary application
+struct Version;
+
+impl ChunkInterpreter for Version {
+    fn matches(&self, chunk_type: &ChunkType) -> bool {
+        chunk_type.bytes() == *b"vrSn"
+    }
+
+    fn describe(&self, data: &[u8]) -> Option<String> {
+        let [major, minor] = data else {
+            return None;
+        };
+        Some(format!("written by version {major}.{minor}"))
+    }
+}
+
+fn image() -> Vec<u8> {
+    png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        ("gAMA", &45455_u32.to_be_bytes()),
+        ("tEXt", b"Title\0Sunset"),
+        ("IDAT", &[0x78, 0x9c]),
+        ("vrSn", &[2, 7]),
+        ("IEND", &[]),
+    ])
+}
+
+#[test]
+fn custom_interpreter_describes_private_chunks() {
+    let png = Png::try_from(image().as_slice()).unwrap();
+    let mut registry = Registry::default();
+    registry.register(Version);
+
+    let mut out = Vec::new();
+    print_chunks(&png, false, 0, &registry, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.contains("(written by version 2.7)"), "{out}");
+    assert!(out.contains("(Title: Sunset)"), "{out}");
+
+    let mut out = Vec::new();
+    print_chunks(&png, false, 0, Registry::builtin(), &mut out).unwrap();
+    assert!(!String::from_utf8(out).unwrap().contains("version 2.7"));
+}
+
+#[test]
+fn print_and_info_describe_known_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &image());
+    let file = file.to_str().unwrap();
+
+    let output = stdout(&pngme(["print", file]));
+    assert!(output.contains("(gamma 0.45455)"), "{output}");
+    assert!(output.contains("(Title: Sunset)"), "{output}");
+
+    let output = stdout(&pngme(["info", file]));
+    assert!(output.contains("#1 gAMA: gamma 0.45455"), "{output}");
+    assert!(output.contains("#2 tEXt: Title: Sunset"), "{output}");
+}
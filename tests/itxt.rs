@@ -0,0 +1,145 @@
+mod common;
+
+use std::io::Write;
+
+use common::*;
+use flate2::{Compression, write::ZlibEncoder};
+use pngme::png::Png;
+
+fn itxt_chunk_data(file: &std::path::Path) -> Vec<u8> {
+    let png = Png::try_from(std::fs::read(file).unwrap().as_slice()).unwrap();
+    png.chunk_by_type("iTXt").unwrap().data().to_vec()
+}
+
+#[test]
+fn message_is_written_as_international_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = path.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "iTXt",
+        "こんにちは 🦀",
+        "--itxt",
+        "Title",
+        "--language",
+        "ja",
+        "--translated-keyword",
+        "タイトル",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        itxt_chunk_data(&path),
+        "Title\0\0\0ja\0タイトル\0こんにちは 🦀".as_bytes()
+    );
+
+    let output = pngme(["decode", "--quiet", file, "iTXt"]);
+    assert_eq!(stdout(&output), "こんにちは 🦀\n");
+
+    let output = pngme(["decode", file, "iTXt"]);
+    assert_eq!(
+        stdout(&output),
+        "Keyword: Title\nLanguage: ja\nTranslated keyword: タイトル\nCompressed: no\nText: こんにちは 🦀\n"
+    );
+
+    let output = pngme(["decode", "--format", "json", file, "iTXt"]);
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(report["data"], "こんにちは 🦀");
+    assert_eq!(report["keyword"], "Title");
+    assert_eq!(report["language"], "ja");
+    assert_eq!(report["translated_keyword"], "タイトル");
+}
+
+#[test]
+fn compressed_text_is_inflated_on_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all("Café".as_bytes()).unwrap();
+    let data = [&b"Title\0\x01\0fr\0Titre\0"[..], &encoder.finish().unwrap()].concat();
+    let png = png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+        ("iTXt", &data),
+        ("IEND", &[]),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &png);
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["decode", file, "iTXt"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = stdout(&output);
+    assert!(output.contains("Compressed: yes\n"), "{output}");
+    assert!(output.contains("Text: Café\n"), "{output}");
+
+    let output = pngme(["--max-decompressed-size", "2", "decode", file, "iTXt"]);
+    assert!(
+        stderr(&output).contains("error[E0521]"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn invalid_fields_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "iTXt",
+        "text",
+        "--itxt",
+        "Title",
+        "--language",
+        "en_GB",
+    ]);
+    assert!(
+        stderr(&output).contains("error[E0710]"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = pngme(["encode", file, "iTXt", "text", "--itxt", " Title"]);
+    assert!(
+        stderr(&output).contains("error[E0703]"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn chunk_name_must_be_itxt() {
+    let output = pngme(["encode", "image.png", "ruSt", "text", "--itxt", "Title"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("--itxt can't be combined with a chunk name other than iTXt"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = pngme(["encode", "image.png", "iTXt", "text", "--language", "fr"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn bytes_that_are_not_utf8_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_fixture(dir.path(), "image.png", &fixture_png());
+    let message = write_fixture(dir.path(), "message.bin", b"caf\xe9");
+
+    let output = pngme([
+        "encode",
+        path.to_str().unwrap(),
+        "iTXt",
+        "--message-file",
+        message.to_str().unwrap(),
+        "--itxt",
+        "Title",
+    ]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("E0808"), "{}", stderr(&output));
+    assert_eq!(std::fs::read(&path).unwrap(), fixture_png());
+}
@@ -0,0 +1,224 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+use serde_json::Value;
+
+/// Three images under `dir/assets`, returned with the glob matching them
+fn assets(dir: &Path) -> (Vec<String>, String) {
+    let assets = dir.join("assets");
+    fs::create_dir(&assets).unwrap();
+    let files = ["a.png", "b.png", "c.png"].map(|name| {
+        write_fixture(&assets, name, &fixture_png())
+            .to_str()
+            .unwrap()
+            .to_string()
+    });
+    (files.to_vec(), format!("{}/*.png", assets.display()))
+}
+
+fn encode(glob: &str, message: &str, journal_flag: &str, journal: &Path) -> std::process::Output {
+    pngme([
+        "encode",
+        "--glob",
+        glob,
+        "--chunk",
+        "abCd",
+        "--message",
+        message,
+        journal_flag,
+        journal.to_str().unwrap(),
+    ])
+}
+
+fn message_chunks(file: &str) -> usize {
+    printed_chunks(&stdout(&pngme(["print", file])))
+        .iter()
+        .filter(|(_, chunk_type)| chunk_type == "abCd")
+        .count()
+}
+
+/// Runs the batch to the end, then puts the journal and the files back as
+/// they would be had it been killed while writing the third journal line,
+/// after `b.png` was replaced when `keep_b` is set
+fn interrupted(dir: &Path, keep_b: bool) -> (Vec<String>, String, std::path::PathBuf) {
+    let (files, glob) = assets(dir);
+    let journal = dir.join("batch.jsonl");
+    let output = encode(&glob, "watermark", "--journal", &journal);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let content = fs::read_to_string(&journal).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 6, "{content}");
+    let cut = &lines[3][..lines[3].len() / 2];
+    fs::write(
+        &journal,
+        format!("{}\n{}\n{}\n{cut}", lines[0], lines[1], lines[2]),
+    )
+    .unwrap();
+
+    if !keep_b {
+        fs::write(&files[1], fixture_png()).unwrap();
+    }
+    fs::write(&files[2], fixture_png()).unwrap();
+    (files, glob, journal)
+}
+
+#[test]
+fn records_each_file_of_the_batch() {
+    let dir = tempfile::tempdir().unwrap();
+    let (files, glob) = assets(dir.path());
+    let journal = dir.path().join("batch.jsonl");
+
+    let output = encode(&glob, "watermark", "--journal", &journal);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let entries: Vec<Value> = fs::read_to_string(&journal)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let statuses: Vec<(&str, &str)> = entries
+        .iter()
+        .map(|entry| {
+            (
+                entry["path"].as_str().unwrap(),
+                entry["status"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        statuses,
+        files
+            .iter()
+            .flat_map(|file| [(file.as_str(), "started"), (file.as_str(), "done")])
+            .collect::<Vec<_>>()
+    );
+    assert!(
+        entries
+            .iter()
+            .all(|entry| entry["operation"] == entries[0]["operation"])
+    );
+    assert!(entries[0]["sha256"].is_string());
+}
+
+#[test]
+fn resume_skips_the_files_already_encoded() {
+    let dir = tempfile::tempdir().unwrap();
+    let (files, glob, journal) = interrupted(dir.path(), true);
+
+    let output = encode(&glob, "watermark", "--resume", &journal);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}: done before\n{}: done before\n{}: encoded\nEncoded 1 of 3 file(s), 2 done before\n",
+            files[0], files[1], files[2]
+        )
+    );
+    for file in &files {
+        assert_eq!(message_chunks(file), 1, "{file}");
+    }
+}
+
+#[test]
+fn resume_encodes_a_started_file_left_unchanged() {
+    let dir = tempfile::tempdir().unwrap();
+    let (files, glob, journal) = interrupted(dir.path(), false);
+
+    let output = encode(&glob, "watermark", "--resume", &journal);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).ends_with("Encoded 2 of 3 file(s), 1 done before\n"),
+        "{}",
+        stdout(&output)
+    );
+    for file in &files {
+        assert_eq!(message_chunks(file), 1, "{file}");
+    }
+
+    // Everything is recorded done now
+    let output = encode(&glob, "watermark", "--resume", &journal);
+    assert!(
+        stdout(&output).ends_with("Encoded 0 of 3 file(s), 3 done before\n"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn resume_refuses_changed_options() {
+    let dir = tempfile::tempdir().unwrap();
+    let (files, glob, journal) = interrupted(dir.path(), true);
+
+    let output = encode(&glob, "another watermark", "--resume", &journal);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("error[E1403]"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(message_chunks(&files[2]), 0);
+}
+
+#[test]
+fn resume_needs_an_existing_journal() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_, glob) = assets(dir.path());
+
+    let output = encode(
+        &glob,
+        "watermark",
+        "--resume",
+        &dir.path().join("missing.jsonl"),
+    );
+    assert!(
+        stderr(&output).contains("error[E1301]"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn journals_a_transactional_batch_once_committed() {
+    let dir = tempfile::tempdir().unwrap();
+    let (files, glob) = assets(dir.path());
+    fs::write(&files[2], b"not a png").unwrap();
+    let journal = dir.path().join("batch.jsonl");
+
+    let output = pngme([
+        "encode",
+        "--glob",
+        &glob,
+        "--chunk",
+        "abCd",
+        "--message",
+        "watermark",
+        "--transactional",
+        "--journal",
+        journal.to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    let content = fs::read_to_string(&journal).unwrap();
+    assert!(!content.contains(r#""status":"done""#), "{content}");
+
+    // Nothing was written, so resuming encodes every file again
+    fs::write(&files[2], fixture_png()).unwrap();
+    let output = pngme([
+        "encode",
+        "--glob",
+        &glob,
+        "--chunk",
+        "abCd",
+        "--message",
+        "watermark",
+        "--transactional",
+        "--resume",
+        journal.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    for file in &files {
+        assert_eq!(message_chunks(file), 1, "{file}");
+    }
+}
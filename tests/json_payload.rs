@@ -0,0 +1,70 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+#[test]
+fn invalid_json_is_refused_before_the_image_is_touched() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "coNf",
+        r#"{"retries": 3,}"#,
+        "--payload-format",
+        "json",
+    ]);
+    assert_eq!(output.status.code(), Some(4));
+    let message = stderr(&output);
+    assert!(message.contains("E0809"), "{message}");
+    assert!(
+        message.contains("Invalid JSON at byte 14 (line 1, column 15): trailing comma"),
+        "{message}"
+    );
+    assert_eq!(fs::read(file).unwrap(), fixture_png());
+}
+
+#[test]
+fn decode_pretty_prints_json_payloads() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "coNf",
+        r#"{"retries":3,"hosts":["a"]}"#,
+        "--payload-format",
+        "json",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["decode", file, "coNf", "--payload-format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "{\n  \"hosts\": [\n    \"a\"\n  ],\n  \"retries\": 3\n}\n"
+    );
+}
+
+#[test]
+fn decode_fails_on_a_chunk_that_is_not_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "decode",
+        file.to_str().unwrap(),
+        "ruSt",
+        "--payload-format",
+        "json",
+    ]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr(&output).contains("E0809"), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "");
+}
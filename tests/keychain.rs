@@ -0,0 +1,38 @@
+#![cfg(feature = "keyring")]
+
+mod common;
+
+use common::*;
+
+#[test]
+fn passphrase_sources_are_exclusive() {
+    let output = pngme([
+        "key",
+        "store",
+        "work",
+        "--password",
+        "secret",
+        "--password-keychain",
+        "other",
+    ]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("cannot be used with"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn missing_passphrase_is_reported() {
+    let output = pngme(["key", "delete", "pngme-test-missing-entry"]);
+
+    // Either the entry is missing or there is no keychain to look into
+    assert!(!output.status.success());
+    let err = stderr(&output);
+    assert!(
+        err.contains("error[E1102]") || err.contains("error[E1103]"),
+        "{err}"
+    );
+}
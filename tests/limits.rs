@@ -0,0 +1,42 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn max_chunks_rejects_files_over_the_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "print".as_ref(),
+        file.as_os_str(),
+        "--max-chunks".as_ref(),
+        "6".as_ref(),
+    ]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("more than 6 chunks"));
+
+    let output = pngme([
+        "--max-chunks".as_ref(),
+        "7".as_ref(),
+        "print".as_ref(),
+        file.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn max_chunks_limits_the_commands_walking_the_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    for command in ["scan", "info", "verify"] {
+        let output = pngme(["--max-chunks", "6", command, file]);
+        assert!(!output.status.success(), "{command}");
+        assert!(stderr(&output).contains("error[E0105]"), "{command}: {}", stderr(&output));
+
+        let output = pngme(["--max-chunks", "7", command, file]);
+        assert!(output.status.success(), "{command}: {}", stderr(&output));
+    }
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use common::*;
+use pngme::{
+    commands::{Context, EncodeOptions, encode},
+    error::PngMeError,
+    input::InputSource,
+    lock::{FileLock, LockError},
+    observer::NoopObserver,
+    png::Png,
+};
+
+fn encode_in_place(
+    file: &Path,
+    chunk_type: &str,
+    lock_timeout: Duration,
+) -> Result<(), PngMeError> {
+    let ctx = Context {
+        lock_timeout,
+        ..Context::new(&NoopObserver)
+    };
+
+    encode(
+        &InputSource::Path(file.to_path_buf()),
+        chunk_type,
+        b"message",
+        &None,
+        &EncodeOptions::default(),
+        &ctx,
+    )
+}
+
+fn temp_image() -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    (dir, file)
+}
+
+#[test]
+fn concurrent_in_place_edits_are_serialized() {
+    let (_dir, file) = temp_image();
+
+    thread::scope(|scope| {
+        for chunk_type in ["teSa", "teSb", "teSc", "teSd"] {
+            let file = &file;
+            scope
+                .spawn(move || encode_in_place(file, chunk_type, Duration::from_secs(30)).unwrap());
+        }
+    });
+
+    let png = Png::try_from(std::fs::read(&file).unwrap().as_slice()).unwrap();
+    for chunk_type in ["teSa", "teSb", "teSc", "teSd"] {
+        assert!(png.chunk_by_type(chunk_type).is_some(), "lost {chunk_type}");
+    }
+}
+
+#[test]
+fn edit_waits_for_lock_holder() {
+    let (_dir, file) = temp_image();
+    let lock = FileLock::acquire(&file, Duration::ZERO).unwrap();
+
+    thread::scope(|scope| {
+        let editor = scope.spawn(|| encode_in_place(&file, "teSt", Duration::from_secs(30)));
+
+        thread::sleep(Duration::from_millis(200));
+        assert!(!editor.is_finished());
+        drop(lock);
+
+        editor.join().unwrap().unwrap();
+    });
+
+    let png = Png::try_from(std::fs::read(&file).unwrap().as_slice()).unwrap();
+    assert!(png.chunk_by_type("teSt").is_some());
+}
+
+#[test]
+fn edit_fails_after_lock_timeout() {
+    let (_dir, file) = temp_image();
+    let _lock = FileLock::acquire(&file, Duration::ZERO).unwrap();
+
+    let result = thread::scope(|scope| {
+        scope
+            .spawn(|| encode_in_place(&file, "teSt", Duration::from_millis(100)))
+            .join()
+            .unwrap()
+    });
+
+    let err = result.unwrap_err();
+    assert!(matches!(err, PngMeError::Lock(LockError::Locked { .. })));
+    assert!(
+        err.to_string()
+            .ends_with("file is locked by another process")
+    );
+    assert_eq!(std::fs::read(&file).unwrap(), fixture_png());
+}
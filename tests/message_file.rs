@@ -0,0 +1,104 @@
+mod common;
+
+use common::*;
+
+fn raw_payload(file: &str) -> Vec<u8> {
+    let output = pngme(["decode", "--raw", file, "abCd"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    output.stdout
+}
+
+#[test]
+fn message_is_read_from_a_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let message = write_fixture(dir.path(), "message.txt", b"line one\n'$(quoted)'\n");
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "--message-file",
+        message.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(raw_payload(file), b"line one\n'$(quoted)'\n");
+}
+
+#[test]
+fn binary_files_are_embedded_as_is() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let bytes = [0x00, 0xFF, 0xFE, 0x80, b'\n'];
+    let message = write_fixture(dir.path(), "message.bin", &bytes);
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "--message-file",
+        message.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(raw_payload(file), bytes);
+}
+
+#[test]
+fn message_file_excludes_the_message() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let message = write_fixture(dir.path(), "message.txt", b"hello");
+
+    let output = pngme([
+        "encode",
+        file.to_str().unwrap(),
+        "abCd",
+        "hello",
+        "--message-file",
+        message.to_str().unwrap(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("cannot be used with"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn a_message_is_required() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["encode", file.to_str().unwrap(), "abCd"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("<MESSAGE>"), "{}", stderr(&output));
+}
+
+#[test]
+fn missing_message_file_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let missing = dir.path().join("missing.txt");
+
+    let output = pngme([
+        "encode",
+        file.to_str().unwrap(),
+        "abCd",
+        "--message-file",
+        missing.to_str().unwrap(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(
+        stderr(&output).contains("--message-file: "),
+        "{}",
+        stderr(&output)
+    );
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::*;
+
+fn raw_payload(file: &str) -> Vec<u8> {
+    let output = pngme(["decode", "--raw", file, "abCd"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    output.stdout
+}
+
+#[test]
+fn dash_reads_the_message_from_stdin() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme_with_stdin(&["encode", file, "abCd", "-"], b"piped\x00secret\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(raw_payload(file), b"piped\x00secret\n");
+}
+
+#[test]
+fn empty_stdin_is_an_empty_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme_with_stdin(&["encode", file, "abCd", "--message", "-"], b"");
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(raw_payload(file), b"");
+}
+
+#[test]
+fn stdin_cannot_hold_both_the_image_and_the_message() {
+    let output = pngme_with_stdin(&["encode", "-", "abCd", "-"], &fixture_png());
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("FILE can't be combined with MESSAGE"),
+        "{}",
+        stderr(&output)
+    );
+}
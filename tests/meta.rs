@@ -0,0 +1,119 @@
+mod common;
+
+use common::*;
+
+fn ancillary(bytes: &[u8]) -> Vec<Vec<u8>> {
+    let png = pngme::png::Png::try_from(bytes).unwrap();
+    png.chunks()
+        .iter()
+        .filter(|chunk| !chunk.chunk_type().is_critical())
+        .map(|chunk| chunk.as_bytes())
+        .collect()
+}
+
+#[test]
+fn round_trip_through_stripped_image() {
+    let dir = tempfile::tempdir().unwrap();
+    let original = png_bytes(&[
+        ("IHDR", b"header"),
+        ("gAMA", b"\0\0\xb1\x8f"),
+        ("IDAT", b"pixels"),
+        ("tEXt", b"Comment\0kept"),
+        ("ruSt", b"hidden message"),
+        ("IEND", b""),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &original);
+    let sidecar = dir.path().join("image.pngmeta");
+
+    let output = pngme([
+        "export-meta".as_ref(),
+        file.as_os_str(),
+        sidecar.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme([
+        "strip".as_ref(),
+        file.as_os_str(),
+        "--strip-color".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(ancillary(&std::fs::read(&file).unwrap()).is_empty());
+
+    let output = pngme([
+        "import-meta".as_ref(),
+        file.as_os_str(),
+        sidecar.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output).trim(), "Imported 3 chunk(s)");
+
+    assert_eq!(
+        ancillary(&std::fs::read(&file).unwrap()),
+        ancillary(&original)
+    );
+}
+
+#[test]
+fn import_follows_conflict_policy() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let sidecar = dir.path().join("image.pngmeta");
+
+    let output = pngme([
+        "export-meta".as_ref(),
+        file.as_os_str(),
+        sidecar.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme([
+        "import-meta".as_ref(),
+        file.as_os_str(),
+        sidecar.as_os_str(),
+    ]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("--on-conflict"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = pngme([
+        "import-meta".as_ref(),
+        file.as_os_str(),
+        sidecar.as_os_str(),
+        "--on-conflict".as_ref(),
+        "skip".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output).trim(), "Imported 0 chunk(s)");
+    assert_eq!(std::fs::read(&file).unwrap(), fixture_png());
+}
+
+#[test]
+fn import_rejects_a_sidecar_with_critical_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let original = fixture_png();
+    let file = write_fixture(dir.path(), "image.png", &original);
+    // A second IHDR and extra image data, which export never writes
+    let sidecar = write_fixture(
+        dir.path(),
+        "malicious.pngmeta",
+        br#"{"version":1,"chunks":[
+            {"type":"IHDR","flags":{"public":true,"safe_to_copy":false},"placement":"before-plte","data":"AAAAAQAAAAEIBgAAAA=="},
+            {"type":"IDAT","flags":{"public":true,"safe_to_copy":false},"placement":"after-idat","data":"eJw="}
+        ]}"#,
+    );
+
+    let output = pngme([
+        "import-meta".as_ref(),
+        file.as_os_str(),
+        sidecar.as_os_str(),
+        "--on-conflict".as_ref(),
+        "append".as_ref(),
+    ]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr(&output).contains("error[E0911]"), "{}", stderr(&output));
+    assert_eq!(std::fs::read(&file).unwrap(), original);
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+#[test]
+fn decode_and_print_show_the_metadata() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "photo.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "hello",
+        "--meta",
+        "--meta-author",
+        "Alice",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let decoded = stdout(&pngme(["decode", file, "abCd"]));
+    assert!(decoded.contains("data: hello,"), "{decoded}");
+    let line = decoded.lines().last().unwrap();
+    assert!(
+        line.starts_with("(metadata of abCd: by Alice on "),
+        "{decoded}"
+    );
+    assert!(
+        line.ends_with(&format!(
+            " with pngme {} in photo.png)",
+            env!("CARGO_PKG_VERSION")
+        )),
+        "{decoded}"
+    );
+
+    let printed = stdout(&pngme(["print", file]));
+    assert!(printed.contains("meTa"), "{printed}");
+    assert!(
+        printed.contains("(metadata of abCd: by Alice on "),
+        "{printed}"
+    );
+
+    let json = stdout(&pngme(["decode", file, "abCd", "--format", "json"]));
+    assert!(
+        json.contains(r#""metadata":{"chunk":"abCd","author":"Alice""#),
+        "{json}"
+    );
+}
+
+#[test]
+fn deterministic_runs_write_the_same_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("out.png");
+    let output = output.to_str().unwrap();
+
+    // The name of the image is recorded, the copies have the same one
+    let mut written = Vec::new();
+    for copy in ["first", "second"] {
+        fs::create_dir(dir.path().join(copy)).unwrap();
+        let file = write_fixture(&dir.path().join(copy), "image.png", &fixture_png());
+        let result = pngme([
+            "encode",
+            file.to_str().unwrap(),
+            "abCd",
+            "hello",
+            "--meta",
+            "--deterministic",
+            "--output",
+            output,
+        ]);
+        assert!(result.status.success(), "{}", stderr(&result));
+        written.push(fs::read(output).unwrap());
+    }
+    assert_eq!(written[0], written[1]);
+
+    let decoded = stdout(&pngme(["decode", output, "abCd"]));
+    assert!(!decoded.contains(" on "), "{decoded}");
+}
+
+#[test]
+fn no_meta_overrides_meta() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", file, "abCd", "hello", "--meta", "--no-meta"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stdout(&pngme(["print", file])).contains("meTa"));
+}
+
+#[test]
+fn replacing_a_message_replaces_its_metadata() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    for author in ["Alice", "Bob"] {
+        let output = pngme([
+            "encode",
+            file,
+            "abCd",
+            "hello",
+            "--meta",
+            "--meta-author",
+            author,
+            "--replace",
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+
+    let printed = stdout(&pngme(["print", file]));
+    assert_eq!(printed.matches("meTa").count(), 1, "{printed}");
+    assert!(printed.contains("by Bob"), "{printed}");
+}
+
+#[test]
+fn replacing_a_message_without_metadata_drops_the_old_one() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "hello",
+        "--meta",
+        "--meta-author",
+        "Alice",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = pngme(["encode", file, "abCd", "world", "--replace"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert!(!stdout(&pngme(["print", file])).contains("meTa"));
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+
+const WARNING: &str = "payload appears to already be a pngme envelope";
+
+/// Encodes the bytes of `payload` compressed into a copy of the fixture,
+/// returning the image and what encode printed on stderr
+fn encode_payload(dir: &Path, name: &str, payload: &[u8], flags: &[&str]) -> (String, String) {
+    let file = write_fixture(dir, name, &fixture_png());
+    let file = file.to_str().unwrap().to_string();
+    let message_file = dir.join(format!("{name}.payload"));
+    fs::write(&message_file, payload).unwrap();
+
+    let mut args = vec![
+        "encode",
+        &file,
+        "abCd",
+        "--message-file",
+        message_file.to_str().unwrap(),
+        "--compress",
+    ];
+    args.extend(flags);
+    let output = pngme(&args);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    (file, stderr(&output))
+}
+
+/// The payload of the abCd chunk of `file` as stored
+fn raw_payload(file: &str) -> Vec<u8> {
+    let output = pngme(["decode", "--raw", file, "abCd"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    output.stdout
+}
+
+fn decoded(file: &str, flags: &[&str]) -> String {
+    let mut args = vec!["decode", "--quiet", file, "abCd"];
+    args.extend(flags);
+    stdout(&pngme(&args))
+}
+
+#[test]
+fn encoding_an_envelope_warns() {
+    let dir = tempfile::tempdir().unwrap();
+    let (plain, message) = encode_payload(dir.path(), "plain.png", b"hello", &[]);
+    assert!(!message.contains(WARNING), "{message}");
+
+    let (nested, message) = encode_payload(dir.path(), "nested.png", &raw_payload(&plain), &[]);
+    assert!(message.contains(WARNING), "{message}");
+    assert!(message.contains("--unwrap"), "{message}");
+    // Decode shows the inner envelope, not the message
+    assert_ne!(decoded(&nested, &[]), "hello\n");
+
+    let (_, message) = encode_payload(
+        dir.path(),
+        "anyway.png",
+        &raw_payload(&plain),
+        &["--wrap-anyway"],
+    );
+    assert!(!message.contains(WARNING), "{message}");
+}
+
+#[test]
+fn unwrap_embeds_the_inner_payload() {
+    let dir = tempfile::tempdir().unwrap();
+    let (plain, _) = encode_payload(dir.path(), "plain.png", b"hello", &[]);
+
+    let (single, message) = encode_payload(
+        dir.path(),
+        "single.png",
+        &raw_payload(&plain),
+        &["--unwrap"],
+    );
+    assert!(!message.contains(WARNING), "{message}");
+    assert_eq!(decoded(&single, &[]), "hello\n");
+
+    // A doubly nested payload loses one level only
+    let (nested, _) = encode_payload(dir.path(), "nested.png", &raw_payload(&plain), &[]);
+    let (double, _) = encode_payload(
+        dir.path(),
+        "double.png",
+        &raw_payload(&nested),
+        &["--unwrap"],
+    );
+    assert_ne!(decoded(&double, &[]), "hello\n");
+    assert_eq!(decoded(&double, &["--unwrap-all"]), "hello\n");
+}
+
+#[test]
+fn unwrap_all_peels_every_level() {
+    let dir = tempfile::tempdir().unwrap();
+    let (plain, _) = encode_payload(dir.path(), "plain.png", b"hello", &[]);
+    let (nested, _) = encode_payload(dir.path(), "nested.png", &raw_payload(&plain), &[]);
+    let (double, _) = encode_payload(dir.path(), "double.png", &raw_payload(&nested), &[]);
+
+    assert_eq!(decoded(&plain, &["--unwrap-all"]), "hello\n");
+    assert_eq!(decoded(&nested, &["--unwrap-all"]), "hello\n");
+    assert_eq!(decoded(&double, &["--unwrap-all"]), "hello\n");
+
+    let output = pngme(["decode", "--raw", "--unwrap-all", &double, "abCd"]);
+    assert_eq!(output.status.code(), Some(2));
+}
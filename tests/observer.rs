@@ -0,0 +1,234 @@
+mod common;
+
+use std::cell::RefCell;
+
+use common::*;
+use pngme::{
+    commands::{Context, EncodeOptions, encode},
+    input::InputSource,
+    observer::{Observer, Stage},
+    png::ParseWarning,
+    timings::StatsObserver,
+};
+
+#[derive(Default)]
+struct RecordingObserver {
+    progress: RefCell<Vec<(Stage, u64, Option<u64>)>>,
+    warnings: RefCell<Vec<ParseWarning>>,
+}
+
+impl Observer for RecordingObserver {
+    fn on_progress(&self, stage: Stage, done: u64, total: Option<u64>) {
+        self.progress.borrow_mut().push((stage, done, total));
+    }
+
+    fn on_warning(&self, warning: &ParseWarning) {
+        self.warnings.borrow_mut().push(warning.clone());
+    }
+}
+
+impl RecordingObserver {
+    fn stages(&self) -> Vec<Stage> {
+        let mut stages: Vec<Stage> = self
+            .progress
+            .borrow()
+            .iter()
+            .map(|(stage, _, _)| *stage)
+            .collect();
+        stages.dedup();
+        stages
+    }
+
+    fn events(&self, stage: Stage) -> Vec<(u64, Option<u64>)> {
+        self.progress
+            .borrow()
+            .iter()
+            .filter(|(event_stage, _, _)| *event_stage == stage)
+            .map(|(_, done, total)| (*done, *total))
+            .collect()
+    }
+}
+
+/// 8 MiB of image data split over 128 IDAT chunks
+fn large_png() -> Vec<u8> {
+    let idat = vec![0xAB; 64 * 1024];
+    let mut chunks: Vec<(&str, &[u8])> = vec![("IHDR", &[0, 0, 4, 0, 0, 0, 4, 0, 8, 6, 0, 0, 0])];
+    chunks.extend(std::iter::repeat_n(("IDAT", idat.as_slice()), 128));
+    chunks.push(("IEND", &[]));
+    png_bytes(&chunks)
+}
+
+#[test]
+fn encode_reports_stages_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = large_png();
+    let file = write_fixture(dir.path(), "large.png", &input);
+    let output = dir.path().join("out.png");
+
+    let observer = RecordingObserver::default();
+    encode(
+        &InputSource::Path(file.clone()),
+        "ruSt",
+        b"message",
+        &Some(output.clone()),
+        &EncodeOptions::default(),
+        &Context::new(&observer),
+    )
+    .unwrap();
+
+    assert_eq!(
+        observer.stages(),
+        [Stage::Read, Stage::Parse, Stage::Embed, Stage::Write]
+    );
+    assert!(observer.warnings.borrow().is_empty());
+
+    let total = input.len() as u64;
+    assert_eq!(
+        observer.events(Stage::Read).last(),
+        Some(&(total, Some(total)))
+    );
+
+    let parse = observer.events(Stage::Parse);
+    // one event after the signature and one per chunk
+    assert_eq!(parse.len(), 1 + 130);
+    assert!(parse.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert_eq!(parse.last(), Some(&(total, Some(total))));
+
+    assert_eq!(observer.events(Stage::Embed), [(0, Some(1)), (1, Some(1))]);
+
+    let written = std::fs::metadata(&output).unwrap().len();
+    assert_eq!(
+        observer.events(Stage::Write).last(),
+        Some(&(written, Some(written)))
+    );
+}
+
+#[test]
+fn encode_forwards_parse_warnings() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut input = fixture_png();
+    input.extend_from_slice(&[0, 0]);
+    let file = write_fixture(dir.path(), "trailing.png", &input);
+
+    let observer = RecordingObserver::default();
+    encode(
+        &InputSource::Path(file.clone()),
+        "ruSt",
+        b"message",
+        &None,
+        &EncodeOptions::default(),
+        &Context::new(&observer),
+    )
+    .unwrap();
+
+    assert_eq!(
+        *observer.warnings.borrow(),
+        [ParseWarning::TrailingBytes {
+            range: input.len() as u64 - 2..input.len() as u64
+        }]
+    );
+}
+
+#[test]
+fn encode_records_timings() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = large_png();
+    let file = write_fixture(dir.path(), "large.png", &input);
+    let output = dir.path().join("out.png");
+
+    let recording = RecordingObserver::default();
+    let observer = StatsObserver::new(&recording);
+    encode(
+        &InputSource::Path(file.clone()),
+        "ruSt",
+        b"message",
+        &Some(output.clone()),
+        &EncodeOptions::default(),
+        &Context::new(&observer),
+    )
+    .unwrap();
+    let report = observer.timings.report();
+
+    let stages: Vec<Stage> = report.stages.iter().map(|timing| timing.stage).collect();
+    assert_eq!(
+        stages,
+        [
+            Stage::Read,
+            Stage::Parse,
+            Stage::Embed,
+            Stage::Serialize,
+            Stage::Write
+        ]
+    );
+    // Events still reach the wrapped observer
+    assert_eq!(recording.stages().first(), Some(&Stage::Read));
+
+    let written = std::fs::metadata(&output).unwrap().len();
+    let peaks: Vec<u64> = report
+        .stages
+        .iter()
+        .map(|timing| timing.peak_bytes)
+        .collect();
+    assert_eq!(
+        peaks,
+        [input.len() as u64, input.len() as u64, 7, written, written]
+    );
+
+    let spans: f64 = report.stages.iter().map(|timing| timing.seconds).sum();
+    assert!(report.stages.iter().all(|timing| timing.seconds >= 0.0));
+    assert!(report.other_seconds >= 0.0);
+    assert!(spans <= report.total_seconds);
+    assert!((spans + report.other_seconds - report.total_seconds).abs() < 1e-6);
+}
+
+#[test]
+fn stats_flag_prints_timings() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["--stats".as_ref(), "info".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let err = stderr(&output);
+    assert!(err.contains("Stage"), "{err}");
+    assert!(err.contains("read"), "{err}");
+    assert!(err.contains("total"), "{err}");
+
+    let output = pngme([
+        "--stats".as_ref(),
+        "verify".as_ref(),
+        "--format".as_ref(),
+        "json".as_ref(),
+        file.as_os_str(),
+    ]);
+    // Nothing else is printed for a valid file
+    let stats: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(stats["stats"]["stages"][0]["stage"], "read");
+    assert!(stats["stats"]["total_seconds"].as_f64().unwrap() >= 0.0);
+}
+
+#[test]
+fn json_stats_are_part_of_the_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let decode = |chunk_type: &str| {
+        pngme([
+            "--stats".as_ref(),
+            "decode".as_ref(),
+            "--format".as_ref(),
+            "json".as_ref(),
+            file.as_os_str(),
+            chunk_type.as_ref(),
+        ])
+    };
+
+    let output = decode("ruSt");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(report["data"], "hidden message");
+    assert_eq!(report["stats"]["stages"][0]["stage"], "read");
+
+    let output = decode("noNe");
+    assert!(!output.status.success());
+    let error: serde_json::Value = serde_json::from_str(&stderr(&output)).unwrap();
+    assert!(error["error"]["stats"]["total_seconds"].as_f64().is_some(), "{error}");
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use std::path::Path;
+
+use common::*;
+
+fn encode_to(dir: &Path, output: &str, flags: &[&str]) -> std::process::Output {
+    let file = write_fixture(dir, "image.png", &fixture_png());
+    let output = dir.join(output);
+    let mut args = vec![
+        "encode",
+        file.to_str().unwrap(),
+        "abCd",
+        "hello",
+        output.to_str().unwrap(),
+    ];
+    args.extend(flags);
+    pngme(args)
+}
+
+#[test]
+fn image_extensions_are_accepted_in_any_case() {
+    for name in ["out.png", "out.PNG", "out.apng", "out.aPnG"] {
+        let dir = tempfile::tempdir().unwrap();
+
+        let output = encode_to(dir.path(), name, &[]);
+
+        assert!(output.status.success(), "{name}: {}", stderr(&output));
+        assert!(
+            !stderr(&output).contains("warning"),
+            "{name}: {}",
+            stderr(&output)
+        );
+        assert!(dir.path().join(name).exists(), "{name}");
+    }
+}
+
+#[test]
+fn other_extensions_are_a_warning() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = encode_to(dir.path(), "photo.jpg", &[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("photo.jpg doesn't end in .png"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(dir.path().join("photo.jpg").exists());
+}
+
+#[test]
+fn strict_extension_refuses_other_extensions() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = encode_to(dir.path(), "message.txt", &["--strict-extension"]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("error[E0510]"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!dir.path().join("message.txt").exists());
+}
+
+#[test]
+fn png_is_added_to_paths_without_extension() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = encode_to(dir.path(), "out", &["--strict-extension"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(dir.path().join("out.png").exists());
+    assert!(!dir.path().join("out").exists());
+}
+
+#[test]
+fn no_ext_fixup_keeps_the_path() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = encode_to(dir.path(), "out", &["--no-ext-fixup"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(dir.path().join("out").exists());
+    assert!(!dir.path().join("out.png").exists());
+}
+
+#[test]
+fn files_edited_in_place_keep_their_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.dat", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", "--strict-extension", file, "abCd", "hello"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("warning"), "{}", stderr(&output));
+    let output = pngme(["decode", "--quiet", "--strict-extension", file, "abCd"]);
+    assert_eq!(stdout(&output), "hello\n");
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use common::*;
+
+fn decode(file: &str, chunk_type: &str) -> String {
+    stdout(&pngme(["decode", "--quiet", file, chunk_type]))
+}
+
+#[test]
+fn pairs_are_encoded_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "--pair",
+        "auTh=Ada",
+        "--pair",
+        "liCe=CC-BY",
+        "--pair",
+        "soFt=pngme",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(
+        chunk_types(file),
+        [
+            "IHDR", "teXt", "IDAT", "IDAT", "IDAT", "ruSt", "auTh", "liCe", "soFt", "IEND"
+        ]
+    );
+    assert_eq!(decode(file, "auTh"), "Ada\n");
+    assert_eq!(decode(file, "liCe"), "CC-BY\n");
+    assert_eq!(decode(file, "soFt"), "pngme\n");
+}
+
+#[test]
+fn pairs_keep_their_order_after_ihdr() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "--pair",
+        "auTh=Ada",
+        "--pair",
+        "liCe=CC-BY",
+        "--position",
+        "after-ihdr",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(chunk_types(file)[..4], ["IHDR", "auTh", "liCe", "teXt"]);
+}
+
+#[test]
+fn one_invalid_pair_aborts_before_writing() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = path.to_str().unwrap();
+
+    for bad in ["li5e=CC-BY", "LiCe=CC-BY", "liCe="] {
+        let output = pngme(["encode", file, "--pair", "auTh=Ada", "--pair", bad]);
+        assert!(!output.status.success(), "{bad}");
+        assert_eq!(std::fs::read(&path).unwrap(), fixture_png(), "{bad}");
+    }
+}
+
+#[test]
+fn pairs_go_through_replace_and_the_template() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "--pair",
+        "ruSt=replaced",
+        "--pair",
+        "auTh={{who}}",
+        "--replace",
+        "--template",
+        "--var",
+        "who=Ada",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(
+        chunk_types(file),
+        [
+            "IHDR", "teXt", "IDAT", "IDAT", "IDAT", "ruSt", "auTh", "IEND"
+        ]
+    );
+    assert_eq!(decode(file, "ruSt"), "replaced\n");
+    assert_eq!(decode(file, "auTh"), "Ada\n");
+}
+
+#[test]
+fn pairs_conflict_with_a_single_message() {
+    let output = pngme(["encode", "image.png", "abCd", "hello", "--pair", "auTh=Ada"]);
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = pngme(["encode", "image.png", "--pair", "no-separator"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("expected NAME=VALUE"));
+}
+
+#[test]
+fn one_chunk_name_is_replaced_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = path.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "--pair",
+        "ruSt=a",
+        "--pair",
+        "ruSt=b",
+        "--replace",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output)
+            .contains("--replace can't be combined with two --pair of the same chunk name"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(std::fs::read(&path).unwrap(), fixture_png());
+}
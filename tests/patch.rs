@@ -0,0 +1,101 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+#[test]
+fn reversing_the_patches_of_encode_and_remove_restores_the_original() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let encoded = dir.path().join("encode.patch");
+    let removed = dir.path().join("remove.patch");
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "hello",
+        "--emit-patch",
+        encoded.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = pngme([
+        "remove",
+        file,
+        "ruSt",
+        "--emit-patch",
+        removed.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let edited = fs::read(file).unwrap();
+
+    let patch = fs::read_to_string(&removed).unwrap();
+    assert!(patch.contains(r#""op":"remove","index":"#), "{patch}");
+    assert!(patch.contains(r#""chunk_type":"ruSt","old":""#), "{patch}");
+
+    let output = pngme(["apply-patch", file, removed.to_str().unwrap(), "--reverse"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "Reversed 1 operation(s)\n");
+    let output = pngme(["apply-patch", file, encoded.to_str().unwrap(), "--reverse"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read(file).unwrap(), fixture_png());
+
+    // Forward again, the patches redo the edits
+    for patch in [&encoded, &removed] {
+        let output = pngme(["apply-patch", file, patch.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+    assert_eq!(fs::read(file).unwrap(), edited);
+}
+
+#[test]
+fn a_patch_is_refused_on_another_image() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let patch = dir.path().join("encode.patch");
+    let copy = dir.path().join("copy.png");
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "hello",
+        "--output",
+        copy.to_str().unwrap(),
+        "--emit-patch",
+        patch.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // Reversing needs the edited image, not the original
+    let output = pngme(["apply-patch", file, patch.to_str().unwrap(), "--reverse"]);
+    assert!(!output.status.success());
+    let stderr = stderr(&output);
+    assert!(stderr.contains("E0910"), "{stderr}");
+    assert!(
+        stderr.contains("The image is not the one the patch made"),
+        "{stderr}"
+    );
+    assert_eq!(fs::read(file).unwrap(), fixture_png());
+}
+
+#[test]
+fn dry_runs_emit_no_patch() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let patch = dir.path().join("remove.patch");
+
+    let output = pngme([
+        "remove",
+        file.to_str().unwrap(),
+        "ruSt",
+        "--dry-run",
+        "--emit-patch",
+        patch.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!patch.exists());
+}
@@ -0,0 +1,83 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+use common::*;
+
+fn make_read_only(path: &Path) {
+    fs::set_permissions(path, fs::Permissions::from_mode(0o444)).unwrap();
+}
+
+#[test]
+fn encode_in_place_fails_early_on_read_only_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "a.png", &fixture_png());
+    make_read_only(&file);
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "noTe".as_ref(),
+        "hello".as_ref(),
+    ]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains(&format!(
+        "Destination is not writable: {} (read-only), pass --output to write elsewhere",
+        file.display()
+    )));
+    assert_eq!(fs::read(&file).unwrap(), fixture_png());
+}
+
+#[test]
+fn encode_read_only_input_with_output_succeeds() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "a.png", &fixture_png());
+    let out = dir.path().join("out.png");
+    make_read_only(&file);
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "noTe".as_ref(),
+        "hello".as_ref(),
+        out.as_os_str(),
+    ]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(out.exists());
+}
+
+#[test]
+fn remove_fails_early_on_read_only_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "a.png", &fixture_png());
+    make_read_only(&file);
+
+    let output = pngme(["remove".as_ref(), file.as_os_str(), "ruSt".as_ref()]);
+    assert!(stderr(&output).contains("(read-only)"));
+    assert_eq!(fs::read(&file).unwrap(), fixture_png());
+
+    let out = dir.path().join("out.png");
+    let output = pngme([
+        "remove".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+        "--output".as_ref(),
+        out.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stdout(&pngme(["print".as_ref(), out.as_os_str()])).contains("ruSt"));
+}
+
+#[test]
+fn read_only_commands_work_on_read_only_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "a.png", &fixture_png());
+    make_read_only(&file);
+
+    let output = pngme(["decode".as_ref(), file.as_os_str(), "ruSt".as_ref()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::*;
+
+fn encode_at(position: Option<&str>) -> Vec<String> {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let mut args = vec!["encode", file, "abCd", "hello"];
+    if let Some(position) = position {
+        args.extend(["--position", position]);
+    }
+    let output = pngme(args);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    chunk_types(file)
+}
+
+#[test]
+fn chunk_goes_before_iend_by_default() {
+    let expected = [
+        "IHDR", "teXt", "IDAT", "IDAT", "IDAT", "ruSt", "abCd", "IEND",
+    ];
+
+    assert_eq!(encode_at(None), expected);
+    assert_eq!(encode_at(Some("before-iend")), expected);
+}
+
+#[test]
+fn chunk_goes_after_ihdr() {
+    assert_eq!(
+        encode_at(Some("after-ihdr")),
+        [
+            "IHDR", "abCd", "teXt", "IDAT", "IDAT", "IDAT", "ruSt", "IEND"
+        ]
+    );
+}
+
+#[test]
+fn chunk_goes_at_an_index() {
+    assert_eq!(
+        encode_at(Some("2")),
+        [
+            "IHDR", "teXt", "abCd", "IDAT", "IDAT", "IDAT", "ruSt", "IEND"
+        ]
+    );
+}
+
+#[test]
+fn missing_anchor_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = png_bytes(&[("IDAT", b"data"), ("IEND", b"")]);
+    let file = write_fixture(dir.path(), "image.png", &png);
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", file, "abCd", "hello", "--position", "after-ihdr"]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("error[E0303]"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(
+        stderr(&output).contains("no IHDR chunk"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = pngme(["encode", file, "abCd", "hello", "--position", "9"]);
+    assert!(
+        stderr(&output).contains("error[E0302]"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn unknown_position_is_a_usage_error() {
+    let output = pngme([
+        "encode",
+        "image.png",
+        "abCd",
+        "hello",
+        "--position",
+        "middle",
+    ]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("before-iend, after-ihdr or an index"));
+}
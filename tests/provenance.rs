@@ -0,0 +1,105 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::*;
+
+fn image(dir: &Path) -> PathBuf {
+    write_fixture(dir, "image.png", &fixture_png())
+}
+
+fn encode(file: &Path, extra: &[&str]) {
+    let mut args = vec!["encode", file.to_str().unwrap(), "apIe", "hello"];
+    args.extend_from_slice(extra);
+
+    let output = pngme(args);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn annotations_survive_a_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image(dir.path());
+    encode(
+        &file,
+        &[
+            "--annotate",
+            "--annotation",
+            "alice/quarterly audit",
+            "--annotation-date",
+            "2025-01-01T00:00:00Z",
+        ],
+    );
+    let version = env!("CARGO_PKG_VERSION");
+    let written =
+        format!("written by pngme {version} on 2025-01-01T00:00:00Z: alice/quarterly audit");
+
+    let output = pngme(["decode", file.to_str().unwrap(), "apIe"]);
+    assert_eq!(stdout(&output), format!("hello\n({written})\n"));
+
+    let output = pngme(["decode", "--quiet", file.to_str().unwrap(), "apIe"]);
+    assert_eq!(stdout(&output), "hello\n");
+
+    let output = pngme(["decode", "--format", "json", file.to_str().unwrap(), "apIe"]);
+    assert!(
+        stdout(&output).contains(&format!(
+            r#""provenance":{{"version":"{version}","created_at":1735689600,"annotation":"alice/quarterly audit"}}"#
+        )),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = pngme(["print", file.to_str().unwrap()]);
+    assert!(stdout(&output).contains(&written), "{}", stdout(&output));
+
+    let output = pngme(["scan", "--provenance", file.to_str().unwrap()]);
+    assert_eq!(stdout(&output), format!("#6 apIe: {written}\n"));
+}
+
+#[test]
+fn no_annotation_without_the_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image(dir.path());
+    encode(&file, &[]);
+
+    let output = pngme(["decode", file.to_str().unwrap(), "apIe"]);
+    assert!(!stdout(&output).contains("written by"));
+
+    let output = pngme(["decode", "--format", "json", file.to_str().unwrap(), "apIe"]);
+    assert!(!stdout(&output).contains("provenance"));
+
+    let output = pngme(["scan", "--provenance", file.to_str().unwrap()]);
+    assert_eq!(stdout(&output), "No chunks written by pngme\n");
+}
+
+#[test]
+fn deterministic_annotations_leave_the_time_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image(dir.path());
+    encode(&file, &["--annotate", "--deterministic"]);
+
+    let output = pngme(["decode", file.to_str().unwrap(), "apIe"]);
+    assert_eq!(
+        stdout(&output),
+        format!("hello\n(written by pngme {})\n", env!("CARGO_PKG_VERSION"))
+    );
+}
+
+#[test]
+fn annotation_requires_annotate() {
+    let output = pngme([
+        "encode",
+        "image.png",
+        "apIe",
+        "hello",
+        "--annotation",
+        "who/why",
+    ]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("--annotate"),
+        "{}",
+        stderr(&output)
+    );
+}
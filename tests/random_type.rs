@@ -0,0 +1,43 @@
+mod common;
+
+use common::*;
+use pngme::chunk_type::ChunkType;
+
+#[test]
+fn the_random_type_is_printed_and_decodes() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", file, "--random-type", "--message", "hello"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    let name = message
+        .lines()
+        .find_map(|line| line.strip_prefix("Using the random chunk type "))
+        .unwrap_or_else(|| panic!("no chunk type in {message}"));
+
+    let chunk_type: ChunkType = name.parse().unwrap();
+    assert!(chunk_type.is_valid());
+    assert!(!chunk_type.is_critical());
+    assert!(!chunk_type.is_public());
+    assert!(chunk_type.is_safe_to_copy());
+
+    let output = pngme(["decode", file, name, "--quiet"]);
+    assert_eq!(stdout(&output), "hello\n");
+}
+
+#[test]
+fn a_random_type_takes_no_chunk_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode",
+        file.to_str().unwrap(),
+        "ruSt",
+        "hello",
+        "--random-type",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+
+/// Images under `dir/tree`, among other files, and one more outside it
+fn tree(dir: &Path) -> String {
+    let tree = dir.join("tree");
+    fs::create_dir_all(tree.join("sub")).unwrap();
+    fs::create_dir_all(tree.join("vendor")).unwrap();
+    fs::create_dir_all(dir.join("elsewhere")).unwrap();
+    write_fixture(&tree, "a.png", &fixture_png());
+    write_fixture(&tree, "fake.png", b"not a png");
+    write_fixture(&tree, "notes.txt", b"notes");
+    write_fixture(&tree.join("sub"), "b.png", &fixture_png());
+    write_fixture(&tree.join("vendor"), "c.png", &fixture_png());
+    write_fixture(&dir.join("elsewhere"), "d.png", &fixture_png());
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(dir.join("elsewhere"), tree.join("linked")).unwrap();
+    tree.to_str().unwrap().to_string()
+}
+
+fn encode(tree: &str, extra: &[&str]) -> std::process::Output {
+    let mut args = vec![
+        "encode",
+        "--recursive",
+        tree,
+        "--chunk",
+        "abCd",
+        "--message",
+        "hi",
+    ];
+    args.extend(extra);
+    pngme(args)
+}
+
+fn has_message(file: &str) -> bool {
+    stdout(&pngme(["print", file])).contains("abCd")
+}
+
+#[test]
+fn encode_edits_every_image_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let tree = tree(dir.path());
+
+    let output = encode(&tree, &["--exclude", "vendor"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        format!("{tree}/a.png: encoded\n{tree}/sub/b.png: encoded\nEncoded 2 of 2 file(s)\n")
+    );
+    assert!(
+        stderr(&output).contains(&format!(
+            "warning[E0101]: skipping {tree}/fake.png: not a PNG file"
+        )),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!has_message(&format!("{tree}/vendor/c.png")));
+    assert_eq!(fs::read(format!("{tree}/fake.png")).unwrap(), b"not a png");
+}
+
+#[cfg(unix)]
+#[test]
+fn symbolic_links_are_followed_on_request() {
+    let dir = tempfile::tempdir().unwrap();
+    let tree = tree(dir.path());
+    let outside = dir.path().join("elsewhere/d.png");
+
+    let output = encode(&tree, &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stdout(&output).contains("linked"), "{}", stdout(&output));
+    assert!(!has_message(outside.to_str().unwrap()));
+
+    let output = encode(&tree, &["--follow-symlinks", "--exclude", "sub/*.png"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains(&format!("{tree}/linked/d.png: encoded")),
+        "{}",
+        stdout(&output)
+    );
+    assert!(
+        !stdout(&output).contains("sub/b.png"),
+        "{}",
+        stdout(&output)
+    );
+    assert!(has_message(outside.to_str().unwrap()));
+}
+
+#[test]
+fn decode_names_each_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let tree = tree(dir.path());
+    let output = encode(&tree, &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["decode", "--recursive", &tree, "abCd", "--quiet"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        format!("{tree}/a.png: hi\n{tree}/sub/b.png: hi\n{tree}/vendor/c.png: hi\n")
+    );
+
+    // Named even when there is a single one
+    let output = pngme([
+        "decode",
+        "--recursive",
+        &format!("{tree}/sub"),
+        "--chunk",
+        "abCd",
+        "--quiet",
+    ]);
+    assert_eq!(stdout(&output), format!("{tree}/sub/b.png: hi\n"));
+}
+
+#[test]
+fn print_lists_each_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let tree = tree(dir.path());
+
+    let output = pngme(["print", "--recursive", &tree, "--exclude", "vendor"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let printed = stdout(&output);
+    assert!(
+        printed.starts_with(&format!("{tree}/a.png:\nPng {{")),
+        "{printed}"
+    );
+    assert!(
+        printed.contains(&format!("\n\n{tree}/sub/b.png:\nPng {{")),
+        "{printed}"
+    );
+    assert!(!printed.contains("fake.png"), "{printed}");
+    assert!(stderr(&output).contains("fake.png: not a PNG file"));
+}
+
+#[test]
+fn recursive_arguments_are_checked() {
+    let dir = tempfile::tempdir().unwrap();
+    let tree = tree(dir.path());
+    let file = format!("{tree}/a.png");
+
+    // Files come from the walk only
+    let output = pngme(["decode", "--recursive", &tree, &file, "abCd"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = pngme(["print", &file, "--recursive", &tree]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = pngme(["print", &file, "--exclude", "vendor"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = pngme([
+        "decode",
+        "--recursive",
+        &tree,
+        "abCd",
+        "--output",
+        "out.bin",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = pngme(["print", "--recursive", &tree, "--exclude", "[ab"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("E1308"), "{}", stderr(&output));
+
+    let missing = format!("{tree}/missing");
+    let output = pngme(["print", "--recursive", &missing]);
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn replace_overwrites_the_chunk_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", file, "ruSt", "new message", "--replace"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(
+        chunk_types(file),
+        ["IHDR", "teXt", "IDAT", "IDAT", "IDAT", "ruSt", "IEND"]
+    );
+    let output = pngme(["decode", "--quiet", file, "ruSt"]);
+    assert_eq!(stdout(&output), "new message\n");
+}
+
+#[test]
+fn replace_without_existing_chunk_appends() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", file, "abCd", "hello", "--replace"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(chunk_types(file).iter().filter(|t| *t == "abCd").count(), 1);
+}
+
+#[test]
+fn duplicate_chunk_is_warned_about() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", file, "ruSt", "second message"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("already has a ruSt chunk"));
+    assert!(stderr(&output).contains("--replace"));
+
+    assert_eq!(chunk_types(file).iter().filter(|t| *t == "ruSt").count(), 2);
+}
+
+#[test]
+fn replace_drops_the_pieces_of_a_split_message() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let message = "0123456789".repeat(5);
+
+    let output = pngme(["encode", file, "abCd", &message, "--split-size", "10"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = pngme(["encode", file, "abCd", "short", "--replace"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(chunk_types(file).iter().filter(|t| *t == "abCd").count(), 1);
+    let output = pngme(["decode", "--quiet", file, "abCd"]);
+    assert_eq!(stdout(&output), "short\n");
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::*;
+
+/// A valid image whose `rust` chunk has the reserved bit set, next to
+/// `others` placed before IEND
+fn image_with_reserved_bit(dir: &tempfile::TempDir, others: &[(&str, &[u8])]) -> PathBuf {
+    let mut chunks: Vec<(&str, &[u8])> = vec![
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+        ("IDAT", &[1, 2, 3]),
+        ("rust", b"reserved"),
+    ];
+    chunks.extend_from_slice(others);
+    chunks.push(("IEND", &[]));
+
+    write_fixture(dir.path(), "image.png", &png_bytes(&chunks))
+}
+
+#[test]
+fn verify_reports_reserved_bit() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image_with_reserved_bit(&dir, &[]);
+
+    let output = pngme(["verify".as_ref(), file.as_os_str()]);
+
+    assert!(!output.status.success());
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains(
+            "[warning] 52..56 W0206 reserved-bit: chunk type rust at offset 52 has the reserved bit set"
+        ),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn fix_only_reports_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image_with_reserved_bit(&dir, &[]);
+    let original = fs::read(&file).unwrap();
+
+    let output = pngme(["fix".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Fixed 0 problem(s)"));
+
+    assert_eq!(fs::read(&file).unwrap(), original);
+}
+
+#[test]
+fn fix_reserved_renames_the_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image_with_reserved_bit(&dir, &[]);
+
+    let output = pngme(["fix".as_ref(), file.as_os_str(), "--fix-reserved".as_ref()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Fixed 1 problem(s)"));
+
+    let output = pngme(["verify".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stdout(&output));
+
+    let output = pngme([
+        "decode".as_ref(),
+        "--raw".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"reserved");
+}
+
+#[test]
+fn fix_reserved_refuses_to_collide() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image_with_reserved_bit(&dir, &[("ruSt", b"already here")]);
+    let original = fs::read(&file).unwrap();
+
+    let output = pngme(["fix".as_ref(), file.as_os_str(), "--fix-reserved".as_ref()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Fixed 0 problem(s)"));
+    assert!(
+        stderr(&output).contains("not renaming rust to ruSt"),
+        "{}",
+        stderr(&output)
+    );
+
+    assert_eq!(fs::read(&file).unwrap(), original);
+}
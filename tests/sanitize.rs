@@ -0,0 +1,56 @@
+mod common;
+
+use common::*;
+
+const SPOOFING_PAYLOAD: &str = "found\u{202E}dnuof ton";
+
+fn spoofing_png(dir: &std::path::Path) -> std::path::PathBuf {
+    let png = png_bytes(&[
+        ("IHDR", b"header"),
+        ("ruSt", SPOOFING_PAYLOAD.as_bytes()),
+        ("IEND", b""),
+    ]);
+    write_fixture(dir, "image.png", &png)
+}
+
+#[test]
+fn human_decode_escapes_bidi_controls() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = spoofing_png(dir.path());
+
+    for command in ["decode", "print"] {
+        let mut args = vec![command.as_ref(), file.as_os_str()];
+        if command == "decode" {
+            args.push("ruSt".as_ref());
+        }
+
+        let output = pngme(args);
+        let stdout = stdout(&output);
+
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(stdout.contains("found\\u{202e}dnuof ton"), "{stdout}");
+        assert!(!stdout.contains('\u{202E}'), "{stdout}");
+    }
+}
+
+#[test]
+fn quiet_and_raw_decode_are_faithful() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = spoofing_png(dir.path());
+
+    let output = pngme([
+        "decode".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+        "--quiet".as_ref(),
+    ]);
+    assert_eq!(stdout(&output), format!("{SPOOFING_PAYLOAD}\n"));
+
+    let output = pngme([
+        "decode".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+        "--raw".as_ref(),
+    ]);
+    assert_eq!(output.stdout, SPOOFING_PAYLOAD.as_bytes());
+}
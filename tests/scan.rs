@@ -0,0 +1,49 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn scan_flags_wrong_sized_standard_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = png_bytes(&[
+        ("IHDR", &[0; 13]),
+        ("pHYs", &[0; 47]),
+        ("IDAT", &[0; 100]),
+        ("IEND", b""),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &png);
+
+    let output = pngme(["scan".as_ref(), file.as_os_str()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output).trim(),
+        "[critical] #1 pHYs chunk has 47 bytes, expected 9"
+    );
+}
+
+#[test]
+fn scan_thresholds_come_from_flags() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = png_bytes(&[
+        ("IHDR", &[0; 13]),
+        ("IDAT", &[0; 100]),
+        ("ruSt", &[0; 80]),
+        ("IEND", b""),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &png);
+
+    let output = pngme(["scan".as_ref(), file.as_os_str()]);
+    assert!(
+        stdout(&output)
+            .contains("[warning] #2 ruSt chunk has 80 bytes, 80% of the 100 bytes of image data")
+    );
+
+    let output = pngme([
+        "scan".as_ref(),
+        file.as_os_str(),
+        "--max-idat-ratio".as_ref(),
+        "1".as_ref(),
+    ]);
+    assert_eq!(stdout(&output).trim(), "No findings");
+}
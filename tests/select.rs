@@ -0,0 +1,148 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+use serde_json::Value;
+
+fn image() -> Vec<u8> {
+    png_bytes(&[
+        ("IHDR", &[0; 13]),
+        ("iCCP", b"profile\0\0x"),
+        ("gAMA", &[0, 0, 0xb1, 0x8f]),
+        ("IDAT", b"pixels"),
+        ("tEXt", b"Comment\0hello"),
+        ("zTXt", b"Title\0\0x"),
+        ("tIME", &[7, 233, 1, 1, 0, 0, 0]),
+        ("ruSt", b"secret"),
+        ("abCd", b"other"),
+        ("IEND", b""),
+    ])
+}
+
+/// The chunk types `export-meta --select` writes for `selection`
+fn exported(selection: &str) -> Vec<String> {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &image());
+
+    let output = pngme([
+        "export-meta",
+        file.to_str().unwrap(),
+        "-",
+        "--select",
+        selection,
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let sidecar: Value = serde_json::from_slice(&output.stdout).unwrap();
+    sidecar["chunks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|chunk| chunk["type"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn export_meta_selects_categories() {
+    assert_eq!(exported("private"), ["ruSt", "abCd"]);
+    assert_eq!(exported("private,text"), ["tEXt", "zTXt", "ruSt", "abCd"]);
+    assert_eq!(exported("private,text,!zTXt,!abCd"), ["tEXt", "ruSt"]);
+    assert_eq!(exported("!color"), ["tEXt", "zTXt", "tIME", "ruSt", "abCd"]);
+    assert_eq!(exported("time, gAMA"), ["gAMA", "tIME"]);
+}
+
+#[test]
+fn import_meta_selects_from_the_sidecar() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = write_fixture(dir.path(), "source.png", &image());
+    let target = write_fixture(dir.path(), "target.png", &fixture_png());
+    let sidecar = dir.path().join("image.pngmeta");
+
+    let output = pngme([
+        "export-meta".as_ref(),
+        source.as_os_str(),
+        sidecar.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme([
+        "import-meta".as_ref(),
+        target.as_os_str(),
+        sidecar.as_os_str(),
+        "--select".as_ref(),
+        "text,!zTXt,time".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output).trim(), "Imported 2 chunk(s)");
+    let types = chunk_types(&target);
+    assert!(types.contains(&"tEXt".to_string()), "{types:?}");
+    assert!(types.contains(&"tIME".to_string()), "{types:?}");
+    assert!(!types.contains(&"zTXt".to_string()), "{types:?}");
+    assert!(!types.contains(&"abCd".to_string()), "{types:?}");
+}
+
+#[test]
+fn strip_removes_the_selected_chunks_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &image());
+
+    let output = pngme(["strip", file.to_str().unwrap(), "--select", "color,!iCCP"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "Removed 1 chunk(s)\nRemoved rendering chunks: gAMA\n"
+    );
+    assert_eq!(
+        chunk_types(&file),
+        [
+            "IHDR", "iCCP", "IDAT", "tEXt", "zTXt", "tIME", "ruSt", "abCd", "IEND"
+        ]
+    );
+
+    let output = pngme(["strip", file.to_str().unwrap(), "--select", "private"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        chunk_types(&file),
+        ["IHDR", "iCCP", "IDAT", "tEXt", "zTXt", "tIME", "IEND"]
+    );
+}
+
+#[test]
+fn a_selection_matching_nothing_warns() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["strip", file.to_str().unwrap(), "--select", "time"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("warning[W0302]: --select time matches none of the 2 chunk(s)"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(fs::read(&file).unwrap(), fixture_png());
+}
+
+#[test]
+fn unknown_terms_are_usage_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "export-meta",
+        file.to_str().unwrap(),
+        "-",
+        "--select",
+        "private,colour",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("E0213"), "{}", stderr(&output));
+
+    let output = pngme([
+        "strip",
+        file.to_str().unwrap(),
+        "--select",
+        "private",
+        "--strip-color",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,349 @@
+#![cfg(feature = "server")]
+
+mod common;
+
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    num::NonZeroUsize,
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::*;
+use pngme::{
+    png::{ParseOptions, Png},
+    server::{Server, ServerOptions, TOKEN_HEADER},
+    split,
+};
+use reqwest::blocking::Client;
+use serde_json::Value;
+
+const TOKEN: &str = "s3cret";
+
+/// Starts a server on an ephemeral port, returns its base URL
+fn start(options: ServerOptions) -> String {
+    let server = Server::bind("127.0.0.1:0", options).unwrap();
+    let address = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+
+    format!("http://{address}")
+}
+
+fn with_token() -> ServerOptions {
+    ServerOptions {
+        token: Some(TOKEN.to_string()),
+        ..ServerOptions::default()
+    }
+}
+
+fn error_code(response: reqwest::blocking::Response) -> String {
+    let body: Value = serde_json::from_str(&response.text().unwrap()).unwrap();
+    body["error"]["code"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn health_needs_no_token() {
+    let base = start(with_token());
+
+    let response = reqwest::blocking::get(format!("{base}/health")).unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), r#"{"status":"ok"}"#);
+}
+
+#[test]
+fn encode_then_decode() {
+    let base = start(with_token());
+    let client = Client::new();
+
+    let response = client
+        .post(format!(
+            "{base}/encode?chunk_type=apIe&message=hello%20world"
+        ))
+        .header(TOKEN_HEADER, TOKEN)
+        .body(fixture_png())
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/png");
+    let encoded = response.bytes().unwrap().to_vec();
+    let png = Png::try_from(encoded.as_slice()).unwrap();
+    assert_eq!(png.chunk_by_type("apIe").unwrap().data(), b"hello world");
+
+    let response = client
+        .post(format!("{base}/decode?chunk_type=apIe"))
+        .header(TOKEN_HEADER, TOKEN)
+        .body(encoded)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().unwrap(),
+        r#"{"chunk_type":"apIe","length":11,"data":"hello world"}"#
+    );
+}
+
+#[test]
+fn errors_map_to_status_codes() {
+    let base = start(ServerOptions {
+        max_body_size: 1024,
+        ..with_token()
+    });
+    let client = Client::new();
+    let post = |path: &str, token: &str, body: Vec<u8>| {
+        client
+            .post(format!("{base}{path}"))
+            .header(TOKEN_HEADER, token)
+            .body(body)
+            .send()
+            .unwrap()
+    };
+
+    let response = post("/decode?chunk_type=ruSt", "wrong", fixture_png());
+    assert_eq!(response.status(), 401);
+    assert_eq!(error_code(response), "E1012");
+
+    let response = post("/decode?chunk_type=noNe", TOKEN, fixture_png());
+    assert_eq!(response.status(), 404);
+    assert_eq!(error_code(response), "E0301");
+
+    let response = post("/decode?chunk_type=ru5t", TOKEN, fixture_png());
+    assert_eq!(response.status(), 400);
+    assert_eq!(error_code(response), "E0404");
+
+    let response = post("/decode", TOKEN, fixture_png());
+    assert_eq!(response.status(), 400);
+    assert_eq!(error_code(response), "E1015");
+
+    let response = post("/decode?chunk_type=ruSt", TOKEN, b"not a png".to_vec());
+    assert_eq!(response.status(), 400);
+    assert_eq!(error_code(response), "E0101");
+
+    let response = post("/decode?chunk_type=ruSt", TOKEN, vec![0; 2048]);
+    assert_eq!(response.status(), 413);
+    assert_eq!(error_code(response), "E1016");
+
+    let response = client.get(format!("{base}/decode")).send().unwrap();
+    assert_eq!(response.status(), 405);
+
+    let response = client.get(format!("{base}/nope")).send().unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+fn encode_refuses_unsafe_chunk_types() {
+    let base = start(ServerOptions::default());
+    let client = Client::new();
+    let encode = |chunk_type: &str| {
+        client
+            .post(format!("{base}/encode?chunk_type={chunk_type}&message=hi"))
+            .body(fixture_png())
+            .send()
+            .unwrap()
+    };
+
+    let response = encode("ABCD");
+    assert_eq!(response.status(), 400);
+    assert_eq!(error_code(response), "E0519");
+
+    let response = encode("abcd");
+    assert_eq!(response.status(), 400);
+    assert_eq!(error_code(response), "E0520");
+}
+
+#[test]
+fn encode_refuses_images_with_unknown_critical_chunks() {
+    let image = png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        ("CRIT", b"unknown"),
+        ("IDAT", &[0x78, 0x9c]),
+        ("IEND", &[]),
+    ]);
+    let client = Client::new();
+    let encode = |base: &str| {
+        client
+            .post(format!("{base}/encode?chunk_type=apIe&message=hi"))
+            .body(image.clone())
+            .send()
+            .unwrap()
+    };
+
+    let response = encode(&start(ServerOptions::default()));
+    assert_eq!(response.status(), 422);
+    assert_eq!(error_code(response), "E0507");
+
+    let response = encode(&start(ServerOptions {
+        allow_unknown_critical: true,
+        ..ServerOptions::default()
+    }));
+    assert_eq!(response.status(), 200);
+}
+
+#[test]
+fn decode_joins_split_messages() {
+    let pieces = split::split(b"hello world", 4).unwrap();
+    let mut chunks: Vec<(&str, &[u8])> = vec![
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        ("IDAT", &[0x78, 0x9c]),
+    ];
+    chunks.extend(pieces.iter().map(|piece| ("apIe", piece.as_slice())));
+    chunks.push(("IEND", &[]));
+    let base = start(ServerOptions::default());
+
+    let response = Client::new()
+        .post(format!("{base}/decode?chunk_type=apIe"))
+        .body(png_bytes(&chunks))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().unwrap(),
+        r#"{"chunk_type":"apIe","length":11,"data":"hello world"}"#
+    );
+}
+
+#[test]
+fn limits_apply_to_posted_images() {
+    let base = start(ServerOptions {
+        parse_options: ParseOptions {
+            max_chunks: 3,
+            ..ParseOptions::default()
+        },
+        ..ServerOptions::default()
+    });
+
+    let response = Client::new()
+        .post(format!("{base}/decode?chunk_type=ruSt"))
+        .body(fixture_png())
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert_eq!(error_code(response), "E0105");
+}
+
+#[test]
+fn slow_body_times_out() {
+    let base = start(ServerOptions {
+        timeout: Duration::from_millis(200),
+        ..ServerOptions::default()
+    });
+    let address = base.trim_start_matches("http://");
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "POST /decode?chunk_type=ruSt HTTP/1.1\r\nHost: {address}\r\nContent-Length: 4096\r\n\r\n"
+    )
+    .unwrap();
+    // Each read gets data in time, the deadline passes between two of them
+    for _ in 0..64 {
+        thread::sleep(Duration::from_millis(20));
+        if stream.write_all(&[0; 64]).is_err() {
+            break;
+        }
+    }
+
+    let mut response = [0; 12];
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.read_exact(&mut response).unwrap();
+    let response = String::from_utf8_lossy(&response);
+    assert!(response.starts_with("HTTP/1.1 408"), "{response}");
+}
+
+#[test]
+fn slow_headers_time_out() {
+    let base = start(ServerOptions {
+        timeout: Duration::from_millis(200),
+        ..ServerOptions::default()
+    });
+    let address = base.trim_start_matches("http://");
+
+    // A byte at a time for much longer than the timeout, never ending the
+    // line
+    let mut stream = TcpStream::connect(address).unwrap();
+    let mut writer = stream.try_clone().unwrap();
+    let started = Instant::now();
+    thread::spawn(move || {
+        for _ in 0..150 {
+            thread::sleep(Duration::from_millis(20));
+            if writer.write_all(b"a").is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut response = [0; 12];
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.read_exact(&mut response).unwrap();
+    let response = String::from_utf8_lossy(&response);
+    assert!(response.starts_with("HTTP/1.1 408"), "{response}");
+    assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+}
+
+#[test]
+fn oversized_headers_are_refused() {
+    let base = start(ServerOptions::default());
+    let address = base.trim_start_matches("http://");
+
+    // A single line longer than the limit
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET /health?{} HTTP/1.1\r\n", "a".repeat(32 * 1024)).unwrap();
+
+    let mut response = [0; 12];
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.read_exact(&mut response).unwrap();
+    let response = String::from_utf8_lossy(&response);
+    assert!(response.starts_with("HTTP/1.1 431"), "{response}");
+
+    // Headers split across reads still parse
+    let response = Client::new()
+        .get(format!("{base}/health"))
+        .header("X-Padding", "a".repeat(12 * 1024))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[test]
+fn stalled_client_frees_its_worker() {
+    let base = start(ServerOptions {
+        timeout: Duration::from_millis(200),
+        workers: NonZeroUsize::MIN,
+        ..ServerOptions::default()
+    });
+    let address = base.trim_start_matches("http://");
+
+    // The headers announce a body that never comes
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "POST /decode?chunk_type=ruSt HTTP/1.1\r\nHost: {address}\r\nContent-Length: 4096\r\n\r\n"
+    )
+    .unwrap();
+    stream.write_all(&[0; 16]).unwrap();
+
+    let mut response = [0; 12];
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.read_exact(&mut response).unwrap();
+    let response = String::from_utf8_lossy(&response);
+    assert!(response.starts_with("HTTP/1.1 408"), "{response}");
+
+    // The only worker is free for the next request
+    let response = Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap()
+        .get(format!("{base}/health"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
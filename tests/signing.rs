@@ -0,0 +1,148 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+use ed25519_dalek::{
+    SigningKey,
+    pkcs8::{EncodePrivateKey, EncodePublicKey, spki::der::pem::LineEnding},
+};
+
+/// Writes the private and public keys of `seed`, returning their paths
+fn write_keys(dir: &Path, seed: u8) -> (String, String) {
+    let key = SigningKey::from_bytes(&[seed; 32]);
+    let private = dir.join(format!("key{seed}.pem"));
+    let public = dir.join(format!("pub{seed}.pem"));
+    fs::write(
+        &private,
+        key.to_pkcs8_pem(LineEnding::LF).unwrap().as_bytes(),
+    )
+    .unwrap();
+    fs::write(
+        &public,
+        key.verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap(),
+    )
+    .unwrap();
+
+    (
+        private.to_str().unwrap().to_string(),
+        public.to_str().unwrap().to_string(),
+    )
+}
+
+#[test]
+fn a_signed_message_verifies_with_the_public_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let (private, public) = write_keys(dir.path(), 1);
+
+    let output = pngme(["encode", file, "abCd", "hello", "--sign-key", &private]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let printed = stdout(&pngme(["print", file]));
+    assert!(printed.contains("siGn"), "{printed}");
+
+    let output = pngme(["verify", file, "abCd", "--pubkey", &public]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("Good signature of the abCd chunk by "));
+
+    // The payload is unchanged for readers ignoring the signature
+    let output = pngme(["decode", file, "abCd"]);
+    assert!(stdout(&output).contains("data: hello,"), "{}", stdout(&output));
+}
+
+#[test]
+fn each_verification_failure_has_its_own_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let (private, _) = write_keys(dir.path(), 1);
+    let (_, other) = write_keys(dir.path(), 2);
+
+    // The fixture's ruSt chunk was never signed
+    let output = pngme(["verify", file, "ruSt", "--pubkey", &other]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("E0524"), "{}", stderr(&output));
+
+    let output = pngme(["encode", file, "abCd", "hello", "--sign-key", &private]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = pngme(["verify", file, "abCd", "--pubkey", &other]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(stderr(&output).contains("E0525"), "{}", stderr(&output));
+
+    // A private key is no public key
+    let output = pngme(["verify", file, "abCd", "--pubkey", &private]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr(&output).contains("E0526"), "{}", stderr(&output));
+}
+
+#[test]
+fn replacing_a_signed_message_drops_the_old_signature() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let (private, public) = write_keys(dir.path(), 1);
+
+    for message in ["hello", "world"] {
+        let output = pngme([
+            "encode",
+            file,
+            "abCd",
+            message,
+            "--sign-key",
+            &private,
+            "--replace",
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+
+    let printed = stdout(&pngme(["print", file]));
+    assert_eq!(printed.matches("siGn").count(), 1, "{printed}");
+    let output = pngme(["verify", file, "abCd", "--pubkey", &public]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn replacing_a_signed_message_without_a_key_drops_the_signature() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let (private, public) = write_keys(dir.path(), 1);
+
+    let output = pngme(["encode", file, "abCd", "hello", "--sign-key", &private]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = pngme(["encode", file, "abCd", "forged", "--replace"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // The old signature would vouch for the new message otherwise
+    let printed = stdout(&pngme(["print", file]));
+    assert!(!printed.contains("siGn"), "{printed}");
+    let output = pngme(["verify", file, "abCd", "--pubkey", &public]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn a_split_message_is_signed_as_a_whole() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let (private, public) = write_keys(dir.path(), 1);
+
+    let message = "a message split in several pieces";
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        message,
+        "--split-size",
+        "8",
+        "--sign-key",
+        &private,
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["verify", file, "abCd", "--pubkey", &public]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+
+/// Embeds `payload` as the `chunk_type` message of `file`
+fn embed(dir: &Path, file: &str, chunk_type: &str, payload: &[u8]) {
+    let message = write_fixture(dir, &format!("{chunk_type}.payload"), payload);
+    let output = pngme([
+        "encode",
+        file,
+        chunk_type,
+        "--message-file",
+        message.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn payloads_extracted_into_a_directory_get_their_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "photo.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let out = dir.path().join("out");
+    fs::create_dir(&out).unwrap();
+
+    let payloads: [(&str, &[u8], &str, &str); 4] = [
+        (
+            "pdFa",
+            b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n",
+            "pdf",
+            "PDF document",
+        ),
+        ("ziPa", b"PK\x03\x04\x14\x00\x00\x00", "zip", "ZIP archive"),
+        ("txTa", b"plain notes\n", "txt", "UTF-8 text"),
+        ("unKa", b"\x00\x01\x02\xfe", "bin", "unknown data"),
+    ];
+    for (chunk_type, payload, _, _) in payloads {
+        embed(dir.path(), file, chunk_type, payload);
+    }
+
+    for (chunk_type, payload, extension, kind) in payloads {
+        let output = pngme([
+            "decode",
+            file,
+            chunk_type,
+            "--output",
+            out.to_str().unwrap(),
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        let expected = out.join(format!("photo-{chunk_type}.{extension}"));
+        assert_eq!(
+            stdout(&output),
+            format!("Detected {kind}, writing {}\n", expected.display())
+        );
+        assert_eq!(fs::read(&expected).unwrap(), payload);
+    }
+    assert_eq!(fs::read_dir(&out).unwrap().count(), payloads.len());
+}
+
+#[test]
+fn a_trailing_dot_is_completed_with_the_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    embed(
+        dir.path(),
+        file,
+        "gzIp",
+        b"\x1f\x8b\x08\x00\x00\x00\x00\x00",
+    );
+    let output = dir.path().join("payload.");
+
+    let result = pngme(["decode", file, "gzIp", "--output", output.to_str().unwrap()]);
+    assert!(result.status.success(), "{}", stderr(&result));
+
+    assert!(dir.path().join("payload.gz").exists());
+    assert!(!output.exists());
+}
+
+#[test]
+fn no_sniff_writes_to_the_path_as_it_is() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    embed(dir.path(), file, "ziPa", b"PK\x03\x04");
+    let output = dir.path().join("payload.");
+
+    let result = pngme([
+        "decode",
+        file,
+        "ziPa",
+        "--output",
+        output.to_str().unwrap(),
+        "--no-sniff",
+    ]);
+    assert!(result.status.success(), "{}", stderr(&result));
+
+    assert_eq!(result.stdout, b"");
+    assert_eq!(fs::read(&output).unwrap(), b"PK\x03\x04");
+}
+
+#[test]
+fn scan_flags_a_chunk_holding_another_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    embed(dir.path(), file, "ziPa", b"PK\x03\x04\x14\x00\x00\x00");
+
+    let output = stdout(&pngme(["scan", file]));
+    assert!(
+        output.contains("ziPa chunk holds a ZIP archive, the file may also open as one"),
+        "{output}"
+    );
+}
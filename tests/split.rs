@@ -0,0 +1,127 @@
+mod common;
+
+use common::*;
+
+/// Data of piece `index` of `total` of a split message
+fn piece(index: u32, total: u32, data: &[u8]) -> Vec<u8> {
+    let mut bytes = b"PNGMP\x01".to_vec();
+    bytes.extend_from_slice(&index.to_be_bytes());
+    bytes.extend_from_slice(&total.to_be_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+#[test]
+fn split_message_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let message = "0123456789".repeat(25);
+
+    let output = pngme(["encode", file, "abCd", &message, "--split-size", "100"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["print", file]);
+    let pieces = printed_chunks(&stdout(&output))
+        .into_iter()
+        .filter(|(_, chunk_type)| chunk_type == "abCd")
+        .count();
+    assert_eq!(pieces, 3);
+
+    let output = pngme(["decode", "--quiet", file, "abCd"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), format!("{message}\n"));
+}
+
+#[test]
+fn pieces_are_joined_in_index_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let (first, second, third) = (
+        piece(0, 3, b"hel"),
+        piece(1, 3, b"lo "),
+        piece(2, 3, b"you"),
+    );
+    let bytes = png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        ("abCd", &third),
+        (
+            "IDAT",
+            &[0x78, 0x9c, 0x62, 0x00, 0x01, 0x00, 0x00, 0xff, 0xff],
+        ),
+        ("abCd", &first),
+        ("abCd", &second),
+        ("IEND", &[]),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &bytes);
+
+    let output = pngme(["decode", "--quiet", file.to_str().unwrap(), "abCd"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "hello you\n");
+}
+
+#[test]
+fn missing_piece_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let (first, third) = (piece(0, 3, b"hel"), piece(2, 3, b"you"));
+    let bytes = png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        (
+            "IDAT",
+            &[0x78, 0x9c, 0x62, 0x00, 0x01, 0x00, 0x00, 0xff, 0xff],
+        ),
+        ("abCd", &first),
+        ("abCd", &third),
+        ("IEND", &[]),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &bytes);
+
+    let output = pngme(["decode", file.to_str().unwrap(), "abCd"]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("error[E0516]"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(
+        stderr(&output).contains("Piece 2 of 3"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn split_size_must_be_positive() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode",
+        file.to_str().unwrap(),
+        "abCd",
+        "hi",
+        "--split-size",
+        "0",
+    ]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("E0515"), "{}", stderr(&output));
+}
+
+#[test]
+fn a_huge_piece_count_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let bytes = png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        ("ruSt", &piece(0, u32::MAX, b"")),
+        ("IEND", &[]),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &bytes);
+
+    // Refused as a missing piece, rather than allocating for 4 billion
+    let output = pngme(["decode", file.to_str().unwrap(), "ruSt"]);
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("error[E0516]"),
+        "{}",
+        stderr(&output)
+    );
+}
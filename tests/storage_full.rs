@@ -0,0 +1,40 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::{
+    fs::{self, OpenOptions},
+    process::{Command, Stdio},
+};
+
+use common::*;
+
+#[test]
+fn full_device_has_its_own_code_and_status() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    // Every write to /dev/full fails with ENOSPC
+    let full = OpenOptions::new().write(true).open("/dev/full").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args([
+            "encode",
+            file.to_str().unwrap(),
+            "abCd",
+            "hello",
+            "--output",
+            "-",
+        ])
+        .stdout(Stdio::from(full))
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(6));
+    assert!(
+        stderr(&output).contains("error[E0518]"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(fs::read(&file).unwrap(), fixture_png());
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+use pngme::format::{Encoding, wrap};
+
+/// Three megabytes, more than is printed without streaming
+fn payload() -> Vec<u8> {
+    (0..3 << 20)
+        .map(|index: u32| (index * 31 % 251) as u8)
+        .collect()
+}
+
+#[test]
+fn large_payloads_are_printed_encoded() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let message = write_fixture(dir.path(), "payload.bin", &payload());
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "--message-file",
+        message.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    for encoding in [Encoding::Hex, Encoding::Base64, Encoding::Base45] {
+        let name = encoding.to_string();
+        let output = pngme([
+            "decode",
+            "--quiet",
+            file,
+            "abCd",
+            "--output-encoding",
+            &name,
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(
+            stdout(&output),
+            format!("{}\n", encoding.encode(&payload()).unwrap())
+        );
+    }
+
+    let output = pngme([
+        "decode",
+        "--quiet",
+        file,
+        "abCd",
+        "--output-encoding",
+        "base64",
+        "--wrap",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let printed = stdout(&output);
+    assert_eq!(
+        printed,
+        format!(
+            "{}\n",
+            wrap(&Encoding::Base64.encode(&payload()).unwrap(), 76)
+        )
+    );
+    assert!(printed.lines().all(|line| line.len() <= 76));
+}
+
+#[test]
+fn small_payloads_are_wrapped_too() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", file, "abCd", "hello world"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme([
+        "decode",
+        "--quiet",
+        file,
+        "abCd",
+        "--output-encoding",
+        "hex",
+        "--wrap",
+        "8",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "68656c6c\n6f20776f\n726c64\n");
+
+    // --wrap needs an encoding to wrap
+    let output = pngme(["decode", file, "abCd", "--wrap"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = pngme([
+        "decode",
+        file,
+        "abCd",
+        "--output-encoding",
+        "hex",
+        "--wrap",
+        "0",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn large_encoded_messages_are_decoded_as_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let text = wrap(&Encoding::Base64.encode(&payload()).unwrap(), 76);
+    let message = write_fixture(dir.path(), "payload.b64", text.as_bytes());
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "--message-file",
+        message.to_str().unwrap(),
+        "--input-encoding",
+        "base64",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let raw = dir.path().join("raw.bin");
+    let output = pngme([
+        "decode",
+        file,
+        "abCd",
+        "--output",
+        raw.to_str().unwrap(),
+        "--no-sniff",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read(&raw).unwrap(), payload());
+
+    let hex = Encoding::Hex.encode(b"from stdin").unwrap();
+    let output = pngme_with_stdin(
+        &["encode", file, "efGh", "-", "--input-encoding", "hex"],
+        hex.as_bytes(),
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&pngme(["decode", "--quiet", file, "efGh"])),
+        "from stdin\n"
+    );
+}
+
+#[test]
+fn invalid_encoded_messages_report_where() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let mut text = "A".repeat(200_000);
+    text.replace_range(150_001..150_002, "!");
+    let message = write_fixture(dir.path(), "payload.b64", text.as_bytes());
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "--message-file",
+        message.to_str().unwrap(),
+        "--input-encoding",
+        "base64",
+    ]);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Invalid symbol 33, offset 150001"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(fs::read(file).unwrap(), fixture_png());
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::*;
+
+fn image() -> Vec<u8> {
+    png_bytes(&[
+        ("IHDR", &[0; 13]),
+        ("gAMA", &[0, 0, 0xb1, 0x8f]),
+        ("sRGB", &[0]),
+        ("IDAT", b"pixels"),
+        ("tEXt", b"Comment\0hello"),
+        ("tIME", &[7, 233, 1, 1, 0, 0, 0]),
+        ("ruSt", b"secret"),
+        ("IEND", b""),
+    ])
+}
+
+#[test]
+fn strip_keeps_rendering_chunks_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &image());
+
+    let output = pngme(["strip".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "Removed 3 chunk(s)\nKept rendering chunks: gAMA, sRGB (pass --strip-color to remove them)\n"
+    );
+    assert_eq!(chunk_types(&file), ["IHDR", "gAMA", "sRGB", "IDAT", "IEND"]);
+}
+
+#[test]
+fn strip_color_removes_rendering_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &image());
+
+    let output = pngme(["strip".as_ref(), file.as_os_str(), "--strip-color".as_ref()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "Removed 5 chunk(s)\nRemoved rendering chunks: gAMA, sRGB\n"
+    );
+    assert_eq!(chunk_types(&file), ["IHDR", "IDAT", "IEND"]);
+}
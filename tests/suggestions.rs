@@ -0,0 +1,94 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn case_miss_is_suggested() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["decode", file.to_str().unwrap(), "rust"]);
+    let err = stderr(&output);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(!err.contains("Chunk type: rust not found"), "{err}");
+    assert_eq!(
+        err.matches("Could not find chunk of type: rust").count(),
+        1,
+        "{err}"
+    );
+    assert!(
+        err.contains("Did you mean ruSt? It only differs by case"),
+        "{err}"
+    );
+    assert!(
+        err.contains("Non-standard chunks in the file: teXt, ruSt"),
+        "{err}"
+    );
+
+    let output = pngme(["decode", "--format", "json", file.to_str().unwrap(), "rust"]);
+    assert!(
+        stdout(&output).contains(
+            r#""suggestions":[{"kind":"different-case","chunk_type":"ruSt"},{"kind":"non-standard","chunk_type":"teXt"},{"kind":"non-standard","chunk_type":"ruSt"}]"#
+        ),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn envelope_under_another_type_is_suggested() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode",
+        file.to_str().unwrap(),
+        "abCd",
+        "hello",
+        "--annotate",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["decode", file.to_str().unwrap(), "xyZw"]);
+    let err = stderr(&output);
+    assert!(
+        err.contains("A pngme message was found in a abCd chunk"),
+        "{err}"
+    );
+    assert!(!err.contains("Did you mean"), "{err}");
+
+    let output = pngme(["decode", "--format", "json", file.to_str().unwrap(), "xyZw"]);
+    assert!(
+        stdout(&output).contains(r#"{"kind":"pngme-message","chunk_type":"abCd"}"#),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn file_without_custom_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(
+        dir.path(),
+        "image.png",
+        &png_bytes(&[
+            ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+            ("tEXt", b"Title\0plain"),
+            ("IDAT", &[0x78, 0x9c]),
+            ("IEND", &[]),
+        ]),
+    );
+
+    let output = pngme(["decode", file.to_str().unwrap(), "xyZw"]);
+    let err = stderr(&output);
+    assert!(err.contains("The file has no non-standard chunks"), "{err}");
+    assert!(!err.contains("Did you mean"), "{err}");
+    assert!(!err.contains("pngme message"), "{err}");
+
+    let output = pngme(["decode", "--format", "json", file.to_str().unwrap(), "xyZw"]);
+    assert!(
+        !stdout(&output).contains("suggestions"),
+        "{}",
+        stdout(&output)
+    );
+}
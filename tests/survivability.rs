@@ -0,0 +1,78 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn survivability_reports_rating_and_suggestions() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = png_bytes(&[
+        ("IHDR", b""),
+        ("IDAT", b"x"),
+        ("ruST", b"hi"),
+        ("IEND", b""),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &png);
+
+    let output = pngme(["survivability".as_ref(), file.as_os_str(), "ruST".as_ref()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Survivability: poor"), "{stdout}");
+    assert!(
+        stdout.contains("Suggestions: set safe-to-copy bit, move before IDAT"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn apply_suggestions_writes_renamed_and_moved_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = png_bytes(&[
+        ("IHDR", b""),
+        ("IDAT", b"x"),
+        ("ruST", b"hi"),
+        ("IEND", b""),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &png);
+    let out = dir.path().join("out.png");
+
+    let output = pngme([
+        "survivability".as_ref(),
+        file.as_os_str(),
+        "ruST".as_ref(),
+        "--apply-suggestions".as_ref(),
+        "--output".as_ref(),
+        out.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let expected = png_bytes(&[
+        ("IHDR", b""),
+        ("ruSt", b"hi"),
+        ("IDAT", b"x"),
+        ("IEND", b""),
+    ]);
+    assert_eq!(std::fs::read(&out).unwrap(), expected);
+    assert_eq!(std::fs::read(&file).unwrap(), png);
+}
+
+#[test]
+fn critical_chunks_get_no_suggestions() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = fixture_png();
+    let file = write_fixture(dir.path(), "image.png", &png);
+
+    for chunk_type in ["IHDR", "IEND"] {
+        let output = pngme([
+            "survivability",
+            file.to_str().unwrap(),
+            chunk_type,
+            "--apply-suggestions",
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        let printed = stdout(&output);
+        assert!(!printed.contains("Suggestions:"), "{printed}");
+        assert!(printed.ends_with("Nothing to apply\n"), "{printed}");
+    }
+    assert_eq!(std::fs::read(&file).unwrap(), png);
+}
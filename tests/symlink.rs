@@ -0,0 +1,30 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::symlink};
+
+use common::*;
+
+#[test]
+fn encode_in_place_through_a_symlink_edits_its_target() {
+    let dir = tempfile::tempdir().unwrap();
+    let real = write_fixture(dir.path(), "real.png", &fixture_png());
+    let link = dir.path().join("link.png");
+    symlink(&real, &link).unwrap();
+
+    let output = pngme([
+        "encode".as_ref(),
+        link.as_os_str(),
+        "ruSt".as_ref(),
+        "hello".as_ref(),
+        "--replace".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    assert_eq!(fs::read_link(&link).unwrap(), real);
+    let output = pngme(["decode".as_ref(), real.as_os_str(), "ruSt".as_ref(), "--quiet".as_ref()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "hello\n");
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+fn dir_entries(dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn no_temporary_file_is_left_behind() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let output = dir.path().join("output.png");
+
+    let result = pngme([
+        "encode",
+        file.to_str().unwrap(),
+        "abCd",
+        "hello",
+        output.to_str().unwrap(),
+    ]);
+    assert!(result.status.success(), "{}", stderr(&result));
+
+    let result = pngme(["remove", file.to_str().unwrap(), "ruSt"]);
+    assert!(result.status.success(), "{}", stderr(&result));
+
+    assert_eq!(dir_entries(dir.path()), ["image.png", "output.png"]);
+}
+
+#[test]
+fn failed_run_leaves_no_temporary_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    for keep in [false, true] {
+        let mut args = vec!["remove", file.to_str().unwrap(), "xyZw"];
+        if keep {
+            args.push("--keep-temp");
+        }
+
+        let result = pngme(args);
+        assert!(!result.status.success());
+        assert_eq!(dir_entries(dir.path()), ["image.png"]);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn new_output_is_readable_by_others() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let output = dir.path().join("output.png");
+
+    let result = pngme([
+        "encode",
+        file.to_str().unwrap(),
+        "abCd",
+        "hello",
+        output.to_str().unwrap(),
+    ]);
+    assert!(result.status.success(), "{}", stderr(&result));
+
+    let mode = fs::metadata(&output).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o644);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use std::path::Path;
+
+use common::*;
+
+fn decoded(file: &Path) -> String {
+    let output = pngme([
+        "decode".as_ref(),
+        file.as_os_str(),
+        "maNi".as_ref(),
+        "-q".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    stdout(&output).trim().to_string()
+}
+
+#[test]
+fn encode_template_file_with_vars() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let template = dir.path().join("manifest.tpl");
+    std::fs::write(&template, "version={{version}} built={{date}}").unwrap();
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "maNi".as_ref(),
+        "--message-template".as_ref(),
+        template.as_os_str(),
+        "--var".as_ref(),
+        "version=1.4.2".as_ref(),
+        "--var".as_ref(),
+        "date=2025-01-01".as_ref(),
+        "--deterministic".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(decoded(&file), "version=1.4.2 built=2025-01-01");
+}
+
+#[test]
+fn encode_inline_template() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "maNi".as_ref(),
+        r"\{{literal}} {{name}}".as_ref(),
+        "--template".as_ref(),
+        "--var".as_ref(),
+        "name=app".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(decoded(&file), "{{literal}} app");
+}
+
+#[test]
+fn unknown_placeholder_leaves_file_untouched() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "maNi".as_ref(),
+        "{{missing}}".as_ref(),
+        "--template".as_ref(),
+    ]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("Unknown placeholder {{missing}} (available: date, file"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(std::fs::read(&file).unwrap(), fixture_png());
+}
+
+#[test]
+fn deterministic_rejects_date() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "maNi".as_ref(),
+        "{{date}}".as_ref(),
+        "--template".as_ref(),
+        "--deterministic".as_ref(),
+    ]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("not deterministic"),
+        "{}",
+        stderr(&output)
+    );
+}
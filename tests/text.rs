@@ -0,0 +1,26 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn decode_text_chunk_as_latin1() {
+    let dir = tempfile::tempdir().unwrap();
+    let bytes = png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        ("tEXt", b"Comment\0caf\xe9"),
+        ("IEND", &[]),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &bytes);
+
+    let output = pngme([
+        "decode".as_ref(),
+        file.as_os_str(),
+        "tEXt".as_ref(),
+        "-q".as_ref(),
+    ]);
+    assert_eq!(stdout(&output), "café\n");
+
+    let output = pngme(["print".as_ref(), file.as_os_str()]);
+    assert!(stdout(&output).contains("café"));
+    assert!(!stdout(&output).contains("<Invalid UTF-8>"));
+}
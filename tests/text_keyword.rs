@@ -0,0 +1,151 @@
+mod common;
+
+use common::*;
+use pngme::png::Png;
+
+fn text_chunk_data(file: &std::path::Path) -> Vec<u8> {
+    let png = Png::try_from(std::fs::read(file).unwrap().as_slice()).unwrap();
+    png.chunk_by_type("tEXt").unwrap().data().to_vec()
+}
+
+#[test]
+fn message_is_written_as_standard_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = path.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "tEXt",
+        "Sunset café",
+        "--text-keyword",
+        "Title",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(text_chunk_data(&path), b"Title\0Sunset caf\xe9");
+
+    let output = pngme(["decode", "--quiet", file, "tEXt"]);
+    assert_eq!(stdout(&output), "Sunset café\n");
+
+    let output = pngme(["decode", "--format", "json", file, "tEXt"]);
+    assert!(
+        stdout(&output).contains(r#""data":"Sunset café","keyword":"Title""#),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn print_and_decode_split_the_keyword() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    pngme(["encode", file, "tEXt", "Ada", "--text-keyword", "Author"]);
+
+    let output = stdout(&pngme(["print", file]));
+    assert!(output.contains("data: Author: Ada,"), "{output}");
+    assert!(!output.contains("\\0"), "{output}");
+
+    let output = stdout(&pngme(["decode", file, "tEXt"]));
+    assert!(output.contains("data: Author: Ada,"), "{output}");
+}
+
+#[test]
+fn invalid_keyword_or_text_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = path.to_str().unwrap();
+    let long = "k".repeat(80);
+
+    for (keyword, text, code) in [
+        (" Title", "text", "E0703"),
+        (long.as_str(), "text", "E0702"),
+        ("", "text", "E0701"),
+        ("Title", "🦀", "E0705"),
+    ] {
+        let output = pngme(["encode", file, "tEXt", text, "--text-keyword", keyword]);
+        assert!(!output.status.success(), "{keyword}");
+        assert!(
+            stderr(&output).contains(&format!("error[{code}]")),
+            "{}",
+            stderr(&output)
+        );
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), fixture_png());
+}
+
+#[test]
+fn keyword_needs_a_text_chunk() {
+    let output = pngme([
+        "encode",
+        "image.png",
+        "ruSt",
+        "hi",
+        "--text-keyword",
+        "Title",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output)
+            .contains("--text-keyword can't be combined with a chunk name other than tEXt")
+    );
+
+    let output = pngme([
+        "encode",
+        "image.png",
+        "tEXt",
+        "hi",
+        "--text-keyword",
+        "Title",
+        "--compress",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn characters_outside_latin1_can_be_replaced() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = path.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "tEXt",
+        "café 🦀",
+        "--text-keyword",
+        "Title",
+        "--force-latin1-lossy",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("replaced 1 character(s) outside Latin-1 with '?'"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(text_chunk_data(&path), b"Title\0caf\xe9 ?");
+
+    let output = pngme(["encode", file, "ruSt", "hi", "--force-latin1-lossy"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn bytes_that_are_not_utf8_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_fixture(dir.path(), "image.png", &fixture_png());
+    let message = write_fixture(dir.path(), "message.bin", b"caf\xe9");
+
+    let output = pngme([
+        "encode",
+        path.to_str().unwrap(),
+        "tEXt",
+        "--message-file",
+        message.to_str().unwrap(),
+        "--text-keyword",
+        "Title",
+    ]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("E0808"), "{}", stderr(&output));
+    assert_eq!(std::fs::read(&path).unwrap(), fixture_png());
+}
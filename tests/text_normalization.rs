@@ -0,0 +1,160 @@
+mod common;
+
+use common::*;
+
+fn raw_payload(file: &str) -> Vec<u8> {
+    let output = pngme(["decode", "--raw", file, "abCd"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    output.stdout
+}
+
+#[test]
+fn encode_cleans_up_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "\u{FEFF}one\r\ntwo\r\n",
+        "--strip-bom",
+        "--normalize-newlines",
+        "lf",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(raw_payload(file), b"one\ntwo\n");
+}
+
+#[test]
+fn text_is_kept_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", file, "abCd", "\u{FEFF}one\r\n"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(raw_payload(file), "\u{FEFF}one\r\n".as_bytes());
+}
+
+#[test]
+fn decode_cleans_up_displayed_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let output = pngme(["encode", file, "abCd", "\u{FEFF}a\nb"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme([
+        "decode",
+        "--quiet",
+        "--strip-bom",
+        "--normalize-newlines",
+        "crlf",
+        file,
+        "abCd",
+    ]);
+    assert_eq!(stdout(&output), "a\r\nb\n");
+
+    let output = pngme([
+        "decode",
+        "--format",
+        "json",
+        "--strip-bom",
+        "--normalize-newlines",
+        "crlf",
+        file,
+        "abCd",
+    ]);
+    assert!(
+        stdout(&output).contains(r#""data":"a\r\nb""#),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn binary_payloads_are_untouched() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    // BOM, CRLF and a lone LF given as hex
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "efbbbf410d0a420a",
+        "--input-encoding",
+        "hex",
+        "--strip-bom",
+        "--normalize-newlines",
+        "crlf",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stored = b"\xEF\xBB\xBFA\r\nB\n";
+    assert_eq!(raw_payload(file), stored);
+
+    let output = pngme([
+        "decode",
+        "--raw",
+        "--strip-bom",
+        "--normalize-newlines",
+        "lf",
+        file,
+        "abCd",
+    ]);
+    assert_eq!(output.stdout, stored);
+
+    let output = pngme([
+        "decode",
+        "--output-encoding",
+        "hex",
+        "--strip-bom",
+        "--normalize-newlines",
+        "lf",
+        file,
+        "abCd",
+    ]);
+    assert_eq!(stdout(&output), "efbbbf410d0a420a\n");
+}
+
+#[test]
+fn message_files_are_embedded_byte_for_byte() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    // A BOM, CRLF, a lone LF and bytes that aren't UTF-8
+    let payload = b"\xEF\xBB\xBF\x00\r\n\xFF\xFE\n\r\x89PNG\r\n\x1a\n";
+    let payload_file = write_fixture(dir.path(), "payload.bin", payload);
+
+    let output = pngme([
+        "encode",
+        file,
+        "--chunk",
+        "abCd",
+        "--input-file",
+        payload_file.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(raw_payload(file), payload);
+
+    for flags in [&["--strip-bom"][..], &["--normalize-newlines", "lf"]] {
+        let mut args = vec![
+            "encode",
+            file,
+            "--chunk",
+            "abCd",
+            "--input-file",
+            payload_file.to_str().unwrap(),
+        ];
+        args.extend(flags);
+        let output = pngme(args);
+        assert_eq!(output.status.code(), Some(2), "{flags:?}");
+        assert!(stderr(&output).contains("cannot be used with"), "{}", stderr(&output));
+    }
+    assert_eq!(raw_payload(file), payload);
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use std::{fs, path::PathBuf, process::Command};
+
+use common::*;
+
+fn plain_png() -> Vec<u8> {
+    png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        (
+            "IDAT",
+            &[0x78, 0x9c, 0x62, 0x00, 0x01, 0x00, 0x00, 0xff, 0xff],
+        ),
+        ("IEND", &[]),
+    ])
+}
+
+/// A sidecar of the chunks of the fixture, and three images to import it
+/// into. The second one already has the ruSt chunk, so importing fails on
+/// it.
+fn setup(dir: &std::path::Path) -> (String, [PathBuf; 3]) {
+    let source = write_fixture(dir, "source.png", &fixture_png());
+    let sidecar = dir.join("source.pngmeta");
+    let output = pngme([
+        "export-meta",
+        source.to_str().unwrap(),
+        sidecar.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let targets = [
+        write_fixture(dir, "first.png", &plain_png()),
+        write_fixture(dir, "second.png", &fixture_png()),
+        write_fixture(dir, "third.png", &plain_png()),
+    ];
+
+    (sidecar.to_str().unwrap().to_string(), targets)
+}
+
+fn import_meta(sidecar: &str, targets: &[PathBuf], flags: &[&str]) -> std::process::Output {
+    let mut args = vec!["import-meta"];
+    args.extend(targets.iter().map(|target| target.to_str().unwrap()));
+    args.push(sidecar);
+    args.extend(flags);
+    pngme(args)
+}
+
+#[test]
+fn a_failed_transaction_changes_none_of_the_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let (sidecar, targets) = setup(dir.path());
+    let before: Vec<_> = targets
+        .iter()
+        .map(|target| fs::read(target).unwrap())
+        .collect();
+
+    let output = import_meta(&sidecar, &targets, &["--transactional"]);
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("E0902"), "{message}");
+    assert!(
+        message.contains("Transaction aborted at stage 2 of 3"),
+        "{message}"
+    );
+    assert!(message.contains("second.png"), "{message}");
+    assert_eq!(stdout(&output), "");
+
+    for (target, before) in targets.iter().zip(&before) {
+        assert_eq!(&fs::read(target).unwrap(), before, "{}", target.display());
+    }
+    // No staged temporary file is left behind
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 5);
+}
+
+#[test]
+fn without_a_transaction_the_files_before_the_failure_are_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let (sidecar, targets) = setup(dir.path());
+
+    let output = import_meta(&sidecar, &targets, &[]);
+    assert!(!output.status.success());
+
+    assert_ne!(fs::read(&targets[0]).unwrap(), plain_png());
+    assert_eq!(fs::read(&targets[2]).unwrap(), plain_png());
+}
+
+#[test]
+fn a_transaction_writes_every_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let (sidecar, targets) = setup(dir.path());
+    fs::write(&targets[1], plain_png()).unwrap();
+
+    let output = import_meta(&sidecar, &targets, &["--transactional"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    for target in &targets {
+        let printed = stdout(&pngme(["print", target.to_str().unwrap()]));
+        assert!(printed.contains("ruSt"), "{printed}");
+    }
+    assert_eq!(
+        stdout(&output).lines().collect::<Vec<_>>(),
+        targets
+            .iter()
+            .map(|target| format!("{}: Imported 2 chunk(s)", target.display()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn one_patch_for_several_files_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let (sidecar, targets) = setup(dir.path());
+    let patch = dir.path().join("import.patch");
+
+    let output = import_meta(
+        &sidecar,
+        &targets,
+        &["--emit-patch", patch.to_str().unwrap()],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("--emit-patch can't be combined with several files"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn a_failure_on_the_last_stage_discards_the_staged_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let (sidecar, mut targets) = setup(dir.path());
+    fs::write(&targets[1], plain_png()).unwrap();
+    targets.swap(1, 2);
+    fs::write(&targets[2], fixture_png()).unwrap();
+
+    let output = import_meta(&sidecar, &targets, &["--transactional"]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("Transaction aborted at stage 3 of 3"),
+        "{}",
+        stderr(&output)
+    );
+
+    assert_eq!(fs::read(&targets[0]).unwrap(), plain_png());
+    assert_eq!(fs::read(&targets[1]).unwrap(), plain_png());
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 5);
+}
+
+#[test]
+fn a_transaction_writes_the_standard_output_directly() {
+    let dir = tempfile::tempdir().unwrap();
+    let (sidecar, targets) = setup(dir.path());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .current_dir(dir.path())
+        .args(["import-meta", targets[0].to_str().unwrap(), &sidecar])
+        .args(["--output", "-", "--transactional"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert!(output.stdout.starts_with(&PNG_SIGNATURE));
+    assert!(!dir.path().join("-").exists());
+    assert_eq!(fs::read(&targets[0]).unwrap(), plain_png());
+}
+
+#[test]
+fn a_transaction_writes_to_the_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let (sidecar, targets) = setup(dir.path());
+    let copy = dir.path().join("copy.png");
+
+    let output = import_meta(
+        &sidecar,
+        &targets[..1],
+        &["--output", copy.to_str().unwrap(), "--transactional"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let printed = stdout(&pngme(["print", copy.to_str().unwrap()]));
+    assert!(printed.contains("ruSt"), "{printed}");
+    assert_eq!(fs::read(&targets[0]).unwrap(), plain_png());
+}
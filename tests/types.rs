@@ -0,0 +1,99 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+
+const IHDR: &[u8] = &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0];
+
+/// 30 images spread over nested directories: each has an IHDR, two IDAT
+/// (of 1 and `n` bytes) and an IEND, every third one a 4-byte ruSt chunk.
+/// A corrupted PNG and a text file sit next to them.
+fn corpus(dir: &Path) {
+    for n in 1..=30usize {
+        let sub = dir
+            .join(format!("set{}", n % 3))
+            .join(format!("part{}", n % 2));
+        fs::create_dir_all(&sub).unwrap();
+
+        let data = vec![0u8; n];
+        let mut chunks: Vec<(&str, &[u8])> = vec![("IHDR", IHDR), ("IDAT", &[0]), ("IDAT", &data)];
+        if n % 3 == 0 {
+            chunks.push(("ruSt", b"note"));
+        }
+        chunks.push(("IEND", &[]));
+
+        write_fixture(&sub, &format!("image{n}.png"), &png_bytes(&chunks));
+    }
+
+    write_fixture(dir, "broken.png", b"not a png");
+    write_fixture(dir, "notes.txt", b"not an image");
+}
+
+#[test]
+fn aggregates_a_corpus() {
+    let dir = tempfile::tempdir().unwrap();
+    corpus(dir.path());
+
+    let output = pngme(["types", "--recursive", dir.path().to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let out = stdout(&output);
+    let lines: Vec<Vec<&str>> = out
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(
+        lines[0],
+        [
+            "Type", "Chunks", "Files", "Total", "bytes", "Min", "Median", "Max"
+        ]
+    );
+    // 30 + 465 bytes over 60 IDAT chunks
+    assert_eq!(lines[1], ["IDAT", "60", "30", "495", "1", "1", "30"]);
+    assert_eq!(lines[2], ["IEND", "30", "30", "0", "0", "0", "0"]);
+    assert_eq!(lines[3], ["IHDR", "30", "30", "390", "13", "13", "13"]);
+    assert_eq!(lines[4], ["ruSt", "10", "10", "40", "4", "4", "4"]);
+    assert_eq!(out.lines().last(), Some("30 files, 1 skipped"));
+    assert!(stderr(&output).contains("skipping"), "{}", stderr(&output));
+}
+
+#[test]
+fn top_and_json() {
+    let dir = tempfile::tempdir().unwrap();
+    corpus(dir.path());
+
+    let output = pngme([
+        "types",
+        "--recursive",
+        "--top",
+        "1",
+        "--format",
+        "json",
+        dir.path().to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(report["files"], 30);
+    assert_eq!(report["skipped"], 1);
+
+    let types = report["types"].as_array().unwrap();
+    assert_eq!(types.len(), 1);
+    assert_eq!(types[0]["chunk_type"], "IDAT");
+    assert_eq!(types[0]["chunks"], 60);
+    assert_eq!(types[0]["total_bytes"], 495);
+}
+
+#[test]
+fn without_recursive_only_the_top_level_is_read() {
+    let dir = tempfile::tempdir().unwrap();
+    corpus(dir.path());
+
+    let output = pngme(["types", "--format", "json", dir.path().to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(report["files"], 0);
+    assert_eq!(report["skipped"], 1);
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+#[test]
+fn undo_restores_each_state_in_turn() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let original = fs::read(&file).unwrap();
+
+    let output = pngme([
+        "--undoable".as_ref(),
+        "encode".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+        "first".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let after_first = fs::read(&file).unwrap();
+
+    let output = pngme([
+        "--undoable".as_ref(),
+        "encode".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+        "second".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["undo".as_ref(), "--list".as_ref(), file.as_os_str()]);
+    let listed = stdout(&output);
+    assert_eq!(listed.lines().count(), 2, "{listed}");
+    assert!(listed.contains("before encode"), "{listed}");
+
+    let output = pngme(["undo".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read(&file).unwrap(), after_first);
+
+    let output = pngme(["undo".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read(&file).unwrap(), original);
+
+    let output = pngme(["undo".as_ref(), file.as_os_str()]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Nothing to undo"));
+}
+
+#[test]
+fn only_the_last_states_are_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    for message in ["one", "two", "three"] {
+        let output = pngme([
+            "--undoable".as_ref(),
+            "--keep-backups".as_ref(),
+            "2".as_ref(),
+            "encode".as_ref(),
+            file.as_os_str(),
+            "ruSt".as_ref(),
+            message.as_ref(),
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+
+    let output = pngme(["undo".as_ref(), "--list".as_ref(), file.as_os_str()]);
+    assert_eq!(stdout(&output).lines().count(), 2);
+}
+
+#[test]
+fn no_state_without_undoable() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+        "message".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert!(!dir.path().join(".pngme").exists());
+}
+
+#[test]
+fn recursive_encode_leaves_undo_states_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let tree = dir.path().join("tree");
+    fs::create_dir(&tree).unwrap();
+    let file = write_fixture(&tree, "image.png", &fixture_png());
+    let original = fs::read(&file).unwrap();
+
+    let encode = |message: &str| {
+        let output = pngme([
+            "--undoable".as_ref(),
+            "encode".as_ref(),
+            "--recursive".as_ref(),
+            tree.as_os_str(),
+            "--chunk".as_ref(),
+            "ruSt".as_ref(),
+            "--message".as_ref(),
+            message.as_ref(),
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(!stdout(&output).contains(".pngme"), "{}", stdout(&output));
+    };
+    encode("first");
+    let after_first = fs::read(&file).unwrap();
+    encode("second");
+
+    assert!(!tree.join(".pngme/undo/.pngme").exists());
+
+    let output = pngme(["undo".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read(&file).unwrap(), after_first);
+
+    let output = pngme(["undo".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read(&file).unwrap(), original);
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::*;
+
+/// A valid image with a fake critical `XXXX` chunk before IEND
+fn image_with_unknown_critical(dir: &tempfile::TempDir) -> PathBuf {
+    let bytes = png_bytes(&[
+        ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+        ("IDAT", &[1, 2, 3]),
+        ("XXXX", b"smuggled"),
+        ("IEND", &[]),
+    ]);
+
+    write_fixture(dir.path(), "image.png", &bytes)
+}
+
+#[test]
+fn verify_reports_unknown_critical_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image_with_unknown_critical(&dir);
+
+    let output = pngme(["verify".as_ref(), file.as_os_str()]);
+
+    assert!(!output.status.success());
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains(
+            "[critical] 48..68 W0205 unknown-critical-chunk: unknown critical chunk XXXX at offset 48"
+        ),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn encode_and_remove_refuse_unless_allowed() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image_with_unknown_critical(&dir);
+    let original = fs::read(&file).unwrap();
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+        "message".as_ref(),
+    ]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--allow-unknown-critical"));
+
+    let output = pngme(["remove".as_ref(), file.as_os_str(), "IDAT".as_ref()]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("unknown critical chunk XXXX"));
+    assert_eq!(fs::read(&file).unwrap(), original);
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+        "message".as_ref(),
+        "--allow-unknown-critical".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_ne!(fs::read(&file).unwrap(), original);
+}
+
+#[test]
+fn removing_the_unknown_critical_chunk_is_allowed() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image_with_unknown_critical(&dir);
+
+    let output = pngme(["remove".as_ref(), file.as_os_str(), "XXXX".as_ref()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["verify".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stdout(&output));
+}
+
+#[test]
+fn fix_drops_unknown_critical_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image_with_unknown_critical(&dir);
+
+    let output = pngme(["fix".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Fixed 1 problem(s)"));
+
+    let output = pngme(["verify".as_ref(), file.as_os_str()]);
+    assert!(output.status.success(), "{}", stdout(&output));
+}
+
+#[test]
+fn scan_flags_unknown_critical_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = image_with_unknown_critical(&dir);
+
+    let output = pngme(["scan".as_ref(), file.as_os_str()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("[critical]") && stdout.contains("unknown critical XXXX chunk"),
+        "{stdout}"
+    );
+}
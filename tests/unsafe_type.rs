@@ -0,0 +1,93 @@
+mod common;
+
+use common::*;
+
+/// Encodes into `chunk_type`, returning the output
+fn encode(chunk_type: &str, extra: &[&str]) -> std::process::Output {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let mut args = vec!["encode", file.to_str().unwrap(), chunk_type, "hi"];
+    args.extend(extra);
+    pngme(args)
+}
+
+#[test]
+fn ancillary_type_with_valid_reserved_bit_is_written() {
+    let output = encode("abCd", &[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn critical_type_is_refused() {
+    let output = encode("AbCd", &[]);
+
+    assert!(!output.status.success());
+    let stderr = stderr(&output);
+    assert!(stderr.contains("error[E0519]"), "{stderr}");
+    assert!(stderr.contains("such as abCd"), "{stderr}");
+    assert!(stderr.contains("--allow-unsafe-type"), "{stderr}");
+}
+
+#[test]
+fn known_critical_type_is_refused() {
+    let output = encode("IDAT", &[]);
+
+    assert!(
+        stderr(&output).contains("error[E0519]"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(
+        stderr(&output).contains("such as iDAT"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn reserved_bit_is_refused() {
+    let output = encode("abcd", &[]);
+
+    assert!(!output.status.success());
+    let stderr = stderr(&output);
+    assert!(stderr.contains("error[E0520]"), "{stderr}");
+    assert!(stderr.contains("did you mean abCd?"), "{stderr}");
+}
+
+#[test]
+fn critical_type_with_reserved_bit_suggests_fixing_both() {
+    let output = encode("Abcd", &[]);
+
+    assert!(
+        stderr(&output).contains("error[E0519]"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(
+        stderr(&output).contains("such as abCd"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn unsafe_types_are_written_when_allowed() {
+    let output = encode("AbCd", &["--allow-unsafe-type", "--allow-unknown-critical"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("warning[W0305]: 'AbCd' is critical"));
+
+    let output = encode("abcd", &["--allow-unsafe-type", "--assume-yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = encode(
+        "Abcd",
+        &[
+            "--allow-unsafe-type",
+            "--assume-yes",
+            "--allow-unknown-critical",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+#[test]
+fn custom_threshold_is_reported_on_stderr() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let size = fixture_png().len() + 12 + 5;
+
+    let output = pngme(["encode", file, "abCd", "hello", "--size-warn", "100"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains(&format!(
+            "warning[W0301]: the output is {size} bytes, over the limit of 100 bytes"
+        )),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = pngme(["encode", file, "abCd", "hello", "--size-warn", "1M"]);
+    assert!(output.status.success());
+    assert!(!stderr(&output).contains("W0301"), "{}", stderr(&output));
+}
+
+#[test]
+fn json_report_lists_the_thresholds_crossed() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "hello",
+        "--size-warn",
+        "10",
+        "--size-warn",
+        "20",
+        "--size-warn",
+        "1K",
+        "--format",
+        "json",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let size = (fixture_png().len() + 12 + 5) as u64;
+    assert_eq!(report["size"], size);
+    assert_eq!(report["output"], file);
+    let warnings = report["warnings"].as_array().unwrap();
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0]["code"], "W0301");
+    assert_eq!(warnings[0]["threshold"], 10);
+    assert_eq!(warnings[1]["threshold"], 20);
+}
+
+#[test]
+fn common_limits_are_checked_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let payload = dir.path().join("payload.bin");
+    fs::write(&payload, vec![b'x'; 8 << 20]).unwrap();
+    let payload = payload.to_str().unwrap();
+
+    let output = pngme(["encode", file, "abCd", "--message-file", payload]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("over the limit of 8 MiB (Discord"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!stderr(&output).contains("10 MiB"));
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCe",
+        "--message-file",
+        payload,
+        "--no-size-warn",
+    ]);
+    assert!(output.status.success());
+    assert!(!stderr(&output).contains("W0301"), "{}", stderr(&output));
+}
+
+#[test]
+fn json_report_needs_another_output_than_stdout() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode",
+        file.to_str().unwrap(),
+        "abCd",
+        "hello",
+        "-",
+        "--format",
+        "json",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("the standard output holds the image"),
+        "{}",
+        stderr(&output)
+    );
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn every_problem_is_listed() {
+    let output = pngme([
+        "--undoable",
+        "--max-input-size",
+        "lots",
+        "encode",
+        "missing.png",
+        "ru5t",
+        "hi",
+        "out.png",
+        "--var",
+        "name=value",
+    ]);
+
+    assert_eq!(output.status.code(), Some(2));
+    let err = stderr(&output);
+    assert!(
+        err.starts_with("error[E1304]: 5 problems with the arguments:\n"),
+        "{err}"
+    );
+    for (number, problem) in [
+        (1, "[E0404] CHUNK_NAME: Chunk name 'ru5t'"),
+        (2, "[E1301] FILE: missing.png does not exist"),
+        (3, "[E1303] --max-input-size: 'lots' is not a size"),
+        (4, "[E1302] --undoable can't be combined with --output"),
+        (5, "[E1302] --var can't be combined"),
+    ] {
+        assert!(err.contains(&format!("  {number}. {problem}")), "{err}");
+    }
+}
+
+#[test]
+fn json_problems_keep_their_codes() {
+    let output = pngme(["decode", "--format", "json", "missing.png", "ru5t"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    let error: serde_json::Value = serde_json::from_str(&stderr(&output)).unwrap();
+    let problems = error["error"]["problems"].as_array().unwrap();
+    let codes: Vec<&str> = problems
+        .iter()
+        .map(|problem| problem["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(codes, ["E0404", "E1301"], "{problems:?}");
+    assert!(
+        problems[1]["message"]
+            .as_str()
+            .unwrap()
+            .contains("missing.png does not exist"),
+        "{problems:?}"
+    );
+}
+
+#[test]
+fn sizes_take_units() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["--max-input-size", "1K", "info", file]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["--max-input-size", "8B", "info", file]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("E0602"), "{}", stderr(&output));
+}
+
+#[test]
+fn valid_arguments_run_the_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "--undoable".as_ref(),
+        "encode".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+        "hi".as_ref(),
+        file.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn verify_reports_bad_crc_and_trailing_data_as_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut png = png_bytes(&[("IHDR", b"header"), ("ruSt", b"hi"), ("IEND", b"")]);
+    // ruSt starts at 8 + 18 = 26, its CRC at 26 + 8 + 2 = 36
+    png[36] ^= 0xff;
+    let end = png.len();
+    png.extend_from_slice(b"zip");
+    let file = write_fixture(dir.path(), "image.png", &png);
+
+    let output = pngme([
+        "verify".as_ref(),
+        file.as_os_str(),
+        "--format".as_ref(),
+        "json".as_ref(),
+    ]);
+
+    assert!(!output.status.success());
+    let reports: Vec<serde_json::Value> = stdout(&output)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(reports.len(), 2);
+
+    assert_eq!(reports[0]["code"], "W0204");
+    assert_eq!(reports[0]["name"], "crc-mismatch");
+    assert_eq!(reports[0]["severity"], "critical");
+    assert_eq!(
+        reports[0]["range"],
+        serde_json::json!({"start": 36, "end": 40})
+    );
+
+    assert_eq!(reports[1]["code"], "W0203");
+    assert_eq!(reports[1]["name"], "trailing-bytes");
+    assert_eq!(
+        reports[1]["range"],
+        serde_json::json!({"start": end, "end": end + 3})
+    );
+}
+
+#[test]
+fn verify_valid_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme(["verify".as_ref(), file.as_os_str()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output).trim(), "No problems found");
+}
+
+#[test]
+fn edited_files_end_with_a_single_iend() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = png_bytes(&[
+        ("IHDR", b"header"),
+        ("IEND", b""),
+        ("IDAT", b"data"),
+        ("IEND", b""),
+    ]);
+    let file = write_fixture(dir.path(), "image.png", &png);
+
+    let output = pngme([
+        "encode".as_ref(),
+        file.as_os_str(),
+        "ruSt".as_ref(),
+        "hi".as_ref(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(chunk_types(&file), ["IHDR", "IDAT", "ruSt", "IEND"]);
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+#[test]
+fn a_message_that_decodes_back_is_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "abCd",
+        "a message split in several pieces",
+        "--split-size",
+        "8",
+        "--compress",
+        "--encrypt",
+        "--password",
+        "secret",
+        "--verify-after",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pngme(["decode", "--decrypt", "--password", "secret", file, "abCd"]);
+    assert!(
+        stdout(&output).contains("a message split in several pieces"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn text_chunks_are_compared_as_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme([
+        "encode",
+        file,
+        "zTXt",
+        "Ada Lovelace",
+        "--ztxt",
+        "Author",
+        "--verify-after",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn the_chunk_written_is_the_one_checked() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    // The fixture's ruSt chunk comes first, the new one is read where it
+    // was added
+    let output = pngme(["encode", file, "ruSt", "hello", "--verify-after"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let printed = stdout(&pngme(["print", file]));
+    assert_eq!(printed.matches("ruSt").count(), 2, "{printed}");
+}
+
+#[test]
+fn an_output_that_does_not_read_back_writes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    // The fixture has 7 chunks, the output one more than the limit
+    let output = pngme([
+        "--max-chunks",
+        "7",
+        "encode",
+        file,
+        "abCd",
+        "hello",
+        "--verify-after",
+    ]);
+    assert_eq!(output.status.code(), Some(5));
+    let message = stderr(&output);
+    assert!(message.contains("error[E0527]"), "{message}");
+    assert!(
+        message.contains("The abCd message doesn't decode back from"),
+        "{message}"
+    );
+    assert!(message.contains("nothing was written"), "{message}");
+    assert_eq!(fs::read(file).unwrap(), fixture_png());
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn keep_on_failure_leaves_the_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+    let out = dir.path().join("out.png");
+    let out = out.to_str().unwrap();
+
+    let encode = |extra: &[&str]| {
+        let mut args = vec![
+            "--max-chunks",
+            "7",
+            "encode",
+            file,
+            "abCd",
+            "hello",
+            out,
+            "--verify-after",
+        ];
+        args.extend(extra);
+        pngme(args)
+    };
+
+    let output = encode(&[]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(!fs::exists(out).unwrap());
+
+    let output = encode(&["--keep-on-failure"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(
+        stderr(&output).contains("the output was kept"),
+        "{}",
+        stderr(&output)
+    );
+    let printed = stdout(&pngme(["print", out]));
+    assert!(printed.contains("abCd"), "{printed}");
+}
+
+#[test]
+fn keep_on_failure_needs_verify_after() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+
+    let output = pngme([
+        "encode",
+        file.to_str().unwrap(),
+        "abCd",
+        "hello",
+        "--keep-on-failure",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use std::io::Write;
+
+use common::*;
+use flate2::{Compression, write::ZlibEncoder};
+use pngme::fixtures::FixtureKind;
+
+/// IHDR data of an 8-bit RGBA image
+fn rgba_header(width: u32, height: u32) -> Vec<u8> {
+    [
+        &width.to_be_bytes()[..],
+        &height.to_be_bytes(),
+        &[8, 6, 0, 0, 0],
+    ]
+    .concat()
+}
+
+/// Compressed scanlines of a `width` x `height` RGBA image
+fn image_data(width: usize, height: usize) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&vec![0; height * (1 + 4 * width)])
+        .unwrap();
+    encoder.finish().unwrap()
+}
+
+fn image(header: &[u8], idat: &[&[u8]]) -> Vec<u8> {
+    let mut chunks: Vec<(&str, &[u8])> = vec![("IHDR", header)];
+    chunks.extend(idat.iter().map(|data| ("IDAT", *data)));
+    chunks.push(("IEND", &[]));
+    png_bytes(&chunks)
+}
+
+#[test]
+fn truncated_image_data_is_only_caught_by_the_deep_check() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = image_data(40, 30);
+    // Every CRC is right, the stream just stops
+    let bytes = image(&rgba_header(40, 30), &[&data[..data.len() / 2]]);
+    let file = write_fixture(dir.path(), "truncated.png", &bytes);
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["verify", file]);
+    assert!(output.status.success(), "{}", stdout(&output));
+
+    let output = pngme(["verify", "--deep", file]);
+    assert!(!output.status.success());
+    let report = stdout(&output);
+    assert!(report.contains("W0207 image-data-size"), "{report}");
+    assert!(report.contains("the IHDR dimensions need 4830"), "{report}");
+    assert!(report.contains("cut short or corrupt"), "{report}");
+}
+
+#[test]
+fn deep_check_reports_short_and_excess_data() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let short = image(&rgba_header(2, 2), &[&image_data(2, 1)]);
+    let file = write_fixture(dir.path(), "short.png", &short);
+    let output = pngme(["verify", "--deep", file.to_str().unwrap()]);
+    assert!(
+        stdout(&output).contains("(9 bytes short)"),
+        "{}",
+        stdout(&output)
+    );
+
+    let excess = image(&rgba_header(1, 1), &[&image_data(1, 2)]);
+    let file = write_fixture(dir.path(), "excess.png", &excess);
+    let output = pngme(["verify", "--deep", file.to_str().unwrap()]);
+    assert!(
+        stdout(&output).contains("(5 bytes too many)"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn deep_check_passes_split_image_data() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = image_data(3, 3);
+    let (head, tail) = data.split_at(5);
+    let file = write_fixture(
+        dir.path(),
+        "image.png",
+        &image(&rgba_header(3, 3), &[head, tail]),
+    );
+
+    let output = pngme(["verify", "--deep", file.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(stdout(&output), "No problems found\n");
+}
+
+#[test]
+fn interlaced_images_are_skipped_with_a_note() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = fixture(dir.path(), FixtureKind::Interlaced);
+
+    let output = pngme(["verify", "--deep", file.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stderr(&output).contains("interlaced images are not checked"));
+}
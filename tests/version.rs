@@ -0,0 +1,89 @@
+mod common;
+
+use common::*;
+use serde_json::Value;
+
+fn json(bytes: &[u8]) -> Value {
+    serde_json::from_slice(bytes).unwrap()
+}
+
+fn assert_build(build: &Value) {
+    assert_eq!(build["name"], "pngme");
+    assert_eq!(build["version"], env!("CARGO_PKG_VERSION"));
+    for field in ["target", "rustc", "profile"] {
+        let value = build[field].as_str().unwrap();
+        assert!(!value.is_empty(), "{field} is empty");
+    }
+    assert!(build["features"].is_array(), "{build}");
+    // Unknown outside a git checkout
+    assert!(
+        build["commit"].is_string() || build["commit"].is_null(),
+        "{build}"
+    );
+}
+
+#[test]
+fn verbose_version_prints_the_build() {
+    for args in [
+        &["--version", "--verbose"][..],
+        &["--verbose", "-V"],
+        &["version"],
+    ] {
+        let output = pngme(args);
+        assert!(output.status.success(), "{}", stderr(&output));
+        let printed = stdout(&output);
+
+        assert!(
+            printed.starts_with(&format!("pngme {}\n", env!("CARGO_PKG_VERSION"))),
+            "{printed}"
+        );
+        for field in [
+            "features: ",
+            "target: ",
+            "rustc: rustc ",
+            "profile: ",
+            "commit: ",
+        ] {
+            assert!(printed.contains(field), "{printed}");
+        }
+    }
+
+    // Without --verbose, clap's short version is unchanged
+    let output = pngme(["--version"]);
+    assert_eq!(
+        stdout(&output),
+        format!("pngme {}\n", env!("CARGO_PKG_VERSION"))
+    );
+}
+
+#[test]
+fn version_json_matches_capabilities() {
+    let output = pngme(["version", "--format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let build = json(&output.stdout);
+    assert_build(&build);
+
+    let output = pngme(["capabilities", "--format", "json"]);
+    assert_eq!(json(&output.stdout)["build"], build);
+}
+
+#[test]
+fn json_errors_name_the_tool() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing.png");
+
+    let output = pngme([
+        "decode",
+        missing.to_str().unwrap(),
+        "ruSt",
+        "--format",
+        "json",
+    ]);
+    assert!(!output.status.success());
+    let error = json(&output.stderr);
+    assert_build(&error["tool"]);
+    assert!(error["error"]["code"].is_string(), "{error}");
+
+    let raw = stderr(&output);
+    assert!(raw.starts_with(r#"{"tool":"#), "{raw}");
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::*;
+use pngme::png::Png;
+
+fn ztxt_chunk_data(file: &std::path::Path) -> Vec<u8> {
+    let png = Png::try_from(std::fs::read(file).unwrap().as_slice()).unwrap();
+    png.chunk_by_type("zTXt").unwrap().data().to_vec()
+}
+
+#[test]
+fn compressed_text_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = path.to_str().unwrap();
+    let message = "Sunset over the bay, café terrace. ".repeat(20);
+
+    let output = pngme(["encode", file, "zTXt", &message, "--ztxt", "Description"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let data = ztxt_chunk_data(&path);
+    assert!(data.starts_with(b"Description\0\0"));
+    assert!(data.len() < message.len() / 2, "{} bytes", data.len());
+
+    // Writing another chunk parses and serializes the image again
+    let output = pngme(["encode", file, "abCd", "hello"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(ztxt_chunk_data(&path), data);
+
+    let output = pngme(["decode", "--quiet", file, "zTXt"]);
+    assert_eq!(stdout(&output), format!("{message}\n"));
+
+    let output = pngme(["decode", file, "zTXt"]);
+    assert_eq!(
+        stdout(&output),
+        format!("Keyword: Description\nText: {message}\n")
+    );
+
+    let output = pngme(["decode", "--format", "json", file, "zTXt"]);
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(report["keyword"], "Description");
+    assert_eq!(report["data"], message);
+
+    let output = stdout(&pngme(["print", file]));
+    assert!(
+        output.contains("(Description: Sunset over the bay"),
+        "{output}"
+    );
+}
+
+#[test]
+fn malformed_chunks_are_reported() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for (data, code) in [
+        (&b"no separator"[..], "E0712"),
+        (b"Title\0", "E0713"),
+        (b"Title\0\x05compressed", "E0714"),
+        (b"Title\0\0\x78\x9c\x4b", "E0512"),
+    ] {
+        let png = png_bytes(&[
+            ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            ("zTXt", data),
+            ("IEND", &[]),
+        ]);
+        let file = write_fixture(dir.path(), "image.png", &png);
+        let file = file.to_str().unwrap();
+
+        let output = pngme(["decode", file, "zTXt"]);
+        assert!(!output.status.success());
+        assert!(
+            stderr(&output).contains(&format!("error[{code}]")),
+            "{}",
+            stderr(&output)
+        );
+
+        // print shows the chunk without a description
+        let output = pngme(["print", file]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+}
+
+#[test]
+fn text_must_be_latin1() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_fixture(dir.path(), "image.png", &fixture_png());
+    let file = file.to_str().unwrap();
+
+    let output = pngme(["encode", file, "zTXt", "hi 🦀", "--ztxt", "Title"]);
+    assert!(
+        stderr(&output).contains("error[E0705]"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = pngme(["encode", file, "ruSt", "hi", "--ztxt", "Title"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("--ztxt can't be combined with a chunk name other than zTXt"));
+}